@@ -0,0 +1,71 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{NodeInfo, PeerInfo};
+
+/// 改用 `Arc<str>`/`SmallVec` 之前的节点信息布局，作为对照
+#[derive(Clone)]
+#[allow(dead_code)]
+struct OwnedNodeInfo {
+    id: Uuid,
+    name: String,
+    version: String,
+    listen_addr: SocketAddr,
+    capabilities: Vec<String>,
+    metadata: HashMap<String, String>,
+    network_id: String,
+}
+
+impl From<&NodeInfo> for OwnedNodeInfo {
+    fn from(info: &NodeInfo) -> Self {
+        Self {
+            id: info.id,
+            name: info.name.to_string(),
+            version: info.version.to_string(),
+            listen_addr: info.listen_addr,
+            capabilities: info.capabilities.iter().map(|c| c.to_string()).collect(),
+            metadata: info.metadata.clone(),
+            network_id: info.network_id.to_string(),
+        }
+    }
+}
+
+fn nodes(count: usize) -> Vec<NodeInfo> {
+    (0..count)
+        .map(|i| {
+            let addr: SocketAddr = format!("10.0.{}.{}:9000", i / 250, i % 250 + 1).parse().unwrap();
+            NodeInfo::new(format!("node-{}", i), addr, "benchmark-network")
+        })
+        .collect()
+}
+
+fn bench_node_info(c: &mut Criterion) {
+    let interned = nodes(1000);
+    let owned: Vec<OwnedNodeInfo> = interned.iter().map(OwnedNodeInfo::from).collect();
+
+    c.bench_function("clone_node_info/interned", |b| b.iter(|| black_box(&interned).clone()));
+    c.bench_function("clone_node_info/owned", |b| b.iter(|| black_box(&owned).clone()));
+
+    // 节点发现列表：每个节点复制一份能力列表
+    c.bench_function("discovery_list/interned", |b| {
+        b.iter(|| {
+            black_box(&interned)
+                .iter()
+                .map(|n| PeerInfo::new(n.id, n.listen_addr, n.capabilities.clone()))
+                .collect::<Vec<_>>()
+        })
+    });
+    c.bench_function("discovery_list/owned", |b| {
+        b.iter(|| {
+            black_box(&owned)
+                .iter()
+                .map(|n| (n.id, n.listen_addr, n.capabilities.clone()))
+                .collect::<Vec<_>>()
+        })
+    });
+}
+
+criterion_group!(benches, bench_node_info);
+criterion_main!(benches);
@@ -0,0 +1,38 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc::generate();
+}
+
+/// 生成 gRPC 控制面服务桩代码
+///
+/// 构建环境不要求安装 protoc：消息类型在 `src/grpc.rs` 中手写（与
+/// `proto/p2p_control.proto` 保持一致），这里只生成服务端与客户端代码。
+#[cfg(feature = "grpc")]
+mod grpc {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    const METHODS: &[(&str, &str, &str, &str)] = &[
+        ("get_stats", "GetStats", "GetStatsRequest", "Stats"),
+        ("list_peers", "ListPeers", "ListPeersRequest", "ListPeersResponse"),
+        ("list_routes", "ListRoutes", "ListRoutesRequest", "ListRoutesResponse"),
+        ("kick_peer", "KickPeer", "KickPeerRequest", "KickPeerResponse"),
+        ("list_relay_sessions", "ListRelaySessions", "ListRelaySessionsRequest", "ListRelaySessionsResponse"),
+    ];
+
+    pub fn generate() {
+        println!("cargo:rerun-if-changed=proto/p2p_control.proto");
+        let mut service = Service::builder().name("ControlPlane").package("p2p.control.v1");
+        for (name, route, input, output) in METHODS {
+            service = service.method(
+                Method::builder()
+                    .name(*name)
+                    .route_name(*route)
+                    .input_type(format!("crate::grpc::pb::{}", input))
+                    .output_type(format!("crate::grpc::pb::{}", output))
+                    .codec_path("tonic_prost::ProstCodec")
+                    .build(),
+            );
+        }
+        Builder::new().compile(&[service.build()]);
+    }
+}
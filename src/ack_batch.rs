@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// 登记待确认ID后调用方应采取的动作
+#[derive(Debug, PartialEq, Eq)]
+pub enum AckQueued {
+    /// 该地址的第一个待确认ID：调用方应在合并窗口结束后取出并发送
+    First,
+    /// 已并入该地址正在积攒的批次
+    Queued,
+    /// 批次已满并被取出，调用方应立即发送
+    Full(Vec<Uuid>),
+}
+
+/// 按目标地址积攒的待确认消息ID
+///
+/// 服务器收到 `requires_ack` 消息时不再立即回复 `Ack`，而是把消息ID登记到发送方地址名下；
+/// 首个ID到达后等待一个合并窗口，再用一条批量 `Ack` 确认期间积攒的全部ID。
+#[derive(Debug)]
+pub struct AckBatcher {
+    max_ids: usize,
+    pending: Mutex<HashMap<SocketAddr, Vec<Uuid>>>,
+}
+
+impl AckBatcher {
+    pub fn new(max_ids: usize) -> Self {
+        Self { max_ids: max_ids.max(1), pending: Mutex::new(HashMap::new()) }
+    }
+
+    /// 登记发往 `addr` 的确认
+    pub fn push(&self, addr: SocketAddr, id: Uuid) -> AckQueued {
+        let mut pending = self.pending.lock().unwrap();
+        let ids = pending.entry(addr).or_default();
+        ids.push(id);
+        if ids.len() >= self.max_ids {
+            return AckQueued::Full(pending.remove(&addr).unwrap_or_default());
+        }
+        if ids.len() == 1 { AckQueued::First } else { AckQueued::Queued }
+    }
+
+    /// 取出 `addr` 名下积攒的全部ID（批次已满发出时为空）
+    pub fn take(&self, addr: SocketAddr) -> Vec<Uuid> {
+        self.pending.lock().unwrap().remove(&addr).unwrap_or_default()
+    }
+
+    /// 有待发确认的地址数
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches_per_address_until_full() {
+        let batcher = AckBatcher::new(3);
+        let (a, b): (SocketAddr, SocketAddr) = ("127.0.0.1:1000".parse().unwrap(), "127.0.0.1:2000".parse().unwrap());
+        let ids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+
+        assert_eq!(batcher.push(a, ids[0]), AckQueued::First);
+        assert_eq!(batcher.push(b, ids[1]), AckQueued::First);
+        assert_eq!(batcher.push(a, ids[2]), AckQueued::Queued);
+        assert_eq!(batcher.push(a, ids[3]), AckQueued::Full(vec![ids[0], ids[2], ids[3]]));
+        // 已满发出的批次不会被计时器再次发送
+        assert!(batcher.take(a).is_empty());
+        assert_eq!(batcher.take(b), vec![ids[1]]);
+        assert!(batcher.is_empty());
+    }
+}
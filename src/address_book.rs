@@ -0,0 +1,189 @@
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::protocol::P2PPath;
+use crate::tr;
+
+/// 地址簿最多记录的节点数，超出时淘汰最久未更新的记录
+pub const MAX_KNOWN_PEERS: usize = 1024;
+
+/// 地址簿中记录的一个节点
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownPeer {
+    pub peer_id: Uuid,
+    /// 服务器最近一次看到的对方地址
+    pub observed: SocketAddr,
+    /// 服务器最近一次下发的打洞候选地址
+    pub candidates: Vec<SocketAddr>,
+    /// 服务器转发的对方NAT类型
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nat_type: Option<String>,
+    /// 最近一次直连成功的对方地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_addr: Option<SocketAddr>,
+    /// 最近一次会话最终使用的路径（公网打洞、内网直连或中继）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<P2PPath>,
+    /// 最近更新时间（Unix 秒）
+    pub updated_at: u64,
+}
+
+impl KnownPeer {
+    /// 重连后预先打洞的候选地址：上次直连成功的地址在前；从未直连成功时为空
+    pub fn punch_candidates(&self) -> Vec<SocketAddr> {
+        let Some(last_addr) = self.last_addr else { return Vec::new() };
+        let mut candidates = vec![last_addr];
+        candidates.extend(self.candidates.iter().filter(|addr| **addr != last_addr));
+        candidates
+    }
+}
+
+/// 持久化的客户端地址簿
+///
+/// 记录直连协调过的节点及其地址、NAT类型与成功的打洞方式，每次更新后写回文件。
+/// 客户端重启后与这些节点建立会话时，先按记录直接打洞，不必等待服务器的直连协调。
+#[derive(Debug)]
+pub struct AddressBook {
+    path: PathBuf,
+    peers: Mutex<HashMap<Uuid, KnownPeer>>,
+}
+
+impl AddressBook {
+    /// 读取地址簿文件；文件不存在时为空，内容无法解析时丢弃并重新记录
+    pub fn load(path: &Path) -> Result<Self> {
+        let peers = match fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str::<Vec<KnownPeer>>(&content) {
+                Ok(peers) => peers.into_iter().map(|peer| (peer.peer_id, peer)).collect(),
+                Err(e) => {
+                    warn!("{}", tr!("地址簿 {} 无法解析，重新记录: {}", "Address book {} is unreadable, starting over: {}", path.display(), e));
+                    HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e).context(format!("读取地址簿 {} 失败", path.display())),
+        };
+        Ok(Self { path: path.to_path_buf(), peers: Mutex::new(peers) })
+    }
+
+    /// 节点的记录
+    pub fn get(&self, peer_id: &Uuid) -> Option<KnownPeer> {
+        self.peers.lock().unwrap().get(peer_id).cloned()
+    }
+
+    /// 全部记录，最近更新的在前
+    pub fn peers(&self) -> Vec<KnownPeer> {
+        let mut peers: Vec<KnownPeer> = self.peers.lock().unwrap().values().cloned().collect();
+        peers.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(a.peer_id.cmp(&b.peer_id)));
+        peers
+    }
+
+    /// 记录服务器下发的对方地址；保留已有的直连结果
+    pub fn learn(&self, peer_id: Uuid, observed: SocketAddr, candidates: &[SocketAddr], nat_type: Option<&str>) -> Result<()> {
+        self.update(peer_id, observed, |peer| {
+            peer.observed = observed;
+            peer.candidates = candidates.to_vec();
+            if let Some(nat_type) = nat_type {
+                peer.nat_type = Some(nat_type.to_string());
+            }
+        })
+    }
+
+    /// 记录会话最终使用的路径；直连时同时记录成功的对方地址
+    pub fn record_path(&self, peer_id: Uuid, observed: SocketAddr, path: P2PPath, direct_addr: Option<SocketAddr>) -> Result<()> {
+        self.update(peer_id, observed, |peer| {
+            peer.path = Some(path);
+            if direct_addr.is_some() {
+                peer.last_addr = direct_addr;
+            }
+        })
+    }
+
+    /// 删除节点的记录
+    pub fn forget(&self, peer_id: &Uuid) -> Result<bool> {
+        let removed = self.peers.lock().unwrap().remove(peer_id).is_some();
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    fn update(&self, peer_id: Uuid, observed: SocketAddr, apply: impl FnOnce(&mut KnownPeer)) -> Result<()> {
+        {
+            let mut peers = self.peers.lock().unwrap();
+            let peer = peers.entry(peer_id).or_insert_with(|| KnownPeer {
+                peer_id,
+                observed,
+                candidates: Vec::new(),
+                nat_type: None,
+                last_addr: None,
+                path: None,
+                updated_at: 0,
+            });
+            apply(peer);
+            peer.updated_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            if peers.len() > MAX_KNOWN_PEERS
+                && let Some(oldest) = peers.values().filter(|peer| peer.peer_id != peer_id).min_by_key(|peer| peer.updated_at).map(|peer| peer.peer_id)
+            {
+                peers.remove(&oldest);
+            }
+        }
+        self.save()
+    }
+
+    /// 写回文件：先写临时文件再改名，写到一半中断时不会留下残缺的地址簿
+    fn save(&self) -> Result<()> {
+        let content = serde_json::to_string_pretty(&self.peers())?;
+        let temp = self.path.with_extension("tmp");
+        fs::write(&temp, content).context(format!("写入地址簿 {} 失败", temp.display()))?;
+        fs::rename(&temp, &self.path).context(format!("写入地址簿 {} 失败", self.path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_records_survive_reload() {
+        let dir = std::env::temp_dir().join(format!("p2p_address_book_{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("peers.json");
+        let peer_id = Uuid::new_v4();
+
+        let book = AddressBook::load(&path).unwrap();
+        assert!(book.peers().is_empty());
+        book.learn(peer_id, addr(1), &[addr(1), addr(2)], Some("FullCone")).unwrap();
+        assert!(book.get(&peer_id).unwrap().punch_candidates().is_empty());
+        book.record_path(peer_id, addr(1), P2PPath::Private, Some(addr(2))).unwrap();
+        // 中继不覆盖上次直连成功的地址，服务器再次下发地址也不覆盖
+        book.record_path(peer_id, addr(1), P2PPath::Relay, None).unwrap();
+        book.learn(peer_id, addr(3), &[addr(3)], None).unwrap();
+
+        let reloaded = AddressBook::load(&path).unwrap();
+        let peer = reloaded.get(&peer_id).unwrap();
+        assert_eq!(peer.observed, addr(3));
+        assert_eq!(peer.nat_type.as_deref(), Some("FullCone"));
+        assert_eq!(peer.path, Some(P2PPath::Relay));
+        assert_eq!(peer.punch_candidates(), vec![addr(2), addr(3)]);
+
+        assert!(reloaded.forget(&peer_id).unwrap());
+        assert!(AddressBook::load(&path).unwrap().peers().is_empty());
+
+        fs::write(&path, "not json").unwrap();
+        assert!(AddressBook::load(&path).unwrap().peers().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,706 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config::{AdminConfig, Config};
+use crate::log_capture::RecentLogs;
+use crate::maintenance::Maintenance;
+use crate::memory::MemoryAccounting;
+use crate::metrics::ServerMetrics;
+use crate::peer::PeerManager;
+use crate::protocol::{DisconnectReason, MaintenanceNotice};
+pub use crate::protocol::PeerSummary;
+use crate::pubsub::TopicBus;
+use crate::quarantine::PanicQuarantine;
+use crate::scanner::ScannerDetector;
+use crate::rpc::ServiceDirectory;
+use crate::relay::RelaySessions;
+use crate::supervisor::Supervisor;
+use crate::router::MessageRouter;
+use crate::topology::{self, TopologyFormat};
+use crate::tr;
+
+/// 请求头最大长度
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+/// 请求体最大长度
+const MAX_BODY_BYTES: usize = 64 * 1024;
+/// 读取完整请求（请求头与请求体）的时限，慢速连接不能长期占用处理任务
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// 强制断开时未指定原因使用的默认原因
+pub(crate) const DEFAULT_KICK_REASON: &str = "被管理员断开";
+
+/// 管理接口可访问的服务器状态
+pub struct AdminState {
+    pub local_node_id: Uuid,
+    pub peer_manager: Arc<PeerManager>,
+    pub message_router: Arc<MessageRouter>,
+    pub metrics: Arc<ServerMetrics>,
+    pub relay_sessions: Arc<RelaySessions>,
+    /// 内存统计与软限制
+    pub memory: Arc<MemoryAccounting>,
+    pub topic_bus: Arc<TopicBus>,
+    pub services: Arc<ServiceDirectory>,
+    pub recent_logs: Option<Arc<RecentLogs>>,
+    /// 计划维护公告与排空状态
+    pub maintenance: Arc<Maintenance>,
+    /// 后台任务监督（健康检查）
+    pub supervisor: Arc<Supervisor>,
+    /// 数据包处理 panic 统计与来源封禁
+    pub quarantine: Arc<PanicQuarantine>,
+    /// 协议扫描器识别与屏蔽
+    pub scanners: Arc<ScannerDetector>,
+    pub config: AdminConfig,
+    /// 服务器启动时的完整配置（`GET /api/config` 在此基础上叠加运行中的调整）
+    pub server_config: Arc<Config>,
+    /// 网络层故障注入
+    #[cfg(feature = "chaos")]
+    pub faults: Arc<crate::chaos::FaultInjector>,
+}
+
+/// 已解析的HTTP请求
+#[derive(Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(|s| s.as_str())
+    }
+}
+
+/// HTTP响应
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn json<T: Serialize>(value: &T) -> Self {
+        Self::json_with_status(200, value)
+    }
+
+    pub fn json_with_status<T: Serialize>(status: u16, value: &T) -> Self {
+        match serde_json::to_vec_pretty(value) {
+            Ok(body) => Self { status, content_type: "application/json", body },
+            Err(e) => Self::error(500, &format!("序列化响应失败: {}", e)),
+        }
+    }
+
+    pub fn text(status: u16, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self { status, content_type, body: body.into() }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        let body = serde_json::json!({ "error": message }).to_string();
+        Self { status, content_type: "application/json", body: body.into_bytes() }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            413 => "Payload Too Large",
+            415 => "Unsupported Media Type",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+
+    pub(crate) async fn write_to(&self, stream: &mut TcpStream) -> Result<()> {
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&self.body).await?;
+        stream.flush().await?;
+        Ok(())
+    }
+}
+
+/// 管理HTTP服务器
+pub struct AdminServer {
+    state: Arc<AdminState>,
+    listener: TcpListener,
+}
+
+impl AdminServer {
+    /// 绑定管理接口监听地址
+    pub async fn bind(state: Arc<AdminState>) -> Result<Self> {
+        let listener = TcpListener::bind(state.config.listen_address).await
+            .context(format!("绑定管理接口地址 {} 失败", state.config.listen_address))?;
+        info!("{}", tr!("管理接口已绑定到 {}", "Admin API bound to {}", listener.local_addr()?));
+        if state.config.token.is_none() {
+            warn!("{}", tr!("管理接口未配置访问令牌，请确保仅在受信任网络中开放", "Admin API has no access token configured; expose it only on trusted networks"));
+        }
+        Ok(Self { state, listener })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// 运行接受循环，每个连接在独立任务中处理
+    pub async fn run(self) -> Result<()> {
+        loop {
+            let (stream, addr) = match self.listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("{}", tr!("管理接口接受连接失败: {}", "Admin API failed to accept connection: {}", e));
+                    continue;
+                }
+            };
+            let state = self.state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, state).await {
+                    debug!("{}", tr!("管理接口连接 {} 处理失败: {}", "Admin connection {} failed: {}", addr, e));
+                }
+            });
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: Arc<AdminState>) -> Result<()> {
+    let request = match tokio::time::timeout(REQUEST_READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(request)) => request,
+        Ok(Err(e)) => {
+            HttpResponse::error(400, &e.to_string()).write_to(&mut stream).await?;
+            return Ok(());
+        }
+        Err(_) => {
+            HttpResponse::error(408, "读取请求超时").write_to(&mut stream).await?;
+            return Ok(());
+        }
+    };
+    debug!("{}", tr!("管理接口请求: {} {}", "Admin request: {} {}", request.method, request.path));
+
+    // 读请求同样校验 Host，DNS 重绑定的页面无法读取配置、节点与日志
+    if !request.header("host").is_some_and(|host| is_allowed_host(host, &state.config)) {
+        HttpResponse::error(403, "Host 不在允许范围内").write_to(&mut stream).await?;
+        return Ok(());
+    }
+
+    if !is_authorized(&request, &state.config) {
+        HttpResponse::error(401, "未授权").write_to(&mut stream).await?;
+        return Ok(());
+    }
+
+    if let Some(rejection) = check_mutation(&request) {
+        rejection.write_to(&mut stream).await?;
+        return Ok(());
+    }
+
+    #[cfg(feature = "dashboard")]
+    if request.method == "GET" && request.path == "/ws" {
+        return crate::dashboard::serve_websocket(stream, &request, state).await;
+    }
+
+    let response = route(&request, &state).await;
+    response.write_to(&mut stream).await
+}
+
+/// 分发管理接口请求
+async fn route(request: &HttpRequest, state: &Arc<AdminState>) -> HttpResponse {
+    match (request.method.as_str(), request.path.as_str()) {
+        #[cfg(feature = "dashboard")]
+        ("GET", "/") | ("GET", "/dashboard") => {
+            HttpResponse::text(200, "text/html; charset=utf-8", crate::dashboard::INDEX_HTML)
+        }
+        ("GET", "/api/health") => {
+            // 受监督的后台任务反复崩溃时返回 503，供负载均衡或编排系统摘除实例
+            let healthy = state.supervisor.healthy();
+            let body = serde_json::json!({ "healthy": healthy, "tasks": state.supervisor.tasks() });
+            HttpResponse::json_with_status(if healthy { 200 } else { 503 }, &body)
+        }
+        ("GET", "/api/banned") => HttpResponse::json(&state.quarantine.banned()),
+        ("GET", "/api/scanners") => {
+            let body = serde_json::json!({ "counts": state.scanners.counts(), "blocked": state.scanners.blocked() });
+            HttpResponse::json(&body)
+        }
+        ("GET", "/api/spoofing") => {
+            let guard = state.peer_manager.spoof_guard();
+            let body = serde_json::json!({ "flagged": guard.flagged(), "audit": guard.audit_log() });
+            HttpResponse::json(&body)
+        }
+        ("GET", "/api/stats") => HttpResponse::json(&stats_json(state).await),
+        ("GET", "/api/peers") => HttpResponse::json(&peer_summaries(&state.peer_manager).await),
+        ("DELETE", path) if path.starts_with("/api/peers/") => {
+            let Ok(peer_id) = Uuid::parse_str(&path["/api/peers/".len()..]) else {
+                return HttpResponse::error(400, "节点ID不是有效的UUID");
+            };
+            let reason = request.query.get("reason").map(|r| r.as_str()).filter(|r| !r.is_empty()).unwrap_or(DEFAULT_KICK_REASON);
+            match kick_peer(state, &peer_id, reason).await {
+                Ok(true) => HttpResponse::json(&serde_json::json!({ "kicked": true })),
+                Ok(false) => HttpResponse::error(404, "节点不存在"),
+                Err(e) => HttpResponse::error(500, &e.to_string()),
+            }
+        }
+        ("GET", "/api/routes") => HttpResponse::json(&routes_json(&state.message_router).await),
+        ("GET", "/api/config") => match config_json(&state.server_config, &state.peer_manager) {
+            Ok(config) => HttpResponse::json(&config),
+            Err(e) => HttpResponse::error(500, &e.to_string()),
+        },
+        ("GET", "/api/limits") => HttpResponse::json(&limits_json(&state.peer_manager).await),
+        ("PUT", "/api/limits") => match update_limits(&state.peer_manager, &request.body) {
+            Ok(()) => HttpResponse::json(&limits_json(&state.peer_manager).await),
+            Err(e) => HttpResponse::error(400, &e.to_string()),
+        },
+        #[cfg(feature = "chaos")]
+        ("GET", "/api/chaos") => HttpResponse::json(&chaos_json(&state.faults)),
+        #[cfg(feature = "chaos")]
+        ("PUT", "/api/chaos") => match update_chaos(&state.faults, &request.body) {
+            Ok(()) => HttpResponse::json(&chaos_json(&state.faults)),
+            Err(e) => HttpResponse::error(400, &e.to_string()),
+        },
+        ("GET", "/api/maintenance") => HttpResponse::json(&maintenance_json(&state.maintenance)),
+        ("POST", "/api/maintenance") => match announce_maintenance(state, &request.body).await {
+            Ok(notified) => {
+                let mut body = maintenance_json(&state.maintenance);
+                body["notified"] = serde_json::json!(notified);
+                HttpResponse::json(&body)
+            }
+            Err(e) => HttpResponse::error(400, &e.to_string()),
+        },
+        ("DELETE", "/api/maintenance") => {
+            if state.maintenance.cancel() {
+                info!("{}", tr!("管理操作：已取消计划维护", "Admin action: scheduled maintenance cancelled"));
+            }
+            HttpResponse::json(&maintenance_json(&state.maintenance))
+        }
+        ("GET", "/api/relays") => HttpResponse::json(&state.relay_sessions.snapshot()),
+        ("GET", "/api/bandwidth") => HttpResponse::json(&state.peer_manager.bandwidth().links()),
+        ("GET", "/api/latency") => HttpResponse::json(&state.peer_manager.latency().entries()),
+        ("GET", "/api/topics") => {
+            let topics: Vec<serde_json::Value> = state.topic_bus
+                .topics()
+                .await
+                .into_iter()
+                .map(|(topic, subscribers)| serde_json::json!({ "topic": topic, "subscribers": subscribers }))
+                .collect();
+            HttpResponse::json(&topics)
+        }
+        ("GET", "/api/services") => {
+            let services: Vec<serde_json::Value> = state.services
+                .services()
+                .await
+                .into_iter()
+                .map(|(service, providers)| serde_json::json!({ "service": service, "providers": providers }))
+                .collect();
+            HttpResponse::json(&services)
+        }
+        ("GET", "/api/topology") => {
+            let snapshot = topology::collect(state.local_node_id, &state.peer_manager, &state.message_router).await;
+            match request.query.get("format").map(|f| f.as_str()) {
+                Some("dot") => HttpResponse::text(200, "text/vnd.graphviz", snapshot.to_dot()),
+                _ => HttpResponse::json(&serde_json::json!({
+                    "format": TopologyFormat::Json,
+                    "topology": snapshot,
+                })),
+            }
+        }
+        ("GET", "/api/logs") => {
+            let limit = request.query.get("limit").and_then(|l| l.parse().ok()).unwrap_or(100);
+            let logs = state.recent_logs.as_ref().map(|l| l.recent(limit)).unwrap_or_default();
+            HttpResponse::json(&logs)
+        }
+        (_, path) if path.starts_with("/api/") => HttpResponse::error(404, "未知的管理接口"),
+        _ => HttpResponse::error(404, "未找到"),
+    }
+}
+
+/// 写操作的跨站防护：要求 `Content-Type: application/json`（浏览器跨站发出的简单请求无法携带）
+fn check_mutation(request: &HttpRequest) -> Option<HttpResponse> {
+    if matches!(request.method.as_str(), "GET" | "HEAD") {
+        return None;
+    }
+    let json = request
+        .header("content-type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|media| media.trim().eq_ignore_ascii_case("application/json"));
+    if !json {
+        return Some(HttpResponse::error(415, "写操作须使用 Content-Type: application/json"));
+    }
+    None
+}
+
+/// `Host` 头（可带端口）是否指向本接口：回环地址、`localhost`、监听地址或 `allowed_hosts` 中的主机，防止 DNS 重绑定
+fn is_allowed_host(host: &str, config: &AdminConfig) -> bool {
+    let host = host.trim();
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    if name.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    if let Ok(ip) = name.parse::<IpAddr>()
+        && (ip.is_loopback() || ip == config.listen_address.ip())
+    {
+        return true;
+    }
+    config.allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(name) || allowed.eq_ignore_ascii_case(host))
+}
+
+/// 浏览器发来的 `Origin`（如 `http://127.0.0.1:8088`）是否指向本接口；非浏览器客户端不带该头，视为允许
+#[cfg(feature = "dashboard")]
+pub(crate) fn is_allowed_origin(origin: Option<&str>, config: &AdminConfig) -> bool {
+    let Some(origin) = origin else { return true };
+    let Some((_, authority)) = origin.trim().split_once("://") else { return false };
+    is_allowed_host(authority.trim_end_matches('/'), config)
+}
+
+/// 校验访问令牌：支持 `Authorization: Bearer <token>` 或查询参数 `token`
+fn is_authorized(request: &HttpRequest, config: &AdminConfig) -> bool {
+    let Some(expected) = &config.token else { return true };
+    let bearer = request
+        .header("authorization")
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|t| t.trim());
+    let provided = bearer.or_else(|| request.query.get("token").map(|t| t.as_str()));
+    token_matches(provided, expected)
+}
+
+/// 比较出示的令牌与配置的令牌；先取摘要再逐字节比较，耗时与令牌内容和长度无关
+pub(crate) fn token_matches(provided: Option<&str>, expected: &str) -> bool {
+    let Some(provided) = provided else { return false };
+    let (provided, expected) = (Sha256::digest(provided.as_bytes()), Sha256::digest(expected.as_bytes()));
+    provided.iter().zip(expected.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<HttpRequest> {
+    let mut buffer = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(anyhow::anyhow!("连接在请求头结束前关闭"));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buffer.len() > MAX_HEADER_BYTES {
+            return Err(anyhow::anyhow!("请求头过长"));
+        }
+    };
+
+    let head = std::str::from_utf8(&buffer[..header_end]).context("请求头不是有效的UTF-8")?;
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().ok_or_else(|| anyhow::anyhow!("无效的请求行"))?;
+
+    let (path, query_str) = target.split_once('?').unwrap_or((target, ""));
+    let query = parse_query(query_str);
+
+    let mut headers = HashMap::new();
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let content_length: usize = headers
+        .get("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if content_length > MAX_BODY_BYTES {
+        return Err(anyhow::anyhow!("请求体过大"));
+    }
+
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(HttpRequest {
+        method,
+        path: path.to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect()
+}
+
+/// 解码查询参数中的 `%XX` 转义与表示空格的 `+`；无效的转义原样保留
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if let Some(byte) = bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// 汇总已认证与连接中的节点信息
+pub async fn peer_summaries(peer_manager: &Arc<PeerManager>) -> Vec<PeerSummary> {
+    let mut summaries = Vec::new();
+    for peer in peer_manager.get_all_peers().await {
+        let guard = peer.read().await;
+        summaries.push(PeerSummary {
+            id: guard.id,
+            name: guard.node_info.as_ref().map(|n| n.name.to_string()),
+            addr: guard.addr(),
+            status: format!("{:?}", guard.status),
+            nat_type: guard.nat_type.clone(),
+            carrier_nat: guard.carrier_nat,
+            connected_secs: guard.connected_for().as_secs(),
+            last_ping_secs_ago: guard.last_ping.map(|t| t.elapsed().as_secs()),
+            link: guard.link_quality(),
+            clock_offset_ms: guard.clock_offset_ms,
+            path_mtu: guard.path_mtu,
+        });
+    }
+    summaries
+}
+
+/// 路由表JSON
+pub async fn routes_json(router: &Arc<MessageRouter>) -> serde_json::Value {
+    let routes: Vec<serde_json::Value> = router
+        .get_routing_table_snapshot()
+        .await
+        .into_iter()
+        .map(|(dest, next_hop, distance)| serde_json::json!({
+            "destination": dest,
+            "next_hop": next_hop,
+            "distance": distance
+        }))
+        .collect();
+    serde_json::json!({ "routes": routes })
+}
+
+/// 统计信息JSON
+pub async fn stats_json(state: &AdminState) -> serde_json::Value {
+    let peer_stats = state.peer_manager.get_stats().await;
+    serde_json::json!({
+        "node_id": state.local_node_id,
+        "routing_mode": state.message_router.routing_mode(),
+        "peers": {
+            "total": peer_stats.total_peers,
+            "authenticated": peer_stats.authenticated_peers,
+            "connecting": peer_stats.connecting_peers,
+        },
+        "carrier_nat": state.peer_manager.carrier_nat_stats().await,
+        "metrics": state.metrics.snapshot(),
+        "memory": state.memory.usage().await,
+    })
+}
+
+/// 当前连接数限制与负载状态
+pub async fn limits_json(peer_manager: &Arc<PeerManager>) -> serde_json::Value {
+    let limits = peer_manager.limits();
+    serde_json::json!({
+        "soft_limit": limits.soft(),
+        "hard_limit": limits.hard(),
+        "peers": peer_manager.get_stats().await.total_peers,
+        "busy": peer_manager.is_busy().await,
+        "full": peer_manager.is_full().await,
+    })
+}
+
+/// 生效配置：启动时的配置叠加运行中调整过的连接数限制，已设置的敏感字段替换为 `<redacted>`
+pub fn config_json(config: &Config, peer_manager: &PeerManager) -> Result<serde_json::Value> {
+    let mut config = config.clone();
+    let limits = peer_manager.limits();
+    config.max_connections = limits.hard();
+    config.connection_limits.soft_limit = limits.soft();
+    config.to_redacted_json()
+}
+
+/// 运行时调整连接数限制，请求体为 `{"soft_limit": 80, "hard_limit": 100}`。
+/// 省略 `hard_limit` 时保留当前值，`soft_limit` 为 `null` 或省略时关闭软限制。
+fn update_limits(peer_manager: &PeerManager, body: &[u8]) -> Result<()> {
+    #[derive(Deserialize)]
+    struct LimitsUpdate {
+        soft_limit: Option<usize>,
+        hard_limit: Option<usize>,
+    }
+    let update: LimitsUpdate = serde_json::from_slice(body)?;
+    let limits = peer_manager.limits();
+    let hard = update.hard_limit.unwrap_or_else(|| limits.hard());
+    limits.set(update.soft_limit, hard)?;
+    info!("{}", tr!("连接数限制已调整: 软限制={:?}，硬限制={}", "Connection limits changed: soft limit={:?}, hard limit={}", update.soft_limit, hard));
+    Ok(())
+}
+
+#[cfg(feature = "chaos")]
+fn chaos_json(faults: &crate::chaos::FaultInjector) -> serde_json::Value {
+    serde_json::json!({ "config": faults.config(), "stats": faults.stats() })
+}
+
+/// 替换故障注入参数，未给出的字段取默认值（即不注入）
+#[cfg(feature = "chaos")]
+fn update_chaos(faults: &crate::chaos::FaultInjector, body: &[u8]) -> Result<()> {
+    let config: crate::config::ChaosConfig = serde_json::from_slice(body)?;
+    faults.set_config(config.clone())?;
+    warn!("{}", tr!("故障注入参数已调整: {:?}", "Fault injection parameters changed: {:?}", config));
+    Ok(())
+}
+
+fn maintenance_json(maintenance: &Maintenance) -> serde_json::Value {
+    serde_json::json!({ "notice": maintenance.notice(), "draining": maintenance.is_draining() })
+}
+
+/// 发布维护公告，返回通知到的节点数
+async fn announce_maintenance(state: &AdminState, body: &[u8]) -> Result<usize> {
+    #[derive(Deserialize)]
+    struct MaintenanceRequest {
+        /// 距现在多少秒后开始
+        #[serde(default)]
+        start_in_secs: u64,
+        duration_secs: u64,
+        alternative_server: Option<SocketAddr>,
+        #[serde(default)]
+        reason: String,
+        #[serde(default)]
+        drain: bool,
+    }
+    let request: MaintenanceRequest = serde_json::from_slice(body)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let notice = MaintenanceNotice {
+        start_time: now + request.start_in_secs,
+        duration_secs: request.duration_secs,
+        alternative_server: request.alternative_server,
+        reason: request.reason,
+        drain: request.drain,
+    };
+    let notified = state.maintenance.announce(notice.clone(), state.peer_manager.clone()).await?;
+    info!("{}", tr!("管理操作：发布维护公告 {:?}，已通知 {} 个节点", "Admin action: maintenance notice {:?} published, {} nodes notified", notice, notified));
+    Ok(notified)
+}
+
+/// 强制断开节点：发送 `Disconnect` 后清理路由、中继会话与节点记录，并广播新的节点列表。
+/// 节点不存在时返回 `false`。
+pub async fn kick_peer(state: &AdminState, peer_id: &Uuid, reason: &str) -> Result<bool> {
+    if !state.peer_manager.kick(peer_id, DisconnectReason::Kicked, reason).await {
+        return Ok(false);
+    }
+    info!("{}", tr!("管理操作：已踢出节点 {}（{}）", "Admin action: kicked node {} ({})", peer_id, reason));
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_authorization() {
+        let config = AdminConfig { token: Some("secret".to_string()), ..AdminConfig::default() };
+        let mut request = HttpRequest {
+            method: "GET".to_string(),
+            path: "/api/stats".to_string(),
+            query: HashMap::new(),
+            headers: HashMap::new(),
+            body: Vec::new(),
+        };
+        assert!(!is_authorized(&request, &config));
+
+        request.headers.insert("authorization".to_string(), "Bearer secret".to_string());
+        assert!(is_authorized(&request, &config));
+
+        request.headers.clear();
+        request.query = parse_query("token=secret&format=dot");
+        assert!(is_authorized(&request, &config));
+        assert_eq!(request.query.get("format").map(|s| s.as_str()), Some("dot"));
+
+        request.query = parse_query("token=secre");
+        assert!(!is_authorized(&request, &config));
+        request.query = parse_query("token=secret%21");
+        assert!(!is_authorized(&request, &config));
+    }
+
+    #[test]
+    fn test_mutations_require_json_and_requests_a_local_host() {
+        let config = AdminConfig { allowed_hosts: vec!["admin.example".to_string()], ..AdminConfig::default() };
+        let mut request = HttpRequest {
+            method: "POST".to_string(),
+            path: "/api/maintenance".to_string(),
+            query: HashMap::new(),
+            headers: HashMap::from([("host".to_string(), "localhost:8088".to_string())]),
+            body: Vec::new(),
+        };
+        // 跨站表单可发出的 text/plain 请求被拒绝
+        assert_eq!(check_mutation(&request).map(|r| r.status), Some(415));
+        request.headers.insert("content-type".to_string(), "text/plain".to_string());
+        assert_eq!(check_mutation(&request).map(|r| r.status), Some(415));
+        request.headers.insert("content-type".to_string(), "application/json; charset=utf-8".to_string());
+        assert!(check_mutation(&request).is_none());
+        request.method = "GET".to_string();
+        request.headers.clear();
+        assert!(check_mutation(&request).is_none());
+
+        for host in ["localhost:8088", "127.0.0.1:8088", "[::1]:8088", "admin.example"] {
+            assert!(is_allowed_host(host, &config), "{}", host);
+        }
+        // DNS 重绑定后浏览器发送的是攻击者的域名
+        assert!(!is_allowed_host("evil.example:8088", &config));
+    }
+
+    #[cfg(feature = "dashboard")]
+    #[test]
+    fn test_websocket_origin_must_be_local() {
+        let config = AdminConfig { allowed_hosts: vec!["admin.example".to_string()], ..AdminConfig::default() };
+        assert!(is_allowed_origin(None, &config));
+        assert!(is_allowed_origin(Some("http://127.0.0.1:8088"), &config));
+        assert!(is_allowed_origin(Some("https://admin.example"), &config));
+        assert!(!is_allowed_origin(Some("http://evil.example:8088"), &config));
+        assert!(!is_allowed_origin(Some("null"), &config));
+    }
+
+    #[test]
+    fn test_query_params_are_percent_decoded() {
+        let query = parse_query("token=a%2Bb%3D&reason=%E7%BB%B4%E6%8A%A4+%E7%AA%97%E5%8F%A3&bad=%zz%4");
+        assert_eq!(query["token"], "a+b=");
+        assert_eq!(query["reason"], "维护 窗口");
+        assert_eq!(query["bad"], "%zz%4");
+    }
+}
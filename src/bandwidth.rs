@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use log::debug;
+use crate::tr;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::protocol::{BandwidthReport, Message};
+
+/// 新测量值在平滑估计中所占的权重
+const SMOOTHING: f64 = 0.5;
+/// 链路开销上限
+const MAX_COST: u64 = 65535;
+
+#[derive(Debug, Clone)]
+struct Estimate {
+    bps: u64,
+    samples: u32,
+    measured_at: Instant,
+}
+
+/// 单向链路的带宽估计（供管理接口展示）
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct LinkBandwidth {
+    pub from: Uuid,
+    pub to: Uuid,
+    pub bandwidth_bps: u64,
+    pub samples: u32,
+    pub age_secs: u64,
+}
+
+#[derive(Debug)]
+struct PendingProbe {
+    sender: Uuid,
+    receiver: Uuid,
+    /// 节点主动请求的探测：(请求方, 请求消息)，结果作为应答发回
+    request: Option<(Uuid, Message)>,
+    started_at: Instant,
+}
+
+/// 一次已结束的探测
+#[derive(Debug)]
+pub struct CompletedProbe {
+    pub sender: Uuid,
+    pub receiver: Uuid,
+    pub bandwidth_bps: Option<u64>,
+    pub request: Option<(Uuid, Message)>,
+}
+
+/// 节点之间 P2P 路径的带宽估计
+///
+/// 服务器让一方沿已打通的直连路径连续发送一串探测包（packet train），接收方按首尾包的到达间隔
+/// 估算可用带宽并上报。估计值按方向记录并做指数平滑，换算成链路开销（参考带宽 / 测得带宽）
+/// 用于拓扑与路由，并在协调直连时判断是否建议改走中继。
+#[derive(Debug)]
+pub struct BandwidthMap {
+    reference_bps: u64,
+    probe_timeout: Duration,
+    links: Mutex<HashMap<(Uuid, Uuid), Estimate>>,
+    probes: Mutex<HashMap<Uuid, PendingProbe>>,
+}
+
+impl Default for BandwidthMap {
+    fn default() -> Self {
+        Self::new(100_000_000, Duration::from_secs(5))
+    }
+}
+
+impl BandwidthMap {
+    pub fn new(reference_bps: u64, probe_timeout: Duration) -> Self {
+        Self {
+            reference_bps: reference_bps.max(1),
+            probe_timeout,
+            links: Mutex::new(HashMap::new()),
+            probes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 登记一次从 `sender` 到 `receiver` 的探测，同方向已有未结束的探测时返回 `None`
+    pub fn begin(&self, sender: Uuid, receiver: Uuid, request: Option<(Uuid, Message)>) -> Option<Uuid> {
+        let now = Instant::now();
+        let mut probes = self.probes.lock().unwrap();
+        probes.retain(|_, p| now.duration_since(p.started_at) < self.probe_timeout);
+        if probes.values().any(|p| p.sender == sender && p.receiver == receiver) {
+            return None;
+        }
+        let probe_id = Uuid::new_v4();
+        probes.insert(probe_id, PendingProbe { sender, receiver, request, started_at: now });
+        Some(probe_id)
+    }
+
+    /// 处理接收方的上报：只接受探测指定的接收方，测得带宽时记入估计
+    pub fn complete(&self, reporter: Uuid, report: &BandwidthReport) -> Option<CompletedProbe> {
+        let probe = {
+            let mut probes = self.probes.lock().unwrap();
+            if probes.get(&report.probe_id)?.receiver != reporter {
+                return None;
+            }
+            probes.remove(&report.probe_id)?
+        };
+        if let Some(bps) = report.bandwidth_bps {
+            self.record(probe.sender, probe.receiver, bps);
+        }
+        Some(CompletedProbe {
+            sender: probe.sender,
+            receiver: probe.receiver,
+            bandwidth_bps: report.bandwidth_bps,
+            request: probe.request,
+        })
+    }
+
+    /// 记录一次测量，与已有估计做指数平滑
+    pub fn record(&self, from: Uuid, to: Uuid, bps: u64) {
+        let mut links = self.links.lock().unwrap();
+        let estimate = links.entry((from, to)).or_insert(Estimate { bps, samples: 0, measured_at: Instant::now() });
+        if estimate.samples > 0 {
+            estimate.bps = (SMOOTHING * bps as f64 + (1.0 - SMOOTHING) * estimate.bps as f64) as u64;
+        }
+        estimate.samples += 1;
+        estimate.measured_at = Instant::now();
+        debug!("{}", tr!("链路 {} -> {} 带宽估计 {} bit/s（{} 次测量）", "Link {} -> {} bandwidth estimate {} bit/s ({} samples)", from, to, estimate.bps, estimate.samples));
+    }
+
+    /// 从 `from` 到 `to` 方向的带宽估计（bit/s）
+    pub fn get(&self, from: &Uuid, to: &Uuid) -> Option<u64> {
+        self.links.lock().unwrap().get(&(*from, *to)).map(|e| e.bps)
+    }
+
+    /// 两个节点之间路径的瓶颈带宽：取已测方向中较小的一个
+    pub fn path_bandwidth(&self, a: &Uuid, b: &Uuid) -> Option<u64> {
+        let links = self.links.lock().unwrap();
+        [links.get(&(*a, *b)), links.get(&(*b, *a))].into_iter().flatten().map(|e| e.bps).min()
+    }
+
+    /// 链路开销：参考带宽 / 瓶颈带宽，取值范围 1..=65535；未测量时返回 `None`
+    pub fn link_cost(&self, a: &Uuid, b: &Uuid) -> Option<u32> {
+        let bps = self.path_bandwidth(a, b)?;
+        Some((self.reference_bps / bps.max(1)).clamp(1, MAX_COST) as u32)
+    }
+
+    /// 全部链路的带宽估计
+    pub fn links(&self) -> Vec<LinkBandwidth> {
+        let mut links: Vec<LinkBandwidth> = self
+            .links
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&(from, to), e)| LinkBandwidth {
+                from,
+                to,
+                bandwidth_bps: e.bps,
+                samples: e.samples,
+                age_secs: e.measured_at.elapsed().as_secs(),
+            })
+            .collect();
+        links.sort_by_key(|l| (l.from, l.to));
+        links
+    }
+
+    /// 节点下线：移除其全部链路与未结束的探测
+    pub fn remove_peer(&self, peer_id: &Uuid) {
+        self.links.lock().unwrap().retain(|(from, to), _| from != peer_id && to != peer_id);
+        self.probes.lock().unwrap().retain(|_, p| p.sender != *peer_id && p.receiver != *peer_id);
+    }
+}
+
+/// 由探测包串的到达时间估算带宽（bit/s）
+///
+/// 第一个包只标记起点，之后每个包的字节数除以首尾包的到达间隔即为估计值；收到的包少于两个或
+/// 间隔为零时无法估算。
+pub fn estimate(arrivals: &[Instant], packet_size: usize) -> Option<u64> {
+    let first = arrivals.iter().min()?;
+    let last = arrivals.iter().max()?;
+    let elapsed = last.duration_since(*first);
+    if arrivals.len() < 2 || elapsed.is_zero() {
+        return None;
+    }
+    let bits = ((arrivals.len() - 1) * packet_size * 8) as f64;
+    Some((bits / elapsed.as_secs_f64()) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_estimate_and_cost() {
+        let start = Instant::now();
+        let arrivals: Vec<Instant> = (0..5).map(|i| start + Duration::from_millis(i * 10)).collect();
+        // 4 个 1000 字节的包用时 40ms：800 kbit/s
+        assert_eq!(estimate(&arrivals, 1000), Some(800_000));
+        assert_eq!(estimate(&arrivals[..1], 1000), None);
+
+        let map = BandwidthMap::new(1_000_000, Duration::from_secs(5));
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let probe_id = map.begin(a, b, None).unwrap();
+        // 同方向同时只有一个探测
+        assert!(map.begin(a, b, None).is_none());
+        let report = BandwidthReport { probe_id, sender: a, receiver: b, received: 5, bandwidth_bps: Some(800_000) };
+        // 只有接收方可以上报
+        assert!(map.complete(c, &report).is_none());
+        assert_eq!(map.complete(b, &report).unwrap().bandwidth_bps, Some(800_000));
+        assert!(map.complete(b, &report).is_none());
+
+        map.record(a, b, 400_000);
+        assert_eq!(map.get(&a, &b), Some(600_000));
+        map.record(b, a, 100_000);
+        assert_eq!(map.path_bandwidth(&a, &b), Some(100_000));
+        assert_eq!(map.link_cost(&b, &a), Some(10));
+        assert_eq!(map.link_cost(&a, &c), None);
+
+        map.remove_peer(&a);
+        assert!(map.links().is_empty());
+    }
+}
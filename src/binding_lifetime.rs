@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+/// NAT 绑定有效期探测的登记表
+///
+/// 客户端在要测量的套接字上登记映射地址并取得令牌，之后保持该套接字空闲；
+/// 等待一段时间后从另一个套接字出示令牌，服务器向登记的地址回送一个指示。
+/// 原套接字能收到说明映射在这段空闲时间后仍然有效，逐步加长等待时间即可测出绑定有效期。
+#[derive(Debug)]
+pub struct BindingRegistry {
+    max_entries: usize,
+    max_age: Duration,
+    entries: Mutex<HashMap<u64, (SocketAddr, Instant)>>,
+}
+
+impl BindingRegistry {
+    pub fn new(max_entries: usize, max_age: Duration) -> Self {
+        Self { max_entries, max_age, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// 登记映射地址，返回探测令牌；登记数已满时返回 `None`
+    pub fn register(&self, mapped: SocketAddr) -> Option<u64> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries {
+            let now = Instant::now();
+            entries.retain(|_, (_, at)| now.duration_since(*at) < self.max_age);
+            if entries.len() >= self.max_entries {
+                return None;
+            }
+        }
+        let mut rng = rand::thread_rng();
+        let token = loop {
+            let token: u64 = rng.r#gen();
+            if token != 0 && !entries.contains_key(&token) {
+                break token;
+            }
+        };
+        entries.insert(token, (mapped, Instant::now()));
+        Some(token)
+    }
+
+    /// 查找令牌登记的映射地址及登记至今的时长
+    pub fn lookup(&self, token: u64) -> Option<(SocketAddr, Duration)> {
+        let entries = self.entries.lock().unwrap();
+        let (mapped, at) = entries.get(&token)?;
+        let age = at.elapsed();
+        (age < self.max_age).then_some((*mapped, age))
+    }
+
+    /// 移除过期的登记
+    pub fn cleanup(&self) {
+        let now = Instant::now();
+        self.entries.lock().unwrap().retain(|_, (_, at)| now.duration_since(*at) < self.max_age);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_lookup_and_capacity() {
+        let registry = BindingRegistry::new(1, Duration::from_secs(60));
+        let mapped: SocketAddr = "203.0.113.5:40000".parse().unwrap();
+
+        let token = registry.register(mapped).unwrap();
+        assert_eq!(registry.lookup(token).map(|(addr, _)| addr), Some(mapped));
+        assert!(registry.lookup(token.wrapping_add(1)).is_none());
+        // 未过期的登记不会被挤出
+        assert!(registry.register(mapped).is_none());
+
+        let expired = BindingRegistry::new(1, Duration::ZERO);
+        let token = expired.register(mapped).unwrap();
+        assert!(expired.lookup(token).is_none());
+        assert!(expired.register(mapped).is_some());
+    }
+}
@@ -0,0 +1,89 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::router::RoutedMessage;
+
+/// 对一次广播的判断结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastCheck {
+    /// 窗口内第一次出现，照常广播
+    First,
+    /// 窗口内重复的广播，丢弃；`suppressed` 为窗口内已丢弃的份数（含本次）
+    Duplicate { suppressed: u64 },
+}
+
+#[derive(Debug)]
+struct Seen {
+    first: Instant,
+    suppressed: u64,
+}
+
+/// 识别短时间内重复的广播
+///
+/// 客户端重试时每次都生成新的 `route_id`，路由消息缓存无法识别；没有路由的消息每重试一次就向所有节点广播一次。
+/// 这里按源节点、目标节点与内层消息的摘要识别内容相同的广播，窗口内只放行第一份。
+#[derive(Debug)]
+pub struct BroadcastGuard {
+    window: Duration,
+    seen: Mutex<HashMap<[u8; 32], Seen>>,
+}
+
+impl BroadcastGuard {
+    pub fn new(window: Duration) -> Self {
+        Self { window, seen: Mutex::new(HashMap::new()) }
+    }
+
+    /// 记录一次即将进行的广播
+    pub fn check(&self, routed_message: &RoutedMessage, now: Instant) -> BroadcastCheck {
+        let key = Self::key(routed_message);
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, entry| now.saturating_duration_since(entry.first) < self.window);
+        match seen.get_mut(&key) {
+            Some(entry) => {
+                entry.suppressed += 1;
+                BroadcastCheck::Duplicate { suppressed: entry.suppressed }
+            }
+            None => {
+                seen.insert(key, Seen { first: now, suppressed: 0 });
+                BroadcastCheck::First
+            }
+        }
+    }
+
+    /// 广播内容的摘要：源节点、目标节点与内层消息的类型和负载，不含每次重试都会变化的ID与时间戳
+    fn key(routed_message: &RoutedMessage) -> [u8; 32] {
+        let inner = &routed_message.original_message;
+        let mut hasher = Sha256::new();
+        hasher.update(routed_message.source_node.as_bytes());
+        hasher.update(routed_message.destination_node.as_bytes());
+        hasher.update(serde_json::to_vec(&inner.message_type).unwrap_or_default());
+        hasher.update(serde_json::to_vec(&inner.payload).unwrap_or_default());
+        hasher.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Message;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_retries_with_new_route_id_are_suppressed_within_window() {
+        let guard = BroadcastGuard::new(Duration::from_secs(2));
+        let (source, destination) = (Uuid::new_v4(), Uuid::new_v4());
+        let retry = || RoutedMessage::new(Message::data(serde_json::json!({ "n": 1 })), source, destination, 10);
+        let start = Instant::now();
+
+        assert_eq!(guard.check(&retry(), start), BroadcastCheck::First);
+        assert_eq!(guard.check(&retry(), start + Duration::from_millis(500)), BroadcastCheck::Duplicate { suppressed: 1 });
+        assert_eq!(guard.check(&retry(), start + Duration::from_millis(900)), BroadcastCheck::Duplicate { suppressed: 2 });
+        // 内容不同的广播不受影响
+        let other = RoutedMessage::new(Message::data(serde_json::json!({ "n": 2 })), source, destination, 10);
+        assert_eq!(guard.check(&other, start + Duration::from_secs(1)), BroadcastCheck::First);
+        // 窗口过后重新放行
+        assert_eq!(guard.check(&retry(), start + Duration::from_secs(2)), BroadcastCheck::First);
+    }
+}
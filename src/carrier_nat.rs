@@ -0,0 +1,82 @@
+use std::net::{IpAddr, SocketAddr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::CarrierNatConfig;
+use crate::protocol::CarrierNat;
+use crate::scripting::ip_in_cidr;
+
+/// RFC 6598 为运营商级 NAT 保留的共享地址空间
+pub const SHARED_ADDRESS_SPACE: &str = "100.64.0.0/10";
+
+/// 按观测地址、节点通告的本地地址与共用该公网IP的节点数识别运营商级地址转换
+///
+/// 节点本机地址位于共享地址空间说明其上游是 CGNAT；观测地址本身位于其中则说明服务器与节点
+/// 处在同一运营商网络内。NAT64 优先于 CGNAT：经 NAT64 上网的节点无论对方类型都无法直连。
+pub fn classify(config: &CarrierNatConfig, observed: IpAddr, advertised: &[SocketAddr], peers_sharing_ip: usize) -> Option<CarrierNat> {
+    let addrs = || std::iter::once(observed).chain(advertised.iter().map(SocketAddr::ip));
+    if addrs().any(|ip| ip.is_ipv6() && config.nat64_prefixes.iter().any(|prefix| ip_in_cidr(ip, prefix))) {
+        return Some(CarrierNat::Nat64);
+    }
+    let shared = config.shared_ip_threshold > 0 && peers_sharing_ip >= config.shared_ip_threshold;
+    if shared || addrs().any(|ip| ip_in_cidr(ip, SHARED_ADDRESS_SPACE)) {
+        return Some(CarrierNat::Cgnat);
+    }
+    None
+}
+
+/// 双方之间的打洞是否注定失败：任一方经 NAT64，或双方都位于 CGNAT 之后
+pub fn punch_doomed(a: Option<CarrierNat>, b: Option<CarrierNat>) -> bool {
+    matches!(a, Some(CarrierNat::Nat64))
+        || matches!(b, Some(CarrierNat::Nat64))
+        || (a == Some(CarrierNat::Cgnat) && b == Some(CarrierNat::Cgnat))
+}
+
+/// 已认证节点按运营商级地址转换分类的统计
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CarrierNatStats {
+    /// 位于 CGNAT 之后的节点数
+    pub cgnat_peers: usize,
+    /// 经 NAT64 上网的节点数
+    pub nat64_peers: usize,
+}
+
+impl CarrierNatStats {
+    pub fn record(&mut self, class: Option<CarrierNat>) {
+        match class {
+            Some(CarrierNat::Cgnat) => self.cgnat_peers += 1,
+            Some(CarrierNat::Nat64) => self.nat64_peers += 1,
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let config = CarrierNatConfig::default();
+        let public: IpAddr = "203.0.113.7".parse().unwrap();
+        let lan: SocketAddr = "192.168.1.5:4000".parse().unwrap();
+
+        assert_eq!(classify(&config, public, &[lan], 1), None);
+        assert_eq!(classify(&config, public, &["100.72.1.9:4000".parse().unwrap()], 1), Some(CarrierNat::Cgnat));
+        assert_eq!(classify(&config, "100.127.255.1".parse().unwrap(), &[], 1), Some(CarrierNat::Cgnat));
+        assert_eq!(classify(&config, public, &[lan], config.shared_ip_threshold), Some(CarrierNat::Cgnat));
+        assert_eq!(classify(&config, public, &["[64:ff9b::cb00:7107]:4000".parse().unwrap()], 1), Some(CarrierNat::Nat64));
+
+        let no_sharing = CarrierNatConfig { shared_ip_threshold: 0, ..CarrierNatConfig::default() };
+        assert_eq!(classify(&no_sharing, public, &[lan], 1000), None);
+    }
+
+    #[test]
+    fn test_punch_doomed() {
+        use CarrierNat::*;
+        assert!(!punch_doomed(None, None));
+        assert!(!punch_doomed(Some(Cgnat), None));
+        assert!(punch_doomed(Some(Cgnat), Some(Cgnat)));
+        assert!(punch_doomed(None, Some(Nat64)));
+    }
+}
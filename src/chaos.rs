@@ -0,0 +1,234 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use log::debug;
+use crate::tr;
+use rand::Rng;
+use serde::Serialize;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::config::{ChaosConfig, FaultProfile};
+
+/// 数据包方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// 按参数决定一个数据包的投递计划：每个元素是一份副本的投递延迟，空表示丢弃
+pub fn plan<R: Rng>(profile: &FaultProfile, rng: &mut R) -> Vec<Duration> {
+    if profile.drop_rate > 0.0 && rng.r#gen::<f64>() < profile.drop_rate {
+        return Vec::new();
+    }
+    let copies = if profile.duplicate_rate > 0.0 && rng.r#gen::<f64>() < profile.duplicate_rate { 2 } else { 1 };
+    (0..copies)
+        .map(|_| {
+            let mut delay = profile.latency_ms;
+            if profile.jitter_ms > 0 {
+                delay += rng.gen_range(0..=profile.jitter_ms);
+            }
+            if profile.reorder_rate > 0.0 && rng.r#gen::<f64>() < profile.reorder_rate {
+                delay += profile.reorder_delay_ms;
+            }
+            Duration::from_millis(delay)
+        })
+        .collect()
+}
+
+/// 单个方向的注入统计
+#[derive(Debug, Default)]
+struct DirectionStats {
+    packets: AtomicU64,
+    dropped: AtomicU64,
+    duplicated: AtomicU64,
+    delayed: AtomicU64,
+}
+
+impl DirectionStats {
+    fn record(&self, plan: &[Duration]) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        match plan.len() {
+            0 => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            1 => {}
+            _ => {
+                self.duplicated.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if plan.iter().any(|d| !d.is_zero()) {
+            self.delayed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> DirectionStatsSnapshot {
+        DirectionStatsSnapshot {
+            packets: self.packets.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            duplicated: self.duplicated.load(Ordering::Relaxed),
+            delayed: self.delayed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectionStatsSnapshot {
+    pub packets: u64,
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub delayed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FaultStats {
+    pub inbound: DirectionStatsSnapshot,
+    pub outbound: DirectionStatsSnapshot,
+}
+
+/// 网络层故障注入器（`chaos` 特性）
+///
+/// 由 `NetworkManager` 在收发数据包时调用，按配置的概率做丢弃、重复、乱序与延迟，
+/// 用于在真实部署形态下检验重传、去重与路由层的韧性。参数可经管理接口 `PUT /api/chaos` 在运行时调整。
+#[derive(Debug)]
+pub struct FaultInjector {
+    config: RwLock<ChaosConfig>,
+    inbound: DirectionStats,
+    outbound: DirectionStats,
+    /// 延迟投递的入站数据包，到期后由 `recv_delayed` 取出
+    delayed_tx: mpsc::UnboundedSender<(Vec<u8>, SocketAddr)>,
+    delayed_rx: Mutex<mpsc::UnboundedReceiver<(Vec<u8>, SocketAddr)>>,
+}
+
+impl FaultInjector {
+    pub fn new(config: ChaosConfig) -> Self {
+        let (delayed_tx, delayed_rx) = mpsc::unbounded_channel();
+        Self {
+            config: RwLock::new(config),
+            inbound: DirectionStats::default(),
+            outbound: DirectionStats::default(),
+            delayed_tx,
+            delayed_rx: Mutex::new(delayed_rx),
+        }
+    }
+
+    pub fn config(&self) -> ChaosConfig {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 运行时替换注入参数
+    pub fn set_config(&self, config: ChaosConfig) -> anyhow::Result<()> {
+        for profile in [&config.inbound, &config.outbound] {
+            for rate in [profile.drop_rate, profile.duplicate_rate, profile.reorder_rate] {
+                if !(0.0..=1.0).contains(&rate) {
+                    return Err(anyhow::anyhow!("概率必须在 0.0 到 1.0 之间: {}", rate));
+                }
+            }
+        }
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> FaultStats {
+        FaultStats { inbound: self.inbound.snapshot(), outbound: self.outbound.snapshot() }
+    }
+
+    /// 计算数据包的投递计划，未启用时原样立即投递
+    fn plan_for(&self, direction: Direction) -> Vec<Duration> {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        if !config.enable {
+            return vec![Duration::ZERO];
+        }
+        let (profile, stats) = match direction {
+            Direction::Inbound => (&config.inbound, &self.inbound),
+            Direction::Outbound => (&config.outbound, &self.outbound),
+        };
+        let plan = plan(profile, &mut rand::thread_rng());
+        stats.record(&plan);
+        plan
+    }
+
+    /// 处理收到的数据包：返回需要立即交给上层的数据包，其余副本延迟后经 `recv_delayed` 投递
+    pub fn inbound(&self, data: Vec<u8>, addr: SocketAddr) -> Option<(Vec<u8>, SocketAddr)> {
+        let mut immediate = None;
+        for delay in self.plan_for(Direction::Inbound) {
+            if delay.is_zero() && immediate.is_none() {
+                immediate = Some((data.clone(), addr));
+                continue;
+            }
+            let tx = self.delayed_tx.clone();
+            let data = data.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = tx.send((data, addr));
+            });
+        }
+        if immediate.is_none() {
+            debug!("{}", tr!("故障注入：来自 {} 的数据包被丢弃或延迟", "Chaos: packet from {} dropped or delayed", addr));
+        }
+        immediate
+    }
+
+    /// 取出一个到期的延迟入站数据包
+    pub async fn recv_delayed(&self) -> Option<(Vec<u8>, SocketAddr)> {
+        self.delayed_rx.lock().await.recv().await
+    }
+
+    /// 处理待发送的数据包：返回调用方是否应立即发送，其余副本延迟后由后台任务发送
+    pub fn outbound(&self, socket: &Arc<UdpSocket>, data: &[u8], addr: SocketAddr) -> bool {
+        let mut send_now = false;
+        for delay in self.plan_for(Direction::Outbound) {
+            if delay.is_zero() && !send_now {
+                send_now = true;
+                continue;
+            }
+            let socket = socket.clone();
+            let data = data.to_vec();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                if let Err(e) = socket.send_to(&data, addr).await {
+                    debug!("{}", tr!("故障注入：延迟发送到 {} 失败: {}", "Chaos: delayed send to {} failed: {}", addr, e));
+                }
+            });
+        }
+        if !send_now {
+            debug!("{}", tr!("故障注入：发往 {} 的数据包被丢弃或延迟", "Chaos: packet to {} dropped or delayed", addr));
+        }
+        send_now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_plan_applies_probabilities() {
+        let mut rng = StdRng::seed_from_u64(7);
+        assert_eq!(plan(&FaultProfile::default(), &mut rng), vec![Duration::ZERO]);
+
+        let drop_all = FaultProfile { drop_rate: 1.0, ..FaultProfile::default() };
+        assert!(plan(&drop_all, &mut rng).is_empty());
+
+        let duplicate = FaultProfile { duplicate_rate: 1.0, latency_ms: 10, ..FaultProfile::default() };
+        assert_eq!(plan(&duplicate, &mut rng), vec![Duration::from_millis(10); 2]);
+
+        let reorder = FaultProfile { reorder_rate: 1.0, reorder_delay_ms: 30, jitter_ms: 5, ..FaultProfile::default() };
+        let delay = plan(&reorder, &mut rng)[0];
+        assert!(delay >= Duration::from_millis(30) && delay <= Duration::from_millis(35));
+
+        // 概率大致符合配置
+        let half = FaultProfile { drop_rate: 0.5, ..FaultProfile::default() };
+        let dropped = (0..1000).filter(|_| plan(&half, &mut rng).is_empty()).count();
+        assert!((400..600).contains(&dropped), "丢包数 {}", dropped);
+
+        let injector = FaultInjector::new(ChaosConfig::default());
+        let invalid = ChaosConfig { inbound: FaultProfile { drop_rate: 1.5, ..FaultProfile::default() }, ..ChaosConfig::default() };
+        assert!(injector.set_config(invalid).is_err());
+    }
+}
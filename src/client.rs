@@ -0,0 +1,1785 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use uuid::Uuid;
+
+use crate::address_book::{AddressBook, KnownPeer};
+use crate::bandwidth;
+use crate::config::RouteSignaturePolicy;
+use crate::correlation::PendingReplies;
+use crate::dormant::{self, WAKE_MAGIC};
+use crate::identity::{self, IdentityError, NodeIdentity, RouteSigner};
+use crate::pmtu::{self, MtuSearch, BASE_PLPMTU};
+use crate::protocol::{
+    AddressUpdate, BandwidthProbe, BandwidthReport, DeliveryReceipt, DiagnoseReport, DisconnectNotice, HandshakeProtocol, HandshakeResponse, KeyRotation, Message, MessageType,
+    MigrateAddressResponse, MtuProbe, NodeInfo, P2PPath, PathMtu, PeerInfo, PresenceUpdate, ProbeRole, PunchBeacon, RelayClose, RelayFrame, RelayResponse, RpcCall, RpcResult, RttSample, ServiceLookupResponse, WatchResponse,
+    RELAY_FRAME_CAPABILITY, parse_relay_data,
+};
+use crate::router::RoutedMessage;
+use crate::server_pool::{ServerHealth, ServerPool};
+use crate::stream::{self, FrameSender, P2PStream, StreamConfig, StreamRegistry};
+use crate::tr;
+
+/// 等待服务器应答（握手、直连协调）的超时
+const SERVER_REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+/// 握手重试次数
+const HANDSHAKE_ATTEMPTS: u32 = 3;
+/// 经服务器路由发送的消息的最大跳数
+const ROUTED_MAX_HOPS: u32 = 8;
+/// 等待带宽探测包的时长，超时后按已收到的包上报
+const PROBE_RECEIVE_WAIT: Duration = Duration::from_secs(2);
+
+/// 托管 P2P 会话的参数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// 每轮打洞向每个候选地址发送探测的次数
+    pub punch_attempts: u32,
+    /// 打洞探测间隔（毫秒）
+    pub punch_interval_ms: u64,
+    /// 直连保活间隔（毫秒）
+    pub keepalive_interval_ms: u64,
+    /// 连续这么多个保活间隔未收到对方的任何数据包即判定直连路径失效
+    pub max_missed_pongs: u32,
+    /// 经中继通信期间重新尝试打洞的间隔（毫秒）
+    pub repunch_interval_ms: u64,
+    /// 打洞失败或直连失效时经服务器中继收发数据（服务器需开启 `allow_symmetric_nat_relay`）
+    pub relay_fallback: bool,
+    /// 直连建立后探测到对方的路径 MTU
+    pub probe_mtu: bool,
+    /// 路径 MTU 探测的上限（字节）
+    pub max_mtu: usize,
+    /// 路径 MTU 探测包的应答等待时间（毫秒）
+    pub mtu_probe_timeout_ms: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            punch_attempts: 10,
+            punch_interval_ms: 200,
+            keepalive_interval_ms: 5000,
+            max_missed_pongs: 3,
+            repunch_interval_ms: 30000,
+            relay_fallback: true,
+            probe_mtu: false,
+            max_mtu: 1472,
+            mtu_probe_timeout_ms: 1000,
+        }
+    }
+}
+
+/// 客户端参数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    /// 握手服务器地址
+    pub server_addr: SocketAddr,
+    /// 备用握手服务器地址：主服务器握手失败或不再应答心跳时按健康分依次切换，以同一节点ID重新握手
+    pub backup_servers: Vec<SocketAddr>,
+    /// 活动服务器连续这么多次心跳未应答即切换到备用服务器；0 表示不切换
+    pub failover_after_missed: u32,
+    /// 本地UDP绑定地址，P2P 流量与服务器流量共用该套接字
+    pub bind_addr: SocketAddr,
+    /// 节点名称
+    pub name: String,
+    /// 网络ID，须与服务器一致
+    pub network_id: String,
+    /// 与服务器之间的心跳间隔（秒），应小于服务器的 `connection_timeout`
+    pub server_keepalive_secs: u64,
+    /// 固定的服务器公钥指纹（`sha256:...`）；非空时只接受签名有效且指纹在列表中的握手响应
+    pub server_pins: Vec<String>,
+    /// 节点身份密钥文件（不存在时生成）；配置后节点ID固定，握手附带密钥证明，可用 [`P2PClient::rotate_key`] 轮换
+    pub identity_key_file: Option<PathBuf>,
+    /// 对发给本节点的路由消息的签名校验策略；配置了 `identity_key_file` 时本节点发出的路由消息总会签名
+    pub route_signatures: RouteSignaturePolicy,
+    /// 地址簿文件（JSON，不存在时创建）；配置后记录直连协调过的节点，重启后与它们建立会话时先按记录打洞
+    pub address_book: Option<PathBuf>,
+    pub session: SessionConfig,
+    pub stream: StreamConfig,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            server_addr: "127.0.0.1:8080".parse().unwrap(),
+            backup_servers: Vec::new(),
+            failover_after_missed: 3,
+            bind_addr: "0.0.0.0:0".parse().unwrap(),
+            name: "p2p_client".to_string(),
+            network_id: "p2p_default".to_string(),
+            server_keepalive_secs: 20,
+            server_pins: Vec::new(),
+            identity_key_file: None,
+            route_signatures: RouteSignaturePolicy::Off,
+            address_book: None,
+            session: SessionConfig::default(),
+            stream: StreamConfig::default(),
+        }
+    }
+}
+
+/// 会话当前使用的路径
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    /// 正在打洞，尚无可用路径
+    Punching,
+    /// 已与对方直连
+    Direct(SocketAddr),
+    /// 经服务器中继，后台定期重新打洞
+    Relay,
+    /// 对方下线或会话已关闭
+    Closed,
+}
+
+/// 会话收发数据所用的套接字、身份与地址簿
+#[derive(Clone)]
+struct Endpoint {
+    socket: Arc<UdpSocket>,
+    servers: Arc<ServerPool>,
+    node_id: Uuid,
+    address_book: Option<Arc<AddressBook>>,
+}
+
+impl Endpoint {
+    /// 当前活动服务器的地址
+    fn server_addr(&self) -> SocketAddr {
+        self.servers.active()
+    }
+
+    /// 在地址簿中记录服务器下发的对方地址
+    fn learn(&self, peer_id: Uuid, addrs: &PeerAddrs) {
+        let Some(book) = &self.address_book else { return };
+        if let Err(e) = book.learn(peer_id, addrs.observed, &addrs.candidates, addrs.nat_type.as_deref()) {
+            warn!("{}", tr!("更新地址簿失败: {}", "Failed to update address book: {}", e));
+        }
+    }
+
+    /// 在地址簿中记录会话最终使用的路径
+    fn remember(&self, link: &Link, path: P2PPath) {
+        let Some(book) = &self.address_book else { return };
+        let direct_addr = match link.state() {
+            SessionState::Direct(addr) => Some(addr),
+            _ => None,
+        };
+        let observed = link.addrs.lock().unwrap().observed;
+        if let Err(e) = book.record_path(link.peer_id, observed, path, direct_addr) {
+            warn!("{}", tr!("更新地址簿失败: {}", "Failed to update address book: {}", e));
+        }
+    }
+
+    async fn send(&self, message: &Message, target: SocketAddr) -> Result<()> {
+        self.socket.send_to(&serde_json::to_vec(message)?, target).await?;
+        Ok(())
+    }
+
+    async fn send_to_server(&self, message: &Message) -> Result<()> {
+        self.send(message, self.server_addr()).await
+    }
+
+    /// 经已建立的中继会话把数据以二进制帧发给服务器
+    async fn send_relay_frame(&self, session_id: Uuid, data: &[u8]) -> Result<()> {
+        let frame = RelayFrame { session_id, from_peer_id: Uuid::nil(), data };
+        self.socket.send_to(&frame.encode(), self.server_addr()).await?;
+        Ok(())
+    }
+
+    /// 节点间直接交换的消息，携带发送方ID以便对方找到会话
+    fn direct(&self, message_type: MessageType, data: Option<&[u8]>) -> Message {
+        let mut payload = serde_json::json!({ "node_id": self.node_id.to_string() });
+        if let Some(data) = data {
+            payload["data"] = serde_json::json!(data);
+        }
+        Message::new(message_type, payload)
+    }
+}
+
+/// 服务器下发的对方地址
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PeerAddrs {
+    /// 服务器看到的对方地址
+    observed: SocketAddr,
+    /// 按尝试顺序排列的打洞候选地址
+    candidates: Vec<SocketAddr>,
+    /// 服务器测得的直连带宽过低，建议先经中继通信
+    prefer_relay: bool,
+    /// 服务器将发送倒计时信标，约在该毫秒数后 GO
+    punch_in_ms: Option<u64>,
+    /// 双方位于运营商级地址转换之后，服务器判定打洞注定失败
+    skip_punch: bool,
+    /// 服务器转发的对方NAT类型
+    nat_type: Option<String>,
+}
+
+impl PeerAddrs {
+    /// 解析 `P2PConnect` 载荷：优先使用服务器给出的 `candidates`，否则依次尝试公网地址与对方通告的地址
+    fn from_payload(payload: &serde_json::Value) -> Option<Self> {
+        let observed: SocketAddr = payload.get("peer_addr")?.as_str()?.parse().ok()?;
+        let parse = |key: &str| -> Vec<SocketAddr> {
+            payload
+                .get(key)
+                .and_then(|v| v.as_array())
+                .map(|list| list.iter().filter_map(|a| a.as_str()?.parse().ok()).collect())
+                .unwrap_or_default()
+        };
+        let mut candidates = parse("candidates");
+        if candidates.is_empty() {
+            candidates.push(observed);
+            candidates.extend(parse("peer_addresses"));
+        }
+        let mut seen = Vec::with_capacity(candidates.len());
+        candidates.retain(|addr| {
+            let fresh = !seen.contains(addr);
+            seen.push(*addr);
+            fresh
+        });
+        let prefer_relay = payload.get("prefer_relay").and_then(|v| v.as_bool()).unwrap_or(false);
+        let punch_in_ms = payload.get("punch_in_ms").and_then(|v| v.as_u64());
+        let skip_punch = payload.get("skip_punch").and_then(|v| v.as_bool()).unwrap_or(false);
+        let nat_type = payload.get("peer_nat_type").and_then(|v| v.as_str()).map(str::to_string);
+        Some(Self { observed, candidates, prefer_relay, punch_in_ms, skip_punch, nat_type })
+    }
+
+    fn from_update(update: &AddressUpdate) -> Self {
+        let mut candidates = vec![update.peer_addr];
+        candidates.extend(update.peer_addresses.iter().filter(|addr| **addr != update.peer_addr));
+        Self { observed: update.peer_addr, candidates, prefer_relay: false, punch_in_ms: None, skip_punch: false, nat_type: None }
+    }
+
+    /// 地址簿中的记录：从未直连成功的节点没有可预先尝试的地址
+    fn from_known(peer: &KnownPeer) -> Option<Self> {
+        let candidates = peer.punch_candidates();
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(Self {
+            observed: peer.observed,
+            candidates,
+            prefer_relay: false,
+            punch_in_ms: None,
+            skip_punch: false,
+            nat_type: peer.nat_type.clone(),
+        })
+    }
+}
+
+/// 一个会话的共享状态：读循环更新，会话驱动任务与应用读取
+struct Link {
+    peer_id: Uuid,
+    state: Mutex<SessionState>,
+    addrs: Mutex<PeerAddrs>,
+    last_heard: Mutex<Instant>,
+    /// 最近一次保活 Ping 的发送时间
+    ping_sent: Mutex<Option<Instant>>,
+    /// 最近测得的直连往返时延
+    rtt: Mutex<Option<Duration>>,
+    /// 服务器分配的中继会话ID，建立后中继数据以二进制帧发送
+    relay_session: Mutex<Option<Uuid>>,
+    /// 服务器安排的打洞开始时间（GO），到达前不发送探测
+    punch_at: Mutex<Option<Instant>>,
+    /// 探测出的直连路径 MTU 及其所属的对方地址
+    path_mtu: Mutex<Option<(SocketAddr, PathMtu)>>,
+    /// 等待对方应答的路径 MTU 探测包
+    probes: PendingReplies,
+    /// 路径建立、地址变化或会话关闭时唤醒驱动任务
+    wake: Notify,
+    inbox: Mutex<Option<mpsc::UnboundedSender<Vec<u8>>>>,
+    driver: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Link {
+    fn state(&self) -> SessionState {
+        *self.state.lock().unwrap()
+    }
+
+    fn set_state(&self, state: SessionState) {
+        let mut current = self.state.lock().unwrap();
+        if *current != SessionState::Closed {
+            *current = state;
+        }
+    }
+
+    /// 收到对方从 `from` 直接发来的数据包：该路径可用
+    fn heard_from(&self, from: SocketAddr) {
+        *self.last_heard.lock().unwrap() = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            SessionState::Closed => {}
+            SessionState::Direct(addr) if addr == from => {}
+            _ => {
+                info!("{}", tr!("与节点 {} 的直连已建立: {}", "Direct connection to node {} established: {}", self.peer_id, from));
+                *state = SessionState::Direct(from);
+                drop(state);
+                self.wake.notify_one();
+            }
+        }
+    }
+
+    /// 对方地址变化：旧的直连路径作废，重新打洞
+    fn update_addrs(&self, addrs: PeerAddrs) {
+        if let Some(punch_in_ms) = addrs.punch_in_ms {
+            *self.punch_at.lock().unwrap() = Some(Instant::now() + Duration::from_millis(punch_in_ms));
+        }
+        *self.addrs.lock().unwrap() = addrs;
+        self.wake.notify_one();
+    }
+
+    /// 按地址簿预先打洞的会话收到服务器的直连协调：已直连时保持现有路径，否则改用服务器给出的地址
+    fn coordinated(&self, addrs: PeerAddrs) {
+        if !matches!(self.state(), SessionState::Direct(_)) {
+            self.update_addrs(addrs);
+        }
+    }
+
+    /// 收到倒计时信标：按服务器的倒计时校准 GO 的时间
+    fn beacon(&self, go_in_ms: u64) {
+        *self.punch_at.lock().unwrap() = Some(Instant::now() + Duration::from_millis(go_in_ms));
+        self.wake.notify_one();
+    }
+
+    /// 收到对方的 Pong：按最近一次保活 Ping 计算往返时延
+    fn pong_received(&self) {
+        if let Some(sent) = self.ping_sent.lock().unwrap().take() {
+            *self.rtt.lock().unwrap() = Some(sent.elapsed());
+        }
+    }
+
+    fn deliver(&self, data: Vec<u8>) {
+        if let Some(inbox) = self.inbox.lock().unwrap().as_ref() {
+            let _ = inbox.send(data);
+        }
+    }
+
+    fn close(&self) {
+        *self.state.lock().unwrap() = SessionState::Closed;
+        self.inbox.lock().unwrap().take();
+        if let Some(driver) = self.driver.lock().unwrap().take() {
+            driver.abort();
+        }
+    }
+}
+
+type Registry = Arc<Mutex<HashMap<Uuid, Arc<Link>>>>;
+
+/// 与一个节点之间的托管 P2P 会话
+///
+/// 会话在后台完成打洞与保活，直连失效（连续未收到对方数据包）时回退到服务器中继并定期重新打洞，
+/// 对方地址变更（`AddressUpdate`）时立即重新打洞。应用只需调用 [`send`](Self::send) 与
+/// [`recv`](Self::recv)，无需关心当前走的是哪条路径。
+pub struct P2PSession {
+    link: Arc<Link>,
+    endpoint: Endpoint,
+    relay_fallback: bool,
+    inbox: mpsc::UnboundedReceiver<Vec<u8>>,
+    registry: Registry,
+}
+
+impl P2PSession {
+    fn start(endpoint: Endpoint, registry: Registry, config: SessionConfig, peer_id: Uuid, addrs: PeerAddrs) -> Self {
+        let (inbox_tx, inbox) = mpsc::unbounded_channel();
+        let link = Arc::new(Link {
+            peer_id,
+            state: Mutex::new(SessionState::Punching),
+            punch_at: Mutex::new(addrs.punch_in_ms.map(|ms| Instant::now() + Duration::from_millis(ms))),
+            addrs: Mutex::new(addrs),
+            last_heard: Mutex::new(Instant::now()),
+            ping_sent: Mutex::new(None),
+            rtt: Mutex::new(None),
+            relay_session: Mutex::new(None),
+            path_mtu: Mutex::new(None),
+            probes: PendingReplies::new(),
+            wake: Notify::new(),
+            inbox: Mutex::new(Some(inbox_tx)),
+            driver: Mutex::new(None),
+        });
+        if let Some(previous) = registry.lock().unwrap().insert(peer_id, link.clone()) {
+            previous.close();
+        }
+        let relay_fallback = config.relay_fallback;
+        let driver = tokio::spawn(drive(endpoint.clone(), link.clone(), config));
+        *link.driver.lock().unwrap() = Some(driver);
+        Self { link, endpoint, relay_fallback, inbox, registry }
+    }
+
+    /// 对方节点ID
+    pub fn peer_id(&self) -> Uuid {
+        self.link.peer_id
+    }
+
+    /// 当前路径
+    pub fn state(&self) -> SessionState {
+        self.link.state()
+    }
+
+    /// 直连期间最近测得的往返时延
+    pub fn rtt(&self) -> Option<Duration> {
+        *self.link.rtt.lock().unwrap()
+    }
+
+    /// 当前直连路径探测出的 MTU；未开启 `probe_mtu`、尚未探测完或路径已变化时为 `None`
+    pub fn path_mtu(&self) -> Option<PathMtu> {
+        let SessionState::Direct(addr) = self.link.state() else { return None };
+        self.link.path_mtu.lock().unwrap().filter(|(probed, _)| *probed == addr).map(|(_, path_mtu)| path_mtu)
+    }
+
+    /// 发送数据：已直连时直接发给对方，否则经服务器中继
+    pub async fn send(&self, data: &[u8]) -> Result<()> {
+        match self.link.state() {
+            SessionState::Direct(addr) => {
+                self.endpoint.send(&self.endpoint.direct(MessageType::Data, Some(data)), addr).await
+            }
+            SessionState::Closed => anyhow::bail!("与节点 {} 的会话已关闭", self.link.peer_id),
+            _ if self.relay_fallback => {
+                // 会话建立前随 `RelayRequest` 发送，服务器应答会话ID后改用二进制帧
+                let session_id = *self.link.relay_session.lock().unwrap();
+                match session_id {
+                    Some(session_id) => self.endpoint.send_relay_frame(session_id, data).await,
+                    None => self.endpoint.send_to_server(&Message::relay_request(self.link.peer_id, data.to_vec())).await,
+                }
+            }
+            _ => anyhow::bail!("与节点 {} 的直连尚未建立", self.link.peer_id),
+        }
+    }
+
+    /// 接收对方发来的数据（直连或中继），会话关闭后返回 `None`
+    pub async fn recv(&mut self) -> Option<Vec<u8>> {
+        self.inbox.recv().await
+    }
+
+    /// 关闭会话并通知服务器，同时关闭已建立的中继会话
+    pub async fn close(self) -> Result<()> {
+        let relay_session = self.link.relay_session.lock().unwrap().take();
+        if let Some(session_id) = relay_session {
+            self.endpoint.send_to_server(&Message::relay_close(session_id)).await?;
+        }
+        self.endpoint.send_to_server(&Message::p2p_connect_result(self.link.peer_id, P2PPath::Failed)).await
+    }
+}
+
+impl Drop for P2PSession {
+    fn drop(&mut self) {
+        self.link.close();
+        let mut registry = self.registry.lock().unwrap();
+        if registry.get(&self.link.peer_id).is_some_and(|link| Arc::ptr_eq(link, &self.link)) {
+            registry.remove(&self.link.peer_id);
+        }
+    }
+}
+
+/// 会话驱动任务：打洞 → 直连保活 → 失效后回退中继并重新打洞
+async fn drive(endpoint: Endpoint, link: Arc<Link>, config: SessionConfig) {
+    let mut reported = None;
+    if config.relay_fallback && link.addrs.lock().unwrap().prefer_relay {
+        info!("{}", tr!("服务器建议与节点 {} 优先经中继通信", "Server suggests relaying traffic to node {}", link.peer_id));
+        fall_back(&endpoint, &link, &config, &mut reported).await;
+        // 打洞注定失败时一直经中继通信，直到对方地址变化或直接收到对方的数据包
+        while link.addrs.lock().unwrap().skip_punch && link.state() == SessionState::Relay {
+            link.wake.notified().await;
+        }
+        tokio::select! {
+            _ = sleep(Duration::from_millis(config.repunch_interval_ms)) => {}
+            _ = link.wake.notified() => {}
+        }
+    }
+    loop {
+        punch(&endpoint, &link, &config).await;
+        match link.state() {
+            SessionState::Closed => return,
+            SessionState::Direct(addr) => {
+                let path = if addr == link.addrs.lock().unwrap().observed { P2PPath::Public } else { P2PPath::Private };
+                report(&endpoint, &link, path, &mut reported).await;
+                tokio::join!(keep_alive(&endpoint, &link, &config), async {
+                    if config.probe_mtu {
+                        probe_path_mtu(&endpoint, &link, &config, addr).await;
+                    }
+                });
+                if link.state() == SessionState::Closed {
+                    return;
+                }
+                warn!("{}", tr!("与节点 {} 的直连路径失效，重新打洞", "Direct path to node {} failed, punching again", link.peer_id));
+                fall_back(&endpoint, &link, &config, &mut reported).await;
+            }
+            _ => {
+                debug!("{}", tr!("与节点 {} 的打洞未成功", "Hole punching with node {} did not succeed", link.peer_id));
+                fall_back(&endpoint, &link, &config, &mut reported).await;
+                let retry = Duration::from_millis(config.repunch_interval_ms);
+                tokio::select! {
+                    _ = sleep(retry) => {}
+                    _ = link.wake.notified() => {}
+                }
+            }
+        }
+    }
+}
+
+/// 向全部候选地址发送探测，直到对方应答或次数用尽
+async fn punch(endpoint: &Endpoint, link: &Link, config: &SessionConfig) {
+    // 服务器安排了 GO 时，等到 GO 再探测，使双方的探测同时到达各自的 NAT
+    loop {
+        let Some(at) = *link.punch_at.lock().unwrap() else { break };
+        if at <= Instant::now() || matches!(link.state(), SessionState::Direct(_) | SessionState::Closed) {
+            break;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep_until(at.into()) => {}
+            _ = link.wake.notified() => {}
+        }
+    }
+    link.punch_at.lock().unwrap().take();
+    let probe = endpoint.direct(MessageType::Ping, None);
+    for _ in 0..config.punch_attempts {
+        if matches!(link.state(), SessionState::Direct(_) | SessionState::Closed) {
+            return;
+        }
+        let candidates = link.addrs.lock().unwrap().candidates.clone();
+        for addr in candidates {
+            if let Err(e) = endpoint.send(&probe, addr).await {
+                debug!("{}", tr!("向 {} 发送打洞探测失败: {}", "Failed to send punch probe to {}: {}", addr, e));
+            }
+        }
+        tokio::select! {
+            _ = sleep(Duration::from_millis(config.punch_interval_ms)) => {}
+            _ = link.wake.notified() => {}
+        }
+    }
+}
+
+/// 直连期间定期发送 Ping；连续 `max_missed_pongs` 个间隔没有收到对方的数据包或地址变化时返回
+async fn keep_alive(endpoint: &Endpoint, link: &Link, config: &SessionConfig) {
+    let interval = Duration::from_millis(config.keepalive_interval_ms);
+    let deadline = interval * config.max_missed_pongs.max(1);
+    let addrs_before = link.addrs.lock().unwrap().clone();
+    loop {
+        tokio::select! {
+            _ = sleep(interval) => {}
+            _ = link.wake.notified() => {}
+        }
+        let SessionState::Direct(addr) = link.state() else { return };
+        if *link.addrs.lock().unwrap() != addrs_before || link.last_heard.lock().unwrap().elapsed() >= deadline {
+            return;
+        }
+        *link.ping_sent.lock().unwrap() = Some(Instant::now());
+        if let Err(e) = endpoint.send(&endpoint.direct(MessageType::Ping, None), addr).await {
+            debug!("{}", tr!("向 {} 发送保活失败: {}", "Failed to send keepalive to {}: {}", addr, e));
+        }
+    }
+}
+
+/// 沿直连路径搜索路径 MTU，路径变化时放弃
+async fn probe_path_mtu(endpoint: &Endpoint, link: &Link, config: &SessionConfig, addr: SocketAddr) {
+    if link.path_mtu.lock().unwrap().is_some_and(|(probed, _)| probed == addr) {
+        return;
+    }
+    let wait = Duration::from_millis(config.mtu_probe_timeout_ms);
+    let mut search = MtuSearch::new(BASE_PLPMTU, config.max_mtu, 3);
+    while let Some(size) = search.next_probe() {
+        if link.state() != SessionState::Direct(addr) {
+            return;
+        }
+        let mut probe = endpoint.direct(MessageType::MtuProbe, None);
+        probe.payload["size"] = serde_json::json!(size);
+        pmtu::pad_to(&mut probe, size);
+        match link.probes.request(&probe, wait, endpoint.send(&probe, addr)).await {
+            Ok(_) => search.acked(size),
+            Err(_) => search.timed_out(size),
+        }
+    }
+    let path_mtu = PathMtu { mtu: search.confirmed(), limited: search.limit().is_some() };
+    info!("{}", tr!("到节点 {} 的直连路径 MTU: {:?}", "Path MTU of direct path to node {}: {:?}", link.peer_id, path_mtu));
+    *link.path_mtu.lock().unwrap() = Some((addr, path_mtu));
+}
+
+/// 放弃当前直连：允许时改走中继，否则继续打洞
+async fn fall_back(endpoint: &Endpoint, link: &Link, config: &SessionConfig, reported: &mut Option<P2PPath>) {
+    if config.relay_fallback {
+        link.set_state(SessionState::Relay);
+        report(endpoint, link, P2PPath::Relay, reported).await;
+    } else {
+        link.set_state(SessionState::Punching);
+        report(endpoint, link, P2PPath::Failed, reported).await;
+    }
+}
+
+/// 路径变化时向服务器上报，服务器据此维护会话并在对方地址变化时推送 `AddressUpdate`
+async fn report(endpoint: &Endpoint, link: &Link, path: P2PPath, reported: &mut Option<P2PPath>) {
+    if *reported == Some(path) {
+        return;
+    }
+    info!("{}", tr!("与节点 {} 的会话路径: {:?}", "Session path to node {}: {:?}", link.peer_id, path));
+    endpoint.remember(link, path);
+    if let Err(e) = endpoint.send_to_server(&Message::p2p_connect_result(link.peer_id, path)).await {
+        warn!("{}", tr!("上报直连结果失败: {}", "Failed to report direct connection result: {}", e));
+    }
+    *reported = Some(path);
+}
+
+/// 正在接收的带宽探测包串
+struct ProbeTrain {
+    sender: Uuid,
+    packets: u32,
+    packet_size: usize,
+    arrivals: Vec<Instant>,
+}
+
+type ProbeTrains = Arc<Mutex<HashMap<Uuid, ProbeTrain>>>;
+
+/// 沿直连路径连续发出探测包，每个包填充到约 `packet_size` 字节
+async fn send_probe_train(endpoint: &Endpoint, addr: SocketAddr, probe: &BandwidthProbe) {
+    for seq in 0..probe.packets {
+        let mut message = endpoint.direct(MessageType::BandwidthProbe, None);
+        message.payload["probe_id"] = serde_json::json!(probe.probe_id.to_string());
+        message.payload["seq"] = serde_json::json!(seq);
+        pmtu::pad_to(&mut message, probe.packet_size);
+        if let Err(e) = endpoint.send(&message, addr).await {
+            debug!("{}", tr!("向 {} 发送带宽探测包失败: {}", "Failed to send bandwidth probe packet to {}: {}", addr, e));
+            return;
+        }
+    }
+}
+
+/// 结束一次带宽探测并向服务器上报结果，每次探测只上报一次
+async fn finish_probe(endpoint: &Endpoint, probes: &ProbeTrains, probe_id: Uuid) {
+    let Some(train) = probes.lock().unwrap().remove(&probe_id) else { return };
+    let report = BandwidthReport {
+        probe_id,
+        sender: train.sender,
+        receiver: endpoint.node_id,
+        received: train.arrivals.len() as u32,
+        bandwidth_bps: bandwidth::estimate(&train.arrivals, train.packet_size),
+    };
+    debug!("{}", tr!("带宽探测 {} 收到 {}/{} 个包，估计 {:?} bit/s", "Bandwidth probe {} received {}/{} packets, estimate {:?} bit/s", probe_id, report.received, train.packets, report.bandwidth_bps));
+    let result = async { endpoint.send_to_server(&Message::bandwidth_report(&report)?).await }.await;
+    if let Err(e) = result {
+        warn!("{}", tr!("上报带宽探测结果失败: {}", "Failed to report bandwidth probe result: {}", e));
+    }
+}
+
+struct Shared {
+    endpoint: Endpoint,
+    session_config: SessionConfig,
+    registry: Registry,
+    /// 等待服务器下发直连协调的 `open_session` 调用
+    pending: Mutex<HashMap<Uuid, oneshot::Sender<PeerAddrs>>>,
+    incoming: mpsc::UnboundedSender<P2PSession>,
+    /// 等待服务器应答的 `request` 调用
+    replies: PendingReplies,
+    /// 其他来自服务器的 `Data` 消息
+    data: mpsc::UnboundedSender<Message>,
+    /// 转发给本节点的远程调用
+    calls: mpsc::UnboundedSender<IncomingCall>,
+    /// 所关注节点的在线状态变化
+    presence: mpsc::UnboundedSender<PresenceUpdate>,
+    /// 服务器发来的断开通知
+    disconnects: mpsc::UnboundedSender<DisconnectNotice>,
+    /// 服务器转发的其他节点的密钥轮换
+    key_rotations: mpsc::UnboundedSender<KeyRotation>,
+    stream_config: StreamConfig,
+    streams: StreamRegistry,
+    /// 其他节点打开的流
+    incoming_streams: mpsc::UnboundedSender<P2PStream>,
+    probes: ProbeTrains,
+    /// 服务器探测出的本节点路径 MTU
+    server_path_mtu: Mutex<Option<PathMtu>>,
+    /// 休眠期间的唤醒令牌，醒着时为 `None`
+    dormant: Mutex<Option<u64>>,
+    /// 收到服务器的唤醒数据包
+    woken: Notify,
+    /// 对本节点发出的路由消息签名
+    signer: RouteSigner,
+    route_signatures: RouteSignaturePolicy,
+    /// 握手所用的节点信息，切换服务器后凭此以同一节点ID重新握手
+    node_info: NodeInfo,
+    server_pins: Vec<String>,
+    /// 活动服务器在握手时下发的信息
+    registration: Mutex<Registration>,
+}
+
+/// 服务器在握手响应中下发的信息，切换服务器后更新
+#[derive(Debug, Default)]
+struct Registration {
+    public_addr: Option<SocketAddr>,
+    session_ticket: Option<String>,
+    server_fingerprint: Option<String>,
+}
+
+impl Registration {
+    fn new(response: &HandshakeResponse, server_fingerprint: Option<String>) -> Self {
+        Self { public_addr: response.public_addr, session_ticket: response.session_ticket.clone(), server_fingerprint }
+    }
+}
+
+impl Shared {
+    fn link(&self, peer_id: &Uuid) -> Option<Arc<Link>> {
+        self.registry.lock().unwrap().get(peer_id).cloned()
+    }
+
+    /// 清除引用该中继会话的记录，下次经中继发送时重新建立
+    fn forget_relay_session(&self, session_id: Uuid) {
+        for link in self.registry.lock().unwrap().values() {
+            let mut relay_session = link.relay_session.lock().unwrap();
+            if *relay_session == Some(session_id) {
+                *relay_session = None;
+            }
+        }
+    }
+
+    /// 各直连会话最近测得的往返时延，随回应服务器心跳的 `Pong` 上报
+    fn rtt_samples(&self) -> Vec<RttSample> {
+        self.registry
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|link| matches!(link.state(), SessionState::Direct(_)))
+            .filter_map(|link| {
+                let rtt = (*link.rtt.lock().unwrap())?;
+                Some(RttSample { peer_id: link.peer_id, rtt_us: rtt.as_micros() as u64 })
+            })
+            .collect()
+    }
+
+    /// 流参数：服务器探测出路径 MTU 上限时收紧每帧的数据量
+    fn stream_config(&self) -> StreamConfig {
+        match *self.server_path_mtu.lock().unwrap() {
+            Some(PathMtu { mtu, limited: true }) => stream::fit_to_mtu(&self.stream_config, mtu, self.signer.is_signing()),
+            _ => self.stream_config.clone(),
+        }
+    }
+
+    /// 结束休眠：发送心跳，服务器收到后补发暂存的消息
+    async fn wake(&self) -> Result<()> {
+        if self.dormant.lock().unwrap().take().is_some() {
+            self.endpoint.send_to_server(&Message::ping()).await?;
+        }
+        Ok(())
+    }
+
+    /// 向活动服务器重新握手，沿用本节点ID与身份密钥
+    async fn register(&self) -> Result<()> {
+        let identity = self.signer.identity();
+        let mut request = handshake_request(identity.as_deref(), self.node_info.clone())?;
+        let mut reply = self.replies.request(&request, SERVER_REPLY_TIMEOUT, self.endpoint.send_to_server(&request)).await?;
+        if let Some(cookie) = HandshakeProtocol::cookie_challenge(&reply) {
+            request = with_cookie(identity.as_deref(), &request, cookie);
+            reply = self.replies.request(&request, SERVER_REPLY_TIMEOUT, self.endpoint.send_to_server(&request)).await?;
+        }
+        if reply.message_type == MessageType::Error {
+            anyhow::bail!("握手被拒绝: {}", reply.payload["error"].as_str().unwrap_or_default());
+        }
+        let server_fingerprint = verify_response(&reply, request.id, self.signer.network_id(), &self.server_pins)
+            .map_err(|e| anyhow::anyhow!("服务器 {} 身份校验失败: {}", self.endpoint.server_addr(), e))?;
+        let response = HandshakeProtocol::validate_handshake_response(&reply).map_err(anyhow::Error::msg)?;
+        if !response.success {
+            anyhow::bail!("握手被拒绝: {}", response.error_message.unwrap_or_default());
+        }
+        *self.registration.lock().unwrap() = Registration::new(&response, server_fingerprint);
+        Ok(())
+    }
+
+    /// 活动服务器不再应答心跳：按健康分依次向备用服务器握手，成功后改用它；都失败时留在原服务器
+    async fn fail_over(&self) {
+        let previous = self.endpoint.server_addr();
+        for server_addr in self.endpoint.servers.standby() {
+            self.endpoint.servers.activate(server_addr);
+            match self.register().await {
+                Ok(()) => {
+                    self.endpoint.servers.handshake_result(server_addr, true);
+                    info!("{}", tr!("服务器 {} 不再应答，已切换到 {}", "Server {} stopped answering, failed over to {}", previous, server_addr));
+                    self.forget_server_state();
+                    return;
+                }
+                Err(e) => {
+                    self.endpoint.servers.handshake_result(server_addr, false);
+                    warn!("{}", tr!("与备用服务器 {} 握手失败: {}", "Handshake with backup server {} failed: {}", server_addr, e));
+                }
+            }
+        }
+        self.endpoint.servers.activate(previous);
+        warn!("{}", tr!("没有可用的备用服务器，继续使用 {}", "No backup server is available, staying on {}", previous));
+    }
+
+    /// 服务器提示改连另一实例（排空或滚动升级）
+    ///
+    /// 会话已移交时从本地址发出携带会话票据的 `MigrateAddress`，无需重新握手；迁移被拒、会话未移交，
+    /// 或配置了 `server_pins` 需要校验新服务器的身份时重新握手。都失败时留在原服务器。
+    async fn reconnect(&self, server_addr: SocketAddr, session_preserved: bool) {
+        let previous = self.endpoint.server_addr();
+        if server_addr == previous {
+            return;
+        }
+        self.endpoint.servers.redirect(server_addr);
+        // 未经公钥固定校验的地址不出示票据
+        let ticket = self.registration.lock().unwrap().session_ticket.clone().filter(|_| session_preserved && self.server_pins.is_empty());
+        let migrated = match ticket {
+            Some(ticket) => match self.migrate(ticket).await {
+                Ok(()) => true,
+                Err(e) => {
+                    debug!("{}", tr!("向服务器 {} 迁移会话失败，改为重新握手: {}", "Migrating the session to server {} failed, handshaking again: {}", server_addr, e));
+                    false
+                }
+            },
+            None => false,
+        };
+        let result = if migrated { Ok(()) } else { self.register().await };
+        match result {
+            Ok(()) => {
+                self.endpoint.servers.handshake_result(server_addr, true);
+                info!("{}", tr!("服务器 {} 提示重连，已改用 {}", "Server {} asked to reconnect, now using {}", previous, server_addr));
+                self.forget_server_state();
+            }
+            Err(e) => {
+                self.endpoint.servers.handshake_result(server_addr, false);
+                self.endpoint.servers.activate(previous);
+                warn!("{}", tr!("重连到服务器 {} 失败，继续使用 {}: {}", "Reconnecting to server {} failed, staying on {}: {}", server_addr, previous, e));
+            }
+        }
+    }
+
+    /// 凭会话票据把活动服务器上的会话迁移到本地址
+    async fn migrate(&self, ticket: String) -> Result<()> {
+        let request = Message::migrate_address(self.endpoint.node_id, ticket)?;
+        let reply = self.replies.request(&request, SERVER_REPLY_TIMEOUT, self.endpoint.send_to_server(&request)).await?;
+        if reply.message_type == MessageType::Error {
+            anyhow::bail!("地址迁移被拒绝: {}", reply.payload["error"].as_str().unwrap_or_default());
+        }
+        let response: MigrateAddressResponse = serde_json::from_value(reply.payload)?;
+        let mut registration = self.registration.lock().unwrap();
+        registration.public_addr = Some(response.public_addr);
+        // 新服务器没有签署过握手响应
+        registration.server_fingerprint = None;
+        Ok(())
+    }
+
+    /// 中继会话、休眠登记与路径 MTU 都属于原服务器，改用其他服务器后清除
+    fn forget_server_state(&self) {
+        for link in self.registry.lock().unwrap().values() {
+            link.relay_session.lock().unwrap().take();
+        }
+        self.dormant.lock().unwrap().take();
+        self.server_path_mtu.lock().unwrap().take();
+    }
+
+    fn frame_sender(&self) -> FrameSender {
+        FrameSender {
+            socket: self.endpoint.socket.clone(),
+            servers: self.endpoint.servers.clone(),
+            node_id: self.endpoint.node_id,
+            max_hops: ROUTED_MAX_HOPS,
+            signer: self.signer.clone(),
+        }
+    }
+
+    async fn read_loop(self: Arc<Self>) {
+        let mut buffer = vec![0u8; 65536];
+        loop {
+            let (len, from) = match self.endpoint.socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(e) => {
+                    debug!("{}", tr!("接收UDP数据包失败: {}", "Failed to receive UDP packet: {}", e));
+                    continue;
+                }
+            };
+            let server_addr = self.endpoint.server_addr();
+            if from == server_addr && buffer[..len].starts_with(&WAKE_MAGIC) {
+                let token = *self.dormant.lock().unwrap();
+                if token.is_some_and(|token| dormant::is_knock(&buffer[..len], token)) {
+                    info!("{}", tr!("收到服务器的唤醒，结束休眠", "Woken up by the server, leaving dormancy"));
+                    if let Err(e) = self.wake().await {
+                        warn!("{}", tr!("结束休眠失败: {}", "Failed to leave dormancy: {}", e));
+                    }
+                    self.woken.notify_one();
+                }
+                continue;
+            }
+            if from == server_addr
+                && let Some(frame) = RelayFrame::parse(&buffer[..len])
+            {
+                if let Some(link) = self.link(&frame.from_peer_id) {
+                    link.deliver(frame.data.to_vec());
+                }
+                continue;
+            }
+            let message: Message = match serde_json::from_slice(&buffer[..len]) {
+                Ok(message) => message,
+                Err(e) => {
+                    debug!("{}", tr!("忽略来自 {} 的无法解析的数据包: {}", "Ignoring unparseable packet from {}: {}", from, e));
+                    continue;
+                }
+            };
+            let result = if from == server_addr {
+                self.handle_server(message).await
+            } else {
+                self.handle_peer(message, from).await
+            };
+            if let Err(e) = result {
+                debug!("{}", tr!("处理来自 {} 的消息失败: {}", "Failed to handle message from {}: {}", from, e));
+            }
+        }
+    }
+
+    async fn handle_server(self: &Arc<Self>, message: Message) -> Result<()> {
+        // 等待中的请求（`request`、服务注册与远程调用）的应答
+        let Some(message) = self.replies.resolve(message) else { return Ok(()) };
+        match message.message_type {
+            MessageType::Ping => self.endpoint.send_to_server(&Message::pong_with_rtts(self.rtt_samples())).await?,
+            MessageType::Pong => self.endpoint.servers.acked(),
+            MessageType::P2PConnect => {
+                let peer_id = parse_peer_id(&message.payload, "peer_id")?;
+                let addrs = PeerAddrs::from_payload(&message.payload).context("直连协调缺少对方地址")?;
+                self.endpoint.learn(peer_id, &addrs);
+                let waiter = self.pending.lock().unwrap().remove(&peer_id);
+                if let Some(waiter) = waiter {
+                    let _ = waiter.send(addrs);
+                } else if let Some(link) = self.link(&peer_id) {
+                    link.update_addrs(addrs);
+                } else {
+                    info!("{}", tr!("节点 {} 请求与本节点直连", "Node {} requested a direct connection", peer_id));
+                    let session = P2PSession::start(
+                        self.endpoint.clone(),
+                        self.registry.clone(),
+                        self.session_config.clone(),
+                        peer_id,
+                        addrs,
+                    );
+                    let _ = self.incoming.send(session);
+                }
+            }
+            MessageType::AddressUpdate => {
+                let update: AddressUpdate = serde_json::from_value(message.payload)?;
+                if let Some(link) = self.link(&update.peer_id) {
+                    info!("{}", tr!("节点 {} 的地址变更为 {}，重新打洞", "Address of node {} changed to {}, punching again", update.peer_id, update.peer_addr));
+                    link.update_addrs(PeerAddrs::from_update(&update));
+                }
+            }
+            MessageType::PunchBeacon => {
+                let beacon: PunchBeacon = serde_json::from_value(message.payload)?;
+                if let Some(link) = self.link(&beacon.peer_id) {
+                    debug!("{}", tr!("与节点 {} 的打洞倒计时: {} ms", "Punch countdown with node {}: {} ms", beacon.peer_id, beacon.go_in_ms));
+                    link.beacon(beacon.go_in_ms);
+                }
+            }
+            MessageType::PeerDown => {
+                let peer_id = parse_peer_id(&message.payload, "peer_id")?;
+                if let Some(link) = self.link(&peer_id) {
+                    info!("{}", tr!("节点 {} 已下线，关闭会话", "Node {} went offline, closing session", peer_id));
+                    link.close();
+                }
+            }
+            MessageType::RelayData => {
+                let peer_id = parse_peer_id(&message.payload, "from_peer_id")?;
+                let data = parse_relay_data(&message.payload).map_err(anyhow::Error::msg)?.unwrap_or_default();
+                if let Some(link) = self.link(&peer_id) {
+                    link.deliver(data);
+                }
+            }
+            MessageType::RelayResponse => {
+                let response: RelayResponse = serde_json::from_value(message.payload)?;
+                if response.success {
+                    if let (Some(session_id), Some(link)) = (response.session_id, response.target_peer_id.and_then(|id| self.link(&id))) {
+                        *link.relay_session.lock().unwrap() = Some(session_id);
+                    }
+                } else {
+                    warn!("{}", tr!("服务器拒绝中继: {}", "Server refused relay: {}", response.error_message.unwrap_or_default()));
+                    // 会话已失效，下次发送时重新建立
+                    if let Some(session_id) = response.session_id {
+                        self.forget_relay_session(session_id);
+                    }
+                }
+            }
+            MessageType::RelayClose => {
+                let closed: RelayClose = serde_json::from_value(message.payload)?;
+                debug!("{}", tr!("中继会话 {} 已关闭: {:?}", "Relay session {} closed: {:?}", closed.session_id, closed.reason));
+                self.forget_relay_session(closed.session_id);
+            }
+            MessageType::Data => match RoutedMessage::from_message(&message) {
+                Ok(routed) if routed.destination_node == self.endpoint.node_id => self.handle_routed(routed, message).await?,
+                _ => {
+                    let _ = self.data.send(message);
+                }
+            },
+            MessageType::RpcCall => {
+                let call: RpcCall = serde_json::from_value(message.payload)?;
+                let _ = self.calls.send(IncomingCall { id: message.id, from: call.from, service: call.service, args: call.args });
+            }
+            MessageType::Presence => {
+                let _ = self.presence.send(serde_json::from_value(message.payload)?);
+            }
+            MessageType::Disconnect => {
+                let notice: DisconnectNotice = serde_json::from_value(message.payload)?;
+                warn!("{}", tr!("服务器断开了本节点: {:?} {}", "Server disconnected this node: {:?} {}", notice.code, notice.reason));
+                let _ = self.disconnects.send(notice);
+            }
+            MessageType::KeyRotation => {
+                let rotation: KeyRotation = serde_json::from_value(message.payload)?;
+                info!("{}", tr!("节点 {} 轮换了密钥", "Node {} rotated its key", rotation.node_id));
+                let _ = self.key_rotations.send(rotation);
+            }
+            MessageType::BandwidthProbe => {
+                let probe: BandwidthProbe = serde_json::from_value(message.payload)?;
+                match probe.role {
+                    ProbeRole::Receive => self.expect_probe(probe),
+                    ProbeRole::Send => self.send_probe(probe),
+                }
+            }
+            MessageType::MtuProbe => {
+                let probe: MtuProbe = serde_json::from_value(message.payload.clone())?;
+                if probe.done {
+                    let path_mtu = probe.path_mtu.map(|mtu| PathMtu { mtu, limited: probe.limited });
+                    info!("{}", tr!("服务器探测出的路径 MTU: {:?}", "Path MTU probed by server: {:?}", path_mtu));
+                    *self.server_path_mtu.lock().unwrap() = path_mtu;
+                } else {
+                    let reply = message.respond_as(MessageType::MtuProbe, serde_json::json!({ "size": probe.size }));
+                    self.endpoint.send_to_server(&reply).await?;
+                }
+            }
+            MessageType::Reconnect => {
+                let server_addr: SocketAddr = message.payload["server_addr"]
+                    .as_str()
+                    .and_then(|addr| addr.parse().ok())
+                    .context("重连提示缺少服务器地址")?;
+                let session_preserved = message.payload["session_preserved"].as_bool().unwrap_or(false);
+                info!("{}", tr!("服务器提示改连 {}: {}", "Server asked to reconnect to {}: {}", server_addr, message.payload["reason"]));
+                // 迁移与握手要等待服务器应答，不能阻塞读循环
+                let shared = self.clone();
+                tokio::spawn(async move { shared.reconnect(server_addr, session_preserved).await });
+            }
+            MessageType::Error => warn!("{}", tr!("服务器返回错误: {}", "Server returned an error: {}", message.payload["error"])),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 发给本节点的路由消息：回执交给等待中的发送方，要求回执的消息先发回回执
+    async fn handle_routed(&self, routed: RoutedMessage, message: Message) -> Result<()> {
+        if let Err(e) = identity::check_routed(&routed, self.route_signatures, self.signer.network_id()) {
+            warn!("{}", tr!("丢弃路由消息 {}（来自 {}）: {}", "Dropping routed message {} from {}: {}", routed.route_id, routed.source_node, e));
+            return Ok(());
+        }
+        match routed.original_message.message_type {
+            MessageType::Receipt => {
+                if self.replies.resolve(routed.original_message).is_some() {
+                    debug!("{}", tr!("收到无人等待的送达回执（可能已超时）", "Received a delivery receipt nobody is waiting for (may have timed out)"));
+                }
+                return Ok(());
+            }
+            MessageType::Stream => {
+                let frame = serde_json::from_value(routed.original_message.payload)?;
+                let sender = self.frame_sender();
+                stream::dispatch(&sender, &self.streams, &self.stream_config(), &self.incoming_streams, routed.source_node, frame).await;
+                return Ok(());
+            }
+            _ => {}
+        }
+        if routed.receipt_requested {
+            let mut receipt = routed.receipt()?;
+            self.signer.sign(&mut receipt);
+            self.endpoint.send_to_server(&receipt.to_message()?).await?;
+        }
+        let _ = self.data.send(message);
+        Ok(())
+    }
+
+    /// 准备接收探测包，所有包到齐或等待超时后上报
+    fn expect_probe(&self, probe: BandwidthProbe) {
+        let train = ProbeTrain {
+            sender: probe.peer_id,
+            packets: probe.packets,
+            packet_size: probe.packet_size,
+            arrivals: Vec::with_capacity(probe.packets as usize),
+        };
+        self.probes.lock().unwrap().insert(probe.probe_id, train);
+        let endpoint = self.endpoint.clone();
+        let probes = self.probes.clone();
+        tokio::spawn(async move {
+            sleep(PROBE_RECEIVE_WAIT).await;
+            finish_probe(&endpoint, &probes, probe.probe_id).await;
+        });
+    }
+
+    /// 沿已建立的直连路径向对方发出探测包
+    fn send_probe(&self, probe: BandwidthProbe) {
+        let Some(SessionState::Direct(addr)) = self.link(&probe.peer_id).map(|link| link.state()) else {
+            warn!("{}", tr!("与节点 {} 尚未直连，无法发送带宽探测包", "No direct connection to node {} yet, cannot send bandwidth probes", probe.peer_id));
+            return;
+        };
+        let endpoint = self.endpoint.clone();
+        tokio::spawn(async move { send_probe_train(&endpoint, addr, &probe).await });
+    }
+
+    /// 记录探测包的到达时间
+    async fn record_probe(&self, peer_id: Uuid, payload: &serde_json::Value) -> Result<()> {
+        let arrived = Instant::now();
+        let probe_id = parse_peer_id(payload, "probe_id")?;
+        let complete = match self.probes.lock().unwrap().get_mut(&probe_id) {
+            Some(train) if train.sender == peer_id => {
+                train.arrivals.push(arrived);
+                train.arrivals.len() >= train.packets as usize
+            }
+            _ => false,
+        };
+        if complete {
+            finish_probe(&self.endpoint, &self.probes, probe_id).await;
+        }
+        Ok(())
+    }
+
+    async fn handle_peer(&self, message: Message, from: SocketAddr) -> Result<()> {
+        let peer_id = parse_peer_id(&message.payload, "node_id")?;
+        if message.message_type == MessageType::BandwidthProbe {
+            if let Some(link) = self.link(&peer_id) {
+                link.heard_from(from);
+            }
+            return self.record_probe(peer_id, &message.payload).await;
+        }
+        // 对方可能先于服务器的直连协调到达，无论会话是否存在都应答探测
+        if message.message_type == MessageType::Ping {
+            self.endpoint.send(&self.endpoint.direct(MessageType::Pong, None), from).await?;
+        }
+        // 路径 MTU 探测包以不带填充的小包应答，只有应答路径与探测路径相同时结果才准确
+        if message.message_type == MessageType::MtuProbe && message.reply_to.is_none() {
+            let mut reply = message.respond_as(MessageType::MtuProbe, self.endpoint.direct(MessageType::MtuProbe, None).payload);
+            reply.payload["size"] = message.payload["size"].clone();
+            self.endpoint.send(&reply, from).await?;
+        }
+        let Some(link) = self.link(&peer_id) else { return Ok(()) };
+        match message.message_type {
+            MessageType::Ping => link.heard_from(from),
+            MessageType::Pong => {
+                link.heard_from(from);
+                link.pong_received();
+            }
+            MessageType::Data => {
+                link.heard_from(from);
+                link.deliver(serde_json::from_value(message.payload["data"].clone())?);
+            }
+            MessageType::MtuProbe => {
+                link.heard_from(from);
+                if message.reply_to.is_some() && link.probes.resolve(message).is_some() {
+                    debug!("{}", tr!("收到无人等待的 MTU 探测应答（可能已超时）", "Received an MTU probe reply nobody is waiting for (may have timed out)"));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// 握手请求：配置了身份密钥时附带密钥证明
+fn handshake_request(identity: Option<&NodeIdentity>, node_info: NodeInfo) -> Result<Message> {
+    Ok(match identity {
+        Some(identity) => identity.handshake_request(node_info)?,
+        None => Message::handshake_request(node_info)?,
+    })
+}
+
+/// 带上服务器下发的 cookie 重发的握手请求；密钥证明覆盖 cookie，配置了身份密钥时重新签名
+fn with_cookie(identity: Option<&NodeIdentity>, request: &Message, cookie: String) -> Message {
+    let mut echoed = request.clone();
+    echoed.payload["cookie"] = cookie.into();
+    if let Some(identity) = identity {
+        identity.sign_handshake_request(&mut echoed);
+    }
+    echoed
+}
+
+/// 校验握手响应上的服务器签名：未固定指纹时签名只作记录，固定后必须有效且指纹在列表中
+fn verify_response(message: &Message, request_id: Uuid, network_id: &str, pins: &[String]) -> Result<Option<String>, IdentityError> {
+    if pins.is_empty() {
+        Ok(identity::verify_handshake_response(message, request_id, network_id).ok())
+    } else {
+        identity::verify_pinned(message, request_id, network_id, pins).map(Some)
+    }
+}
+
+/// 向 `server_addr` 握手（最多尝试 [`HANDSHAKE_ATTEMPTS`] 次），返回被接受的响应与服务器公钥指纹
+async fn handshake(
+    socket: &UdpSocket,
+    server_addr: SocketAddr,
+    request: &Message,
+    identity: Option<&NodeIdentity>,
+    config: &ClientConfig,
+) -> Result<(HandshakeResponse, Option<String>)> {
+    let request_id = request.id;
+    let mut packet = serde_json::to_vec(request)?;
+    let mut buffer = vec![0u8; 65536];
+    let mut response = None;
+    let mut identity_error = None;
+    'attempts: for attempt in 1..=HANDSHAKE_ATTEMPTS {
+        socket.send_to(&packet, server_addr).await?;
+        let deadline = tokio::time::Instant::now() + SERVER_REPLY_TIMEOUT;
+        while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await {
+            let (len, from) = received?;
+            if from != server_addr {
+                continue;
+            }
+            let Ok(message) = serde_json::from_slice::<Message>(&buffer[..len]) else { continue };
+            // 服务器在校验握手请求（网络ID、节点身份）失败时以 `Error` 应答
+            if message.message_type == MessageType::Error {
+                anyhow::bail!("握手被拒绝: {}", message.payload["error"].as_str().unwrap_or_default());
+            }
+            // cookie 质询不带签名：伪造的质询至多让客户端多发一次请求
+            if let Some(cookie) = HandshakeProtocol::cookie_challenge(&message) {
+                debug!("{}", tr!("服务器 {} 要求握手 cookie，带上后重发握手请求", "Server {} requires a handshake cookie, resending the handshake with it", server_addr));
+                packet = serde_json::to_vec(&with_cookie(identity, request, cookie))?;
+                socket.send_to(&packet, server_addr).await?;
+                continue;
+            }
+            if message.message_type == MessageType::HandshakeResponse {
+                // 未固定指纹时签名只作记录；固定后伪造的响应被忽略，继续等待真正的服务器
+                match verify_response(&message, request_id, &config.network_id, &config.server_pins) {
+                    Ok(fingerprint) => {
+                        let validated = HandshakeProtocol::validate_handshake_response(&message).map_err(anyhow::Error::msg)?;
+                        response = Some((validated, fingerprint));
+                        break 'attempts;
+                    }
+                    Err(e) => {
+                        warn!("{}", tr!("忽略未通过身份校验的握手响应: {}", "Ignoring handshake response that failed identity verification: {}", e));
+                        identity_error = Some(e);
+                    }
+                }
+            }
+        }
+        debug!("{}", tr!("第 {} 次握手未收到响应", "Handshake attempt {} got no response", attempt));
+    }
+    let (response, server_fingerprint) = match (response, identity_error) {
+        (Some(response), _) => response,
+        (None, Some(e)) => anyhow::bail!("服务器 {} 身份校验失败: {}", server_addr, e),
+        (None, None) => anyhow::bail!("服务器 {} 未响应握手", server_addr),
+    };
+    if !response.success {
+        anyhow::bail!("握手被拒绝: {}", response.error_message.unwrap_or_default());
+    }
+
+    Ok((response, server_fingerprint))
+}
+
+fn parse_peer_id(payload: &serde_json::Value, key: &str) -> Result<Uuid> {
+    let id = payload.get(key).and_then(|v| v.as_str()).context(format!("消息缺少 {}", key))?;
+    Ok(Uuid::parse_str(id)?)
+}
+
+/// 转发给本节点的远程调用，用 [`P2PClient::reply_call`] 返回结果
+#[derive(Debug, Clone)]
+pub struct IncomingCall {
+    pub id: Uuid,
+    /// 调用方节点ID
+    pub from: Option<Uuid>,
+    pub service: String,
+    pub args: serde_json::Value,
+}
+
+/// 嵌入式 P2P 客户端
+///
+/// 完成与服务器的握手并维持心跳，通过 [`open_session`](Self::open_session) 主动发起、
+/// [`accept`](Self::accept) 接受对方发起的托管会话。
+pub struct P2PClient {
+    shared: Arc<Shared>,
+    incoming: mpsc::UnboundedReceiver<P2PSession>,
+    data: mpsc::UnboundedReceiver<Message>,
+    calls: mpsc::UnboundedReceiver<IncomingCall>,
+    presence: mpsc::UnboundedReceiver<PresenceUpdate>,
+    disconnects: mpsc::UnboundedReceiver<DisconnectNotice>,
+    key_rotations: mpsc::UnboundedReceiver<KeyRotation>,
+    incoming_streams: mpsc::UnboundedReceiver<P2PStream>,
+    stored_messages: usize,
+    /// 节点身份密钥及其文件
+    identity: Option<(Arc<NodeIdentity>, PathBuf)>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl P2PClient {
+    /// 绑定本地套接字并与服务器握手
+    pub async fn connect(config: ClientConfig) -> Result<Self> {
+        let socket = UdpSocket::bind(config.bind_addr).await
+            .context(format!("绑定UDP地址 {} 失败", config.bind_addr))?;
+        let mut node_info = NodeInfo::new(config.name.clone(), socket.local_addr()?, config.network_id.clone());
+        node_info.add_capability(RELAY_FRAME_CAPABILITY);
+        let address_book = match &config.address_book {
+            Some(path) => Some(Arc::new(AddressBook::load(path)?)),
+            None => None,
+        };
+        let identity = match &config.identity_key_file {
+            Some(path) => Some((Arc::new(NodeIdentity::load_or_create(path)?), path.clone())),
+            None => None,
+        };
+        if let Some((identity, _)) = &identity {
+            node_info.id = identity.node_id();
+        }
+        let node_id = node_info.id;
+        let request = handshake_request(identity.as_ref().map(|(identity, _)| identity.as_ref()), node_info.clone())?;
+        // 依次尝试主服务器与备用服务器，第一个握手成功的成为活动服务器
+        let servers = ServerPool::new(config.server_addr, &config.backup_servers);
+        let mut registered = None;
+        let mut last_error = None;
+        for server_addr in servers.addrs() {
+            match handshake(&socket, server_addr, &request, identity.as_ref().map(|(identity, _)| identity.as_ref()), &config).await {
+                Ok(result) => {
+                    servers.handshake_result(server_addr, true);
+                    servers.activate(server_addr);
+                    registered = Some((server_addr, result));
+                    break;
+                }
+                Err(e) => {
+                    servers.handshake_result(server_addr, false);
+                    if !config.backup_servers.is_empty() {
+                        warn!("{}", tr!("与服务器 {} 握手失败: {}", "Handshake with server {} failed: {}", server_addr, e));
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+        let Some((server_addr, (response, server_fingerprint))) = registered else {
+            return Err(last_error.unwrap_or_else(|| anyhow::anyhow!("没有可用的握手服务器")));
+        };
+        info!("{}", tr!("已与服务器 {} 握手，本节点ID {}", "Handshake with server {} complete, local node ID {}", server_addr, node_id));
+
+        let (incoming_tx, incoming) = mpsc::unbounded_channel();
+        let (data_tx, data) = mpsc::unbounded_channel();
+        let (calls_tx, calls) = mpsc::unbounded_channel();
+        let (presence_tx, presence) = mpsc::unbounded_channel();
+        let (disconnects_tx, disconnects) = mpsc::unbounded_channel();
+        let (key_rotations_tx, key_rotations) = mpsc::unbounded_channel();
+        let (streams_tx, incoming_streams) = mpsc::unbounded_channel();
+        let shared = Arc::new(Shared {
+            endpoint: Endpoint { socket: Arc::new(socket), servers: Arc::new(servers), node_id, address_book },
+            session_config: config.session,
+            registry: Registry::default(),
+            pending: Mutex::new(HashMap::new()),
+            incoming: incoming_tx,
+            replies: PendingReplies::new(),
+            data: data_tx,
+            calls: calls_tx,
+            presence: presence_tx,
+            disconnects: disconnects_tx,
+            key_rotations: key_rotations_tx,
+            stream_config: config.stream,
+            streams: StreamRegistry::default(),
+            incoming_streams: streams_tx,
+            probes: ProbeTrains::default(),
+            server_path_mtu: Mutex::new(None),
+            dormant: Mutex::new(None),
+            woken: Notify::new(),
+            signer: RouteSigner::new(identity.as_ref().map(|(identity, _)| identity.clone()), &config.network_id),
+            route_signatures: config.route_signatures,
+            node_info,
+            server_pins: config.server_pins,
+            registration: Mutex::new(Registration::new(&response, server_fingerprint)),
+        });
+        let reader = tokio::spawn(shared.clone().read_loop());
+        let keepalive_shared = shared.clone();
+        let heartbeat = Duration::from_secs(config.server_keepalive_secs.max(1));
+        let failover_after = if config.backup_servers.is_empty() { 0 } else { config.failover_after_missed };
+        let keepalive = tokio::spawn(async move {
+            loop {
+                sleep(heartbeat).await;
+                // 休眠期间不发心跳，由服务器在需要时唤醒
+                if keepalive_shared.dormant.lock().unwrap().is_some() {
+                    continue;
+                }
+                let missed = keepalive_shared.endpoint.servers.heartbeat();
+                if failover_after > 0 && missed >= failover_after {
+                    keepalive_shared.fail_over().await;
+                    continue;
+                }
+                if let Err(e) = keepalive_shared.endpoint.send_to_server(&Message::ping()).await {
+                    warn!("{}", tr!("向服务器发送心跳失败: {}", "Failed to send heartbeat to server: {}", e));
+                }
+            }
+        });
+
+        Ok(Self {
+            shared,
+            incoming,
+            data,
+            calls,
+            presence,
+            disconnects,
+            key_rotations,
+            incoming_streams,
+            stored_messages: response.stored_messages.unwrap_or(0),
+            identity,
+            tasks: vec![reader, keepalive],
+        })
+    }
+
+    /// 本节点ID
+    pub fn node_id(&self) -> Uuid {
+        self.shared.endpoint.node_id
+    }
+
+    /// 本地绑定地址
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.shared.endpoint.socket.local_addr()?)
+    }
+
+    /// 服务器看到的本节点地址
+    pub fn public_addr(&self) -> Option<SocketAddr> {
+        self.shared.registration.lock().unwrap().public_addr
+    }
+
+    /// 握手时下发的会话票据，切换网络后凭此迁移会话
+    pub fn session_ticket(&self) -> Option<String> {
+        self.shared.registration.lock().unwrap().session_ticket.clone()
+    }
+
+    /// 握手响应上签名有效的服务器公钥指纹，服务器未签名时为 `None`
+    pub fn server_fingerprint(&self) -> Option<String> {
+        self.shared.registration.lock().unwrap().server_fingerprint.clone()
+    }
+
+    /// 当前使用的握手服务器
+    pub fn active_server(&self) -> SocketAddr {
+        self.shared.endpoint.server_addr()
+    }
+
+    /// 各握手服务器的健康状况，主服务器在前
+    pub fn server_health(&self) -> Vec<ServerHealth> {
+        self.shared.endpoint.servers.health()
+    }
+
+    /// 本节点身份公钥的指纹，未配置 `identity_key_file` 时为 `None`
+    pub fn node_fingerprint(&self) -> Option<String> {
+        self.identity.as_ref().map(|(identity, _)| identity.fingerprint())
+    }
+
+    /// 轮换本节点的身份密钥：用旧密钥签署新公钥，服务器确认后写回密钥文件，返回新指纹
+    ///
+    /// 服务器随后只接受新密钥的握手，并把轮换转发给其他节点。
+    pub async fn rotate_key(&mut self) -> Result<String> {
+        let (identity, path) = self.identity.as_ref().context("未配置 identity_key_file，本节点没有身份密钥")?;
+        let (next, rotation) = identity.rotate(self.shared.signer.network_id());
+        let path = path.clone();
+        self.exchange(Message::key_rotation(&rotation)?, SERVER_REPLY_TIMEOUT).await?;
+        next.save(&path)?;
+        let fingerprint = next.fingerprint();
+        info!("{}", tr!("本节点密钥已轮换，新指纹 {}", "Node key rotated, new fingerprint {}", fingerprint));
+        let next = Arc::new(next);
+        self.shared.signer.replace(next.clone());
+        self.identity = Some((next, path));
+        Ok(fingerprint)
+    }
+
+    /// 握手时服务器告知的离线消息数，这些消息随后通过 [`recv_data`](Self::recv_data) 收取
+    pub fn stored_messages(&self) -> usize {
+        self.stored_messages
+    }
+
+    /// 地址簿中的节点，最近更新的在前；未配置 `address_book` 时为空
+    pub fn known_peers(&self) -> Vec<KnownPeer> {
+        self.shared.endpoint.address_book.as_ref().map(|book| book.peers()).unwrap_or_default()
+    }
+
+    /// 请求服务器协调与 `peer_id` 直连，并返回托管会话
+    ///
+    /// 地址簿中有该节点直连成功的记录时立即按记录打洞并返回会话，服务器的直连协调随后到达，
+    /// 届时尚未直连则改用服务器给出的地址。
+    pub async fn open_session(&self, peer_id: Uuid) -> Result<P2PSession> {
+        let (tx, rx) = oneshot::channel();
+        self.shared.pending.lock().unwrap().insert(peer_id, tx);
+        let known = self.shared.endpoint.address_book.as_ref().and_then(|book| book.get(&peer_id));
+        if let Some(addrs) = known.as_ref().and_then(PeerAddrs::from_known) {
+            debug!("{}", tr!("按地址簿向节点 {} 预先打洞: {:?}", "Pre-punching node {} from the address book: {:?}", peer_id, addrs.candidates));
+            if let Err(e) = self.shared.endpoint.send_to_server(&Message::initiate_p2p(peer_id)).await {
+                self.shared.pending.lock().unwrap().remove(&peer_id);
+                return Err(e);
+            }
+            let session = P2PSession::start(
+                self.shared.endpoint.clone(),
+                self.shared.registry.clone(),
+                self.shared.session_config.clone(),
+                peer_id,
+                addrs,
+            );
+            let shared = self.shared.clone();
+            let link = session.link.clone();
+            tokio::spawn(async move {
+                match timeout(SERVER_REPLY_TIMEOUT, rx).await {
+                    Ok(Ok(addrs)) => link.coordinated(addrs),
+                    _ => {
+                        shared.pending.lock().unwrap().remove(&peer_id);
+                        debug!("{}", tr!("服务器未协调与节点 {} 的直连，继续按地址簿打洞", "Server did not coordinate a direct connection with node {}, continuing to punch from the address book", peer_id));
+                    }
+                }
+            });
+            return Ok(session);
+        }
+        let result = async {
+            self.shared.endpoint.send_to_server(&Message::initiate_p2p(peer_id)).await?;
+            timeout(SERVER_REPLY_TIMEOUT, rx).await
+                .ok()
+                .and_then(Result::ok)
+                .context(format!("服务器未协调与节点 {} 的直连", peer_id))
+        }.await;
+        let addrs = match result {
+            Ok(addrs) => addrs,
+            Err(e) => {
+                self.shared.pending.lock().unwrap().remove(&peer_id);
+                return Err(e);
+            }
+        };
+        Ok(P2PSession::start(
+            self.shared.endpoint.clone(),
+            self.shared.registry.clone(),
+            self.shared.session_config.clone(),
+            peer_id,
+            addrs,
+        ))
+    }
+
+    /// 向服务器发送 `Data` 请求，等待 `reply_to` 指向它的应答直到 `wait` 超时
+    pub async fn request(&self, payload: serde_json::Value, wait: Duration) -> Result<Message> {
+        self.exchange(Message::data(payload), wait).await
+    }
+
+    /// 发送请求并等待应答，服务器以 `Error` 应答时返回错误
+    async fn exchange(&self, request: Message, wait: Duration) -> Result<Message> {
+        let reply = self.shared.replies.request(&request, wait, self.shared.endpoint.send_to_server(&request)).await?;
+        if reply.message_type == MessageType::Error {
+            anyhow::bail!("服务器返回错误: {}", reply.payload["error"]);
+        }
+        Ok(reply)
+    }
+
+    /// 应答服务器发来的请求
+    pub async fn respond(&self, request: &Message, payload: serde_json::Value) -> Result<()> {
+        self.shared.endpoint.send_to_server(&request.respond(payload)).await
+    }
+
+    /// 接收服务器发来的其他 `Data` 消息（包括服务器经 `Requester` 发起的请求）
+    pub async fn recv_data(&mut self) -> Option<Message> {
+        self.data.recv().await
+    }
+
+    /// 请求服务器协调一次本节点到 `peer_id` 的带宽探测（须已与对方直连），返回估算的带宽（bit/s）
+    pub async fn probe_bandwidth(&self, peer_id: Uuid, wait: Duration) -> Result<u64> {
+        let reply = self.exchange(Message::bandwidth_probe_request(peer_id), wait).await?;
+        let report: BandwidthReport = serde_json::from_value(reply.payload)?;
+        report.bandwidth_bps.context("服务器未返回带宽估计")
+    }
+
+    /// 查询服务器上的其他节点，按到本节点的往返时延从近到远排序，最多返回 `limit` 个
+    ///
+    /// 时延来自各节点随心跳上报的直连测量，尚未测量的节点排在最后。
+    pub async fn discover_nearest(&self, limit: usize) -> Result<Vec<PeerInfo>> {
+        let reply = self.exchange(Message::discovery_request_by_latency(Some(limit)), SERVER_REPLY_TIMEOUT).await?;
+        Ok(serde_json::from_value(reply.payload)?)
+    }
+
+    /// 请求服务器诊断本节点的连接：观测地址、NAT 分类、最近心跳的时延与丢失率、中继与频率限制状况
+    pub async fn diagnose(&self) -> Result<DiagnoseReport> {
+        let reply = self.exchange(Message::diagnose(), SERVER_REPLY_TIMEOUT).await?;
+        Ok(serde_json::from_value(reply.payload)?)
+    }
+
+    /// 经服务器路由向节点发送数据，对方以 [`recv_data`](Self::recv_data) 收取
+    pub async fn route(&self, destination: Uuid, payload: serde_json::Value) -> Result<()> {
+        let mut routed = RoutedMessage::new(Message::data(payload), self.node_id(), destination, ROUTED_MAX_HOPS);
+        self.shared.signer.sign(&mut routed);
+        self.shared.endpoint.send_to_server(&routed.to_message()?).await
+    }
+
+    /// 经服务器多播数据到一组节点：只向服务器发出一份，沿途按路由表分叉，每个目标以 [`recv_data`](Self::recv_data) 收取
+    pub async fn multicast(&self, members: Vec<Uuid>, payload: serde_json::Value) -> Result<()> {
+        let mut routed = RoutedMessage::multicast(Message::data(payload), self.node_id(), members, ROUTED_MAX_HOPS);
+        self.shared.signer.sign(&mut routed);
+        self.shared.endpoint.send_to_server(&routed.to_message()?).await
+    }
+
+    /// 经服务器路由发送数据，并等待目标节点发回的送达回执
+    ///
+    /// 回执只说明目标节点收到了消息；`wait` 内未收到回执时返回错误，消息可能仍在途中、被服务器暂存或已丢失。
+    pub async fn route_with_receipt(
+        &self,
+        destination: Uuid,
+        payload: serde_json::Value,
+        wait: Duration,
+    ) -> Result<DeliveryReceipt> {
+        let mut routed = RoutedMessage::new(Message::data(payload), self.node_id(), destination, ROUTED_MAX_HOPS).with_receipt();
+        self.shared.signer.sign(&mut routed);
+        let request = routed.original_message.clone();
+        let reply = self.shared.replies.request(&request, wait, self.shared.endpoint.send_to_server(&routed.to_message()?)).await?;
+        if reply.message_type == MessageType::Error {
+            anyhow::bail!("服务器拒绝转发: {}", reply.payload["error"]);
+        }
+        Ok(serde_json::from_value(reply.payload)?)
+    }
+
+    /// 在服务目录中注册本节点提供的服务
+    pub async fn register_service(&self, service: &str) -> Result<()> {
+        self.exchange(Message::register_services(vec![service.to_string()])?, SERVER_REPLY_TIMEOUT).await?;
+        Ok(())
+    }
+
+    /// 注销本节点提供的服务
+    pub async fn unregister_service(&self, service: &str) -> Result<()> {
+        self.exchange(Message::unregister_services(vec![service.to_string()])?, SERVER_REPLY_TIMEOUT).await?;
+        Ok(())
+    }
+
+    /// 查询服务的提供者
+    pub async fn lookup_service(&self, service: &str) -> Result<Vec<Uuid>> {
+        let reply = self.exchange(Message::service_lookup(service), SERVER_REPLY_TIMEOUT).await?;
+        let response: ServiceLookupResponse = serde_json::from_value(reply.payload)?;
+        Ok(response.providers)
+    }
+
+    /// 调用服务，由服务器选择提供者；`wait` 内未返回结果时报错
+    pub async fn call(&self, service: &str, args: serde_json::Value, wait: Duration) -> Result<serde_json::Value> {
+        self.call_target(None, service, args, wait).await
+    }
+
+    /// 调用指定节点提供的服务
+    pub async fn call_peer(&self, peer_id: Uuid, service: &str, args: serde_json::Value, wait: Duration) -> Result<serde_json::Value> {
+        self.call_target(Some(peer_id), service, args, wait).await
+    }
+
+    async fn call_target(&self, target: Option<Uuid>, service: &str, args: serde_json::Value, wait: Duration) -> Result<serde_json::Value> {
+        let call = RpcCall {
+            service: service.to_string(),
+            target,
+            from: None,
+            args,
+            timeout_ms: Some(wait.as_millis() as u64),
+        };
+        let reply = self.exchange(Message::rpc_call(&call)?, wait).await?;
+        let result: RpcResult = serde_json::from_value(reply.payload)?;
+        match result.error {
+            Some(error) => anyhow::bail!("远程调用 {} 失败: {}", service, error),
+            None => Ok(result.result),
+        }
+    }
+
+    /// 接收转发给本节点的远程调用
+    pub async fn recv_call(&mut self) -> Option<IncomingCall> {
+        self.calls.recv().await
+    }
+
+    /// 返回远程调用的结果
+    pub async fn reply_call(&self, call: &IncomingCall, result: Result<serde_json::Value, String>) -> Result<()> {
+        self.shared.endpoint.send_to_server(&Message::rpc_result(call.id, result)?).await
+    }
+
+    /// 关注一组节点的在线状态，返回其中当前在线的节点；之后的变化通过 [`recv_presence`](Self::recv_presence) 接收
+    pub async fn watch(&self, peer_ids: Vec<Uuid>) -> Result<Vec<Uuid>> {
+        let reply = self.exchange(Message::watch(peer_ids)?, SERVER_REPLY_TIMEOUT).await?;
+        let response: WatchResponse = serde_json::from_value(reply.payload)?;
+        Ok(response.online)
+    }
+
+    /// 取消关注一组节点
+    pub async fn unwatch(&self, peer_ids: Vec<Uuid>) -> Result<()> {
+        self.exchange(Message::unwatch(peer_ids)?, SERVER_REPLY_TIMEOUT).await?;
+        Ok(())
+    }
+
+    /// 接收所关注节点的上线/下线通知
+    pub async fn recv_presence(&mut self) -> Option<PresenceUpdate> {
+        self.presence.recv().await
+    }
+
+    /// 接收服务器的断开通知，可按 [`DisconnectReason::should_retry`](crate::protocol::DisconnectReason::should_retry) 决定是否重连
+    pub async fn recv_disconnect(&mut self) -> Option<DisconnectNotice> {
+        self.disconnects.recv().await
+    }
+
+    /// 接收服务器转发的其他节点的密钥轮换（已经服务器按登记的密钥链校验）
+    pub async fn recv_key_rotation(&mut self) -> Option<KeyRotation> {
+        self.key_rotations.recv().await
+    }
+
+    /// 等待其他节点发起的会话
+    pub async fn accept(&mut self) -> Option<P2PSession> {
+        self.incoming.recv().await
+    }
+
+    /// 登记休眠，返回服务器允许的最长休眠时长
+    ///
+    /// 休眠期间本客户端不再发心跳，服务器暂存发给本节点的消息；有节点要联系本节点时，服务器发来唤醒数据包，
+    /// 客户端随即结束休眠，暂存的消息照常从各接收接口到达。直连会话的保活不受影响，休眠前应先关闭。
+    pub async fn go_dormant(&self) -> Result<Duration> {
+        let token = rand::random::<u64>();
+        let reply = self.exchange(Message::dormant(token), SERVER_REPLY_TIMEOUT).await?;
+        *self.shared.dormant.lock().unwrap() = Some(token);
+        info!("{}", tr!("本节点进入休眠", "This node is now dormant"));
+        Ok(Duration::from_secs(reply.payload["max_dormant_secs"].as_u64().unwrap_or(0)))
+    }
+
+    /// 主动结束休眠
+    pub async fn wake(&self) -> Result<()> {
+        self.shared.wake().await
+    }
+
+    /// 是否处于休眠
+    pub fn is_dormant(&self) -> bool {
+        self.shared.dormant.lock().unwrap().is_some()
+    }
+
+    /// 等待服务器的唤醒（客户端收到唤醒数据包后已自动结束休眠）
+    pub async fn wait_for_wake(&self) {
+        self.shared.woken.notified().await
+    }
+
+    /// 服务器探测出的本节点路径 MTU（服务器开启 `path_mtu` 且探测结束后才有）
+    pub fn path_mtu(&self) -> Option<PathMtu> {
+        *self.shared.server_path_mtu.lock().unwrap()
+    }
+
+    /// 打开到 `peer_id` 的可靠字节流（经服务器路由），对方确认后返回
+    ///
+    /// 服务器探测出路径 MTU 上限时，每帧的数据量收紧到编码后不超过该 MTU。
+    pub async fn open_stream(&self, peer_id: Uuid) -> Result<P2PStream> {
+        P2PStream::open(self.shared.frame_sender(), self.shared.streams.clone(), self.shared.stream_config(), peer_id).await
+    }
+
+    /// 等待其他节点打开的流
+    pub async fn accept_stream(&mut self) -> Option<P2PStream> {
+        self.incoming_streams.recv().await
+    }
+}
+
+impl Drop for P2PClient {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        for link in self.shared.registry.lock().unwrap().values() {
+            link.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_addrs_candidate_order() {
+        let addr = |s: &str| -> SocketAddr { s.parse().unwrap() };
+
+        // 无内网直连信息时先试公网地址，再试对方通告的地址（去重）
+        let payload = serde_json::json!({
+            "peer_id": Uuid::new_v4().to_string(),
+            "peer_addr": "203.0.113.5:4000",
+            "peer_addresses": ["192.168.1.5:4000", "203.0.113.5:4000"],
+        });
+        let addrs = PeerAddrs::from_payload(&payload).unwrap();
+        assert_eq!(addrs.observed, addr("203.0.113.5:4000"));
+        assert_eq!(addrs.candidates, vec![addr("203.0.113.5:4000"), addr("192.168.1.5:4000")]);
+
+        // 服务器给出的候选顺序优先
+        let payload = serde_json::json!({
+            "peer_addr": "203.0.113.5:4000",
+            "candidates": ["192.168.1.5:4000", "203.0.113.5:4000"],
+        });
+        let addrs = PeerAddrs::from_payload(&payload).unwrap();
+        assert_eq!(addrs.candidates, vec![addr("192.168.1.5:4000"), addr("203.0.113.5:4000")]);
+    }
+}
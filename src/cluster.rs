@@ -0,0 +1,886 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use anyhow::{Context, Result};
+use base64::Engine;
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use crate::config::ClusterConfig;
+use crate::protocol::{Message, NodeInfo, RelayCloseReason};
+use crate::rendezvous;
+use crate::timesync;
+use crate::tr;
+
+/// 共享注册表中的节点条目
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RegisteredPeer {
+    /// 节点信息（`listen_addr` 为服务器观察到的地址）
+    pub node_info: NodeInfo,
+    /// NAT类型（如已上报）
+    pub nat_type: Option<String>,
+    /// 节点所连接的服务器实例ID
+    pub instance_id: Uuid,
+}
+
+/// 会话移交中的路由条目
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HandoffRoute {
+    pub destination: Uuid,
+    pub next_hop: Uuid,
+    pub distance: u32,
+}
+
+/// 移交给兄弟实例的节点会话
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HandoffSession {
+    /// 节点信息（`listen_addr` 为服务器观察到的地址）
+    pub node_info: NodeInfo,
+    pub nat_type: Option<String>,
+    /// 以该节点为下一跳的路由
+    pub routes: Vec<HandoffRoute>,
+    /// 节点的会话票据：节点重连到接收方时凭此从新地址迁移会话
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_ticket: Option<String>,
+    /// 节点在身份登记表中的当前公钥，接收方据此校验其密钥轮换与签名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+/// 接收移交会话的兄弟实例
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandoffTarget {
+    pub instance_id: Uuid,
+    /// 客户端应重连的地址
+    pub client_addr: SocketAddr,
+}
+
+/// 其他实例转来、需要本实例处理的事件
+#[derive(Debug, Clone)]
+pub enum ClusterDelivery {
+    /// 投递给本实例某个节点的消息
+    Message { target: Uuid, message: Message },
+    /// 正在下线的实例移交过来的会话
+    Handoff { from: Uuid, sessions: Vec<HandoffSession> },
+    /// 实例 `from` 上的节点经中继会话发给本实例节点 `target` 的数据
+    Relay { from: Uuid, session_id: Uuid, from_peer_id: Uuid, target: Uuid, data: Vec<u8> },
+    /// 跨实例中继会话已在另一实例上关闭
+    RelayClose { session_id: Uuid, reason: RelayCloseReason },
+}
+
+/// 每个移交报文携带的会话数，避免超出UDP报文大小
+const HANDOFF_CHUNK: usize = 64;
+/// 等待移交确认的超时与重试次数
+const HANDOFF_ACK_TIMEOUT: Duration = Duration::from_secs(1);
+const HANDOFF_RETRIES: usize = 3;
+/// 向归属实例查询节点位置的超时
+const LOCATE_TIMEOUT: Duration = Duration::from_secs(1);
+/// 设置集群密钥时每个gossip报文前附加的 HMAC-SHA256 标签长度
+const GOSSIP_TAG_LEN: usize = 32;
+/// 标签之后、正文之前的发送时间（Unix 毫秒）与序号，各 8 字节，同受标签保护
+const GOSSIP_STAMP_LEN: usize = 16;
+/// 发送时间与本机时钟相差超过该值的报文视为重放
+const GOSSIP_MAX_AGE: Duration = Duration::from_secs(30);
+/// 每个实例记录的最近序号数，窗口内乱序到达的报文仍被接受
+const REPLAY_WINDOW: u64 = 128;
+
+/// 可插拔的共享节点注册表
+///
+/// 集群中每个服务器实例将自己的已认证节点发布到注册表，并从中查询其他实例上的节点，
+/// 使位于UDP负载均衡之后的客户端无论连接到哪个实例都能互相发现。
+pub trait PeerRegistry: Send + Sync {
+    /// 用本实例当前的节点全量替换本实例的注册信息
+    fn publish_local(&self, peers: Vec<RegisteredPeer>);
+
+    /// 注册在其他实例上的节点
+    fn remote_peers(&self) -> Vec<RegisteredPeer>;
+
+    /// 查询注册在其他实例上的节点
+    fn lookup(&self, peer_id: &Uuid) -> Option<RegisteredPeer> {
+        self.remote_peers().into_iter().find(|p| p.node_info.id == *peer_id)
+    }
+
+    /// 查询节点位置；本地注册表中没有时，分片后端可向负责该节点的实例查询
+    fn locate<'a>(&'a self, peer_id: &'a Uuid) -> BoxFuture<'a, Option<RegisteredPeer>> {
+        Box::pin(async move { self.lookup(peer_id) })
+    }
+
+    /// 将消息转交给节点所在的实例，由其投递给该节点
+    fn forward<'a>(&'a self, entry: &'a RegisteredPeer, message: &'a Message) -> BoxFuture<'a, Result<()>>;
+
+    /// 经中继隧道把会话数据交给接收方所在的实例，由其转发给接收方
+    fn relay<'a>(&'a self, _entry: &'a RegisteredPeer, _session_id: Uuid, _from_peer_id: Uuid, _data: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Err(anyhow::anyhow!("该注册表后端不支持跨实例中继")) })
+    }
+
+    /// 通知实例 `instance_id` 跨实例中继会话已关闭
+    fn close_relay(&self, _instance_id: Uuid, _session_id: Uuid, _reason: RelayCloseReason) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Err(anyhow::anyhow!("该注册表后端不支持跨实例中继")) })
+    }
+
+    /// 将本实例的会话移交给一个兄弟实例（滚动升级时下线前调用），返回接收方
+    fn handoff(&self, _sessions: Vec<HandoffSession>) -> BoxFuture<'_, Result<HandoffTarget>> {
+        Box::pin(async { Err(anyhow::anyhow!("该注册表后端不支持会话移交")) })
+    }
+
+    /// 加入新发现的实例地址（例如来自 DNS 引导记录），不支持的后端忽略
+    fn add_members(&self, _addrs: &[SocketAddr]) {}
+
+    /// 启动后台任务，其他实例转来的消息与会话通过 `deliveries` 交给本实例处理
+    fn start(self: Arc<Self>, deliveries: mpsc::UnboundedSender<ClusterDelivery>) -> tokio::task::JoinHandle<()>;
+}
+
+/// gossip 报文
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum GossipPacket {
+    /// 实例全量状态（反熵）
+    State {
+        instance_id: Uuid,
+        version: u64,
+        /// 实例面向客户端的地址
+        client_addr: SocketAddr,
+        peers: Vec<RegisteredPeer>,
+        members: Vec<SocketAddr>,
+    },
+    /// 转交给目标节点的消息
+    Deliver {
+        instance_id: Uuid,
+        target: Uuid,
+        message: Message,
+    },
+    /// 移交会话（分片发送，每片单独确认）
+    Handoff {
+        instance_id: Uuid,
+        handoff_id: Uuid,
+        sessions: Vec<HandoffSession>,
+    },
+    /// 移交确认
+    HandoffAck {
+        instance_id: Uuid,
+        handoff_id: Uuid,
+    },
+    /// 分片模式：发送方归属于接收方的节点
+    Directory {
+        instance_id: Uuid,
+        version: u64,
+        peers: Vec<RegisteredPeer>,
+    },
+    /// 分片模式：向归属实例查询节点位置
+    Locate {
+        instance_id: Uuid,
+        request_id: Uuid,
+        peer_id: Uuid,
+    },
+    /// 节点位置查询结果
+    Located {
+        instance_id: Uuid,
+        request_id: Uuid,
+        entry: Option<RegisteredPeer>,
+    },
+    /// 中继隧道：经发送方实例的会话转发给接收方的数据（base64）
+    Relay {
+        instance_id: Uuid,
+        session_id: Uuid,
+        from_peer_id: Uuid,
+        target: Uuid,
+        data: String,
+    },
+    /// 跨实例中继会话已关闭
+    RelayClose {
+        instance_id: Uuid,
+        session_id: Uuid,
+        reason: RelayCloseReason,
+    },
+}
+
+/// 其他实例的最新状态
+#[derive(Debug)]
+struct InstanceState {
+    addr: SocketAddr,
+    client_addr: SocketAddr,
+    version: u64,
+    /// 该实例的节点（分片模式下只有归属于本实例的部分）
+    peers: Vec<RegisteredPeer>,
+    last_seen: Instant,
+}
+
+/// 带密钥报文的发送时间与序号
+#[derive(Debug, Clone, Copy)]
+struct GossipStamp {
+    sent_ms: u64,
+    sequence: u64,
+}
+
+/// 某个实例已接受的序号：最高序号及其之前 `REPLAY_WINDOW` 个序号的位图
+#[derive(Debug)]
+struct ReplayWindow {
+    highest: u64,
+    seen: u128,
+    last_seen: Instant,
+}
+
+impl ReplayWindow {
+    fn new(sequence: u64) -> Self {
+        Self { highest: sequence, seen: 1, last_seen: Instant::now() }
+    }
+
+    /// 登记序号；已经见过或早于窗口的序号返回 false
+    fn accept(&mut self, sequence: u64) -> bool {
+        if sequence > self.highest {
+            let shift = sequence - self.highest;
+            self.seen = if shift >= REPLAY_WINDOW { 1 } else { (self.seen << shift) | 1 };
+            self.highest = sequence;
+        } else {
+            let offset = self.highest - sequence;
+            if offset >= REPLAY_WINDOW || self.seen & (1 << offset) != 0 {
+                return false;
+            }
+            self.seen |= 1 << offset;
+        }
+        self.last_seen = Instant::now();
+        true
+    }
+}
+
+/// 基于UDP gossip的注册表后端
+///
+/// 每个实例周期性地向所有已知成员推送自身的全量节点列表和成员列表，
+/// 超过 `instance_ttl_secs` 未收到状态的实例连同其节点一起被移除。
+/// 分片模式下状态报文不再携带节点，每个节点只登记到 rendezvous hashing 选出的归属实例，
+/// 查询未知节点时向其归属实例询问。
+pub struct GossipRegistry {
+    instance_id: Uuid,
+    client_addr: SocketAddr,
+    config: ClusterConfig,
+    socket: Arc<UdpSocket>,
+    local_peers: RwLock<Vec<RegisteredPeer>>,
+    version: AtomicU64,
+    instances: Mutex<HashMap<Uuid, InstanceState>>,
+    members: Mutex<HashSet<SocketAddr>>,
+    /// 已移交会话并下线的实例，忽略其后续状态报文
+    departed: Mutex<HashSet<Uuid>>,
+    /// 等待确认的移交分片
+    pending_acks: Mutex<HashMap<Uuid, oneshot::Sender<()>>>,
+    /// 等待结果的节点位置查询
+    pending_locates: Mutex<HashMap<Uuid, oneshot::Sender<Option<RegisteredPeer>>>>,
+    /// 本实例带密钥报文的序号，从启动时的 Unix 微秒开始递增
+    sequence: AtomicU64,
+    /// 各实例的重放窗口
+    replay: Mutex<HashMap<Uuid, ReplayWindow>>,
+}
+
+impl GossipRegistry {
+    /// 绑定gossip端口；`client_addr` 为本实例面向客户端的地址，会话移交时告知客户端
+    pub async fn bind(instance_id: Uuid, client_addr: SocketAddr, config: ClusterConfig) -> Result<Self> {
+        let socket = UdpSocket::bind(config.bind_address).await
+            .context(format!("绑定集群gossip地址 {} 失败", config.bind_address))?;
+        let members = config.seeds.iter().copied().collect();
+        Ok(Self {
+            instance_id,
+            client_addr,
+            config,
+            socket: Arc::new(socket),
+            local_peers: RwLock::new(Vec::new()),
+            version: AtomicU64::new(0),
+            instances: Mutex::new(HashMap::new()),
+            members: Mutex::new(members),
+            departed: Mutex::new(HashSet::new()),
+            pending_acks: Mutex::new(HashMap::new()),
+            pending_locates: Mutex::new(HashMap::new()),
+            sequence: AtomicU64::new(timesync::unix_millis(SystemTime::now()).saturating_mul(1000)),
+            replay: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// 当前存活的其他实例数
+    pub fn instance_count(&self) -> usize {
+        self.instances.lock().map(|i| i.len()).unwrap_or(0)
+    }
+
+    /// 节点的归属实例（在本实例与当前存活的实例中选择）
+    pub fn home_of(&self, peer_id: &Uuid) -> Uuid {
+        let Ok(instances) = self.instances.lock() else { return self.instance_id };
+        rendezvous::home_server(peer_id, instances.keys().chain([&self.instance_id])).unwrap_or(self.instance_id)
+    }
+
+    /// 分片模式：把本实例的节点按归属实例分组发送；空列表同样发送，用于清除过期登记
+    async fn publish_directory(&self) {
+        let local = self.local_peers.read().map(|p| p.clone()).unwrap_or_default();
+        let targets: Vec<(Uuid, SocketAddr)> = match self.instances.lock() {
+            Ok(instances) => instances.iter().map(|(id, state)| (*id, state.addr)).collect(),
+            Err(_) => return,
+        };
+        for (instance_id, addr) in targets {
+            let packet = GossipPacket::Directory {
+                instance_id: self.instance_id,
+                version: self.version.load(Ordering::Relaxed),
+                peers: local.iter().filter(|p| self.home_of(&p.node_info.id) == instance_id).cloned().collect(),
+            };
+            let Ok(data) = self.seal(&packet) else { continue };
+            if let Err(e) = self.socket.send_to(&data, addr).await {
+                debug!("{}", tr!("向集群实例 {} 发送节点登记失败: {}", "Failed to send node registration to cluster instance {}: {}", addr, e));
+            }
+        }
+    }
+
+    /// 向所有成员推送本实例状态，并移除过期实例
+    async fn gossip_round(&self) {
+        let local_addr = self.socket.local_addr().ok();
+        let members: Vec<SocketAddr> = match self.members.lock() {
+            Ok(members) => members.iter().copied().filter(|m| Some(*m) != local_addr).collect(),
+            Err(_) => return,
+        };
+        let peers = if self.config.sharding {
+            Vec::new()
+        } else {
+            self.local_peers.read().map(|p| p.clone()).unwrap_or_default()
+        };
+        let packet = GossipPacket::State {
+            instance_id: self.instance_id,
+            version: self.version.load(Ordering::Relaxed),
+            client_addr: self.client_addr,
+            peers,
+            members: members.clone(),
+        };
+        let Ok(data) = self.seal(&packet) else { return };
+        for member in &members {
+            if let Err(e) = self.socket.send_to(&data, member).await {
+                debug!("{}", tr!("向集群成员 {} 发送状态失败: {}", "Failed to send state to cluster member {}: {}", member, e));
+            }
+        }
+        if self.config.sharding {
+            self.publish_directory().await;
+        }
+
+        let ttl = Duration::from_secs(self.config.instance_ttl_secs);
+        let mut expired = Vec::new();
+        if let Ok(mut instances) = self.instances.lock() {
+            instances.retain(|id, state| {
+                let alive = state.last_seen.elapsed() < ttl;
+                if !alive {
+                    warn!("{}", tr!("集群实例 {} ({}) 超时，移除其 {} 个节点", "Cluster instance {} ({}) timed out, removing its {} nodes", id, state.addr, state.peers.len()));
+                    expired.push(state.addr);
+                }
+                alive
+            });
+        }
+        // 超过有效期的报文会因发送时间被拒绝，之后不必再保留其序号窗口
+        if let Ok(mut replay) = self.replay.lock() {
+            replay.retain(|_, window| window.last_seen.elapsed() < GOSSIP_MAX_AGE * 2);
+        }
+        // 种子地址始终保留，以便实例恢复后重新加入
+        if !expired.is_empty() && let Ok(mut members) = self.members.lock() {
+            for addr in expired {
+                if !self.config.seeds.contains(&addr) {
+                    members.remove(&addr);
+                }
+            }
+        }
+    }
+
+    /// 序列化gossip报文；设置了集群密钥时在正文前附加发送时间与序号，
+    /// 并在最前面附加覆盖两者的 HMAC-SHA256 标签，密钥本身从不发送
+    fn seal(&self, packet: &GossipPacket) -> Result<Vec<u8>> {
+        let body = serde_json::to_vec(packet)?;
+        let Some(mac) = self.mac() else { return Ok(body) };
+        let mut signed = Vec::with_capacity(GOSSIP_STAMP_LEN + body.len());
+        signed.extend_from_slice(&timesync::unix_millis(SystemTime::now()).to_be_bytes());
+        signed.extend_from_slice(&self.sequence.fetch_add(1, Ordering::Relaxed).to_be_bytes());
+        signed.extend_from_slice(&body);
+        let mut data = mac.chain_update(&signed).finalize().into_bytes().to_vec();
+        data.extend_from_slice(&signed);
+        Ok(data)
+    }
+
+    /// 校验报文标签并返回发送时间、序号与正文；未设置集群密钥时原样返回正文
+    fn open<'a>(&self, data: &'a [u8]) -> Option<(Option<GossipStamp>, &'a [u8])> {
+        let Some(mac) = self.mac() else { return Some((None, data)) };
+        let (tag, signed) = data.split_at_checked(GOSSIP_TAG_LEN)?;
+        mac.chain_update(signed).verify_slice(tag).ok()?;
+        let (stamp, body) = signed.split_at_checked(GOSSIP_STAMP_LEN)?;
+        let (sent_ms, sequence) = stamp.split_at(8);
+        let stamp = GossipStamp {
+            sent_ms: u64::from_be_bytes(sent_ms.try_into().ok()?),
+            sequence: u64::from_be_bytes(sequence.try_into().ok()?),
+        };
+        Some((Some(stamp), body))
+    }
+
+    /// 拒绝发送时间超出有效期或序号已经见过的报文
+    fn accept_stamp(&self, instance_id: Uuid, stamp: GossipStamp) -> bool {
+        let now = timesync::unix_millis(SystemTime::now());
+        if now.abs_diff(stamp.sent_ms) > GOSSIP_MAX_AGE.as_millis() as u64 {
+            return false;
+        }
+        let Ok(mut replay) = self.replay.lock() else { return false };
+        match replay.entry(instance_id) {
+            Entry::Occupied(entry) => entry.into_mut().accept(stamp.sequence),
+            Entry::Vacant(entry) => {
+                entry.insert(ReplayWindow::new(stamp.sequence));
+                true
+            }
+        }
+    }
+
+    fn mac(&self) -> Option<Hmac<Sha256>> {
+        let key = self.config.cluster_key.as_ref()?;
+        Some(Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC 接受任意长度的密钥"))
+    }
+
+    fn handle_packet(&self, data: &[u8], from: SocketAddr, deliveries: &mpsc::UnboundedSender<ClusterDelivery>) {
+        let Some((stamp, body)) = self.open(data) else {
+            warn!("{}", tr!("丢弃集群密钥认证失败的gossip报文，来自 {}", "Dropping gossip packet that failed cluster key authentication from {}", from));
+            return;
+        };
+        let packet: GossipPacket = match serde_json::from_slice(body) {
+            Ok(packet) => packet,
+            Err(e) => {
+                debug!("{}", tr!("丢弃无效的gossip报文（来自 {}）: {}", "Dropping invalid gossip packet (from {}): {}", from, e));
+                return;
+            }
+        };
+        let instance_id = match &packet {
+            GossipPacket::State { instance_id, .. }
+            | GossipPacket::Deliver { instance_id, .. }
+            | GossipPacket::Handoff { instance_id, .. }
+            | GossipPacket::HandoffAck { instance_id, .. }
+            | GossipPacket::Directory { instance_id, .. }
+            | GossipPacket::Locate { instance_id, .. }
+            | GossipPacket::Located { instance_id, .. }
+            | GossipPacket::Relay { instance_id, .. }
+            | GossipPacket::RelayClose { instance_id, .. } => *instance_id,
+        };
+        if instance_id == self.instance_id {
+            return;
+        }
+        if let Some(stamp) = stamp && !self.accept_stamp(instance_id, stamp) {
+            warn!("{}", tr!("丢弃过期或重放的gossip报文（实例 {}，来自 {}）", "Dropping stale or replayed gossip packet (instance {}, from {})", instance_id, from));
+            return;
+        }
+
+        match packet {
+            GossipPacket::State { version, client_addr, peers, members, .. } => {
+                if self.departed.lock().is_ok_and(|d| d.contains(&instance_id)) {
+                    return;
+                }
+                if let Ok(mut known) = self.members.lock() {
+                    known.insert(from);
+                    known.extend(members);
+                }
+                let Ok(mut instances) = self.instances.lock() else { return };
+                let state = instances.entry(instance_id).or_insert_with(|| {
+                    info!("{}", tr!("发现集群实例 {} ({})", "Discovered cluster instance {} ({})", instance_id, from));
+                    InstanceState { addr: from, client_addr, version: 0, peers: Vec::new(), last_seen: Instant::now() }
+                });
+                state.addr = from;
+                state.client_addr = client_addr;
+                state.last_seen = Instant::now();
+                // UDP可能乱序，只接受不旧于当前的状态（实例重启后会使用新的实例ID）
+                // 分片模式下节点经 Directory 报文登记
+                if version >= state.version && !self.config.sharding {
+                    state.version = version;
+                    state.peers = peers;
+                }
+            }
+            GossipPacket::Directory { version, peers, .. } => {
+                let Ok(mut instances) = self.instances.lock() else { return };
+                // 尚未收到该实例的状态报文时忽略，等待下一轮
+                if let Some(state) = instances.get_mut(&instance_id)
+                    && version >= state.version
+                {
+                    state.version = version;
+                    state.peers = peers;
+                }
+            }
+            GossipPacket::Locate { request_id, peer_id, .. } => {
+                let entry = self.local_peers.read().ok()
+                    .and_then(|local| local.iter().find(|p| p.node_info.id == peer_id).cloned())
+                    .or_else(|| self.lookup(&peer_id));
+                let reply = GossipPacket::Located {
+                    instance_id: self.instance_id,
+                    request_id,
+                    entry,
+                };
+                if let Ok(data) = self.seal(&reply) {
+                    let socket = self.socket.clone();
+                    tokio::spawn(async move {
+                        let _ = socket.send_to(&data, from).await;
+                    });
+                }
+            }
+            GossipPacket::Located { request_id, entry, .. } => {
+                if let Some(tx) = self.pending_locates.lock().ok().and_then(|mut p| p.remove(&request_id)) {
+                    let _ = tx.send(entry);
+                }
+            }
+            GossipPacket::Deliver { target, message, .. } => {
+                let _ = deliveries.send(ClusterDelivery::Message { target, message });
+            }
+            GossipPacket::Relay { session_id, from_peer_id, target, data, .. } => {
+                match base64::engine::general_purpose::STANDARD.decode(data) {
+                    Ok(data) => {
+                        let _ = deliveries.send(ClusterDelivery::Relay { from: instance_id, session_id, from_peer_id, target, data });
+                    }
+                    Err(e) => debug!("{}", tr!("丢弃数据无效的中继隧道报文（来自 {}）: {}", "Dropping relay tunnel packet with invalid data (from {}): {}", from, e)),
+                }
+            }
+            GossipPacket::RelayClose { session_id, reason, .. } => {
+                let _ = deliveries.send(ClusterDelivery::RelayClose { session_id, reason });
+            }
+            GossipPacket::Handoff { handoff_id, sessions, .. } => {
+                // 发送方即将下线：立即移除其注册信息，会话由本实例接管
+                if let Ok(mut departed) = self.departed.lock() {
+                    departed.insert(instance_id);
+                }
+                if let Ok(mut instances) = self.instances.lock() {
+                    instances.remove(&instance_id);
+                }
+                info!("{}", tr!("接收来自实例 {} 的 {} 个移交会话", "Receiving {1} handed-over sessions from instance {0}", instance_id, sessions.len()));
+                // 重试的分片可能重复到达，接管操作本身是幂等的
+                let _ = deliveries.send(ClusterDelivery::Handoff { from: instance_id, sessions });
+                let ack = GossipPacket::HandoffAck {
+                    instance_id: self.instance_id,
+                    handoff_id,
+                };
+                if let Ok(data) = self.seal(&ack) {
+                    let socket = self.socket.clone();
+                    tokio::spawn(async move {
+                        let _ = socket.send_to(&data, from).await;
+                    });
+                }
+            }
+            GossipPacket::HandoffAck { handoff_id, .. } => {
+                if let Some(tx) = self.pending_acks.lock().ok().and_then(|mut p| p.remove(&handoff_id)) {
+                    let _ = tx.send(());
+                }
+            }
+        }
+    }
+
+    /// 存活实例的gossip地址
+    fn instance_addr(&self, instance_id: &Uuid) -> Result<SocketAddr> {
+        self.instances
+            .lock()
+            .ok()
+            .and_then(|instances| instances.get(instance_id).map(|state| state.addr))
+            .ok_or_else(|| anyhow::anyhow!("集群实例不可达: {}", instance_id))
+    }
+
+    /// 选择节点数最少的存活实例作为移交目标
+    fn pick_handoff_target(&self) -> Option<(HandoffTarget, SocketAddr)> {
+        let instances = self.instances.lock().ok()?;
+        instances
+            .iter()
+            .min_by_key(|(_, state)| state.peers.len())
+            .map(|(id, state)| (HandoffTarget { instance_id: *id, client_addr: state.client_addr }, state.addr))
+    }
+
+    /// 发送一个移交分片并等待确认，超时重试
+    async fn send_handoff_chunk(&self, addr: SocketAddr, sessions: &[HandoffSession]) -> Result<()> {
+        let handoff_id = Uuid::new_v4();
+        let packet = GossipPacket::Handoff {
+            instance_id: self.instance_id,
+            handoff_id,
+            sessions: sessions.to_vec(),
+        };
+        for attempt in 1..=HANDOFF_RETRIES {
+            // 每次重试重新封装，使用新的序号，以免被接收方当作重放
+            let data = self.seal(&packet)?;
+            let (tx, rx) = oneshot::channel();
+            if let Ok(mut pending) = self.pending_acks.lock() {
+                pending.insert(handoff_id, tx);
+            }
+            self.socket.send_to(&data, addr).await?;
+            if let Ok(Ok(())) = tokio::time::timeout(HANDOFF_ACK_TIMEOUT, rx).await {
+                return Ok(());
+            }
+            debug!("{}", tr!("移交分片 {} 第{}次未确认", "Shard handoff {} not acknowledged (attempt {})", handoff_id, attempt));
+        }
+        if let Ok(mut pending) = self.pending_acks.lock() {
+            pending.remove(&handoff_id);
+        }
+        Err(anyhow::anyhow!("移交分片未被实例 {} 确认", addr))
+    }
+}
+
+impl PeerRegistry for GossipRegistry {
+    fn add_members(&self, addrs: &[SocketAddr]) {
+        if let Ok(mut members) = self.members.lock() {
+            members.extend(addrs.iter().copied());
+        }
+    }
+
+    fn publish_local(&self, peers: Vec<RegisteredPeer>) {
+        if let Ok(mut local) = self.local_peers.write()
+            && *local != peers
+        {
+            *local = peers;
+            self.version.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn remote_peers(&self) -> Vec<RegisteredPeer> {
+        let Ok(instances) = self.instances.lock() else { return Vec::new() };
+        instances.values().flat_map(|state| state.peers.iter().cloned()).collect()
+    }
+
+    fn locate<'a>(&'a self, peer_id: &'a Uuid) -> BoxFuture<'a, Option<RegisteredPeer>> {
+        Box::pin(async move {
+            if let Some(entry) = self.lookup(peer_id) {
+                return Some(entry);
+            }
+            if !self.config.sharding {
+                return None;
+            }
+            let home = self.home_of(peer_id);
+            let addr = self.instances.lock().ok()?.get(&home).map(|state| state.addr)?;
+            let request_id = Uuid::new_v4();
+            let packet = GossipPacket::Locate {
+                instance_id: self.instance_id,
+                request_id,
+                peer_id: *peer_id,
+            };
+            let (tx, rx) = oneshot::channel();
+            self.pending_locates.lock().ok()?.insert(request_id, tx);
+            let data = self.seal(&packet).ok()?;
+            let located = match self.socket.send_to(&data, addr).await {
+                Ok(_) => tokio::time::timeout(LOCATE_TIMEOUT, rx).await.ok().and_then(|r| r.ok()).flatten(),
+                Err(e) => {
+                    debug!("{}", tr!("向归属实例 {} 查询节点 {} 失败: {}", "Failed to query home instance {} for node {}: {}", addr, peer_id, e));
+                    None
+                }
+            };
+            if let Ok(mut pending) = self.pending_locates.lock() {
+                pending.remove(&request_id);
+            }
+            located
+        })
+    }
+
+    fn forward<'a>(&'a self, entry: &'a RegisteredPeer, message: &'a Message) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let addr = self.instance_addr(&entry.instance_id)?;
+            let packet = GossipPacket::Deliver {
+                instance_id: self.instance_id,
+                target: entry.node_info.id,
+                message: message.clone(),
+            };
+            self.socket.send_to(&self.seal(&packet)?, addr).await?;
+            Ok(())
+        })
+    }
+
+    fn relay<'a>(&'a self, entry: &'a RegisteredPeer, session_id: Uuid, from_peer_id: Uuid, data: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let addr = self.instance_addr(&entry.instance_id)?;
+            let packet = GossipPacket::Relay {
+                instance_id: self.instance_id,
+                session_id,
+                from_peer_id,
+                target: entry.node_info.id,
+                data: base64::engine::general_purpose::STANDARD.encode(data),
+            };
+            self.socket.send_to(&self.seal(&packet)?, addr).await?;
+            Ok(())
+        })
+    }
+
+    fn close_relay(&self, instance_id: Uuid, session_id: Uuid, reason: RelayCloseReason) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let addr = self.instance_addr(&instance_id)?;
+            let packet = GossipPacket::RelayClose {
+                instance_id: self.instance_id,
+                session_id,
+                reason,
+            };
+            self.socket.send_to(&self.seal(&packet)?, addr).await?;
+            Ok(())
+        })
+    }
+
+    fn handoff(&self, sessions: Vec<HandoffSession>) -> BoxFuture<'_, Result<HandoffTarget>> {
+        Box::pin(async move {
+            let (target, addr) = self
+                .pick_handoff_target()
+                .ok_or_else(|| anyhow::anyhow!("没有可接收移交的集群实例"))?;
+            for chunk in sessions.chunks(HANDOFF_CHUNK) {
+                self.send_handoff_chunk(addr, chunk).await?;
+            }
+            // 不再发布本实例的节点
+            self.publish_local(Vec::new());
+            info!("{}", tr!("已将 {} 个会话移交给实例 {} ({})", "Handed over {} sessions to instance {} ({})", sessions.len(), target.instance_id, target.client_addr));
+            Ok(target)
+        })
+    }
+
+    fn start(self: Arc<Self>, deliveries: mpsc::UnboundedSender<ClusterDelivery>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(self.config.gossip_interval_ms.max(50)));
+            let mut buf = vec![0u8; 65536];
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => self.gossip_round().await,
+                    received = self.socket.recv_from(&mut buf) => match received {
+                        Ok((len, from)) => self.handle_packet(&buf[..len], from, &deliveries),
+                        Err(e) => debug!("{}", tr!("接收gossip报文失败: {}", "Failed to receive gossip packet: {}", e)),
+                    },
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registered(instance_id: Uuid, name: &str) -> RegisteredPeer {
+        RegisteredPeer {
+            node_info: NodeInfo::new(name.to_string(), "127.0.0.1:40000".parse().unwrap(), "p2p_default".to_string()),
+            nat_type: None,
+            instance_id,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gossip_shares_peers_delivers_and_hands_off() {
+        let config = ClusterConfig {
+            enable: true,
+            bind_address: "127.0.0.1:0".parse().unwrap(),
+            gossip_interval_ms: 50,
+            ..ClusterConfig::default()
+        };
+        let a_client: SocketAddr = "127.0.0.1:18080".parse().unwrap();
+        let a = Arc::new(GossipRegistry::bind(Uuid::new_v4(), a_client, config.clone()).await.unwrap());
+        let b_config = ClusterConfig { seeds: vec![a.local_addr().unwrap()], ..config };
+        let b = Arc::new(GossipRegistry::bind(Uuid::new_v4(), "127.0.0.1:18081".parse().unwrap(), b_config).await.unwrap());
+
+        let peer_a = registered(a.instance_id, "on-a");
+        a.publish_local(vec![peer_a.clone()]);
+        b.publish_local(vec![registered(b.instance_id, "on-b")]);
+
+        let (a_tx, mut a_rx) = mpsc::unbounded_channel();
+        let (b_tx, mut b_rx) = mpsc::unbounded_channel();
+        let tasks = [a.clone().start(a_tx), b.clone().start(b_tx)];
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        // A 只通过 B 的推送得知 B 的地址，双方最终都能看到对方的节点
+        assert_eq!(b.lookup(&peer_a.node_info.id), Some(peer_a.clone()));
+        assert_eq!(a.remote_peers()[0].node_info.name.as_ref(), "on-b");
+
+        let entry = a.remote_peers().remove(0);
+        a.forward(&entry, &Message::ping()).await.unwrap();
+        let delivery = tokio::time::timeout(Duration::from_secs(1), b_rx.recv()).await.unwrap().unwrap();
+        assert!(matches!(delivery, ClusterDelivery::Message { target, .. } if target == entry.node_info.id));
+
+        // 中继隧道与会话关闭通知
+        let (session_id, from_peer_id) = (Uuid::new_v4(), peer_a.node_info.id);
+        a.relay(&entry, session_id, from_peer_id, &[0, 1, 255]).await.unwrap();
+        let delivery = tokio::time::timeout(Duration::from_secs(1), b_rx.recv()).await.unwrap().unwrap();
+        assert!(matches!(delivery, ClusterDelivery::Relay { from, data, .. } if from == a.instance_id && data == [0, 1, 255]));
+        b.close_relay(a.instance_id, session_id, RelayCloseReason::PeerDisconnected).await.unwrap();
+        let delivery = tokio::time::timeout(Duration::from_secs(1), a_rx.recv()).await.unwrap().unwrap();
+        assert!(matches!(delivery, ClusterDelivery::RelayClose { session_id: id, reason: RelayCloseReason::PeerDisconnected } if id == session_id));
+
+        // B 下线前把会话移交给 A，A 随即不再把 B 视为集群成员
+        let session = HandoffSession {
+            node_info: entry.node_info.clone(),
+            nat_type: None,
+            routes: Vec::new(),
+            session_ticket: Some("ticket".to_string()),
+            public_key: None,
+        };
+        let target = b.handoff(vec![session.clone()]).await.unwrap();
+        assert_eq!(target, HandoffTarget { instance_id: a.instance_id, client_addr: a_client });
+        let delivery = tokio::time::timeout(Duration::from_secs(1), a_rx.recv()).await.unwrap().unwrap();
+        assert!(matches!(delivery, ClusterDelivery::Handoff { from, sessions } if from == b.instance_id && sessions == vec![session]));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(a.remote_peers().is_empty());
+
+        for task in tasks {
+            task.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gossip_packets_are_authenticated_without_sending_key() {
+        let config = |key: Option<&str>| ClusterConfig {
+            enable: true,
+            bind_address: "127.0.0.1:0".parse().unwrap(),
+            cluster_key: key.map(str::to_string),
+            ..ClusterConfig::default()
+        };
+        let client: SocketAddr = "127.0.0.1:18082".parse().unwrap();
+        let a = GossipRegistry::bind(Uuid::new_v4(), client, config(Some("cluster-secret"))).await.unwrap();
+        let b = GossipRegistry::bind(Uuid::new_v4(), client, config(Some("cluster-secret"))).await.unwrap();
+        let other = GossipRegistry::bind(Uuid::new_v4(), client, config(Some("other-secret"))).await.unwrap();
+        let open = GossipRegistry::bind(Uuid::new_v4(), client, config(None)).await.unwrap();
+
+        let packet = GossipPacket::RelayClose { instance_id: a.instance_id, session_id: Uuid::new_v4(), reason: RelayCloseReason::PeerDisconnected };
+        let data = a.seal(&packet).unwrap();
+        assert!(!String::from_utf8_lossy(&data).contains("cluster-secret"));
+        assert!(b.open(&data).is_some());
+        assert!(other.open(&data).is_none());
+
+        // 未签名或被篡改的报文都无法通过认证
+        let unsigned = open.seal(&packet).unwrap();
+        assert!(b.open(&unsigned).is_none());
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(b.open(&tampered).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_replayed_and_stale_gossip_packets_are_dropped() {
+        let config = ClusterConfig {
+            enable: true,
+            bind_address: "127.0.0.1:0".parse().unwrap(),
+            cluster_key: Some("cluster-secret".to_string()),
+            ..ClusterConfig::default()
+        };
+        let client: SocketAddr = "127.0.0.1:18083".parse().unwrap();
+        let a = GossipRegistry::bind(Uuid::new_v4(), client, config.clone()).await.unwrap();
+        let b = GossipRegistry::bind(Uuid::new_v4(), client, config).await.unwrap();
+        let from = a.local_addr().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        // 同一报文只投递一次，重放的副本被丢弃
+        let close = |session_id| GossipPacket::RelayClose { instance_id: a.instance_id, session_id, reason: RelayCloseReason::PeerDisconnected };
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let earlier = a.seal(&close(first)).unwrap();
+        let later = a.seal(&close(second)).unwrap();
+        b.handle_packet(&later, from, &tx);
+        b.handle_packet(&later, from, &tx);
+        // 窗口内乱序到达的报文仍被接受
+        b.handle_packet(&earlier, from, &tx);
+        b.handle_packet(&earlier, from, &tx);
+        let mut delivered = Vec::new();
+        while let Ok(ClusterDelivery::RelayClose { session_id, .. }) = rx.try_recv() {
+            delivered.push(session_id);
+        }
+        assert_eq!(delivered, vec![second, first]);
+
+        // 发送时间超出有效期或早于序号窗口的报文同样被拒绝
+        let stale_ms = timesync::unix_millis(SystemTime::now() - GOSSIP_MAX_AGE * 2);
+        assert!(!b.accept_stamp(Uuid::new_v4(), GossipStamp { sent_ms: stale_ms, sequence: 0 }));
+        let now_ms = timesync::unix_millis(SystemTime::now());
+        let instance = Uuid::new_v4();
+        assert!(b.accept_stamp(instance, GossipStamp { sent_ms: now_ms, sequence: 1000 }));
+        assert!(b.accept_stamp(instance, GossipStamp { sent_ms: now_ms, sequence: 1000 - REPLAY_WINDOW + 1 }));
+        assert!(!b.accept_stamp(instance, GossipStamp { sent_ms: now_ms, sequence: 1000 - REPLAY_WINDOW }));
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::Message;
+
+/// 消息编解码器
+///
+/// 协议消息在UDP上的编码方式。实现 [`Codec::detect`] 后可参与自动识别，
+/// 使同一服务器能同时服务使用不同编码的客户端。
+pub trait Codec: Send + Sync + Debug {
+    /// 编码名称，用于日志与配置
+    fn name(&self) -> &'static str;
+
+    /// 编码消息
+    fn encode(&self, message: &Message) -> Result<Vec<u8>>;
+
+    /// 解码消息
+    fn decode(&self, data: &[u8]) -> Result<Message>;
+
+    /// 判断数据包是否可能使用本编码（只检查前几个字节，不做完整解析）
+    fn detect(&self, data: &[u8]) -> bool;
+}
+
+/// JSON 编码（默认）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>> {
+        serde_json::to_vec(message).context("序列化消息失败")
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Message> {
+        serde_json::from_slice(data).context("反序列化UDP消息失败")
+    }
+
+    fn detect(&self, data: &[u8]) -> bool {
+        data.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{')
+    }
+}
+
+/// MessagePack 编码（结构体以map形式编码，字段名与JSON一致）
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackCodec;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MessagePackCodec {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn encode(&self, message: &Message) -> Result<Vec<u8>> {
+        rmp_serde::to_vec_named(message).context("MessagePack序列化消息失败")
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Message> {
+        rmp_serde::from_slice(data).context("MessagePack反序列化消息失败")
+    }
+
+    fn detect(&self, data: &[u8]) -> bool {
+        // fixmap / map16 / map32
+        matches!(data.first(), Some(0x80..=0x8f | 0xde | 0xdf))
+    }
+}
+
+/// 可配置的编码格式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CodecFormat {
+    #[default]
+    Json,
+    /// 需要启用 `msgpack` 特性
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl CodecFormat {
+    /// 创建对应的编解码器
+    pub fn build(self) -> Result<Arc<dyn Codec>> {
+        match self {
+            CodecFormat::Json => Ok(Arc::new(JsonCodec)),
+            #[cfg(feature = "msgpack")]
+            CodecFormat::MessagePack => Ok(Arc::new(MessagePackCodec)),
+            #[cfg(not(feature = "msgpack"))]
+            CodecFormat::MessagePack => Err(anyhow::anyhow!("MessagePack编码需要启用 msgpack 特性")),
+        }
+    }
+}
+
+/// 编解码器集合：默认编码用于主动发送，自动识别时按顺序匹配收到的数据包
+#[derive(Debug, Clone)]
+pub struct CodecSet {
+    default: Arc<dyn Codec>,
+    codecs: Vec<Arc<dyn Codec>>,
+    auto_detect: bool,
+}
+
+impl Default for CodecSet {
+    fn default() -> Self {
+        Self::new(Arc::new(JsonCodec), true)
+    }
+}
+
+impl CodecSet {
+    /// 以 `default` 为默认编码创建集合，并加入所有已编译的内置编码
+    pub fn new(default: Arc<dyn Codec>, auto_detect: bool) -> Self {
+        let mut set = Self { default: default.clone(), codecs: vec![default], auto_detect };
+        set.register(Arc::new(JsonCodec));
+        #[cfg(feature = "msgpack")]
+        set.register(Arc::new(MessagePackCodec));
+        set
+    }
+
+    /// 注册自定义编解码器（同名编码只保留先注册的）
+    pub fn register(&mut self, codec: Arc<dyn Codec>) {
+        if !self.codecs.iter().any(|c| c.name() == codec.name()) {
+            self.codecs.push(codec);
+        }
+    }
+
+    pub fn default_codec(&self) -> Arc<dyn Codec> {
+        self.default.clone()
+    }
+
+    /// 解码数据包，返回消息及所用的编解码器
+    pub fn decode(&self, data: &[u8]) -> Result<(Message, Arc<dyn Codec>)> {
+        let codec = if self.auto_detect {
+            self.codecs
+                .iter()
+                .find(|c| c.detect(data))
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("无法识别消息编码"))?
+        } else {
+            self.default.clone()
+        };
+        let message = codec.decode(data)?;
+        Ok((message, codec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::MessageType;
+
+    #[test]
+    fn test_auto_detect_roundtrip() {
+        let set = CodecSet::default();
+        let message = Message::new(MessageType::Data, serde_json::json!({ "k": [1, 2, 3] }));
+
+        let data = JsonCodec.encode(&message).unwrap();
+        let (decoded, codec) = set.decode(&data).unwrap();
+        assert_eq!(codec.name(), "json");
+        assert_eq!(decoded.payload, message.payload);
+        assert!(set.decode(&[0xc1, 0x00]).is_err());
+
+        #[cfg(feature = "msgpack")]
+        {
+            let data = MessagePackCodec.encode(&message).unwrap();
+            let (decoded, codec) = set.decode(&data).unwrap();
+            assert_eq!(codec.name(), "msgpack");
+            assert_eq!(decoded.id, message.id);
+            assert_eq!(decoded.payload, message.payload);
+        }
+    }
+}
@@ -1,7 +1,13 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use uuid::Uuid;
 use anyhow::Result;
+use crate::codec::CodecFormat;
+use crate::i18n::Language;
+use crate::pmtu::BASE_PLPMTU;
 use crate::stun_server::StunServerConfig;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -138,20 +144,1274 @@ impl Default for IceConfig {
     }
 }
 
+/// 路由模式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingMode {
+    /// 距离向量：根据节点发现响应推导下一跳（默认）
+    #[default]
+    DistanceVector,
+    /// 链路状态：泛洪邻接关系，维护拓扑图并用Dijkstra计算下一跳
+    LinkState,
+}
+
+/// 路由消息签名的校验策略
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteSignaturePolicy {
+    /// 不校验签名（默认）
+    #[default]
+    Off,
+    /// 丢弃签名无效的路由消息，未签名的照常转发
+    Verify,
+    /// 丢弃未签名或签名无效的路由消息
+    Require,
+}
+
+/// 负载超过 `routing.max_routed_payload_bytes` 的路由消息的处理策略
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedRoutePolicy {
+    /// 一律拒绝（默认）
+    #[default]
+    Reject,
+    /// 有到目标节点的路由时照常转发，没有时拒绝而不广播
+    NoFlood,
+}
+
+/// 路由配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingConfig {
+    /// 路由模式
+    pub mode: RoutingMode,
+
+    /// 链路状态模式下重新泛洪本地通告的间隔（秒）
+    pub lsa_refresh_interval: u64,
+
+    /// 链路状态通告的最大存活时间（秒），超时未刷新的通告将被丢弃
+    pub lsa_max_age: u64,
+
+    /// 路由消息去重的保证窗口（秒）：窗口内重发的同一 `route_id` 最多转发一次
+    pub dedup_window_secs: u64,
+
+    /// 去重日志最多保存的 `route_id` 条数，超出后覆盖最旧的记录
+    pub dedup_log_capacity: usize,
+
+    /// 去重日志文件路径，为空时只保存在内存中（服务器重启后丢失）
+    pub dedup_log_path: Option<PathBuf>,
+
+    /// 一条多播消息最多携带的目标节点数
+    pub max_multicast_members: usize,
+
+    /// 转发前对路由消息签名的校验策略
+    pub signatures: RouteSignaturePolicy,
+
+    /// 路由消息负载（序列化后）的字节上限，0 表示不限制
+    pub max_routed_payload_bytes: usize,
+
+    /// 负载超过上限的路由消息的处理策略
+    pub oversized_payloads: OversizedRoutePolicy,
+
+    /// 重复广播的识别窗口（毫秒）：窗口内内容相同的广播只发出第一份，0 表示不识别
+    pub broadcast_dedup_window_ms: u64,
+
+    /// 链路状态来源的身份公钥（节点ID到十六进制公钥）；经其他节点转发的通告只用登记的公钥校验，
+    /// 未直接相连的来源须在此登记
+    pub origin_keys: HashMap<Uuid, String>,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        Self {
+            mode: RoutingMode::DistanceVector,
+            lsa_refresh_interval: 60,
+            lsa_max_age: 180,
+            dedup_window_secs: 600,
+            dedup_log_capacity: 65536,
+            dedup_log_path: None,
+            max_multicast_members: 256,
+            signatures: RouteSignaturePolicy::Off,
+            max_routed_payload_bytes: 8192,
+            oversized_payloads: OversizedRoutePolicy::Reject,
+            broadcast_dedup_window_ms: 2000,
+            origin_keys: HashMap::new(),
+        }
+    }
+}
+
+/// 管理接口配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// 是否启用管理HTTP接口
+    pub enable: bool,
+
+    /// 管理接口监听地址
+    pub listen_address: SocketAddr,
+
+    /// 访问令牌（Bearer 或 `?token=`），未设置时不校验
+    pub token: Option<String>,
+
+    /// 监控面板实时推送间隔（毫秒，需启用 `dashboard` 特性）
+    pub dashboard_refresh_ms: u64,
+
+    /// 额外接受的 `Host`（如反向代理使用的域名）；回环地址、`localhost` 与监听地址始终接受
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            listen_address: "127.0.0.1:8088".parse().unwrap(),
+            token: None,
+            dashboard_refresh_ms: 1000,
+            allowed_hosts: Vec::new(),
+        }
+    }
+}
+
+/// gRPC 控制面配置（需启用 `grpc` 特性）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    /// 是否启用 gRPC 控制面
+    pub enable: bool,
+
+    /// gRPC 监听地址
+    pub listen_address: SocketAddr,
+
+    /// 访问令牌（metadata `authorization: Bearer <token>`），未设置时不校验
+    pub token: Option<String>,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            listen_address: "127.0.0.1:50051".parse().unwrap(),
+            token: None,
+        }
+    }
+}
+
+/// MQTT 桥接方向
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeDirection {
+    /// 覆盖网络 -> MQTT
+    Out,
+    /// MQTT -> 覆盖网络
+    In,
+    /// 双向
+    #[default]
+    Both,
+}
+
+impl BridgeDirection {
+    pub fn outbound(self) -> bool {
+        matches!(self, BridgeDirection::Out | BridgeDirection::Both)
+    }
+
+    pub fn inbound(self) -> bool {
+        matches!(self, BridgeDirection::In | BridgeDirection::Both)
+    }
+}
+
+/// 桥接的主题
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MqttTopicMapping {
+    /// 覆盖网络内的主题名，MQTT 主题为 `topic_prefix + topic`
+    pub topic: String,
+    #[serde(default)]
+    pub direction: BridgeDirection,
+}
+
+/// MQTT 桥接配置（需启用 `mqtt` 特性）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// 是否启用 MQTT 桥接
+    pub enable: bool,
+    /// broker 主机名
+    pub broker_host: String,
+    /// broker 端口
+    pub broker_port: u16,
+    /// MQTT 客户端ID
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// 心跳间隔（秒），节点较多时按 `heartbeat` 配置放大
+    pub keep_alive_secs: u64,
+    /// 发布与订阅使用的 QoS（0 或 1）
+    pub qos: u8,
+    /// MQTT 主题前缀
+    pub topic_prefix: String,
+    /// 桥接的主题列表
+    pub topics: Vec<MqttTopicMapping>,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            broker_host: "127.0.0.1".to_string(),
+            broker_port: 1883,
+            client_id: "p2p_handshake_server".to_string(),
+            username: None,
+            password: None,
+            keep_alive_secs: 30,
+            qos: 0,
+            topic_prefix: "p2p/".to_string(),
+            topics: Vec::new(),
+        }
+    }
+}
+
+/// OTLP 遥测导出配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// 是否启用 OTLP 导出
+    pub enable: bool,
+    /// OTLP/HTTP 收集器地址（JSON编码），例如 `http://127.0.0.1:4318`；须为回环地址，不支持 https
+    pub endpoint: String,
+    /// 上报的 `service.name`
+    pub service_name: String,
+    /// 导出间隔（毫秒）
+    pub export_interval_ms: u64,
+    /// 待导出span队列上限，超出时丢弃最旧的span
+    pub max_queued_spans: usize,
+    /// 是否导出span
+    pub export_traces: bool,
+    /// 是否导出指标
+    pub export_metrics: bool,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            endpoint: "http://127.0.0.1:4318".to_string(),
+            service_name: "p2p_handshake_server".to_string(),
+            export_interval_ms: 10_000,
+            max_queued_spans: 2048,
+            export_traces: true,
+            export_metrics: true,
+        }
+    }
+}
+
+/// 集群注册表后端
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterBackend {
+    /// 实例间UDP gossip
+    #[default]
+    Gossip,
+}
+
+/// 多实例集群配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClusterConfig {
+    /// 是否启用集群模式
+    pub enable: bool,
+    /// 注册表后端
+    pub backend: ClusterBackend,
+    /// gossip 监听地址，默认只监听回环地址；绑定其他地址时必须设置 `cluster_key`
+    pub bind_address: SocketAddr,
+    /// 种子实例的gossip地址，其余成员通过gossip自动发现
+    pub seeds: Vec<SocketAddr>,
+    /// gossip 推送间隔（毫秒）
+    pub gossip_interval_ms: u64,
+    /// 超过该时间未收到状态的实例视为下线（秒）
+    pub instance_ttl_secs: u64,
+    /// 集群共享密钥：每个gossip报文附带用它计算的 HMAC-SHA256 标签，认证失败、过期或重放的报文将被丢弃
+    pub cluster_key: Option<String>,
+    /// 本实例面向客户端的地址（会话移交时告知客户端），默认为监听地址
+    pub advertise_address: Option<SocketAddr>,
+    /// 关闭时将会话移交给其他实例并向客户端发送重连提示
+    pub handoff_on_shutdown: bool,
+    /// 分片模式：节点注册信息只发送到按 rendezvous hashing 选出的归属实例，不再全量复制；
+    /// 集群内所有实例必须使用相同的设置
+    pub sharding: bool,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            backend: ClusterBackend::Gossip,
+            bind_address: "127.0.0.1:7946".parse().unwrap(),
+            seeds: Vec::new(),
+            gossip_interval_ms: 1000,
+            instance_ttl_secs: 10,
+            cluster_key: None,
+            advertise_address: None,
+            handoff_on_shutdown: true,
+            sharding: false,
+        }
+    }
+}
+
+/// 消息编码配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CodecConfig {
+    /// 主动发送消息时使用的默认编码
+    pub format: CodecFormat,
+    /// 是否自动识别收到的数据包编码（混合编码的客户端群）
+    pub auto_detect: bool,
+}
+
+impl Default for CodecConfig {
+    fn default() -> Self {
+        Self {
+            format: CodecFormat::Json,
+            auto_detect: true,
+        }
+    }
+}
+
+/// 节点列表下发配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryConfig {
+    /// 节点列表负载的字节上限，放不下时挑选对接收方最可能直连成功的节点；0 表示不限制
+    pub max_payload_bytes: usize,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            // 加上消息头后仍可放进常见 MTU（IPv6 最小 MTU 为 1280 字节）
+            max_payload_bytes: 1000,
+        }
+    }
+}
+
+/// 自适应心跳配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    /// 每增加这么多个节点，心跳间隔增加一个 `heartbeat_interval`；0 表示不随节点数调整
+    pub peers_per_step: usize,
+    /// 随节点数放大后的心跳间隔上限（秒），不低于 `heartbeat_interval`
+    pub max_interval_secs: u64,
+    /// 把一轮心跳均匀分散在整个间隔内发送（带随机起始偏移），而不是同时发出
+    pub spread: bool,
+    /// 全局每秒最多发送的心跳数，0 表示不限制
+    pub max_pings_per_sec: u32,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            peers_per_step: 1000,
+            max_interval_secs: 300,
+            spread: true,
+            max_pings_per_sec: 1000,
+        }
+    }
+}
+
+/// 后台任务监督配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SupervisorConfig {
+    /// 任务 panic 后首次重启前的等待时间（毫秒），连续崩溃时逐次加倍
+    pub initial_backoff_ms: u64,
+    /// 重启等待时间上限（毫秒）
+    pub max_backoff_ms: u64,
+    /// 统计窗口内崩溃达到该次数的任务视为不健康，0 表示不判定
+    pub unhealthy_after: usize,
+    /// 崩溃统计窗口（秒）
+    pub failure_window_secs: u64,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 60000,
+            unhealthy_after: 3,
+            failure_window_secs: 300,
+        }
+    }
+}
+
+/// 数据包处理 panic 隔离配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PanicIsolationConfig {
+    /// 统计窗口内触发 panic 达到该次数的来源IP被封禁，0 表示不封禁
+    pub ban_after: usize,
+    /// panic 统计窗口（秒）
+    pub window_secs: u64,
+    /// 封禁时长（秒）
+    pub ban_secs: u64,
+}
+
+impl Default for PanicIsolationConfig {
+    fn default() -> Self {
+        Self {
+            ban_after: 3,
+            window_secs: 60,
+            ban_secs: 600,
+        }
+    }
+}
+
+/// 收到数据包时的日志内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketLogMode {
+    /// 不记录
+    Off,
+    /// 只记录消息类型、大小与发送方（debug 级别）
+    #[default]
+    Redacted,
+    /// 记录数据包内容（debug 级别，可能包含用户数据，仅用于调试）
+    Raw,
+}
+
+/// 日志输出配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// 收到数据包时记录的内容
+    pub packets: PacketLogMode,
+    /// `raw` 模式下最多输出的数据包字节数，超出部分截断
+    pub raw_preview_bytes: usize,
+    /// 运行日志的语言（info 及以上级别），`zh` 或 `en`
+    pub language: Language,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self { packets: PacketLogMode::Redacted, raw_preview_bytes: 256, language: Language::Zh }
+    }
+}
+
+/// 协议扫描器识别配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScannerDetectionConfig {
+    /// 统计窗口内发来这么多个无法解析的数据包的来源IP被判定为扫描器，0 表示不识别
+    pub block_after: usize,
+    /// 统计窗口（秒）
+    pub window_secs: u64,
+    /// 首次判定后静默丢弃其数据包的时长（秒），每次再犯翻倍
+    pub block_secs: u64,
+    /// 屏蔽时长上限（秒）
+    pub max_block_secs: u64,
+}
+
+impl Default for ScannerDetectionConfig {
+    fn default() -> Self {
+        Self {
+            block_after: 10,
+            window_secs: 60,
+            block_secs: 60,
+            max_block_secs: 3600,
+        }
+    }
+}
+
+/// 冒用节点身份的握手识别配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpoofGuardConfig {
+    /// 统计窗口（秒）
+    pub window_secs: u64,
+    /// 统计窗口内声称同一节点ID的来源IP超过这么多个即判定为冒用，0 表示不按IP数判定
+    pub max_source_ips: usize,
+    /// 统计窗口内来源端口分布的熵（比特）超过此值即判定为冒用，0 表示不按端口判定
+    pub max_port_entropy_bits: f64,
+    /// 判定后拒绝该节点ID没有有效会话票据的握手的时长（秒）
+    pub block_secs: u64,
+    /// 保留的审计记录条数
+    pub audit_entries: usize,
+}
+
+impl Default for SpoofGuardConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 60,
+            max_source_ips: 3,
+            max_port_entropy_bits: 3.0,
+            block_secs: 60,
+            audit_entries: 256,
+        }
+    }
+}
+
+/// 服务器身份密钥配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdentityConfig {
+    /// 身份私钥文件（32 字节种子的十六进制），不存在时生成并写入；
+    /// 未设置时每次启动使用临时密钥，指纹随之变化，客户端无法固定
+    pub key_file: Option<PathBuf>,
+}
+
+/// 连接数软限制配置（硬限制为 `max_connections`）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionLimitsConfig {
+    /// 软限制：超过后仍接受新节点，但节点发现响应标记服务器繁忙并放慢心跳；为空表示不启用
+    pub soft_limit: Option<usize>,
+    /// 繁忙时心跳间隔与超时判定的放大倍数
+    pub busy_heartbeat_multiplier: u32,
+    /// 达到硬限制拒绝握手时，建议客户端等待的重试秒数
+    pub retry_after_secs: u64,
+}
+
+impl Default for ConnectionLimitsConfig {
+    fn default() -> Self {
+        Self {
+            soft_limit: None,
+            busy_heartbeat_multiplier: 2,
+            retry_after_secs: 30,
+        }
+    }
+}
+
+/// 无状态握手 cookie
+///
+/// 开启后，新来源的握手请求速率超过 `trigger_per_sec` 时，服务器不为请求分配连接与节点，
+/// 只回复一个绑定来源地址的 cookie；客户端带回有效 cookie 重发握手后才进入正常握手流程。
+/// 声明了公钥的握手不论是否开启都须带回 cookie，密钥证明借此绑定来源地址。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HandshakeCookieConfig {
+    /// 是否启用
+    pub enable: bool,
+    /// 每秒新来源握手请求超过该数时要求 cookie；0 表示始终要求
+    pub trigger_per_sec: u32,
+    /// cookie 的有效期（秒）
+    pub lifetime_secs: u64,
+}
+
+impl Default for HandshakeCookieConfig {
+    fn default() -> Self {
+        Self { enable: false, trigger_per_sec: 50, lifetime_secs: 10 }
+    }
+}
+
+/// NAT 保活间隔探测配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepaliveConfig {
+    /// 未探测时建议的保活间隔（秒），通过握手响应下发
+    pub default_interval_secs: u64,
+    /// 建议保活间隔的下限（秒）
+    pub min_interval_secs: u64,
+    /// 第一轮探测的空闲时长（秒），之后每轮翻倍
+    pub initial_probe_secs: u64,
+    /// 探测的最长空闲时长（秒）
+    pub max_probe_secs: u64,
+    /// 发出探测包后等待回显的时间（秒），超时视为 NAT 映射已失效
+    pub probe_grace_secs: u64,
+    /// 建议间隔 = 已确认的映射寿命 × 该系数
+    pub safety_factor: f64,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            default_interval_secs: 25,
+            min_interval_secs: 5,
+            initial_probe_secs: 15,
+            max_probe_secs: 240,
+            probe_grace_secs: 5,
+            safety_factor: 0.8,
+        }
+    }
+}
+
+/// 时间同步与消息时效配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeSyncConfig {
+    /// 消息时间戳（按节点时钟偏差校正后）早于服务器时间超过该秒数即视为过期，0 表示不检查
+    pub max_message_age_secs: u64,
+    /// 尚未完成时间同步的节点允许的时钟偏差（秒）
+    pub max_clock_skew_secs: u64,
+    /// 已完成时间同步的节点在校正后仍允许的误差（毫秒），覆盖秒级时间戳的截断与网络延迟
+    pub sync_tolerance_ms: u64,
+}
+
+impl Default for TimeSyncConfig {
+    fn default() -> Self {
+        Self {
+            max_message_age_secs: 120,
+            max_clock_skew_secs: 300,
+            sync_tolerance_ms: 2000,
+        }
+    }
+}
+
+/// 单个方向的故障注入参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FaultProfile {
+    /// 丢包概率（0.0 ~ 1.0）
+    pub drop_rate: f64,
+    /// 重复发送概率
+    pub duplicate_rate: f64,
+    /// 乱序概率：命中的数据包额外延迟 `reorder_delay_ms`，让后续数据包先到达
+    pub reorder_rate: f64,
+    /// 乱序数据包的额外延迟（毫秒）
+    pub reorder_delay_ms: u64,
+    /// 固定延迟（毫秒）
+    pub latency_ms: u64,
+    /// 在固定延迟之上随机增加 0 ~ `jitter_ms` 毫秒
+    pub jitter_ms: u64,
+}
+
+impl Default for FaultProfile {
+    fn default() -> Self {
+        Self {
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+            reorder_rate: 0.0,
+            reorder_delay_ms: 50,
+            latency_ms: 0,
+            jitter_ms: 0,
+        }
+    }
+}
+
+/// 固定节点
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinnedPeer {
+    pub node_id: Uuid,
+    pub addr: SocketAddr,
+}
+
+/// 固定节点配置：服务器主动保持与这些节点的连接，并始终在节点列表中提供它们
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PinningConfig {
+    pub peers: Vec<PinnedPeer>,
+    /// 固定节点不在线时重新发起握手的间隔（秒）
+    pub reconnect_interval_secs: u64,
+}
+
+impl Default for PinningConfig {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            reconnect_interval_secs: 15,
+        }
+    }
+}
+
+/// 节点可发起的控制查询
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlCommand {
+    GetRoutes,
+    GetStats,
+    GetPeers,
+    /// `ListNodesRequest`
+    ListNodes,
+    /// `TopologyRequest`
+    Topology,
+    /// `GetConfigRequest`，未在 `required_roles` 中列出时要求 `admin`
+    GetConfig,
+}
+
+/// 节点的诊断访问角色，按权限从低到高排列
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerRole {
+    /// 只能查看不涉及拓扑的统计信息
+    Observer,
+    /// 普通成员，可查看节点列表与路由
+    Member,
+    /// 管理者，可执行全部查询且不受频率限制
+    Admin,
+}
+
+/// 控制查询配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// 允许已认证节点发起的控制查询
+    pub allowed: Vec<ControlCommand>,
+    /// 是否仍接受 `Data` 中的 `{"cmd": "get_routes"}` 字符串命令（已弃用）
+    pub legacy_commands: bool,
+    /// 握手时未携带有效访问令牌的节点的角色
+    pub default_role: PeerRole,
+    /// 访问令牌 → 角色；节点在握手元数据 `access_token` 中出示令牌
+    pub tokens: HashMap<String, PeerRole>,
+    /// 各查询要求的最低角色，未列出的查询要求 `member`
+    pub required_roles: HashMap<ControlCommand, PeerRole>,
+    /// 每个节点每分钟最多发起的控制查询数（`admin` 不受限），0 表示不限制
+    pub rate_limit_per_minute: u32,
+}
+
+impl ControlConfig {
+    /// 根据握手时出示的令牌确定节点角色
+    pub fn role_for(&self, token: Option<&str>) -> PeerRole {
+        token
+            .and_then(|t| self.tokens.get(t).copied())
+            .unwrap_or(self.default_role)
+    }
+
+    /// 查询要求的最低角色
+    pub fn required_role(&self, command: ControlCommand) -> PeerRole {
+        let default = match command {
+            ControlCommand::GetConfig => PeerRole::Admin,
+            _ => PeerRole::Member,
+        };
+        self.required_roles.get(&command).copied().unwrap_or(default)
+    }
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            allowed: vec![
+                ControlCommand::GetRoutes,
+                ControlCommand::GetStats,
+                ControlCommand::GetPeers,
+                ControlCommand::ListNodes,
+                ControlCommand::Topology,
+                ControlCommand::GetConfig,
+            ],
+            legacy_commands: true,
+            default_role: PeerRole::Member,
+            tokens: HashMap::new(),
+            required_roles: HashMap::from([(ControlCommand::GetStats, PeerRole::Observer)]),
+            rate_limit_per_minute: 60,
+        }
+    }
+}
+
+/// 套接字参数配置，未设置的项保持操作系统默认值
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// 接收缓冲区大小（SO_RCVBUF，字节）
+    pub recv_buffer_size: Option<usize>,
+    /// 发送缓冲区大小（SO_SNDBUF，字节）
+    pub send_buffer_size: Option<usize>,
+    /// 主端口与STUN端口（控制流量）的DSCP标记（0-63）
+    pub control_dscp: Option<u8>,
+    /// TURN中继端口（中继流量）的DSCP标记（0-63）
+    pub relay_dscp: Option<u8>,
+    /// 发出数据包的TTL（IPv6为跳数限制）
+    pub ttl: Option<u32>,
+    /// 绑定IPv6地址时是否只接受IPv6（IPV6_V6ONLY）
+    pub ipv6_only: Option<bool>,
+    /// 绑定的网络接口名（SO_BINDTODEVICE，仅Linux）
+    pub interface: Option<String>,
+    /// 多宿主主机通告给其他节点的本地地址（附加在监听地址之后）
+    pub advertise_addresses: Vec<SocketAddr>,
+}
+
+/// DNS 引导配置：从域名下的 SRV/TXT 记录获取引导服务器、集群种子与网络ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsBootstrapConfig {
+    /// 引导域名，为空时不启用
+    pub domain: Option<String>,
+    /// DNS 服务器地址，为空时使用 `/etc/resolv.conf` 中的第一个 nameserver
+    pub nameserver: Option<SocketAddr>,
+    /// 重新解析的间隔（秒）
+    pub refresh_interval_secs: u64,
+    /// 单次查询超时（毫秒）
+    pub timeout_ms: u64,
+}
+
+impl Default for DnsBootstrapConfig {
+    fn default() -> Self {
+        Self {
+            domain: None,
+            nameserver: None,
+            refresh_interval_secs: 300,
+            timeout_ms: 2000,
+        }
+    }
+}
+
+/// 故障注入配置（需启用 `chaos` 特性，仅用于测试环境）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    /// 是否启用故障注入
+    pub enable: bool,
+    /// 接收方向（客户端 → 服务器）
+    pub inbound: FaultProfile,
+    /// 发送方向（服务器 → 客户端）
+    pub outbound: FaultProfile,
+}
+
+/// 数据包调度配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// 同时处理的数据包数量上限（不同来源并发，同一来源串行）
+    pub workers: usize,
+    /// 每个来源地址最多排队的数据包数
+    pub max_queue_per_peer: usize,
+    /// 全局最多排队的数据包数
+    pub max_queued: usize,
+    /// 数据包排队超过该时长（毫秒）后直接丢弃，0 表示不丢弃
+    pub packet_ttl_ms: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            workers: 64,
+            max_queue_per_peer: 256,
+            max_queued: 8192,
+            packet_ttl_ms: 5000,
+        }
+    }
+}
+
+/// 离线消息暂存配置：路由消息的目标节点不在线时暂存，待其重新握手后投递
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OfflineStoreConfig {
+    pub enable: bool,
+    /// 每个目标节点最多暂存的消息数，超出时丢弃最早的消息
+    pub max_per_peer: usize,
+    /// 最多为多少个目标节点暂存消息
+    pub max_destinations: usize,
+    /// 消息暂存时长（秒），过期未投递即丢弃
+    pub ttl_secs: u64,
+}
+
+impl Default for OfflineStoreConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            max_per_peer: 100,
+            max_destinations: 10000,
+            ttl_secs: 3600,
+        }
+    }
+}
+
+/// 事件流配置：以 JSON Lines 输出节点上线、认证、断开、路由变化、中继开始等事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventStreamConfig {
+    pub enable: bool,
+    /// 追加写入事件的文件
+    pub file: Option<PathBuf>,
+    /// 订阅事件的 TCP 监听地址，每个连接收到此后发生的事件
+    pub listen_address: Option<SocketAddr>,
+    /// 每个订阅者最多积压的事件数，落后更多时丢弃最早的事件
+    pub buffer: usize,
+}
+
+impl Default for EventStreamConfig {
+    fn default() -> Self {
+        Self { enable: false, file: None, listen_address: None, buffer: 1024 }
+    }
+}
+
+/// 一个 webhook 接收地址
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookEndpoint {
+    /// 接收 POST 的 `http://` 地址，须为回环地址，不支持 https
+    pub url: String,
+    /// HMAC-SHA256 签名密钥，未设置时不签名
+    pub secret: Option<String>,
+    /// 推送的事件类型代码（见事件流），为空时推送全部事件
+    pub events: Vec<String>,
+}
+
+impl Default for WebhookEndpoint {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            secret: None,
+            events: ["peer.authenticated", "peer.disconnected", "peer.banned", "relay.started"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+/// webhook 配置：节点上线、下线、封禁、中继等事件以 HTTP POST 推送给外部服务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub enable: bool,
+    pub endpoints: Vec<WebhookEndpoint>,
+    /// 投递失败后的最多重试次数
+    pub max_retries: u32,
+    /// 首次重试前的等待时间（毫秒），之后每次翻倍
+    pub retry_backoff_ms: u64,
+    /// 单次请求超时（毫秒）
+    pub timeout_ms: u64,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self { enable: false, endpoints: Vec::new(), max_retries: 5, retry_backoff_ms: 500, timeout_ms: 5000 }
+    }
+}
+
+/// 消息策略脚本配置（需启用 `scripting` 特性）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptingConfig {
+    pub enable: bool,
+    /// Lua 脚本路径，启动时加载一次
+    pub script_file: Option<PathBuf>,
+    /// 脚本虚拟机的内存上限（字节），0 表示不限制
+    pub memory_limit_bytes: usize,
+    /// 每次钩子调用最多执行的 Lua 指令数，0 表示不限制
+    pub instruction_limit: u64,
+    /// 脚本出错（含超出上限）时拒绝消息；默认放行
+    pub deny_on_error: bool,
+}
+
+impl Default for ScriptingConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            script_file: None,
+            memory_limit_bytes: 16 * 1024 * 1024,
+            instruction_limit: 1_000_000,
+            deny_on_error: false,
+        }
+    }
+}
+
+/// 中继会话
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayConfig {
+    /// 会话超过该时长（秒）没有转发数据即关闭，并通知双方；0 表示不因空闲关闭。
+    /// 由清理任务按 `cleanup_interval` 检查
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self { idle_timeout_secs: 300 }
+    }
+}
+
+/// 打洞协调时的倒计时信标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PunchTimingConfig {
+    /// 是否在协调直连后向双方发送倒计时信标
+    pub enable: bool,
+    /// 在 GO 之前多少毫秒发送信标（如 `[2000, 1000]` 即 T-2s、T-1s），GO 本身总会发送；
+    /// 最大的一项即协调到 GO 的等待时长
+    pub countdown_ms: Vec<u64>,
+}
+
+impl Default for PunchTimingConfig {
+    fn default() -> Self {
+        Self { enable: false, countdown_ms: vec![2000, 1000] }
+    }
+}
+
+impl PunchTimingConfig {
+    /// 各信标距 GO 的毫秒数，从大到小、去重，最后一项为 0（GO）
+    pub fn schedule(&self) -> Vec<u64> {
+        let mut schedule = self.countdown_ms.clone();
+        schedule.push(0);
+        schedule.sort_unstable_by(|a, b| b.cmp(a));
+        schedule.dedup();
+        schedule
+    }
+}
+
+/// TCP 同时打开（simultaneous open）打洞协调
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TcpPunchConfig {
+    /// 是否协调 TCP 打洞
+    pub enable: bool,
+    /// 双方收到对方端点后距统一连接时间的毫秒数
+    pub lead_ms: u64,
+    /// 提议等待对方应答的时长（秒）
+    pub offer_timeout_secs: u64,
+}
+
+impl Default for TcpPunchConfig {
+    fn default() -> Self {
+        Self { enable: false, lead_ms: 1000, offer_timeout_secs: 10 }
+    }
+}
+
+/// 批量确认
+///
+/// 开启后，发给同一地址的 `Ack` 先积攒 `delay_ms` 毫秒，再合并为一条确认全部消息ID的 `Ack` 发出，
+/// 频繁发送 `requires_ack` 消息的客户端因此少收一半以上的数据报。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AckBatchConfig {
+    /// 是否合并确认（关闭时每条消息立即单独确认）
+    pub enable: bool,
+    /// 首个待确认ID到达后等待合并的毫秒数
+    pub delay_ms: u64,
+    /// 单条 `Ack` 最多确认的ID数，攒满即立即发出
+    pub max_ids: usize,
+}
+
+impl Default for AckBatchConfig {
+    fn default() -> Self {
+        Self { enable: false, delay_ms: 5, max_ids: 32 }
+    }
+}
+
+/// 路径 MTU 探测（DPLPMTUD）
+///
+/// 开启后，服务器在节点握手成功后向其发送逐步增大的填充探测包，记录节点应答的最大大小；
+/// 中继转发给该节点的数据包超过探测出的上限时被拒绝，而不是在路上被悄悄丢弃。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathMtuConfig {
+    /// 是否探测
+    pub enable: bool,
+    /// 不经探测即认为可用的大小（字节）
+    pub base_mtu: usize,
+    /// 探测的上限（字节），默认为以太网 MTU 减去 IPv4/UDP 头
+    pub max_mtu: usize,
+    /// 单个探测包等待应答的时长（毫秒）
+    pub probe_timeout_ms: u64,
+    /// 同一大小连续这么多次无应答才视为超过路径 MTU
+    pub max_probes: u32,
+}
+
+impl Default for PathMtuConfig {
+    fn default() -> Self {
+        Self { enable: false, base_mtu: BASE_PLPMTU, max_mtu: 1472, probe_timeout_ms: 1000, max_probes: 3 }
+    }
+}
+
+/// 节点休眠
+///
+/// 电量受限的节点可以登记休眠：服务器不再向它发心跳、也不按心跳超时移除它，发给它的消息先暂存；
+/// 其他节点要联系它（直连协调、路由消息、中继、远程调用）时，服务器向它的地址发送几个唤醒数据包。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DormantConfig {
+    /// 是否接受节点的休眠登记
+    pub enable: bool,
+    /// 单次休眠的最长时长（秒），过期后按普通节点做心跳超时判定
+    pub max_dormant_secs: u64,
+    /// 休眠期间最多暂存的消息数，超出时丢弃最早的
+    pub max_queued: usize,
+    /// 每次唤醒发送的数据包个数
+    pub knock_count: u32,
+    /// 唤醒数据包之间的间隔（毫秒）
+    pub knock_interval_ms: u64,
+    /// 两次唤醒之间的最短间隔（毫秒），期间的联系请求不再重复唤醒
+    pub knock_gap_ms: u64,
+}
+
+impl Default for DormantConfig {
+    fn default() -> Self {
+        Self { enable: false, max_dormant_secs: 3600, max_queued: 64, knock_count: 3, knock_interval_ms: 200, knock_gap_ms: 5000 }
+    }
+}
+
+/// 运营商级 NAT（CGNAT）与 NAT64 识别
+///
+/// 识别出的节点在握手时打上标记；协调直连时，若双方都位于 CGNAT 之后或任一方经 NAT64 上网，
+/// 服务器不再安排注定失败的打洞，而是让双方直接使用中继（需开启 `allow_symmetric_nat_relay`）。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CarrierNatConfig {
+    /// 是否按识别结果跳过打洞
+    pub enable: bool,
+    /// 同一公网IP下的节点数达到该值时视为 CGNAT，0 表示不按共享IP判断
+    pub shared_ip_threshold: usize,
+    /// NAT64 前缀，节点的观测地址或通告地址落在其中即视为经 NAT64 上网
+    pub nat64_prefixes: Vec<String>,
+}
+
+impl Default for CarrierNatConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            shared_ip_threshold: 8,
+            nat64_prefixes: vec!["64:ff9b::/96".to_string(), "64:ff9b:1::/48".to_string()],
+        }
+    }
+}
+
+/// 节点信息超出限制时的处理方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitAction {
+    /// 截断超出的部分后接受握手（默认）
+    #[default]
+    Truncate,
+    /// 拒绝握手
+    Reject,
+}
+
+/// 握手中节点信息的大小限制：名称、能力与元数据由节点自行声明，并随节点列表下发给其他节点。
+/// 各项为 0 表示不限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeInfoLimitsConfig {
+    /// 节点名称的最大字节数
+    pub max_name_len: usize,
+    /// 能力数量上限
+    pub max_capabilities: usize,
+    /// 单个能力名称的最大字节数
+    pub max_capability_len: usize,
+    /// 元数据条目数上限
+    pub max_metadata_entries: usize,
+    /// 元数据键的最大字节数
+    pub max_metadata_key_len: usize,
+    /// 元数据值的最大字节数
+    pub max_metadata_value_len: usize,
+    /// 附加本地地址数量上限
+    pub max_addresses: usize,
+    /// 超出限制时截断还是拒绝
+    pub action: LimitAction,
+}
+
+impl Default for NodeInfoLimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_name_len: 128,
+            max_capabilities: 32,
+            max_capability_len: 64,
+            max_metadata_entries: 32,
+            max_metadata_key_len: 64,
+            max_metadata_value_len: 1024,
+            max_addresses: 8,
+            action: LimitAction::Truncate,
+        }
+    }
+}
+
+/// 内存统计与软限制：各部分的近似占用超过软限制时主动淘汰，0 表示不限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// 检查占用并执行淘汰的间隔（秒）
+    pub check_interval_secs: u64,
+    /// 节点表软限制（字节），超出时淘汰未完成握手的节点
+    pub peer_table_bytes: usize,
+    /// 路由表软限制（字节），超出时淘汰距离最远的间接路由
+    pub routing_table_bytes: usize,
+    /// 路由去重缓存软限制（字节），超出时淘汰最早的条目
+    pub message_cache_bytes: usize,
+    /// 中继会话软限制（字节），超出时淘汰空闲最久的会话
+    pub relay_bytes: usize,
+    /// 离线消息暂存软限制（字节），超出时淘汰最早暂存的消息
+    pub offline_queue_bytes: usize,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_secs: 10,
+            peer_table_bytes: 0,
+            routing_table_bytes: 0,
+            message_cache_bytes: 0,
+            relay_bytes: 0,
+            offline_queue_bytes: 0,
+        }
+    }
+}
+
+/// 启动自检：校验配置、试绑定各监听端口、解析外部服务域名、检查配置的 STUN 服务器是否可达
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StartupConfig {
+    /// 启动前运行自检，有失败项时不启动
+    pub self_test: bool,
+    /// 等待每个 STUN 服务器应答的时间（毫秒）
+    pub stun_timeout_ms: u64,
+    /// STUN 服务器不可达时视为失败；默认只记录警告
+    pub require_stun: bool,
+    /// 解析 MQTT、遥测、webhook 等外部服务域名的超时（毫秒）
+    pub dns_timeout_ms: u64,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self {
+            self_test: true,
+            stun_timeout_ms: 2000,
+            require_stun: false,
+            dns_timeout_ms: 2000,
+        }
+    }
+}
+
+/// 节点间带宽探测
+///
+/// 服务器让已直连的一方沿 P2P 路径发送一串探测包，由接收方估算带宽并上报。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthConfig {
+    /// 定期探测全部直连会话的间隔（秒），0 表示只在节点请求时探测
+    pub probe_interval_secs: u64,
+    /// 每次探测发送的包数
+    pub packets: u32,
+    /// 探测包大小（字节）
+    pub packet_size: usize,
+    /// 等待接收方上报结果的时长（秒），超时的探测作废
+    pub report_timeout_secs: u64,
+    /// 参考带宽（bit/s）：链路开销 = 参考带宽 / 测得带宽，至少为 1
+    pub reference_bps: u64,
+    /// 测得带宽低于该值（bit/s）的节点对在协调直连时建议优先中继，0 表示不启用
+    pub min_direct_bps: u64,
+}
+
+impl Default for BandwidthConfig {
+    fn default() -> Self {
+        Self {
+            probe_interval_secs: 0,
+            packets: 16,
+            packet_size: 1200,
+            report_timeout_secs: 5,
+            reference_bps: 100_000_000,
+            min_direct_bps: 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
     /// 服务器监听地址
     pub listen_address: SocketAddr,
     
-    /// 最大连接数
+    /// 最大连接数（硬限制，达到后拒绝新的握手）
     pub max_connections: usize,
+
+    /// 连接数软限制
+    pub connection_limits: ConnectionLimitsConfig,
+
+    /// 无状态握手 cookie
+    pub handshake_cookies: HandshakeCookieConfig,
     
-    /// 心跳间隔（秒）
+    /// 心跳间隔（秒），节点较多时按 `heartbeat` 配置放大
     pub heartbeat_interval: u64,
     
     /// 连接超时时间（秒）
     pub connection_timeout: u64,
+
+    /// 清理断开、未完成握手与超时节点的间隔（秒）
+    pub cleanup_interval: u64,
     
     /// 节点发现端口范围
     pub discovery_port_range: (u16, u16),
@@ -159,6 +1419,9 @@ pub struct Config {
     /// 是否启用节点发现
     pub enable_discovery: bool,
 
+    /// 节点列表下发（大小上限与截断策略）
+    pub discovery: DiscoveryConfig,
+
     /// 网络ID（用于网络隔离与校验）
     pub network_id: String,
 
@@ -174,17 +1437,282 @@ pub struct Config {
     /// 是否允许为全对称NAT客户端转发流量
     pub allow_symmetric_nat_relay: bool,
 
+    /// 中继会话
+    pub relay: RelayConfig,
+
+    /// 打洞倒计时信标
+    pub punch_timing: PunchTimingConfig,
+
+    /// TCP 打洞协调
+    pub tcp_punch: TcpPunchConfig,
+
+    /// 运营商级 NAT（CGNAT）与 NAT64 识别
+    pub carrier_nat: CarrierNatConfig,
+
+    /// 批量确认
+    pub ack_batch: AckBatchConfig,
+
+    /// 路径 MTU 探测
+    pub path_mtu: PathMtuConfig,
+
+    /// 节点休眠与唤醒
+    pub dormant: DormantConfig,
+
+    /// 未注册消息处理器时是否回显无法识别的数据消息（仅用于调试，默认丢弃并计数）
+    pub echo_unhandled_data: bool,
+
+    /// 握手中节点信息的大小限制
+    pub node_info_limits: NodeInfoLimitsConfig,
+
+    /// 接受的最大UDP数据包（字节），更大的数据包在解析前丢弃并计数
+    pub max_datagram_size: usize,
+
+    /// 日志输出配置
+    pub logging: LoggingConfig,
+
     /// NAT类型检测配置
     pub nat_detection: NatDetectionConfig,
+
+    /// 路由配置
+    pub routing: RoutingConfig,
+
+    /// 管理接口配置
+    pub admin: AdminConfig,
+
+    /// gRPC 控制面配置
+    pub grpc: GrpcConfig,
+
+    /// MQTT 桥接配置
+    pub mqtt: MqttConfig,
+
+    /// OTLP 遥测导出配置
+    pub telemetry: TelemetryConfig,
+
+    /// 多实例集群配置
+    pub cluster: ClusterConfig,
+
+    /// 消息编码配置
+    pub codec: CodecConfig,
+
+    /// 数据包调度配置
+    pub scheduler: SchedulerConfig,
+
+    /// NAT 保活间隔探测配置
+    pub keepalive: KeepaliveConfig,
+
+    /// 时间同步与消息时效配置
+    pub time_sync: TimeSyncConfig,
+
+    /// 故障注入配置
+    pub chaos: ChaosConfig,
+
+    /// 固定节点配置
+    pub pinning: PinningConfig,
+
+    /// DNS 引导配置
+    pub dns_bootstrap: DnsBootstrapConfig,
+
+    /// 控制查询配置
+    pub control: ControlConfig,
+
+    /// 套接字参数
+    pub network: NetworkConfig,
+
+    /// 离线消息暂存配置
+    pub offline_store: OfflineStoreConfig,
+
+    /// 节点间带宽探测
+    pub bandwidth: BandwidthConfig,
+
+    /// 自适应心跳
+    pub heartbeat: HeartbeatConfig,
+
+    /// 后台任务监督
+    pub supervisor: SupervisorConfig,
+
+    /// 数据包处理 panic 隔离
+    pub panic_isolation: PanicIsolationConfig,
+
+    /// 协议扫描器识别
+    pub scanner_detection: ScannerDetectionConfig,
+
+    /// 冒用节点身份的握手识别
+    pub spoof_guard: SpoofGuardConfig,
+
+    /// 服务器身份密钥（握手响应签名）
+    pub identity: IdentityConfig,
+
+    /// 服务器身份文件，保存本服务器的节点ID，不存在时生成并写入；
+    /// 未设置时每次启动生成新的节点ID，客户端缓存的路由与固定的服务器ID随之失效
+    pub identity_path: Option<PathBuf>,
+
+    /// 结构化事件流
+    pub events: EventStreamConfig,
+
+    /// 事件 webhook
+    pub webhooks: WebhooksConfig,
+
+    /// 消息策略脚本
+    pub scripting: ScriptingConfig,
+
+    /// 内存统计与软限制
+    pub memory: MemoryConfig,
+
+    /// 启动自检
+    pub startup: StartupConfig,
 }
 
 impl Config {
+    /// 读取配置文件，并解析其中的密钥引用与加密段（见 [`secrets::resolve`](crate::secrets::resolve)）
     pub fn from_file(path: &str) -> Result<Self> {
         let content = fs::read_to_string(path)?;
-        let config: Config = serde_json::from_str(&content)?;
+        let mut value: serde_json::Value = serde_json::from_str(&content)?;
+        crate::secrets::resolve(&mut value)?;
+        let config: Config = serde_json::from_value(value)?;
         Ok(config)
     }
     
+    /// 检查配置中无意义或相互矛盾的取值，一次列出全部问题
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+        if self.network_id.trim().is_empty() {
+            problems.push("network_id 不能为空，客户端握手时据此校验网络".to_string());
+        }
+        if self.max_connections == 0 {
+            problems.push("max_connections 不能为 0，否则所有握手都会被拒绝".to_string());
+        }
+        if let Some(soft_limit) = self.connection_limits.soft_limit
+            && soft_limit > self.max_connections
+        {
+            problems.push(format!("connection_limits.soft_limit ({}) 不能大于 max_connections ({})", soft_limit, self.max_connections));
+        }
+        if self.heartbeat_interval == 0 {
+            problems.push("heartbeat_interval 不能为 0（单位为秒）".to_string());
+        }
+        if self.connection_timeout == 0 {
+            problems.push("connection_timeout 不能为 0（单位为秒）".to_string());
+        } else if self.connection_timeout < self.heartbeat_interval {
+            problems.push(format!(
+                "connection_timeout ({}) 小于 heartbeat_interval ({})，节点会在收到下一次心跳前被判定超时",
+                self.connection_timeout, self.heartbeat_interval
+            ));
+        }
+        if self.cleanup_interval == 0 {
+            problems.push("cleanup_interval 不能为 0（单位为秒）".to_string());
+        }
+        let (first, last) = self.discovery_port_range;
+        if first > last {
+            problems.push(format!("discovery_port_range 起止颠倒: [{}, {}]，应为 [{}, {}]", first, last, last, first));
+        } else if self.enable_discovery && first == 0 {
+            problems.push("discovery_port_range 不能从端口 0 开始".to_string());
+        }
+        let (first, last) = self.ice.port_prediction.port_range;
+        if first > last {
+            problems.push(format!("ice.port_prediction.port_range 起止颠倒: [{}, {}]", first, last));
+        }
+        if let Some(lead) = self.punch_timing.countdown_ms.iter().max()
+            && *lead > 10_000
+        {
+            problems.push(format!("punch_timing.countdown_ms 的最大值 {} 超过 10000 毫秒，NAT 映射可能在 GO 之前失效", lead));
+        }
+        for prefix in &self.carrier_nat.nat64_prefixes {
+            let valid = prefix.split_once('/').is_some_and(|(network, len)| {
+                network.parse::<std::net::Ipv6Addr>().is_ok() && len.parse::<u32>().is_ok_and(|len| len <= 128)
+            });
+            if !valid {
+                problems.push(format!("carrier_nat.nat64_prefixes 中的前缀无效: {}", prefix));
+            }
+        }
+        if self.ack_batch.enable && self.ack_batch.max_ids == 0 {
+            problems.push("ack_batch.max_ids 不能为 0".to_string());
+        }
+        // 声明了公钥的握手始终要带回 cookie，未开启洪泛防护时有效期同样生效
+        if self.handshake_cookies.lifetime_secs == 0 {
+            problems.push("handshake_cookies.lifetime_secs 不能为 0".to_string());
+        }
+        if self.spoof_guard.max_port_entropy_bits < 0.0 {
+            problems.push("spoof_guard.max_port_entropy_bits 不能为负数".to_string());
+        }
+        if self.dormant.enable && self.dormant.knock_count == 0 {
+            problems.push("dormant.knock_count 不能为 0".to_string());
+        }
+        if self.path_mtu.base_mtu > self.path_mtu.max_mtu {
+            problems.push(format!("path_mtu.base_mtu ({}) 大于 max_mtu ({})", self.path_mtu.base_mtu, self.path_mtu.max_mtu));
+        }
+        if self.max_datagram_size == 0 {
+            problems.push("max_datagram_size 不能为 0".to_string());
+        }
+        for (section, servers) in [("ice.stun_servers", &self.ice.stun_servers), ("nat_detection.stun_servers", &self.nat_detection.stun_servers)] {
+            for server in servers {
+                let valid = server.rsplit_once(':').is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p > 0));
+                if !valid {
+                    problems.push(format!("{} 中的 {:?} 不是 主机:端口 格式", section, server));
+                }
+            }
+        }
+        for (origin, public_key) in &self.routing.origin_keys {
+            if public_key.len() != 64 || !public_key.bytes().all(|b| b.is_ascii_hexdigit()) {
+                problems.push(format!("routing.origin_keys 中节点 {} 的公钥不是 32 字节的十六进制", origin));
+            }
+        }
+        if self.cluster.enable && self.cluster.cluster_key.is_none() && !self.cluster.bind_address.ip().is_loopback() {
+            problems.push(format!("cluster.bind_address ({}) 不是回环地址，必须设置 cluster_key 认证gossip报文", self.cluster.bind_address));
+        }
+        // 出站HTTP没有TLS，事件与指标只能以明文发往本机（可由本机代理转发到外部）
+        let mut http_targets = Vec::new();
+        if self.telemetry.enable {
+            http_targets.push(("telemetry.endpoint", &self.telemetry.endpoint));
+        }
+        if self.webhooks.enable {
+            http_targets.extend(self.webhooks.endpoints.iter().map(|endpoint| ("webhooks.endpoints.url", &endpoint.url)));
+        }
+        for (field, url) in http_targets {
+            match crate::http_client::HttpUrl::parse(url) {
+                Ok(parsed) if !parsed.is_loopback() => {
+                    problems.push(format!("{} ({}) 不是回环地址；出站HTTP不支持TLS，请发往本机代理", field, url));
+                }
+                Ok(_) => {}
+                Err(e) => problems.push(format!("{} 无效: {}", field, e)),
+            }
+        }
+        if self.stun_server.enable && self.stun_server.port == self.listen_address.port() {
+            problems.push(format!("stun_server.port ({}) 与主监听端口相同，请改用其他端口", self.stun_server.port));
+        }
+        // 控制接口可以踢出节点、排空服务器，开放到回环地址之外时必须设置令牌
+        if self.admin.enable && self.admin.token.is_none() && !self.admin.listen_address.ip().is_loopback() {
+            problems.push(format!("admin.listen_address ({}) 不是回环地址，必须设置 admin.token", self.admin.listen_address));
+        }
+        if self.grpc.enable && self.grpc.token.is_none() && !self.grpc.listen_address.ip().is_loopback() {
+            problems.push(format!("grpc.listen_address ({}) 不是回环地址，必须设置 grpc.token", self.grpc.listen_address));
+        }
+        if self.admin.enable && self.grpc.enable && self.admin.listen_address == self.grpc.listen_address {
+            problems.push(format!("admin 与 grpc 使用了同一监听地址 {}", self.admin.listen_address));
+        }
+        if self.scripting.enable && self.scripting.script_file.is_none() {
+            problems.push("scripting.enable 为 true 时必须设置 scripting.script_file".to_string());
+        }
+        if self.offline_store.enable && self.offline_store.max_destinations == 0 {
+            problems.push("offline_store.max_destinations 为 0 时无法暂存任何消息".to_string());
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let mut message = format!("配置无效（{} 项）:", problems.len());
+        for problem in problems {
+            message.push_str("\n  - ");
+            message.push_str(&problem);
+        }
+        anyhow::bail!(message)
+    }
+
+    /// 序列化为 JSON，已设置的敏感字段替换为 [`REDACTED`](crate::secrets::REDACTED)
+    pub fn to_redacted_json(&self) -> Result<serde_json::Value> {
+        let mut value = serde_json::to_value(self)?;
+        crate::secrets::redact(&mut value);
+        Ok(value)
+    }
+
     #[allow(dead_code)]
     pub fn to_file(&self, path: &str) -> Result<()> {
         let content = serde_json::to_string_pretty(self)?;
@@ -198,16 +1726,60 @@ impl Default for Config {
         Self {
             listen_address: "127.0.0.1:8080".parse().unwrap(),
             max_connections: 100,
+            connection_limits: ConnectionLimitsConfig::default(),
+            handshake_cookies: HandshakeCookieConfig::default(),
             heartbeat_interval: 30,
             connection_timeout: 60,
+            cleanup_interval: 30,
             discovery_port_range: (8081, 8090),
             enable_discovery: true,
+            discovery: DiscoveryConfig::default(),
             network_id: "p2p_default".to_string(),
             peerlist_broadcast_debounce_ms: 300,
             ice: IceConfig::default(),
             stun_server: StunServerConfig::default(),
             allow_symmetric_nat_relay: false,  // 默认不允许为全对称NAT转发流量
+            relay: RelayConfig::default(),
+            punch_timing: PunchTimingConfig::default(),
+            tcp_punch: TcpPunchConfig::default(),
+            carrier_nat: CarrierNatConfig::default(),
+            ack_batch: AckBatchConfig::default(),
+            path_mtu: PathMtuConfig::default(),
+            dormant: DormantConfig::default(),
+            echo_unhandled_data: false,
+            node_info_limits: NodeInfoLimitsConfig::default(),
+            max_datagram_size: 16 * 1024,
+            logging: LoggingConfig::default(),
             nat_detection: NatDetectionConfig::default(),
+            routing: RoutingConfig::default(),
+            admin: AdminConfig::default(),
+            grpc: GrpcConfig::default(),
+            mqtt: MqttConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            cluster: ClusterConfig::default(),
+            codec: CodecConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            keepalive: KeepaliveConfig::default(),
+            time_sync: TimeSyncConfig::default(),
+            chaos: ChaosConfig::default(),
+            pinning: PinningConfig::default(),
+            dns_bootstrap: DnsBootstrapConfig::default(),
+            control: ControlConfig::default(),
+            network: NetworkConfig::default(),
+            offline_store: OfflineStoreConfig::default(),
+            bandwidth: BandwidthConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+            supervisor: SupervisorConfig::default(),
+            panic_isolation: PanicIsolationConfig::default(),
+            scanner_detection: ScannerDetectionConfig::default(),
+            spoof_guard: SpoofGuardConfig::default(),
+            identity: IdentityConfig::default(),
+            identity_path: None,
+            events: EventStreamConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            scripting: ScriptingConfig::default(),
+            memory: MemoryConfig::default(),
+            startup: StartupConfig::default(),
         }
     }
 }
\ No newline at end of file
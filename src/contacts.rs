@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// 通信关系的有效窗口：超过该时间未再通信的节点不再接收下线通知
+pub const CONTACT_WINDOW: Duration = Duration::from_secs(300);
+
+/// 节点之间最近的通信关系
+///
+/// 记录近期经服务器协调直连、互发路由消息或中继数据的节点对。节点下线时据此定向发送
+/// `PeerDown` 通知，对端无需等待全量节点列表广播即可失效相应的 P2P 会话。
+#[derive(Debug)]
+pub struct RecentContacts {
+    window: Duration,
+    contacts: Mutex<HashMap<Uuid, HashMap<Uuid, Instant>>>,
+}
+
+impl Default for RecentContacts {
+    fn default() -> Self {
+        Self::new(CONTACT_WINDOW)
+    }
+}
+
+impl RecentContacts {
+    pub fn new(window: Duration) -> Self {
+        Self { window, contacts: Mutex::new(HashMap::new()) }
+    }
+
+    /// 记录一次双向通信关系
+    pub fn record(&self, a: Uuid, b: Uuid) {
+        if a == b {
+            return;
+        }
+        let now = Instant::now();
+        let mut contacts = self.contacts.lock().unwrap();
+        for (from, to) in [(a, b), (b, a)] {
+            let peers = contacts.entry(from).or_default();
+            peers.retain(|_, seen| now.duration_since(*seen) <= self.window);
+            peers.insert(to, now);
+        }
+    }
+
+    /// 窗口内与节点通信过的节点（不移除记录）
+    pub fn interested(&self, peer_id: &Uuid) -> Vec<Uuid> {
+        let now = Instant::now();
+        let contacts = self.contacts.lock().unwrap();
+        contacts
+            .get(peer_id)
+            .map(|peers| {
+                peers
+                    .iter()
+                    .filter(|(_, seen)| now.duration_since(**seen) <= self.window)
+                    .map(|(id, _)| *id)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 移除节点的全部记录，返回窗口内与其通信过的节点
+    pub fn take_interested(&self, peer_id: &Uuid) -> Vec<Uuid> {
+        let now = Instant::now();
+        let mut contacts = self.contacts.lock().unwrap();
+        let Some(peers) = contacts.remove(peer_id) else { return Vec::new() };
+        for other in peers.keys() {
+            if let Some(other_peers) = contacts.get_mut(other) {
+                other_peers.remove(peer_id);
+                if other_peers.is_empty() {
+                    contacts.remove(other);
+                }
+            }
+        }
+        peers
+            .into_iter()
+            .filter(|(_, seen)| now.duration_since(*seen) <= self.window)
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_interested_is_symmetric_and_windowed() {
+        let contacts = RecentContacts::new(Duration::from_secs(60));
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        contacts.record(a, b);
+        contacts.record(c, a);
+        contacts.record(b, b);
+
+        let mut interested = contacts.take_interested(&a);
+        interested.sort();
+        let mut expected = vec![b, c];
+        expected.sort();
+        assert_eq!(interested, expected);
+        // 对端的反向记录已一并清除
+        assert!(contacts.take_interested(&b).is_empty());
+
+        let expired = RecentContacts::new(Duration::ZERO);
+        expired.record(a, b);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(expired.take_interested(&a).is_empty());
+    }
+}
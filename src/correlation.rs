@@ -0,0 +1,247 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::protocol::Message;
+
+/// 等待应答的请求，按应答消息的 `reply_to` 与请求ID匹配
+///
+/// 服务器（[`Requester`](crate::handler::Requester)）与客户端库（`P2PClient::request`）共用，
+/// 应用无需自行比对消息ID。
+#[derive(Debug, Default)]
+pub struct PendingReplies {
+    waiting: Mutex<HashMap<Uuid, oneshot::Sender<Message>>>,
+}
+
+impl PendingReplies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记 `request` 后执行 `send`，等待应答直到 `wait` 超时
+    pub async fn request<F>(&self, request: &Message, wait: Duration, send: F) -> Result<Message>
+    where
+        F: Future<Output = Result<()>>,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.waiting.lock().unwrap().insert(request.id, tx);
+        let result = async {
+            send.await?;
+            match tokio::time::timeout(wait, rx).await {
+                Ok(Ok(reply)) => Ok(reply),
+                _ => anyhow::bail!("请求 {} 在 {:?} 内未收到应答", request.id, wait),
+            }
+        }
+        .await;
+        self.waiting.lock().unwrap().remove(&request.id);
+        result
+    }
+
+    /// 把应答交给等待中的请求；不是应答或请求已超时时原样返回
+    pub fn resolve(&self, message: Message) -> Option<Message> {
+        let Some(request_id) = message.reply_to else { return Some(message) };
+        let Some(waiter) = self.waiting.lock().unwrap().remove(&request_id) else { return Some(message) };
+        // 接收端已放弃等待（超时）时 send 失败，此时应答丢弃
+        let _ = waiter.send(message);
+        None
+    }
+
+    /// 等待应答的请求数
+    pub fn len(&self) -> usize {
+        self.waiting.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// 握手请求在关联表中的保留时间：覆盖客户端的重传间隔即可
+pub const HANDSHAKE_DEDUP_TTL: Duration = Duration::from_secs(10);
+
+/// 握手请求的序号：带 `sequence_number` 时取序号，否则取消息ID（UDP 重复的数据包消息ID相同）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HandshakeSeq {
+    Sequence(u32),
+    Id(Uuid),
+}
+
+impl HandshakeSeq {
+    pub fn of(message: &Message) -> Self {
+        message.sequence_number.map_or(Self::Id(message.id), Self::Sequence)
+    }
+}
+
+/// 握手请求在关联表中的状态
+#[derive(Debug, Clone)]
+pub enum HandshakeState {
+    /// 首次收到，应正常处理
+    New,
+    /// 同一请求仍在处理中，重复的数据包应丢弃
+    InFlight,
+    /// 已应答，重发同一应答即可
+    Answered(Message),
+    /// 同一地址已发起更新的握手，旧请求的重传应丢弃
+    Superseded,
+}
+
+#[derive(Debug)]
+struct PendingHandshake {
+    started: Instant,
+    response: Option<Message>,
+    superseded: bool,
+}
+
+/// 进行中与刚应答的握手请求，按 (对端地址, 请求序号) 关联
+///
+/// UDP 可能重复投递握手请求，客户端也会在未收到应答时重传。同一请求只处理一次，
+/// 之后的重复请求原样重发首次的应答，不会重复签发会话票据或推送节点列表；
+/// 同一地址交错发起的多次握手以最新的一次为准。条目在 [`HANDSHAKE_DEDUP_TTL`] 后过期。
+#[derive(Debug)]
+pub struct PendingHandshakes {
+    ttl: Duration,
+    entries: Mutex<HashMap<(SocketAddr, HandshakeSeq), PendingHandshake>>,
+}
+
+impl Default for PendingHandshakes {
+    fn default() -> Self {
+        Self::new(HANDSHAKE_DEDUP_TTL)
+    }
+}
+
+impl PendingHandshakes {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// 登记来自 `addr` 的握手请求并返回其状态；首次出现的请求会使该地址较早的请求失效
+    pub fn begin(&self, addr: SocketAddr, request: &Message) -> HandshakeState {
+        let now = Instant::now();
+        let seq = HandshakeSeq::of(request);
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| now.duration_since(entry.started) <= self.ttl);
+        if let Some(entry) = entries.get(&(addr, seq)) {
+            return match (&entry.response, entry.superseded) {
+                (_, true) => HandshakeState::Superseded,
+                (Some(response), false) => HandshakeState::Answered(response.clone()),
+                (None, false) => HandshakeState::InFlight,
+            };
+        }
+        for ((entry_addr, _), entry) in entries.iter_mut() {
+            if *entry_addr == addr {
+                entry.superseded = true;
+                entry.response = None;
+            }
+        }
+        entries.insert((addr, seq), PendingHandshake { started: now, response: None, superseded: false });
+        HandshakeState::New
+    }
+
+    /// 记录请求的应答，供重复请求重发
+    pub fn answer(&self, addr: SocketAddr, request: &Message, response: &Message) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(&(addr, HandshakeSeq::of(request)))
+            && !entry.superseded
+        {
+            entry.response = Some(response.clone());
+        }
+    }
+
+    /// 请求处理失败：移除登记，重传的请求会重新处理
+    pub fn abandon(&self, addr: SocketAddr, request: &Message) {
+        self.entries.lock().unwrap().remove(&(addr, HandshakeSeq::of(request)));
+    }
+
+    /// 移除地址的全部登记（节点断开后同一地址可重新从头握手）
+    pub fn forget(&self, addr: &SocketAddr) {
+        self.entries.lock().unwrap().retain(|(entry_addr, _), _| entry_addr != addr);
+    }
+
+    /// 关联表中的请求数（含未过期的已应答请求）
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::MessageType;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_reply_matched_by_reply_to() {
+        let pending = Arc::new(PendingReplies::new());
+        let request = Message::data(serde_json::json!({ "q": 1 }));
+
+        let resolver = pending.clone();
+        let reply = request.respond(serde_json::json!({ "a": 2 }));
+        let answered = pending
+            .request(&request, Duration::from_secs(1), async move {
+                // 不相关的消息原样返回
+                assert!(resolver.resolve(Message::data(serde_json::Value::Null)).is_some());
+                assert!(resolver.resolve(reply).is_none());
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(answered.payload["a"], 2);
+
+        // 超时后清理登记，迟到的应答原样返回
+        let late = Message::data(serde_json::Value::Null);
+        assert!(pending.request(&late, Duration::from_millis(10), async { Ok(()) }).await.is_err());
+        assert!(pending.is_empty());
+        assert!(pending.resolve(late.respond(serde_json::Value::Null)).is_some());
+    }
+
+    #[test]
+    fn test_duplicate_handshake_resends_answer() {
+        let pending = PendingHandshakes::default();
+        let addr: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let request = Message::data(serde_json::Value::Null);
+        let response = request.respond(serde_json::json!({ "ok": true }));
+
+        assert!(matches!(pending.begin(addr, &request), HandshakeState::New));
+        assert!(matches!(pending.begin(addr, &request), HandshakeState::InFlight));
+        pending.answer(addr, &request, &response);
+        let HandshakeState::Answered(resent) = pending.begin(addr, &request) else { panic!("未重发应答") };
+        assert_eq!(resent.id, response.id);
+
+        // 带序号的请求以序号关联，其他地址的相同序号互不影响
+        let first = Message::new_with_ack(MessageType::HandshakeRequest, serde_json::Value::Null, addr, 1);
+        let mut retry = first.clone();
+        retry.id = Uuid::new_v4();
+        assert!(matches!(pending.begin(addr, &first), HandshakeState::New));
+        assert!(matches!(pending.begin(addr, &retry), HandshakeState::InFlight));
+        assert!(matches!(pending.begin("127.0.0.1:4001".parse().unwrap(), &first), HandshakeState::New));
+
+        // 同一地址更新的握手使旧请求失效
+        let second = Message::new_with_ack(MessageType::HandshakeRequest, serde_json::Value::Null, addr, 2);
+        assert!(matches!(pending.begin(addr, &second), HandshakeState::New));
+        assert!(matches!(pending.begin(addr, &first), HandshakeState::Superseded));
+        assert!(matches!(pending.begin(addr, &request), HandshakeState::Superseded));
+
+        // 处理失败的请求可以重新处理
+        pending.abandon(addr, &second);
+        assert!(matches!(pending.begin(addr, &second), HandshakeState::New));
+    }
+
+    #[test]
+    fn test_handshake_entries_expire() {
+        let pending = PendingHandshakes::new(Duration::from_millis(10));
+        let addr: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let request = Message::data(serde_json::Value::Null);
+        assert!(matches!(pending.begin(addr, &request), HandshakeState::New));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(matches!(pending.begin(addr, &request), HandshakeState::New));
+        assert_eq!(pending.len(), 1);
+    }
+}
@@ -0,0 +1,234 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use anyhow::{Context, Result};
+use base64::Engine;
+use log::debug;
+use crate::tr;
+use sha1::{Digest, Sha1};
+
+use crate::admin::{self, AdminState, HttpRequest};
+use crate::metrics::MetricsSnapshot;
+
+/// 内嵌的监控面板页面
+pub const INDEX_HTML: &str = include_str!("../assets/dashboard.html");
+
+/// RFC 6455 握手使用的固定GUID
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// 客户端帧负载上限
+const MAX_CLIENT_FRAME: u64 = 64 * 1024;
+/// 每次推送附带的最近日志条数
+const FEED_LOG_LINES: usize = 50;
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// 计算 `Sec-WebSocket-Accept`
+fn accept_key(client_key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(client_key.trim().as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+/// 编码服务端帧（服务端发送的帧不加掩码）
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// 读取一个客户端帧，返回（操作码，去掩码后的负载）
+async fn read_frame<R: AsyncReadExt + Unpin>(reader: &mut R) -> Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header).await?;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let mut len = (header[1] & 0x7F) as u64;
+    if len == 126 {
+        let mut ext = [0u8; 2];
+        reader.read_exact(&mut ext).await?;
+        len = u16::from_be_bytes(ext) as u64;
+    } else if len == 127 {
+        let mut ext = [0u8; 8];
+        reader.read_exact(&mut ext).await?;
+        len = u64::from_be_bytes(ext);
+    }
+    if len > MAX_CLIENT_FRAME {
+        return Err(anyhow::anyhow!("WebSocket帧过大: {} bytes", len));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).await?;
+    if masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    Ok((opcode, payload))
+}
+
+/// 读取任务送来的客户端帧（操作码，负载）
+type FrameReceiver = mpsc::Receiver<Result<(u8, Vec<u8>)>>;
+
+/// 在独立任务中持续读取客户端帧并送入通道，读取出错或收到关闭帧后结束
+///
+/// `read_frame` 由多次 `read_exact` 组成，不能作为 `select!` 的分支被取消，否则读到一半的帧会丢失。
+fn spawn_frame_reader<R>(mut reader: R) -> (FrameReceiver, JoinHandle<()>)
+where
+    R: AsyncReadExt + Unpin + Send + 'static,
+{
+    let (frame_tx, frames) = mpsc::channel(8);
+    let task = tokio::spawn(async move {
+        loop {
+            let frame = read_frame(&mut reader).await;
+            let stop = !matches!(&frame, Ok((opcode, _)) if *opcode != OPCODE_CLOSE);
+            if frame_tx.send(frame).await.is_err() || stop {
+                break;
+            }
+        }
+    });
+    (frames, task)
+}
+
+/// 构建一次实时推送的数据
+async fn build_update(state: &AdminState, previous: &mut (MetricsSnapshot, Instant)) -> serde_json::Value {
+    let metrics = state.metrics.snapshot();
+    let elapsed = previous.1.elapsed().as_secs_f64();
+    let rates = metrics.rates_since(&previous.0, elapsed);
+    *previous = (metrics.clone(), Instant::now());
+
+    serde_json::json!({
+        "type": "update",
+        "stats": admin::stats_json(state).await,
+        "rates": rates,
+        "peers": admin::peer_summaries(&state.peer_manager).await,
+        "routes": admin::routes_json(&state.message_router).await["routes"],
+        "relay": {
+            "packets": metrics.relay_packets,
+            "bytes": metrics.relay_bytes,
+            "sessions": state.relay_sessions.snapshot(),
+        },
+        "logs": state.recent_logs.as_ref().map(|l| l.recent(FEED_LOG_LINES)).unwrap_or_default(),
+    })
+}
+
+/// 升级为WebSocket并周期性推送服务器状态
+pub async fn serve_websocket(mut stream: TcpStream, request: &HttpRequest, state: Arc<AdminState>) -> Result<()> {
+    // 浏览器不限制跨站 WebSocket 连接，须自行校验来源页面
+    if !admin::is_allowed_origin(request.header("origin"), &state.config) {
+        return admin::HttpResponse::error(403, "Origin 不在允许范围内").write_to(&mut stream).await;
+    }
+    let key = request
+        .header("sec-websocket-key")
+        .context("缺少 Sec-WebSocket-Key")?;
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+    stream.write_all(handshake.as_bytes()).await?;
+
+    let (reader, mut writer) = stream.into_split();
+    let (mut frames, reader_task) = spawn_frame_reader(reader);
+    let result = push_updates(&mut writer, &mut frames, &state).await;
+    reader_task.abort();
+    result
+}
+
+/// 周期性推送服务器状态，并应答读取任务送来的客户端帧
+async fn push_updates<W: AsyncWriteExt + Unpin>(
+    writer: &mut W,
+    frames: &mut FrameReceiver,
+    state: &AdminState,
+) -> Result<()> {
+    let mut ticker = tokio::time::interval(Duration::from_millis(state.config.dashboard_refresh_ms.max(100)));
+    let mut previous = (state.metrics.snapshot(), Instant::now());
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                let update = build_update(state, &mut previous).await;
+                writer.write_all(&encode_frame(OPCODE_TEXT, update.to_string().as_bytes())).await?;
+            }
+            frame = frames.recv() => {
+                let Some(frame) = frame else { return Ok(()) };
+                let (opcode, payload) = frame?;
+                match opcode {
+                    OPCODE_CLOSE => {
+                        let _ = writer.write_all(&encode_frame(OPCODE_CLOSE, &[])).await;
+                        debug!("{}", tr!("监控面板WebSocket连接关闭", "Dashboard WebSocket connection closed"));
+                        return Ok(());
+                    }
+                    OPCODE_PING => {
+                        writer.write_all(&encode_frame(OPCODE_PONG, &payload)).await?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key_rfc6455_example() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[tokio::test]
+    async fn test_masked_frame_roundtrip() {
+        let mask = [1u8, 2, 3, 4];
+        let payload = b"hello";
+        let mut frame = vec![0x81, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+
+        let (opcode, decoded) = read_frame(&mut frame.as_slice()).await.unwrap();
+        assert_eq!(opcode, OPCODE_TEXT);
+        assert_eq!(decoded, payload);
+        assert_eq!(encode_frame(OPCODE_TEXT, payload)[1], payload.len() as u8);
+    }
+
+    #[tokio::test]
+    async fn test_frame_split_across_reads_is_not_lost() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let (mut frames, _task) = spawn_frame_reader(server);
+
+        // 一个帧分两次到达，中间的等待期间读取任务不会丢弃已读到的头部
+        let frame = [0x89, 0x82, 0, 0, 0, 0, b'h', b'i'];
+        client.write_all(&frame[..3]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.write_all(&frame[3..]).await.unwrap();
+        let (opcode, payload) = frames.recv().await.unwrap().unwrap();
+        assert_eq!((opcode, payload.as_slice()), (OPCODE_PING, &b"hi"[..]));
+
+        // 收到关闭帧后读取任务结束
+        client.write_all(&[0x88, 0x80, 0, 0, 0, 0]).await.unwrap();
+        assert_eq!(frames.recv().await.unwrap().unwrap().0, OPCODE_CLOSE);
+        assert!(frames.recv().await.is_none());
+    }
+}
@@ -0,0 +1,372 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use crate::tr;
+use rand::Rng;
+use serde::Serialize;
+use tokio::net::UdpSocket;
+
+use crate::config::DnsBootstrapConfig;
+
+/// DNS记录类型常量
+pub const DNS_TYPE_A: u16 = 1;
+pub const DNS_TYPE_TXT: u16 = 16;
+pub const DNS_TYPE_AAAA: u16 = 28;
+pub const DNS_TYPE_SRV: u16 = 33;
+
+const DNS_CLASS_IN: u16 = 1;
+const DNS_RCODE_NXDOMAIN: u16 = 3;
+
+/// 面向客户端的服务器所在的 SRV 记录前缀
+pub const SERVER_SRV_PREFIX: &str = "_p2p._udp";
+/// 集群 gossip 种子所在的 SRV 记录前缀
+pub const GOSSIP_SRV_PREFIX: &str = "_p2p-gossip._udp";
+/// 网络参数所在的 TXT 记录前缀
+pub const TXT_PREFIX: &str = "_p2p";
+
+/// 解析出的资源记录数据
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Srv { priority: u16, weight: u16, port: u16, target: String },
+    Txt(Vec<String>),
+    Other(u16),
+}
+
+/// 资源记录（来自应答区或附加区）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub data: RecordData,
+}
+
+/// 构造一个递归查询报文
+pub fn build_query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>> {
+    let mut packet = Vec::with_capacity(name.len() + 18);
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&0x0100u16.to_be_bytes()); // RD
+    packet.extend_from_slice(&1u16.to_be_bytes());
+    packet.extend_from_slice(&[0; 6]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(anyhow!("无效的域名: {}", name));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+    Ok(packet)
+}
+
+fn read_u16(packet: &[u8], offset: usize) -> Result<u16> {
+    packet.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| anyhow!("DNS报文过短"))
+}
+
+/// 读取（可能经过压缩的）域名，返回域名与名称之后的偏移
+fn read_name(packet: &[u8], mut offset: usize) -> Result<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // 压缩指针最多跟随的次数，防止恶意报文构成环
+    for _ in 0..64 {
+        let len = *packet.get(offset).ok_or_else(|| anyhow!("DNS报文过短"))? as usize;
+        if len & 0xC0 == 0xC0 {
+            let pointer = (read_u16(packet, offset)? & 0x3FFF) as usize;
+            end.get_or_insert(offset + 2);
+            offset = pointer;
+        } else if len == 0 {
+            return Ok((labels.join("."), end.unwrap_or(offset + 1)));
+        } else {
+            let label = packet.get(offset + 1..offset + 1 + len).ok_or_else(|| anyhow!("DNS报文过短"))?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            offset += 1 + len;
+        }
+    }
+    Err(anyhow!("DNS名称压缩指针过多"))
+}
+
+/// 解析应答报文，返回应答区与附加区的记录；域名不存在时返回空列表
+pub fn parse_response(packet: &[u8], id: u16) -> Result<Vec<DnsRecord>> {
+    if read_u16(packet, 0)? != id {
+        return Err(anyhow!("DNS应答ID不匹配"));
+    }
+    let flags = read_u16(packet, 2)?;
+    if flags & 0x8000 == 0 {
+        return Err(anyhow!("不是DNS应答报文"));
+    }
+    match flags & 0x000F {
+        0 => {}
+        DNS_RCODE_NXDOMAIN => return Ok(Vec::new()),
+        rcode => return Err(anyhow!("DNS查询失败，RCODE={}", rcode)),
+    }
+    let questions = read_u16(packet, 4)?;
+    let records = read_u16(packet, 6)? as usize + read_u16(packet, 8)? as usize + read_u16(packet, 10)? as usize;
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = read_name(packet, offset)?.1 + 4;
+    }
+
+    let mut result = Vec::with_capacity(records);
+    for _ in 0..records {
+        let (name, next) = read_name(packet, offset)?;
+        let rtype = read_u16(packet, next)?;
+        let rdlength = read_u16(packet, next + 8)? as usize;
+        let start = next + 10;
+        let rdata = packet.get(start..start + rdlength).ok_or_else(|| anyhow!("DNS记录数据过短"))?;
+        let data = match rtype {
+            DNS_TYPE_A if rdlength == 4 => RecordData::A(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3])),
+            DNS_TYPE_AAAA if rdlength == 16 => {
+                let octets: [u8; 16] = rdata.try_into()?;
+                RecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            DNS_TYPE_SRV if rdlength >= 7 => RecordData::Srv {
+                priority: read_u16(packet, start)?,
+                weight: read_u16(packet, start + 2)?,
+                port: read_u16(packet, start + 4)?,
+                target: read_name(packet, start + 6)?.0,
+            },
+            DNS_TYPE_TXT => {
+                let mut strings = Vec::new();
+                let mut pos = 0;
+                while pos < rdata.len() {
+                    let len = rdata[pos] as usize;
+                    let text = rdata.get(pos + 1..pos + 1 + len).ok_or_else(|| anyhow!("TXT记录数据过短"))?;
+                    strings.push(String::from_utf8_lossy(text).into_owned());
+                    pos += 1 + len;
+                }
+                RecordData::Txt(strings)
+            }
+            other => RecordData::Other(other),
+        };
+        result.push(DnsRecord { name, data });
+        offset = start + rdlength;
+    }
+    Ok(result)
+}
+
+/// 读取系统配置的第一个 DNS 服务器
+pub fn system_nameserver() -> Option<SocketAddr> {
+    let content = std::fs::read_to_string("/etc/resolv.conf").ok()?;
+    content.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("nameserver"), Some(addr)) => addr.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 53)),
+            _ => None,
+        }
+    })
+}
+
+/// DNS 引导结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BootstrapInfo {
+    /// 面向客户端的服务器，按 SRV 优先级与权重排序
+    pub servers: Vec<SocketAddr>,
+    /// 集群 gossip 种子
+    pub seeds: Vec<SocketAddr>,
+    /// TXT 记录中的网络ID
+    pub network_id: Option<String>,
+}
+
+/// DNS 引导解析器
+///
+/// 在配置的域名下查询：
+/// - `_p2p._udp.<domain>` SRV：面向客户端的服务器地址
+/// - `_p2p-gossip._udp.<domain>` SRV：集群 gossip 种子
+/// - `_p2p.<domain>` TXT：`network_id=<id>` 形式的网络参数
+///
+/// 客户端可直接使用该解析器获取服务器地址，避免在配置中写死IP。
+#[derive(Debug, Clone)]
+pub struct DnsBootstrap {
+    domain: String,
+    nameserver: SocketAddr,
+    timeout: Duration,
+}
+
+impl DnsBootstrap {
+    pub fn new(domain: impl Into<String>, nameserver: SocketAddr, timeout: Duration) -> Self {
+        Self { domain: domain.into(), nameserver, timeout }
+    }
+
+    /// 按配置创建，未配置域名或找不到 DNS 服务器时返回错误
+    pub fn from_config(config: &DnsBootstrapConfig) -> Result<Self> {
+        let domain = config.domain.clone().ok_or_else(|| anyhow!("未配置DNS引导域名"))?;
+        let nameserver = config.nameserver.or_else(system_nameserver)
+            .ok_or_else(|| anyhow!("未配置DNS服务器且无法读取 /etc/resolv.conf"))?;
+        Ok(Self::new(domain, nameserver, Duration::from_millis(config.timeout_ms)))
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// 解析全部引导记录
+    pub async fn resolve(&self) -> Result<BootstrapInfo> {
+        let servers = self.resolve_srv(&format!("{}.{}", SERVER_SRV_PREFIX, self.domain)).await?;
+        let seeds = self.resolve_srv(&format!("{}.{}", GOSSIP_SRV_PREFIX, self.domain)).await?;
+        let records = self.query(&format!("{}.{}", TXT_PREFIX, self.domain), DNS_TYPE_TXT).await?;
+        let network_id = records.iter()
+            .filter_map(|r| match &r.data {
+                RecordData::Txt(strings) => Some(strings),
+                _ => None,
+            })
+            .flatten()
+            .find_map(|s| s.strip_prefix("network_id=").map(str::to_string));
+        Ok(BootstrapInfo { servers, seeds, network_id })
+    }
+
+    /// 查询 SRV 记录并解析目标地址：优先使用附加区的 A/AAAA 记录，否则查询系统解析器
+    pub async fn resolve_srv(&self, name: &str) -> Result<Vec<SocketAddr>> {
+        let records = self.query(name, DNS_TYPE_SRV).await?;
+        let mut targets: Vec<(u16, u16, u16, &str)> = records.iter()
+            .filter_map(|r| match &r.data {
+                // 目标为 "." 表示该服务不可用
+                RecordData::Srv { priority, weight, port, target } if !target.is_empty() => {
+                    Some((*priority, *weight, *port, target.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+        targets.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+        let mut addrs = Vec::new();
+        for (_, _, port, target) in targets {
+            let glue: Vec<SocketAddr> = records.iter()
+                .filter(|r| r.name.eq_ignore_ascii_case(target))
+                .filter_map(|r| match r.data {
+                    RecordData::A(ip) => Some(SocketAddr::new(IpAddr::V4(ip), port)),
+                    RecordData::Aaaa(ip) => Some(SocketAddr::new(IpAddr::V6(ip), port)),
+                    _ => None,
+                })
+                .collect();
+            if glue.is_empty() {
+                match tokio::net::lookup_host((target, port)).await {
+                    Ok(resolved) => addrs.extend(resolved),
+                    Err(e) => debug!("{}", tr!("解析SRV目标 {} 失败: {}", "Failed to resolve SRV target {}: {}", target, e)),
+                }
+            } else {
+                addrs.extend(glue);
+            }
+        }
+        addrs.dedup();
+        Ok(addrs)
+    }
+
+    /// 发送一次查询并返回应答区与附加区的记录
+    pub async fn query(&self, name: &str, qtype: u16) -> Result<Vec<DnsRecord>> {
+        let id: u16 = rand::thread_rng().r#gen();
+        let query = build_query(id, name, qtype)?;
+        let bind: SocketAddr = if self.nameserver.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse()?;
+        let socket = UdpSocket::bind(bind).await?;
+        socket.send_to(&query, self.nameserver).await
+            .context(format!("发送DNS查询到 {} 失败", self.nameserver))?;
+
+        let mut buffer = vec![0u8; 4096];
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let (len, from) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await
+                .map_err(|_| anyhow!("DNS查询 {} 超时", name))??;
+            // 忽略来源不符或ID不匹配的报文
+            if from != self.nameserver || read_u16(&buffer[..len], 0).ok() != Some(id) {
+                continue;
+            }
+            return parse_response(&buffer[..len], id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 追加一条资源记录，名称使用指向问题区域名的压缩指针
+    fn push_record(packet: &mut Vec<u8>, rtype: u16, rdata: &[u8]) {
+        packet.extend_from_slice(&[0xC0, 12]);
+        packet.extend_from_slice(&rtype.to_be_bytes());
+        packet.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+        packet.extend_from_slice(&60u32.to_be_bytes());
+        packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        packet.extend_from_slice(rdata);
+    }
+
+    fn srv_rdata(priority: u16, weight: u16, port: u16, target: &str) -> Vec<u8> {
+        let mut rdata = Vec::new();
+        for value in [priority, weight, port] {
+            rdata.extend_from_slice(&value.to_be_bytes());
+        }
+        if target == "." {
+            rdata.push(0);
+        } else {
+            // 目标名称取自查询报文去掉头部和类型字段后的域名部分
+            let name = build_query(0, target, 0).unwrap();
+            rdata.extend_from_slice(&name[12..name.len() - 4]);
+        }
+        rdata
+    }
+
+    /// 简易 DNS 服务器：按查询类型返回固定记录
+    async fn serve(socket: UdpSocket) {
+        let mut buffer = vec![0u8; 512];
+        while let Ok((len, from)) = socket.recv_from(&mut buffer).await {
+            let query = &buffer[..len];
+            let (name, end) = read_name(query, 12).unwrap();
+            let qtype = read_u16(query, end).unwrap();
+            let mut response = query.to_vec();
+            response[2] = 0x81;
+            response[3] = 0x80;
+            let mut answers = 0u16;
+            let mut additional = 0u16;
+            match (name.as_str(), qtype) {
+                ("_p2p._udp.example.test", DNS_TYPE_SRV) => {
+                    push_record(&mut response, DNS_TYPE_SRV, &srv_rdata(20, 0, 9000, "."));
+                    push_record(&mut response, DNS_TYPE_SRV, &srv_rdata(10, 5, 8080, "127.0.0.1"));
+                    push_record(&mut response, DNS_TYPE_SRV, &srv_rdata(10, 9, 8081, "127.0.0.2"));
+                    answers = 3;
+                }
+                ("_p2p-gossip._udp.example.test", DNS_TYPE_SRV) => {
+                    push_record(&mut response, DNS_TYPE_SRV, &srv_rdata(10, 0, 7946, "_p2p-gossip._udp.example.test"));
+                    push_record(&mut response, DNS_TYPE_A, &[10, 0, 0, 7]);
+                    answers = 1;
+                    additional = 1;
+                }
+                ("_p2p.example.test", DNS_TYPE_TXT) => {
+                    let mut rdata = vec![6];
+                    rdata.extend_from_slice(b"v=p2p1");
+                    rdata.push(15);
+                    rdata.extend_from_slice(b"network_id=mesh");
+                    push_record(&mut response, DNS_TYPE_TXT, &rdata);
+                    answers = 1;
+                }
+                _ => response[3] = 0x83,
+            }
+            response[6..8].copy_from_slice(&answers.to_be_bytes());
+            response[10..12].copy_from_slice(&additional.to_be_bytes());
+            let _ = socket.send_to(&response, from).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_bootstrap_records() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let nameserver = socket.local_addr().unwrap();
+        tokio::spawn(serve(socket));
+
+        let resolver = DnsBootstrap::new("example.test", nameserver, Duration::from_secs(2));
+        let info = resolver.resolve().await.unwrap();
+        // 按优先级升序、权重降序排列；目标为 "." 的记录被跳过
+        let expected: Vec<SocketAddr> = vec!["127.0.0.2:8081".parse().unwrap(), "127.0.0.1:8080".parse().unwrap()];
+        assert_eq!(info.servers, expected);
+        // 目标地址取自附加区的 A 记录
+        assert_eq!(info.seeds, vec!["10.0.0.7:7946".parse().unwrap()]);
+        assert_eq!(info.network_id.as_deref(), Some("mesh"));
+
+        // 不存在的域名返回空结果
+        assert!(resolver.resolve_srv("_p2p._udp.missing.test").await.unwrap().is_empty());
+    }
+}
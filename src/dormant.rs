@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+use crate::protocol::Message;
+
+/// 唤醒数据包的前缀，后接 8 字节（大端）的唤醒令牌
+pub const WAKE_MAGIC: [u8; 4] = *b"P2PW";
+
+/// 唤醒数据包的长度
+pub const WAKE_LEN: usize = WAKE_MAGIC.len() + 8;
+
+/// 构造携带 `token` 的唤醒数据包
+pub fn knock(token: u64) -> [u8; WAKE_LEN] {
+    let mut packet = [0u8; WAKE_LEN];
+    packet[..WAKE_MAGIC.len()].copy_from_slice(&WAKE_MAGIC);
+    packet[WAKE_MAGIC.len()..].copy_from_slice(&token.to_be_bytes());
+    packet
+}
+
+/// 数据包是否为携带 `token` 的唤醒数据包
+pub fn is_knock(packet: &[u8], token: u64) -> bool {
+    packet == knock(token)
+}
+
+/// 轻量的唤醒监听：只等待服务器发来的携带 `token` 的唤醒数据包，忽略其他一切数据包
+///
+/// 休眠期间应用可以释放完整的客户端，只保留套接字与此监听；返回后重新连接或发送任意消息即结束休眠。
+pub async fn wait_for_knock(socket: &UdpSocket, server_addr: SocketAddr, token: u64) -> io::Result<()> {
+    let mut buffer = [0u8; 64];
+    loop {
+        let (len, from) = socket.recv_from(&mut buffer).await?;
+        if from == server_addr && is_knock(&buffer[..len], token) {
+            return Ok(());
+        }
+    }
+}
+
+/// 节点的休眠状态
+///
+/// 休眠期间服务器不向节点发心跳、也不按心跳超时移除它，发给它的消息先暂存；
+/// 有节点要联系它时，服务器向其地址发送唤醒数据包。节点发来任何消息即结束休眠，暂存的消息随即补发。
+#[derive(Debug)]
+pub struct Dormancy {
+    token: u64,
+    /// 休眠期限，过期后按普通节点做心跳超时判定
+    until: Instant,
+    max_queued: usize,
+    queue: Mutex<VecDeque<Message>>,
+    /// 最近一次发送唤醒数据包的时间
+    last_knock: Mutex<Option<Instant>>,
+}
+
+impl Dormancy {
+    pub fn new(token: u64, max_dormant: Duration, max_queued: usize) -> Self {
+        Self {
+            token,
+            until: Instant::now() + max_dormant,
+            max_queued,
+            queue: Mutex::new(VecDeque::new()),
+            last_knock: Mutex::new(None),
+        }
+    }
+
+    /// 唤醒令牌
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// 是否仍在休眠期限内
+    pub fn is_active(&self) -> bool {
+        Instant::now() < self.until
+    }
+
+    /// 暂存发给节点的消息，超出上限时丢弃最早的一条并返回 `false`
+    pub fn queue(&self, message: Message) -> bool {
+        let mut queue = self.queue.lock().unwrap();
+        queue.push_back(message);
+        if queue.len() > self.max_queued {
+            queue.pop_front();
+            return false;
+        }
+        true
+    }
+
+    /// 取出暂存的全部消息
+    pub fn take_queued(&self) -> Vec<Message> {
+        self.queue.lock().unwrap().drain(..).collect()
+    }
+
+    /// 暂存的消息数
+    pub fn queued(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// 距上次唤醒已超过 `gap` 时登记本次唤醒并返回 `true`，避免连续的联系请求反复发送唤醒数据包
+    pub fn begin_knock(&self, gap: Duration) -> bool {
+        let mut last_knock = self.last_knock.lock().unwrap();
+        if last_knock.is_some_and(|at| at.elapsed() < gap) {
+            return false;
+        }
+        *last_knock = Some(Instant::now());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::MessageType;
+
+    #[test]
+    fn test_knock_carries_token() {
+        let packet = knock(0x0102_0304_0506_0708);
+        assert_eq!(&packet[..4], b"P2PW");
+        assert!(is_knock(&packet, 0x0102_0304_0506_0708));
+        assert!(!is_knock(&packet, 1));
+        assert!(!is_knock(&packet[..WAKE_LEN - 1], 0x0102_0304_0506_0708));
+    }
+
+    #[test]
+    fn test_queue_drops_oldest_and_knocks_are_spaced() {
+        let dormancy = Dormancy::new(7, Duration::from_secs(60), 2);
+        let messages: Vec<Message> = (0..3).map(|_| Message::new(MessageType::Ping, serde_json::Value::Null)).collect();
+        assert!(dormancy.queue(messages[0].clone()));
+        assert!(dormancy.queue(messages[1].clone()));
+        assert!(!dormancy.queue(messages[2].clone()));
+        let ids: Vec<_> = dormancy.take_queued().iter().map(|message| message.id).collect();
+        assert_eq!(ids, vec![messages[1].id, messages[2].id]);
+        assert_eq!(dormancy.queued(), 0);
+
+        assert!(dormancy.begin_knock(Duration::from_secs(1)));
+        assert!(!dormancy.begin_knock(Duration::from_secs(1)));
+        assert!(dormancy.begin_knock(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_listener_ignores_other_packets() {
+        let listener = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let stranger = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap();
+        // 陌生来源的唤醒包与服务器发来的其他数据包都不算唤醒
+        stranger.send_to(&knock(9), target).await.unwrap();
+        server.send_to(b"{}", target).await.unwrap();
+        server.send_to(&knock(8), target).await.unwrap();
+        server.send_to(&knock(9), target).await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        tokio::time::timeout(Duration::from_secs(2), wait_for_knock(&listener, server_addr, 9)).await.unwrap().unwrap();
+    }
+}
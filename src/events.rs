@@ -0,0 +1,241 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::config::EventStreamConfig;
+use crate::timesync::unix_millis;
+use crate::tr;
+
+/// 事件类型；序列化后的代码保持稳定，外部系统可据此过滤
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventKind {
+    /// 新地址发来第一个数据包，服务器为其创建了节点记录（尚未握手）
+    #[serde(rename = "peer.joined")]
+    PeerJoined,
+    /// 节点握手成功
+    #[serde(rename = "peer.authenticated")]
+    PeerAuthenticated,
+    /// 节点记录被移除（主动断开、超时或被踢出）
+    #[serde(rename = "peer.disconnected")]
+    PeerDisconnected,
+    /// 来源IP被封禁（反复触发 panic）或判定为扫描器而被屏蔽，或节点ID疑遭冒用而被限制握手
+    #[serde(rename = "peer.banned")]
+    PeerBanned,
+    /// 路由表新增到某节点的路由，或其下一跳发生变化
+    #[serde(rename = "route.added")]
+    RouteAdded,
+    /// 路由表移除到某节点的路由
+    #[serde(rename = "route.removed")]
+    RouteRemoved,
+    /// 服务器开始为一对节点中继数据
+    #[serde(rename = "relay.started")]
+    RelayStarted,
+    /// 中继会话关闭（主动关闭、空闲超时、一方下线或被淘汰）
+    #[serde(rename = "relay.closed")]
+    RelayClosed,
+    /// 订阅者处理过慢，部分事件未送达（只出现在该订阅者的流中）
+    #[serde(rename = "events.dropped")]
+    EventsDropped,
+}
+
+impl EventKind {
+    /// 事件类型代码，与序列化结果一致
+    pub fn code(&self) -> &'static str {
+        match self {
+            EventKind::PeerJoined => "peer.joined",
+            EventKind::PeerAuthenticated => "peer.authenticated",
+            EventKind::PeerDisconnected => "peer.disconnected",
+            EventKind::PeerBanned => "peer.banned",
+            EventKind::RouteAdded => "route.added",
+            EventKind::RouteRemoved => "route.removed",
+            EventKind::RelayStarted => "relay.started",
+            EventKind::RelayClosed => "relay.closed",
+            EventKind::EventsDropped => "events.dropped",
+        }
+    }
+}
+
+/// 一条事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    /// 服务器进程内单调递增的序号；`events.dropped` 由订阅者本地生成，序号为 0
+    pub seq: u64,
+    /// UNIX时间戳（毫秒）
+    pub timestamp_ms: u64,
+    #[serde(rename = "type")]
+    pub kind: EventKind,
+    /// 事件涉及的节点
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<Uuid>,
+    /// 事件类型相关的字段
+    #[serde(default)]
+    pub data: Value,
+}
+
+impl Event {
+    fn dropped(count: u64) -> Self {
+        Self {
+            seq: 0,
+            timestamp_ms: unix_millis(SystemTime::now()),
+            kind: EventKind::EventsDropped,
+            node_id: None,
+            data: json!({ "count": count }),
+        }
+    }
+}
+
+/// 进程内事件总线：各模块发布事件，事件流输出（文件、TCP 订阅者）与其他消费者订阅
+///
+/// 没有订阅者时发布事件几乎没有开销；调用方可用 [`EventBus::is_active`] 跳过昂贵的事件构造。
+#[derive(Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<Event>>,
+    seq: AtomicU64,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(EventStreamConfig::default().buffer)
+    }
+}
+
+impl EventBus {
+    /// `buffer` 为每个订阅者最多积压的事件数
+    pub fn new(buffer: usize) -> Self {
+        Self { sender: broadcast::channel(buffer.max(1)).0, seq: AtomicU64::new(0) }
+    }
+
+    /// 是否有订阅者
+    pub fn is_active(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// 订阅此后发布的事件
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Event>> {
+        self.sender.subscribe()
+    }
+
+    /// 发布一条事件，`data` 应为 JSON 对象
+    pub fn emit(&self, kind: EventKind, node_id: Option<Uuid>, data: Value) {
+        if !self.is_active() {
+            return;
+        }
+        let event = Event {
+            seq: self.seq.fetch_add(1, Ordering::Relaxed) + 1,
+            timestamp_ms: unix_millis(SystemTime::now()),
+            kind,
+            node_id,
+            data,
+        };
+        debug!("{}", tr!("事件: {} {:?}", "Event: {} {:?}", kind.code(), node_id));
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    /// 按配置启动事件流输出任务：追加写入文件，和/或在 TCP 地址上接受订阅者
+    pub async fn start_outputs(&self, config: &EventStreamConfig) -> Result<Vec<tokio::task::JoinHandle<()>>> {
+        let mut tasks = Vec::new();
+        if let Some(path) = &config.file {
+            let file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await
+                .with_context(|| format!("打开事件文件 {} 失败", path.display()))?;
+            let events = self.subscribe();
+            let path = path.clone();
+            info!("{}", tr!("事件流写入文件 {}", "Writing event stream to file {}", path.display()));
+            tasks.push(tokio::spawn(async move {
+                if let Err(e) = write_events(events, file).await {
+                    warn!("{}", tr!("写入事件文件 {} 失败: {}", "Failed to write event file {}: {}", path.display(), e));
+                }
+            }));
+        }
+        if let Some(addr) = config.listen_address {
+            let listener = TcpListener::bind(addr).await.with_context(|| format!("事件流绑定到 {} 失败", addr))?;
+            info!("{}", tr!("事件流监听 {}", "Event stream listening on {}", listener.local_addr()?));
+            let sender = self.sender.clone();
+            tasks.push(tokio::spawn(async move {
+                // 订阅者连接随监听任务一起取消
+                let mut connections = tokio::task::JoinSet::new();
+                loop {
+                    while connections.try_join_next().is_some() {}
+                    let (stream, subscriber) = match listener.accept().await {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!("{}", tr!("事件流接受连接失败: {}", "Event stream failed to accept connection: {}", e));
+                            continue;
+                        }
+                    };
+                    debug!("{}", tr!("事件流订阅者已连接: {}", "Event stream subscriber connected: {}", subscriber));
+                    let events = sender.subscribe();
+                    connections.spawn(async move {
+                        if let Err(e) = write_events(events, stream).await {
+                            debug!("{}", tr!("事件流订阅者 {} 断开: {}", "Event stream subscriber {} disconnected: {}", subscriber, e));
+                        }
+                    });
+                }
+            }));
+        }
+        Ok(tasks)
+    }
+}
+
+/// 以 JSON Lines 写出事件，直到总线关闭或写入失败
+async fn write_events(mut events: broadcast::Receiver<Arc<Event>>, mut output: impl AsyncWrite + Unpin) -> Result<()> {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(count)) => Arc::new(Event::dropped(count)),
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        let mut line = serde_json::to_vec(&*event)?;
+        line.push(b'\n');
+        output.write_all(&line).await?;
+        output.flush().await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_codes_are_stable() {
+        let bus = EventBus::new(2);
+        let mut events = bus.subscribe();
+        let node = Uuid::new_v4();
+        bus.emit(EventKind::PeerAuthenticated, Some(node), json!({ "name": "a" }));
+        let event = events.try_recv().unwrap();
+        let value = serde_json::to_value(&*event).unwrap();
+        assert_eq!(value["type"], "peer.authenticated");
+        assert_eq!(value["seq"], 1);
+        assert_eq!(value["node_id"], node.to_string());
+        assert_eq!(event.kind.code(), "peer.authenticated");
+        assert_eq!(serde_json::to_value(EventKind::RelayStarted).unwrap(), "relay.started");
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_sees_dropped_marker() {
+        let bus = EventBus::new(2);
+        let events = bus.subscribe();
+        for _ in 0..5 {
+            bus.emit(EventKind::RouteAdded, None, json!({}));
+        }
+        drop(bus);
+        let mut output = Vec::new();
+        write_events(events, &mut output).await.unwrap();
+        let lines: Vec<Event> = output.split(|b| *b == b'\n').filter(|l| !l.is_empty()).map(|l| serde_json::from_slice(l).unwrap()).collect();
+        assert_eq!(lines[0].kind, EventKind::EventsDropped);
+        assert_eq!(lines[0].data["count"], 3);
+        assert_eq!(lines.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![0, 4, 5]);
+    }
+}
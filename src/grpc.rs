@@ -0,0 +1,314 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+use crate::admin::{self, AdminState};
+use crate::config::{GrpcConfig, RoutingMode};
+use crate::tr;
+
+/// 控制面消息类型与服务桩代码
+///
+/// 消息类型与 `proto/p2p_control.proto` 一一对应（字段编号保持一致），
+/// 服务桩代码由 `build.rs` 生成，因此构建时无需 protoc。
+pub mod pb {
+    #[derive(Clone, Copy, PartialEq, prost::Message)]
+    pub struct GetStatsRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Stats {
+        #[prost(string, tag = "1")]
+        pub node_id: String,
+        #[prost(string, tag = "2")]
+        pub routing_mode: String,
+        #[prost(uint64, tag = "3")]
+        pub total_peers: u64,
+        #[prost(uint64, tag = "4")]
+        pub authenticated_peers: u64,
+        #[prost(uint64, tag = "5")]
+        pub connecting_peers: u64,
+        #[prost(uint64, tag = "6")]
+        pub uptime_secs: u64,
+        #[prost(uint64, tag = "7")]
+        pub packets_received: u64,
+        #[prost(uint64, tag = "8")]
+        pub bytes_received: u64,
+        #[prost(uint64, tag = "9")]
+        pub messages_handled: u64,
+        #[prost(uint64, tag = "10")]
+        pub handle_errors: u64,
+        #[prost(uint64, tag = "11")]
+        pub routed_messages: u64,
+        #[prost(uint64, tag = "12")]
+        pub relay_packets: u64,
+        #[prost(uint64, tag = "13")]
+        pub relay_bytes: u64,
+    }
+
+    #[derive(Clone, Copy, PartialEq, prost::Message)]
+    pub struct ListPeersRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Peer {
+        #[prost(string, tag = "1")]
+        pub id: String,
+        #[prost(string, optional, tag = "2")]
+        pub name: Option<String>,
+        #[prost(string, tag = "3")]
+        pub addr: String,
+        #[prost(string, tag = "4")]
+        pub status: String,
+        #[prost(string, optional, tag = "5")]
+        pub nat_type: Option<String>,
+        #[prost(uint64, tag = "6")]
+        pub connected_secs: u64,
+        #[prost(uint64, optional, tag = "7")]
+        pub last_ping_secs_ago: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListPeersResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub peers: Vec<Peer>,
+    }
+
+    #[derive(Clone, Copy, PartialEq, prost::Message)]
+    pub struct ListRoutesRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Route {
+        #[prost(string, tag = "1")]
+        pub destination: String,
+        #[prost(string, tag = "2")]
+        pub next_hop: String,
+        #[prost(uint32, tag = "3")]
+        pub distance: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListRoutesResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub routes: Vec<Route>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct KickPeerRequest {
+        #[prost(string, tag = "1")]
+        pub peer_id: String,
+        #[prost(string, tag = "2")]
+        pub reason: String,
+    }
+
+    #[derive(Clone, Copy, PartialEq, prost::Message)]
+    pub struct KickPeerResponse {
+        #[prost(bool, tag = "1")]
+        pub kicked: bool,
+    }
+
+    #[derive(Clone, Copy, PartialEq, prost::Message)]
+    pub struct ListRelaySessionsRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RelaySession {
+        #[prost(string, tag = "1")]
+        pub from_peer_id: String,
+        #[prost(string, tag = "2")]
+        pub to_peer_id: String,
+        #[prost(uint64, tag = "3")]
+        pub packets: u64,
+        #[prost(uint64, tag = "4")]
+        pub bytes: u64,
+        #[prost(uint64, tag = "5")]
+        pub age_secs: u64,
+        #[prost(uint64, tag = "6")]
+        pub idle_secs: u64,
+        #[prost(string, tag = "7")]
+        pub session_id: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListRelaySessionsResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub sessions: Vec<RelaySession>,
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/p2p.control.v1.ControlPlane.rs"));
+}
+
+pub use pb::control_plane_client::ControlPlaneClient;
+pub use pb::control_plane_server::{ControlPlane, ControlPlaneServer};
+
+/// gRPC 控制面服务实现，与管理HTTP接口共享服务器状态
+pub struct ControlService {
+    state: Arc<AdminState>,
+}
+
+impl ControlService {
+    pub fn new(state: Arc<AdminState>) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl ControlPlane for ControlService {
+    async fn get_stats(&self, _request: Request<pb::GetStatsRequest>) -> Result<Response<pb::Stats>, Status> {
+        let peers = self.state.peer_manager.get_stats().await;
+        let metrics = self.state.metrics.snapshot();
+        let routing_mode = match self.state.message_router.routing_mode() {
+            RoutingMode::DistanceVector => "distance_vector",
+            RoutingMode::LinkState => "link_state",
+        };
+        Ok(Response::new(pb::Stats {
+            node_id: self.state.local_node_id.to_string(),
+            routing_mode: routing_mode.to_string(),
+            total_peers: peers.total_peers as u64,
+            authenticated_peers: peers.authenticated_peers as u64,
+            connecting_peers: peers.connecting_peers as u64,
+            uptime_secs: metrics.uptime_secs,
+            packets_received: metrics.packets_received,
+            bytes_received: metrics.bytes_received,
+            messages_handled: metrics.messages_handled,
+            handle_errors: metrics.handle_errors,
+            routed_messages: metrics.routed_messages,
+            relay_packets: metrics.relay_packets,
+            relay_bytes: metrics.relay_bytes,
+        }))
+    }
+
+    async fn list_peers(&self, _request: Request<pb::ListPeersRequest>) -> Result<Response<pb::ListPeersResponse>, Status> {
+        let peers = admin::peer_summaries(&self.state.peer_manager)
+            .await
+            .into_iter()
+            .map(|p| pb::Peer {
+                id: p.id.to_string(),
+                name: p.name,
+                addr: p.addr.to_string(),
+                status: p.status,
+                nat_type: p.nat_type,
+                connected_secs: p.connected_secs,
+                last_ping_secs_ago: p.last_ping_secs_ago,
+            })
+            .collect();
+        Ok(Response::new(pb::ListPeersResponse { peers }))
+    }
+
+    async fn list_routes(&self, _request: Request<pb::ListRoutesRequest>) -> Result<Response<pb::ListRoutesResponse>, Status> {
+        let routes = self.state.message_router
+            .get_routing_table_snapshot()
+            .await
+            .into_iter()
+            .map(|(destination, next_hop, distance)| pb::Route {
+                destination: destination.to_string(),
+                next_hop: next_hop.to_string(),
+                distance,
+            })
+            .collect();
+        Ok(Response::new(pb::ListRoutesResponse { routes }))
+    }
+
+    async fn kick_peer(&self, request: Request<pb::KickPeerRequest>) -> Result<Response<pb::KickPeerResponse>, Status> {
+        let request = request.into_inner();
+        let peer_id = Uuid::parse_str(&request.peer_id)
+            .map_err(|_| Status::invalid_argument("peer_id 不是有效的UUID"))?;
+        let reason = if request.reason.is_empty() { admin::DEFAULT_KICK_REASON } else { request.reason.as_str() };
+        match admin::kick_peer(&self.state, &peer_id, reason).await {
+            Ok(true) => Ok(Response::new(pb::KickPeerResponse { kicked: true })),
+            Ok(false) => Err(Status::not_found("节点不存在")),
+            Err(e) => Err(Status::internal(e.to_string())),
+        }
+    }
+
+    async fn list_relay_sessions(
+        &self,
+        _request: Request<pb::ListRelaySessionsRequest>,
+    ) -> Result<Response<pb::ListRelaySessionsResponse>, Status> {
+        let sessions = self.state.relay_sessions
+            .snapshot()
+            .into_iter()
+            .map(|s| pb::RelaySession {
+                from_peer_id: s.from_peer_id.to_string(),
+                to_peer_id: s.to_peer_id.to_string(),
+                packets: s.packets,
+                bytes: s.bytes,
+                age_secs: s.age_secs,
+                idle_secs: s.idle_secs,
+                session_id: s.session_id.to_string(),
+            })
+            .collect();
+        Ok(Response::new(pb::ListRelaySessionsResponse { sessions }))
+    }
+}
+
+/// 校验 `authorization: Bearer <token>` metadata
+fn check_token(expected: &Option<String>, request: &Request<()>) -> Result<(), Status> {
+    let Some(expected) = expected else { return Ok(()) };
+    let provided = request
+        .metadata()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|t| t.trim());
+    if admin::token_matches(provided, expected) {
+        Ok(())
+    } else {
+        Err(Status::unauthenticated("未授权"))
+    }
+}
+
+/// gRPC 控制面服务器
+pub struct GrpcServer {
+    state: Arc<AdminState>,
+    token: Option<String>,
+    incoming: TcpIncoming,
+    local_addr: SocketAddr,
+}
+
+impl GrpcServer {
+    /// 绑定 gRPC 监听地址
+    pub async fn bind(state: Arc<AdminState>, config: GrpcConfig) -> Result<Self> {
+        let incoming = TcpIncoming::bind(config.listen_address)
+            .context(format!("绑定gRPC控制面地址 {} 失败", config.listen_address))?;
+        let local_addr = incoming.local_addr()?;
+        info!("{}", tr!("gRPC控制面已绑定到 {}", "gRPC control plane bound to {}", local_addr));
+        if config.token.is_none() {
+            warn!("{}", tr!("gRPC控制面未配置访问令牌，请确保仅在受信任网络中开放", "gRPC control plane has no access token configured; expose it only on trusted networks"));
+        }
+        Ok(Self { state, token: config.token, incoming, local_addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// 运行 gRPC 服务
+    pub async fn run(self) -> Result<()> {
+        let token = self.token;
+        let service = ControlPlaneServer::with_interceptor(
+            ControlService::new(self.state),
+            move |request: Request<()>| check_token(&token, &request).map(|_| request),
+        );
+        tonic::transport::Server::builder()
+            .add_service(service)
+            .serve_with_incoming(self.incoming)
+            .await
+            .context("gRPC控制面服务异常退出")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_check() {
+        let token = Some("secret".to_string());
+        let mut request = Request::new(());
+        assert!(check_token(&token, &request).is_err());
+        request.metadata_mut().insert("authorization", "Bearer secret".parse().unwrap());
+        assert!(check_token(&token, &request).is_ok());
+        assert!(check_token(&None, &Request::new(())).is_ok());
+    }
+}
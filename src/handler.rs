@@ -0,0 +1,43 @@
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::correlation::PendingReplies;
+use crate::peer::PeerManager;
+use crate::protocol::Message;
+
+/// 应用层数据消息处理器
+///
+/// 服务器内部不识别的 `Data` 消息（非路由消息、非控制命令）交给通过
+/// `P2PServer::set_message_handler` 注册的处理器；返回的消息会回复给发送方。
+/// 回复未设置 `reply_to` 时服务器自动指向收到的消息，也可用 [`Message::respond`] 构造。
+pub trait MessageHandler: Send + Sync {
+    /// 处理来自节点 `from` 的数据消息
+    fn handle_data<'a>(&'a self, from: Uuid, message: &'a Message) -> BoxFuture<'a, Result<Option<Message>>>;
+}
+
+/// 由服务器向节点发起请求并等待应答
+///
+/// 通过 `P2PServer::requester` 获取，可在处理器中持有；节点以 `reply_to` 指向请求的 `Data` 消息应答。
+#[derive(Clone)]
+pub struct Requester {
+    peer_manager: Arc<PeerManager>,
+    pending: Arc<PendingReplies>,
+}
+
+impl Requester {
+    pub fn new(peer_manager: Arc<PeerManager>, pending: Arc<PendingReplies>) -> Self {
+        Self { peer_manager, pending }
+    }
+
+    /// 向节点 `peer_id` 发送 `Data` 请求，等待应答直到 `wait` 超时
+    pub async fn request(&self, peer_id: Uuid, payload: serde_json::Value, wait: Duration) -> Result<Message> {
+        let peer = self.peer_manager.get_peer(&peer_id).await.context(format!("节点 {} 不存在", peer_id))?;
+        let request = Message::data(payload);
+        self.pending
+            .request(&request, wait, async { peer.read().await.send_message(&request).await })
+            .await
+    }
+}
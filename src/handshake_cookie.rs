@@ -0,0 +1,155 @@
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::config::HandshakeCookieConfig;
+use crate::identity::{decode_hex, encode_hex};
+
+/// 无状态握手的 cookie 签发与校验
+///
+/// cookie 形如 `<签发时间>.<HMAC>`，HMAC-SHA256 以启动时生成的随机密钥覆盖来源地址与签发时间（Unix 秒）。
+/// 校验只需重新计算，客户端带回有效 cookie 之前服务器不为其保存任何状态，
+/// 伪造来源地址的握手洪泛因而无法占用连接与节点表。
+///
+/// 声明了公钥的握手无论是否洪泛都须带回 cookie 并以密钥证明覆盖它，证明因此绑定了来源地址；
+/// 带 cookie 的请求ID在 cookie 有效期内只受理一个来源（[`HandshakeCookies::claim`]）。
+pub struct HandshakeCookies {
+    secret: [u8; 32],
+    lifetime_secs: u64,
+    trigger_per_sec: u32,
+    /// 当前一秒窗口的开始时间与其中新来源的握手请求数
+    window: Mutex<(Instant, u32)>,
+    /// 已受理的带 cookie 请求ID：来源地址与受理时间（Unix 秒）
+    claimed: Mutex<HashMap<Uuid, (SocketAddr, u64)>>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl HandshakeCookies {
+    pub fn new(config: &HandshakeCookieConfig) -> Self {
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        Self {
+            secret,
+            lifetime_secs: config.lifetime_secs,
+            trigger_per_sec: config.trigger_per_sec,
+            window: Mutex::new((Instant::now(), 0)),
+            claimed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 计入一个新来源的握手请求，返回此时是否要求 cookie：阈值为 0，或本秒内的请求数超过阈值
+    pub fn note_request(&self) -> bool {
+        self.note_request_at(Instant::now())
+    }
+
+    fn note_request_at(&self, now: Instant) -> bool {
+        let mut window = self.window.lock().unwrap();
+        if now.saturating_duration_since(window.0) >= Duration::from_secs(1) {
+            *window = (now, 0);
+        }
+        window.1 = window.1.saturating_add(1);
+        window.1 > self.trigger_per_sec
+    }
+
+    /// 为来源地址签发 cookie
+    pub fn issue(&self, addr: SocketAddr) -> String {
+        self.issue_at(addr, now_secs())
+    }
+
+    /// cookie 是否由本服务器为该来源地址签发且未过期
+    pub fn verify(&self, addr: SocketAddr, cookie: &str) -> bool {
+        self.verify_at(addr, cookie, now_secs())
+    }
+
+    /// 受理来自 `addr` 的带 cookie 请求ID；同一请求ID已由其他来源受理时返回 `false`（重放）
+    ///
+    /// 同一来源的重复请求是重传，交由握手去重重发首次的应答。记录只保留 cookie 有效期，之后 cookie 本身已过期。
+    pub fn claim(&self, request_id: Uuid, addr: SocketAddr) -> bool {
+        self.claim_at(request_id, addr, now_secs())
+    }
+
+    fn claim_at(&self, request_id: Uuid, addr: SocketAddr, now: u64) -> bool {
+        let mut claimed = self.claimed.lock().unwrap();
+        claimed.retain(|_, (_, at)| now.saturating_sub(*at) <= self.lifetime_secs);
+        let (owner, _) = *claimed.entry(request_id).or_insert((addr, now));
+        owner == addr
+    }
+
+    fn mac(&self, addr: SocketAddr, issued: u64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC 接受任意长度的密钥");
+        mac.update(addr.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(&issued.to_be_bytes());
+        mac
+    }
+
+    fn issue_at(&self, addr: SocketAddr, issued: u64) -> String {
+        format!("{}.{}", issued, encode_hex(&self.mac(addr, issued).finalize().into_bytes()))
+    }
+
+    fn verify_at(&self, addr: SocketAddr, cookie: &str, now: u64) -> bool {
+        let Some((issued, tag)) = cookie.split_once('.') else { return false };
+        let (Ok(issued), Some(tag)) = (issued.parse::<u64>(), decode_hex(tag)) else { return false };
+        // 允许 1 秒的时钟回拨
+        if issued > now + 1 || now.saturating_sub(issued) > self.lifetime_secs {
+            return false;
+        }
+        self.mac(addr, issued).verify_slice(&tag).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookies(trigger_per_sec: u32) -> HandshakeCookies {
+        HandshakeCookies::new(&HandshakeCookieConfig { enable: true, trigger_per_sec, lifetime_secs: 10 })
+    }
+
+    #[test]
+    fn test_cookie_is_bound_to_address_and_expires() {
+        let jar = cookies(0);
+        let addr: SocketAddr = "192.0.2.1:4000".parse().unwrap();
+        let cookie = jar.issue_at(addr, 1000);
+        assert!(jar.verify_at(addr, &cookie, 1005));
+        assert!(!jar.verify_at(addr, &cookie, 1011));
+        assert!(!jar.verify_at("192.0.2.1:4001".parse().unwrap(), &cookie, 1005));
+        // 其他服务器（密钥不同）签发的 cookie 与篡改过时间的 cookie 都无效
+        assert!(!cookies(0).verify_at(addr, &cookie, 1005));
+        let tampered = cookie.replacen("1000", "1004", 1);
+        assert!(!jar.verify_at(addr, &tampered, 1005));
+        assert!(!jar.verify_at(addr, "garbage", 1005));
+    }
+
+    #[test]
+    fn test_request_id_is_claimed_by_one_source() {
+        let jar = cookies(0);
+        let id = Uuid::new_v4();
+        let addr: SocketAddr = "192.0.2.1:4000".parse().unwrap();
+        assert!(jar.claim_at(id, addr, 1000));
+        // 同一来源的重传照常受理，其他来源重放同一请求被拒绝
+        assert!(jar.claim_at(id, addr, 1001));
+        assert!(!jar.claim_at(id, "198.51.100.7:4000".parse().unwrap(), 1002));
+        // 有效期过后记录被清理（此时 cookie 也已过期）
+        assert!(jar.claim_at(id, "198.51.100.7:4000".parse().unwrap(), 1020));
+    }
+
+    #[test]
+    fn test_cookies_required_only_above_trigger_rate() {
+        let jar = cookies(2);
+        let start = Instant::now();
+        assert!(!jar.note_request_at(start));
+        assert!(!jar.note_request_at(start));
+        assert!(jar.note_request_at(start));
+        assert!(!jar.note_request_at(start + Duration::from_secs(1)));
+        assert!(cookies(0).note_request());
+    }
+}
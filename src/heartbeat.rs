@@ -0,0 +1,86 @@
+use std::time::Duration;
+use rand::Rng;
+
+use crate::config::HeartbeatConfig;
+
+/// 一轮心跳的安排
+///
+/// 节点数较多时按 `peers_per_step` 放大心跳间隔（不超过 `max_interval_secs`），并把一轮心跳均匀
+/// 分散在整个间隔内发送；全局速率上限使一轮发不完时，这一轮相应拉长。超时判定按同样的倍数放宽，
+/// 避免间隔变长后节点被误判超时。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeartbeatPlan {
+    /// 本轮时长：从本轮开始到下一轮开始
+    pub round: Duration,
+    /// 相邻两个心跳之间的间隔，不分散且不限速时为零
+    pub gap: Duration,
+    /// 超时判定相对配置值的放大倍数
+    pub timeout_scale: u64,
+}
+
+impl HeartbeatPlan {
+    /// 按基础间隔（秒，已含繁忙倍数）与本轮要发送心跳的节点数安排一轮心跳
+    pub fn new(config: &HeartbeatConfig, base_secs: u64, peers: usize) -> Self {
+        let base = Duration::from_secs(base_secs.max(1));
+        let mut interval = base;
+        if let Some(step) = peers.checked_div(config.peers_per_step) {
+            let steps = 1 + step as u32;
+            let cap = Duration::from_secs(config.max_interval_secs).max(base);
+            interval = base.saturating_mul(steps).min(cap);
+        }
+
+        let mut gap = Duration::ZERO;
+        if config.spread && peers > 0 {
+            gap = interval / peers as u32;
+        }
+        if config.max_pings_per_sec > 0 {
+            gap = gap.max(Duration::from_secs(1) / config.max_pings_per_sec);
+        }
+        let round = interval.max(gap * peers as u32);
+        let timeout_scale = round.as_secs_f64() / base.as_secs_f64();
+        Self { round, gap, timeout_scale: timeout_scale.ceil() as u64 }
+    }
+
+    /// 本轮第一个心跳前的随机等待，使各轮心跳的发送时刻错开
+    pub fn jitter(&self) -> Duration {
+        if self.gap.is_zero() {
+            return Duration::ZERO;
+        }
+        self.gap.mul_f64(rand::thread_rng().r#gen::<f64>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_scales_spreads_and_caps_rate() {
+        let config = HeartbeatConfig { peers_per_step: 1000, max_interval_secs: 120, spread: true, max_pings_per_sec: 0 };
+
+        // 节点较少时保持基础间隔，心跳均匀分散
+        let plan = HeartbeatPlan::new(&config, 30, 300);
+        assert_eq!(plan.round, Duration::from_secs(30));
+        assert_eq!(plan.gap, Duration::from_millis(100));
+        assert_eq!(plan.timeout_scale, 1);
+        assert!(plan.jitter() < plan.gap);
+
+        // 每 1000 个节点增加一个基础间隔，不超过上限
+        let plan = HeartbeatPlan::new(&config, 30, 2500);
+        assert_eq!(plan.round, Duration::from_secs(90));
+        assert_eq!(plan.timeout_scale, 3);
+        assert_eq!(HeartbeatPlan::new(&config, 30, 10_000).round, Duration::from_secs(120));
+
+        // 速率上限使一轮发不完时拉长这一轮
+        let limited = HeartbeatConfig { peers_per_step: 0, max_pings_per_sec: 10, ..config.clone() };
+        let plan = HeartbeatPlan::new(&limited, 30, 600);
+        assert_eq!(plan.gap, Duration::from_millis(100));
+        assert_eq!(plan.round, Duration::from_secs(60));
+        assert_eq!(plan.timeout_scale, 2);
+
+        // 关闭自适应时与固定间隔一致
+        let fixed = HeartbeatConfig { peers_per_step: 0, max_interval_secs: 0, spread: false, max_pings_per_sec: 0 };
+        let plan = HeartbeatPlan::new(&fixed, 30, 5000);
+        assert_eq!(plan, HeartbeatPlan { round: Duration::from_secs(30), gap: Duration::ZERO, timeout_scale: 1 });
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use anyhow::{Context, Result};
+
+/// 解析后的 `http://` 地址
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpUrl {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl HttpUrl {
+    /// 解析 `http://host[:port][/path]`，仅支持明文HTTP
+    pub fn parse(url: &str) -> Result<Self> {
+        if url.starts_with("https://") {
+            anyhow::bail!("不支持 https:// 地址，请经回环地址上的本机代理转发: {}", url);
+        }
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| anyhow::anyhow!("仅支持 http:// 地址: {}", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, "/"),
+        };
+        // IPv6 字面量形如 [::1]:4318
+        let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+            let (host, tail) = bracketed
+                .split_once(']')
+                .ok_or_else(|| anyhow::anyhow!("无效的IPv6地址: {}", url))?;
+            match tail.strip_prefix(':') {
+                Some(port) => (host, port.parse().context("无效的端口")?),
+                None => (host, 80),
+            }
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().context("无效的端口")?),
+                None => (authority, 80),
+            }
+        };
+        if host.is_empty() {
+            return Err(anyhow::anyhow!("缺少主机名: {}", url));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// 主机是否为回环地址（`localhost` 或回环IP）；明文请求只应发往本机
+    pub fn is_loopback(&self) -> bool {
+        self.host.eq_ignore_ascii_case("localhost") || self.host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback())
+    }
+
+    /// 拼接子路径，例如 OTLP 的 `/v1/traces`
+    pub fn join(&self, suffix: &str) -> Self {
+        let base = self.path.trim_end_matches('/');
+        Self {
+            host: self.host.clone(),
+            port: self.port,
+            path: format!("{}{}", base, suffix),
+        }
+    }
+}
+
+/// 发送一次 JSON POST 请求，返回HTTP状态码
+pub async fn post_json(
+    url: &HttpUrl,
+    body: &[u8],
+    extra_headers: &[(&str, String)],
+    timeout: Duration,
+) -> Result<u16> {
+    tokio::time::timeout(timeout, post_json_inner(url, body, extra_headers))
+        .await
+        .map_err(|_| anyhow::anyhow!("HTTP请求超时: {}:{}{}", url.host, url.port, url.path))?
+}
+
+async fn post_json_inner(url: &HttpUrl, body: &[u8], extra_headers: &[(&str, String)]) -> Result<u16> {
+    let mut stream = TcpStream::connect((url.host.as_str(), url.port)).await
+        .context(format!("连接 {}:{} 失败", url.host, url.port))?;
+
+    let mut head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        url.path,
+        url.host,
+        url.port,
+        body.len()
+    );
+    for (name, value) in extra_headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+
+    // 只需要状态行
+    let mut buffer = Vec::with_capacity(256);
+    let mut chunk = [0u8; 256];
+    while !buffer.windows(2).any(|w| w == b"\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if buffer.len() > 4096 {
+            break;
+        }
+    }
+    let status_line = String::from_utf8_lossy(&buffer);
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("无效的HTTP响应"))?;
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let url = HttpUrl::parse("http://collector:4318").unwrap();
+        assert_eq!(url, HttpUrl { host: "collector".into(), port: 4318, path: "/".into() });
+        assert_eq!(url.join("/v1/traces").path, "/v1/traces");
+
+        let url = HttpUrl::parse("http://127.0.0.1/hooks/p2p").unwrap();
+        assert_eq!(url.port, 80);
+        assert_eq!(url.path, "/hooks/p2p");
+
+        assert!(HttpUrl::parse("https://example.com").is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+/// 运行日志使用的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// 中文（默认）
+    #[default]
+    Zh,
+    /// 英文
+    En,
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::Zh as u8);
+
+/// 设置进程内运行日志的语言，通常由 `Config.logging.language` 在启动时决定
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+/// 当前运行日志的语言
+pub fn language() -> Language {
+    if LANGUAGE.load(Ordering::Relaxed) == Language::En as u8 { Language::En } else { Language::Zh }
+}
+
+/// 当前是否输出英文日志（供 [`tr!`](crate::tr) 使用）
+pub fn is_english() -> bool {
+    language() == Language::En
+}
+
+/// 按当前日志语言选择中文或英文文本
+///
+/// 两种语言的模板接收相同的参数；只有模板时返回 `&'static str`，带参数时返回格式化后的 `String`：
+///
+/// ```
+/// use p2p_handshake_server::tr;
+///
+/// let text = tr!("节点 {} 已下线", "Node {} went offline", 42);
+/// assert_eq!(text, "节点 42 已下线");
+/// ```
+#[macro_export]
+macro_rules! tr {
+    ($zh:literal, $en:literal $(,)?) => {
+        if $crate::i18n::is_english() { $en } else { $zh }
+    };
+    ($zh:literal, $en:literal, $($arg:tt)+) => {
+        if $crate::i18n::is_english() { format!($en, $($arg)+) } else { format!($zh, $($arg)+) }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_selects_template() {
+        assert_eq!(serde_json::from_str::<Language>("\"en\"").unwrap(), Language::En);
+        set_language(Language::En);
+        assert_eq!(crate::tr!("节点 {} 已下线", "Node {} went offline", 7), "Node 7 went offline");
+        assert_eq!(crate::tr!("已停止", "stopped"), "stopped");
+        set_language(Language::Zh);
+        assert_eq!(crate::tr!("节点 {} 已下线", "Node {} went offline", 7), "节点 7 已下线");
+    }
+}
@@ -0,0 +1,765 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::config::{IdentityConfig, RouteSignaturePolicy};
+use crate::link_state::LinkStateAdvertisement;
+use crate::protocol::{KeyRotation, Message, NodeInfo, ProtocolError, ServerSignature};
+use crate::router::{RouteSignature, RoutedMessage};
+use crate::tr;
+
+/// 签名内容的域分隔前缀，防止签名被挪作他用；前缀之后紧跟网络ID，一个网络的签名与票据不能在另一个网络中重放
+const HANDSHAKE_CONTEXT: &[u8] = b"p2p-handshake-response\0";
+const NODE_HANDSHAKE_CONTEXT: &[u8] = b"p2p-handshake-request\0";
+const ROTATION_CONTEXT: &[u8] = b"p2p-key-rotation\0";
+const ROUTE_CONTEXT: &[u8] = b"p2p-routed-message\0";
+const TICKET_CONTEXT: &[u8] = b"p2p-session-ticket\0";
+const LINK_STATE_CONTEXT: &[u8] = b"p2p-link-state\0";
+
+/// 身份签名校验失败的原因
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum IdentityError {
+    #[error("握手响应没有服务器签名")]
+    Unsigned,
+    #[error("握手响应不是对本次请求的应答")]
+    NotReply,
+    #[error("公钥或签名格式错误")]
+    Malformed,
+    #[error("签名无效")]
+    BadSignature,
+    #[error("服务器指纹 {0} 不在固定列表中")]
+    NotPinned(String),
+    #[error("握手请求缺少节点密钥证明")]
+    MissingProof,
+    #[error("节点 {0} 已登记公钥，握手须使用该密钥")]
+    KeyRequired(Uuid),
+    #[error("节点 {0} 的公钥与登记的不一致")]
+    KeyMismatch(Uuid),
+    #[error("节点 {0} 的公钥已被轮换替换")]
+    StaleKey(Uuid),
+    #[error("路由消息没有签名")]
+    UnsignedRoute,
+    #[error("路由消息的目标节点不在签名范围内")]
+    OutOfScope,
+    #[error("节点 {0} 没有登记公钥")]
+    UnknownKey(Uuid),
+    #[error("链路状态通告没有来源节点的签名")]
+    UnsignedLinkState,
+}
+
+/// 服务器身份文件（`identity_path`）的内容
+///
+/// 保存服务器的节点ID，重启后沿用，客户端缓存的路由与固定的ID仍然有效。以 JSON 保存，以后可加入更多字段。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerIdentityFile {
+    pub node_id: Uuid,
+}
+
+impl ServerIdentityFile {
+    /// 读取身份文件，文件不存在时生成新的节点ID并写入
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let content = fs::read_to_string(path).with_context(|| format!("读取身份文件 {} 失败", path.display()))?;
+            return serde_json::from_str(&content).with_context(|| format!("身份文件 {} 格式错误", path.display()));
+        }
+        let file = Self { node_id: Uuid::new_v4() };
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&file)?).with_context(|| format!("写入身份文件 {} 失败", path.display()))?;
+        info!("{}", tr!("已生成服务器节点ID {} 并写入 {}", "Generated server node ID {} and saved it to {}", file.node_id, path.display()));
+        Ok(file)
+    }
+}
+
+/// 服务器的长期身份密钥，用于对握手响应签名
+pub struct ServerIdentity {
+    key: SigningKey,
+}
+
+impl std::fmt::Debug for ServerIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerIdentity").field("fingerprint", &self.fingerprint()).finish()
+    }
+}
+
+impl ServerIdentity {
+    /// 生成临时密钥
+    pub fn generate() -> Self {
+        Self { key: SigningKey::generate(&mut rand::rngs::OsRng) }
+    }
+
+    /// 按配置加载密钥：配置了密钥文件时读取（不存在则生成并写入），否则使用临时密钥
+    pub fn from_config(config: &IdentityConfig) -> Result<Self> {
+        let identity = match &config.key_file {
+            Some(path) => Self::load_or_create(path)?,
+            None => {
+                let identity = Self::generate();
+                warn!("{}", tr!("未配置 identity.key_file，使用临时身份密钥，重启后指纹会变化", "identity.key_file is not configured, using an ephemeral identity key; the fingerprint will change on restart"));
+                identity
+            }
+        };
+        info!("{}", tr!("服务器身份指纹: {}", "Server identity fingerprint: {}", identity.fingerprint()));
+        Ok(identity)
+    }
+
+    /// 从文件读取密钥种子，文件不存在时生成新密钥并写入
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let content = fs::read_to_string(path).with_context(|| format!("读取身份密钥 {} 失败", path.display()))?;
+            return Ok(Self { key: parse_seed(content.trim(), path)? });
+        }
+        let identity = Self::generate();
+        write_secret(path, &encode_hex(&identity.key.to_bytes()))
+            .with_context(|| format!("写入身份密钥 {} 失败", path.display()))?;
+        info!("{}", tr!("已生成服务器身份密钥: {}", "Generated server identity key: {}", path.display()));
+        Ok(identity)
+    }
+
+    /// 公钥（十六进制）
+    pub fn public_key_hex(&self) -> String {
+        encode_hex(self.key.verifying_key().as_bytes())
+    }
+
+    /// 公钥指纹，运维将其发布给客户端固定
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.key.verifying_key())
+    }
+
+    /// 对握手响应签名：响应标记为对 `request_id` 的应答，签名覆盖网络ID、请求ID与其余负载
+    pub fn sign_handshake_response(&self, message: &mut Message, request_id: Uuid, network_id: &str) {
+        message.reply_to = Some(request_id);
+        if let Some(payload) = message.payload.as_object_mut() {
+            payload.remove("signature");
+        }
+        let signature = self.key.sign(&signed_bytes(network_id, request_id, &message.payload));
+        let signature = ServerSignature {
+            public_key: self.public_key_hex(),
+            signature: encode_hex(&signature.to_bytes()),
+        };
+        if let (Some(payload), Ok(value)) = (message.payload.as_object_mut(), serde_json::to_value(signature)) {
+            payload.insert("signature".to_string(), value);
+        }
+    }
+
+    /// 对服务器泛洪的链路状态通告签名，通告经其他服务器转发后仍可校验来源
+    pub fn sign_link_state(&self, lsa: &mut LinkStateAdvertisement, network_id: &str) {
+        sign_link_state(&self.key, lsa, network_id);
+    }
+}
+
+/// 节点的长期身份密钥：证明握手的节点持有登记的公钥，并可轮换为新密钥
+///
+/// 密钥文件第一行是私钥种子，第二行是节点ID；节点ID在轮换前后保持不变。
+pub struct NodeIdentity {
+    node_id: Uuid,
+    key: SigningKey,
+}
+
+impl std::fmt::Debug for NodeIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeIdentity")
+            .field("node_id", &self.node_id)
+            .field("fingerprint", &self.fingerprint())
+            .finish()
+    }
+}
+
+impl NodeIdentity {
+    /// 为节点生成新密钥
+    pub fn generate(node_id: Uuid) -> Self {
+        Self { node_id, key: SigningKey::generate(&mut rand::rngs::OsRng) }
+    }
+
+    /// 从文件读取节点ID与密钥，文件不存在时以新的节点ID生成并写入
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let content = fs::read_to_string(path).with_context(|| format!("读取节点密钥 {} 失败", path.display()))?;
+            let mut lines = content.lines().map(str::trim);
+            let key = parse_seed(lines.next().unwrap_or_default(), path)?;
+            let node_id = lines
+                .next()
+                .and_then(|line| Uuid::parse_str(line).ok())
+                .with_context(|| format!("节点密钥 {} 缺少节点ID", path.display()))?;
+            return Ok(Self { node_id, key });
+        }
+        let identity = Self::generate(Uuid::new_v4());
+        identity.save(path)?;
+        info!("{}", tr!("已生成节点身份密钥: {}", "Generated node identity key: {}", path.display()));
+        Ok(identity)
+    }
+
+    /// 写入密钥文件（轮换后用新密钥覆盖旧文件）
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = format!("{}\n{}\n", encode_hex(&self.key.to_bytes()), self.node_id);
+        write_secret(path, &content).with_context(|| format!("写入节点密钥 {} 失败", path.display()))
+    }
+
+    pub fn node_id(&self) -> Uuid {
+        self.node_id
+    }
+
+    /// 公钥（十六进制）
+    pub fn public_key_hex(&self) -> String {
+        encode_hex(self.key.verifying_key().as_bytes())
+    }
+
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.key.verifying_key())
+    }
+
+    /// 创建带密钥证明的握手请求：节点ID与公钥取自本身份，证明见 [`NodeIdentity::sign_handshake_request`]
+    pub fn handshake_request(&self, mut node_info: NodeInfo) -> Result<Message, ProtocolError> {
+        node_info.id = self.node_id;
+        node_info.public_key = Some(self.public_key_hex());
+        let mut message = Message::handshake_request(node_info)?;
+        self.sign_handshake_request(&mut message);
+        Ok(message)
+    }
+
+    /// （重新）计算握手请求的密钥证明，覆盖网络ID、节点ID、请求ID、时间戳与负载
+    ///
+    /// 负载中服务器下发的 `cookie` 绑定了来源地址，因此带回 cookie 后须重新签名，截获的请求无法从别的地址重放。
+    pub fn sign_handshake_request(&self, message: &mut Message) {
+        let Some(payload) = message.payload.as_object_mut() else { return };
+        payload.remove("key_proof");
+        let network_id = payload.get("network_id").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let proof = self.key.sign(&request_bytes(&network_id, self.node_id, message.id, message.timestamp, &message.payload));
+        if let Some(payload) = message.payload.as_object_mut() {
+            payload.insert("key_proof".to_string(), encode_hex(&proof.to_bytes()).into());
+        }
+    }
+
+    /// 生成新密钥，并用当前密钥签署只在 `network_id` 中有效的轮换声明
+    pub fn rotate(&self, network_id: &str) -> (NodeIdentity, KeyRotation) {
+        let next = Self::generate(self.node_id);
+        let new_public_key = next.public_key_hex();
+        let signature = self.key.sign(&rotation_bytes(network_id, self.node_id, &new_public_key));
+        let rotation = KeyRotation {
+            node_id: self.node_id,
+            old_public_key: self.public_key_hex(),
+            new_public_key,
+            signature: encode_hex(&signature.to_bytes()),
+        };
+        (next, rotation)
+    }
+
+    /// 对路由消息签名：覆盖网络ID、源节点、目标节点（多播时为全部目标）、`route_id` 与内层消息的摘要
+    pub fn sign_routed(&self, routed: &mut RoutedMessage, network_id: &str) {
+        let multicast = routed.multicast.clone();
+        let destinations = if multicast.is_empty() { vec![routed.destination_node] } else { multicast.clone() };
+        let signature = self.key.sign(&route_bytes(network_id, routed, &destinations));
+        routed.signature = Some(RouteSignature {
+            public_key: self.public_key_hex(),
+            signature: encode_hex(&signature.to_bytes()),
+            multicast,
+        });
+    }
+
+    /// 对本节点的链路状态通告签名，通告经其他节点转发后仍可校验来源
+    pub fn sign_link_state(&self, lsa: &mut LinkStateAdvertisement, network_id: &str) {
+        sign_link_state(&self.key, lsa, network_id);
+    }
+}
+
+/// 客户端在所属网络中发出路由消息时使用的节点密钥，轮换后替换为新密钥；未配置身份时不签名
+#[derive(Debug, Clone)]
+pub(crate) struct RouteSigner {
+    identity: Arc<Mutex<Option<Arc<NodeIdentity>>>>,
+    network_id: Arc<str>,
+}
+
+impl RouteSigner {
+    pub(crate) fn new(identity: Option<Arc<NodeIdentity>>, network_id: &str) -> Self {
+        Self { identity: Arc::new(Mutex::new(identity)), network_id: network_id.into() }
+    }
+
+    pub(crate) fn replace(&self, identity: Arc<NodeIdentity>) {
+        *self.identity.lock().unwrap() = Some(identity);
+    }
+
+    pub(crate) fn identity(&self) -> Option<Arc<NodeIdentity>> {
+        self.identity.lock().unwrap().clone()
+    }
+
+    pub(crate) fn is_signing(&self) -> bool {
+        self.identity.lock().unwrap().is_some()
+    }
+
+    pub(crate) fn network_id(&self) -> &str {
+        &self.network_id
+    }
+
+    pub(crate) fn sign(&self, routed: &mut RoutedMessage) {
+        if let Some(identity) = self.identity.lock().unwrap().as_ref() {
+            identity.sign_routed(routed, &self.network_id);
+        }
+    }
+}
+
+/// 节点公钥登记表：记录每个节点依次使用过的公钥，最后一个为当前公钥
+///
+/// 节点首次带公钥握手时登记；之后该节点ID的握手必须使用当前公钥，
+/// 更换公钥只能通过旧密钥签署的轮换声明，已被替换的公钥不能再用于握手或轮换。
+#[derive(Debug, Default)]
+pub struct IdentityRegistry {
+    keys: Mutex<HashMap<Uuid, Vec<String>>>,
+}
+
+impl IdentityRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 握手时检查节点的公钥：未登记的节点带公钥则登记，已登记的节点必须使用当前公钥
+    pub fn admit(&self, node_id: Uuid, public_key: Option<&str>) -> Result<(), IdentityError> {
+        let mut keys = self.keys.lock().unwrap();
+        let (Some(chain), public_key) = (keys.get(&node_id), public_key) else {
+            if let Some(public_key) = public_key {
+                keys.insert(node_id, vec![public_key.to_ascii_lowercase()]);
+            }
+            return Ok(());
+        };
+        let public_key = public_key.ok_or(IdentityError::KeyRequired(node_id))?.to_ascii_lowercase();
+        check_current(chain, node_id, &public_key)
+    }
+
+    /// 校验 `network_id` 中的轮换声明：签名须来自当前公钥，且新公钥未在该节点的历史中出现过
+    pub fn rotate(&self, rotation: &KeyRotation, network_id: &str) -> Result<(), IdentityError> {
+        verify_rotation(rotation, network_id)?;
+        let node_id = rotation.node_id;
+        let mut keys = self.keys.lock().unwrap();
+        let chain = keys.get_mut(&node_id).ok_or(IdentityError::KeyRequired(node_id))?;
+        check_current(chain, node_id, &rotation.old_public_key.to_ascii_lowercase())?;
+        let new_public_key = rotation.new_public_key.to_ascii_lowercase();
+        if chain.contains(&new_public_key) {
+            return Err(IdentityError::StaleKey(node_id));
+        }
+        chain.push(new_public_key);
+        info!("{}", tr!("节点 {} 的公钥已轮换（第 {} 把密钥）", "Public key of node {} rotated (key #{})", node_id, chain.len()));
+        Ok(())
+    }
+
+    /// 节点当前登记的公钥
+    pub fn current(&self, node_id: &Uuid) -> Option<String> {
+        self.keys.lock().unwrap().get(node_id).and_then(|chain| chain.last().cloned())
+    }
+
+    /// 节点依次使用过的公钥，最早的在前
+    pub fn history(&self, node_id: &Uuid) -> Vec<String> {
+        self.keys.lock().unwrap().get(node_id).cloned().unwrap_or_default()
+    }
+}
+
+fn check_current(chain: &[String], node_id: Uuid, public_key: &str) -> Result<(), IdentityError> {
+    match chain.iter().position(|key| key == public_key) {
+        Some(index) if index + 1 == chain.len() => Ok(()),
+        Some(_) => Err(IdentityError::StaleKey(node_id)),
+        None => Err(IdentityError::KeyMismatch(node_id)),
+    }
+}
+
+/// 公钥指纹：`sha256:` 加公钥 SHA-256 摘要的十六进制
+pub fn fingerprint(key: &VerifyingKey) -> String {
+    format!("sha256:{}", encode_hex(&Sha256::digest(key.as_bytes())))
+}
+
+/// 校验 `network_id` 中对 `request_id` 的握手响应上的服务器签名，成功时返回服务器指纹
+pub fn verify_handshake_response(message: &Message, request_id: Uuid, network_id: &str) -> Result<String, IdentityError> {
+    if message.reply_to != Some(request_id) {
+        return Err(IdentityError::NotReply);
+    }
+    let mut payload = message.payload.clone();
+    let signature = payload
+        .as_object_mut()
+        .and_then(|payload| payload.remove("signature"))
+        .ok_or(IdentityError::Unsigned)?;
+    let signature: ServerSignature = serde_json::from_value(signature).map_err(|_| IdentityError::Malformed)?;
+
+    let public_key = parse_public_key(&signature.public_key)?;
+    verify(&public_key, &signed_bytes(network_id, request_id, &payload), &signature.signature)?;
+    Ok(fingerprint(&public_key))
+}
+
+/// 校验签名并要求服务器指纹在 `pins` 中
+pub fn verify_pinned(message: &Message, request_id: Uuid, network_id: &str, pins: &[String]) -> Result<String, IdentityError> {
+    let fingerprint = verify_handshake_response(message, request_id, network_id)?;
+    if !pins.iter().any(|pin| pin.eq_ignore_ascii_case(&fingerprint)) {
+        return Err(IdentityError::NotPinned(fingerprint));
+    }
+    Ok(fingerprint)
+}
+
+/// 校验握手请求上的节点密钥证明（按节点声明的网络ID）；节点未声明公钥时无需证明
+pub fn verify_handshake_request(message: &Message, node_info: &NodeInfo) -> Result<(), IdentityError> {
+    let Some(public_key) = &node_info.public_key else { return Ok(()) };
+    let mut payload = message.payload.clone();
+    let proof = payload
+        .as_object_mut()
+        .and_then(|payload| payload.remove("key_proof"))
+        .ok_or(IdentityError::MissingProof)?;
+    let proof = proof.as_str().ok_or(IdentityError::Malformed)?;
+    verify(&parse_public_key(public_key)?, &request_bytes(&node_info.network_id, node_info.id, message.id, message.timestamp, &payload), proof)
+}
+
+/// 校验 `network_id` 中路由消息上的源节点签名，成功时返回签名公钥（小写十六进制）
+///
+/// 多播分叉后的副本仍带原签名，只要其目标都在签名覆盖的目标之内即有效。
+pub fn verify_routed(routed: &RoutedMessage, network_id: &str) -> Result<String, IdentityError> {
+    let signature = routed.signature.as_ref().ok_or(IdentityError::UnsignedRoute)?;
+    let signed = if signature.multicast.is_empty() { vec![routed.destination_node] } else { signature.multicast.clone() };
+    let destinations = if routed.multicast.is_empty() { std::slice::from_ref(&routed.destination_node) } else { &routed.multicast[..] };
+    if !destinations.iter().all(|destination| signed.contains(destination)) {
+        return Err(IdentityError::OutOfScope);
+    }
+    verify(&parse_public_key(&signature.public_key)?, &route_bytes(network_id, routed, &signed), &signature.signature)?;
+    Ok(signature.public_key.to_ascii_lowercase())
+}
+
+/// 按策略检查路由消息的签名：签名有效时返回签名公钥，策略允许的未签名消息返回 `None`
+pub fn check_routed(routed: &RoutedMessage, policy: RouteSignaturePolicy, network_id: &str) -> Result<Option<String>, IdentityError> {
+    if policy == RouteSignaturePolicy::Off {
+        return Ok(None);
+    }
+    match verify_routed(routed, network_id) {
+        Err(IdentityError::UnsignedRoute) if policy == RouteSignaturePolicy::Verify => Ok(None),
+        result => result.map(Some),
+    }
+}
+
+/// 校验 `network_id` 中轮换声明上旧密钥的签名（不检查旧公钥是否为登记的当前公钥）
+pub fn verify_rotation(rotation: &KeyRotation, network_id: &str) -> Result<(), IdentityError> {
+    let old_key = parse_public_key(&rotation.old_public_key)?;
+    parse_public_key(&rotation.new_public_key)?;
+    verify(&old_key, &rotation_bytes(network_id, rotation.node_id, &rotation.new_public_key), &rotation.signature)
+}
+
+/// 用来源节点登记的公钥校验 `network_id` 中链路状态通告的签名
+pub fn verify_link_state(lsa: &LinkStateAdvertisement, public_key: &str, network_id: &str) -> Result<(), IdentityError> {
+    let signature = lsa.signature.as_deref().ok_or(IdentityError::UnsignedLinkState)?;
+    verify(&parse_public_key(public_key)?, &link_state_bytes(network_id, lsa), signature)
+}
+
+/// 签发会话票据：网络ID、节点ID与 256 位随机数的 SHA-256 摘要（十六进制）
+///
+/// 票据只与签发它的服务器保存的副本比对；网络ID参与派生，不同网络签发的票据不会相同。
+pub fn session_ticket(network_id: &str, node_id: Uuid) -> String {
+    let mut bytes = domain(TICKET_CONTEXT, network_id);
+    bytes.extend_from_slice(node_id.as_bytes());
+    bytes.extend_from_slice(&rand::random::<[u8; 32]>());
+    encode_hex(&Sha256::digest(&bytes))
+}
+
+fn parse_public_key(hex: &str) -> Result<VerifyingKey, IdentityError> {
+    let bytes: [u8; 32] = decode_hex(hex)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(IdentityError::Malformed)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| IdentityError::Malformed)
+}
+
+fn verify(public_key: &VerifyingKey, bytes: &[u8], signature: &str) -> Result<(), IdentityError> {
+    let signature: [u8; 64] = decode_hex(signature)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(IdentityError::Malformed)?;
+    public_key
+        .verify(bytes, &Signature::from_bytes(&signature))
+        .map_err(|_| IdentityError::BadSignature)
+}
+
+fn parse_seed(hex: &str, path: &Path) -> Result<SigningKey> {
+    let seed: [u8; 32] = decode_hex(hex)
+        .and_then(|bytes| bytes.try_into().ok())
+        .with_context(|| format!("密钥文件 {} 不是 32 字节的十六进制种子", path.display()))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// 域分隔前缀：用途前缀、网络ID、`\0`
+fn domain(context: &[u8], network_id: &str) -> Vec<u8> {
+    let mut bytes = context.to_vec();
+    bytes.extend_from_slice(network_id.as_bytes());
+    bytes.push(0);
+    bytes
+}
+
+/// 节点密钥证明覆盖的字节：域前缀、节点ID、请求ID、时间戳、去掉证明字段后的负载（含 cookie）
+fn request_bytes(network_id: &str, node_id: Uuid, request_id: Uuid, timestamp: u64, payload: &serde_json::Value) -> Vec<u8> {
+    let mut bytes = domain(NODE_HANDSHAKE_CONTEXT, network_id);
+    bytes.extend_from_slice(node_id.as_bytes());
+    bytes.extend_from_slice(request_id.as_bytes());
+    bytes.extend_from_slice(&timestamp.to_be_bytes());
+    bytes.extend_from_slice(&serde_json::to_vec(payload).unwrap_or_default());
+    bytes
+}
+
+/// 轮换声明覆盖的字节：域前缀、节点ID、新公钥
+fn rotation_bytes(network_id: &str, node_id: Uuid, new_public_key: &str) -> Vec<u8> {
+    let mut bytes = domain(ROTATION_CONTEXT, network_id);
+    bytes.extend_from_slice(node_id.as_bytes());
+    bytes.extend_from_slice(new_public_key.to_ascii_lowercase().as_bytes());
+    bytes
+}
+
+/// 路由消息签名覆盖的字节：域前缀、源节点ID、`route_id`、各目标节点ID、内层消息 JSON 的 SHA-256 摘要
+fn route_bytes(network_id: &str, routed: &RoutedMessage, destinations: &[Uuid]) -> Vec<u8> {
+    let mut bytes = domain(ROUTE_CONTEXT, network_id);
+    bytes.extend_from_slice(routed.source_node.as_bytes());
+    bytes.extend_from_slice(routed.route_id.as_bytes());
+    for destination in destinations {
+        bytes.extend_from_slice(destination.as_bytes());
+    }
+    bytes.extend_from_slice(&Sha256::digest(serde_json::to_vec(&routed.original_message).unwrap_or_default()));
+    bytes
+}
+
+/// 在通告上附上签名公钥与签名
+fn sign_link_state(key: &SigningKey, lsa: &mut LinkStateAdvertisement, network_id: &str) {
+    lsa.public_key = Some(encode_hex(key.verifying_key().as_bytes()));
+    lsa.signature = Some(encode_hex(&key.sign(&link_state_bytes(network_id, lsa)).to_bytes()));
+}
+
+/// 链路状态通告签名覆盖的字节：域前缀、来源节点ID、序列号、邻居列表 JSON
+fn link_state_bytes(network_id: &str, lsa: &LinkStateAdvertisement) -> Vec<u8> {
+    let mut bytes = domain(LINK_STATE_CONTEXT, network_id);
+    bytes.extend_from_slice(lsa.origin.as_bytes());
+    bytes.extend_from_slice(&lsa.sequence.to_be_bytes());
+    bytes.extend_from_slice(&serde_json::to_vec(&lsa.neighbors).unwrap_or_default());
+    bytes
+}
+
+/// 被签名的字节：域前缀、请求ID、去掉签名字段后的负载（`serde_json` 的对象按键排序，序列化结果确定）
+fn signed_bytes(network_id: &str, request_id: Uuid, payload: &serde_json::Value) -> Vec<u8> {
+    let mut bytes = domain(HANDSHAKE_CONTEXT, network_id);
+    bytes.extend_from_slice(request_id.as_bytes());
+    bytes.extend_from_slice(&serde_json::to_vec(payload).unwrap_or_default());
+    bytes
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// 写入私钥文件，Unix 上只允许所有者读写
+fn write_secret(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::NodeInfo;
+
+    #[test]
+    fn test_signed_handshake_response_verifies_against_pin() {
+        let identity = ServerIdentity::generate();
+        let info = NodeInfo::new("server".to_string(), "127.0.0.1:8080".parse().unwrap(), "test".to_string());
+        let request_id = Uuid::new_v4();
+        let mut response = Message::handshake_response(info, true).unwrap();
+        identity.sign_handshake_response(&mut response, request_id, "test");
+
+        // 经过一次序列化往返后仍能校验
+        let response: Message = serde_json::from_slice(&serde_json::to_vec(&response).unwrap()).unwrap();
+        let pins = vec![identity.fingerprint().to_uppercase()];
+        assert_eq!(verify_pinned(&response, request_id, "test", &pins), Ok(identity.fingerprint()));
+        assert!(matches!(verify_pinned(&response, request_id, "test", &[]), Err(IdentityError::NotPinned(_))));
+        assert_eq!(verify_handshake_response(&response, Uuid::new_v4(), "test"), Err(IdentityError::NotReply));
+
+        // 篡改负载或换用其他密钥签名都会失败
+        let mut tampered = response.clone();
+        tampered.payload["success"] = serde_json::Value::Bool(false);
+        assert_eq!(verify_handshake_response(&tampered, request_id, "test"), Err(IdentityError::BadSignature));
+        let mut forged = response.clone();
+        ServerIdentity::generate().sign_handshake_response(&mut forged, request_id, "test");
+        assert!(matches!(verify_pinned(&forged, request_id, "test", &pins), Err(IdentityError::NotPinned(_))));
+    }
+
+    #[test]
+    fn test_node_handshake_proof_and_rotation_chain() {
+        let node = NodeIdentity::generate(Uuid::new_v4());
+        let info = NodeInfo::new("node".to_string(), "127.0.0.1:9000".parse().unwrap(), "test".to_string());
+        let request = node.handshake_request(info).unwrap();
+        let request: Message = serde_json::from_slice(&serde_json::to_vec(&request).unwrap()).unwrap();
+        let node_info: NodeInfo = serde_json::from_value(request.payload.clone()).unwrap();
+        assert_eq!(node_info.id, node.node_id());
+        assert_eq!(verify_handshake_request(&request, &node_info), Ok(()));
+
+        // 冒用他人公钥但无法给出证明
+        let mut forged = request.clone();
+        forged.payload["network_id"] = "other".into();
+        assert_eq!(verify_handshake_request(&forged, &node_info), Err(IdentityError::BadSignature));
+        forged.payload.as_object_mut().unwrap().remove("key_proof");
+        assert_eq!(verify_handshake_request(&forged, &node_info), Err(IdentityError::MissingProof));
+
+        // 证明覆盖时间戳与 cookie：改动时间戳或在签名后加入 cookie 都会失效，带回 cookie 后重新签名即可
+        let mut stale = request.clone();
+        stale.timestamp -= 60;
+        assert_eq!(verify_handshake_request(&stale, &node_info), Err(IdentityError::BadSignature));
+        let mut echoed = request.clone();
+        echoed.payload["cookie"] = "1760000000.00".into();
+        assert_eq!(verify_handshake_request(&echoed, &node_info), Err(IdentityError::BadSignature));
+        node.sign_handshake_request(&mut echoed);
+        assert_eq!(verify_handshake_request(&echoed, &node_info), Ok(()));
+
+        let registry = IdentityRegistry::new();
+        let id = node.node_id();
+        registry.admit(id, node_info.public_key.as_deref()).unwrap();
+        assert_eq!(registry.admit(id, None), Err(IdentityError::KeyRequired(id)));
+        let stranger = NodeIdentity::generate(id).public_key_hex();
+        assert_eq!(registry.admit(id, Some(&stranger)), Err(IdentityError::KeyMismatch(id)));
+
+        // 轮换后只接受新密钥，旧密钥既不能握手也不能再次轮换
+        let (next, rotation) = node.rotate("test");
+        registry.rotate(&rotation, "test").unwrap();
+        assert_eq!(registry.current(&id), Some(next.public_key_hex()));
+        assert_eq!(registry.admit(id, Some(&node.public_key_hex())), Err(IdentityError::StaleKey(id)));
+        assert_eq!(registry.admit(id, Some(&next.public_key_hex())), Ok(()));
+        assert_eq!(registry.rotate(&node.rotate("test").1, "test"), Err(IdentityError::StaleKey(id)));
+        assert_eq!(registry.history(&id).len(), 2);
+
+        // 篡改新公钥后签名失效
+        let (_, mut tampered) = next.rotate("test");
+        tampered.new_public_key = stranger;
+        assert_eq!(registry.rotate(&tampered, "test"), Err(IdentityError::BadSignature));
+    }
+
+    #[test]
+    fn test_signatures_and_tickets_do_not_cross_networks() {
+        // 握手响应
+        let server = ServerIdentity::generate();
+        let info = NodeInfo::new("server".to_string(), "127.0.0.1:8080".parse().unwrap(), "alpha".to_string());
+        let request_id = Uuid::new_v4();
+        let mut response = Message::handshake_response(info, true).unwrap();
+        server.sign_handshake_response(&mut response, request_id, "alpha");
+        assert!(verify_handshake_response(&response, request_id, "alpha").is_ok());
+        assert_eq!(verify_handshake_response(&response, request_id, "beta"), Err(IdentityError::BadSignature));
+
+        // 握手请求的密钥证明：改写声明的网络ID即失效
+        let node = NodeIdentity::generate(Uuid::new_v4());
+        let request = node.handshake_request(NodeInfo::new("node".to_string(), "127.0.0.1:9000".parse().unwrap(), "alpha".to_string())).unwrap();
+        let mut node_info: NodeInfo = serde_json::from_value(request.payload.clone()).unwrap();
+        assert_eq!(verify_handshake_request(&request, &node_info), Ok(()));
+        node_info.network_id = "beta".into();
+        assert_eq!(verify_handshake_request(&request, &node_info), Err(IdentityError::BadSignature));
+
+        // 密钥轮换
+        let (_, rotation) = node.rotate("alpha");
+        assert_eq!(verify_rotation(&rotation, "alpha"), Ok(()));
+        assert_eq!(verify_rotation(&rotation, "beta"), Err(IdentityError::BadSignature));
+        let registry = IdentityRegistry::new();
+        registry.admit(node.node_id(), Some(&node.public_key_hex())).unwrap();
+        assert_eq!(registry.rotate(&rotation, "beta"), Err(IdentityError::BadSignature));
+
+        // 路由消息
+        let mut routed = RoutedMessage::new(Message::data(serde_json::json!({})), node.node_id(), Uuid::new_v4(), 8);
+        node.sign_routed(&mut routed, "alpha");
+        assert!(verify_routed(&routed, "alpha").is_ok());
+        assert_eq!(check_routed(&routed, RouteSignaturePolicy::Verify, "beta"), Err(IdentityError::BadSignature));
+
+        // 会话票据每次签发都不同
+        let id = Uuid::new_v4();
+        assert_ne!(session_ticket("alpha", id), session_ticket("alpha", id));
+        assert_eq!(session_ticket("beta", id).len(), 64);
+    }
+
+    #[test]
+    fn test_routed_signature_covers_source_destination_and_payload() {
+        let node = NodeIdentity::generate(Uuid::new_v4());
+        let mut routed = RoutedMessage::new(Message::data(serde_json::json!({ "n": 1 })), node.node_id(), Uuid::new_v4(), 8);
+        assert_eq!(check_routed(&routed, RouteSignaturePolicy::Verify, "test"), Ok(None));
+        assert_eq!(check_routed(&routed, RouteSignaturePolicy::Require, "test"), Err(IdentityError::UnsignedRoute));
+
+        node.sign_routed(&mut routed, "test");
+        // 跳数不在签名范围内，沿途递增不影响校验
+        routed.hop_count = 3;
+        assert_eq!(check_routed(&routed, RouteSignaturePolicy::Require, "test"), Ok(Some(node.public_key_hex())));
+
+        let mut tampered = routed.clone();
+        tampered.original_message.payload = serde_json::json!({ "n": 2 });
+        assert_eq!(verify_routed(&tampered, "test"), Err(IdentityError::BadSignature));
+        let mut tampered = routed.clone();
+        tampered.source_node = Uuid::new_v4();
+        assert_eq!(verify_routed(&tampered, "test"), Err(IdentityError::BadSignature));
+        let mut tampered = routed.clone();
+        tampered.destination_node = Uuid::new_v4();
+        assert_eq!(verify_routed(&tampered, "test"), Err(IdentityError::BadSignature));
+        assert_eq!(check_routed(&tampered, RouteSignaturePolicy::Off, "test"), Ok(None));
+    }
+
+    #[test]
+    fn test_multicast_signature_allows_only_signed_members() {
+        let node = NodeIdentity::generate(Uuid::new_v4());
+        let members = vec![Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let mut routed = RoutedMessage::multicast(Message::data(serde_json::json!({})), node.node_id(), members.clone(), 8);
+        node.sign_routed(&mut routed, "test");
+        assert!(verify_routed(&routed, "test").is_ok());
+
+        // 分叉后的副本只要发往签名覆盖的节点就仍然有效
+        let mut branch = routed.clone();
+        branch.multicast = members[1..].to_vec();
+        assert!(verify_routed(&branch, "test").is_ok());
+        let mut leaf = routed.clone();
+        leaf.multicast = Vec::new();
+        leaf.destination_node = members[0];
+        assert!(verify_routed(&leaf, "test").is_ok());
+        leaf.destination_node = Uuid::new_v4();
+        assert_eq!(verify_routed(&leaf, "test"), Err(IdentityError::OutOfScope));
+    }
+
+    #[test]
+    fn test_node_key_file_keeps_node_id_across_rotation() {
+        let path = std::env::temp_dir().join(format!("p2p-node-identity-{}.key", Uuid::new_v4()));
+        let created = NodeIdentity::load_or_create(&path).unwrap();
+        let (next, _) = created.rotate("test");
+        next.save(&path).unwrap();
+        let loaded = NodeIdentity::load_or_create(&path).unwrap();
+        assert_eq!(loaded.node_id(), created.node_id());
+        assert_eq!(loaded.fingerprint(), next.fingerprint());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_key_file_is_created_then_reused() {
+        let path = std::env::temp_dir().join(format!("p2p-identity-{}.key", Uuid::new_v4()));
+        let created = ServerIdentity::load_or_create(&path).unwrap();
+        let loaded = ServerIdentity::load_or_create(&path).unwrap();
+        assert_eq!(created.fingerprint(), loaded.fingerprint());
+        assert_eq!(fs::read_to_string(&path).unwrap(), encode_hex(&created.key.to_bytes()));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_identity_file_keeps_server_node_id() {
+        let path = std::env::temp_dir().join(format!("p2p-server-identity-{}.json", Uuid::new_v4()));
+        let created = ServerIdentityFile::load_or_create(&path).unwrap();
+        assert_eq!(ServerIdentityFile::load_or_create(&path).unwrap(), created);
+        fs::write(&path, "not json").unwrap();
+        assert!(ServerIdentityFile::load_or_create(&path).is_err());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Deserializer};
+use smallvec::SmallVec;
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// 驻留表最多保存的字符串数，超出后新字符串不再驻留，避免对端用随机值撑大驻留表
+const MAX_INTERNED: usize = 4096;
+/// 只驻留不超过该长度的字符串
+const MAX_INTERNED_LEN: usize = 64;
+
+static INTERNED: LazyLock<Mutex<HashSet<Arc<str>>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// 驻留常用字符串（网络ID、版本号、能力名）
+///
+/// 内容相同的字符串共享同一份 `Arc<str>`，节点信息在各处复制时只增加引用计数。
+pub fn intern(value: &str) -> Arc<str> {
+    if value.len() > MAX_INTERNED_LEN {
+        return Arc::from(value);
+    }
+    let mut interned = INTERNED.lock().unwrap();
+    if let Some(existing) = interned.get(value) {
+        return existing.clone();
+    }
+    let value: Arc<str> = Arc::from(value);
+    if interned.len() < MAX_INTERNED {
+        interned.insert(value.clone());
+    }
+    value
+}
+
+/// 反序列化并驻留字符串
+pub fn deserialize_interned<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<str>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Ok(intern(&value))
+}
+
+/// 反序列化并驻留字符串列表
+pub fn deserialize_interned_seq<'de, D, A>(deserializer: D) -> Result<SmallVec<A>, D::Error>
+where
+    D: Deserializer<'de>,
+    A: smallvec::Array<Item = Arc<str>>,
+{
+    let values = Vec::<String>::deserialize(deserializer)?;
+    Ok(values.iter().map(|value| intern(value)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_shares_and_bounds() {
+        let a = intern("test-network");
+        let b = intern(&String::from("test-network"));
+        assert!(Arc::ptr_eq(&a, &b));
+
+        // 过长的字符串不驻留
+        let long = "x".repeat(MAX_INTERNED_LEN + 1);
+        assert!(!Arc::ptr_eq(&intern(&long), &intern(&long)));
+
+        let seq: SmallVec<[Arc<str>; 4]> =
+            deserialize_interned_seq(serde_json::json!(["handshake", "test-network"])).unwrap();
+        assert!(Arc::ptr_eq(&seq[1], &a));
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::KeepaliveConfig;
+
+/// 探测过程中需要服务器执行的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeAction {
+    /// 向节点发送延迟为 `delay_secs` 的探测包
+    Send { peer_id: Uuid, delay_secs: u64 },
+    /// 探测结束，向节点下发建议的保活间隔
+    Finished { peer_id: Uuid, recommended_secs: u64 },
+}
+
+#[derive(Debug)]
+struct Probe {
+    ip: IpAddr,
+    /// 当前探测的空闲时长（秒）
+    delay: u64,
+    /// 已确认 NAT 映射能保持的最长空闲时长
+    confirmed: Option<u64>,
+    /// 最近一次收到该节点数据包的时间，空闲时长从这里开始计算
+    idle_since: Instant,
+    /// 探测包发出的时间，等待回显期间为 `Some`
+    sent_at: Option<Instant>,
+}
+
+/// NAT 映射存活时间探测
+///
+/// 节点发起探测后，服务器在节点空闲 `delay` 秒后才发送探测包，节点收到后回显；
+/// 每次回显成功后空闲时长翻倍，直到 `max_probe_secs` 或某次回显超时，
+/// 由此估计 NAT 映射的空闲超时并给出建议的保活间隔。
+/// 结果按公网IP记录，同一 NAT 后的其他节点握手时也能拿到该建议。
+#[derive(Debug)]
+pub struct KeepaliveProber {
+    config: KeepaliveConfig,
+    probes: Mutex<HashMap<Uuid, Probe>>,
+    learned: Mutex<HashMap<IpAddr, u64>>,
+    /// 因回显超时结束的探测结果：NAT 映射可能已失效，待节点下次发包时补发
+    undelivered: Mutex<HashMap<Uuid, u64>>,
+}
+
+impl KeepaliveProber {
+    pub fn new(config: KeepaliveConfig) -> Self {
+        Self {
+            config,
+            probes: Mutex::new(HashMap::new()),
+            learned: Mutex::new(HashMap::new()),
+            undelivered: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 给指定公网IP的建议保活间隔（秒），尚未探测时返回默认值
+    pub fn recommended_for(&self, ip: IpAddr) -> u64 {
+        self.learned.lock().unwrap().get(&ip).copied().unwrap_or(self.config.default_interval_secs)
+    }
+
+    /// 开始探测，节点已在探测中时返回 `false`
+    pub fn start(&self, peer_id: Uuid, ip: IpAddr, now: Instant) -> bool {
+        let mut probes = self.probes.lock().unwrap();
+        if probes.contains_key(&peer_id) {
+            return false;
+        }
+        probes.insert(peer_id, Probe {
+            ip,
+            delay: self.config.initial_probe_secs.max(1),
+            confirmed: None,
+            idle_since: now,
+            sent_at: None,
+        });
+        true
+    }
+
+    pub fn is_probing(&self, peer_id: &Uuid) -> bool {
+        self.probes.lock().unwrap().contains_key(peer_id)
+    }
+
+    pub fn cancel(&self, peer_id: &Uuid) {
+        self.probes.lock().unwrap().remove(peer_id);
+        self.undelivered.lock().unwrap().remove(peer_id);
+    }
+
+    /// 收到节点的任意数据包：NAT 映射被刷新，重新计算空闲时长。
+    /// 有待补发的探测结果时返回建议的保活间隔
+    pub fn on_inbound(&self, peer_id: &Uuid, now: Instant) -> Option<u64> {
+        if let Some(probe) = self.probes.lock().unwrap().get_mut(peer_id) {
+            probe.idle_since = now;
+        }
+        self.undelivered.lock().unwrap().remove(peer_id)
+    }
+
+    /// 收到节点对探测包的回显
+    pub fn on_echo(&self, peer_id: &Uuid, delay_secs: u64, now: Instant) -> Option<ProbeAction> {
+        let mut probes = self.probes.lock().unwrap();
+        let probe = probes.get_mut(peer_id)?;
+        if probe.sent_at.is_none() || probe.delay != delay_secs {
+            return None;
+        }
+        probe.confirmed = Some(delay_secs);
+        probe.idle_since = now;
+        probe.sent_at = None;
+        let max = self.config.max_probe_secs.max(1);
+        if delay_secs >= max {
+            let probe = probes.remove(peer_id)?;
+            return Some(self.finish(*peer_id, probe));
+        }
+        probe.delay = (delay_secs * 2).min(max);
+        None
+    }
+
+    /// 推进所有探测：空闲时长达到的发送探测包，回显超时的结束探测
+    pub fn poll(&self, now: Instant) -> Vec<ProbeAction> {
+        let grace = Duration::from_secs(self.config.probe_grace_secs);
+        let mut actions = Vec::new();
+        let mut finished = Vec::new();
+        let mut probes = self.probes.lock().unwrap();
+        for (peer_id, probe) in probes.iter_mut() {
+            match probe.sent_at {
+                None if now.saturating_duration_since(probe.idle_since) >= Duration::from_secs(probe.delay) => {
+                    probe.sent_at = Some(now);
+                    actions.push(ProbeAction::Send { peer_id: *peer_id, delay_secs: probe.delay });
+                }
+                Some(sent_at) if now.saturating_duration_since(sent_at) >= grace => finished.push(*peer_id),
+                _ => {}
+            }
+        }
+        for peer_id in finished {
+            if let Some(probe) = probes.remove(&peer_id) {
+                let action = self.finish(peer_id, probe);
+                if let ProbeAction::Finished { recommended_secs, .. } = action {
+                    self.undelivered.lock().unwrap().insert(peer_id, recommended_secs);
+                }
+                actions.push(action);
+            }
+        }
+        actions
+    }
+
+    fn finish(&self, peer_id: Uuid, probe: Probe) -> ProbeAction {
+        let recommended = probe
+            .confirmed
+            .map(|secs| (secs as f64 * self.config.safety_factor) as u64)
+            .unwrap_or(0)
+            .max(self.config.min_interval_secs.max(1));
+        self.learned.lock().unwrap().insert(probe.ip, recommended);
+        ProbeAction::Finished { peer_id, recommended_secs: recommended }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_doubles_until_echo_times_out() {
+        let prober = KeepaliveProber::new(KeepaliveConfig {
+            initial_probe_secs: 10,
+            max_probe_secs: 80,
+            probe_grace_secs: 5,
+            safety_factor: 0.8,
+            ..KeepaliveConfig::default()
+        });
+        let peer = Uuid::new_v4();
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let t0 = Instant::now();
+        assert!(prober.start(peer, ip, t0));
+        assert!(!prober.start(peer, ip, t0));
+
+        // 空闲未满 10 秒不发送；其间收到数据包会重新计时
+        assert!(prober.poll(t0 + Duration::from_secs(9)).is_empty());
+        prober.on_inbound(&peer, t0 + Duration::from_secs(9));
+        assert!(prober.poll(t0 + Duration::from_secs(18)).is_empty());
+        let t1 = t0 + Duration::from_secs(19);
+        assert_eq!(prober.poll(t1), vec![ProbeAction::Send { peer_id: peer, delay_secs: 10 }]);
+
+        // 回显后下一轮空闲时长翻倍
+        assert_eq!(prober.on_echo(&peer, 10, t1), None);
+        let t2 = t1 + Duration::from_secs(20);
+        assert_eq!(prober.poll(t2), vec![ProbeAction::Send { peer_id: peer, delay_secs: 20 }]);
+
+        // 20 秒的探测包没有回显：映射寿命在 10 到 20 秒之间
+        let done = prober.poll(t2 + Duration::from_secs(5));
+        assert_eq!(done, vec![ProbeAction::Finished { peer_id: peer, recommended_secs: 8 }]);
+        assert!(!prober.is_probing(&peer));
+        assert_eq!(prober.recommended_for(ip), 8);
+        // 结果可能因映射失效而丢失，节点下次发包时补发一次
+        assert_eq!(prober.on_inbound(&peer, t2 + Duration::from_secs(30)), Some(8));
+        assert_eq!(prober.on_inbound(&peer, t2 + Duration::from_secs(31)), None);
+        assert_eq!(prober.recommended_for("198.51.100.1".parse().unwrap()), KeepaliveConfig::default().default_interval_secs);
+    }
+}
@@ -0,0 +1,124 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::protocol::{PeerInfo, RttSample};
+
+/// 新样本在平滑值中所占的权重
+const SMOOTHING: f64 = 0.25;
+/// 单个 Pong 最多接受的样本数
+pub const MAX_SAMPLES_PER_REPORT: usize = 64;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    rtt_us: u64,
+    updated_at: Instant,
+}
+
+/// 延迟矩阵中的一项（供管理接口展示）
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct LatencyEntry {
+    pub from: Uuid,
+    pub to: Uuid,
+    pub rtt_us: u64,
+    pub age_secs: u64,
+}
+
+/// 节点之间的往返时延矩阵
+///
+/// 节点在回应服务器心跳的 `Pong` 中附带到各直连节点的 RTT，服务器按 (上报方, 对方) 平滑记录，
+/// 用于按延迟排序的节点发现，并通过管理接口提供给运维。
+#[derive(Debug, Default)]
+pub struct LatencyMatrix {
+    entries: Mutex<HashMap<(Uuid, Uuid), Entry>>,
+}
+
+impl LatencyMatrix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录节点上报的样本（忽略指向自身的样本），返回接受的样本数
+    pub fn record(&self, from: Uuid, samples: &[RttSample]) -> usize {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let mut accepted = 0;
+        for sample in samples.iter().filter(|s| s.peer_id != from).take(MAX_SAMPLES_PER_REPORT) {
+            entries
+                .entry((from, sample.peer_id))
+                .and_modify(|e| {
+                    e.rtt_us = (SMOOTHING * sample.rtt_us as f64 + (1.0 - SMOOTHING) * e.rtt_us as f64) as u64;
+                    e.updated_at = now;
+                })
+                .or_insert(Entry { rtt_us: sample.rtt_us, updated_at: now });
+            accepted += 1;
+        }
+        accepted
+    }
+
+    /// 两个节点之间的往返时延（微秒）：两端都有上报时取平均
+    pub fn rtt(&self, a: &Uuid, b: &Uuid) -> Option<u64> {
+        let entries = self.entries.lock().unwrap();
+        match (entries.get(&(*a, *b)), entries.get(&(*b, *a))) {
+            (Some(x), Some(y)) => Some((x.rtt_us + y.rtt_us) / 2),
+            (Some(x), None) | (None, Some(x)) => Some(x.rtt_us),
+            (None, None) => None,
+        }
+    }
+
+    /// 按到 `from` 的往返时延从小到大排序节点列表，未测量的节点保持原顺序排在最后
+    pub fn sort_by_latency(&self, from: &Uuid, peers: &mut [PeerInfo]) {
+        peers.sort_by(|x, y| match (self.rtt(from, &x.id), self.rtt(from, &y.id)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+    }
+
+    /// 全部上报的时延
+    pub fn entries(&self) -> Vec<LatencyEntry> {
+        let mut entries: Vec<LatencyEntry> = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&(from, to), e)| LatencyEntry { from, to, rtt_us: e.rtt_us, age_secs: e.updated_at.elapsed().as_secs() })
+            .collect();
+        entries.sort_by_key(|e| (e.from, e.to));
+        entries
+    }
+
+    /// 节点下线：移除与其相关的全部时延
+    pub fn remove_peer(&self, peer_id: &Uuid) {
+        self.entries.lock().unwrap().retain(|(from, to), _| from != peer_id && to != peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_smooth_and_sort() {
+        let matrix = LatencyMatrix::new();
+        let (a, b, c, d) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let sample = |peer_id, rtt_us| RttSample { peer_id, rtt_us };
+        assert_eq!(matrix.record(a, &[sample(b, 4000), sample(c, 1000), sample(a, 1)]), 2);
+        matrix.record(a, &[sample(b, 8000)]);
+        assert_eq!(matrix.rtt(&a, &b), Some(5000));
+        matrix.record(c, &[sample(a, 3000)]);
+        assert_eq!(matrix.rtt(&c, &a), Some(2000));
+
+        let addr = "127.0.0.1:9000".parse().unwrap();
+        let mut peers: Vec<PeerInfo> = [d, b, c].iter().map(|&id| PeerInfo::new(id, addr, Vec::new())).collect();
+        matrix.sort_by_latency(&a, &mut peers);
+        assert_eq!(peers.iter().map(|p| p.id).collect::<Vec<_>>(), vec![c, b, d]);
+
+        matrix.remove_peer(&a);
+        assert!(matrix.entries().is_empty());
+    }
+}
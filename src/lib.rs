@@ -1,3 +1,5 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
 //! # P2P握手服务器
 //! 
 //! 这是一个用Rust编写的P2P网络握手服务器实现。
@@ -10,6 +12,16 @@
 //! - 节点管理和连接池
 //! - 配置文件支持
 //! - 完整的日志记录
+//! - 管理HTTP接口（可选的 `dashboard` 特性提供Web监控面板）
+//! - OpenTelemetry（OTLP/HTTP）span与指标导出
+//! - 主题发布/订阅（可选的 `mqtt` 特性桥接到外部 MQTT broker）
+//! - gRPC 控制面（可选的 `grpc` 特性，schema 见 `proto/p2p_control.proto`）
+//! - DNS引导（从 SRV/TXT 记录获取服务器地址、集群种子与网络ID）
+//! - 网络层故障注入（可选的 `chaos` 特性，用于韧性测试）
+//! - 消息策略脚本（可选的 `scripting` 特性，Lua 钩子放行、拒绝或改写消息）
+//! - 插件（认领扩展消息类型，接收并主动发送消息）
+//! - 嵌入式客户端（托管 P2P 会话：打洞、保活、直连失效后回退中继）
+//! - 与传输无关的协议核心（[`wire`]）：关闭默认的 `server` 特性后只需 `alloc`，可用于嵌入式客户端
 //! 
 //! ## 使用示例
 //! 
@@ -25,22 +37,249 @@
 //! }
 //! ```
 
+extern crate alloc;
+
+#[cfg(feature = "server")]
+pub mod ack_batch;
+#[cfg(feature = "server")]
+pub mod address_book;
+#[cfg(feature = "server")]
+pub mod admin;
+#[cfg(feature = "server")]
+pub mod bandwidth;
+#[cfg(feature = "server")]
+pub mod carrier_nat;
+#[cfg(feature = "server")]
+pub mod binding_lifetime;
+#[cfg(feature = "server")]
+pub mod broadcast_guard;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "server")]
+pub mod client;
+#[cfg(feature = "server")]
+pub mod cluster;
+#[cfg(feature = "server")]
+pub mod codec;
+#[cfg(feature = "server")]
 pub mod config;
+#[cfg(feature = "server")]
+pub mod contacts;
+#[cfg(feature = "server")]
+pub mod correlation;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+#[cfg(feature = "server")]
+pub mod dns_bootstrap;
+#[cfg(feature = "server")]
+pub mod dormant;
+#[cfg(feature = "server")]
+pub mod events;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "server")]
+pub mod handler;
+#[cfg(feature = "server")]
+pub mod handshake_cookie;
+#[cfg(feature = "server")]
+pub mod heartbeat;
+#[cfg(feature = "server")]
+pub mod http_client;
+#[cfg(feature = "server")]
+pub mod i18n;
+#[cfg(feature = "std")]
+pub mod intern;
+#[cfg(feature = "server")]
+pub mod identity;
+#[cfg(feature = "server")]
+pub mod keepalive;
+#[cfg(feature = "server")]
+pub mod latency;
+#[cfg(feature = "server")]
+pub mod link_quality;
+#[cfg(feature = "server")]
+pub mod link_state;
+#[cfg(feature = "server")]
+pub mod loadtest;
+#[cfg(feature = "server")]
+pub mod log_capture;
+#[cfg(feature = "server")]
+pub mod maintenance;
+#[cfg(feature = "server")]
+pub mod memory;
+#[cfg(feature = "server")]
+pub mod metrics;
+#[cfg(feature = "mqtt")]
+pub mod mqtt_bridge;
+#[cfg(feature = "server")]
 pub mod network;
+#[cfg(feature = "server")]
+pub mod offline;
+#[cfg(feature = "server")]
 pub mod peer;
+#[cfg(feature = "server")]
+pub mod peer_list;
+#[cfg(feature = "server")]
+pub mod plugin;
+#[cfg(feature = "server")]
+pub mod pmtu;
+#[cfg(feature = "server")]
+pub mod presence;
+#[cfg(feature = "server")]
 pub mod protocol;
+#[cfg(feature = "server")]
+pub mod pubsub;
+#[cfg(feature = "server")]
+pub mod quarantine;
+#[cfg(feature = "server")]
+pub mod reachability;
+#[cfg(feature = "server")]
+pub mod relay;
+#[cfg(feature = "server")]
+pub mod rendezvous;
+#[cfg(feature = "server")]
+pub mod route_log;
+#[cfg(feature = "server")]
+pub mod rpc;
+#[cfg(feature = "server")]
 pub mod router;
+#[cfg(feature = "server")]
+pub mod scanner;
+#[cfg(feature = "server")]
+pub mod scheduler;
+#[cfg(feature = "server")]
+pub mod scripting;
+#[cfg(feature = "server")]
+pub mod secrets;
+#[cfg(feature = "server")]
+pub mod selftest;
+#[cfg(feature = "server")]
 pub mod server;
+#[cfg(feature = "server")]
+pub mod server_pool;
+#[cfg(feature = "server")]
+pub mod service;
+#[cfg(feature = "server")]
+pub mod sessions;
+#[cfg(feature = "server")]
+pub mod spoof_guard;
+#[cfg(feature = "server")]
+pub mod sockopt;
+#[cfg(feature = "server")]
+pub mod supervisor;
+#[cfg(feature = "server")]
+pub mod stream;
+#[cfg(feature = "server")]
+pub mod stun_limiter;
+#[cfg(feature = "server")]
 pub mod stun_server;
+#[cfg(feature = "server")]
 pub mod stun_protocol;
+#[cfg(feature = "server")]
+pub mod tcp_punch;
+#[cfg(feature = "server")]
+pub mod telemetry;
+#[cfg(feature = "server")]
+pub mod timesync;
+#[cfg(feature = "server")]
+pub mod topology;
+#[cfg(feature = "turn")]
+pub mod turn;
+#[cfg(feature = "server")]
+pub mod webhooks;
+pub mod wire;
 
 
 // 重新导出主要的公共API
-pub use config::Config;
+#[cfg(feature = "server")]
+pub use config::{AckBatchConfig, AdminConfig, BandwidthConfig, BridgeDirection, CarrierNatConfig, ChaosConfig, ClusterBackend, ClusterConfig, CodecConfig, Config, ConnectionLimitsConfig, ControlCommand, ControlConfig, DiscoveryConfig, DnsBootstrapConfig, DormantConfig, EventStreamConfig, FaultProfile, GrpcConfig, HandshakeCookieConfig, HeartbeatConfig, IdentityConfig, KeepaliveConfig, LimitAction, LoggingConfig, MemoryConfig, MqttConfig, MqttTopicMapping, NetworkConfig, NodeInfoLimitsConfig, OfflineStoreConfig, OversizedRoutePolicy, PacketLogMode, PanicIsolationConfig, PathMtuConfig, PeerRole, PinnedPeer, PinningConfig, PunchTimingConfig, RelayConfig, RouteSignaturePolicy, RoutingConfig, RoutingMode, ScannerDetectionConfig, SchedulerConfig, ScriptingConfig, SpoofGuardConfig, StartupConfig, SupervisorConfig, TcpPunchConfig, TelemetryConfig, TimeSyncConfig, WebhookEndpoint, WebhooksConfig};
+#[cfg(feature = "server")]
+pub use i18n::Language;
+#[cfg(feature = "server")]
+pub use events::{Event, EventBus, EventKind};
+#[cfg(feature = "server")]
+pub use address_book::{AddressBook, KnownPeer};
+#[cfg(feature = "server")]
+pub use server_pool::ServerHealth;
+#[cfg(feature = "server")]
+pub use client::{ClientConfig, IncomingCall, P2PClient, P2PSession, SessionConfig, SessionState};
+#[cfg(feature = "server")]
+pub use stream::{P2PStream, StreamConfig};
+#[cfg(feature = "server")]
+pub use cluster::{GossipRegistry, PeerRegistry, RegisteredPeer};
+#[cfg(feature = "server")]
+pub use codec::{Codec, CodecFormat, CodecSet, JsonCodec};
+#[cfg(feature = "server")]
+pub use dns_bootstrap::{BootstrapInfo, DnsBootstrap};
+#[cfg(feature = "server")]
+pub use correlation::{HandshakeSeq, HandshakeState, PendingHandshakes, PendingReplies};
+#[cfg(feature = "server")]
+pub use handler::{MessageHandler, Requester};
+#[cfg(feature = "server")]
+pub use plugin::{Plugin, PluginContext, PluginRegistry};
+#[cfg(feature = "server")]
+pub use admin::AdminServer;
+#[cfg(feature = "server")]
+pub use bandwidth::{BandwidthMap, LinkBandwidth};
+#[cfg(feature = "server")]
+pub use broadcast_guard::{BroadcastCheck, BroadcastGuard};
+#[cfg(feature = "server")]
+pub use carrier_nat::CarrierNatStats;
+#[cfg(feature = "server")]
+pub use identity::{IdentityError, IdentityRegistry, NodeIdentity, ServerIdentity, ServerIdentityFile};
+#[cfg(feature = "server")]
+pub use secrets::EncryptedSection;
+#[cfg(feature = "server")]
+pub use keepalive::KeepaliveProber;
+#[cfg(feature = "server")]
+pub use latency::{LatencyEntry, LatencyMatrix};
+#[cfg(feature = "server")]
+pub use link_quality::{PingHistory, RttEstimator};
+#[cfg(feature = "server")]
+pub use log_capture::{CapturingLogger, RecentLogs};
+#[cfg(feature = "server")]
+pub use memory::{Evictions, MemoryAccounting, MemoryUsage};
+#[cfg(feature = "server")]
+pub use metrics::ServerMetrics;
+#[cfg(feature = "server")]
+pub use offline::OfflineStore;
+#[cfg(feature = "server")]
+pub use pubsub::{Published, TopicBus};
+#[cfg(feature = "server")]
+pub use quarantine::{BannedSource, PanicQuarantine};
+#[cfg(feature = "server")]
+pub use scripting::{Hook, HookContext, PolicyScripts, Verdict};
+#[cfg(feature = "server")]
+pub use scanner::{BlockedScanner, ScannerCounts, ScannerDetector, ScannerKind};
+#[cfg(feature = "server")]
+pub use spoof_guard::{FlaggedIdentity, SpoofAction, SpoofAuditEntry, SpoofGuard, SpoofVerdict};
+#[cfg(feature = "server")]
+pub use reachability::PeerTraits;
+#[cfg(feature = "server")]
+pub use relay::{ClosedRelay, RelaySessionInfo, RelaySessions};
+#[cfg(feature = "server")]
+pub use rpc::ServiceDirectory;
+#[cfg(feature = "server")]
+pub use scheduler::FairScheduler;
+#[cfg(feature = "server")]
+pub use supervisor::{Supervisor, TaskHealth};
+#[cfg(feature = "server")]
+pub use telemetry::Telemetry;
+#[cfg(feature = "server")]
 pub use server::P2PServer;
-pub use protocol::{Message, MessageType, NodeInfo};
-pub use peer::{Peer, PeerManager, PeerStatus};
+#[cfg(feature = "server")]
+pub use protocol::{CarrierNat, DeliveryReceipt, DiagnoseReport, DisconnectNotice, DisconnectReason, KeyRotation, LinkQuality, LoadHint, MaintenanceNotice, Message, MessageType, MtuProbe, NodeInfo, PathMtu, PingSummary, ProtocolError, PunchBeacon, RateLimitStanding, RelayFrame, RouteRejectReason, RouteRejection, TcpPunch};
+#[cfg(feature = "server")]
+pub use peer::{ConnectionLimits, Peer, PeerManager, PeerRelease, PeerSnapshot, PeerStatus};
+#[cfg(feature = "server")]
+pub use peer_list::PeerListSnapshot;
+#[cfg(feature = "server")]
 pub use network::{Connection, NetworkManager};
+#[cfg(feature = "server")]
 pub use router::{MessageRouter, RoutedMessage, RoutingTable};
-pub use stun_server::{StunServer, StunServerConfig, StunServerStats};
-pub use stun_protocol::{is_stun_packet, extract_transaction_id};
\ No newline at end of file
+#[cfg(feature = "server")]
+pub use stun_server::{LifetimeProbeConfig, StunServer, StunServerConfig, StunRateLimitConfig, StunServerStats, StunTlsConfig, TurnConfig};
+#[cfg(feature = "server")]
+pub use stun_protocol::{is_stun_packet, extract_transaction_id};
+#[cfg(feature = "server")]
+pub use topology::{TopologyFormat, TopologySnapshot};
\ No newline at end of file
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::protocol::{LinkQuality, PingSummary};
+
+/// 每个节点保留的最近心跳数
+pub const PING_HISTORY_LEN: usize = 20;
+
+/// 心跳发出后超过该时长仍未收到 Pong 即视为丢失
+pub const PING_LOSS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 重传超时的默认下限与上限
+pub const MIN_RTO: Duration = Duration::from_millis(50);
+pub const MAX_RTO: Duration = Duration::from_secs(3);
+
+/// 平滑系数（RFC 6298）：SRTT 取新样本的 1/8，RTTVAR 取 1/4；丢失率同样按 1/8 平滑
+const SRTT_GAIN: f64 = 0.125;
+const RTTVAR_GAIN: f64 = 0.25;
+const LOSS_GAIN: f64 = 0.125;
+/// 抖动按 RFC 3550 取相邻往返时延之差的 1/16 平滑
+const JITTER_GAIN: f64 = 1.0 / 16.0;
+
+/// 往返时延、抖动与丢失率的滑动估计，并据此给出重传超时
+///
+/// 服务器按心跳的 Pong 更新每个节点的估计；可靠字节流按数据帧的确认更新，代替固定的重传间隔。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RttEstimator {
+    /// 平滑往返时延（毫秒），尚无样本时为 `None`
+    srtt: Option<f64>,
+    rttvar: f64,
+    jitter: f64,
+    last_rtt: Option<f64>,
+    loss: f64,
+}
+
+impl RttEstimator {
+    /// 记录一个往返时延样本（也算作一次成功送达）
+    pub fn sample(&mut self, rtt: Duration) {
+        let rtt = rtt.as_secs_f64() * 1000.0;
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2.0;
+            }
+            Some(srtt) => {
+                self.rttvar += RTTVAR_GAIN * ((srtt - rtt).abs() - self.rttvar);
+                self.srtt = Some(srtt + SRTT_GAIN * (rtt - srtt));
+            }
+        }
+        if let Some(last) = self.last_rtt {
+            self.jitter += JITTER_GAIN * ((rtt - last).abs() - self.jitter);
+        }
+        self.last_rtt = Some(rtt);
+        self.loss -= LOSS_GAIN * self.loss;
+    }
+
+    /// 记录一次丢失
+    pub fn lost(&mut self) {
+        self.loss += LOSS_GAIN * (1.0 - self.loss);
+    }
+
+    /// 平滑往返时延，尚无样本时为 `None`
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt.map(|srtt| Duration::from_secs_f64(srtt / 1000.0))
+    }
+
+    /// 重传超时：`SRTT + 4 × RTTVAR`，限制在 `[min, max]` 内；尚无样本时为 `initial`
+    pub fn rto(&self, initial: Duration, min: Duration, max: Duration) -> Duration {
+        let Some(srtt) = self.srtt else { return initial };
+        Duration::from_secs_f64((srtt + 4.0 * self.rttvar) / 1000.0).clamp(min, max.max(min))
+    }
+
+    /// 当前估计（重传超时按默认上下限），尚无往返时延样本时为 `None`
+    pub fn quality(&self) -> Option<LinkQuality> {
+        let srtt = self.srtt?;
+        Some(LinkQuality {
+            srtt_ms: srtt,
+            rttvar_ms: self.rttvar,
+            jitter_ms: self.jitter,
+            loss_rate: self.loss,
+            rto_ms: self.rto(Duration::ZERO, MIN_RTO, MAX_RTO).as_millis() as u64,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PingSample {
+    sent: Instant,
+    rtt: Option<Duration>,
+    /// 已作为丢失计入估计
+    lost: bool,
+}
+
+/// 服务器发给节点的最近若干次心跳及其往返时延
+///
+/// `Pong` 不携带对应的 `Ping`，按发送顺序与最早一个未超时、未应答的心跳配对。每个应答与超时
+/// 都会计入节点的 [`RttEstimator`]。
+#[derive(Debug, Clone, Default)]
+pub struct PingHistory {
+    samples: VecDeque<PingSample>,
+    estimator: RttEstimator,
+}
+
+impl PingHistory {
+    /// 记录一次发出的心跳
+    pub fn sent(&mut self, now: Instant) {
+        self.settle(now);
+        if self.samples.len() == PING_HISTORY_LEN {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(PingSample { sent: now, rtt: None, lost: false });
+    }
+
+    /// 收到 Pong：返回配对心跳的往返时延，没有待应答的心跳时返回 `None`
+    pub fn answered(&mut self, now: Instant) -> Option<Duration> {
+        self.settle(now);
+        let sample = self.samples.iter_mut().find(|sample| sample.rtt.is_none() && !sample.lost)?;
+        let rtt = now.duration_since(sample.sent);
+        sample.rtt = Some(rtt);
+        self.estimator.sample(rtt);
+        Some(rtt)
+    }
+
+    /// 把超时未应答的心跳作为丢失计入估计
+    fn settle(&mut self, now: Instant) {
+        for sample in &mut self.samples {
+            if sample.rtt.is_none() && !sample.lost && now.duration_since(sample.sent) > PING_LOSS_TIMEOUT {
+                sample.lost = true;
+                self.estimator.lost();
+            }
+        }
+    }
+
+    /// 往返时延、抖动与丢失率的平滑估计
+    pub fn estimator(&self) -> &RttEstimator {
+        &self.estimator
+    }
+
+    /// 汇总最近的心跳；仍在等待应答的心跳不计入丢失
+    pub fn summary(&self, now: Instant) -> PingSummary {
+        let rtts: Vec<f64> = self.samples.iter().filter_map(|sample| sample.rtt).map(|rtt| rtt.as_secs_f64() * 1000.0).collect();
+        let lost = self
+            .samples
+            .iter()
+            .filter(|sample| sample.rtt.is_none() && now.duration_since(sample.sent) > PING_LOSS_TIMEOUT)
+            .count() as u32;
+        let answered = rtts.len() as u32;
+        let settled = answered + lost;
+        PingSummary {
+            sent: self.samples.len() as u32,
+            answered,
+            lost,
+            loss_rate: if settled == 0 { 0.0 } else { lost as f64 / settled as f64 },
+            rtt_avg_ms: (!rtts.is_empty()).then(|| rtts.iter().sum::<f64>() / rtts.len() as f64),
+            rtt_min_ms: rtts.iter().copied().reduce(f64::min),
+            rtt_max_ms: rtts.iter().copied().reduce(f64::max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pongs_pair_in_order_and_late_pings_count_as_lost() {
+        let start = Instant::now();
+        let mut history = PingHistory::default();
+        history.sent(start);
+        history.sent(start + Duration::from_secs(1));
+        history.sent(start + Duration::from_secs(10));
+
+        // 第一个心跳超时未应答，Pong 配给仍在等待的最早心跳
+        let now = start + Duration::from_millis(10_040);
+        assert_eq!(history.answered(now), Some(Duration::from_millis(40)));
+        let summary = history.summary(now);
+        assert_eq!((summary.sent, summary.answered, summary.lost), (3, 1, 2));
+        assert!((summary.loss_rate - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(summary.rtt_avg_ms, Some(40.0));
+        assert_eq!(history.answered(now), None);
+
+        let estimator = history.estimator();
+        assert_eq!(estimator.srtt(), Some(Duration::from_millis(40)));
+        assert!((estimator.loss - (1.0 - 0.875 * 0.875) * 0.875).abs() < 1e-9, "{:?}", estimator);
+    }
+
+    #[test]
+    fn test_estimator_smooths_rtt_and_bounds_rto() {
+        let (initial, min, max) = (Duration::from_millis(300), MIN_RTO, MAX_RTO);
+        let mut estimator = RttEstimator::default();
+        assert_eq!(estimator.rto(initial, min, max), initial);
+        assert_eq!(estimator.quality(), None);
+
+        // 首个样本：SRTT = R，RTTVAR = R/2，RTO = 3R
+        estimator.sample(Duration::from_millis(100));
+        assert_eq!(estimator.rto(initial, min, max), Duration::from_millis(300));
+        estimator.sample(Duration::from_millis(200));
+        // SRTT = 100 + (200 − 100)/8，RTTVAR = 50 + (100 − 50)/4，抖动 = 100/16
+        let quality = estimator.quality().unwrap();
+        assert_eq!((quality.srtt_ms, quality.rttvar_ms, quality.jitter_ms), (112.5, 62.5, 6.25));
+        assert_eq!(quality.rto_ms, 362);
+
+        // 稳定的极小时延按下限取值，丢失使丢失率上升
+        let mut fast = RttEstimator::default();
+        fast.sample(Duration::from_millis(1));
+        fast.lost();
+        assert_eq!(fast.rto(initial, min, max), min);
+        assert_eq!(fast.quality().unwrap().loss_rate, 0.125);
+    }
+
+    #[test]
+    fn test_history_keeps_latest_pings() {
+        let start = Instant::now();
+        let mut history = PingHistory::default();
+        for i in 0..PING_HISTORY_LEN as u64 + 5 {
+            let sent = start + Duration::from_secs(i);
+            history.sent(sent);
+            history.answered(sent + Duration::from_millis(i));
+        }
+        let summary = history.summary(start + Duration::from_secs(60));
+        assert_eq!((summary.sent, summary.lost), (PING_HISTORY_LEN as u32, 0));
+        assert_eq!(summary.rtt_min_ms, Some(5.0));
+        assert_eq!(summary.rtt_max_ms, Some(24.0));
+    }
+}
@@ -0,0 +1,312 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use log::debug;
+use crate::tr;
+
+/// 链路状态通告中的单条邻接关系
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LinkEntry {
+    /// 邻居节点ID
+    pub node_id: Uuid,
+    /// 链路开销
+    pub cost: u32,
+}
+
+/// 链路状态通告（LSA），由节点泛洪其直连邻接关系
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkStateAdvertisement {
+    /// 通告来源节点
+    pub origin: Uuid,
+    /// 单调递增的序列号，用于判断通告新旧
+    pub sequence: u64,
+    /// 来源节点的直连邻居
+    pub neighbors: Vec<LinkEntry>,
+    /// 来源节点身份密钥的签名（十六进制）；经其他节点转发的通告须带有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// 来源节点签名所用的公钥（十六进制）；只有直接从来源节点收到的通告才会据此登记该公钥
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+/// 链路状态数据库：保存每个来源节点最新的通告，并维护本地邻接关系
+#[derive(Debug)]
+pub struct LinkStateDatabase {
+    local_node_id: Uuid,
+    /// 本地节点的直连邻居及链路开销
+    local_links: HashMap<Uuid, u32>,
+    /// 本地通告的序列号
+    local_sequence: u64,
+    /// 来源节点ID到（通告，接收时间）的映射
+    advertisements: HashMap<Uuid, (LinkStateAdvertisement, Instant)>,
+}
+
+impl LinkStateDatabase {
+    pub fn new(local_node_id: Uuid) -> Self {
+        Self {
+            local_node_id,
+            local_links: HashMap::new(),
+            local_sequence: 0,
+            advertisements: HashMap::new(),
+        }
+    }
+
+    /// 添加本地直连链路，返回邻接关系是否发生变化
+    pub fn add_local_link(&mut self, neighbor: Uuid, cost: u32) -> bool {
+        self.local_links.insert(neighbor, cost) != Some(cost)
+    }
+
+    /// 移除本地直连链路，返回邻接关系是否发生变化
+    pub fn remove_local_link(&mut self, neighbor: &Uuid) -> bool {
+        self.local_links.remove(neighbor).is_some()
+    }
+
+    /// 基于当前本地邻接关系生成新的本地通告（序列号递增）
+    pub fn originate(&mut self) -> LinkStateAdvertisement {
+        self.local_sequence += 1;
+        let lsa = LinkStateAdvertisement {
+            origin: self.local_node_id,
+            sequence: self.local_sequence,
+            neighbors: self
+                .local_links
+                .iter()
+                .map(|(&node_id, &cost)| LinkEntry { node_id, cost })
+                .collect(),
+            signature: None,
+            public_key: None,
+        };
+        self.advertisements.insert(self.local_node_id, (lsa.clone(), Instant::now()));
+        lsa
+    }
+
+    /// 安装收到的通告，仅当其比已有通告更新时生效，返回是否已安装
+    pub fn install(&mut self, lsa: LinkStateAdvertisement) -> bool {
+        if lsa.origin == self.local_node_id {
+            // 网络中残留了更新的本地通告（例如重启后序列号回退），跳过其序列号
+            if lsa.sequence >= self.local_sequence {
+                self.local_sequence = lsa.sequence;
+            }
+            return false;
+        }
+
+        if let Some((existing, _)) = self.advertisements.get(&lsa.origin)
+            && existing.sequence >= lsa.sequence
+        {
+            debug!(
+                "{}",
+                tr!(
+                    "忽略过期链路状态通告: origin={} seq={} (现有: {})",
+                    "Ignoring stale link-state advertisement: origin={} seq={} (current: {})",
+                    lsa.origin,
+                    lsa.sequence,
+                    existing.sequence,
+                )
+            );
+            return false;
+        }
+
+        self.advertisements.insert(lsa.origin, (lsa, Instant::now()));
+        true
+    }
+
+    /// 移除指定来源的通告
+    pub fn remove_origin(&mut self, origin: &Uuid) -> bool {
+        self.advertisements.remove(origin).is_some()
+    }
+
+    /// 丢弃超过最大存活时间的通告（本地通告除外），返回被丢弃的数量
+    pub fn expire(&mut self, max_age: Duration) -> usize {
+        let local = self.local_node_id;
+        let before = self.advertisements.len();
+        self.advertisements
+            .retain(|origin, (_, received)| *origin == local || received.elapsed() < max_age);
+        before - self.advertisements.len()
+    }
+
+    /// 列出所有已知的有向链路（来源，邻居，开销），包含本地邻接关系
+    pub fn links(&self) -> Vec<(Uuid, Uuid, u32)> {
+        let mut links: Vec<(Uuid, Uuid, u32)> = self
+            .local_links
+            .iter()
+            .map(|(&n, &c)| (self.local_node_id, n, c))
+            .collect();
+        for (origin, (lsa, _)) in &self.advertisements {
+            if *origin == self.local_node_id {
+                continue;
+            }
+            links.extend(lsa.neighbors.iter().map(|l| (*origin, l.node_id, l.cost)));
+        }
+        links
+    }
+
+    /// 当前保存的通告数量
+    pub fn len(&self) -> usize {
+        self.advertisements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.advertisements.is_empty()
+    }
+
+    /// 邻居 `node` 是否也通告了到 `neighbor` 的链路；没有通告的节点（普通客户端）视为末端节点
+    fn confirms(&self, node: &Uuid, neighbor: &Uuid) -> bool {
+        if *node == self.local_node_id {
+            return self.local_links.contains_key(neighbor);
+        }
+        match self.advertisements.get(node) {
+            Some((lsa, _)) => lsa.neighbors.iter().any(|l| l.node_id == *neighbor),
+            None => true,
+        }
+    }
+
+    /// 运行Dijkstra算法，计算到所有可达节点的（目标，下一跳，距离）
+    pub fn compute_routes(&self) -> Vec<(Uuid, Uuid, u32)> {
+        // 构建有向图：本地邻接关系直接使用，其它节点的通告只采用对端同样通告了的链路（双向检查），
+        // 避免某个节点单方面声称与任意节点相邻而吸走发往该节点的流量
+        let mut graph: HashMap<Uuid, Vec<(Uuid, u32)>> = HashMap::new();
+        graph.insert(
+            self.local_node_id,
+            self.local_links.iter().map(|(&n, &c)| (n, c)).collect(),
+        );
+        for (origin, (lsa, _)) in &self.advertisements {
+            if *origin == self.local_node_id {
+                continue;
+            }
+            graph.insert(
+                *origin,
+                lsa.neighbors
+                    .iter()
+                    .filter(|l| self.confirms(&l.node_id, origin))
+                    .map(|l| (l.node_id, l.cost))
+                    .collect(),
+            );
+        }
+
+        // 目标 -> (距离, 第一跳)
+        let mut best: HashMap<Uuid, (u32, Uuid)> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+
+        for (&neighbor, &cost) in &self.local_links {
+            let better = best.get(&neighbor).is_none_or(|&(d, _)| cost < d);
+            if better {
+                best.insert(neighbor, (cost, neighbor));
+                heap.push(Reverse((cost, neighbor)));
+            }
+        }
+
+        while let Some(Reverse((dist, node))) = heap.pop() {
+            let Some(&(known, first_hop)) = best.get(&node) else { continue };
+            if dist > known {
+                continue;
+            }
+            let Some(edges) = graph.get(&node) else { continue };
+            for &(next, cost) in edges {
+                if next == self.local_node_id {
+                    continue;
+                }
+                let candidate = dist.saturating_add(cost);
+                let better = best.get(&next).is_none_or(|&(d, _)| candidate < d);
+                if better {
+                    best.insert(next, (candidate, first_hop));
+                    heap.push(Reverse((candidate, next)));
+                }
+            }
+        }
+
+        best.into_iter()
+            .map(|(dest, (distance, next_hop))| (dest, next_hop, distance))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lsa(origin: Uuid, sequence: u64, neighbors: &[Uuid]) -> LinkStateAdvertisement {
+        LinkStateAdvertisement {
+            origin,
+            sequence,
+            neighbors: neighbors.iter().map(|&node_id| LinkEntry { node_id, cost: 1 }).collect(),
+            signature: None,
+            public_key: None,
+        }
+    }
+
+    #[test]
+    fn test_install_keeps_newest_sequence() {
+        let mut db = LinkStateDatabase::new(Uuid::new_v4());
+        let origin = Uuid::new_v4();
+
+        assert!(db.install(lsa(origin, 2, &[])));
+        assert!(!db.install(lsa(origin, 1, &[])));
+        assert!(!db.install(lsa(origin, 2, &[])));
+        assert!(db.install(lsa(origin, 3, &[])));
+    }
+
+    #[test]
+    fn test_dijkstra_next_hop_over_multiple_hops() {
+        // local - a - b - c，且 local 直连 c 的链路开销很高
+        let local = Uuid::new_v4();
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        let c = Uuid::new_v4();
+
+        let mut db = LinkStateDatabase::new(local);
+        db.add_local_link(a, 1);
+        db.add_local_link(c, 10);
+        db.install(lsa(a, 1, &[local, b]));
+        db.install(lsa(b, 1, &[a, c]));
+
+        let routes: HashMap<Uuid, (Uuid, u32)> = db
+            .compute_routes()
+            .into_iter()
+            .map(|(dest, hop, dist)| (dest, (hop, dist)))
+            .collect();
+
+        assert_eq!(routes.get(&a), Some(&(a, 1)));
+        assert_eq!(routes.get(&b), Some(&(a, 2)));
+        assert_eq!(routes.get(&c), Some(&(a, 3)));
+    }
+
+    #[test]
+    fn test_one_sided_link_is_ignored() {
+        // local - a - b，另一个邻居 m 单方面声称与 b 以开销 0 相邻
+        let local = Uuid::new_v4();
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        let m = Uuid::new_v4();
+
+        let mut db = LinkStateDatabase::new(local);
+        db.add_local_link(a, 1);
+        db.add_local_link(m, 1);
+        db.install(lsa(a, 1, &[local, b]));
+        db.install(lsa(b, 1, &[a]));
+        let before: HashMap<Uuid, (Uuid, u32)> = db.compute_routes().into_iter().map(|(dest, hop, dist)| (dest, (hop, dist))).collect();
+
+        let claim = LinkStateAdvertisement {
+            neighbors: vec![LinkEntry { node_id: local, cost: 1 }, LinkEntry { node_id: b, cost: 0 }],
+            ..lsa(m, 1, &[])
+        };
+        db.install(claim);
+        let after: HashMap<Uuid, (Uuid, u32)> = db.compute_routes().into_iter().map(|(dest, hop, dist)| (dest, (hop, dist))).collect();
+
+        assert_eq!(after.get(&b), Some(&(a, 2)));
+        assert_eq!(after.get(&b), before.get(&b));
+        // b 确认了该链路后才会采用
+        db.install(lsa(b, 2, &[a, m]));
+        let routes: HashMap<Uuid, (Uuid, u32)> = db.compute_routes().into_iter().map(|(dest, hop, dist)| (dest, (hop, dist))).collect();
+        assert_eq!(routes.get(&b), Some(&(m, 1)));
+    }
+
+    #[test]
+    fn test_local_sequence_skips_stale_self_advertisement() {
+        let local = Uuid::new_v4();
+        let mut db = LinkStateDatabase::new(local);
+        assert!(!db.install(lsa(local, 7, &[])));
+        assert_eq!(db.originate().sequence, 8);
+    }
+}
@@ -0,0 +1,387 @@
+//! 负载测试：模拟大量客户端连接目标服务器，统计握手与消息往返的时延分位数和错误率
+//!
+//! 由 `p2p_server loadtest` 子命令调用，也可在测试中直接使用 [`run`]。
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::protocol::{Message, MessageType, NodeInfo};
+
+/// 负载测试参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadtestConfig {
+    /// 目标服务器地址
+    pub target: SocketAddr,
+    /// 模拟客户端数量
+    pub clients: usize,
+    /// 全体客户端每秒最多发起的握手数，0 表示不限制
+    pub handshake_rate: f64,
+    /// 每个客户端每秒发送的数据消息数，0 表示只握手
+    pub message_rate: f64,
+    /// 数据消息负载大小（字节）
+    pub payload_size: usize,
+    /// 全体客户端每秒平均断开并以新身份重连的次数，0 表示不断开
+    pub churn_rate: f64,
+    /// 客户端侧模拟的丢包率（0.0 ~ 1.0），收发两个方向分别生效
+    pub loss_rate: f64,
+    /// 测试时长（秒）
+    pub duration_secs: u64,
+    /// 等待握手响应或消息确认的超时（毫秒）
+    pub timeout_ms: u64,
+    /// 握手使用的网络ID
+    pub network_id: String,
+}
+
+impl Default for LoadtestConfig {
+    fn default() -> Self {
+        Self {
+            target: "127.0.0.1:8080".parse().unwrap(),
+            clients: 100,
+            handshake_rate: 50.0,
+            message_rate: 1.0,
+            payload_size: 256,
+            churn_rate: 0.0,
+            loss_rate: 0.0,
+            duration_secs: 30,
+            timeout_ms: 2000,
+            network_id: crate::config::Config::default().network_id,
+        }
+    }
+}
+
+/// 时延分位数（毫秒）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencySummary {
+    /// 由以微秒为单位的样本计算分位数（最近秩法）
+    pub fn from_micros(samples: &mut [u64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_unstable();
+        let at = |q: f64| {
+            let rank = ((q * samples.len() as f64).ceil() as usize).clamp(1, samples.len());
+            samples[rank - 1] as f64 / 1000.0
+        };
+        Self { p50_ms: at(0.50), p90_ms: at(0.90), p99_ms: at(0.99), max_ms: samples[samples.len() - 1] as f64 / 1000.0 }
+    }
+}
+
+/// 一类操作（握手或消息）的统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OperationStats {
+    pub attempted: u64,
+    pub succeeded: u64,
+    /// 超时或被拒绝
+    pub failed: u64,
+    pub latency: LatencySummary,
+}
+
+impl OperationStats {
+    /// 失败占已完成操作的比例
+    pub fn error_rate(&self) -> f64 {
+        let completed = self.succeeded + self.failed;
+        if completed == 0 { 0.0 } else { self.failed as f64 / completed as f64 }
+    }
+}
+
+/// 负载测试结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoadtestReport {
+    pub elapsed_secs: f64,
+    pub clients: usize,
+    pub handshakes: OperationStats,
+    pub messages: OperationStats,
+    /// 因模拟流失而主动断开的次数
+    pub disconnects: u64,
+}
+
+impl std::fmt::Display for LoadtestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "elapsed: {:.1}s, clients: {}, disconnects: {}", self.elapsed_secs, self.clients, self.disconnects)?;
+        for (name, stats) in [("handshakes", &self.handshakes), ("messages", &self.messages)] {
+            writeln!(
+                f,
+                "{:<10} attempted {:>8}  ok {:>8}  failed {:>6} ({:.2}%)  p50 {:.2}ms  p90 {:.2}ms  p99 {:.2}ms  max {:.2}ms",
+                name,
+                stats.attempted,
+                stats.succeeded,
+                stats.failed,
+                stats.error_rate() * 100.0,
+                stats.latency.p50_ms,
+                stats.latency.p90_ms,
+                stats.latency.p99_ms,
+                stats.latency.max_ms,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// 各客户端共享的计数与时延样本
+#[derive(Default)]
+struct Recorder {
+    handshakes_attempted: AtomicU64,
+    handshakes_failed: AtomicU64,
+    messages_attempted: AtomicU64,
+    messages_failed: AtomicU64,
+    disconnects: AtomicU64,
+    handshake_micros: std::sync::Mutex<Vec<u64>>,
+    message_micros: std::sync::Mutex<Vec<u64>>,
+}
+
+impl Recorder {
+    fn record(samples: &std::sync::Mutex<Vec<u64>>, latency: Duration) {
+        samples.lock().unwrap_or_else(|e| e.into_inner()).push(latency.as_micros() as u64);
+    }
+
+    fn stats(attempted: &AtomicU64, failed: &AtomicU64, samples: &std::sync::Mutex<Vec<u64>>) -> OperationStats {
+        let mut samples = std::mem::take(&mut *samples.lock().unwrap_or_else(|e| e.into_inner()));
+        OperationStats {
+            attempted: attempted.load(Ordering::Relaxed),
+            succeeded: samples.len() as u64,
+            failed: failed.load(Ordering::Relaxed),
+            latency: LatencySummary::from_micros(&mut samples),
+        }
+    }
+}
+
+/// 全局握手速率限制：按固定间隔分配发起握手的时间点
+struct RateLimiter {
+    interval: Option<Duration>,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> Self {
+        let interval = (rate > 0.0).then(|| Duration::from_secs_f64(1.0 / rate));
+        Self { interval, next: Mutex::new(Instant::now()) }
+    }
+
+    async fn acquire(&self) {
+        let Some(interval) = self.interval else { return };
+        let slot = {
+            let mut next = self.next.lock().await;
+            let slot = (*next).max(Instant::now());
+            *next = slot + interval;
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+/// 按模拟丢包率决定是否丢弃一个数据包
+fn dropped(loss_rate: f64) -> bool {
+    loss_rate > 0.0 && rand::thread_rng().gen_bool(loss_rate.min(1.0))
+}
+
+/// 发送消息；被模拟丢包丢弃时视为已发送
+async fn send(socket: &UdpSocket, message: &Message, loss_rate: f64) -> Result<()> {
+    let bytes = serde_json::to_vec(message)?;
+    if !dropped(loss_rate) {
+        socket.send(&bytes).await?;
+    }
+    Ok(())
+}
+
+/// 运行负载测试，直到 `duration_secs` 结束后返回统计
+pub async fn run(config: LoadtestConfig) -> Result<LoadtestReport> {
+    let config = Arc::new(config);
+    let recorder = Arc::new(Recorder::default());
+    let limiter = Arc::new(RateLimiter::new(config.handshake_rate));
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(config.duration_secs);
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for index in 0..config.clients {
+        let (config, recorder, limiter) = (config.clone(), recorder.clone(), limiter.clone());
+        tasks.spawn(async move { simulate_client(index, &config, &recorder, &limiter, deadline).await });
+    }
+    while let Some(result) = tasks.join_next().await {
+        result??;
+    }
+
+    Ok(LoadtestReport {
+        elapsed_secs: started.elapsed().as_secs_f64(),
+        clients: config.clients,
+        handshakes: Recorder::stats(&recorder.handshakes_attempted, &recorder.handshakes_failed, &recorder.handshake_micros),
+        messages: Recorder::stats(&recorder.messages_attempted, &recorder.messages_failed, &recorder.message_micros),
+        disconnects: recorder.disconnects.load(Ordering::Relaxed),
+    })
+}
+
+/// 一个模拟客户端：握手 → 按速率发送需确认的数据消息并应答服务器心跳 → 按流失率断开并以新身份重连，直到截止时间
+async fn simulate_client(
+    index: usize,
+    config: &LoadtestConfig,
+    recorder: &Recorder,
+    limiter: &RateLimiter,
+    deadline: Instant,
+) -> Result<()> {
+    let timeout = Duration::from_millis(config.timeout_ms.max(1));
+    let payload = serde_json::json!({ "loadtest": "x".repeat(config.payload_size) });
+    let message_interval = (config.message_rate > 0.0).then(|| Duration::from_secs_f64(1.0 / config.message_rate));
+    let bind_addr: SocketAddr = if config.target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse()?;
+    let mut buffer = vec![0u8; 65536];
+
+    while Instant::now() < deadline {
+        limiter.acquire().await;
+        if Instant::now() >= deadline {
+            break;
+        }
+        let socket = UdpSocket::bind(bind_addr).await?;
+        socket.connect(config.target).await?;
+        let local_addr = socket.local_addr()?;
+
+        // 握手
+        let info = NodeInfo::new(format!("loadtest-{}", index), local_addr, config.network_id.clone());
+        recorder.handshakes_attempted.fetch_add(1, Ordering::Relaxed);
+        let sent_at = Instant::now();
+        send(&socket, &Message::handshake_request(info)?, config.loss_rate).await?;
+        let accepted = loop {
+            let remaining = timeout.saturating_sub(sent_at.elapsed());
+            match tokio::time::timeout(remaining, socket.recv(&mut buffer)).await {
+                Ok(Ok(_)) if dropped(config.loss_rate) => continue,
+                Ok(Ok(len)) => match serde_json::from_slice::<Message>(&buffer[..len]) {
+                    Ok(message) if message.message_type == MessageType::HandshakeResponse => {
+                        break message.payload.get("success").and_then(|v| v.as_bool()).unwrap_or(false);
+                    }
+                    Ok(message) if message.message_type == MessageType::Error => break false,
+                    _ => continue,
+                },
+                // 目标不可达时本地套接字可能报错（如 ICMP 端口不可达）
+                Ok(Err(_)) | Err(_) => break false,
+            }
+        };
+        if !accepted {
+            recorder.handshakes_failed.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        Recorder::record(&recorder.handshake_micros, sent_at.elapsed());
+
+        // 本次会话的时长：流失率均摊到每个客户端后按指数分布抽样
+        let session_end = if config.churn_rate > 0.0 {
+            let mean_secs = config.clients as f64 / config.churn_rate;
+            let sample: f64 = rand::thread_rng().gen_range(f64::EPSILON..1.0);
+            (Instant::now() + Duration::from_secs_f64(-sample.ln() * mean_secs)).min(deadline)
+        } else {
+            deadline
+        };
+
+        let mut pending: HashMap<Uuid, Instant> = HashMap::new();
+        let mut sequence = 0u32;
+        let mut ticker = message_interval.map(|interval| {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker
+        });
+        loop {
+            let now = Instant::now();
+            if now >= session_end {
+                break;
+            }
+            pending.retain(|_, sent| {
+                let alive = now.duration_since(*sent) < timeout;
+                if !alive {
+                    recorder.messages_failed.fetch_add(1, Ordering::Relaxed);
+                }
+                alive
+            });
+            tokio::select! {
+                _ = tokio::time::sleep_until(session_end.into()) => break,
+                _ = async { ticker.as_mut().unwrap().tick().await }, if ticker.is_some() => {
+                    sequence = sequence.wrapping_add(1);
+                    let message = Message::new_with_ack(MessageType::Data, payload.clone(), local_addr, sequence);
+                    recorder.messages_attempted.fetch_add(1, Ordering::Relaxed);
+                    if send(&socket, &message, config.loss_rate).await.is_ok() {
+                        pending.insert(message.id, Instant::now());
+                    } else {
+                        recorder.messages_failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                received = socket.recv(&mut buffer) => {
+                    let Ok(len) = received else { continue };
+                    if dropped(config.loss_rate) {
+                        continue;
+                    }
+                    let Ok(message) = serde_json::from_slice::<Message>(&buffer[..len]) else { continue };
+                    match message.message_type {
+                        MessageType::Ack => {
+                            for sent in message.acked_ids().iter().filter_map(|id| pending.remove(id)) {
+                                Recorder::record(&recorder.message_micros, sent.elapsed());
+                            }
+                        }
+                        MessageType::Ping => {
+                            let _ = send(&socket, &Message::pong(), config.loss_rate).await;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        // 测试结束时仍未确认的消息等待到超时
+        let drain_until = Instant::now() + timeout;
+        while !pending.is_empty() {
+            let remaining = drain_until.saturating_duration_since(Instant::now());
+            match tokio::time::timeout(remaining, socket.recv(&mut buffer)).await {
+                Ok(Ok(_)) if dropped(config.loss_rate) => continue,
+                Ok(Ok(len)) => {
+                    if let Ok(message) = serde_json::from_slice::<Message>(&buffer[..len])
+                        && message.message_type == MessageType::Ack
+                    {
+                        for sent in message.acked_ids().iter().filter_map(|id| pending.remove(id)) {
+                            Recorder::record(&recorder.message_micros, sent.elapsed());
+                        }
+                    }
+                }
+                Ok(Err(_)) => continue,
+                Err(_) => break,
+            }
+        }
+        recorder.messages_failed.fetch_add(pending.len() as u64, Ordering::Relaxed);
+
+        let _ = send(&socket, &Message::disconnect("loadtest".to_string()), config.loss_rate).await;
+        if session_end < deadline {
+            recorder.disconnects.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_percentiles() {
+        let mut samples: Vec<u64> = (1..=100).map(|ms| ms * 1000).collect();
+        let summary = LatencySummary::from_micros(&mut samples);
+        assert_eq!(summary, LatencySummary { p50_ms: 50.0, p90_ms: 90.0, p99_ms: 99.0, max_ms: 100.0 });
+        assert_eq!(LatencySummary::from_micros(&mut []), LatencySummary::default());
+    }
+
+    #[test]
+    fn test_error_rate() {
+        let stats = OperationStats { attempted: 10, succeeded: 6, failed: 2, ..OperationStats::default() };
+        assert_eq!(stats.error_rate(), 0.25);
+        assert_eq!(OperationStats::default().error_rate(), 0.0);
+    }
+}
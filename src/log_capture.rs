@@ -0,0 +1,120 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use log::{Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+
+/// 一条被捕获的日志
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// UNIX时间戳（毫秒）
+    pub timestamp_ms: u64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// 最近日志的环形缓冲区，供管理接口展示
+#[derive(Debug)]
+pub struct RecentLogs {
+    capacity: usize,
+    entries: Mutex<VecDeque<LogEntry>>,
+}
+
+impl RecentLogs {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn push(&self, entry: LogEntry) {
+        let Ok(mut entries) = self.entries.lock() else { return };
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// 获取最近的 `limit` 条日志（按时间顺序）
+    pub fn recent(&self, limit: usize) -> Vec<LogEntry> {
+        let Ok(entries) = self.entries.lock() else { return Vec::new() };
+        let skip = entries.len().saturating_sub(limit);
+        entries.iter().skip(skip).cloned().collect()
+    }
+}
+
+/// 包装 env_logger：照常输出日志，同时把记录写入 [`RecentLogs`]
+pub struct CapturingLogger {
+    inner: env_logger::Logger,
+    logs: Arc<RecentLogs>,
+}
+
+impl CapturingLogger {
+    pub fn new(inner: env_logger::Logger, logs: Arc<RecentLogs>) -> Self {
+        Self { inner, logs }
+    }
+
+    /// 安装为全局日志实现
+    pub fn init(self) -> Result<(), log::SetLoggerError> {
+        let max_level = self.inner.filter();
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for CapturingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+        self.logs.push(LogEntry {
+            timestamp_ms: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            level: record.level().to_string(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// 数据包内容的日志预览：UTF-8 文本原样输出，否则输出十六进制；超过 `limit` 字节的部分截断
+pub fn packet_preview(data: &[u8], limit: usize) -> String {
+    let shown = &data[..data.len().min(limit)];
+    let mut preview = match std::str::from_utf8(shown) {
+        Ok(text) => text.to_string(),
+        // 截断点可能落在多字节字符中间，只要前面的部分是合法文本就仍按文本输出
+        Err(e) if e.error_len().is_none() && e.valid_up_to() > 0 => String::from_utf8_lossy(&shown[..e.valid_up_to()]).into_owned(),
+        Err(_) => format!("(非UTF-8) {}", shown.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+    };
+    if data.len() > shown.len() {
+        preview.push_str(&format!("…（共 {} 字节，已截断）", data.len()));
+    }
+    preview
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_preview_truncates() {
+        assert_eq!(packet_preview(b"hello", 16), "hello");
+        assert_eq!(packet_preview(b"hello world", 5), "hello…（共 11 字节，已截断）");
+        assert_eq!(packet_preview(&[0xff, 0x00], 16), "(非UTF-8) ff00");
+        // 截断在多字节字符中间
+        assert_eq!(packet_preview("你好".as_bytes(), 4), "你…（共 6 字节，已截断）");
+    }
+}
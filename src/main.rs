@@ -1,19 +1,17 @@
-use log::{info, error};
+use log::{info, warn, error};
 use log::LevelFilter;
-use clap::{Parser, ArgAction};
+use clap::{Parser, Subcommand, ArgAction};
 use clap::ArgGroup;
 
-mod network;
-mod peer;
-mod protocol;
-mod server;
-mod config;
-mod router;
-mod stun_server;
-mod stun_protocol;
-
-use crate::server::P2PServer;
-use crate::config::Config;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use p2p_handshake_server::service::{self, SystemdNotifier};
+use p2p_handshake_server::i18n;
+use p2p_handshake_server::loadtest::{self, LoadtestConfig};
+use p2p_handshake_server::secrets;
+use p2p_handshake_server::selftest;
+use p2p_handshake_server::{CapturingLogger, Config, EncryptedSection, P2PServer, RecentLogs};
+use p2p_handshake_server::tr;
 
 #[derive(Parser)]
 #[command(name = "p2p_server")]
@@ -60,6 +58,23 @@ struct Args {
     #[arg(long = "relay", action = ArgAction::SetTrue)]
     enable_relay: bool,
 
+    /// 启动后写入进程号的PID文件（退出时删除）
+    #[arg(long)]
+    pid_file: Option<String>,
+
+    /// 用环境变量 P2P_CONFIG_PASSPHRASE 中的口令加密指定的 JSON 文件，输出可放入配置 `encrypted` 字段的内容后退出
+    #[arg(long, value_name = "FILE")]
+    encrypt_secrets: Option<String>,
+
+    /// 预检后退出：加载并校验配置、解析域名、试绑定端口，输出生效配置（敏感字段已隐藏）；有失败项时以非零状态退出
+    #[arg(long = "check", action = ArgAction::SetTrue)]
+    check: bool,
+
+    /// 由Windows服务控制管理器启动（仅Windows）
+    #[cfg(windows)]
+    #[arg(long = "service", action = ArgAction::SetTrue)]
+    service: bool,
+
     /// 设置日志级别为 TRACE
     #[arg(long = "TRACE", action = ArgAction::SetTrue)]
     trace: bool,
@@ -75,13 +90,141 @@ struct Args {
     /// 设置日志级别为 ERROR
     #[arg(long = "ERROR", action = ArgAction::SetTrue)]
     error: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+#[derive(Subcommand)]
+enum Command {
+    /// 模拟大量客户端连接目标服务器，报告握手与消息往返的时延分位数和错误率
+    Loadtest(LoadtestArgs),
+}
+
+#[derive(clap::Args)]
+struct LoadtestArgs {
+    /// 目标服务器地址
+    #[arg(long)]
+    target: std::net::SocketAddr,
+    /// 模拟客户端数量
+    #[arg(long, default_value_t = 100)]
+    clients: usize,
+    /// 全体客户端每秒最多发起的握手数，0 表示不限制
+    #[arg(long, default_value_t = 50.0)]
+    handshake_rate: f64,
+    /// 每个客户端每秒发送的数据消息数，0 表示只握手
+    #[arg(long, default_value_t = 1.0)]
+    message_rate: f64,
+    /// 数据消息负载大小（字节）
+    #[arg(long, default_value_t = 256)]
+    payload_size: usize,
+    /// 全体客户端每秒平均断开并重连的次数
+    #[arg(long, default_value_t = 0.0)]
+    churn_rate: f64,
+    /// 客户端侧模拟的丢包率（0.0 ~ 1.0）
+    #[arg(long, default_value_t = 0.0)]
+    loss_rate: f64,
+    /// 测试时长（秒）
+    #[arg(long, default_value_t = 30)]
+    duration: u64,
+    /// 等待握手响应或消息确认的超时（毫秒）
+    #[arg(long, default_value_t = 2000)]
+    timeout_ms: u64,
+    /// 握手使用的网络ID
+    #[arg(long, default_value = "p2p_default")]
+    network_id: String,
+    /// 以 JSON 输出结果
+    #[arg(long, action = ArgAction::SetTrue)]
+    json: bool,
+}
+
+/// 启动顺序：解析参数 → 初始化日志 → 加载配置 → 绑定端口 → 写PID文件/通知就绪 → 运行 → 通知停止
+///
+/// 进程始终在前台运行，由 systemd（`Type=notify`）、Windows 服务控制管理器或其他进程管理器负责后台化与监管。
+fn main() -> anyhow::Result<()> {
     // 解析命令行参数，并根据日志级别初始化日志
     let args = Args::parse();
+    if let Some(path) = &args.encrypt_secrets {
+        return encrypt_secrets(path);
+    }
+    let recent_logs = init_logging(&args)?;
+    if let Some(Command::Loadtest(loadtest_args)) = &args.command {
+        return run_loadtest(loadtest_args);
+    }
 
+    if args.check {
+        return run_check(&args);
+    }
+
+    #[cfg(windows)]
+    if args.service {
+        return service::windows::run(move |stop_rx| {
+            let config = load_config(&args)?;
+            tokio::runtime::Runtime::new()?
+                .block_on(run_server(config, recent_logs.clone(), args.pid_file.as_deref(), Some(stop_rx)))
+        });
+    }
+
+    let config = load_config(&args)?;
+    tokio::runtime::Runtime::new()?
+        .block_on(run_server(config, recent_logs, args.pid_file.as_deref(), None))
+}
+
+/// 预检：生效配置（JSON）输出到标准输出，自检结果输出到标准错误
+fn run_check(args: &Args) -> anyhow::Result<()> {
+    let config = load_config(args)?;
+    let report = tokio::runtime::Runtime::new()?.block_on(selftest::run(&config));
+    println!("{}", serde_json::to_string_pretty(&config.to_redacted_json()?)?);
+    eprint!("{}", report);
+    report.into_result()?;
+    eprintln!("{}", tr!("预检通过", "Preflight check passed"));
+    Ok(())
+}
+
+/// 加密一段配置并输出到标准输出
+fn encrypt_secrets(path: &str) -> anyhow::Result<()> {
+    let passphrase = std::env::var(secrets::PASSPHRASE_ENV)
+        .map_err(|_| anyhow::anyhow!("请在环境变量 {} 中提供口令", secrets::PASSPHRASE_ENV))?;
+    let section: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let sealed = EncryptedSection::seal(&section, &passphrase)?;
+    println!("{}", serde_json::to_string_pretty(&sealed)?);
+    Ok(())
+}
+
+/// 运行负载测试并输出结果
+fn run_loadtest(args: &LoadtestArgs) -> anyhow::Result<()> {
+    let config = LoadtestConfig {
+        target: args.target,
+        clients: args.clients,
+        handshake_rate: args.handshake_rate,
+        message_rate: args.message_rate,
+        payload_size: args.payload_size,
+        churn_rate: args.churn_rate,
+        loss_rate: args.loss_rate,
+        duration_secs: args.duration,
+        timeout_ms: args.timeout_ms,
+        network_id: args.network_id.clone(),
+    };
+    info!(
+        "{}",
+        tr!(
+            "负载测试开始：目标 {}，{} 个客户端，持续 {} 秒",
+            "Load test started: target {}, {} clients, {} seconds",
+            config.target,
+            config.clients,
+            config.duration_secs,
+        )
+    );
+    let report = tokio::runtime::Runtime::new()?.block_on(loadtest::run(config))?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
+
+fn init_logging(args: &Args) -> anyhow::Result<Arc<RecentLogs>> {
     let explicit_level = if args.trace {
         Some(LevelFilter::Trace)
     } else if args.debug {
@@ -96,23 +239,24 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
+    let mut log_builder = env_logger::Builder::from_default_env();
     if let Some(level) = explicit_level {
-        env_logger::Builder::from_default_env()
-            .filter_level(level)
-            .init();
-    } else {
-        // 未指定日志级别时，使用环境变量或默认级别
-        env_logger::Builder::from_default_env().init();
+        log_builder.filter_level(level);
     }
+    // 未指定日志级别时，使用环境变量或默认级别；同时捕获最近日志供管理接口展示
+    let recent_logs = Arc::new(RecentLogs::new(500));
+    CapturingLogger::new(log_builder.build(), recent_logs.clone()).init()?;
+    Ok(recent_logs)
+}
 
-    info!("启动P2P握手服务器...");
-    
+fn load_config(args: &Args) -> anyhow::Result<Config> {
     // 确定基础配置：优先从文件加载，否则使用默认值
-    let mut config = if let Some(config_path) = args.config {
-        Config::from_file(&config_path)?
+    let mut config = if let Some(config_path) = &args.config {
+        Config::from_file(config_path)?
     } else {
         Config::default()
     };
+    i18n::set_language(config.logging.language);
 
     // 使用命令行参数覆盖配置
     if let Some(address) = args.address {
@@ -121,8 +265,8 @@ async fn main() -> anyhow::Result<()> {
     if let Some(max_connections) = args.max_connections {
         config.max_connections = max_connections;
     }
-    if let Some(network_id) = args.network_id {
-        config.network_id = network_id;
+    if let Some(network_id) = &args.network_id {
+        config.network_id = network_id.clone();
     }
     if let Some(heartbeat_interval) = args.heartbeat_interval {
         config.heartbeat_interval = heartbeat_interval;
@@ -144,18 +288,92 @@ async fn main() -> anyhow::Result<()> {
         config.allow_symmetric_nat_relay = true;
     }
 
-    info!("最终配置: {:?}", config);
+    info!("{}", tr!("最终配置: {:?}", "Effective configuration: {:?}", config));
+    Ok(config)
+}
+
+/// 等待关闭请求：操作系统信号或（Windows服务模式下）服务控制管理器的停止命令
+async fn wait_for_stop(external_stop: Option<oneshot::Receiver<()>>) -> &'static str {
+    let os_signal = async {
+        match service::shutdown_signal().await {
+            Ok(name) => name,
+            Err(e) => {
+                warn!("{}", tr!("无法监听关闭信号: {}", "Unable to listen for shutdown signals: {}", e));
+                std::future::pending().await
+            }
+        }
+    };
+    let external = async move {
+        match external_stop {
+            Some(rx) => {
+                let _ = rx.await;
+            }
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        name = os_signal => name,
+        _ = external => "服务停止命令",
+    }
+}
+
+async fn run_server(
+    config: Config,
+    recent_logs: Arc<RecentLogs>,
+    pid_file: Option<&str>,
+    external_stop: Option<oneshot::Receiver<()>>,
+) -> anyhow::Result<()> {
+    info!("{}", tr!("启动P2P握手服务器...", "Starting P2P handshake server..."));
+
+    // 启动自检：配置有误、端口无法绑定时不启动
+    if config.startup.self_test {
+        let report = selftest::run(&config).await;
+        report.log();
+        report.into_result()?;
+    }
 
-    // 创建并启动服务器
+    // 创建服务器（此时已绑定监听端口）
     let mut server = P2PServer::new(config.clone()).await?;
+    server.set_recent_logs(recent_logs);
+    let shutdown_tx = server.shutdown_sender();
+
+    if let Some(path) = pid_file {
+        service::write_pid_file(path)?;
+    }
+
+    // 通知进程管理器服务已就绪，并按需启动看门狗心跳
+    let notifier = SystemdNotifier::from_env();
+    let watchdog_task = notifier.as_ref().and_then(|notifier| {
+        notifier.ready(&format!("监听 {}", config.listen_address));
+        notifier.start_watchdog_task()
+    });
+
+    let stop_notifier = notifier.clone();
+    tokio::spawn(async move {
+        let reason = wait_for_stop(external_stop).await;
+        info!("{}", tr!("收到{}，正在关闭服务器...", "Received {}, shutting down server...", reason));
+        if let Some(notifier) = stop_notifier {
+            notifier.stopping();
+        }
+        let _ = shutdown_tx.send(());
+    });
     
-    info!("服务器正在监听地址: {}", config.listen_address);
+    info!("{}", tr!("服务器正在监听地址: {}", "Server listening on: {}", config.listen_address));
     
     // 启动服务器
-    if let Err(e) = server.run().await {
-        error!("服务器运行错误: {}", e);
+    let result = server.run().await;
+
+    if let Some(watchdog_task) = watchdog_task {
+        watchdog_task.abort();
+    }
+    if let Some(path) = pid_file {
+        let _ = std::fs::remove_file(path);
+    }
+
+    if let Err(e) = result {
+        error!("{}", tr!("服务器运行错误: {}", "Server error: {}", e));
         return Err(e);
     }
     
     Ok(())
-}
\ No newline at end of file
+}
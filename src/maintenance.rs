@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use tokio::task::JoinHandle;
+
+use crate::peer::PeerManager;
+use crate::protocol::{DisconnectReason, MaintenanceNotice, Message, ProtocolError};
+use crate::tr;
+
+/// 计划维护状态
+///
+/// 管理接口发布公告后向所有已认证节点广播 `MaintenanceNotice`；之后握手成功的节点也会收到尚未开始的公告。
+/// 公告要求排空时，到开始时间后服务器拒绝新的握手，并向现有节点发送指向备用服务器的 `Reconnect` 提示（没有备用服务器时发送 `Disconnect`）。
+#[derive(Debug, Default)]
+pub struct Maintenance {
+    notice: Mutex<Option<MaintenanceNotice>>,
+    draining: AtomicBool,
+    drain_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl Maintenance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前公告（包括进行中的维护），维护结束后返回 `None`
+    pub fn notice(&self) -> Option<MaintenanceNotice> {
+        let mut notice = self.notice.lock().unwrap();
+        if notice.as_ref().is_some_and(|n| n.start_time + n.duration_secs <= now_secs()) {
+            *notice = None;
+        }
+        notice.clone()
+    }
+
+    /// 尚未开始的公告，用于通知新加入的节点
+    pub fn upcoming(&self) -> Option<MaintenanceNotice> {
+        self.notice().filter(|n| n.start_time > now_secs())
+    }
+
+    /// 是否处于排空状态，维护窗口结束后自动恢复
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed) && self.notice().is_some()
+    }
+
+    /// 发布公告并广播给所有已认证节点，返回通知到的节点数；新公告替换尚未执行的排空计划
+    ///
+    /// 公告无法序列化时返回错误，不改变当前公告。
+    pub async fn announce(self: &Arc<Self>, notice: MaintenanceNotice, peer_manager: Arc<PeerManager>) -> Result<usize, ProtocolError> {
+        let message = Message::maintenance_notice(&notice)?;
+        if let Some(task) = self.drain_task.lock().unwrap().take() {
+            task.abort();
+        }
+        *self.notice.lock().unwrap() = Some(notice.clone());
+
+        let mut notified = 0;
+        for peer in peer_manager.get_authenticated_peers().await {
+            let peer = peer.read().await;
+            match peer.send_message(&message).await {
+                Ok(()) => notified += 1,
+                Err(e) => warn!("{}", tr!("发送维护公告到 {} 失败: {}", "Failed to send maintenance notice to {}: {}", peer.addr(), e)),
+            }
+        }
+
+        if notice.drain {
+            let delay = Duration::from_secs(notice.start_time.saturating_sub(now_secs()));
+            let maintenance = self.clone();
+            *self.drain_task.lock().unwrap() = Some(tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                maintenance.drain(&notice, &peer_manager).await;
+            }));
+        }
+        Ok(notified)
+    }
+
+    /// 取消公告与排空计划，并恢复接受握手；没有公告时返回 `false`
+    pub fn cancel(&self) -> bool {
+        if let Some(task) = self.drain_task.lock().unwrap().take() {
+            task.abort();
+        }
+        self.draining.store(false, Ordering::Relaxed);
+        self.notice.lock().unwrap().take().is_some()
+    }
+
+    /// 进入排空状态：拒绝新握手，提示现有节点改连备用服务器；没有备用服务器时通知节点断开，维护结束后再重连
+    async fn drain(&self, notice: &MaintenanceNotice, peer_manager: &PeerManager) {
+        self.draining.store(true, Ordering::Relaxed);
+        let peers = peer_manager.get_authenticated_peers().await;
+        info!("{}", tr!("维护开始，服务器进入排空状态（{} 个节点在线）", "Maintenance started, server is draining ({} nodes online)", peers.len()));
+        let reason = format!("服务器维护：{}", notice.reason);
+        let hint = match notice.alternative_server {
+            Some(alternative) => Message::reconnect(alternative, false, reason),
+            None => Message::disconnect_with_code(DisconnectReason::Drain, reason),
+        };
+        for peer in peers {
+            let peer = peer.read().await;
+            if let Err(e) = peer.send_message(&hint).await {
+                warn!("{}", tr!("发送重连提示到 {} 失败: {}", "Failed to send reconnect hint to {}: {}", peer.addr(), e));
+            }
+        }
+    }
+
+    /// 排空状态下拒绝握手时建议的重试等待秒数（到维护结束为止）
+    pub fn retry_after_secs(&self) -> u64 {
+        self.notice()
+            .map(|n| (n.start_time + n.duration_secs).saturating_sub(now_secs()))
+            .unwrap_or(0)
+            .max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::NodeInfo;
+
+    #[tokio::test]
+    async fn test_drain_starts_at_start_time_and_cancel_resumes() {
+        let local = NodeInfo::new("local".to_string(), "127.0.0.1:0".parse().unwrap(), "test".to_string());
+        let peer_manager = Arc::new(PeerManager::new(local, 10));
+        let maintenance = Arc::new(Maintenance::new());
+        let notice = MaintenanceNotice {
+            start_time: now_secs() + 1,
+            duration_secs: 60,
+            alternative_server: None,
+            reason: "升级".to_string(),
+            drain: true,
+        };
+        assert_eq!(maintenance.announce(notice.clone(), peer_manager).await.unwrap(), 0);
+        assert_eq!(maintenance.upcoming(), Some(notice));
+        assert!(!maintenance.is_draining());
+
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        assert!(maintenance.is_draining());
+        assert!(maintenance.upcoming().is_none());
+        assert!(maintenance.notice().is_some());
+        assert!(maintenance.retry_after_secs() <= 60);
+
+        assert!(maintenance.cancel());
+        assert!(!maintenance.is_draining());
+        assert!(maintenance.notice().is_none());
+    }
+}
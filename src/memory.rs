@@ -0,0 +1,160 @@
+use std::sync::Arc;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::MemoryConfig;
+use crate::metrics::ServerMetrics;
+use crate::peer::PeerManager;
+use crate::protocol::RelayCloseReason;
+use crate::relay::{self, RelaySessions};
+use crate::router::MessageRouter;
+use crate::tr;
+
+/// 各部分的近似内存占用（字节）
+///
+/// 按条目数乘以条目的固定大小估算，再加上节点信息、离线消息等可变长度的内容；
+/// 不含哈希表的空闲容量与分配器开销，只用于观察趋势和触发软限制。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryUsage {
+    pub peer_table: usize,
+    pub routing_table: usize,
+    pub message_cache: usize,
+    pub relay_sessions: usize,
+    pub offline_queues: usize,
+    pub total: usize,
+}
+
+/// 一次软限制检查中各部分淘汰的条目数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Evictions {
+    pub peers: usize,
+    pub routes: usize,
+    pub cached_messages: usize,
+    pub relay_sessions: usize,
+    pub offline_messages: usize,
+}
+
+impl Evictions {
+    pub fn total(&self) -> usize {
+        self.peers + self.routes + self.cached_messages + self.relay_sessions + self.offline_messages
+    }
+}
+
+/// 内存统计：汇总节点表、路由表、去重缓存、中继会话与离线消息暂存的占用，
+/// 并在超过配置的软限制时主动淘汰
+pub struct MemoryAccounting {
+    config: MemoryConfig,
+    peer_manager: Arc<PeerManager>,
+    message_router: Arc<MessageRouter>,
+    relay_sessions: Arc<RelaySessions>,
+    metrics: Arc<ServerMetrics>,
+}
+
+impl MemoryAccounting {
+    pub fn new(
+        config: MemoryConfig,
+        peer_manager: Arc<PeerManager>,
+        message_router: Arc<MessageRouter>,
+        relay_sessions: Arc<RelaySessions>,
+        metrics: Arc<ServerMetrics>,
+    ) -> Self {
+        Self { config, peer_manager, message_router, relay_sessions, metrics }
+    }
+
+    pub fn config(&self) -> &MemoryConfig {
+        &self.config
+    }
+
+    /// 当前各部分的近似占用
+    pub async fn usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage {
+            peer_table: self.peer_manager.approx_bytes().await,
+            routing_table: self.message_router.routing_table_bytes().await,
+            message_cache: self.message_router.message_cache_bytes().await,
+            relay_sessions: self.relay_sessions.approx_bytes(),
+            offline_queues: self.peer_manager.offline_store().map_or(0, |store| store.approx_bytes()),
+            total: 0,
+        };
+        usage.total = usage.peer_table + usage.routing_table + usage.message_cache + usage.relay_sessions + usage.offline_queues;
+        usage
+    }
+
+    /// 对超过软限制的部分执行淘汰，返回各部分淘汰的条目数
+    pub async fn enforce(&self) -> Evictions {
+        let config = &self.config;
+        let mut evictions = Evictions::default();
+        if config.peer_table_bytes > 0 {
+            evictions.peers = self.peer_manager.evict_unauthenticated(config.peer_table_bytes).await;
+        }
+        if config.routing_table_bytes > 0 {
+            evictions.routes = self.message_router.evict_routes(config.routing_table_bytes).await;
+        }
+        if config.message_cache_bytes > 0 {
+            evictions.cached_messages = self.message_router.evict_message_cache(config.message_cache_bytes).await;
+        }
+        if config.relay_bytes > 0 {
+            let closed = self.relay_sessions.evict_idle(config.relay_bytes);
+            evictions.relay_sessions = closed.len();
+            relay::notify_closed(&self.peer_manager, &closed, RelayCloseReason::Evicted).await;
+        }
+        if config.offline_queue_bytes > 0
+            && let Some(store) = self.peer_manager.offline_store()
+        {
+            evictions.offline_messages = store.evict_oldest(config.offline_queue_bytes);
+        }
+
+        let total = evictions.total();
+        if total > 0 {
+            ServerMetrics::add(&self.metrics.memory_evictions, total as u64);
+            warn!("{}", tr!("内存占用超过软限制，已淘汰 {} 个条目: {:?}", "Memory usage exceeded soft limits, evicted {} entries: {:?}", total, evictions));
+        } else {
+            debug!("{}", tr!("内存占用未超过软限制", "Memory usage is below the soft limit"));
+        }
+        evictions
+    }
+
+    /// 是否配置了任一软限制
+    pub fn has_limits(&self) -> bool {
+        let config = &self.config;
+        [config.peer_table_bytes, config.routing_table_bytes, config.message_cache_bytes, config.relay_bytes, config.offline_queue_bytes]
+            .iter()
+            .any(|limit| *limit > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::protocol::NodeInfo;
+    use uuid::Uuid;
+
+    async fn accounting(config: MemoryConfig) -> MemoryAccounting {
+        let info = NodeInfo::new("server".to_string(), "127.0.0.1:0".parse().unwrap(), "test".to_string());
+        let peer_manager = Arc::new(PeerManager::new(info.clone(), 100));
+        let router = Arc::new(MessageRouter::new(info.id, peer_manager.clone()));
+        let metrics = Arc::new(ServerMetrics::new());
+        MemoryAccounting::new(config, peer_manager, router, Arc::new(RelaySessions::new()), metrics)
+    }
+
+    #[tokio::test]
+    async fn test_usage_and_relay_eviction() {
+        let memory = accounting(MemoryConfig { relay_bytes: 1, ..Config::default().memory }).await;
+        assert_eq!(memory.usage().await, MemoryUsage::default());
+        assert!(memory.has_limits());
+
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        memory.relay_sessions.record(a, b, 100);
+        memory.relay_sessions.record(a, c, 100);
+        let usage = memory.usage().await;
+        assert!(usage.relay_sessions > 0);
+        assert_eq!(usage.total, usage.relay_sessions);
+
+        // 软限制小于一个会话，全部淘汰
+        let evictions = memory.enforce().await;
+        assert_eq!(evictions.relay_sessions, 2);
+        assert_eq!(memory.usage().await.relay_sessions, 0);
+        assert_eq!(memory.metrics.snapshot().memory_evictions, 2);
+    }
+}
@@ -0,0 +1,409 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+/// 服务器运行指标（原子计数器，可在任意任务中无锁更新）
+#[derive(Debug)]
+pub struct ServerMetrics {
+    started_at: Instant,
+    /// 启动时刻的UNIX时间戳（秒）
+    started_at_unix: u64,
+    /// 收到的UDP数据包数量
+    pub packets_received: AtomicU64,
+    /// 收到的UDP字节数
+    pub bytes_received: AtomicU64,
+    /// 成功处理的协议消息数量
+    pub messages_handled: AtomicU64,
+    /// 处理失败的数据包数量（解析或处理出错）
+    pub handle_errors: AtomicU64,
+    /// 经路由器转发的数据消息数量
+    pub routed_messages: AtomicU64,
+    /// 成功转发的流量转发数据包数量
+    pub relay_packets: AtomicU64,
+    /// 成功转发的流量转发字节数
+    pub relay_bytes: AtomicU64,
+    /// 双方公网IP相同、下发了内网地址的直连协调次数
+    pub p2p_same_ip_coordinations: AtomicU64,
+    /// 上报经内网地址打通的直连次数
+    pub p2p_private_path: AtomicU64,
+    /// 上报经公网地址打通的直连次数
+    pub p2p_public_path: AtomicU64,
+    /// 上报改用流量转发的直连次数
+    pub p2p_relay_path: AtomicU64,
+    /// 上报失败的直连次数
+    pub p2p_failed: AtomicU64,
+    /// 因调度队列已满丢弃的数据包数量
+    pub packets_dropped: AtomicU64,
+    /// 因排队超时丢弃的数据包数量
+    pub packets_expired: AtomicU64,
+    /// 处理时发生 panic 的数据包数量
+    pub packet_panics: AtomicU64,
+    /// 来源IP被封禁而丢弃的数据包数量
+    pub packets_banned: AtomicU64,
+    /// 超过 `max_datagram_size` 而丢弃的数据包数量
+    pub packets_oversized: AtomicU64,
+    /// 无法解析为协议消息的数据包数量
+    pub packets_malformed: AtomicU64,
+    /// 判定为协议扫描器的次数
+    pub scanners_detected: AtomicU64,
+    /// 来源被判定为扫描器而静默丢弃的数据包数量
+    pub packets_from_scanners: AtomicU64,
+    /// 成功投递的 webhook 请求数量
+    pub webhooks_delivered: AtomicU64,
+    /// 重试耗尽后放弃投递的 webhook 事件数量
+    pub webhooks_failed: AtomicU64,
+    /// 被策略脚本拒绝的消息数量
+    pub script_vetoes: AtomicU64,
+    /// 策略脚本执行出错（含超出指令或内存上限）的次数
+    pub script_errors: AtomicU64,
+    /// 内存占用超过软限制时主动淘汰的条目数量
+    pub memory_evictions: AtomicU64,
+    /// 因时间戳过期或超前被拒绝的消息数量
+    pub messages_stale: AtomicU64,
+    /// 无处理器、被丢弃的数据消息数量
+    pub data_unhandled: AtomicU64,
+    /// 因权限或频率限制被拒绝的控制查询数量
+    pub control_denied: AtomicU64,
+    /// 成功迁移到新地址的会话数量
+    pub address_migrations: AtomicU64,
+    /// 因票据无效等原因被拒绝的地址迁移数量
+    pub address_migrations_rejected: AtomicU64,
+    /// 转发给服务提供者的远程调用数量
+    pub rpc_calls: AtomicU64,
+    /// 无可用提供者或转发失败的远程调用数量
+    pub rpc_calls_failed: AtomicU64,
+    /// STUN服务器处理的请求数量（含TURN请求）
+    pub stun_requests: AtomicU64,
+    /// STUN服务器返回错误响应或处理失败的请求数量
+    pub stun_errors: AtomicU64,
+    /// 因频率限制或并发上限被丢弃的STUN请求数量
+    pub stun_dropped: AtomicU64,
+    /// 向STUN服务器发起过请求的不同来源IP数量
+    pub stun_unique_clients: AtomicU64,
+    /// STUN请求处理耗时总和（微秒），除以 `stun_requests` 得到平均耗时
+    pub stun_latency_us_total: AtomicU64,
+    /// 单个STUN请求的最大处理耗时（微秒）
+    pub stun_latency_us_max: AtomicU64,
+    /// 经集群实例之间的中继隧道发出与收到的字节数
+    pub relay_tunnel_bytes: AtomicU64,
+    /// 节点信息超出 `node_info_limits`、被截断后接受的握手数量
+    pub node_info_truncated: AtomicU64,
+    /// 节点信息超出 `node_info_limits` 而被拒绝的握手数量
+    pub node_info_rejected: AtomicU64,
+    /// 重复（重传或 UDP 重复投递）的握手请求数量
+    pub handshake_duplicates: AtomicU64,
+    /// 下发了连接时间的 TCP 打洞协调次数
+    pub tcp_punch_coordinations: AtomicU64,
+    /// 因双方位于运营商级地址转换之后而跳过打洞、直接改用中继的协调次数
+    pub carrier_nat_relay_fallbacks: AtomicU64,
+    /// 批量确认省下的 Ack 数据报数量（每批确认的ID数减一）
+    pub acks_coalesced: AtomicU64,
+    /// 超过目标节点路径 MTU 而拒绝转发的中继数据包数量
+    pub relay_mtu_drops: AtomicU64,
+    /// 因其他节点要联系而唤醒休眠节点的次数
+    pub dormant_wakes: AtomicU64,
+    /// 多播按分发树转发相比逐个单播少发的副本数
+    pub multicast_copies_saved: AtomicU64,
+    /// 因签名缺失或无效而丢弃的路由消息数量
+    pub route_signature_drops: AtomicU64,
+    /// 握手洪泛时发放的无状态握手 cookie 数量
+    pub handshake_cookie_challenges: AtomicU64,
+    /// 带回有效 cookie 的握手请求数量
+    pub handshake_cookie_passes: AtomicU64,
+    /// 启用无状态握手时未分配状态即丢弃的数据包数量：未握手来源的非握手消息，以及小于 cookie 质询的握手请求
+    pub handshake_cookie_drops: AtomicU64,
+    /// 因请求ID已被其他来源使用而丢弃的重放握手数量
+    pub handshake_replays_rejected: AtomicU64,
+    /// 被判定为遭冒用的节点ID次数
+    pub spoof_identities_flagged: AtomicU64,
+    /// 因节点ID疑遭冒用而拒绝的握手数量
+    pub spoof_handshakes_rejected: AtomicU64,
+    /// 因负载超过上限而拒绝的路由消息数量
+    pub routed_payload_rejections: AtomicU64,
+    /// 窗口内内容重复而未发出的广播数量
+    pub broadcasts_suppressed: AtomicU64,
+}
+
+impl Default for ServerMetrics {
+    fn default() -> Self { Self::new() }
+}
+
+impl ServerMetrics {
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            started_at_unix: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            packets_received: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            messages_handled: AtomicU64::new(0),
+            handle_errors: AtomicU64::new(0),
+            routed_messages: AtomicU64::new(0),
+            relay_packets: AtomicU64::new(0),
+            relay_bytes: AtomicU64::new(0),
+            p2p_same_ip_coordinations: AtomicU64::new(0),
+            p2p_private_path: AtomicU64::new(0),
+            p2p_public_path: AtomicU64::new(0),
+            p2p_relay_path: AtomicU64::new(0),
+            p2p_failed: AtomicU64::new(0),
+            packets_dropped: AtomicU64::new(0),
+            packets_expired: AtomicU64::new(0),
+            packet_panics: AtomicU64::new(0),
+            packets_banned: AtomicU64::new(0),
+            packets_oversized: AtomicU64::new(0),
+            packets_malformed: AtomicU64::new(0),
+            scanners_detected: AtomicU64::new(0),
+            packets_from_scanners: AtomicU64::new(0),
+            webhooks_delivered: AtomicU64::new(0),
+            webhooks_failed: AtomicU64::new(0),
+            script_vetoes: AtomicU64::new(0),
+            script_errors: AtomicU64::new(0),
+            memory_evictions: AtomicU64::new(0),
+            messages_stale: AtomicU64::new(0),
+            data_unhandled: AtomicU64::new(0),
+            control_denied: AtomicU64::new(0),
+            address_migrations: AtomicU64::new(0),
+            address_migrations_rejected: AtomicU64::new(0),
+            rpc_calls: AtomicU64::new(0),
+            rpc_calls_failed: AtomicU64::new(0),
+            stun_requests: AtomicU64::new(0),
+            stun_errors: AtomicU64::new(0),
+            stun_dropped: AtomicU64::new(0),
+            stun_unique_clients: AtomicU64::new(0),
+            stun_latency_us_total: AtomicU64::new(0),
+            stun_latency_us_max: AtomicU64::new(0),
+            relay_tunnel_bytes: AtomicU64::new(0),
+            node_info_truncated: AtomicU64::new(0),
+            node_info_rejected: AtomicU64::new(0),
+            handshake_duplicates: AtomicU64::new(0),
+            tcp_punch_coordinations: AtomicU64::new(0),
+            carrier_nat_relay_fallbacks: AtomicU64::new(0),
+            acks_coalesced: AtomicU64::new(0),
+            relay_mtu_drops: AtomicU64::new(0),
+            dormant_wakes: AtomicU64::new(0),
+            multicast_copies_saved: AtomicU64::new(0),
+            route_signature_drops: AtomicU64::new(0),
+            handshake_cookie_challenges: AtomicU64::new(0),
+            handshake_cookie_passes: AtomicU64::new(0),
+            handshake_cookie_drops: AtomicU64::new(0),
+            handshake_replays_rejected: AtomicU64::new(0),
+            spoof_identities_flagged: AtomicU64::new(0),
+            spoof_handshakes_rejected: AtomicU64::new(0),
+            routed_payload_rejections: AtomicU64::new(0),
+            broadcasts_suppressed: AtomicU64::new(0),
+        }
+    }
+
+    /// 服务器已运行的时长
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// 服务器启动时刻的UNIX时间戳（秒）
+    pub fn started_at_unix(&self) -> u64 {
+        self.started_at_unix
+    }
+
+    /// 计数器加一
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 计数器增加指定值
+    pub fn add(counter: &AtomicU64, value: u64) {
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// 记录最大值
+    pub fn max(counter: &AtomicU64, value: u64) {
+        counter.fetch_max(value, Ordering::Relaxed);
+    }
+
+    /// 生成当前指标快照
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            uptime_secs: self.uptime().as_secs(),
+            started_at: self.started_at_unix,
+            packets_received: self.packets_received.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            messages_handled: self.messages_handled.load(Ordering::Relaxed),
+            handle_errors: self.handle_errors.load(Ordering::Relaxed),
+            routed_messages: self.routed_messages.load(Ordering::Relaxed),
+            relay_packets: self.relay_packets.load(Ordering::Relaxed),
+            relay_bytes: self.relay_bytes.load(Ordering::Relaxed),
+            p2p_same_ip_coordinations: self.p2p_same_ip_coordinations.load(Ordering::Relaxed),
+            p2p_private_path: self.p2p_private_path.load(Ordering::Relaxed),
+            p2p_public_path: self.p2p_public_path.load(Ordering::Relaxed),
+            p2p_relay_path: self.p2p_relay_path.load(Ordering::Relaxed),
+            p2p_failed: self.p2p_failed.load(Ordering::Relaxed),
+            packets_dropped: self.packets_dropped.load(Ordering::Relaxed),
+            packets_expired: self.packets_expired.load(Ordering::Relaxed),
+            packet_panics: self.packet_panics.load(Ordering::Relaxed),
+            packets_banned: self.packets_banned.load(Ordering::Relaxed),
+            packets_oversized: self.packets_oversized.load(Ordering::Relaxed),
+            packets_malformed: self.packets_malformed.load(Ordering::Relaxed),
+            scanners_detected: self.scanners_detected.load(Ordering::Relaxed),
+            packets_from_scanners: self.packets_from_scanners.load(Ordering::Relaxed),
+            webhooks_delivered: self.webhooks_delivered.load(Ordering::Relaxed),
+            webhooks_failed: self.webhooks_failed.load(Ordering::Relaxed),
+            script_vetoes: self.script_vetoes.load(Ordering::Relaxed),
+            script_errors: self.script_errors.load(Ordering::Relaxed),
+            memory_evictions: self.memory_evictions.load(Ordering::Relaxed),
+            messages_stale: self.messages_stale.load(Ordering::Relaxed),
+            data_unhandled: self.data_unhandled.load(Ordering::Relaxed),
+            control_denied: self.control_denied.load(Ordering::Relaxed),
+            address_migrations: self.address_migrations.load(Ordering::Relaxed),
+            address_migrations_rejected: self.address_migrations_rejected.load(Ordering::Relaxed),
+            rpc_calls: self.rpc_calls.load(Ordering::Relaxed),
+            rpc_calls_failed: self.rpc_calls_failed.load(Ordering::Relaxed),
+            stun_requests: self.stun_requests.load(Ordering::Relaxed),
+            stun_errors: self.stun_errors.load(Ordering::Relaxed),
+            stun_dropped: self.stun_dropped.load(Ordering::Relaxed),
+            stun_unique_clients: self.stun_unique_clients.load(Ordering::Relaxed),
+            stun_latency_us_total: self.stun_latency_us_total.load(Ordering::Relaxed),
+            stun_latency_us_max: self.stun_latency_us_max.load(Ordering::Relaxed),
+            relay_tunnel_bytes: self.relay_tunnel_bytes.load(Ordering::Relaxed),
+            node_info_truncated: self.node_info_truncated.load(Ordering::Relaxed),
+            node_info_rejected: self.node_info_rejected.load(Ordering::Relaxed),
+            handshake_duplicates: self.handshake_duplicates.load(Ordering::Relaxed),
+            tcp_punch_coordinations: self.tcp_punch_coordinations.load(Ordering::Relaxed),
+            carrier_nat_relay_fallbacks: self.carrier_nat_relay_fallbacks.load(Ordering::Relaxed),
+            acks_coalesced: self.acks_coalesced.load(Ordering::Relaxed),
+            relay_mtu_drops: self.relay_mtu_drops.load(Ordering::Relaxed),
+            dormant_wakes: self.dormant_wakes.load(Ordering::Relaxed),
+            multicast_copies_saved: self.multicast_copies_saved.load(Ordering::Relaxed),
+            route_signature_drops: self.route_signature_drops.load(Ordering::Relaxed),
+            handshake_cookie_challenges: self.handshake_cookie_challenges.load(Ordering::Relaxed),
+            handshake_cookie_passes: self.handshake_cookie_passes.load(Ordering::Relaxed),
+            handshake_cookie_drops: self.handshake_cookie_drops.load(Ordering::Relaxed),
+            handshake_replays_rejected: self.handshake_replays_rejected.load(Ordering::Relaxed),
+            spoof_identities_flagged: self.spoof_identities_flagged.load(Ordering::Relaxed),
+            spoof_handshakes_rejected: self.spoof_handshakes_rejected.load(Ordering::Relaxed),
+            routed_payload_rejections: self.routed_payload_rejections.load(Ordering::Relaxed),
+            broadcasts_suppressed: self.broadcasts_suppressed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 指标快照
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub uptime_secs: u64,
+    /// 服务器启动时刻的UNIX时间戳（秒）
+    #[serde(default)]
+    pub started_at: u64,
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    pub messages_handled: u64,
+    pub handle_errors: u64,
+    pub routed_messages: u64,
+    pub relay_packets: u64,
+    pub relay_bytes: u64,
+    pub p2p_same_ip_coordinations: u64,
+    pub p2p_private_path: u64,
+    pub p2p_public_path: u64,
+    pub p2p_relay_path: u64,
+    pub p2p_failed: u64,
+    pub packets_dropped: u64,
+    pub packets_expired: u64,
+    pub packet_panics: u64,
+    pub packets_banned: u64,
+    #[serde(default)]
+    pub packets_oversized: u64,
+    #[serde(default)]
+    pub packets_malformed: u64,
+    #[serde(default)]
+    pub scanners_detected: u64,
+    #[serde(default)]
+    pub packets_from_scanners: u64,
+    #[serde(default)]
+    pub webhooks_delivered: u64,
+    #[serde(default)]
+    pub webhooks_failed: u64,
+    #[serde(default)]
+    pub script_vetoes: u64,
+    #[serde(default)]
+    pub script_errors: u64,
+    #[serde(default)]
+    pub memory_evictions: u64,
+    pub messages_stale: u64,
+    pub data_unhandled: u64,
+    pub control_denied: u64,
+    pub address_migrations: u64,
+    pub address_migrations_rejected: u64,
+    pub rpc_calls: u64,
+    pub rpc_calls_failed: u64,
+    pub stun_requests: u64,
+    pub stun_errors: u64,
+    pub stun_dropped: u64,
+    pub stun_unique_clients: u64,
+    pub stun_latency_us_total: u64,
+    pub stun_latency_us_max: u64,
+    #[serde(default)]
+    pub relay_tunnel_bytes: u64,
+    #[serde(default)]
+    pub node_info_truncated: u64,
+    #[serde(default)]
+    pub node_info_rejected: u64,
+    #[serde(default)]
+    pub handshake_duplicates: u64,
+    #[serde(default)]
+    pub tcp_punch_coordinations: u64,
+    #[serde(default)]
+    pub carrier_nat_relay_fallbacks: u64,
+    #[serde(default)]
+    pub acks_coalesced: u64,
+    #[serde(default)]
+    pub relay_mtu_drops: u64,
+    #[serde(default)]
+    pub dormant_wakes: u64,
+    #[serde(default)]
+    pub multicast_copies_saved: u64,
+    #[serde(default)]
+    pub route_signature_drops: u64,
+    #[serde(default)]
+    pub handshake_cookie_challenges: u64,
+    #[serde(default)]
+    pub handshake_cookie_passes: u64,
+    #[serde(default)]
+    pub handshake_cookie_drops: u64,
+    #[serde(default)]
+    pub handshake_replays_rejected: u64,
+    #[serde(default)]
+    pub spoof_identities_flagged: u64,
+    #[serde(default)]
+    pub spoof_handshakes_rejected: u64,
+    #[serde(default)]
+    pub routed_payload_rejections: u64,
+    #[serde(default)]
+    pub broadcasts_suppressed: u64,
+}
+
+impl MetricsSnapshot {
+    /// 计算与上一次快照之间的每秒速率
+    pub fn rates_since(&self, previous: &MetricsSnapshot, elapsed_secs: f64) -> MetricsRates {
+        let per_sec = |now: u64, before: u64| {
+            if elapsed_secs <= 0.0 {
+                0.0
+            } else {
+                now.saturating_sub(before) as f64 / elapsed_secs
+            }
+        };
+        MetricsRates {
+            packets_per_sec: per_sec(self.packets_received, previous.packets_received),
+            bytes_per_sec: per_sec(self.bytes_received, previous.bytes_received),
+            messages_per_sec: per_sec(self.messages_handled, previous.messages_handled),
+            routed_per_sec: per_sec(self.routed_messages, previous.routed_messages),
+            relay_packets_per_sec: per_sec(self.relay_packets, previous.relay_packets),
+        }
+    }
+}
+
+/// 每秒速率
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricsRates {
+    pub packets_per_sec: f64,
+    pub bytes_per_sec: f64,
+    pub messages_per_sec: f64,
+    pub routed_per_sec: f64,
+    pub relay_packets_per_sec: f64,
+}
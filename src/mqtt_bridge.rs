@@ -0,0 +1,197 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use log::{debug, info, warn};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS, SubscribeFilter};
+use tokio::select;
+
+use crate::config::MqttConfig;
+use crate::peer::PeerManager;
+use crate::pubsub::{Published, TopicBus};
+use crate::tr;
+
+/// 断线后重连前的等待时间
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+/// 用于识别 broker 回送的最近发出消息数
+const ECHO_WINDOW: usize = 256;
+
+/// MQTT 桥接
+///
+/// 覆盖网络内发布到出站主题的消息被转发到 broker 的 `topic_prefix + topic`；
+/// broker 上入站主题的消息被投递给覆盖网络内的订阅者（`from` 为空）。
+/// 双向主题上 broker 回送的本桥接消息会被丢弃，避免回环。
+pub struct MqttBridge {
+    config: MqttConfig,
+    topic_bus: Arc<TopicBus>,
+    peer_manager: Arc<PeerManager>,
+    /// 最近发往 broker 的 (MQTT主题, 负载)，用于过滤回送
+    recent_outbound: VecDeque<(String, Vec<u8>)>,
+}
+
+impl MqttBridge {
+    pub fn new(config: MqttConfig, topic_bus: Arc<TopicBus>, peer_manager: Arc<PeerManager>) -> Self {
+        Self { config, topic_bus, peer_manager, recent_outbound: VecDeque::new() }
+    }
+
+    /// 启动桥接任务（断线自动重连）
+    pub fn start(mut self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut options = MqttOptions::new(
+                self.config.client_id.clone(),
+                self.config.broker_host.clone(),
+                self.config.broker_port,
+            );
+            options.set_keep_alive(Duration::from_secs(self.config.keep_alive_secs.max(5)));
+            if let Some(username) = &self.config.username {
+                options.set_credentials(username.clone(), self.config.password.clone().unwrap_or_default());
+            }
+            let (client, mut eventloop) = AsyncClient::new(options, 64);
+            let mut published = self.topic_bus.tap();
+            let qos = self.qos();
+            info!("{}", tr!("MQTT桥接已启动: {}:{}", "MQTT bridge started: {}:{}", self.config.broker_host, self.config.broker_port));
+
+            loop {
+                select! {
+                    event = eventloop.poll() => match event {
+                        Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                            info!("{}", tr!("已连接到MQTT broker", "Connected to MQTT broker"));
+                            let filters: Vec<SubscribeFilter> = self.inbound_topics()
+                                .into_iter()
+                                .map(|topic| SubscribeFilter::new(topic, qos))
+                                .collect();
+                            if !filters.is_empty()
+                                && let Err(e) = client.try_subscribe_many(filters)
+                            {
+                                warn!("{}", tr!("订阅MQTT主题失败: {}", "Failed to subscribe to MQTT topic: {}", e));
+                            }
+                        }
+                        Ok(Event::Incoming(Packet::Publish(publish))) => {
+                            self.handle_inbound(&publish.topic, &publish.payload).await;
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            warn!("{}", tr!("MQTT连接异常: {}，{}秒后重连", "MQTT connection error: {}, reconnecting in {} seconds", e, RECONNECT_DELAY.as_secs()));
+                            tokio::time::sleep(RECONNECT_DELAY).await;
+                        }
+                    },
+                    Some(message) = published.recv() => {
+                        if let Some((topic, payload)) = self.outbound(&message) {
+                            match client.try_publish(topic.clone(), qos, false, payload.clone()) {
+                                Ok(()) => self.remember_outbound(topic, payload),
+                                Err(e) => warn!("{}", tr!("转发到MQTT失败（主题 {}）: {}", "Failed to forward to MQTT (topic {}): {}", message.topic, e)),
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    fn qos(&self) -> QoS {
+        match self.config.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            _ => QoS::ExactlyOnce,
+        }
+    }
+
+    /// 需要在 broker 上订阅的 MQTT 主题
+    fn inbound_topics(&self) -> Vec<String> {
+        self.config.topics
+            .iter()
+            .filter(|m| m.direction.inbound())
+            .map(|m| format!("{}{}", self.config.topic_prefix, m.topic))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// 覆盖网络消息 -> (MQTT主题, 负载)，非出站主题返回 `None`
+    fn outbound(&self, message: &Published) -> Option<(String, Vec<u8>)> {
+        self.config.topics
+            .iter()
+            .any(|m| m.direction.outbound() && m.topic == message.topic)
+            .then(|| (format!("{}{}", self.config.topic_prefix, message.topic), to_mqtt_payload(&message.data)))
+    }
+
+    fn remember_outbound(&mut self, topic: String, payload: Vec<u8>) {
+        if self.recent_outbound.len() >= ECHO_WINDOW {
+            self.recent_outbound.pop_front();
+        }
+        self.recent_outbound.push_back((topic, payload));
+    }
+
+    /// MQTT 消息 -> 覆盖网络订阅者
+    async fn handle_inbound(&mut self, mqtt_topic: &str, payload: &[u8]) {
+        if let Some(pos) = self.recent_outbound.iter().position(|(t, p)| t == mqtt_topic && p == payload) {
+            self.recent_outbound.remove(pos);
+            return;
+        }
+        let Some(topic) = mqtt_topic.strip_prefix(self.config.topic_prefix.as_str()) else { return };
+        if !self.config.topics.iter().any(|m| m.direction.inbound() && m.topic == topic) {
+            return;
+        }
+        let published = Published { topic: topic.to_string(), from: None, data: from_mqtt_payload(payload) };
+        let delivered = self.topic_bus.deliver(&self.peer_manager, &published).await;
+        debug!("{}", tr!("MQTT主题 {} 的消息已投递给 {} 个订阅者", "MQTT topic {} message delivered to {} subscribers", mqtt_topic, delivered));
+    }
+}
+
+/// 字符串按原文发送，其他值编码为JSON
+fn to_mqtt_payload(data: &serde_json::Value) -> Vec<u8> {
+    match data {
+        serde_json::Value::String(text) => text.clone().into_bytes(),
+        other => other.to_string().into_bytes(),
+    }
+}
+
+/// 依次尝试按JSON、UTF-8文本解析，否则以字节数组形式携带
+fn from_mqtt_payload(payload: &[u8]) -> serde_json::Value {
+    if let Ok(value) = serde_json::from_slice(payload) {
+        return value;
+    }
+    match std::str::from_utf8(payload) {
+        Ok(text) => serde_json::Value::String(text.to_string()),
+        Err(_) => serde_json::json!(payload),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BridgeDirection, MqttTopicMapping};
+    use crate::protocol::NodeInfo;
+
+    #[tokio::test]
+    async fn test_topic_mapping_and_echo_suppression() {
+        let config = MqttConfig {
+            topics: vec![
+                MqttTopicMapping { topic: "sensors/temp".to_string(), direction: BridgeDirection::Both },
+                MqttTopicMapping { topic: "commands".to_string(), direction: BridgeDirection::In },
+            ],
+            ..MqttConfig::default()
+        };
+        let peer_manager = Arc::new(PeerManager::new(
+            NodeInfo::new("n".to_string(), "127.0.0.1:0".parse().unwrap(), "t".to_string()),
+            10,
+        ));
+        let mut bridge = MqttBridge::new(config, Arc::new(TopicBus::new()), peer_manager);
+
+        let mut inbound = bridge.inbound_topics();
+        inbound.sort();
+        assert_eq!(inbound, vec!["p2p/commands", "p2p/sensors/temp"]);
+
+        let message = Published { topic: "sensors/temp".to_string(), from: None, data: serde_json::json!({ "c": 21 }) };
+        let (topic, payload) = bridge.outbound(&message).unwrap();
+        assert_eq!((topic.as_str(), payload.as_slice()), ("p2p/sensors/temp", br#"{"c":21}"#.as_slice()));
+        assert!(bridge.outbound(&Published { topic: "commands".to_string(), ..message }).is_none());
+
+        bridge.remember_outbound(topic.clone(), payload.clone());
+        bridge.handle_inbound(&topic, &payload).await;
+        assert!(bridge.recent_outbound.is_empty());
+
+        assert_eq!(from_mqtt_payload(b"on"), serde_json::json!("on"));
+        assert_eq!(from_mqtt_payload(&[0xff, 0x01]), serde_json::json!([255, 1]));
+        assert_eq!(to_mqtt_payload(&serde_json::json!("on")), b"on");
+    }
+}
@@ -7,7 +7,13 @@ use anyhow::{Result, Context};
 use log::{info, debug};
 
 
+#[cfg(feature = "chaos")]
+use crate::chaos::FaultInjector;
+use crate::codec::{Codec, CodecSet, JsonCodec};
+use crate::config::NetworkConfig;
 use crate::protocol::Message;
+use crate::sockopt;
+use crate::tr;
 
 /// UDP连接抽象
 #[derive(Debug, Clone)]
@@ -17,14 +23,52 @@ pub struct Connection {
 
     #[allow(dead_code)]
     local_addr: SocketAddr,
+    /// 对端使用的编码（收到数据包时更新，回复使用同一编码）
+    codec: Arc<std::sync::RwLock<Arc<dyn Codec>>>,
+    /// 发送方向的故障注入（由 `NetworkManager` 创建的连接共享）
+    #[cfg(feature = "chaos")]
+    faults: Option<Arc<FaultInjector>>,
 }
 
 impl Connection {
     pub fn new(socket: Arc<UdpSocket>, peer_addr: SocketAddr, local_addr: SocketAddr) -> Self {
-        Self { 
-            socket, 
+        Self::with_codec(socket, peer_addr, local_addr, Arc::new(JsonCodec))
+    }
+
+    pub fn with_codec(
+        socket: Arc<UdpSocket>,
+        peer_addr: SocketAddr,
+        local_addr: SocketAddr,
+        codec: Arc<dyn Codec>,
+    ) -> Self {
+        Self {
+            socket,
             peer_addr,
             local_addr,
+            codec: Arc::new(std::sync::RwLock::new(codec)),
+            #[cfg(feature = "chaos")]
+            faults: None,
+        }
+    }
+
+    /// 发送时经过故障注入
+    #[cfg(feature = "chaos")]
+    pub fn with_faults(mut self, faults: Arc<FaultInjector>) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// 当前使用的编码
+    pub fn codec(&self) -> Arc<dyn Codec> {
+        self.codec.read().map(|c| c.clone()).unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    /// 切换编码（对端改用其他编码时）
+    pub fn set_codec(&self, codec: Arc<dyn Codec>) {
+        let mut current = self.codec.write().unwrap_or_else(|e| e.into_inner());
+        if current.name() != codec.name() {
+            debug!("{}", tr!("对端 {} 的消息编码切换为 {}", "Message codec for peer {} switched to {}", self.peer_addr, codec.name()));
+            *current = codec;
         }
     }
     
@@ -39,14 +83,24 @@ impl Connection {
     
     /// 发送消息
     pub async fn send_message(&self, message: &Message) -> Result<()> {
-        let data = serde_json::to_vec(message)
-            .context("序列化消息失败")?;
+        let data = self.codec().encode(message)?;
+        self.send_raw(&data).await
+    }
+
+    /// 发送已编码的数据包（如中继帧），不经过消息编码
+    pub async fn send_raw(&self, data: &[u8]) -> Result<()> {
+        #[cfg(feature = "chaos")]
+        if let Some(faults) = &self.faults
+            && !faults.outbound(&self.socket, data, self.peer_addr)
+        {
+            return Ok(());
+        }
         
         // UDP直接发送数据，不需要长度前缀
-        let bytes_sent = self.socket.send_to(&data, self.peer_addr).await
+        let bytes_sent = self.socket.send_to(data, self.peer_addr).await
             .context("发送UDP消息失败")?;
         
-        debug!("发送UDP消息到 {}: {} bytes", self.peer_addr, bytes_sent);
+        debug!("{}", tr!("发送UDP消息到 {}: {} bytes", "Sent UDP message to {}: {} bytes", self.peer_addr, bytes_sent));
         Ok(())
     }
     
@@ -64,25 +118,48 @@ pub struct NetworkManager {
     local_addr: SocketAddr,
     // 存储已知的对等节点连接
     connections: Arc<RwLock<HashMap<SocketAddr, Arc<Connection>>>>,
+    /// 消息编解码器
+    codecs: CodecSet,
+    /// 收发数据包的故障注入，默认不启用
+    #[cfg(feature = "chaos")]
+    faults: Arc<FaultInjector>,
 }
 
 impl NetworkManager {
     /// 创建新的网络管理器
     pub async fn new(bind_addr: SocketAddr) -> Result<Self> {
-        let socket = UdpSocket::bind(bind_addr).await
-            .context(format!("绑定UDP地址 {} 失败", bind_addr))?;
+        Self::with_codecs(bind_addr, CodecSet::default()).await
+    }
+
+    /// 使用指定的编解码器集合创建网络管理器
+    pub async fn with_codecs(bind_addr: SocketAddr, codecs: CodecSet) -> Result<Self> {
+        Self::with_options(bind_addr, codecs, &NetworkConfig::default()).await
+    }
+
+    /// 使用指定的编解码器与套接字参数创建网络管理器，主端口使用控制流量的DSCP标记
+    pub async fn with_options(bind_addr: SocketAddr, codecs: CodecSet, options: &NetworkConfig) -> Result<Self> {
+        let socket = sockopt::bind_udp(bind_addr, options, options.control_dscp, Some(tr!("主端口", "main port")))?;
         
         let local_addr = socket.local_addr()
             .context("获取本地地址失败")?;
         
-        info!("UDP网络管理器已绑定到 {}", local_addr);
+        info!("{}", tr!("UDP网络管理器已绑定到 {}", "UDP network manager bound to {}", local_addr));
         
         Ok(Self {
             socket: Arc::new(socket),
             local_addr,
             connections: Arc::new(RwLock::new(HashMap::new())),
+            codecs,
+            #[cfg(feature = "chaos")]
+            faults: Arc::new(FaultInjector::new(Default::default())),
         })
     }
+
+    /// 故障注入器，可在运行时调整参数
+    #[cfg(feature = "chaos")]
+    pub fn faults(&self) -> &Arc<FaultInjector> {
+        &self.faults
+    }
     
     /// 获取本地监听地址
     #[allow(dead_code)]
@@ -91,22 +168,44 @@ impl NetworkManager {
     }
     
     /// 接收UDP数据包和发送者地址
+    #[cfg(not(feature = "chaos"))]
+    pub async fn receive_from(&self) -> Result<(Vec<u8>, SocketAddr)> {
+        self.receive_raw().await
+    }
+
+    /// 接收UDP数据包和发送者地址，经故障注入后交给上层（被延迟的数据包到期后从这里取出）
+    #[cfg(feature = "chaos")]
     pub async fn receive_from(&self) -> Result<(Vec<u8>, SocketAddr)> {
+        loop {
+            let (data, peer_addr) = tokio::select! {
+                Some(packet) = self.faults.recv_delayed() => return Ok(packet),
+                received = self.receive_raw() => received?,
+            };
+            if let Some(packet) = self.faults.inbound(data, peer_addr) {
+                return Ok(packet);
+            }
+        }
+    }
+
+    async fn receive_raw(&self) -> Result<(Vec<u8>, SocketAddr)> {
         let mut buffer = vec![0u8; 65536]; // UDP最大包大小
         let (len, peer_addr) = self.socket.recv_from(&mut buffer).await
             .context("接收UDP数据失败")?;
         
         buffer.truncate(len);
-        debug!("从 {} 接收UDP数据: {} bytes", peer_addr, len);
+        debug!("{}", tr!("从 {} 接收UDP数据: {} bytes", "Received UDP data from {}: {} bytes", peer_addr, len));
         
         Ok((buffer, peer_addr))
     }
     
     /// 解析接收到的数据为消息
     pub fn parse_message(&self, data: &[u8]) -> Result<Message> {
-        let message: Message = serde_json::from_slice(data)
-            .context("反序列化UDP消息失败")?;
-        Ok(message)
+        Ok(self.codecs.decode(data)?.0)
+    }
+
+    /// 解析接收到的数据为消息，并返回识别出的编解码器
+    pub fn decode_message(&self, data: &[u8]) -> Result<(Message, Arc<dyn Codec>)> {
+        self.codecs.decode(data)
     }
     
     /// 获取或创建到指定地址的连接
@@ -116,13 +215,17 @@ impl NetworkManager {
         if let Some(connection) = connections.get(&peer_addr) {
             connection.clone()
         } else {
-            let connection = Arc::new(Connection::new(
+            let connection = Connection::with_codec(
                 self.socket.clone(),
                 peer_addr,
                 self.local_addr,
-            ));
+                self.codecs.default_codec(),
+            );
+            #[cfg(feature = "chaos")]
+            let connection = connection.with_faults(self.faults.clone());
+            let connection = Arc::new(connection);
             connections.insert(peer_addr, connection.clone());
-            info!("创建到 {} 的新UDP连接", peer_addr);
+            info!("{}", tr!("创建到 {} 的新UDP连接", "Creating new UDP connection to {}", peer_addr));
             connection
         }
     }
@@ -132,7 +235,7 @@ impl NetworkManager {
     pub async fn remove_connection(&self, peer_addr: &SocketAddr) {
         let mut connections = self.connections.write().await;
         if connections.remove(peer_addr).is_some() {
-            info!("移除到 {} 的UDP连接", peer_addr);
+            info!("{}", tr!("移除到 {} 的UDP连接", "Removing UDP connection to {}", peer_addr));
         }
     }
     
@@ -147,19 +250,27 @@ impl NetworkManager {
     #[allow(dead_code)]
     pub async fn connect_to_peer(&self, addr: SocketAddr) -> Result<Arc<Connection>> {
         let connection = self.get_or_create_connection(addr).await;
-        info!("准备连接到UDP对等节点 {}", addr);
+        info!("{}", tr!("准备连接到UDP对等节点 {}", "Preparing to connect to UDP peer {}", addr));
         Ok(connection)
     }
     
     /// 发送消息到指定地址
     pub async fn send_to(&self, message: &Message, addr: SocketAddr) -> Result<()> {
-        let data = serde_json::to_vec(message)
-            .context("序列化消息失败")?;
+        // 已知对端时沿用其编码
+        let codec = match self.connections.read().await.get(&addr) {
+            Some(connection) => connection.codec(),
+            None => self.codecs.default_codec(),
+        };
+        let data = codec.encode(message)?;
+        #[cfg(feature = "chaos")]
+        if !self.faults.outbound(&self.socket, &data, addr) {
+            return Ok(());
+        }
         
         let bytes_sent = self.socket.send_to(&data, addr).await
             .context("发送UDP消息失败")?;
         
-        debug!("直接发送UDP消息到 {}: {} bytes", addr, bytes_sent);
+        debug!("{}", tr!("直接发送UDP消息到 {}: {} bytes", "Sent UDP message directly to {}: {} bytes", addr, bytes_sent));
         Ok(())
     }
 }
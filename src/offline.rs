@@ -0,0 +1,194 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use log::{debug, warn};
+use uuid::Uuid;
+
+use crate::config::OfflineStoreConfig;
+use crate::router::RoutedMessage;
+use crate::tr;
+
+/// 一个目标节点队列本身的近似占用
+const QUEUE_ENTRY_BYTES: usize = size_of::<(Uuid, VecDeque<StoredMessage>)>() + 1;
+
+#[derive(Debug)]
+struct StoredMessage {
+    routed: RoutedMessage,
+    expires_at: Instant,
+    /// 暂存时的序列化大小，用于内存统计
+    bytes: usize,
+}
+
+impl StoredMessage {
+    fn approx_bytes(&self) -> usize {
+        size_of::<Self>() + self.bytes
+    }
+}
+
+/// 离线消息暂存
+///
+/// 路由消息的目标节点不在线时，服务器按目标节点暂存消息（每个目标最多 `max_per_peer` 条，
+/// 超出时丢弃最早的一条），在 `ttl_secs` 内该节点重新握手即按原顺序投递，过期的消息直接丢弃。
+#[derive(Debug)]
+pub struct OfflineStore {
+    max_per_peer: usize,
+    max_destinations: usize,
+    ttl: Duration,
+    queues: Mutex<HashMap<Uuid, VecDeque<StoredMessage>>>,
+}
+
+impl OfflineStore {
+    pub fn new(config: &OfflineStoreConfig) -> Self {
+        Self {
+            max_per_peer: config.max_per_peer.max(1),
+            max_destinations: config.max_destinations,
+            ttl: Duration::from_secs(config.ttl_secs),
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 暂存发往离线节点的消息，目标节点数已达上限时拒绝并返回 `false`
+    pub fn push(&self, routed: RoutedMessage) -> bool {
+        let now = Instant::now();
+        let destination = routed.destination_node;
+        let mut queues = self.queues.lock().unwrap();
+        Self::purge(&mut queues, now);
+        if !queues.contains_key(&destination) && queues.len() >= self.max_destinations {
+            warn!("{}", tr!("离线消息暂存的目标节点数已达上限 {}，丢弃发往 {} 的消息", "Offline store reached its limit of {} target nodes, dropping message for {}", self.max_destinations, destination));
+            return false;
+        }
+        let queue = queues.entry(destination).or_default();
+        if queue.len() >= self.max_per_peer
+            && let Some(dropped) = queue.pop_front()
+        {
+            warn!("{}", tr!("节点 {} 的离线消息已满，丢弃最早的消息 {}", "Offline queue of node {} is full, dropping oldest message {}", destination, dropped.routed.route_id));
+        }
+        debug!("{}", tr!("暂存发往离线节点 {} 的消息 {}", "Stored message for offline node {}: {}", destination, routed.route_id));
+        let bytes = serde_json::to_vec(&routed).map_or(0, |encoded| encoded.len());
+        queue.push_back(StoredMessage { routed, expires_at: now + self.ttl, bytes });
+        true
+    }
+
+    /// 节点当前暂存的未过期消息数
+    pub fn pending(&self, peer_id: &Uuid) -> usize {
+        let now = Instant::now();
+        self.queues
+            .lock()
+            .unwrap()
+            .get(peer_id)
+            .map_or(0, |queue| queue.iter().filter(|m| m.expires_at > now).count())
+    }
+
+    /// 取出节点暂存的全部未过期消息（按暂存顺序）
+    pub fn take(&self, peer_id: &Uuid) -> Vec<RoutedMessage> {
+        let now = Instant::now();
+        self.queues
+            .lock()
+            .unwrap()
+            .remove(peer_id)
+            .map(|queue| queue.into_iter().filter(|m| m.expires_at > now).map(|m| m.routed).collect())
+            .unwrap_or_default()
+    }
+
+    /// 所有节点暂存的消息总数（含尚未清理的过期消息）
+    pub fn len(&self) -> usize {
+        self.queues.lock().unwrap().values().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 近似内存占用（字节）
+    pub fn approx_bytes(&self) -> usize {
+        let queues = self.queues.lock().unwrap();
+        queues.values().flatten().map(StoredMessage::approx_bytes).sum::<usize>() + queues.len() * QUEUE_ENTRY_BYTES
+    }
+
+    /// 先清理过期消息，再按暂存顺序淘汰最早的消息，直到占用不超过 `limit` 字节，返回淘汰数
+    pub fn evict_oldest(&self, limit: usize) -> usize {
+        let mut queues = self.queues.lock().unwrap();
+        let before: usize = queues.values().map(VecDeque::len).sum();
+        Self::purge(&mut queues, Instant::now());
+        let mut usage = queues.values().flatten().map(StoredMessage::approx_bytes).sum::<usize>() + queues.len() * QUEUE_ENTRY_BYTES;
+        // 暂存时长相同，最早过期的就是最早暂存的
+        while usage > limit {
+            let Some(oldest) = queues.iter().filter_map(|(id, queue)| Some((*id, queue.front()?.expires_at))).min_by_key(|(_, at)| *at).map(|(id, _)| id) else {
+                break;
+            };
+            let queue = queues.get_mut(&oldest).expect("队列存在");
+            if let Some(dropped) = queue.pop_front() {
+                usage -= dropped.approx_bytes();
+                debug!("{}", tr!("内存超出软限制，淘汰发往 {} 的离线消息 {}", "Memory above soft limit, evicting offline message for {}: {}", oldest, dropped.routed.route_id));
+            }
+            if queue.is_empty() {
+                queues.remove(&oldest);
+                usage -= QUEUE_ENTRY_BYTES;
+            }
+        }
+        before - queues.values().map(VecDeque::len).sum::<usize>()
+    }
+
+    fn purge(queues: &mut HashMap<Uuid, VecDeque<StoredMessage>>, now: Instant) {
+        queues.retain(|_, queue| {
+            queue.retain(|m| m.expires_at > now);
+            !queue.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Message;
+
+    fn routed(destination: Uuid, n: u32) -> RoutedMessage {
+        RoutedMessage::new(Message::data(serde_json::json!({ "n": n })), Uuid::new_v4(), destination, 5)
+    }
+
+    #[test]
+    fn test_bounded_per_destination_and_ttl() {
+        let store = OfflineStore::new(&OfflineStoreConfig {
+            enable: true,
+            max_per_peer: 2,
+            max_destinations: 1,
+            ttl_secs: 60,
+        });
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        assert!(store.push(routed(a, 1)));
+        assert!(store.push(routed(a, 2)));
+        assert!(store.push(routed(a, 3)));
+        // 目标节点数达到上限
+        assert!(!store.push(routed(b, 1)));
+        assert_eq!(store.pending(&a), 2);
+
+        // 超出上限时丢弃最早的消息，取出后清空
+        let taken: Vec<_> = store.take(&a).into_iter().map(|m| m.original_message.payload["n"].clone()).collect();
+        assert_eq!(taken, vec![serde_json::json!(2), serde_json::json!(3)]);
+        assert!(store.is_empty());
+
+        let expired = OfflineStore::new(&OfflineStoreConfig { enable: true, ttl_secs: 0, ..OfflineStoreConfig::default() });
+        assert!(expired.push(routed(a, 1)));
+        assert_eq!(expired.pending(&a), 0);
+        assert!(expired.take(&a).is_empty());
+    }
+
+    #[test]
+    fn test_evict_oldest_across_destinations() {
+        let store = OfflineStore::new(&OfflineStoreConfig { enable: true, ..OfflineStoreConfig::default() });
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        assert!(store.push(routed(a, 1)));
+        assert!(store.push(routed(b, 2)));
+        assert!(store.push(routed(a, 3)));
+        let full = store.approx_bytes();
+        assert!(full > 0);
+
+        // 保留约一半，最早暂存的两条被淘汰
+        assert_eq!(store.evict_oldest(full / 2), 2);
+        assert!(store.approx_bytes() <= full / 2);
+        assert_eq!(store.pending(&b), 0);
+        let remaining: Vec<_> = store.take(&a).into_iter().map(|m| m.original_message.payload["n"].clone()).collect();
+        assert_eq!(remaining, vec![serde_json::json!(3)]);
+        assert_eq!(store.evict_oldest(0), 0);
+    }
+}
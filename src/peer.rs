@@ -1,13 +1,36 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 use std::net::SocketAddr;
 use log::{info, warn, debug};
 use anyhow::Result;
+use futures::future::BoxFuture;
+use serde_json::json;
 
+use crate::carrier_nat::{self, CarrierNatStats};
+use crate::config::{CarrierNatConfig, Config, KeepaliveConfig, LimitAction, NodeInfoLimitsConfig, PeerRole, PinnedPeer, SpoofGuardConfig};
+use crate::cluster::HandoffSession;
+use crate::contacts::RecentContacts;
+use crate::dormant::Dormancy;
+use crate::correlation::{HandshakeState, PendingHandshakes};
+use crate::events::{EventBus, EventKind};
+use crate::offline::OfflineStore;
+use crate::bandwidth::BandwidthMap;
+use crate::latency::LatencyMatrix;
+use crate::link_quality::PingHistory;
+use crate::peer_list::PeerListSnapshot;
+use crate::presence::WatchList;
+use crate::identity::{self, IdentityRegistry, ServerIdentity};
+use crate::reachability::PeerTraits;
+use crate::sessions::P2PSessions;
+use crate::spoof_guard::{SpoofGuard, SpoofVerdict};
+use crate::keepalive::KeepaliveProber;
+use crate::metrics::ServerMetrics;
 use crate::network::Connection;
-use crate::protocol::{NodeInfo, PeerInfo, Message, HandshakeProtocol};
+use crate::protocol::{CarrierNat, DisconnectReason, LinkQuality, NodeInfo, PeerInfo, Message, MessageType, HandshakeProtocol, LoadHint, P2PPath, PathMtu, RttSample};
+use crate::tr;
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -20,6 +43,23 @@ pub enum PeerStatus {
     Error(String),
 }
 
+/// 处理一条消息时节点的不可变快照
+///
+/// 每个数据包只加锁读取一次，处理函数直接使用快照中的 ID、地址与状态。握手等会改变
+/// 节点ID或状态的处理之后需重新读取节点。
+#[derive(Debug, Clone)]
+pub struct PeerSnapshot {
+    pub id: Uuid,
+    pub addr: SocketAddr,
+    pub status: PeerStatus,
+}
+
+impl PeerSnapshot {
+    pub fn is_authenticated(&self) -> bool {
+        matches!(self.status, PeerStatus::Authenticated)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Peer {
     pub id: Uuid,
@@ -29,6 +69,28 @@ pub struct Peer {
     pub last_ping: Option<std::time::Instant>,
     #[allow(dead_code)]
     pub created_at: std::time::Instant,
+    /// 节点上报的NAT类型（握手元数据或P2P协调请求中携带）
+    pub nat_type: Option<String>,
+    /// 服务器识别出的运营商级地址转换（CGNAT/NAT64），握手与协调直连时更新
+    pub carrier_nat: Option<CarrierNat>,
+    /// 服务器发给该节点的最近心跳
+    pub pings: PingHistory,
+    /// 时间同步得到的时钟偏差（节点时间 − 服务器时间，毫秒），未同步时为 `None`
+    pub clock_offset_ms: Option<i64>,
+    /// 服务器到该节点的路径 MTU，探测结束前为 `None`
+    pub path_mtu: Option<PathMtu>,
+    /// 节点登记的休眠状态，休眠期间发给它的消息先暂存
+    pub dormant: Option<Arc<Dormancy>>,
+    /// 握手时由访问令牌确定的诊断角色，`None` 表示使用 `control.default_role`
+    pub role: Option<PeerRole>,
+    /// 握手成功时下发的会话票据，节点凭此从新地址迁移会话
+    pub session_ticket: Option<String>,
+    /// 握手元数据中出示的访问令牌，不随节点信息保存或下发；服务器据此确定角色后清除
+    pub access_token: Option<String>,
+    /// 当前频率限制窗口的起始时间与已发起的控制查询数
+    control_window: (std::time::Instant, u32),
+    /// 所属节点管理器的计数器，节点加入管理器后才设置
+    counters: Option<Arc<PeerCounters>>,
 }
 
 impl Peer {
@@ -40,10 +102,20 @@ impl Peer {
             status: PeerStatus::Connecting,
             last_ping: None,
             created_at: std::time::Instant::now(),
+            nat_type: None,
+            carrier_nat: None,
+            pings: PingHistory::default(),
+            clock_offset_ms: None,
+            path_mtu: None,
+            dormant: None,
+            role: None,
+            session_ticket: None,
+            access_token: None,
+            control_window: (std::time::Instant::now(), 0),
+            counters: None,
         }
     }
     
-    #[allow(dead_code)]
     pub fn with_node_info(connection: Arc<Connection>, node_info: NodeInfo) -> Self {
         Self {
             id: node_info.id,
@@ -52,13 +124,41 @@ impl Peer {
             status: PeerStatus::Authenticated,
             last_ping: None,
             created_at: std::time::Instant::now(),
+            nat_type: None,
+            carrier_nat: None,
+            pings: PingHistory::default(),
+            clock_offset_ms: None,
+            path_mtu: None,
+            dormant: None,
+            role: None,
+            session_ticket: None,
+            access_token: None,
+            control_window: (std::time::Instant::now(), 0),
+            counters: None,
         }
     }
     
     pub fn update_status(&mut self, status: PeerStatus) {
-        debug!("节点 {} 状态更新: {:?} -> {:?}", self.id, self.status, status);
+        debug!("{}", tr!("节点 {} 状态更新: {:?} -> {:?}", "Node {} status changed: {:?} -> {:?}", self.id, self.status, status));
+        if let Some(counters) = &self.counters {
+            counters.leave(&self.status);
+            counters.enter(&status);
+        }
         self.status = status;
     }
+
+    /// 计入节点管理器的计数器
+    fn attach_counters(&mut self, counters: Arc<PeerCounters>) {
+        counters.enter(&self.status);
+        self.counters = Some(counters);
+    }
+
+    /// 从节点管理器的计数器中移除
+    fn detach_counters(&mut self) {
+        if let Some(counters) = self.counters.take() {
+            counters.leave(&self.status);
+        }
+    }
     
     pub fn update_ping(&mut self) {
         self.last_ping = Some(std::time::Instant::now());
@@ -71,15 +171,98 @@ impl Peer {
     pub fn is_connected(&self) -> bool {
         matches!(self.status, PeerStatus::Connected | PeerStatus::Authenticated)
     }
+
+    /// 节点记录的近似内存占用（字节），含节点信息与会话票据
+    pub fn approx_bytes(&self) -> usize {
+        let node_info = self.node_info.as_ref().map_or(0, |info| {
+            size_of::<NodeInfo>()
+                + info.name.len()
+                + info.metadata.iter().map(|(k, v)| k.len() + v.len() + size_of::<(String, String)>()).sum::<usize>()
+                + info.addresses.len() * size_of::<SocketAddr>()
+        });
+        size_of::<Self>() + size_of::<Connection>() + node_info + self.session_ticket.as_ref().map_or(0, String::len)
+    }
     
     pub fn addr(&self) -> SocketAddr {
         self.connection.peer_addr()
     }
+
+    /// 当前 ID、地址与状态的快照
+    pub fn snapshot(&self) -> PeerSnapshot {
+        PeerSnapshot { id: self.id, addr: self.addr(), status: self.status.clone() }
+    }
+
+    /// 节点已连接的时长
+    pub fn connected_for(&self) -> std::time::Duration {
+        self.created_at.elapsed()
+    }
+
+    /// 用于挑选下发节点的可达性特征（区域、NAT类型、已连接时长）
+    pub fn traits(&self) -> PeerTraits {
+        PeerTraits::new(self.node_info.as_ref(), self.nat_type.clone(), self.connected_for())
+    }
+
+    /// 节点握手时上报的内网监听地址（未指定地址或端口时视为未知）
+    pub fn private_addr(&self) -> Option<SocketAddr> {
+        self.node_info
+            .as_ref()
+            .map(|info| info.listen_addr)
+            .filter(|addr| !addr.ip().is_unspecified() && addr.port() != 0)
+    }
+
+    /// 节点握手时通告的全部本地地址（多宿主主机可能有多个）
+    pub fn advertised_addrs(&self) -> Vec<SocketAddr> {
+        self.node_info.as_ref().map(NodeInfo::advertised_addrs).unwrap_or_default()
+    }
     
-    /// 发送消息给对等节点
+    /// 占用一次控制查询配额（每分钟 `per_minute` 次，0 表示不限制），超出时返回 `false`
+    pub fn take_control_quota(&mut self, per_minute: u32) -> bool {
+        if per_minute == 0 {
+            return true;
+        }
+        let (started, count) = &mut self.control_window;
+        if started.elapsed() >= std::time::Duration::from_secs(60) {
+            *started = std::time::Instant::now();
+            *count = 0;
+        }
+        if *count >= per_minute {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    /// 服务器按心跳估计的到该节点的链路质量，尚无应答过的心跳时为 `None`
+    pub fn link_quality(&self) -> Option<LinkQuality> {
+        self.pings.estimator().quality()
+    }
+
+    /// 当前一分钟窗口内已发起的控制查询数（窗口已过期时为 0）
+    pub fn control_quota_used(&self) -> u32 {
+        let (started, count) = self.control_window;
+        if started.elapsed() >= std::time::Duration::from_secs(60) { 0 } else { count }
+    }
+
+    /// 发送消息；节点休眠期间先暂存，节点醒来后补发
     pub async fn send_message(&self, message: &Message) -> Result<()> {
+        if let Some(dormancy) = self.dormant.as_ref().filter(|dormancy| dormancy.is_active()) {
+            if !dormancy.queue(message.clone()) {
+                debug!("{}", tr!("节点 {} 休眠期间暂存的消息已满，丢弃最早的一条", "Queue for dormant node {} is full, dropping the oldest message", self.id));
+            }
+            return Ok(());
+        }
         self.connection.send_message(message).await
     }
+
+    /// 是否在休眠期限内：不发心跳，也不按心跳超时移除
+    pub fn is_dormant(&self) -> bool {
+        self.dormant.as_ref().is_some_and(|dormancy| dormancy.is_active())
+    }
+
+    /// 发送已编码的数据包（如中继帧）
+    pub async fn send_raw(&self, data: &[u8]) -> Result<()> {
+        self.connection.send_raw(data).await
+    }
     
     /// 接收来自对等节点的消息
     pub async fn receive_message(&self) -> Result<Option<Message>> {
@@ -87,32 +270,492 @@ impl Peer {
     }
 }
 
+/// 按状态统计的节点数
+///
+/// 节点加入、移除与状态变化时（均在节点写锁内）原子地更新，统计无需遍历并锁定每个节点，
+/// 可以高频采样用于指标。
+#[derive(Debug, Default)]
+pub struct PeerCounters {
+    total: AtomicUsize,
+    authenticated: AtomicUsize,
+    connecting: AtomicUsize,
+}
+
+impl PeerCounters {
+    fn counter(&self, status: &PeerStatus) -> Option<&AtomicUsize> {
+        match status {
+            PeerStatus::Authenticated => Some(&self.authenticated),
+            PeerStatus::Connecting | PeerStatus::Handshaking => Some(&self.connecting),
+            _ => None,
+        }
+    }
+
+    fn enter(&self, status: &PeerStatus) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if let Some(counter) = self.counter(status) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn leave(&self, status: &PeerStatus) {
+        self.total.fetch_sub(1, Ordering::Relaxed);
+        if let Some(counter) = self.counter(status) {
+            counter.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> PeerStats {
+        PeerStats {
+            total_peers: self.total.load(Ordering::Relaxed),
+            authenticated_peers: self.authenticated.load(Ordering::Relaxed),
+            connecting_peers: self.connecting.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 连接数软/硬限制
+///
+/// 限制可在运行时调整：调低硬限制不会断开已有节点，只会拒绝新的握手，
+/// 直到节点数回落到新限制以下。
+#[derive(Debug)]
+pub struct ConnectionLimits {
+    /// 软限制，0 表示未启用
+    soft: AtomicUsize,
+    hard: AtomicUsize,
+    heartbeat_interval_secs: u64,
+    busy_heartbeat_multiplier: u32,
+    retry_after_secs: u64,
+}
+
+impl ConnectionLimits {
+    /// 只有硬限制的默认策略
+    pub fn new(hard: usize) -> Self {
+        Self {
+            soft: AtomicUsize::new(0),
+            hard: AtomicUsize::new(hard),
+            heartbeat_interval_secs: 30,
+            busy_heartbeat_multiplier: 2,
+            retry_after_secs: 30,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        let limits = Self {
+            heartbeat_interval_secs: config.heartbeat_interval,
+            busy_heartbeat_multiplier: config.connection_limits.busy_heartbeat_multiplier.max(1),
+            retry_after_secs: config.connection_limits.retry_after_secs,
+            ..Self::new(config.max_connections)
+        };
+        if let Err(e) = limits.set(config.connection_limits.soft_limit, config.max_connections) {
+            warn!("{}", tr!("连接数限制配置无效，忽略软限制: {}", "Invalid connection limit configuration, ignoring soft limit: {}", e));
+        }
+        limits
+    }
+
+    pub fn soft(&self) -> Option<usize> {
+        Some(self.soft.load(Ordering::Relaxed)).filter(|&soft| soft > 0)
+    }
+
+    pub fn hard(&self) -> usize {
+        self.hard.load(Ordering::Relaxed)
+    }
+
+    /// 调整限制，软限制须小于硬限制
+    pub fn set(&self, soft: Option<usize>, hard: usize) -> Result<()> {
+        if hard == 0 {
+            return Err(anyhow::anyhow!("硬限制必须大于0"));
+        }
+        if let Some(soft) = soft
+            && (soft == 0 || soft >= hard)
+        {
+            return Err(anyhow::anyhow!("软限制必须在 1 到 {} 之间", hard - 1));
+        }
+        self.soft.store(soft.unwrap_or(0), Ordering::Relaxed);
+        self.hard.store(hard, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after_secs
+    }
+
+    /// 繁忙时心跳间隔与超时判定的放大倍数
+    pub fn heartbeat_multiplier(&self, busy: bool) -> u32 {
+        if busy { self.busy_heartbeat_multiplier } else { 1 }
+    }
+
+    /// 当前应使用的心跳间隔（秒）
+    pub fn heartbeat_interval_secs(&self, busy: bool) -> u64 {
+        self.heartbeat_interval_secs * self.heartbeat_multiplier(busy) as u64
+    }
+}
+
+/// 按限制截断节点信息，返回超出的各项说明；未超出时为空
+///
+/// 能力名称与元数据键超长时整项丢弃，名称与元数据值超长时截断到字符边界；
+/// 元数据条目超出数量时按键排序保留前面的条目。
+fn limit_node_info(info: &mut NodeInfo, limits: &NodeInfoLimitsConfig) -> Vec<String> {
+    let exceeds = |len: usize, max: usize| max > 0 && len > max;
+    let mut violations = Vec::new();
+    if exceeds(info.name.len(), limits.max_name_len) {
+        violations.push(format!("name 长度 {} 超过 {}", info.name.len(), limits.max_name_len));
+        info.name = Arc::from(truncate_utf8(&info.name, limits.max_name_len));
+    }
+
+    let before = info.capabilities.len();
+    info.capabilities.retain(|capability| !exceeds(capability.len(), limits.max_capability_len));
+    if info.capabilities.len() < before {
+        violations.push(format!("{} 个能力名称超过 {} 字节", before - info.capabilities.len(), limits.max_capability_len));
+    }
+    if exceeds(info.capabilities.len(), limits.max_capabilities) {
+        violations.push(format!("capabilities 数量 {} 超过 {}", info.capabilities.len(), limits.max_capabilities));
+        info.capabilities.truncate(limits.max_capabilities);
+    }
+
+    let before = info.metadata.len();
+    info.metadata.retain(|key, _| !exceeds(key.len(), limits.max_metadata_key_len));
+    if info.metadata.len() < before {
+        violations.push(format!("{} 个元数据键超过 {} 字节", before - info.metadata.len(), limits.max_metadata_key_len));
+    }
+    let mut long_values = 0;
+    for value in info.metadata.values_mut() {
+        if exceeds(value.len(), limits.max_metadata_value_len) {
+            long_values += 1;
+            let keep = truncate_utf8(value, limits.max_metadata_value_len).len();
+            value.truncate(keep);
+        }
+    }
+    if long_values > 0 {
+        violations.push(format!("{} 个元数据值超过 {} 字节", long_values, limits.max_metadata_value_len));
+    }
+    if exceeds(info.metadata.len(), limits.max_metadata_entries) {
+        violations.push(format!("metadata 条目数 {} 超过 {}", info.metadata.len(), limits.max_metadata_entries));
+        let mut keys: Vec<String> = info.metadata.keys().cloned().collect();
+        keys.sort();
+        for key in keys.into_iter().skip(limits.max_metadata_entries) {
+            info.metadata.remove(&key);
+        }
+    }
+
+    if exceeds(info.addresses.len(), limits.max_addresses) {
+        violations.push(format!("addresses 数量 {} 超过 {}", info.addresses.len(), limits.max_addresses));
+        info.addresses.truncate(limits.max_addresses);
+    }
+    violations
+}
+
+/// 截取不超过 `max` 字节的前缀，不切断多字节字符
+fn truncate_utf8(value: &str, max: usize) -> &str {
+    let mut end = max.min(value.len());
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+/// 一个节点在两个索引中的近似占用：键、`Arc` 与哈希表的控制字节
+const PEER_INDEX_BYTES: usize = size_of::<(Uuid, Arc<RwLock<Peer>>)>() + size_of::<(SocketAddr, Arc<RwLock<Peer>>)>() + size_of::<RwLock<Peer>>() - size_of::<Peer>() + 2;
+
+/// 节点被移除后释放其在服务器其他组件中的状态（路由、中继会话、主题订阅与服务注册）
+///
+/// 服务器运行时通过 [`PeerManager::set_release_hook`] 注册；未注册时 [`PeerManager::kick`] 只移除节点记录。
+pub trait PeerRelease: Send + Sync {
+    fn release<'a>(&'a self, peer_id: &'a Uuid) -> BoxFuture<'a, ()>;
+}
+
 pub struct PeerManager {
     peers: Arc<RwLock<HashMap<Uuid, Arc<RwLock<Peer>>>>>,
     // UDP需要基于地址的索引
     peers_by_addr: Arc<RwLock<HashMap<SocketAddr, Arc<RwLock<Peer>>>>>,
     local_node_info: NodeInfo,
-    max_connections: usize,
+    limits: ConnectionLimits,
+    /// 近期通信关系，用于定向发送下线通知
+    contacts: RecentContacts,
+    /// 已建立的 P2P 会话，用于推送地址变更
+    sessions: P2PSessions,
+    /// 在线状态关注列表
+    presence: WatchList,
+    /// 离线消息暂存（未启用时为 `None`）
+    offline_store: Option<Arc<OfflineStore>>,
+    /// 节点间 P2P 路径的带宽估计
+    bandwidth: Arc<BandwidthMap>,
+    /// 节点上报的到直连节点的往返时延
+    latency: Arc<LatencyMatrix>,
+    /// NAT 保活间隔探测
+    keepalive: Arc<KeepaliveProber>,
+    /// 运维固定的节点
+    pinned: Vec<PinnedPeer>,
+    /// 按状态统计的节点数
+    counters: Arc<PeerCounters>,
+    /// 节点列表负载的字节上限，0 表示不限制
+    discovery_max_bytes: usize,
+    /// 对握手响应签名的服务器身份密钥
+    identity: Option<Arc<ServerIdentity>>,
+    /// 节点公钥登记表
+    identities: Arc<IdentityRegistry>,
+    /// 结构化事件总线
+    events: Arc<EventBus>,
+    /// 握手中节点信息的大小限制
+    node_info_limits: NodeInfoLimitsConfig,
+    /// 服务器指标（节点信息超限计数）
+    metrics: Arc<ServerMetrics>,
+    /// 进行中与刚应答的握手请求，用于识别重复的握手
+    pending_handshakes: PendingHandshakes,
+    /// 节点被强制断开后释放其状态的钩子（弱引用，钩子由服务器持有）
+    release_hook: std::sync::RwLock<Option<Weak<dyn PeerRelease>>>,
+    /// 运营商级地址转换的识别参数
+    carrier_nat: CarrierNatConfig,
+    /// 冒用节点身份的握手识别
+    spoof_guard: Arc<SpoofGuard>,
 }
 
 impl PeerManager {
     pub fn new(local_node_info: NodeInfo, max_connections: usize) -> Self {
+        Self::with_limits(local_node_info, ConnectionLimits::new(max_connections))
+    }
+
+    pub fn with_limits(local_node_info: NodeInfo, limits: ConnectionLimits) -> Self {
         Self {
             peers: Arc::new(RwLock::new(HashMap::new())),
             peers_by_addr: Arc::new(RwLock::new(HashMap::new())),
             local_node_info,
-            max_connections,
+            limits,
+            contacts: RecentContacts::default(),
+            sessions: P2PSessions::new(),
+            presence: WatchList::new(),
+            offline_store: None,
+            bandwidth: Arc::new(BandwidthMap::default()),
+            latency: Arc::new(LatencyMatrix::new()),
+            keepalive: Arc::new(KeepaliveProber::new(KeepaliveConfig::default())),
+            pinned: Vec::new(),
+            counters: Arc::new(PeerCounters::default()),
+            discovery_max_bytes: 0,
+            identity: None,
+            identities: Arc::new(IdentityRegistry::new()),
+            events: Arc::new(EventBus::default()),
+            node_info_limits: NodeInfoLimitsConfig::default(),
+            metrics: Arc::new(ServerMetrics::new()),
+            pending_handshakes: PendingHandshakes::default(),
+            release_hook: std::sync::RwLock::new(None),
+            carrier_nat: CarrierNatConfig::default(),
+            spoof_guard: Arc::new(SpoofGuard::new(SpoofGuardConfig::default())),
+        }
+    }
+
+    /// 设置握手中节点信息的大小限制
+    pub fn with_node_info_limits(mut self, limits: NodeInfoLimitsConfig) -> Self {
+        self.node_info_limits = limits;
+        self
+    }
+
+    /// 设置运营商级地址转换的识别参数
+    pub fn with_carrier_nat(mut self, config: CarrierNatConfig) -> Self {
+        self.carrier_nat = config;
+        self
+    }
+
+    /// 设置冒用节点身份的握手识别参数
+    pub fn with_spoof_guard(mut self, config: SpoofGuardConfig) -> Self {
+        self.spoof_guard = Arc::new(SpoofGuard::new(config));
+        self
+    }
+
+    pub fn spoof_guard(&self) -> &Arc<SpoofGuard> {
+        &self.spoof_guard
+    }
+
+    /// 使用指定的指标（例如与服务器共享）
+    pub fn with_metrics(mut self, metrics: Arc<ServerMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// 限制下发的节点列表大小，超出时按对接收方的可达性挑选节点
+    pub fn with_discovery_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.discovery_max_bytes = max_bytes;
+        self
+    }
+
+    /// 用服务器身份密钥对握手响应签名
+    pub fn with_identity(mut self, identity: Arc<ServerIdentity>) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// 本服务器所属的网络ID
+    pub fn network_id(&self) -> &str {
+        &self.local_node_info.network_id
+    }
+
+    /// 服务器身份密钥（未配置时为 `None`）
+    pub fn identity(&self) -> Option<&Arc<ServerIdentity>> {
+        self.identity.as_ref()
+    }
+
+    /// 节点公钥登记表
+    pub fn identities(&self) -> &Arc<IdentityRegistry> {
+        &self.identities
+    }
+
+    /// 使用指定的事件总线（例如与服务器共享）
+    pub fn with_events(mut self, events: Arc<EventBus>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn events(&self) -> &Arc<EventBus> {
+        &self.events
+    }
+
+    /// 启用离线消息暂存：节点重新握手后投递暂存的路由消息
+    pub fn with_offline_store(mut self, store: Arc<OfflineStore>) -> Self {
+        self.offline_store = Some(store);
+        self
+    }
+
+    /// 离线消息暂存（未启用时为 `None`）
+    pub fn offline_store(&self) -> Option<&Arc<OfflineStore>> {
+        self.offline_store.as_ref()
+    }
+
+    /// 使用指定的带宽估计（例如按配置的参考带宽创建）
+    pub fn with_bandwidth(mut self, bandwidth: Arc<BandwidthMap>) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+
+    pub fn bandwidth(&self) -> &Arc<BandwidthMap> {
+        &self.bandwidth
+    }
+
+    pub fn latency(&self) -> &Arc<LatencyMatrix> {
+        &self.latency
+    }
+
+    /// 使用指定的保活探测器（例如按配置创建）
+    pub fn with_keepalive(mut self, keepalive: Arc<KeepaliveProber>) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    pub fn keepalive(&self) -> &Arc<KeepaliveProber> {
+        &self.keepalive
+    }
+
+    /// 设置固定节点
+    pub fn with_pinned(mut self, pinned: Vec<PinnedPeer>) -> Self {
+        self.pinned = pinned;
+        self
+    }
+
+    pub fn pinned(&self) -> &[PinnedPeer] {
+        &self.pinned
+    }
+
+    /// 按配置地址查找固定节点
+    pub fn pinned_by_addr(&self, addr: &SocketAddr) -> Option<&PinnedPeer> {
+        self.pinned.iter().find(|p| p.addr == *addr)
+    }
+
+    /// 记录两个节点之间的通信（协调直连、路由消息、中继数据）
+    pub fn record_contact(&self, a: Uuid, b: Uuid) {
+        self.contacts.record(a, b);
+    }
+
+    /// 记录节点上报的直连结果（成功建立会话，失败移除会话）
+    pub fn record_session(&self, a: Uuid, b: Uuid, path: P2PPath) {
+        self.sessions.record(a, b, path);
+    }
+
+    pub fn sessions(&self) -> &P2PSessions {
+        &self.sessions
+    }
+
+    pub fn presence(&self) -> &WatchList {
+        &self.presence
+    }
+
+    /// 给定节点中当前在线（已认证）的节点
+    pub async fn online_among(&self, peer_ids: &[Uuid]) -> Vec<Uuid> {
+        let peers = self.peers.read().await;
+        let mut online = Vec::new();
+        for id in peer_ids {
+            if let Some(peer) = peers.get(id)
+                && peer.read().await.is_authenticated()
+                && !online.contains(id)
+            {
+                online.push(*id);
+            }
+        }
+        online
+    }
+
+    /// 向关注节点的节点推送其上线/下线
+    async fn notify_presence(&self, peer_id: &Uuid, online: bool) {
+        let watchers = self.presence.watchers(peer_id);
+        if watchers.is_empty() {
+            return;
+        }
+        let message = match Message::presence(*peer_id, online) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("{}", tr!("构造节点 {} 的在线状态通知失败: {}", "Failed to build presence notification for node {}: {}", peer_id, e));
+                return;
+            }
+        };
+        for id in watchers {
+            let Some(peer) = self.get_peer(&id).await else { continue };
+            let guard = peer.read().await;
+            if !guard.is_authenticated() {
+                continue;
+            }
+            if let Err(e) = guard.send_message(&message).await {
+                warn!("{}", tr!("向节点 {} 发送在线状态通知失败: {}", "Failed to send presence notification to node {}: {}", id, e));
+            }
+        }
+    }
+
+    pub fn limits(&self) -> &ConnectionLimits {
+        &self.limits
+    }
+
+    /// 节点数是否超过软限制
+    pub async fn is_busy(&self) -> bool {
+        match self.limits.soft() {
+            Some(soft) => self.peers.read().await.len() > soft,
+            None => false,
         }
     }
+
+    /// 节点数是否已达到硬限制
+    pub async fn is_full(&self) -> bool {
+        self.peers.read().await.len() >= self.limits.hard()
+    }
+
+    /// 负载提示，仅在启用软限制时提供
+    pub async fn load_hint(&self) -> Option<LoadHint> {
+        self.limits.soft()?;
+        let busy = self.is_busy().await;
+        Some(LoadHint { busy, heartbeat_interval_secs: self.limits.heartbeat_interval_secs(busy) })
+    }
+
+    /// 创建附带负载提示的节点发现响应，列表按 `with_discovery_max_bytes` 的上限截断
+    pub async fn discovery_message(&self, peers: &PeerListSnapshot, exclude_id: Option<Uuid>, requester: Option<&PeerTraits>) -> Message {
+        let mut message = Message::new(MessageType::DiscoveryResponse, peers.payload_for(exclude_id, requester, self.discovery_max_bytes));
+        message.load = self.load_hint().await;
+        message
+    }
     
     /// 添加新的对等节点
     pub async fn add_peer(&self, connection: Arc<Connection>) -> Result<Arc<RwLock<Peer>>> {
-        let peers_count = self.peers.read().await.len();
-        if peers_count >= self.max_connections {
-            return Err(anyhow::anyhow!("已达到最大连接数限制: {}", self.max_connections));
+        // 固定节点不受连接数限制
+        if self.is_full().await && self.pinned_by_addr(&connection.peer_addr()).is_none() {
+            return Err(anyhow::anyhow!("已达到最大连接数限制: {}", self.limits.hard()));
         }
         
-        let peer = Arc::new(RwLock::new(Peer::new(connection)));
+        let mut peer = Peer::new(connection);
+        peer.attach_counters(self.counters.clone());
+        let peer = Arc::new(RwLock::new(peer));
         let peer_id = peer.read().await.id;
         let peer_addr = peer.read().await.addr();
         
@@ -120,23 +763,212 @@ impl PeerManager {
         self.peers.write().await.insert(peer_id, peer.clone());
         self.peers_by_addr.write().await.insert(peer_addr, peer.clone());
         
-        info!("添加新的对等节点: {} ({})", peer_id, peer_addr);
+        info!("{}", tr!("添加新的对等节点: {} ({})", "Adding new peer: {} ({})", peer_id, peer_addr));
+        self.events.emit(EventKind::PeerJoined, None, json!({ "addr": peer_addr }));
         
         Ok(peer)
     }
     
-    /// 移除对等节点
+    /// 接管其他实例移交的已认证节点（无需重新握手），同ID或同地址的旧条目会被替换
+    ///
+    /// 条目先按原实例观察到的地址登记；节点换了 NAT 映射时凭移交的会话票据经 [`Self::migrate_peer`] 迁移到新地址。
+    pub async fn adopt_peer(&self, connection: Arc<Connection>, session: &HandoffSession) -> Result<Arc<RwLock<Peer>>> {
+        let peer_id = session.node_info.id;
+        let peer_addr = connection.peer_addr();
+        self.detach_peer(&peer_id).await;
+        let stale_id = match self.peers_by_addr.read().await.get(&peer_addr) {
+            Some(stale) => Some(stale.read().await.id),
+            None => None,
+        };
+        if let Some(stale_id) = stale_id {
+            self.remove_peer(&stale_id).await;
+        }
+
+        if self.is_full().await {
+            return Err(anyhow::anyhow!("已达到最大连接数限制: {}", self.limits.hard()));
+        }
+        self.identities.admit(peer_id, session.public_key.as_deref())?;
+
+        let mut peer = Peer::with_node_info(connection, session.node_info.clone());
+        peer.nat_type = session.nat_type.clone();
+        peer.session_ticket = session.session_ticket.clone();
+        peer.attach_counters(self.counters.clone());
+        // 视为刚刚活跃，避免在客户端重连前被清理
+        peer.update_ping();
+        let peer = Arc::new(RwLock::new(peer));
+        self.peers.write().await.insert(peer_id, peer.clone());
+        self.peers_by_addr.write().await.insert(peer_addr, peer.clone());
+
+        info!("{}", tr!("接管移交的节点: {} ({})", "Taking over handed-over node: {} ({})", peer_id, peer_addr));
+        let name = peer.read().await.node_info.as_ref().map(|info| info.name.clone());
+        self.events.emit(EventKind::PeerAuthenticated, Some(peer_id), json!({ "addr": peer_addr, "name": name, "handoff": true }));
+        Ok(peer)
+    }
+
+    /// 移除对等节点，并向近期与其通信过的节点发送 `PeerDown` 通知、向关注者发送下线通知
     pub async fn remove_peer(&self, peer_id: &Uuid) -> Option<Arc<RwLock<Peer>>> {
+        let removed = self.detach_peer(peer_id).await;
+        if let Some(peer) = &removed {
+            if self.events.is_active() {
+                let guard = peer.read().await;
+                let authenticated = guard.node_info.is_some();
+                let name = guard.node_info.as_ref().map(|info| info.name.clone());
+                let data = json!({ "addr": guard.addr(), "name": name, "authenticated": authenticated });
+                self.events.emit(EventKind::PeerDisconnected, authenticated.then_some(*peer_id), data);
+            }
+            self.keepalive.cancel(peer_id);
+            self.bandwidth.remove_peer(peer_id);
+            self.latency.remove_peer(peer_id);
+            self.notify_peer_down(peer_id).await;
+            self.presence.remove_watcher(peer_id);
+            self.notify_presence(peer_id, false).await;
+        }
+        removed
+    }
+
+    /// 注册节点被强制断开后释放其状态的钩子；只保存弱引用
+    pub fn set_release_hook(&self, hook: Weak<dyn PeerRelease>) {
+        *self.release_hook.write().unwrap() = Some(hook);
+    }
+
+    /// 强制断开节点：发送带结构化原因的 `Disconnect`，移除节点记录及其路由、中继会话等状态，
+    /// 断开的是已认证节点时广播新的节点列表。节点不存在时返回 `false`。
+    pub async fn kick(&self, peer_id: &Uuid, code: DisconnectReason, reason: &str) -> bool {
+        let Some(peer) = self.remove_peer(peer_id).await else {
+            return false;
+        };
+        let authenticated = {
+            let guard = peer.read().await;
+            if let Err(e) = guard.send_message(&Message::disconnect_with_code(code, reason.to_string())).await {
+                debug!("{}", tr!("向节点 {} 发送断开通知失败: {}", "Failed to send disconnect notice to node {}: {}", peer_id, e));
+            }
+            guard.is_authenticated()
+        };
+        let hook = self.release_hook.read().unwrap().as_ref().and_then(Weak::upgrade);
+        if let Some(hook) = hook {
+            hook.release(peer_id).await;
+        }
+        if authenticated && let Err(e) = self.broadcast_peer_list(None).await {
+            warn!("{}", tr!("断开节点后广播节点列表失败: {}", "Failed to broadcast node list after disconnecting node: {}", e));
+        }
+        debug!("{}", tr!("已强制断开节点 {}（{:?}: {}）", "Forcibly disconnected node {} ({:?}: {})", peer_id, code, reason));
+        true
+    }
+
+    /// 只移除节点记录，不发送下线通知（例如节点被移交后重新接管）
+    async fn detach_peer(&self, peer_id: &Uuid) -> Option<Arc<RwLock<Peer>>> {
         let removed = self.peers.write().await.remove(peer_id);
         
         if let Some(ref peer) = removed {
-            let peer_addr = peer.read().await.addr();
+            let peer_addr = {
+                let mut guard = peer.write().await;
+                guard.detach_counters();
+                guard.addr()
+            };
             self.peers_by_addr.write().await.remove(&peer_addr);
-            info!("移除对等节点: {} ({})", peer_id, peer_addr);
+            self.pending_handshakes.forget(&peer_addr);
+            info!("{}", tr!("移除对等节点: {} ({})", "Removing peer: {} ({})", peer_id, peer_addr));
         }
         
         removed
     }
+
+    /// 将已认证节点迁移到新地址（节点切换网络），凭握手时下发的会话票据验证身份
+    ///
+    /// 地址索引在同一把锁内完成替换；新地址上尚未认证的临时节点会被移除。返回节点与其旧地址。
+    pub async fn migrate_peer(
+        &self,
+        node_id: &Uuid,
+        session_ticket: &str,
+        connection: Arc<Connection>,
+    ) -> Result<(Arc<RwLock<Peer>>, SocketAddr)> {
+        let new_addr = connection.peer_addr();
+        let mut peers = self.peers.write().await;
+        let Some(peer) = peers.get(node_id).cloned() else {
+            anyhow::bail!("节点 {} 不存在或已下线，请重新握手", node_id);
+        };
+        {
+            let guard = peer.read().await;
+            if !guard.is_authenticated() || guard.session_ticket.as_deref() != Some(session_ticket) {
+                anyhow::bail!("会话票据无效");
+            }
+        }
+
+        let mut peers_by_addr = self.peers_by_addr.write().await;
+        if let Some(occupant) = peers_by_addr.get(&new_addr).cloned()
+            && !Arc::ptr_eq(&occupant, &peer)
+        {
+            let mut occupant = occupant.write().await;
+            if occupant.is_authenticated() {
+                anyhow::bail!("地址 {} 已被节点 {} 使用", new_addr, occupant.id);
+            }
+            occupant.detach_counters();
+            peers.remove(&occupant.id);
+            peers_by_addr.remove(&new_addr);
+        }
+
+        let old_addr = {
+            let mut guard = peer.write().await;
+            let old_addr = guard.addr();
+            guard.connection = connection;
+            guard.update_ping();
+            old_addr
+        };
+        peers_by_addr.remove(&old_addr);
+        peers_by_addr.insert(new_addr, peer.clone());
+        drop(peers_by_addr);
+        drop(peers);
+
+        // 旧地址上的保活探测已无意义
+        self.keepalive.cancel(node_id);
+        info!("{}", tr!("节点 {} 地址迁移: {} -> {}", "Node {} migrated address: {} -> {}", node_id, old_addr, new_addr));
+        Ok((peer, old_addr))
+    }
+
+    /// 向与节点保持 P2P 会话或近期通信过的节点发送地址变更通知
+    pub async fn notify_address_update(&self, peer_id: &Uuid, message: &Message) {
+        let mut recipients = self.sessions.partners(peer_id);
+        for id in self.contacts.interested(peer_id) {
+            if !recipients.contains(&id) {
+                recipients.push(id);
+            }
+        }
+        for id in recipients {
+            let Some(peer) = self.get_peer(&id).await else { continue };
+            let guard = peer.read().await;
+            if !guard.is_authenticated() {
+                continue;
+            }
+            match guard.send_message(message).await {
+                Ok(()) => debug!("{}", tr!("已通知节点 {}: {} 地址已变更", "Notified node {}: {} changed address", id, peer_id)),
+                Err(e) => warn!("{}", tr!("向节点 {} 发送地址变更通知失败: {}", "Failed to send address change notification to node {}: {}", id, e)),
+            }
+        }
+    }
+
+    async fn notify_peer_down(&self, peer_id: &Uuid) {
+        let mut interested = self.contacts.take_interested(peer_id);
+        for id in self.sessions.remove(peer_id) {
+            if !interested.contains(&id) {
+                interested.push(id);
+            }
+        }
+        if interested.is_empty() {
+            return;
+        }
+        let message = Message::peer_down(*peer_id);
+        for id in interested {
+            let Some(peer) = self.get_peer(&id).await else { continue };
+            let guard = peer.read().await;
+            if !guard.is_authenticated() {
+                continue;
+            }
+            match guard.send_message(&message).await {
+                Ok(()) => debug!("{}", tr!("已通知节点 {}: {} 已下线", "Notified node {}: {} went offline", id, peer_id)),
+                Err(e) => warn!("{}", tr!("向节点 {} 发送下线通知失败: {}", "Failed to send offline notification to node {}: {}", id, e)),
+            }
+        }
+    }
     
     /// 获取对等节点
     pub async fn get_peer(&self, peer_id: &Uuid) -> Option<Arc<RwLock<Peer>>> {
@@ -148,19 +980,6 @@ impl PeerManager {
         self.peers_by_addr.read().await.get(addr).cloned()
     }
     
-    /// 获取或创建基于地址的peer（UDP需要）
-    pub async fn get_or_create_peer_by_addr(&self, connection: Arc<Connection>) -> Result<Arc<RwLock<Peer>>> {
-        let addr = connection.peer_addr();
-        
-        // 先尝试获取现有的peer
-        if let Some(peer) = self.get_peer_by_addr(&addr).await {
-            return Ok(peer);
-        }
-        
-        // 如果不存在，创建新的peer
-        self.add_peer(connection).await
-    }
-    
     /// 获取所有对等节点
     pub async fn get_all_peers(&self) -> Vec<Arc<RwLock<Peer>>> {
         self.peers.read().await.values().cloned().collect()
@@ -181,45 +1000,153 @@ impl PeerManager {
     }
     
     /// 处理握手请求
+    /// 识别重复的握手请求：已应答的重发首次的应答，仍在处理或已被更新握手取代的直接丢弃
+    ///
+    /// 返回 `true` 表示该请求是重复的、无需再处理。
+    pub async fn answer_duplicate_handshake(&self, peer: &Arc<RwLock<Peer>>, message: &Message) -> Result<bool> {
+        let peer_addr = peer.read().await.addr();
+        match self.pending_handshakes.begin(peer_addr, message) {
+            HandshakeState::New => Ok(false),
+            HandshakeState::Answered(mut response) => {
+                ServerMetrics::incr(&self.metrics.handshake_duplicates);
+                // 重传时换了消息ID的请求，签名按新的请求ID重新计算
+                if response.reply_to != Some(message.id)
+                    && let Some(identity) = &self.identity
+                {
+                    identity.sign_handshake_response(&mut response, message.id, &self.local_node_info.network_id);
+                }
+                debug!("{}", tr!("重复的握手请求，重发应答给 {} (seq={:?})", "Duplicate handshake request, resending the reply to {} (seq={:?})", peer_addr, message.sequence_number));
+                peer.read().await.send_message(&response).await?;
+                Ok(true)
+            }
+            state => {
+                ServerMetrics::incr(&self.metrics.handshake_duplicates);
+                debug!("{}", tr!("丢弃来自 {} 的重复握手请求: {:?} (seq={:?})", "Dropping duplicate handshake request from {}: {:?} (seq={:?})", peer_addr, state, message.sequence_number));
+                Ok(true)
+            }
+        }
+    }
+
+    /// 处理握手请求；失败的请求从握手关联表中移除，重传时会重新处理
     pub async fn handle_handshake_request(
         &self,
         peer: Arc<RwLock<Peer>>, 
         message: &Message,
     ) -> Result<()> {
-        let node_info = HandshakeProtocol::validate_handshake_request(message)
+        let peer_addr = peer.read().await.addr();
+        let result = self.accept_handshake(peer, message).await;
+        if result.is_err() {
+            self.pending_handshakes.abandon(peer_addr, message);
+        }
+        result
+    }
+
+    async fn accept_handshake(
+        &self,
+        peer: Arc<RwLock<Peer>>, 
+        message: &Message,
+    ) -> Result<()> {
+        let mut node_info = HandshakeProtocol::validate_handshake_request(message)
             .map_err(|e| anyhow::anyhow!("握手请求验证失败: {}", e))?;
         
         let peer_addr = peer.read().await.addr();
         info!(
-            "收到握手请求: 对端地址={}、节点名={}、节点ID={}、网络ID={}",
-            peer_addr, node_info.name, node_info.id, node_info.network_id
+            "{}",
+            tr!(
+                "收到握手请求: 对端地址={}、节点名={}、节点ID={}、网络ID={}",
+                "Handshake request received: peer address={}, node name={}, node ID={}, network ID={}",
+                peer_addr,
+                node_info.name,
+                node_info.id,
+                node_info.network_id,
+            )
         );
 
         // 检查网络ID是否匹配
         if node_info.network_id != self.local_node_info.network_id {
             let error_msg = format!("网络ID不匹配: 期望 {}，收到 {}", self.local_node_info.network_id, node_info.network_id);
-            warn!("{}", error_msg);
+            warn!(
+                "{}",
+                tr!(
+                    "网络ID不匹配: 期望 {}，收到 {}",
+                    "Network ID mismatch: expected {}, got {}",
+                    self.local_node_info.network_id,
+                    node_info.network_id,
+                )
+            );
             let error_response = Message::error(error_msg.clone());
             peer.read().await.send_message(&error_response).await?;
             return Err(anyhow::anyhow!(error_msg));
         }
 
+        // 声明了公钥的节点须证明持有私钥；登记过公钥的节点ID只能用当前公钥握手
+        let verified = identity::verify_handshake_request(message, &node_info)
+            .and_then(|()| self.identities.admit(node_info.id, node_info.public_key.as_deref()));
+        if let Err(e) = verified {
+            let error_msg = format!("节点身份校验失败: {}", e);
+            warn!("{}", tr!("节点身份校验失败: {} (对端地址={})", "Node identity verification failed: {} (peer address={})", e, peer_addr));
+            peer.read().await.send_message(&Message::error(error_msg.clone())).await?;
+            peer.write().await.update_status(PeerStatus::Error("节点身份校验失败".to_string()));
+            return Err(anyhow::anyhow!(error_msg));
+        }
+
+        // 没有该节点当前会话票据的握手按节点ID统计来源，来源分布异常的节点ID暂时拒绝握手
+        if !self.holds_session_ticket(&node_info.id, message).await
+            && let Some(retry_after_secs) = self.screen_spoofing(node_info.id, peer_addr)
+        {
+            let error_msg = "该节点ID近期的握手来源异常，疑遭冒用，请稍后重试".to_string();
+            let mut reject = Message::handshake_rejected(self.local_node_info.clone(), error_msg.clone(), retry_after_secs)?;
+            if let Some(identity) = &self.identity {
+                identity.sign_handshake_response(&mut reject, message.id, &self.local_node_info.network_id);
+            }
+            peer.read().await.send_message(&reject).await?;
+            peer.write().await.update_status(PeerStatus::Error("节点ID疑遭冒用".to_string()));
+            return Err(anyhow::anyhow!(error_msg));
+        }
+
+        // 访问令牌只用于确定角色，在保存与广播节点信息之前取出（签名已在取出前校验）
+        let access_token = node_info.metadata.remove("access_token");
+
+        // 节点信息随节点列表下发给所有节点，超出限制的部分截断或拒绝握手（签名已在截断前校验）
+        let violations = limit_node_info(&mut node_info, &self.node_info_limits);
+        if !violations.is_empty() {
+            let violations = violations.join("; ");
+            if self.node_info_limits.action == LimitAction::Reject {
+                ServerMetrics::incr(&self.metrics.node_info_rejected);
+                warn!("{}", tr!("节点信息超出限制，拒绝握手: {} (对端地址={})", "Node info exceeds limits, rejecting handshake: {} (peer address={})", violations, peer_addr));
+                let error_msg = format!("节点信息超出限制: {}", violations);
+                peer.read().await.send_message(&Message::error(error_msg.clone())).await?;
+                peer.write().await.update_status(PeerStatus::Error("节点信息超出限制".to_string()));
+                return Err(anyhow::anyhow!(error_msg));
+            }
+            ServerMetrics::incr(&self.metrics.node_info_truncated);
+            warn!("{}", tr!("节点信息超出限制，已截断: {} (对端地址={})", "Node info exceeds limits, truncated: {} (peer address={})", violations, peer_addr));
+        }
+
         // 同ID重连处理：如果节点ID已存在，视为重连并替换旧映射
         {
             let mut peers_guard = self.peers.write().await;
             if let Some(existing_peer) = peers_guard.get(&node_info.id).cloned() {
                 // 如果映射的是同一个Peer对象，则允许继续（可能是重复握手）
                 if !Arc::ptr_eq(&existing_peer, &peer) {
-                    let old_addr = existing_peer.read().await.addr();
+                    let old_addr = {
+                        let mut guard = existing_peer.write().await;
+                        guard.detach_counters();
+                        guard.addr()
+                    };
                     // 从地址索引中移除旧地址
                     self.peers_by_addr.write().await.remove(&old_addr);
                     // 从ID索引中移除旧Peer
                     peers_guard.remove(&node_info.id);
                     info!(
-                        "检测到节点ID重用，视为重连：ID={} 旧地址={} 新地址={}，替换旧映射",
-                        node_info.id,
-                        old_addr,
-                        peer_addr
+                        "{}",
+                        tr!(
+                            "检测到节点ID重用，视为重连：ID={} 旧地址={} 新地址={}，替换旧映射",
+                            "Node ID reused, treating as reconnect: ID={} old address={} new address={}, replacing old mapping",
+                            node_info.id,
+                            old_addr,
+                            peer_addr,
+                        )
                     );
                 }
             }
@@ -238,49 +1165,152 @@ impl PeerManager {
             return Err(anyhow::anyhow!("缺少 network_id"));
         }
         
-        // 更新节点信息
+        // 更新节点信息，每次握手都签发新的会话票据
+        let session_ticket = identity::session_ticket(&self.local_node_info.network_id, node_info.id);
         {
             let mut peer_guard = peer.write().await;
             peer_guard.id = node_info.id;
             peer_guard.node_info = Some(node_info.clone());
+            peer_guard.session_ticket = Some(session_ticket.clone());
+            peer_guard.access_token = access_token;
             peer_guard.update_status(PeerStatus::Authenticated);
         }
         
-        // 更新peers映射中的键
-        {
-            let mut peers = self.peers.write().await;
-            // 找到旧的键并移除
-            let old_key = peers.iter()
-                .find(|(_, v)| Arc::ptr_eq(v, &peer))
-                .map(|(k, _)| *k);
-            
-            if let Some(old_key) = old_key {
-                peers.remove(&old_key);
-            }
-            
-            peers.insert(node_info.id, peer.clone());
-        }
+        self.reindex_peer(&peer, node_info.id).await;
         
         // 发送握手响应：回显客户端的 network_id，并告知其公网地址
+        if let Some(nat_type) = node_info.metadata.get("nat_type") {
+            peer.write().await.nat_type = Some(nat_type.clone());
+        }
+        if let Some(class) = self.tag_carrier_nat(&peer).await {
+            info!("{}", tr!("节点 {} ({}) 位于运营商级地址转换之后: {:?}", "Node {} ({}) is behind carrier-grade translation: {:?}", node_info.id, peer_addr, class));
+        }
+
         let mut local_info = self.local_node_info.clone();
         local_info.network_id = incoming_network_id;
-        let response = Message::handshake_response_with_public_addr(local_info, true, peer_addr);
+        local_info.metadata.insert(
+            "keepalive_interval_secs".to_string(),
+            self.keepalive.recommended_for(peer_addr.ip()).to_string(),
+        );
+        let stored_messages = self.offline_store.as_ref().map(|store| store.pending(&node_info.id));
+        let mut response = Message::handshake_accepted(local_info, peer_addr, session_ticket, stored_messages)?;
+        if let Some(identity) = &self.identity {
+            identity.sign_handshake_response(&mut response, message.id, &self.local_node_info.network_id);
+        }
         
         peer.read().await.send_message(&response).await?;
+        self.pending_handshakes.answer(peer_addr, message, &response);
 
         // 在握手成功后，将当前已认证节点列表推送给新加入的客户端（排除其自身）
-        let peer_infos = self.get_peer_info_list_excluding(Some(node_info.id)).await;
-        let discovery_msg = Message::discovery_response(peer_infos);
+        let snapshot = self.peer_list_snapshot().await;
+        let traits = peer.read().await.traits();
+        let discovery_msg = self.discovery_message(&snapshot, Some(node_info.id), Some(&traits)).await;
         if let Err(e) = peer.read().await.send_message(&discovery_msg).await {
-            warn!("发送节点列表到新客户端失败: {}", e);
+            warn!("{}", tr!("发送节点列表到新客户端失败: {}", "Failed to send node list to new client: {}", e));
         }
+        self.notify_presence(&node_info.id, true).await;
+        self.events.emit(
+            EventKind::PeerAuthenticated,
+            Some(node_info.id),
+            json!({ "addr": peer_addr, "name": node_info.name, "network_id": node_info.network_id }),
+        );
+        self.deliver_stored(&peer, &node_info.id).await;
 
         // 广播延后，由服务器端进行去抖合并触发
 
         Ok(())
     }
     
-    /// 处理节点发现请求
+    /// 投递节点离线期间暂存的路由消息
+    async fn deliver_stored(&self, peer: &Arc<RwLock<Peer>>, peer_id: &Uuid) {
+        let Some(store) = &self.offline_store else { return };
+        let stored = store.take(peer_id);
+        if stored.is_empty() {
+            return;
+        }
+        info!("{}", tr!("向节点 {} 投递 {} 条离线消息", "Delivering {1} offline messages to node {0}", peer_id, stored.len()));
+        for routed in stored {
+            let result = async { peer.read().await.send_message(&routed.to_message()?).await }.await;
+            if let Err(e) = result {
+                warn!("{}", tr!("投递离线消息 {} 到节点 {} 失败: {}", "Failed to deliver offline message {} to node {}: {}", routed.route_id, peer_id, e));
+            }
+        }
+    }
+
+    /// 握手确定节点ID后更新peers映射中的键
+    async fn reindex_peer(&self, peer: &Arc<RwLock<Peer>>, id: Uuid) {
+        let mut peers = self.peers.write().await;
+        // 找到旧的键并移除
+        let old_key = peers.iter()
+            .find(|(_, v)| Arc::ptr_eq(v, peer))
+            .map(|(k, _)| *k);
+        
+        if let Some(old_key) = old_key {
+            peers.remove(&old_key);
+        }
+        
+        // 被同ID的新连接替换的旧节点不再计入统计
+        if let Some(replaced) = peers.insert(id, peer.clone())
+            && !Arc::ptr_eq(&replaced, peer)
+        {
+            replaced.write().await.detach_counters();
+        }
+    }
+
+    /// 握手是否带有该节点当前的会话票据（已认证的节点换了地址重新握手）
+    async fn holds_session_ticket(&self, node_id: &Uuid, message: &Message) -> bool {
+        let Some(ticket) = message.payload.get("session_ticket").and_then(|v| v.as_str()) else {
+            return false;
+        };
+        match self.get_peer(node_id).await {
+            Some(peer) => {
+                let guard = peer.read().await;
+                guard.is_authenticated() && guard.session_ticket.as_deref() == Some(ticket)
+            }
+            None => false,
+        }
+    }
+
+    /// 记录声称 `node_id` 的握手来源；该节点ID疑遭冒用时返回建议的重试等待秒数
+    fn screen_spoofing(&self, node_id: Uuid, addr: SocketAddr) -> Option<u64> {
+        match self.spoof_guard.check(node_id, addr, std::time::Instant::now()) {
+            SpoofVerdict::Allow => None,
+            SpoofVerdict::Flagged { distinct_ips, port_entropy_bits, block } => {
+                ServerMetrics::incr(&self.metrics.spoof_identities_flagged);
+                ServerMetrics::incr(&self.metrics.spoof_handshakes_rejected);
+                warn!(
+                    "{}",
+                    tr!(
+                        "节点ID {} 的握手来源异常（{} 个来源IP，端口熵 {:.2} 比特），疑遭冒用，{} 秒内拒绝其不带会话票据的握手",
+                        "Handshakes claiming node ID {} come from suspicious sources ({} source IPs, port entropy {:.2} bits), possible spoofing; rejecting its handshakes without a session ticket for {} seconds",
+                        node_id,
+                        distinct_ips,
+                        port_entropy_bits,
+                        block.as_secs(),
+                    )
+                );
+                self.events.emit(
+                    EventKind::PeerBanned,
+                    Some(node_id),
+                    json!({
+                        "ip": addr.ip(),
+                        "reason": "spoofing",
+                        "distinct_ips": distinct_ips,
+                        "port_entropy_bits": port_entropy_bits,
+                        "block_secs": block.as_secs(),
+                    }),
+                );
+                Some(block.as_secs())
+            }
+            SpoofVerdict::Limited { remaining } => {
+                ServerMetrics::incr(&self.metrics.spoof_handshakes_rejected);
+                debug!("{}", tr!("节点ID {} 仍受冒用限制，拒绝来自 {} 的握手", "Node ID {} is still under impersonation hold, rejecting handshake from {}", node_id, addr));
+                Some(remaining.as_secs().max(1))
+            }
+        }
+    }
+
+    /// 处理握手响应（服务器主动向固定节点发起握手时）
     pub async fn handle_handshake_response(
         &self,
         peer: Arc<RwLock<Peer>>, 
@@ -293,8 +1323,15 @@ impl PeerManager {
         let remote_network_id_dbg = response.node_info.metadata.get("network_id").cloned();
         let peer_addr = peer.read().await.addr();
         info!(
-            "收到握手响应: 对端地址={}、节点名={}、节点ID={}、网络ID={:?}",
-            peer_addr, response.node_info.name, response.node_info.id, remote_network_id_dbg
+            "{}",
+            tr!(
+                "收到握手响应: 对端地址={}、节点名={}、节点ID={}、网络ID={:?}",
+                "Handshake response received: peer address={}, node name={}, node ID={}, network ID={:?}",
+                peer_addr,
+                response.node_info.name,
+                response.node_info.id,
+                remote_network_id_dbg,
+            )
         );
 
         if response.success {
@@ -302,25 +1339,43 @@ impl PeerManager {
             let expected_network_id = self.local_node_info.metadata.get("network_id").cloned();
             let remote_network_id = response.node_info.metadata.get("network_id").cloned();
             if expected_network_id.is_some() && expected_network_id != remote_network_id {
-                let error_msg = format!(
-                    "网络ID不匹配: 本地={:?}, 对端={:?}",
-                    expected_network_id, remote_network_id
+                warn!(
+                    "{}",
+                    tr!(
+                        "网络ID不匹配: 本地={:?}, 对端={:?}",
+                        "Network ID mismatch: local={:?}, remote={:?}",
+                        expected_network_id,
+                        remote_network_id,
+                    )
                 );
-                warn!("{}", error_msg);
                 peer.write().await.update_status(PeerStatus::Error("网络ID不匹配".to_string()));
                 return Err(anyhow::anyhow!("网络ID不匹配"));
             }
 
-            let mut peer_guard = peer.write().await;
-            peer_guard.id = response.node_info.id;
-            peer_guard.node_info = Some(response.node_info.clone());
-            peer_guard.update_status(PeerStatus::Authenticated);
+            {
+                let mut peer_guard = peer.write().await;
+                peer_guard.id = response.node_info.id;
+                peer_guard.node_info = Some(response.node_info.clone());
+                peer_guard.update_status(PeerStatus::Authenticated);
+                peer_guard.update_ping();
+            }
+            self.reindex_peer(&peer, response.node_info.id).await;
+            self.notify_presence(&response.node_info.id, true).await;
+            self.events.emit(
+                EventKind::PeerAuthenticated,
+                Some(response.node_info.id),
+                json!({ "addr": peer_addr, "name": response.node_info.name, "network_id": remote_network_id }),
+            );
             
             info!(
-                "握手响应成功: 节点名={}、节点ID={}、网络ID={:?}",
-                peer_guard.node_info.as_ref().map(|n| n.name.clone()).unwrap_or_default(),
-                peer_guard.id,
-                remote_network_id_dbg
+                "{}",
+                tr!(
+                    "握手响应成功: 节点名={}、节点ID={}、网络ID={:?}",
+                    "Handshake response accepted: node name={}, node ID={}, network ID={:?}",
+                    response.node_info.name,
+                    response.node_info.id,
+                    remote_network_id_dbg,
+                )
             );
         } else {
             let error_msg = response.error_message.unwrap_or_else(|| "握手失败".to_string());
@@ -343,9 +1398,28 @@ impl PeerManager {
         Ok(())
     }
     
-    /// 处理心跳响应
-    pub async fn handle_pong(&self, peer: Arc<RwLock<Peer>>, _message: &Message) -> Result<()> {
-        peer.write().await.update_ping();
+    /// 处理心跳响应，记录其中附带的往返时延
+    pub async fn handle_pong(&self, peer: Arc<RwLock<Peer>>, message: &Message) -> Result<()> {
+        {
+            let mut guard = peer.write().await;
+            guard.update_ping();
+            guard.pings.answered(std::time::Instant::now());
+        }
+        let Some(rtts) = message.payload.get("rtts") else { return Ok(()) };
+        let (peer_id, authenticated) = {
+            let guard = peer.read().await;
+            (guard.id, guard.is_authenticated())
+        };
+        if !authenticated {
+            return Ok(());
+        }
+        match serde_json::from_value::<Vec<RttSample>>(rtts.clone()) {
+            Ok(samples) => {
+                let accepted = self.latency.record(peer_id, &samples);
+                debug!("{}", tr!("节点 {} 上报 {} 条往返时延", "Node {} reported {} round-trip times", peer_id, accepted));
+            }
+            Err(e) => debug!("{}", tr!("忽略节点 {} 格式错误的往返时延: {}", "Ignoring malformed round-trip times from node {}: {}", peer_id, e)),
+        }
         Ok(())
     }
     
@@ -358,11 +1432,12 @@ impl PeerManager {
         for peer in peers {
             let peer_guard = peer.read().await;
             if let Some(node_info) = &peer_guard.node_info {
-                let peer_info = PeerInfo::new(
+                let mut peer_info = PeerInfo::new(
                     node_info.id,
                     peer_guard.addr(),
                     node_info.capabilities.clone(),
                 );
+                peer_info.addresses = node_info.advertised_addrs();
                 peer_infos.push(peer_info);
             }
         }
@@ -370,51 +1445,74 @@ impl PeerManager {
         peer_infos
     }
 
-    /// 获取对等节点信息列表（可排除指定节点）
+    /// 获取对等节点信息列表（可排除指定节点）；固定节点即使不在线也会列出
     pub async fn get_peer_info_list_excluding(&self, exclude_id: Option<Uuid>) -> Vec<PeerInfo> {
+        self.peer_list_entries(exclude_id).await.into_iter().map(|(info, _)| info).collect()
+    }
+
+    /// 节点信息列表及各节点的可达性特征；离线的固定节点没有特征信息
+    pub async fn peer_list_entries(&self, exclude_id: Option<Uuid>) -> Vec<(PeerInfo, PeerTraits)> {
         let peers = self.get_authenticated_peers().await;
         let mut peer_infos = Vec::new();
 
         for peer in peers {
             let peer_guard = peer.read().await;
             if let Some(node_info) = &peer_guard.node_info {
-                if let Some(ex_id) = exclude_id {
-                    if node_info.id == ex_id { continue; }
-                }
-                let peer_info = PeerInfo::new(
+                if let Some(ex_id) = exclude_id && node_info.id == ex_id { continue; }
+                let mut peer_info = PeerInfo::new(
                     node_info.id,
                     peer_guard.addr(),
                     node_info.capabilities.clone(),
                 );
-                peer_infos.push(peer_info);
+                peer_info.pinned = self.pinned.iter().any(|p| p.node_id == node_info.id);
+                peer_info.addresses = node_info.advertised_addrs();
+                peer_info.connected_secs = Some(peer_guard.connected_for().as_secs());
+                peer_infos.push((peer_info, peer_guard.traits()));
             }
         }
 
+        for pinned in &self.pinned {
+            if exclude_id == Some(pinned.node_id) || peer_infos.iter().any(|(p, _)| p.id == pinned.node_id) {
+                continue;
+            }
+            let mut peer_info = PeerInfo::new(pinned.node_id, pinned.addr, Vec::new());
+            peer_info.pinned = true;
+            peer_infos.push((peer_info, PeerTraits::default()));
+        }
+
         peer_infos
     }
 
+    /// 当前节点列表的快照，供一次广播中的所有接收者共用
+    pub async fn peer_list_snapshot(&self) -> PeerListSnapshot {
+        PeerListSnapshot::with_traits(self.peer_list_entries(None).await)
+    }
+
     /// 广播当前的节点信息列表到所有已认证节点（每个接收者的列表会排除其自身）
-    #[allow(dead_code)]
     pub async fn broadcast_peer_list(&self, exclude_id: Option<Uuid>) -> Result<()> {
         let peers = self.get_authenticated_peers().await;
+        let snapshot = self.peer_list_snapshot().await;
+        let load = self.load_hint().await;
 
         for p in peers {
-            let pid = p.read().await.id;
-            if let Some(ex_id) = exclude_id {
-                if pid == ex_id { continue; }
-            }
-            let infos = self.get_peer_info_list_excluding(Some(pid)).await;
-            let msg = Message::discovery_response(infos);
+            let (pid, traits) = {
+                let guard = p.read().await;
+                (guard.id, guard.traits())
+            };
+            if let Some(ex_id) = exclude_id && pid == ex_id { continue; }
+            let payload = snapshot.payload_for(Some(pid), Some(&traits), self.discovery_max_bytes);
+            let mut msg = Message::new(MessageType::DiscoveryResponse, payload);
+            msg.load = load;
             if let Err(e) = p.read().await.send_message(&msg).await {
-                warn!("广播节点列表到 {} 失败: {}", p.read().await.addr(), e);
+                warn!("{}", tr!("广播节点列表到 {} 失败: {}", "Failed to broadcast node list to {}: {}", p.read().await.addr(), e));
             }
         }
 
         Ok(())
     }
     
-    /// 清理断开的连接
-    pub async fn cleanup_disconnected_peers(&self, timeout_secs: u64) {
+    /// 清理断开的连接，返回被移除的节点ID
+    pub async fn cleanup_disconnected_peers(&self, timeout_secs: u64) -> Vec<Uuid> {
         let mut to_remove = Vec::new();
         
         {
@@ -425,13 +1523,15 @@ impl PeerManager {
                 // 1) 非连接状态（Disconnected/Error/未握手完成）直接移除
                 let mut should_remove = !pg.is_connected();
                 let mut removal_reason = String::new();
+                let mut timed_out = false;
 
                 if should_remove {
                     removal_reason = format!("状态异常: {:?}", pg.status);
                 }
 
-                // 2) 仍为已认证但超时未响应（last_ping 过期或从未收到过）也移除
-                if !should_remove && pg.is_authenticated() {
+                // 2) 仍为已认证但超时未响应（last_ping 过期或从未收到过）也移除；
+                //    正在进行保活探测或休眠的节点会刻意保持静默，不按超时处理
+                if !should_remove && pg.is_authenticated() && !self.keepalive.is_probing(id) && !pg.is_dormant() {
                     let stale = match pg.last_ping {
                         Some(ts) => {
                             let elapsed = ts.elapsed().as_secs();
@@ -454,42 +1554,124 @@ impl PeerManager {
                     };
                     if stale {
                         should_remove = true;
+                        timed_out = true;
                     }
                 }
 
                 if should_remove {
-                    to_remove.push((*id, pg.addr(), removal_reason));
+                    to_remove.push((*id, pg.addr(), removal_reason, timed_out));
                 }
             }
         }
         
-        for (id, addr, reason) in to_remove {
-            info!("清理节点 {} ({}): {}", id, addr, reason);
-            self.remove_peer(&id).await;
+        let mut removed = Vec::with_capacity(to_remove.len());
+        for (id, addr, reason, timed_out) in to_remove {
+            info!("{}", tr!("清理节点 {} ({}): {}", "Cleaning up node {} ({}): {}", id, addr, reason));
+            removed.push(id);
+            // 超时的节点可能只是单向不通，仍尝试告知它重新握手
+            if let Some(peer) = self.remove_peer(&id).await
+                && timed_out
+                && let Err(e) = peer.read().await.send_message(&Message::disconnect_with_code(DisconnectReason::IdleTimeout, reason)).await
+            {
+                debug!("{}", tr!("发送超时断开通知到 {} 失败: {}", "Failed to send timeout disconnect notice to {}: {}", addr, e));
+            }
         }
+        removed
     }
     
-    /// 获取连接统计信息
-    pub async fn get_stats(&self) -> PeerStats {
+    /// 指定节点已连接的时长，节点不存在时为 `None`
+    pub async fn connected_for(&self, peer_id: &Uuid) -> Option<std::time::Duration> {
+        let peer = self.get_peer(peer_id).await?;
+        let connected = peer.read().await.connected_for();
+        Some(connected)
+    }
+
+    /// 各已认证节点已连接的时长
+    pub async fn connection_durations(&self) -> Vec<(Uuid, std::time::Duration)> {
+        let mut durations = Vec::new();
+        for peer in self.get_authenticated_peers().await {
+            let guard = peer.read().await;
+            durations.push((guard.id, guard.connected_for()));
+        }
+        durations
+    }
+
+    /// 各已认证节点的链路质量估计（尚无应答过的心跳的节点不在其中）
+    pub async fn link_qualities(&self) -> Vec<(Uuid, LinkQuality)> {
+        let mut qualities = Vec::new();
+        for peer in self.get_authenticated_peers().await {
+            let guard = peer.read().await;
+            if let Some(quality) = guard.link_quality() {
+                qualities.push((guard.id, quality));
+            }
+        }
+        qualities
+    }
+
+    /// 节点表的近似内存占用（字节），含按ID与按地址的两个索引
+    pub async fn approx_bytes(&self) -> usize {
         let peers = self.peers.read().await;
-        let total = peers.len();
-        let mut authenticated = 0;
-        let mut connecting = 0;
-        
+        let mut bytes = peers.len() * PEER_INDEX_BYTES;
         for peer in peers.values() {
-            let peer_guard = peer.read().await;
-            match peer_guard.status {
-                PeerStatus::Authenticated => authenticated += 1,
-                PeerStatus::Connecting | PeerStatus::Handshaking => connecting += 1,
-                _ => {}
+            bytes += peer.read().await.approx_bytes();
+        }
+        bytes
+    }
+
+    /// 按创建时间从早到晚淘汰未完成握手的节点（已认证节点保留），直到占用不超过 `limit` 字节，返回淘汰数
+    pub async fn evict_unauthenticated(&self, limit: usize) -> usize {
+        let mut usage = self.approx_bytes().await;
+        if usage <= limit {
+            return 0;
+        }
+        let mut pending = Vec::new();
+        for peer in self.get_all_peers().await {
+            let pg = peer.read().await;
+            if !pg.is_authenticated() {
+                pending.push((pg.id, pg.created_at, pg.approx_bytes() + PEER_INDEX_BYTES));
             }
         }
-        
-        PeerStats {
-            total_peers: total,
-            authenticated_peers: authenticated,
-            connecting_peers: connecting,
+        pending.sort_by_key(|(_, created_at, _)| *created_at);
+        let mut evicted = 0;
+        for (id, _, bytes) in pending {
+            if usage <= limit {
+                break;
+            }
+            if self.kick(&id, DisconnectReason::QuotaExceeded, "服务器内存不足，请稍后重试").await {
+                debug!("{}", tr!("内存超出软限制，淘汰未完成握手的节点 {}", "Memory above soft limit, evicting unauthenticated node {}", id));
+                usage = usage.saturating_sub(bytes);
+                evicted += 1;
+            }
         }
+        evicted
+    }
+
+    /// 重新识别节点的运营商级地址转换并更新其标记
+    ///
+    /// 共用同一公网IP的节点数随连接变化，协调直连前应重新识别。
+    pub async fn tag_carrier_nat(&self, peer: &Arc<RwLock<Peer>>) -> Option<CarrierNat> {
+        let (addr, advertised) = {
+            let guard = peer.read().await;
+            (guard.addr(), guard.advertised_addrs())
+        };
+        let sharing = self.peers_by_addr.read().await.keys().filter(|other| other.ip() == addr.ip()).count();
+        let class = carrier_nat::classify(&self.carrier_nat, addr.ip(), &advertised, sharing);
+        peer.write().await.carrier_nat = class;
+        class
+    }
+
+    /// 已认证节点按运营商级地址转换标记的统计
+    pub async fn carrier_nat_stats(&self) -> CarrierNatStats {
+        let mut stats = CarrierNatStats::default();
+        for peer in self.get_authenticated_peers().await {
+            stats.record(peer.read().await.carrier_nat);
+        }
+        stats
+    }
+
+    /// 获取连接统计信息（读取计数器，不锁定节点）
+    pub async fn get_stats(&self) -> PeerStats {
+        self.counters.snapshot()
     }
 }
 
@@ -0,0 +1,125 @@
+use std::cmp::Reverse;
+use uuid::Uuid;
+
+use crate::protocol::PeerInfo;
+use crate::reachability::{self, PeerTraits};
+
+#[derive(Debug, Clone)]
+struct Entry {
+    id: Uuid,
+    value: serde_json::Value,
+    /// 序列化后的字节数
+    len: usize,
+    traits: PeerTraits,
+    pinned: bool,
+}
+
+/// 一次广播使用的节点列表快照
+///
+/// 广播时只遍历一次节点表，并把每个条目预先序列化；为每个接收者生成列表时只剔除其自身，
+/// 不再逐个加锁重建、重新序列化整张列表，节点集中加入时可显著降低开销。
+#[derive(Debug, Clone, Default)]
+pub struct PeerListSnapshot {
+    entries: Vec<Entry>,
+}
+
+impl PeerListSnapshot {
+    pub fn new(peers: &[PeerInfo]) -> Self {
+        Self::with_traits(peers.iter().map(|peer| (peer.clone(), PeerTraits::default())))
+    }
+
+    /// 附带各节点可达性特征的快照，列表需要截断时据此挑选条目
+    pub fn with_traits(peers: impl IntoIterator<Item = (PeerInfo, PeerTraits)>) -> Self {
+        let entries = peers
+            .into_iter()
+            .filter_map(|(peer, traits)| {
+                let len = serde_json::to_vec(&peer).ok()?.len();
+                let value = serde_json::to_value(&peer).ok()?;
+                Some(Entry { id: peer.id, value, len, traits, pinned: peer.pinned })
+            })
+            .collect();
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 排除指定节点后的列表，即 `DiscoveryResponse` 的负载
+    pub fn payload_excluding(&self, exclude_id: Option<Uuid>) -> serde_json::Value {
+        self.payload_for(exclude_id, None, 0)
+    }
+
+    /// 发给节点的列表：排除其自身，序列化后不超过 `max_bytes`（0 表示不限制）
+    ///
+    /// 放不下全部节点时，固定节点优先，其余按对 `requester` 的可达性评分从高到低挑选；
+    /// 未给出 `requester` 时保持原有顺序（例如已按时延排序）。
+    pub fn payload_for(&self, exclude_id: Option<Uuid>, requester: Option<&PeerTraits>, max_bytes: usize) -> serde_json::Value {
+        let mut candidates: Vec<&Entry> = self.entries.iter().filter(|entry| Some(entry.id) != exclude_id).collect();
+        if max_bytes > 0 && encoded_len(candidates.iter().copied()) > max_bytes {
+            if let Some(requester) = requester {
+                candidates.sort_by_key(|entry| Reverse((entry.pinned, reachability::score(requester, &entry.traits))));
+            }
+            // 数组的方括号，条目之间的逗号
+            let mut used = 2;
+            candidates.retain(|entry| {
+                let cost = entry.len + usize::from(used > 2);
+                if used + cost > max_bytes {
+                    return false;
+                }
+                used += cost;
+                true
+            });
+        }
+        serde_json::Value::Array(candidates.into_iter().map(|entry| entry.value.clone()).collect())
+    }
+}
+
+/// 条目组成的 JSON 数组的字节数
+fn encoded_len<'a>(entries: impl Iterator<Item = &'a Entry>) -> usize {
+    let (count, bytes) = entries.fold((0usize, 0), |(count, bytes), entry| (count + 1, bytes + entry.len));
+    2 + bytes + count.saturating_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_excludes_recipient() {
+        let addr = "127.0.0.1:9000".parse().unwrap();
+        let peers: Vec<PeerInfo> = (0..3).map(|_| PeerInfo::new(Uuid::new_v4(), addr, Vec::new())).collect();
+        let snapshot = PeerListSnapshot::new(&peers);
+        assert_eq!(snapshot.len(), 3);
+
+        let payload = snapshot.payload_excluding(Some(peers[1].id));
+        let decoded: Vec<PeerInfo> = serde_json::from_value(payload).unwrap();
+        assert_eq!(decoded.iter().map(|p| p.id).collect::<Vec<_>>(), vec![peers[0].id, peers[2].id]);
+
+        // 与逐个构建的列表序列化结果一致
+        let full = snapshot.payload_excluding(None);
+        assert_eq!(full, serde_json::to_value(&peers).unwrap());
+        assert_eq!(serde_json::to_vec(&full).unwrap().len(), encoded_len(snapshot.entries.iter()));
+        assert!(PeerListSnapshot::default().is_empty());
+
+        // 超出预算时按可达性挑选，结果不超过预算
+        let nat = |nat_type: &str| PeerTraits { nat_type: Some(nat_type.to_string()), ..PeerTraits::default() };
+        let snapshot = PeerListSnapshot::with_traits(vec![
+            (peers[0].clone(), nat("Symmetric")),
+            (peers[1].clone(), nat("FullCone")),
+            (peers[2].clone(), nat("Restricted")),
+        ]);
+        let budget = encoded_len(snapshot.entries[..2].iter());
+        let payload = snapshot.payload_for(None, Some(&nat("Symmetric")), budget);
+        assert!(serde_json::to_vec(&payload).unwrap().len() <= budget);
+        let decoded: Vec<PeerInfo> = serde_json::from_value(payload).unwrap();
+        assert_eq!(decoded.iter().map(|p| p.id).collect::<Vec<_>>(), vec![peers[1].id, peers[2].id]);
+        // 未给出请求方时保持原有顺序
+        let decoded: Vec<PeerInfo> = serde_json::from_value(snapshot.payload_for(None, None, budget)).unwrap();
+        assert_eq!(decoded.iter().map(|p| p.id).collect::<Vec<_>>(), vec![peers[0].id, peers[1].id]);
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{Context, Result, bail};
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::network::NetworkManager;
+use crate::peer::PeerManager;
+use crate::protocol::Message;
+
+/// 服务器插件
+///
+/// 插件在启动前通过 `P2PServer::register_plugin` 注册，认领一组扩展标签；节点发来
+/// `MessageType::Extension(标签)` 的消息时，服务器把它交给认领该标签的插件处理，返回的消息
+/// 会回复给发送方（未设置 `reply_to` 时自动指向收到的消息）。只有已认证节点的扩展消息会被分发。
+pub trait Plugin: Send + Sync {
+    /// 插件名称，用于日志
+    fn name(&self) -> &str;
+
+    /// 认领的扩展标签；同一标签只能被一个插件认领
+    fn tags(&self) -> Vec<String>;
+
+    /// 服务器开始运行时调用一次，插件可保存上下文以便主动发送消息
+    fn start(&self, _context: PluginContext) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// 处理来自节点 `from` 的扩展消息
+    fn handle<'a>(&'a self, from: Uuid, message: &'a Message) -> BoxFuture<'a, Result<Option<Message>>>;
+}
+
+/// 插件发送消息所用的句柄，消息经服务器的网络管理器从主端口发出
+#[derive(Clone)]
+pub struct PluginContext {
+    network: Arc<NetworkManager>,
+    peer_manager: Arc<PeerManager>,
+}
+
+impl PluginContext {
+    pub fn new(network: Arc<NetworkManager>, peer_manager: Arc<PeerManager>) -> Self {
+        Self { network, peer_manager }
+    }
+
+    /// 发送消息给已连接的节点
+    pub async fn send_to_peer(&self, peer_id: Uuid, message: &Message) -> Result<()> {
+        let peer = self.peer_manager.get_peer(&peer_id).await.context(format!("节点 {} 不存在", peer_id))?;
+        let addr = peer.read().await.addr();
+        self.network.send_to(message, addr).await
+    }
+
+    /// 发送消息到任意地址
+    pub async fn send_to(&self, addr: SocketAddr, message: &Message) -> Result<()> {
+        self.network.send_to(message, addr).await
+    }
+
+    /// 当前已认证节点的ID
+    pub async fn authenticated_peers(&self) -> Vec<Uuid> {
+        let mut ids = Vec::new();
+        for peer in self.peer_manager.get_authenticated_peers().await {
+            ids.push(peer.read().await.id);
+        }
+        ids
+    }
+}
+
+/// 已注册的插件，按扩展标签索引
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: Vec<Arc<dyn Plugin>>,
+    by_tag: HashMap<String, Arc<dyn Plugin>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册插件；标签为空或已被其他插件认领时返回错误，不注册任何标签
+    pub fn register(&mut self, plugin: Arc<dyn Plugin>) -> Result<()> {
+        let tags = plugin.tags();
+        for tag in &tags {
+            if tag.is_empty() {
+                bail!("插件 {} 认领了空的扩展标签", plugin.name());
+            }
+            if let Some(owner) = self.by_tag.get(tag) {
+                bail!("扩展标签 {} 已被插件 {} 认领", tag, owner.name());
+            }
+        }
+        for tag in tags {
+            self.by_tag.insert(tag, plugin.clone());
+        }
+        self.plugins.push(plugin);
+        Ok(())
+    }
+
+    /// 认领该标签的插件
+    pub fn get(&self, tag: &str) -> Option<&Arc<dyn Plugin>> {
+        self.by_tag.get(tag)
+    }
+
+    /// 全部插件，按注册顺序
+    pub fn plugins(&self) -> &[Arc<dyn Plugin>] {
+        &self.plugins
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo(&'static str);
+
+    impl Plugin for Echo {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn tags(&self) -> Vec<String> {
+            vec!["acme.echo".to_string()]
+        }
+
+        fn handle<'a>(&'a self, _from: Uuid, message: &'a Message) -> BoxFuture<'a, Result<Option<Message>>> {
+            Box::pin(async move { Ok(Some(message.clone())) })
+        }
+    }
+
+    #[test]
+    fn test_tags_are_claimed_once() {
+        let mut registry = PluginRegistry::new();
+        registry.register(Arc::new(Echo("first"))).unwrap();
+        let err = registry.register(Arc::new(Echo("second"))).unwrap_err();
+        assert!(err.to_string().contains("first"));
+        assert_eq!(registry.plugins().len(), 1);
+        assert_eq!(registry.get("acme.echo").unwrap().name(), "first");
+        assert!(registry.get("acme.other").is_none());
+    }
+}
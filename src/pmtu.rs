@@ -0,0 +1,118 @@
+use crate::protocol::Message;
+
+/// 不经探测即认为可用的路径 MTU（RFC 8899 的 BASE_PLPMTU），IPv6 最小 MTU 减去 IP/UDP 头
+pub const BASE_PLPMTU: usize = 1200;
+
+/// 上下界相差不超过该字节数即结束搜索
+const SEARCH_PRECISION: usize = 16;
+
+/// 路径 MTU 搜索（DPLPMTUD，RFC 8899）
+///
+/// 从已确认的 `base` 出发，先直接探测上限 `max`，不通时在已确认大小与最小的失败大小之间二分。
+/// 同一大小连续 `max_probes` 次未得到应答才视为超过路径 MTU，单个探测包的丢失不会压低结果。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MtuSearch {
+    /// 已确认可达的最大大小
+    confirmed: usize,
+    /// 确认超过路径 MTU 的最小大小；尚无失败时为 `max + 1`
+    too_big: usize,
+    max: usize,
+    max_probes: u32,
+    /// 当前探测大小已超时的次数
+    misses: u32,
+}
+
+impl MtuSearch {
+    pub fn new(base: usize, max: usize, max_probes: u32) -> Self {
+        let max = max.max(base);
+        Self { confirmed: base, too_big: max + 1, max, max_probes: max_probes.max(1), misses: 0 }
+    }
+
+    /// 下一个要探测的大小，搜索结束时为 `None`
+    pub fn next_probe(&self) -> Option<usize> {
+        if self.too_big > self.max {
+            return (self.confirmed < self.max).then_some(self.max);
+        }
+        (self.too_big - self.confirmed > SEARCH_PRECISION).then(|| (self.confirmed + self.too_big) / 2)
+    }
+
+    /// `size` 的探测得到应答
+    pub fn acked(&mut self, size: usize) {
+        self.confirmed = self.confirmed.max(size);
+        self.misses = 0;
+    }
+
+    /// `size` 的探测超时未应答
+    pub fn timed_out(&mut self, size: usize) {
+        self.misses += 1;
+        if self.misses >= self.max_probes {
+            self.too_big = self.too_big.min(size);
+            self.misses = 0;
+        }
+    }
+
+    /// 已确认的路径 MTU
+    pub fn confirmed(&self) -> usize {
+        self.confirmed
+    }
+
+    /// 更大的探测包确实不可达时返回已确认的路径 MTU；一直探测到上限都可达时为 `None`
+    pub fn limit(&self) -> Option<usize> {
+        (self.too_big <= self.max).then_some(self.confirmed)
+    }
+}
+
+/// 用 `padding` 字段把消息填充到编码后约 `size` 字节
+pub fn pad_to(message: &mut Message, size: usize) {
+    message.payload["padding"] = serde_json::json!("");
+    let len = serde_json::to_vec(message).map_or(0, |bytes| bytes.len());
+    message.payload["padding"] = serde_json::json!("0".repeat(size.saturating_sub(len)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按给定的路径 MTU 模拟应答，返回搜索结束时的状态
+    fn run(path_mtu: usize) -> MtuSearch {
+        let mut search = MtuSearch::new(BASE_PLPMTU, 9000, 3);
+        let mut probes = 0;
+        while let Some(size) = search.next_probe() {
+            if size <= path_mtu {
+                search.acked(size);
+            } else {
+                search.timed_out(size);
+            }
+            probes += 1;
+            assert!(probes < 100, "搜索未收敛: {:?}", search);
+        }
+        search
+    }
+
+    #[test]
+    fn test_search_converges_below_path_mtu() {
+        let search = run(1472);
+        assert!(search.confirmed() <= 1472 && 1472 - search.confirmed() <= SEARCH_PRECISION, "{:?}", search);
+        assert_eq!(search.limit(), Some(search.confirmed()));
+
+        // 上限可达时一次探测即结束，没有限制
+        let open = run(65_000);
+        assert_eq!((open.confirmed(), open.limit()), (9000, None));
+    }
+
+    #[test]
+    fn test_single_loss_does_not_lower_result() {
+        let mut search = MtuSearch::new(BASE_PLPMTU, 1500, 3);
+        search.timed_out(1500);
+        assert_eq!(search.next_probe(), Some(1500));
+        search.acked(1500);
+        assert_eq!((search.next_probe(), search.limit()), (None, None));
+    }
+
+    #[test]
+    fn test_pad_to_reaches_size() {
+        let mut message = Message::new(crate::protocol::MessageType::MtuProbe, serde_json::json!({ "size": 1400 }));
+        pad_to(&mut message, 1400);
+        assert_eq!(serde_json::to_vec(&message).unwrap().len(), 1400);
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// 每个节点最多关注的节点数
+pub const MAX_WATCHED_PER_PEER: usize = 1024;
+
+#[derive(Debug, Default)]
+struct Watches {
+    /// 被关注节点 -> 关注者
+    by_target: HashMap<Uuid, HashSet<Uuid>>,
+    /// 关注者 -> 被关注节点
+    by_watcher: HashMap<Uuid, HashSet<Uuid>>,
+}
+
+/// 在线状态关注列表
+///
+/// 节点以 `Watch` 关注一组节点ID，这些节点握手成功（上线）或断开、超时、被踢出（下线）时
+/// 只向关注者推送 `Presence`，无需比对完整的节点发现列表。关注者下线时其关注列表一并清除。
+#[derive(Debug, Default)]
+pub struct WatchList {
+    watches: Mutex<Watches>,
+}
+
+impl WatchList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 关注一组节点，超过上限时不做任何修改并返回错误
+    pub fn watch(&self, watcher: Uuid, targets: &[Uuid]) -> Result<(), &'static str> {
+        let mut watches = self.watches.lock().unwrap();
+        let watching = watches.by_watcher.get(&watcher);
+        let added = targets.iter().filter(|t| **t != watcher && watching.is_none_or(|w| !w.contains(t))).collect::<HashSet<_>>().len();
+        if watching.map_or(0, HashSet::len) + added > MAX_WATCHED_PER_PEER {
+            return Err("关注的节点数超过上限");
+        }
+        for target in targets {
+            if *target == watcher {
+                continue;
+            }
+            watches.by_target.entry(*target).or_default().insert(watcher);
+            watches.by_watcher.entry(watcher).or_default().insert(*target);
+        }
+        Ok(())
+    }
+
+    /// 取消关注一组节点
+    pub fn unwatch(&self, watcher: &Uuid, targets: &[Uuid]) {
+        let mut watches = self.watches.lock().unwrap();
+        for target in targets {
+            Self::unlink(&mut watches, watcher, target);
+        }
+    }
+
+    /// 关注节点 `target` 的节点
+    pub fn watchers(&self, target: &Uuid) -> Vec<Uuid> {
+        self.watches
+            .lock()
+            .unwrap()
+            .by_target
+            .get(target)
+            .map(|watchers| watchers.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// 节点关注的节点数
+    pub fn watching(&self, watcher: &Uuid) -> usize {
+        self.watches.lock().unwrap().by_watcher.get(watcher).map_or(0, HashSet::len)
+    }
+
+    /// 清除节点的关注列表（节点下线时）
+    pub fn remove_watcher(&self, watcher: &Uuid) {
+        let mut watches = self.watches.lock().unwrap();
+        let Some(targets) = watches.by_watcher.get(watcher).cloned() else { return };
+        for target in targets {
+            Self::unlink(&mut watches, watcher, &target);
+        }
+    }
+
+    fn unlink(watches: &mut Watches, watcher: &Uuid, target: &Uuid) {
+        if let Some(watchers) = watches.by_target.get_mut(target) {
+            watchers.remove(watcher);
+            if watchers.is_empty() {
+                watches.by_target.remove(target);
+            }
+        }
+        if let Some(targets) = watches.by_watcher.get_mut(watcher) {
+            targets.remove(target);
+            if targets.is_empty() {
+                watches.by_watcher.remove(watcher);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_unwatch_and_remove_watcher() {
+        let list = WatchList::new();
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        list.watch(a, &[b, c, a]).unwrap();
+        list.watch(b, &[c]).unwrap();
+        // 不能关注自己
+        assert_eq!(list.watching(&a), 2);
+        let mut watchers = list.watchers(&c);
+        watchers.sort();
+        let mut expected = vec![a, b];
+        expected.sort();
+        assert_eq!(watchers, expected);
+
+        list.unwatch(&a, &[c]);
+        assert_eq!(list.watchers(&c), vec![b]);
+
+        list.remove_watcher(&a);
+        assert!(list.watchers(&b).is_empty());
+        assert_eq!(list.watching(&a), 0);
+
+        let many: Vec<Uuid> = (0..=MAX_WATCHED_PER_PEER).map(|_| Uuid::new_v4()).collect();
+        assert!(list.watch(c, &many).is_err());
+        assert_eq!(list.watching(&c), 0);
+    }
+}
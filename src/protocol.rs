@@ -1,93 +1,54 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::net::SocketAddr;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum MessageType {
-    /// 握手请求
-    HandshakeRequest,
-    /// 握手响应
-    HandshakeResponse,
-    /// 心跳包
-    Ping,
-    /// 心跳响应
-    Pong,
-    /// 节点发现请求
-    DiscoveryRequest,
-    /// 节点发现响应
-    DiscoveryResponse,
-    /// 请求节点列表
-    ListNodesRequest,
-    /// 响应节点列表
-    ListNodesResponse,
-    /// 数据传输
-    Data,
-    /// 错误消息
-    Error,
-    /// 断开连接
-    Disconnect,
-    /// 消息确认（UDP可靠性）
-    Ack,
-    /// 重传请求
-    Retransmit,
-    /// P2P 直连指令（NAT 打洞）
-    P2PConnect,
-    /// 流量转发请求（用于全对称NAT）
-    RelayRequest,
-    /// 流量转发响应
-    RelayResponse,
-    /// 转发的数据包
-    RelayData,
+use crate::intern::deserialize_interned_seq;
+use crate::wire::unix_secs;
+
+pub use crate::wire::{Capabilities, LoadHint, Message, MessageType, Metadata, NodeInfo};
+
+use crate::link_state::LinkStateAdvertisement;
+use crate::memory::MemoryUsage;
+use crate::metrics::MetricsSnapshot;
+use crate::topology::{TopologyFormat, TopologySnapshot};
+
+/// 协议消息构造错误
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    /// 负载无法序列化为 JSON（例如映射的键不是字符串）
+    #[error("消息负载序列化失败: {0}")]
+    Serialize(#[from] serde_json::Error),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Message {
-    pub id: Uuid,
-    pub message_type: MessageType,
-    pub timestamp: u64,
-    pub payload: serde_json::Value,
-    /// 发送者地址（UDP需要）
-    pub sender_addr: Option<SocketAddr>,
-    /// 序列号（用于UDP重传和去重）
-    pub sequence_number: Option<u32>,
-    /// 是否需要确认
-    pub requires_ack: bool,
-    /// 确认的消息ID（用于Ack消息）
-    pub ack_for: Option<Uuid>,
+/// P2P 直连最终使用的路径
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum P2PPath {
+    /// 经对端的内网监听地址（同一公网IP下的局域网直连）
+    Private,
+    /// 经对端的公网映射地址（打洞）
+    Public,
+    /// 经服务器流量转发
+    Relay,
+    /// 未能建立连接
+    Failed,
 }
 
 impl Message {
-    pub fn new(message_type: MessageType, payload: serde_json::Value) -> Self {
-        Self {
-            id: Uuid::new_v4(),
-            message_type,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            payload,
-            sender_addr: None,
-            sequence_number: None,
-            requires_ack: false,
-            ack_for: None,
-        }
-    }
-    
     /// 创建需要确认的消息
     pub fn new_with_ack(message_type: MessageType, payload: serde_json::Value, sender_addr: SocketAddr, sequence_number: u32) -> Self {
         Self {
             id: Uuid::new_v4(),
             message_type,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            timestamp: unix_secs(),
             payload,
             sender_addr: Some(sender_addr),
             sequence_number: Some(sequence_number),
             requires_ack: true,
             ack_for: None,
+            reply_to: None,
+            load: None,
         }
     }
     
@@ -96,48 +57,121 @@ impl Message {
         Self {
             id: Uuid::new_v4(),
             message_type: MessageType::Ack,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            timestamp: unix_secs(),
             payload: serde_json::Value::Null,
             sender_addr: Some(sender_addr),
             sequence_number: None,
             requires_ack: false,
             ack_for: Some(original_message_id),
+            reply_to: None,
+            load: None,
+        }
+    }
+
+    /// 创建一次确认多条消息的批量确认
+    ///
+    /// `ack_for` 仍为首个ID，只认单条确认的对端照常处理；负载 `{"ack_for": [...]}` 列出全部ID。
+    /// 只有一个ID时与 [`Message::ack`] 相同。
+    pub fn ack_batch(ids: &[Uuid], sender_addr: SocketAddr) -> Self {
+        let mut ack = Self::ack(ids.first().copied().unwrap_or_default(), sender_addr);
+        if ids.len() > 1 {
+            ack.payload = serde_json::json!({ "ack_for": ids });
         }
+        ack
+    }
+
+    /// `Ack` 确认的全部消息ID：批量确认取负载中的列表，否则为 `ack_for`
+    pub fn acked_ids(&self) -> Vec<Uuid> {
+        self.payload
+            .get("ack_for")
+            .and_then(|ids| serde_json::from_value(ids.clone()).ok())
+            .unwrap_or_else(|| self.ack_for.into_iter().collect())
     }
     
     #[allow(dead_code)]
-    pub fn handshake_request(node_info: NodeInfo) -> Self {
-        let payload = serde_json::to_value(node_info).unwrap();
-        Self::new(MessageType::HandshakeRequest, payload)
+    pub fn handshake_request(node_info: NodeInfo) -> Result<Self, ProtocolError> {
+        let payload = serde_json::to_value(node_info)?;
+        Ok(Self::new(MessageType::HandshakeRequest, payload))
     }
     
     #[allow(dead_code)]
-    pub fn handshake_response(node_info: NodeInfo, success: bool) -> Self {
+    pub fn handshake_response(node_info: NodeInfo, success: bool) -> Result<Self, ProtocolError> {
         let response = HandshakeResponse {
             node_info,
             success,
             error_message: None,
             public_addr: None,
+            retry_after_secs: None,
+            session_ticket: None,
+            stored_messages: None,
+            signature: None,
         };
-        let payload = serde_json::to_value(response).unwrap();
-        Self::new(MessageType::HandshakeResponse, payload)
+        let payload = serde_json::to_value(response)?;
+        Ok(Self::new(MessageType::HandshakeResponse, payload))
     }
 
     /// 创建包含公网地址的握手响应
-    pub fn handshake_response_with_public_addr(node_info: NodeInfo, success: bool, public_addr: SocketAddr) -> Self {
+    pub fn handshake_response_with_public_addr(node_info: NodeInfo, success: bool, public_addr: SocketAddr) -> Result<Self, ProtocolError> {
         let response = HandshakeResponse {
             node_info,
             success,
             error_message: None,
             public_addr: Some(public_addr),
+            retry_after_secs: None,
+            session_ticket: None,
+            stored_messages: None,
+            signature: None,
+        };
+        let payload = serde_json::to_value(response)?;
+        Ok(Self::new(MessageType::HandshakeResponse, payload))
+    }
+
+    /// 创建握手成功的响应，附带公网地址、会话票据与待投递的离线消息数
+    pub fn handshake_accepted(
+        node_info: NodeInfo,
+        public_addr: SocketAddr,
+        session_ticket: String,
+        stored_messages: Option<usize>,
+    ) -> Result<Self, ProtocolError> {
+        let response = HandshakeResponse {
+            node_info,
+            success: true,
+            error_message: None,
+            public_addr: Some(public_addr),
+            retry_after_secs: None,
+            session_ticket: Some(session_ticket),
+            stored_messages,
+            signature: None,
         };
-        let payload = serde_json::to_value(response).unwrap();
-        Self::new(MessageType::HandshakeResponse, payload)
+        let payload = serde_json::to_value(response)?;
+        Ok(Self::new(MessageType::HandshakeResponse, payload))
+    }
+
+    /// 创建拒绝握手的响应，提示客户端在 `retry_after_secs` 秒后重试
+    pub fn handshake_rejected(node_info: NodeInfo, reason: String, retry_after_secs: u64) -> Result<Self, ProtocolError> {
+        let response = HandshakeResponse {
+            node_info,
+            success: false,
+            error_message: Some(reason),
+            public_addr: None,
+            retry_after_secs: Some(retry_after_secs),
+            session_ticket: None,
+            stored_messages: None,
+            signature: None,
+        };
+        let payload = serde_json::to_value(response)?;
+        Ok(Self::new(MessageType::HandshakeResponse, payload))
     }
     
+    /// 要求客户端带回 cookie 重发握手请求的质询
+    ///
+    /// 只含 cookie、不带节点信息与签名：服务器对每个（可能伪造来源的）握手请求只做一次 HMAC，应答也不大于请求。
+    pub fn handshake_cookie(request_id: Uuid, cookie: String) -> Self {
+        let mut challenge = Self::new(MessageType::HandshakeResponse, serde_json::json!({ "success": false, "cookie": cookie }));
+        challenge.reply_to = Some(request_id);
+        challenge
+    }
+
     pub fn ping() -> Self {
         Self::new(MessageType::Ping, serde_json::Value::Null)
     }
@@ -145,21 +179,55 @@ impl Message {
     pub fn pong() -> Self {
         Self::new(MessageType::Pong, serde_json::Value::Null)
     }
+
+    /// 附带到直连节点往返时延的心跳响应
+    pub fn pong_with_rtts(rtts: Vec<RttSample>) -> Self {
+        if rtts.is_empty() {
+            return Self::pong();
+        }
+        Self::new(MessageType::Pong, serde_json::json!({ "rtts": rtts }))
+    }
     
     #[allow(dead_code)]
     pub fn discovery_request() -> Self {
         Self::new(MessageType::DiscoveryRequest, serde_json::Value::Null)
     }
+
+    /// 请求按到本节点的往返时延排序的节点列表，`limit` 限制返回数量
+    pub fn discovery_request_by_latency(limit: Option<usize>) -> Self {
+        let mut payload = serde_json::json!({ "order": "latency" });
+        if let Some(limit) = limit {
+            payload["limit"] = serde_json::json!(limit);
+        }
+        Self::new(MessageType::DiscoveryRequest, payload)
+    }
     
-    pub fn discovery_response(peers: Vec<PeerInfo>) -> Self {
-        let payload = serde_json::to_value(peers).unwrap();
-        Self::new(MessageType::DiscoveryResponse, payload)
+    pub fn discovery_response(peers: Vec<PeerInfo>) -> Result<Self, ProtocolError> {
+        let payload = serde_json::to_value(peers)?;
+        Ok(Self::new(MessageType::DiscoveryResponse, payload))
     }
     
+    /// 创建扩展消息，交给服务器上认领 `tag` 的插件处理
+    pub fn extension(tag: impl Into<String>, payload: serde_json::Value) -> Self {
+        Self::new(MessageType::Extension(tag.into()), payload)
+    }
+
     pub fn data(data: serde_json::Value) -> Self {
         Self::new(MessageType::Data, data)
     }
     
+    /// 对本消息的应答：`Data` 消息，`reply_to` 指向本消息ID
+    pub fn respond(&self, payload: serde_json::Value) -> Self {
+        self.respond_as(MessageType::Data, payload)
+    }
+    
+    /// 以指定类型应答本消息
+    pub fn respond_as(&self, message_type: MessageType, payload: serde_json::Value) -> Self {
+        let mut reply = Self::new(message_type, payload);
+        reply.reply_to = Some(self.id);
+        reply
+    }
+    
     pub fn error(error_message: String) -> Self {
         let payload = serde_json::json!({ "error": error_message });
         Self::new(MessageType::Error, payload)
@@ -170,15 +238,21 @@ impl Message {
         Self::new(MessageType::Disconnect, payload)
     }
 
+    /// 创建服务器发出的断开通知，`code` 供节点判断是否重连
+    pub fn disconnect_with_code(code: DisconnectReason, reason: String) -> Self {
+        let payload = serde_json::json!({ "code": code, "reason": reason });
+        Self::new(MessageType::Disconnect, payload)
+    }
+
     #[allow(dead_code)]
     pub fn list_nodes_request() -> Self {
         Self::new(MessageType::ListNodesRequest, serde_json::Value::Null)
     }
 
-    pub fn list_nodes_response(nodes: Vec<NodeInfo>) -> Self {
+    pub fn list_nodes_response(nodes: Vec<NodeInfo>) -> Result<Self, ProtocolError> {
         let response = ListNodesResponse { nodes };
-        let payload = serde_json::to_value(response).unwrap();
-        Self::new(MessageType::ListNodesResponse, payload)
+        let payload = serde_json::to_value(response)?;
+        Ok(Self::new(MessageType::ListNodesResponse, payload))
     }
 
     /// 发起 P2P 直连请求（由服务器协调打洞）
@@ -213,20 +287,191 @@ impl Message {
         Self::new(MessageType::P2PConnect, payload)
     }
 
+    /// 从新地址请求迁移会话
+    pub fn migrate_address(node_id: Uuid, session_ticket: String) -> Result<Self, ProtocolError> {
+        let request = MigrateAddressRequest { node_id, session_ticket };
+        Ok(Self::new(MessageType::MigrateAddress, serde_json::to_value(request)?))
+    }
+
+    /// 地址迁移成功的应答
+    pub fn migrate_address_response(public_addr: SocketAddr) -> Result<Self, ProtocolError> {
+        let response = MigrateAddressResponse { success: true, public_addr };
+        Ok(Self::new(MessageType::MigrateAddress, serde_json::to_value(response)?))
+    }
+
+    /// 请求服务器对本节点的连接做一次诊断
+    pub fn diagnose() -> Self {
+        Self::new(MessageType::Diagnose, serde_json::Value::Null)
+    }
+
+    /// 创建填充到约 `probe.size` 字节的路径 MTU 探测包
+    pub fn mtu_probe(probe: &MtuProbe) -> Result<Self, ProtocolError> {
+        let mut message = Self::new(MessageType::MtuProbe, serde_json::to_value(probe)?);
+        crate::pmtu::pad_to(&mut message, probe.size);
+        Ok(message)
+    }
+
+    /// 创建休眠登记请求，服务器唤醒本节点时发送携带 `token` 的唤醒数据包
+    pub fn dormant(token: u64) -> Self {
+        Self::new(MessageType::Dormant, serde_json::json!({ "token": token }))
+    }
+
+    /// 创建节点密钥轮换消息（节点发给服务器、服务器转发给其他节点时共用）
+    pub fn key_rotation(rotation: &KeyRotation) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::KeyRotation, serde_json::to_value(rotation)?))
+    }
+
+    /// 创建节点地址变更通知
+    pub fn address_update(update: AddressUpdate) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::AddressUpdate, serde_json::to_value(update)?))
+    }
+
+    /// 创建打洞倒计时信标
+    pub fn punch_beacon(beacon: &PunchBeacon) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::PunchBeacon, serde_json::to_value(beacon)?))
+    }
+
+    /// 向服务器提交本节点的公网 TCP 端点，请求与 `peer_id` 进行 TCP 打洞（也用于应答对方的提议）
+    pub fn tcp_punch(peer_id: Uuid, tcp_addr: SocketAddr) -> Result<Self, ProtocolError> {
+        let request = TcpPunch { peer_id, tcp_addr: Some(tcp_addr), peer_tcp_addr: None, connect_at_ms: None, connect_in_ms: None };
+        Ok(Self::new(MessageType::TcpPunch, serde_json::to_value(request)?))
+    }
+
+    /// 创建节点下线通知
+    pub fn peer_down(peer_id: Uuid) -> Self {
+        Self::new(MessageType::PeerDown, serde_json::json!({ "peer_id": peer_id.to_string() }))
+    }
+
+    /// 注册本节点提供的服务
+    pub fn register_services(services: Vec<String>) -> Result<Self, ProtocolError> {
+        let registration = ServiceRegistration { services };
+        Ok(Self::new(MessageType::RegisterService, serde_json::to_value(registration)?))
+    }
+
+    /// 注销本节点提供的服务
+    pub fn unregister_services(services: Vec<String>) -> Result<Self, ProtocolError> {
+        let registration = ServiceRegistration { services };
+        Ok(Self::new(MessageType::UnregisterService, serde_json::to_value(registration)?))
+    }
+
+    /// 查询服务的提供者
+    pub fn service_lookup(service: &str) -> Self {
+        Self::new(MessageType::ServiceLookup, serde_json::json!({ "service": service }))
+    }
+
+    /// 创建远程调用
+    pub fn rpc_call(call: &RpcCall) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::RpcCall, serde_json::to_value(call)?))
+    }
+
+    /// 远程调用 `call_id` 的结果
+    pub fn rpc_result(call_id: Uuid, result: Result<serde_json::Value, String>) -> Result<Self, ProtocolError> {
+        let result = match result {
+            Ok(value) => RpcResult { result: value, error: None },
+            Err(error) => RpcResult { result: serde_json::Value::Null, error: Some(error) },
+        };
+        let mut message = Self::new(MessageType::RpcResult, serde_json::to_value(result)?);
+        message.reply_to = Some(call_id);
+        Ok(message)
+    }
+
+    /// 关注一组节点的在线状态
+    pub fn watch(peer_ids: Vec<Uuid>) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::Watch, serde_json::to_value(WatchRequest { peer_ids })?))
+    }
+
+    /// 取消关注一组节点
+    pub fn unwatch(peer_ids: Vec<Uuid>) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::Unwatch, serde_json::to_value(WatchRequest { peer_ids })?))
+    }
+
+    /// 创建在线状态通知
+    pub fn presence(peer_id: Uuid, online: bool) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::Presence, serde_json::to_value(PresenceUpdate { peer_id, online })?))
+    }
+
+    /// 可靠字节流的帧
+    pub fn stream_frame(frame: &StreamFrame) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::Stream, serde_json::to_value(frame)?))
+    }
+
+    /// 请求服务器协调一次本节点到 `peer_id` 的带宽探测（双方须已直连）
+    pub fn bandwidth_probe_request(peer_id: Uuid) -> Self {
+        Self::new(MessageType::BandwidthProbe, serde_json::json!({ "peer_id": peer_id.to_string() }))
+    }
+
+    /// 服务器下发的带宽探测指令
+    pub fn bandwidth_probe(probe: &BandwidthProbe) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::BandwidthProbe, serde_json::to_value(probe)?))
+    }
+
+    /// 带宽探测结果
+    pub fn bandwidth_report(report: &BandwidthReport) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::BandwidthReport, serde_json::to_value(report)?))
+    }
+
+    /// 请求服务器探测本端 NAT 映射的空闲超时
+    pub fn keepalive_probe_request() -> Self {
+        Self::new(MessageType::KeepaliveProbe, serde_json::json!({}))
+    }
+
+    /// 探测包（服务器发出）或其回显（客户端发出）
+    pub fn keepalive_probe(delay_secs: u64) -> Self {
+        Self::new(MessageType::KeepaliveProbe, serde_json::json!({ "delay_secs": delay_secs }))
+    }
+
+    /// 探测结果：建议的保活间隔
+    pub fn keepalive_result(recommended_secs: u64) -> Self {
+        Self::new(MessageType::KeepaliveProbe, serde_json::json!({ "recommended_keepalive_secs": recommended_secs }))
+    }
+
+    /// 时间同步请求，`offset_ms` 为客户端根据上一次交换算得的时钟偏差
+    pub fn time_sync_request(client_send_ms: u64, offset_ms: Option<i64>) -> Self {
+        let mut payload = serde_json::json!({ "client_send_ms": client_send_ms });
+        if let Some(offset_ms) = offset_ms {
+            payload["offset_ms"] = serde_json::json!(offset_ms);
+        }
+        Self::new(MessageType::TimeSync, payload)
+    }
+
+    /// 时间同步响应：回传客户端发送时间以及服务器收到、发出请求的时间（毫秒）
+    pub fn time_sync_response(client_send_ms: u64, server_receive_ms: u64, server_send_ms: u64) -> Self {
+        Self::new(MessageType::TimeSync, serde_json::json!({
+            "client_send_ms": client_send_ms,
+            "server_receive_ms": server_receive_ms,
+            "server_send_ms": server_send_ms,
+        }))
+    }
+
+    /// 上报与指定节点的直连结果
+    pub fn p2p_connect_result(peer_id: Uuid, path: P2PPath) -> Self {
+        let payload = serde_json::json!({ "peer_id": peer_id.to_string(), "path": path });
+        Self::new(MessageType::P2PConnectResult, payload)
+    }
+
     /// 创建流量转发请求
     #[allow(dead_code)]
     pub fn relay_request(target_peer_id: Uuid, data: Vec<u8>) -> Self {
-        let mut payload = serde_json::Map::new();
-        payload.insert("target_peer_id".to_string(), serde_json::Value::String(target_peer_id.to_string()));
-        payload.insert("data".to_string(), serde_json::Value::Array(data.into_iter().map(|b| serde_json::Value::Number(serde_json::Number::from(b))).collect()));
-        
-        Self::new(MessageType::RelayRequest, serde_json::Value::Object(payload))
+        let payload = serde_json::json!({
+            "target_peer_id": target_peer_id.to_string(),
+            "data": base64::engine::general_purpose::STANDARD.encode(data),
+        });
+        Self::new(MessageType::RelayRequest, payload)
+    }
+
+    /// 创建只建立中继会话、不携带数据的转发请求
+    pub fn relay_session_request(target_peer_id: Uuid) -> Self {
+        let payload = serde_json::json!({ "target_peer_id": target_peer_id.to_string() });
+        Self::new(MessageType::RelayRequest, payload)
     }
 
-    /// 创建流量转发响应
-    pub fn relay_response(success: bool, error_message: Option<String>) -> Self {
+    /// 创建流量转发响应；已建立中继会话时附上会话ID
+    pub fn relay_response(success: bool, session_id: Option<Uuid>, error_message: Option<String>) -> Self {
         let mut payload = serde_json::Map::new();
         payload.insert("success".to_string(), serde_json::Value::Bool(success));
+        if let Some(session_id) = session_id {
+            payload.insert("session_id".to_string(), serde_json::Value::String(session_id.to_string()));
+        }
         if let Some(error) = error_message {
             payload.insert("error_message".to_string(), serde_json::Value::String(error));
         }
@@ -235,53 +480,128 @@ impl Message {
     }
 
     /// 创建转发的数据包
-    pub fn relay_data(from_peer_id: Uuid, data: Vec<u8>) -> Self {
+    pub fn relay_data(from_peer_id: Uuid, session_id: Option<Uuid>, data: Vec<u8>) -> Self {
         let mut payload = serde_json::Map::new();
         payload.insert("from_peer_id".to_string(), serde_json::Value::String(from_peer_id.to_string()));
+        if let Some(session_id) = session_id {
+            payload.insert("session_id".to_string(), serde_json::Value::String(session_id.to_string()));
+        }
         payload.insert("data".to_string(), serde_json::Value::Array(data.into_iter().map(|b| serde_json::Value::Number(serde_json::Number::from(b))).collect()));
         
         Self::new(MessageType::RelayData, serde_json::Value::Object(payload))
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NodeInfo {
-    pub id: Uuid,
-    pub name: String,
-    pub version: String,
-    pub listen_addr: SocketAddr,
-    pub capabilities: Vec<String>,
-    pub metadata: HashMap<String, String>,
-    pub network_id: String, // 新增 network_id 字段
-}
+    /// 创建经已建立的中继会话发送的数据包（客户端发往服务器）；支持二进制帧时应改用 [`RelayFrame`]
+    pub fn relay_session_data(session_id: Uuid, data: Vec<u8>) -> Self {
+        let payload = serde_json::json!({
+            "session_id": session_id.to_string(),
+            "data": base64::engine::general_purpose::STANDARD.encode(data),
+        });
+        Self::new(MessageType::RelayData, payload)
+    }
 
-impl NodeInfo {
-    pub fn new(name: String, listen_addr: SocketAddr, network_id: String) -> Self {
-        Self {
-            id: Uuid::new_v4(),
-            name,
-            version: env!("CARGO_PKG_VERSION").to_string(),
-            listen_addr,
-            capabilities: vec![
-                "handshake".to_string(),
-                "discovery".to_string(),
-                "data_transfer".to_string(),
-            ],
-            metadata: HashMap::new(),
-            network_id,
-        }
+    /// 请求关闭中继会话
+    pub fn relay_close(session_id: Uuid) -> Self {
+        Self::new(MessageType::RelayClose, serde_json::json!({ "session_id": session_id.to_string() }))
     }
-    
-    #[allow(dead_code)]
-    pub fn add_capability(&mut self, capability: String) {
-        if !self.capabilities.contains(&capability) {
-            self.capabilities.push(capability);
-        }
+
+    /// 中继会话已关闭的通知，`peer_id` 为会话的另一方
+    pub fn relay_closed(session_id: Uuid, peer_id: Uuid, reason: RelayCloseReason) -> Self {
+        let payload = serde_json::json!({ "session_id": session_id.to_string(), "peer_id": peer_id.to_string(), "reason": reason });
+        Self::new(MessageType::RelayClose, payload)
     }
-    
-    #[allow(dead_code)]
-    pub fn add_metadata(&mut self, key: String, value: String) {
-        self.metadata.insert(key, value);
+
+    /// 中继会话已建立的响应，附上会话ID与目标节点
+    pub fn relay_session_opened(session_id: Uuid, target_peer_id: Uuid) -> Self {
+        let mut message = Self::relay_response(true, Some(session_id), None);
+        message.payload["target_peer_id"] = serde_json::Value::String(target_peer_id.to_string());
+        message
+    }
+
+    /// 创建链路状态通告消息
+    pub fn link_state_update(lsa: &LinkStateAdvertisement) -> Result<Self, ProtocolError> {
+        let payload = serde_json::to_value(lsa)?;
+        Ok(Self::new(MessageType::LinkStateUpdate, payload))
+    }
+
+    /// 创建重连提示；`session_preserved` 为真时客户端无需重新握手
+    pub fn reconnect(server_addr: SocketAddr, session_preserved: bool, reason: String) -> Self {
+        let payload = serde_json::json!({
+            "server_addr": server_addr.to_string(),
+            "session_preserved": session_preserved,
+            "reason": reason,
+        });
+        Self::new(MessageType::Reconnect, payload)
+    }
+
+    /// 创建计划维护公告
+    pub fn maintenance_notice(notice: &MaintenanceNotice) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::MaintenanceNotice, serde_json::to_value(notice)?))
+    }
+
+    /// 创建控制查询请求（`GetRoutesRequest`、`GetStatsRequest`、`GetPeersRequest` 或 `GetConfigRequest`）
+    pub fn control_request(message_type: MessageType) -> Self {
+        Self::new(message_type, serde_json::Value::Null)
+    }
+
+    /// 创建路由表响应
+    pub fn get_routes_response(response: &GetRoutesResponse) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::GetRoutesResponse, serde_json::to_value(response)?))
+    }
+
+    /// 创建运行统计响应
+    pub fn get_stats_response(response: &GetStatsResponse) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::GetStatsResponse, serde_json::to_value(response)?))
+    }
+
+    /// 创建节点查询响应
+    pub fn get_peers_response(response: &GetPeersResponse) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::GetPeersResponse, serde_json::to_value(response)?))
+    }
+
+    /// 创建生效配置响应
+    pub fn get_config_response(response: &GetConfigResponse) -> Result<Self, ProtocolError> {
+        Ok(Self::new(MessageType::GetConfigResponse, serde_json::to_value(response)?))
+    }
+
+    /// 创建主题订阅请求
+    pub fn subscribe(topic: &str) -> Self {
+        Self::new(MessageType::Subscribe, serde_json::json!({ "topic": topic }))
+    }
+
+    /// 创建取消订阅请求
+    pub fn unsubscribe(topic: &str) -> Self {
+        Self::new(MessageType::Unsubscribe, serde_json::json!({ "topic": topic }))
+    }
+
+    /// 创建主题发布消息
+    pub fn publish(topic: &str, data: serde_json::Value) -> Self {
+        Self::new(MessageType::Publish, serde_json::json!({ "topic": topic, "data": data }))
+    }
+
+    /// 创建投递给订阅者的主题消息；`from` 为 `None` 表示来自外部桥接
+    pub fn publish_delivery(topic: &str, from: Option<Uuid>, data: serde_json::Value) -> Self {
+        let payload = serde_json::json!({
+            "topic": topic,
+            "from": from.map(|id| id.to_string()),
+            "data": data,
+        });
+        Self::new(MessageType::Publish, payload)
+    }
+
+    /// 创建拓扑导出响应，DOT格式时以字符串形式携带
+    pub fn topology_response(format: TopologyFormat, snapshot: &TopologySnapshot) -> Self {
+        let payload = match format {
+            TopologyFormat::Json => serde_json::json!({
+                "format": format,
+                "topology": snapshot,
+            }),
+            TopologyFormat::Dot => serde_json::json!({
+                "format": format,
+                "dot": snapshot.to_dot(),
+            }),
+        };
+        Self::new(MessageType::TopologyResponse, payload)
     }
 }
 
@@ -292,6 +612,417 @@ pub struct HandshakeResponse {
     pub error_message: Option<String>,
     /// 客户端的公网地址（服务器看到的地址）
     pub public_addr: Option<SocketAddr>,
+    /// 握手被拒绝时建议的重试等待秒数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+    /// 会话票据：节点切换网络后凭此从新地址迁移会话（`MigrateAddress`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_ticket: Option<String>,
+    /// 服务器为该节点暂存、将在握手后投递的离线消息数（启用离线消息暂存时）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_messages: Option<usize>,
+    /// 服务器身份签名，客户端据此核对服务器公钥指纹（见 `identity` 模块）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ServerSignature>,
+}
+
+/// 握手响应上的服务器签名
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerSignature {
+    /// 服务器的 Ed25519 公钥（十六进制）
+    pub public_key: String,
+    /// 对握手请求ID与其余响应负载的签名（十六进制）
+    pub signature: String,
+}
+
+/// 节点密钥轮换：旧私钥对节点ID与新公钥签名，证明新密钥由原持有者发布
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub node_id: Uuid,
+    /// 被替换的公钥（十六进制）
+    pub old_public_key: String,
+    /// 新公钥（十六进制）
+    pub new_public_key: String,
+    /// 旧私钥的签名（十六进制）
+    pub signature: String,
+}
+
+/// 地址迁移请求：从新地址证明自己是已认证的节点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrateAddressRequest {
+    pub node_id: Uuid,
+    /// 握手响应中下发的会话票据
+    pub session_ticket: String,
+}
+
+/// 地址迁移结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrateAddressResponse {
+    pub success: bool,
+    /// 服务器看到的新地址
+    pub public_addr: SocketAddr,
+}
+
+/// 节点地址变更通知
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressUpdate {
+    pub peer_id: Uuid,
+    /// 服务器看到的新地址
+    pub peer_addr: SocketAddr,
+    /// 节点通告的本地地址
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peer_addresses: Vec<SocketAddr>,
+}
+
+/// 打洞倒计时信标：双方在收到 `go_in_ms` 为 0 的信标（GO）时同时开始发送探测
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PunchBeacon {
+    /// 打洞的对方
+    pub peer_id: Uuid,
+    /// 距 GO 的毫秒数
+    pub go_in_ms: u64,
+}
+
+/// TCP 同时打开协调
+///
+/// 节点发给服务器时 `peer_id` 为对方、`tcp_addr` 为本节点的公网 TCP 端点；服务器转交的提议只带
+/// `peer_tcp_addr`，最终计划还带有统一的连接时间。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TcpPunch {
+    pub peer_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp_addr: Option<SocketAddr>,
+    /// 对方的公网 TCP 端点
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_tcp_addr: Option<SocketAddr>,
+    /// 双方同时发起连接的时间（Unix 毫秒，已换算为接收方的时钟）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_at_ms: Option<u64>,
+    /// 距连接时间的毫秒数（未做时间同步的节点按收到时刻计算）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_in_ms: Option<u64>,
+}
+
+/// 服务器到节点最近若干次心跳的统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PingSummary {
+    /// 统计范围内发出的心跳数
+    pub sent: u32,
+    /// 收到 Pong 的心跳数
+    pub answered: u32,
+    /// 超时未应答的心跳数（仍在等待的不计入）
+    pub lost: u32,
+    /// 丢失率：`lost / (answered + lost)`
+    pub loss_rate: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_avg_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_min_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_max_ms: Option<f64>,
+}
+
+/// 服务器按心跳估计的到节点链路质量
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkQuality {
+    /// 平滑往返时延（毫秒）
+    pub srtt_ms: f64,
+    /// 往返时延的平均偏差（毫秒）
+    pub rttvar_ms: f64,
+    /// 相邻往返时延之差的平滑值（毫秒）
+    pub jitter_ms: f64,
+    /// 平滑丢失率（0–1）
+    pub loss_rate: f64,
+    /// 据此得出的重传超时（毫秒）
+    pub rto_ms: u64,
+}
+
+/// 节点在控制查询频率限制中的当前状况
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitStanding {
+    /// 每分钟允许的控制查询数，0 表示不限制
+    pub control_per_minute: u32,
+    /// 当前一分钟窗口内已发起的控制查询数
+    pub control_used: u32,
+    /// 当前窗口内剩余的查询数，不受限制时为 `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control_remaining: Option<u32>,
+}
+
+/// `Diagnose` 的应答：服务器视角下本节点连接的状况
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiagnoseReport {
+    /// 服务器看到的本节点地址
+    pub observed_addr: SocketAddr,
+    /// 节点上报的NAT类型
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nat_type: Option<String>,
+    /// 服务器识别出的运营商级地址转换
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub carrier_nat: Option<CarrierNat>,
+    /// 已连接的秒数
+    pub connected_secs: u64,
+    /// 最近心跳的往返时延与丢失率
+    pub pings: PingSummary,
+    /// 平滑后的链路质量估计，尚无应答过的心跳时省略
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkQuality>,
+    /// 服务器到本节点的路径 MTU，未探测时省略
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_mtu: Option<PathMtu>,
+    /// 服务器是否允许中继
+    pub relay_allowed: bool,
+    pub rate_limit: RateLimitStanding,
+}
+
+/// 关注/取消关注请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchRequest {
+    pub peer_ids: Vec<Uuid>,
+}
+
+/// 关注应答：所关注的节点中当前在线的节点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchResponse {
+    pub online: Vec<Uuid>,
+}
+
+/// 在线状态通知
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceUpdate {
+    pub peer_id: Uuid,
+    pub online: bool,
+}
+
+/// 端到端送达回执
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryReceipt {
+    /// 被确认的路由消息
+    pub route_id: Uuid,
+    /// 收到消息的目标节点
+    pub destination: Uuid,
+}
+
+/// 服务器拒绝路由消息的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteRejectReason {
+    /// 负载超过服务器的 `routing.max_routed_payload_bytes`
+    PayloadTooLarge,
+}
+
+/// 服务器拒绝转发路由消息时发回的 `Error` 负载，`reply_to` 指向被拒绝的原始消息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteRejection {
+    /// 可读的错误说明
+    pub error: String,
+    pub code: RouteRejectReason,
+    /// 被拒绝的路由消息
+    pub route_id: Uuid,
+    /// 负载的字节数
+    pub size: usize,
+    /// 服务器允许的上限
+    pub max_bytes: usize,
+}
+
+/// 可靠字节流的帧类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamFrameKind {
+    /// 打开流，对方以 `Ack` 确认
+    Open,
+    /// 一个数据块
+    Data,
+    /// 累计确认：`seq` 之前的帧均已收到，`window` 为接收方还能缓存的帧数
+    Ack,
+    /// 发送方的数据已全部发出，占用一个序号
+    Fin,
+    /// 中止流
+    Reset,
+}
+
+/// 可靠字节流的帧
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamFrame {
+    pub stream_id: Uuid,
+    pub kind: StreamFrameKind,
+    #[serde(default)]
+    pub seq: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub data: Vec<u8>,
+    #[serde(default)]
+    pub window: u32,
+    /// 选择性确认位图（仅 `Ack`）：第 i 位表示序号 `seq + 1 + i` 的帧已先行收到；为 0 时不发送，
+    /// 不认识该字段的旧版本按累计确认处理
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub sack: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// 节点测得的到某个直连节点的往返时延
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RttSample {
+    pub peer_id: Uuid,
+    pub rtt_us: u64,
+}
+
+/// 带宽探测中节点的角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeRole {
+    /// 向对方连续发送探测包
+    Send,
+    /// 接收探测包并上报结果
+    Receive,
+}
+
+/// 路径 MTU 探测
+///
+/// 探测包用 `padding` 填充到 `size` 字节；收到方以不带填充的 `MtuProbe` 应答，`reply_to` 指向探测包。
+/// 服务器发出的探测包附带到目前为止确认的路径 MTU，搜索结束后再发一个 `done` 的小包告知最终结果。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MtuProbe {
+    /// 探测包的大小（字节）
+    pub size: usize,
+    /// 发送方已确认的路径 MTU
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_mtu: Option<usize>,
+    /// 搜索已结束，`path_mtu` 为最终结果
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub done: bool,
+    /// 更大的探测包确实不可达，`path_mtu` 是路径的真实上限而非探测上限
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub limited: bool,
+}
+
+/// 服务器下发的带宽探测指令
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthProbe {
+    pub probe_id: Uuid,
+    /// 探测的另一方
+    pub peer_id: Uuid,
+    pub role: ProbeRole,
+    pub packets: u32,
+    pub packet_size: usize,
+}
+
+/// 带宽探测结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthReport {
+    pub probe_id: Uuid,
+    pub sender: Uuid,
+    pub receiver: Uuid,
+    /// 收到的探测包数
+    pub received: u32,
+    /// 估算的带宽（bit/s），收到的包不足以估算时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bandwidth_bps: Option<u64>,
+}
+
+/// 服务注册/注销请求，应答中列出实际生效的服务名
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceRegistration {
+    pub services: Vec<String>,
+}
+
+/// 服务提供者查询结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceLookupResponse {
+    pub service: String,
+    /// 按注册顺序排列的提供者节点ID
+    pub providers: Vec<Uuid>,
+}
+
+/// 远程调用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcCall {
+    /// 服务名，如 `storage.get`
+    pub service: String,
+    /// 指定提供者；为空时由服务器在提供者之间轮询
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Uuid>,
+    /// 调用方节点ID（服务器转发时填写）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<Uuid>,
+    #[serde(default)]
+    pub args: serde_json::Value,
+    /// 调用超时（毫秒），服务器超时后不再转发结果
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+/// 远程调用结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcResult {
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub result: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 服务器识别出的运营商级地址转换
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CarrierNat {
+    /// 运营商级 NAT：地址位于 100.64.0.0/10 共享地址空间，或大量节点共用同一公网IP
+    Cgnat,
+    /// 经 NAT64 访问 IPv4 网络的纯 IPv6 节点
+    Nat64,
+}
+
+/// 服务器断开节点的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectReason {
+    /// 服务器关闭
+    ServerShutdown,
+    /// 超时未响应心跳
+    IdleTimeout,
+    /// 被运维踢出
+    Kicked,
+    /// 超出服务器的配额限制
+    QuotaExceeded,
+    /// 违反协议（例如反复发送导致处理失败的数据包）
+    ProtocolViolation,
+    /// 服务器维护排空
+    Drain,
+}
+
+impl DisconnectReason {
+    /// 节点是否值得稍后重连；被踢出或违反协议时重连大概率仍会被断开
+    pub fn should_retry(self) -> bool {
+        !matches!(self, Self::Kicked | Self::ProtocolViolation)
+    }
+}
+
+/// `Disconnect` 的负载
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisconnectNotice {
+    /// 服务器给出的结构化原因，节点主动断开时没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<DisconnectReason>,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// 计划维护公告
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceNotice {
+    /// 维护开始时间（Unix 秒，服务器时钟）
+    pub start_time: u64,
+    /// 预计持续时长（秒）
+    pub duration_secs: u64,
+    /// 维护期间可改连的服务器
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alternative_server: Option<SocketAddr>,
+    #[serde(default)]
+    pub reason: String,
+    /// 开始时服务器是否排空（拒绝新握手并提示现有节点改连）
+    #[serde(default)]
+    pub drain: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -299,33 +1030,117 @@ pub struct ListNodesResponse {
     pub nodes: Vec<NodeInfo>,
 }
 
+/// 路由表条目
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteEntry {
+    pub destination: Uuid,
+    pub next_hop: Uuid,
+    pub distance: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetRoutesResponse {
+    pub routes: Vec<RouteEntry>,
+}
+
+/// 各状态的节点数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerCounts {
+    pub total: usize,
+    pub authenticated: usize,
+    pub connecting: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetStatsResponse {
+    /// 服务器节点ID
+    pub node_id: Uuid,
+    pub peers: PeerCounts,
+    pub metrics: MetricsSnapshot,
+    /// 各部分的近似内存占用（字节）
+    #[serde(default)]
+    pub memory: MemoryUsage,
+}
+
+/// 节点摘要（控制查询与管理接口输出）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerSummary {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub addr: SocketAddr,
+    pub status: String,
+    pub nat_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub carrier_nat: Option<CarrierNat>,
+    pub connected_secs: u64,
+    pub last_ping_secs_ago: Option<u64>,
+    /// 服务器按心跳估计的链路质量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkQuality>,
+    /// 时间同步得到的时钟偏差（节点时间 − 服务器时间，毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_offset_ms: Option<i64>,
+    /// 服务器到该节点的路径 MTU 探测结果
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_mtu: Option<PathMtu>,
+}
+
+/// 一条路径的 MTU 探测结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathMtu {
+    /// 已确认可达的最大数据报（字节）
+    pub mtu: usize,
+    /// 更大的探测包确实不可达；为 `false` 时只是探测到了配置的上限
+    pub limited: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPeersResponse {
+    pub peers: Vec<PeerSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetConfigResponse {
+    /// 服务器节点ID
+    pub node_id: Uuid,
+    /// 启动时的配置叠加运行中的调整，敏感字段为 `<redacted>`
+    pub config: serde_json::Value,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PeerInfo {
     pub id: Uuid,
     pub addr: SocketAddr,
     pub last_seen: u64,
-    pub capabilities: Vec<String>,
+    #[serde(deserialize_with = "deserialize_interned_seq")]
+    pub capabilities: Capabilities,
+    /// 运维固定的基础设施节点，始终出现在节点列表中（未在线时为配置的地址）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// 节点通告的本地地址（多宿主主机可能有多个）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<SocketAddr>,
+    /// 节点已连接到服务器的时长（秒），可据此优先选择长期在线的节点；离线节点没有此字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connected_secs: Option<u64>,
 }
 
 impl PeerInfo {
-    pub fn new(id: Uuid, addr: SocketAddr, capabilities: Vec<String>) -> Self {
+    pub fn new(id: Uuid, addr: SocketAddr, capabilities: impl Into<Capabilities>) -> Self {
         Self {
             id,
             addr,
-            last_seen: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-            capabilities,
+            last_seen: unix_secs(),
+            capabilities: capabilities.into(),
+            pinned: false,
+            addresses: Vec::new(),
+            connected_secs: None,
         }
     }
     
     #[allow(dead_code)]
     pub fn update_last_seen(&mut self) {
-        self.last_seen = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        self.last_seen = unix_secs();
     }
 }
 
@@ -333,6 +1148,7 @@ impl PeerInfo {
 #[allow(dead_code)]
 pub struct RelayRequest {
     pub target_peer_id: Uuid,
+    #[serde(default, deserialize_with = "deserialize_relay_bytes")]
     pub data: Vec<u8>,
 }
 
@@ -340,6 +1156,11 @@ pub struct RelayRequest {
 #[allow(dead_code)]
 pub struct RelayResponse {
     pub success: bool,
+    #[serde(default)]
+    pub session_id: Option<Uuid>,
+    /// 会话建立成功时为会话的目标节点
+    #[serde(default)]
+    pub target_peer_id: Option<Uuid>,
     pub error_message: Option<String>,
 }
 
@@ -347,9 +1168,119 @@ pub struct RelayResponse {
 #[allow(dead_code)]
 pub struct RelayData {
     pub from_peer_id: Uuid,
+    #[serde(default)]
+    pub session_id: Option<Uuid>,
+    #[serde(deserialize_with = "deserialize_relay_bytes")]
     pub data: Vec<u8>,
 }
 
+/// 中继会话关闭的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayCloseReason {
+    /// 会话一方以 `RelayClose` 关闭
+    Closed,
+    /// 超过 `relay.idle_timeout_secs` 没有转发数据
+    IdleTimeout,
+    /// 会话另一方已下线
+    PeerDisconnected,
+    /// 中继会话占用超过内存软限制被淘汰
+    Evicted,
+}
+
+/// `RelayClose` 的负载：节点请求关闭时只有 `session_id`，服务器的通知附上另一方与原因
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayClose {
+    pub session_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<RelayCloseReason>,
+}
+
+/// 中继负载中 `data` 的两种写法：base64 字符串，或旧版的字节值数组
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RelayBytes {
+    Base64(String),
+    Array(Vec<u8>),
+}
+
+fn deserialize_relay_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    match RelayBytes::deserialize(deserializer)? {
+        RelayBytes::Base64(encoded) => base64::engine::general_purpose::STANDARD.decode(encoded).map_err(serde::de::Error::custom),
+        RelayBytes::Array(bytes) => Ok(bytes),
+    }
+}
+
+/// 解析中继负载中的 `data`（base64 字符串或字节值数组），缺少时返回 `None`
+pub fn parse_relay_data(payload: &serde_json::Value) -> Result<Option<Vec<u8>>, String> {
+    match payload.get("data") {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::String(encoded)) => base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map(Some)
+            .map_err(|e| format!("数据格式错误：base64 解码失败: {}", e)),
+        Some(serde_json::Value::Array(values)) => values
+            .iter()
+            .map(|value| match value.as_u64() {
+                Some(num) if num <= 255 => Ok(num as u8),
+                Some(_) => Err("数据格式错误：字节值超出范围".to_string()),
+                None => Err("数据格式错误：非数字值".to_string()),
+            })
+            .collect::<Result<Vec<u8>, String>>()
+            .map(Some),
+        Some(_) => Err("数据格式错误：应为 base64 字符串或字节数组".to_string()),
+    }
+}
+
+/// 中继帧的起始标记；不会与 JSON（`{`）、MessagePack map 头或 STUN（首字节高两位为 0）混淆
+pub const RELAY_FRAME_MAGIC: [u8; 2] = [0xF5, b'R'];
+const RELAY_FRAME_VERSION: u8 = 1;
+/// 帧头长度：标记 2 字节、版本 1 字节、会话ID 16 字节、发送方ID 16 字节
+pub const RELAY_FRAME_HEADER_LEN: usize = 35;
+/// 握手时通告该能力的节点，服务器以二进制中继帧而不是 JSON `RelayData` 向其转发
+pub const RELAY_FRAME_CAPABILITY: &str = "relay_frame";
+
+/// 二进制中继帧：经已建立的中继会话收发数据时代替 JSON `RelayData`，数据原样跟在定长帧头之后
+///
+/// 节点发往服务器时发送方ID填零，服务器写入通过认证的发送方后原样转发，不重新序列化。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayFrame<'a> {
+    pub session_id: Uuid,
+    pub from_peer_id: Uuid,
+    pub data: &'a [u8],
+}
+
+impl<'a> RelayFrame<'a> {
+    /// 解析数据包；不是中继帧或版本不支持时返回 `None`
+    pub fn parse(packet: &'a [u8]) -> Option<Self> {
+        if packet.len() < RELAY_FRAME_HEADER_LEN || packet[..2] != RELAY_FRAME_MAGIC || packet[2] != RELAY_FRAME_VERSION {
+            return None;
+        }
+        Some(Self {
+            session_id: Uuid::from_slice(&packet[3..19]).ok()?,
+            from_peer_id: Uuid::from_slice(&packet[19..35]).ok()?,
+            data: &packet[RELAY_FRAME_HEADER_LEN..],
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(RELAY_FRAME_HEADER_LEN + self.data.len());
+        packet.extend_from_slice(&RELAY_FRAME_MAGIC);
+        packet.push(RELAY_FRAME_VERSION);
+        packet.extend_from_slice(self.session_id.as_bytes());
+        packet.extend_from_slice(self.from_peer_id.as_bytes());
+        packet.extend_from_slice(self.data);
+        packet
+    }
+
+    /// 在已编码的帧中写入发送方ID
+    pub fn set_sender(packet: &mut [u8], from_peer_id: Uuid) {
+        packet[19..35].copy_from_slice(from_peer_id.as_bytes());
+    }
+}
+
 /// 握手协议处理器
 pub struct HandshakeProtocol;
 
@@ -386,6 +1317,14 @@ impl HandshakeProtocol {
         
         Ok(response)
     }
+
+    /// 握手响应若是 cookie 质询（见 [`Message::handshake_cookie`]），返回要带回的 cookie
+    pub fn cookie_challenge(message: &Message) -> Option<String> {
+        if message.message_type != MessageType::HandshakeResponse || message.payload.get("success") != Some(&serde_json::Value::Bool(false)) {
+            return None;
+        }
+        message.payload.get("cookie")?.as_str().map(str::to_string)
+    }
 }
 
 #[cfg(test)]
@@ -399,7 +1338,7 @@ mod tests {
             "127.0.0.1:8080".parse().unwrap(),
             "testnet".to_string(),
         );
-        let message = Message::handshake_request(node_info);
+        let message = Message::handshake_request(node_info).unwrap();
         
         assert_eq!(message.message_type, MessageType::HandshakeRequest);
         assert!(!message.id.is_nil());
@@ -412,7 +1351,7 @@ mod tests {
             "127.0.0.1:8080".parse().unwrap(),
             "testnet".to_string(),
         );
-        let message = Message::handshake_request(node_info.clone());
+        let message = Message::handshake_request(node_info.clone()).unwrap();
         
         let result = HandshakeProtocol::validate_handshake_request(&message);
         assert!(result.is_ok());
@@ -420,4 +1359,52 @@ mod tests {
         let validated_info = result.unwrap();
         assert_eq!(validated_info.name, node_info.name);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_ack_batch_lists_all_ids() {
+        let addr = "127.0.0.1:8080".parse().unwrap();
+        let ids = [Uuid::new_v4(), Uuid::new_v4()];
+        let batch = Message::ack_batch(&ids, addr);
+        assert_eq!(batch.ack_for, Some(ids[0]));
+        assert_eq!(batch.acked_ids(), ids);
+
+        let single = Message::ack_batch(&ids[..1], addr);
+        assert_eq!((single.ack_for, single.payload.clone()), (Some(ids[0]), serde_json::Value::Null));
+        assert_eq!(single.acked_ids(), &ids[..1]);
+    }
+
+    #[test]
+    fn test_disconnect_notice_codes() {
+        let message = Message::disconnect_with_code(DisconnectReason::IdleTimeout, "心跳超时".to_string());
+        assert_eq!(message.payload["code"], "idle_timeout");
+        let notice: DisconnectNotice = serde_json::from_value(message.payload).unwrap();
+        assert_eq!(notice.code, Some(DisconnectReason::IdleTimeout));
+        assert!(DisconnectReason::IdleTimeout.should_retry());
+        assert!(!DisconnectReason::Kicked.should_retry());
+
+        // 节点主动断开时只有文字原因
+        let notice: DisconnectNotice = serde_json::from_value(Message::disconnect("bye".to_string()).payload).unwrap();
+        assert_eq!(notice, DisconnectNotice { code: None, reason: "bye".to_string() });
+    }
+    #[test]
+    fn test_relay_frame_and_data_encodings() {
+        let (session_id, from) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut packet = RelayFrame { session_id, from_peer_id: Uuid::nil(), data: b"payload" }.encode();
+        assert_eq!(packet.len(), RELAY_FRAME_HEADER_LEN + 7);
+        RelayFrame::set_sender(&mut packet, from);
+        assert_eq!(RelayFrame::parse(&packet), Some(RelayFrame { session_id, from_peer_id: from, data: b"payload" }));
+        assert!(RelayFrame::parse(&packet[..RELAY_FRAME_HEADER_LEN - 1]).is_none());
+        assert!(RelayFrame::parse(b"{\"message_type\": \"Ping\"}").is_none());
+
+        // base64 与旧版字节数组都可解析
+        let request = Message::relay_request(from, vec![0, 255, 7]);
+        assert_eq!(parse_relay_data(&request.payload), Ok(Some(vec![0, 255, 7])));
+        let parsed: RelayRequest = serde_json::from_value(request.payload).unwrap();
+        assert_eq!(parsed.data, vec![0, 255, 7]);
+        let legacy = Message::relay_data(from, None, vec![1, 2]);
+        assert_eq!(parse_relay_data(&legacy.payload), Ok(Some(vec![1, 2])));
+        assert!(parse_relay_data(&serde_json::json!({ "data": [256] })).is_err());
+        assert!(parse_relay_data(&serde_json::json!({ "data": "not base64!" })).is_err());
+        assert_eq!(parse_relay_data(&serde_json::json!({})), Ok(None));
+    }
+}
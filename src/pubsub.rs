@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use log::{debug, warn};
+use tokio::sync::{mpsc, RwLock};
+use uuid::Uuid;
+
+use crate::peer::PeerManager;
+use crate::protocol::Message;
+use crate::tr;
+
+/// 主题名最大长度
+pub const MAX_TOPIC_LEN: usize = 256;
+
+/// 一条已发布的主题消息
+#[derive(Debug, Clone, PartialEq)]
+pub struct Published {
+    pub topic: String,
+    /// 发布者节点ID；来自桥接等外部来源时为 `None`
+    pub from: Option<Uuid>,
+    pub data: serde_json::Value,
+}
+
+/// 主题发布/订阅
+///
+/// 节点通过 `Subscribe` / `Unsubscribe` 消息管理订阅，`Publish` 消息被投递给
+/// 该主题的所有订阅者（发布者自身除外）。主题按完整名称匹配。
+/// 通过 [`TopicBus::tap`] 可以观察覆盖网络内发布的所有消息（例如 MQTT 桥接）。
+#[derive(Debug, Default)]
+pub struct TopicBus {
+    subscriptions: RwLock<HashMap<String, HashSet<Uuid>>>,
+    taps: std::sync::Mutex<Vec<mpsc::UnboundedSender<Published>>>,
+}
+
+impl TopicBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 校验主题名
+    pub fn validate_topic(topic: &str) -> Result<(), &'static str> {
+        if topic.is_empty() {
+            Err("主题不能为空")
+        } else if topic.len() > MAX_TOPIC_LEN {
+            Err("主题过长")
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 订阅主题，返回是否为新订阅
+    pub async fn subscribe(&self, peer_id: Uuid, topic: &str) -> bool {
+        self.subscriptions
+            .write()
+            .await
+            .entry(topic.to_string())
+            .or_default()
+            .insert(peer_id)
+    }
+
+    /// 取消订阅，返回是否存在该订阅
+    pub async fn unsubscribe(&self, peer_id: &Uuid, topic: &str) -> bool {
+        let mut subscriptions = self.subscriptions.write().await;
+        let Some(subscribers) = subscriptions.get_mut(topic) else { return false };
+        let removed = subscribers.remove(peer_id);
+        if subscribers.is_empty() {
+            subscriptions.remove(topic);
+        }
+        removed
+    }
+
+    /// 移除节点的全部订阅
+    pub async fn remove_peer(&self, peer_id: &Uuid) {
+        self.subscriptions.write().await.retain(|_, subscribers| {
+            subscribers.remove(peer_id);
+            !subscribers.is_empty()
+        });
+    }
+
+    pub async fn subscribers(&self, topic: &str) -> Vec<Uuid> {
+        self.subscriptions
+            .read()
+            .await
+            .get(topic)
+            .map(|s| s.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// 当前有订阅者的主题及订阅数
+    pub async fn topics(&self) -> Vec<(String, usize)> {
+        let mut topics: Vec<(String, usize)> = self.subscriptions
+            .read()
+            .await
+            .iter()
+            .map(|(topic, subscribers)| (topic.clone(), subscribers.len()))
+            .collect();
+        topics.sort();
+        topics
+    }
+
+    /// 注册观察者，接收之后在覆盖网络内发布的所有消息
+    pub fn tap(&self) -> mpsc::UnboundedReceiver<Published> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.taps.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// 处理节点发布的消息：投递给订阅者并通知观察者，返回投递成功的订阅者数量
+    pub async fn publish(&self, peer_manager: &Arc<PeerManager>, published: Published) -> usize {
+        self.taps.lock().unwrap().retain(|tap| tap.send(published.clone()).is_ok());
+        self.deliver(peer_manager, &published).await
+    }
+
+    /// 只投递给本地订阅者（不通知观察者），用于桥接等外部来源，避免回环
+    pub async fn deliver(&self, peer_manager: &Arc<PeerManager>, published: &Published) -> usize {
+        let message = Message::publish_delivery(&published.topic, published.from, published.data.clone());
+        let mut delivered = 0;
+        let mut stale = Vec::new();
+        for subscriber in self.subscribers(&published.topic).await {
+            if Some(subscriber) == published.from {
+                continue;
+            }
+            let Some(peer) = peer_manager.get_peer(&subscriber).await else {
+                stale.push(subscriber);
+                continue;
+            };
+            match peer.read().await.send_message(&message).await {
+                Ok(()) => delivered += 1,
+                Err(e) => warn!("{}", tr!("向订阅者 {} 投递主题 {} 失败: {}", "Failed to deliver topic {1} to subscriber {0}: {2}", subscriber, published.topic, e)),
+            }
+        }
+        // 清理已离线节点遗留的订阅
+        for peer_id in stale {
+            debug!("{}", tr!("移除离线节点 {} 的订阅", "Removing subscriptions of offline node {}", peer_id));
+            self.remove_peer(&peer_id).await;
+        }
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribe_and_tap() {
+        let bus = TopicBus::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        assert!(bus.subscribe(a, "sensors/temp").await);
+        assert!(!bus.subscribe(a, "sensors/temp").await);
+        bus.subscribe(b, "sensors/temp").await;
+        bus.subscribe(b, "alerts").await;
+        assert_eq!(bus.topics().await, vec![("alerts".to_string(), 1), ("sensors/temp".to_string(), 2)]);
+
+        assert!(bus.unsubscribe(&a, "sensors/temp").await);
+        bus.remove_peer(&b).await;
+        assert!(bus.topics().await.is_empty());
+
+        let mut tap = bus.tap();
+        let manager = Arc::new(PeerManager::new(
+            crate::protocol::NodeInfo::new("n".to_string(), "127.0.0.1:0".parse().unwrap(), "t".to_string()),
+            10,
+        ));
+        let published = Published { topic: "alerts".to_string(), from: Some(a), data: serde_json::json!(1) };
+        assert_eq!(bus.publish(&manager, published.clone()).await, 0);
+        assert_eq!(tap.recv().await, Some(published));
+        assert!(TopicBus::validate_topic("").is_err());
+    }
+}
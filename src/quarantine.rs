@@ -0,0 +1,102 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::PanicIsolationConfig;
+
+#[derive(Debug, Default)]
+struct SourceRecord {
+    panics: VecDeque<Instant>,
+    banned_until: Option<Instant>,
+}
+
+/// 被封禁的来源（供管理接口展示）
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct BannedSource {
+    pub ip: IpAddr,
+    pub remaining_secs: u64,
+}
+
+/// 按来源 IP 统计处理数据包时的 panic，反复触发 panic 的来源被暂时封禁
+///
+/// 按 IP 而不是地址统计，来源换端口不能绕过封禁。
+#[derive(Debug)]
+pub struct PanicQuarantine {
+    config: PanicIsolationConfig,
+    sources: Mutex<HashMap<IpAddr, SourceRecord>>,
+}
+
+impl PanicQuarantine {
+    pub fn new(config: PanicIsolationConfig) -> Self {
+        Self { config, sources: Mutex::new(HashMap::new()) }
+    }
+
+    /// 记录来源触发的一次 panic，返回是否因此被封禁
+    pub fn record_panic(&self, ip: IpAddr, now: Instant) -> bool {
+        let window = Duration::from_secs(self.config.window_secs);
+        let mut sources = self.sources.lock().unwrap();
+        // 顺带清理已解封且窗口内没有 panic 的来源
+        sources.retain(|_, r| {
+            r.banned_until.is_some_and(|t| t > now) || r.panics.back().is_some_and(|t| now.duration_since(*t) <= window)
+        });
+        let record = sources.entry(ip).or_default();
+        record.panics.push_back(now);
+        while record.panics.front().is_some_and(|t| now.duration_since(*t) > window) {
+            record.panics.pop_front();
+        }
+        if self.config.ban_after == 0 || record.panics.len() < self.config.ban_after {
+            return false;
+        }
+        record.panics.clear();
+        record.banned_until = Some(now + Duration::from_secs(self.config.ban_secs));
+        true
+    }
+
+    /// 来源当前是否被封禁
+    pub fn is_banned(&self, ip: &IpAddr, now: Instant) -> bool {
+        self.sources.lock().unwrap().get(ip).and_then(|r| r.banned_until).is_some_and(|t| t > now)
+    }
+
+    /// 当前被封禁的来源
+    pub fn banned(&self) -> Vec<BannedSource> {
+        let now = Instant::now();
+        let mut banned: Vec<BannedSource> = self
+            .sources
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(ip, r)| {
+                let until = r.banned_until.filter(|t| *t > now)?;
+                Some(BannedSource { ip: *ip, remaining_secs: until.duration_since(now).as_secs() })
+            })
+            .collect();
+        banned.sort_by_key(|b| b.ip);
+        banned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ban_after_repeated_panics() {
+        let quarantine = PanicQuarantine::new(PanicIsolationConfig { ban_after: 3, window_secs: 10, ban_secs: 60 });
+        let (a, b): (IpAddr, IpAddr) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        let start = Instant::now();
+
+        // 窗口外的 panic 不累计
+        assert!(!quarantine.record_panic(a, start));
+        assert!(!quarantine.record_panic(a, start + Duration::from_secs(11)));
+        assert!(!quarantine.record_panic(a, start + Duration::from_secs(12)));
+        assert!(quarantine.record_panic(a, start + Duration::from_secs(13)));
+        assert!(quarantine.is_banned(&a, start + Duration::from_secs(14)));
+        assert!(!quarantine.is_banned(&b, start + Duration::from_secs(14)));
+        assert_eq!(quarantine.banned().iter().map(|s| s.ip).collect::<Vec<_>>(), vec![a]);
+
+        // 封禁到期后解封
+        assert!(!quarantine.is_banned(&a, start + Duration::from_secs(74)));
+    }
+}
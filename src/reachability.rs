@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use crate::protocol::NodeInfo;
+
+/// 握手元数据中表示节点所在区域的键
+pub const REGION_METADATA_KEY: &str = "region";
+
+/// 影响两个节点能否直连的特征
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerTraits {
+    /// 节点在握手元数据 `region` 中声明的区域
+    pub region: Option<String>,
+    /// 节点上报的NAT类型
+    pub nat_type: Option<String>,
+    /// 节点已连接的时长（秒）
+    pub connected_secs: u64,
+}
+
+impl PeerTraits {
+    pub fn new(node_info: Option<&NodeInfo>, nat_type: Option<String>, connected: Duration) -> Self {
+        Self {
+            region: node_info.and_then(|info| info.metadata.get(REGION_METADATA_KEY).cloned()),
+            nat_type,
+            connected_secs: connected.as_secs(),
+        }
+    }
+}
+
+/// NAT类型按打洞难度粗分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NatClass {
+    /// 公网地址或完全锥形，几乎总能直连
+    Open,
+    /// 地址（IP）受限锥形
+    Restricted,
+    /// 端口受限锥形
+    PortRestricted,
+    /// 对称型
+    Symmetric,
+    /// 未上报或无法识别
+    Unknown,
+}
+
+/// 节点上报的NAT类型是自由文本，按关键字归类
+fn classify(nat_type: Option<&str>) -> NatClass {
+    let Some(nat_type) = nat_type else { return NatClass::Unknown };
+    let nat_type = nat_type.to_ascii_lowercase().replace(['_', '-', ' '], "");
+    if nat_type.contains("symmetric") {
+        NatClass::Symmetric
+    } else if nat_type.contains("portrestricted") {
+        NatClass::PortRestricted
+    } else if nat_type.contains("restricted") {
+        NatClass::Restricted
+    } else if ["open", "public", "none", "fullcone"].iter().any(|k| nat_type.contains(k)) {
+        NatClass::Open
+    } else {
+        NatClass::Unknown
+    }
+}
+
+/// 两种NAT之间打洞成功的可能性（0–3）
+fn nat_compatibility(a: NatClass, b: NatClass) -> u32 {
+    use NatClass::*;
+    match (a, b) {
+        (Open, _) | (_, Open) => 3,
+        (Symmetric, Symmetric) | (Symmetric, PortRestricted) | (PortRestricted, Symmetric) => 0,
+        (Symmetric, _) | (_, Symmetric) => 1,
+        // 锥形之间，或一方类型未知
+        _ => 2,
+    }
+}
+
+/// 候选节点对请求方的可达性评分，越高越可能直连成功
+///
+/// NAT 兼容性权重最高，其次是同区域，连接越久越稳定的节点略微优先。
+pub fn score(requester: &PeerTraits, candidate: &PeerTraits) -> u32 {
+    let nat = nat_compatibility(classify(requester.nat_type.as_deref()), classify(candidate.nat_type.as_deref()));
+    let same_region = match (&requester.region, &candidate.region) {
+        (Some(a), Some(b)) if a.eq_ignore_ascii_case(b) => 1,
+        _ => 0,
+    };
+    // 按分钟取对数：1 分钟、3 分钟、7 分钟以上分别加 1、2、3
+    let stability = (candidate.connected_secs / 60 + 1).ilog2().min(3);
+    nat * 3 + same_region * 4 + stability
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traits(region: &str, nat_type: &str, connected_secs: u64) -> PeerTraits {
+        PeerTraits { region: Some(region.to_string()), nat_type: Some(nat_type.to_string()), connected_secs }
+    }
+
+    #[test]
+    fn test_score_prefers_reachable_nearby_stable_peers() {
+        let requester = traits("eu", "Symmetric", 0);
+        // 对称型之间难以直连，完全锥形的节点优先
+        assert!(score(&requester, &traits("us", "FullCone", 0)) > score(&requester, &traits("us", "Symmetric", 0)));
+        assert!(score(&requester, &traits("us", "Restricted", 0)) > score(&requester, &traits("us", "port_restricted", 0)));
+        // 同区域、连接更久的节点优先
+        assert!(score(&requester, &traits("EU", "Restricted", 0)) > score(&requester, &traits("us", "Restricted", 0)));
+        assert!(score(&requester, &traits("us", "Restricted", 600)) > score(&requester, &traits("us", "Restricted", 30)));
+        assert_eq!(score(&requester, &traits("us", "Restricted", 600)), score(&requester, &traits("us", "Restricted", 6000)));
+        assert_eq!(classify(None), NatClass::Unknown);
+    }
+}
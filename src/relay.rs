@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use log::debug;
+use crate::tr;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::events::EventKind;
+use crate::peer::PeerManager;
+use crate::protocol::{Message, RelayCloseReason};
+
+/// 一个会话条目的近似占用：键值对与会话ID索引，加上两个哈希表的控制字节
+const SESSION_ENTRY_BYTES: usize = size_of::<((Uuid, Uuid), RelaySession)>() + size_of::<(Uuid, (Uuid, Uuid))>() + 2;
+
+/// 一对节点之间的中继会话统计
+#[derive(Debug, Clone)]
+struct RelaySession {
+    session_id: Uuid,
+    started_at: Instant,
+    last_active: Instant,
+    packets: u64,
+    bytes: u64,
+    /// 另一端连接在其他集群实例上时，经该实例中继
+    remote_instance: Option<Uuid>,
+}
+
+/// 中继会话快照（供管理接口与gRPC控制面展示）
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RelaySessionInfo {
+    pub session_id: Uuid,
+    pub from_peer_id: Uuid,
+    pub to_peer_id: Uuid,
+    pub packets: u64,
+    pub bytes: u64,
+    pub age_secs: u64,
+    pub idle_secs: u64,
+    /// 跨实例中继时会话另一段所在的集群实例
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_instance: Option<Uuid>,
+}
+
+/// 已关闭的中继会话
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClosedRelay {
+    pub session_id: Uuid,
+    pub from_peer_id: Uuid,
+    pub to_peer_id: Uuid,
+    pub remote_instance: Option<Uuid>,
+}
+
+#[derive(Debug, Default)]
+struct SessionTable {
+    sessions: HashMap<(Uuid, Uuid), RelaySession>,
+    /// 会话ID -> (发送方, 接收方)
+    ids: HashMap<Uuid, (Uuid, Uuid)>,
+}
+
+impl SessionTable {
+    /// 取得一对节点的会话，不存在时新建；返回会话与是否新建
+    fn entry(&mut self, from: Uuid, to: Uuid) -> (&mut RelaySession, bool) {
+        let started = !self.sessions.contains_key(&(from, to));
+        if started {
+            self.insert(Uuid::new_v4(), from, to, None);
+        }
+        (self.sessions.get_mut(&(from, to)).unwrap(), started)
+    }
+
+    fn insert(&mut self, session_id: Uuid, from: Uuid, to: Uuid, remote_instance: Option<Uuid>) {
+        let now = Instant::now();
+        self.ids.insert(session_id, (from, to));
+        let session = RelaySession { session_id, started_at: now, last_active: now, packets: 0, bytes: 0, remote_instance };
+        self.sessions.insert((from, to), session);
+    }
+
+    fn remove(&mut self, key: &(Uuid, Uuid)) -> Option<ClosedRelay> {
+        let session = self.sessions.remove(key)?;
+        self.ids.remove(&session.session_id);
+        Some(ClosedRelay { session_id: session.session_id, from_peer_id: key.0, to_peer_id: key.1, remote_instance: session.remote_instance })
+    }
+
+    /// 移除满足条件的会话
+    fn remove_where(&mut self, mut predicate: impl FnMut(&(Uuid, Uuid), &RelaySession) -> bool) -> Vec<ClosedRelay> {
+        let keys: Vec<(Uuid, Uuid)> = self.sessions.iter().filter(|(key, s)| predicate(key, s)).map(|(key, _)| *key).collect();
+        keys.iter().filter_map(|key| self.remove(key)).collect()
+    }
+}
+
+/// 服务器转发中的中继会话，按 (发送方, 接收方) 聚合
+///
+/// 每个会话有一个会话ID：节点以 `RelayRequest` 建立会话后，后续的 `RelayData` 只需携带会话ID。
+#[derive(Debug, Default)]
+pub struct RelaySessions {
+    table: Mutex<SessionTable>,
+}
+
+impl RelaySessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为一对节点建立中继会话（已存在时沿用），返回会话ID与是否新建
+    pub fn bind(&self, from: Uuid, to: Uuid) -> (Uuid, bool) {
+        let mut table = self.table.lock().unwrap();
+        let (session, started) = table.entry(from, to);
+        session.last_active = Instant::now();
+        (session.session_id, started)
+    }
+
+    /// 为发送方与连接在集群实例 `instance` 上的接收方建立跨实例中继会话（已存在时沿用）
+    pub fn bind_remote(&self, from: Uuid, to: Uuid, instance: Uuid) -> (Uuid, bool) {
+        let mut table = self.table.lock().unwrap();
+        let (session, started) = table.entry(from, to);
+        session.last_active = Instant::now();
+        session.remote_instance = Some(instance);
+        (session.session_id, started)
+    }
+
+    /// 登记发送方所在实例建立的跨实例会话，沿用对方的会话ID；返回是否新建
+    pub fn mirror(&self, session_id: Uuid, from: Uuid, to: Uuid, instance: Uuid) -> bool {
+        let mut table = self.table.lock().unwrap();
+        if table.ids.get(&session_id) == Some(&(from, to)) {
+            return false;
+        }
+        // 这对节点在发送方实例上重新建立了会话
+        table.remove(&(from, to));
+        table.insert(session_id, from, to, Some(instance));
+        true
+    }
+
+    /// 跨实例会话另一段所在的集群实例
+    pub fn remote_instance(&self, session_id: &Uuid) -> Option<Uuid> {
+        let table = self.table.lock().unwrap();
+        let key = table.ids.get(session_id)?;
+        table.sessions.get(key)?.remote_instance
+    }
+
+    /// 按会话ID查找 (发送方, 接收方)
+    pub fn resolve(&self, session_id: &Uuid) -> Option<(Uuid, Uuid)> {
+        self.table.lock().unwrap().ids.get(session_id).copied()
+    }
+
+    /// 记录一次成功转发，返回是否因此开始了新的会话
+    pub fn record(&self, from: Uuid, to: Uuid, bytes: usize) -> bool {
+        let mut table = self.table.lock().unwrap();
+        let (session, started) = table.entry(from, to);
+        session.last_active = Instant::now();
+        session.packets += 1;
+        session.bytes += bytes as u64;
+        started
+    }
+
+    /// 关闭指定会话
+    pub fn close(&self, session_id: &Uuid) -> Option<ClosedRelay> {
+        let mut table = self.table.lock().unwrap();
+        let key = *table.ids.get(session_id)?;
+        table.remove(&key)
+    }
+
+    /// 移除与指定节点相关的所有会话
+    pub fn remove_peer(&self, peer_id: &Uuid) -> Vec<ClosedRelay> {
+        self.table.lock().unwrap().remove_where(|(from, to), _| from == peer_id || to == peer_id)
+    }
+
+    /// 关闭超过 `idle` 没有转发数据的会话
+    pub fn expire_idle(&self, idle: Duration) -> Vec<ClosedRelay> {
+        self.table.lock().unwrap().remove_where(|_, session| session.last_active.elapsed() >= idle)
+    }
+
+    /// 近似内存占用（字节）
+    pub fn approx_bytes(&self) -> usize {
+        self.len() * SESSION_ENTRY_BYTES
+    }
+
+    /// 淘汰空闲最久的会话，直到占用不超过 `limit` 字节
+    pub fn evict_idle(&self, limit: usize) -> Vec<ClosedRelay> {
+        let mut table = self.table.lock().unwrap();
+        let excess = (table.sessions.len() * SESSION_ENTRY_BYTES).saturating_sub(limit).div_ceil(SESSION_ENTRY_BYTES);
+        if excess == 0 {
+            return Vec::new();
+        }
+        let mut idle: Vec<((Uuid, Uuid), Instant)> = table.sessions.iter().map(|(key, s)| (*key, s.last_active)).collect();
+        idle.sort_by_key(|(_, last_active)| *last_active);
+        let evicted = idle.into_iter().take(excess).filter_map(|(key, _)| table.remove(&key)).collect();
+        table.sessions.shrink_to_fit();
+        table.ids.shrink_to_fit();
+        evicted
+    }
+
+    /// 当前会话快照，按最近活跃排序
+    pub fn snapshot(&self) -> Vec<RelaySessionInfo> {
+        let table = self.table.lock().unwrap();
+        let mut list: Vec<RelaySessionInfo> = table
+            .sessions
+            .iter()
+            .map(|((from, to), s)| RelaySessionInfo {
+                session_id: s.session_id,
+                from_peer_id: *from,
+                to_peer_id: *to,
+                packets: s.packets,
+                bytes: s.bytes,
+                age_secs: s.started_at.elapsed().as_secs(),
+                idle_secs: s.last_active.elapsed().as_secs(),
+                remote_instance: s.remote_instance,
+            })
+            .collect();
+        list.sort_by_key(|s| s.idle_secs);
+        list
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.lock().unwrap().sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// 向仍在线的会话双方发送 `RelayClose` 通知，并发布 `relay.closed` 事件
+pub async fn notify_closed(peer_manager: &PeerManager, closed: &[ClosedRelay], reason: RelayCloseReason) {
+    for relay in closed {
+        debug!("{}", tr!("中继会话 {} 已关闭（{:?}）: {} -> {}", "Relay session {} closed ({:?}): {} -> {}", relay.session_id, reason, relay.from_peer_id, relay.to_peer_id));
+        peer_manager.events().emit(
+            EventKind::RelayClosed,
+            Some(relay.from_peer_id),
+            serde_json::json!({ "to_node_id": relay.to_peer_id, "session_id": relay.session_id, "reason": reason }),
+        );
+        for (endpoint, other) in [(relay.from_peer_id, relay.to_peer_id), (relay.to_peer_id, relay.from_peer_id)] {
+            let Some(peer) = peer_manager.get_peer(&endpoint).await else { continue };
+            if let Err(e) = peer.read().await.send_message(&Message::relay_closed(relay.session_id, other, reason)).await {
+                debug!("{}", tr!("向节点 {} 发送中继会话关闭通知失败: {}", "Failed to send relay session close notice to node {}: {}", endpoint, e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_remove_peer() {
+        let sessions = RelaySessions::new();
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        sessions.record(a, b, 10);
+        sessions.record(a, b, 5);
+        sessions.record(c, a, 1);
+
+        let snapshot = sessions.snapshot();
+        let ab = snapshot.iter().find(|s| s.from_peer_id == a && s.to_peer_id == b).unwrap();
+        assert_eq!((ab.packets, ab.bytes), (2, 15));
+        assert_eq!(sessions.len(), 2);
+
+        assert_eq!(sessions.remove_peer(&a).len(), 2);
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn test_bind_reuses_session_and_resolves_by_id() {
+        let sessions = RelaySessions::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (session_id, started) = sessions.bind(a, b);
+        assert!(started);
+        assert_eq!(sessions.bind(a, b), (session_id, false));
+        assert!(!sessions.record(a, b, 10));
+        assert_eq!(sessions.resolve(&session_id), Some((a, b)));
+        assert_eq!(sessions.snapshot()[0].session_id, session_id);
+
+        assert_eq!(sessions.evict_idle(0), vec![ClosedRelay { session_id, from_peer_id: a, to_peer_id: b, remote_instance: None }]);
+        assert_eq!(sessions.resolve(&session_id), None);
+        let (session_id, _) = sessions.bind(a, b);
+        sessions.remove_peer(&b);
+        assert_eq!(sessions.resolve(&session_id), None);
+    }
+
+    #[test]
+    fn test_close_and_expire_idle() {
+        let sessions = RelaySessions::new();
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let (ab, _) = sessions.bind(a, b);
+        sessions.bind(b, c);
+        assert_eq!(sessions.close(&ab).map(|closed| closed.to_peer_id), Some(b));
+        assert_eq!(sessions.close(&ab), None);
+
+        assert!(sessions.expire_idle(Duration::from_secs(60)).is_empty());
+        assert_eq!(sessions.expire_idle(Duration::ZERO).len(), 1);
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn test_remote_sessions_are_mirrored_by_id() {
+        let (a_side, b_side) = (RelaySessions::new(), RelaySessions::new());
+        let (a, b, instance_a, instance_b) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let (session_id, started) = a_side.bind_remote(a, b, instance_b);
+        assert!(started);
+        assert_eq!(a_side.remote_instance(&session_id), Some(instance_b));
+
+        assert!(b_side.mirror(session_id, a, b, instance_a));
+        assert!(!b_side.mirror(session_id, a, b, instance_a));
+        b_side.record(a, b, 10);
+        assert_eq!(b_side.snapshot()[0].session_id, session_id);
+        assert_eq!(b_side.snapshot()[0].bytes, 10);
+
+        // 发送方实例重建会话后，镜像换成新的会话ID
+        let renewed = Uuid::new_v4();
+        assert!(b_side.mirror(renewed, a, b, instance_a));
+        assert_eq!((b_side.resolve(&session_id), b_side.resolve(&renewed)), (None, Some((a, b))));
+        assert_eq!(b_side.remove_peer(&b)[0].remote_instance, Some(instance_a));
+    }
+}
@@ -0,0 +1,62 @@
+use uuid::Uuid;
+
+/// 节点与服务器组合的权重（FNV-1a 后接 splitmix64 混合）
+///
+/// 不使用 `DefaultHasher`：其算法不保证跨版本稳定，而集群中所有实例必须算出相同的结果。
+pub fn score(node_id: &Uuid, server_id: &Uuid) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in node_id.as_bytes().iter().chain(server_id.as_bytes()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+/// 用最高随机权重（rendezvous hashing）为节点选择归属服务器
+///
+/// 每个服务器与节点ID组合得到一个权重，权重最高者为归属服务器。服务器增减时，
+/// 只有归属于变动服务器的节点会改变归属。权重相同时取ID较小者，保证结果与候选顺序无关。
+pub fn home_server<'a, I>(node_id: &Uuid, servers: I) -> Option<Uuid>
+where
+    I: IntoIterator<Item = &'a Uuid>,
+{
+    servers
+        .into_iter()
+        .max_by(|a, b| score(node_id, a).cmp(&score(node_id, b)).then(b.cmp(a)))
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_home_server_is_stable_and_minimally_disrupted() {
+        let servers: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let nodes: Vec<Uuid> = (0..1000).map(|_| Uuid::new_v4()).collect();
+        let homes: Vec<Uuid> = nodes.iter().map(|n| home_server(n, &servers).unwrap()).collect();
+
+        // 与候选顺序无关
+        let reversed: Vec<Uuid> = servers.iter().rev().copied().collect();
+        assert!(nodes.iter().zip(&homes).all(|(n, home)| home_server(n, &reversed) == Some(*home)));
+
+        // 分布大致均匀
+        for server in &servers {
+            let count = homes.iter().filter(|h| *h == server).count();
+            assert!((150..350).contains(&count), "服务器 {} 分到 {} 个节点", server, count);
+        }
+
+        // 移除一个服务器只影响原本归属于它的节点
+        let removed = servers[0];
+        for (node, home) in nodes.iter().zip(&homes) {
+            let new_home = home_server(node, &servers[1..]).unwrap();
+            if *home != removed {
+                assert_eq!(new_home, *home);
+            }
+        }
+
+        assert_eq!(home_server(&nodes[0], &[]), None);
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use uuid::Uuid;
+
+use crate::tr;
+
+const MAGIC: &[u8; 8] = b"P2PRIDL1";
+const HEADER_LEN: u64 = 16;
+/// 每条记录：目标节点ID(16) + route_id(16) + 记录时间(8，Unix秒)
+const RECORD_LEN: usize = 40;
+
+type Record = (Uuid, Uuid, u64);
+
+#[derive(Debug)]
+struct Inner {
+    file: Option<File>,
+    slots: Vec<Option<Record>>,
+    next: usize,
+    /// 目标节点 → (route_id → 记录时间)
+    index: HashMap<Uuid, HashMap<Uuid, u64>>,
+}
+
+/// 持久化的路由消息ID日志
+///
+/// 路由器的内存缓存只在进程内、且只保留几分钟；客户端重连后重发的路由消息可能因此被再次投递。
+/// 该日志按目标节点记录近期转发过的 `route_id`，保存在固定大小的环形文件中，服务器重启后仍然有效：
+/// 在 `window` 内、且期间转发的消息不超过 `capacity` 条时，同一目标的同一 `route_id` 最多转发一次。
+#[derive(Debug)]
+pub struct RouteIdLog {
+    window: Duration,
+    inner: Mutex<Inner>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn decode(record: &[u8]) -> Option<Record> {
+    if record.iter().all(|b| *b == 0) {
+        return None;
+    }
+    let destination = Uuid::from_slice(&record[0..16]).ok()?;
+    let route_id = Uuid::from_slice(&record[16..32]).ok()?;
+    let recorded_at = u64::from_le_bytes(record[32..40].try_into().ok()?);
+    Some((destination, route_id, recorded_at))
+}
+
+/// 打开或新建环形文件；容量与已有文件不一致时重建
+fn open_file(path: &Path, capacity: usize) -> Result<(File, Vec<Option<Record>>)> {
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)
+        .context(format!("打开路由ID日志 {} 失败", path.display()))?;
+    let expected_len = HEADER_LEN + (capacity * RECORD_LEN) as u64;
+    let mut header = [0u8; HEADER_LEN as usize];
+    let reusable = file.metadata()?.len() == expected_len
+        && file.read_exact(&mut header).is_ok()
+        && &header[..8] == MAGIC
+        && u64::from_le_bytes(header[8..].try_into()?) == capacity as u64;
+
+    if reusable {
+        let mut data = vec![0u8; capacity * RECORD_LEN];
+        file.read_exact(&mut data)?;
+        return Ok((file, data.chunks(RECORD_LEN).map(decode).collect()));
+    }
+    if file.metadata()?.len() > 0 {
+        warn!("{}", tr!("路由ID日志 {} 格式或容量不匹配，重新创建", "Route ID log {} has a mismatched format or capacity, recreating", path.display()));
+    }
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(MAGIC)?;
+    file.write_all(&(capacity as u64).to_le_bytes())?;
+    file.set_len(expected_len)?;
+    Ok((file, vec![None; capacity]))
+}
+
+impl RouteIdLog {
+    /// 仅保存在内存中的日志（重启后丢失）
+    pub fn in_memory(capacity: usize, window: Duration) -> Self {
+        Self::from_slots(None, vec![None; capacity.max(1)], window)
+    }
+
+    /// 打开持久化日志，`path` 为空时等同于 `in_memory`
+    pub fn open(path: Option<&Path>, capacity: usize, window: Duration) -> Result<Self> {
+        let capacity = capacity.max(1);
+        let Some(path) = path else {
+            return Ok(Self::in_memory(capacity, window));
+        };
+        let (file, slots) = open_file(path, capacity)?;
+        Ok(Self::from_slots(Some(file), slots, window))
+    }
+
+    fn from_slots(file: Option<File>, slots: Vec<Option<Record>>, window: Duration) -> Self {
+        let mut index: HashMap<Uuid, HashMap<Uuid, u64>> = HashMap::new();
+        let mut next = 0;
+        let mut newest = None;
+        for (slot, record) in slots.iter().enumerate() {
+            let Some((destination, route_id, recorded_at)) = *record else { continue };
+            index.entry(destination).or_default().insert(route_id, recorded_at);
+            // 从最新记录之后继续写入，覆盖最旧的记录
+            if newest.is_none_or(|t| recorded_at >= t) {
+                newest = Some(recorded_at);
+                next = (slot + 1) % slots.len();
+            }
+        }
+        Self { window, inner: Mutex::new(Inner { file, slots, next, index }) }
+    }
+
+    /// 保证窗口
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// 记录一次转发；同一目标的同一 `route_id` 在窗口内已记录过时返回 `false`
+    pub fn record(&self, destination: Uuid, route_id: Uuid) -> bool {
+        self.record_at(destination, route_id, now_secs())
+    }
+
+    fn record_at(&self, destination: Uuid, route_id: Uuid, now: u64) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let window = self.window.as_secs();
+        if inner.index.get(&destination)
+            .and_then(|ids| ids.get(&route_id))
+            .is_some_and(|recorded_at| now.saturating_sub(*recorded_at) < window)
+        {
+            return false;
+        }
+
+        let slot = inner.next;
+        if let Some((old_destination, old_route_id, old_at)) = inner.slots[slot]
+            && let Some(ids) = inner.index.get_mut(&old_destination)
+        {
+            // 同一 route_id 可能在更晚的位置被重新记录，只移除与被覆盖记录一致的索引
+            if ids.get(&old_route_id) == Some(&old_at) {
+                ids.remove(&old_route_id);
+            }
+            if ids.is_empty() {
+                inner.index.remove(&old_destination);
+            }
+        }
+        inner.slots[slot] = Some((destination, route_id, now));
+        inner.index.entry(destination).or_default().insert(route_id, now);
+        inner.next = (slot + 1) % inner.slots.len();
+
+        if let Some(file) = inner.file.as_mut() {
+            let mut record = [0u8; RECORD_LEN];
+            record[0..16].copy_from_slice(destination.as_bytes());
+            record[16..32].copy_from_slice(route_id.as_bytes());
+            record[32..40].copy_from_slice(&now.to_le_bytes());
+            let offset = HEADER_LEN + (slot * RECORD_LEN) as u64;
+            if let Err(e) = file.seek(SeekFrom::Start(offset)).and_then(|_| file.write_all(&record)) {
+                debug!("{}", tr!("写入路由ID日志失败: {}", "Failed to write route ID log: {}", e));
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_ids_survive_reopen_and_expire() {
+        let path = std::env::temp_dir().join(format!("route_log_{}.bin", Uuid::new_v4()));
+        let window = Duration::from_secs(60);
+        let (dest_a, dest_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let route = Uuid::new_v4();
+
+        let log = RouteIdLog::open(Some(&path), 3, window).unwrap();
+        assert!(log.record_at(dest_a, route, 1000));
+        assert!(!log.record_at(dest_a, route, 1010));
+        // 按目标节点区分
+        assert!(log.record_at(dest_b, route, 1010));
+        drop(log);
+
+        // 重新打开后仍能识别重复，窗口过后允许再次转发
+        let log = RouteIdLog::open(Some(&path), 3, window).unwrap();
+        assert!(!log.record_at(dest_a, route, 1020));
+        assert!(log.record_at(dest_a, route, 1061));
+
+        // 容量用尽后最旧的记录被覆盖（dest_b 的记录位于第2个槽位）
+        assert!(log.record_at(Uuid::new_v4(), Uuid::new_v4(), 1062));
+        assert!(log.record_at(Uuid::new_v4(), Uuid::new_v4(), 1063));
+        assert!(log.record_at(dest_b, route, 1064));
+
+        // 容量变化时重建
+        let log = RouteIdLog::open(Some(&path), 4, window).unwrap();
+        assert!(log.record_at(dest_a, route, 1065));
+        let _ = std::fs::remove_file(&path);
+    }
+}
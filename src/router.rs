@@ -1,13 +1,30 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 use log::{info, warn, debug};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
-use crate::protocol::{Message, MessageType};
+use crate::broadcast_guard::{BroadcastCheck, BroadcastGuard};
+use crate::config::{RouteSignaturePolicy, RoutingMode};
+use crate::link_state::{LinkStateAdvertisement, LinkStateDatabase};
+use crate::metrics::ServerMetrics;
+use crate::correlation::PendingReplies;
+use crate::events::{EventBus, EventKind};
+use crate::identity::{self, IdentityError};
+use crate::protocol::{DeliveryReceipt, Message, MessageType, ProtocolError};
 use crate::peer::PeerManager;
+use crate::route_log::RouteIdLog;
+use crate::tr;
+
+pub use crate::wire::{RouteSignature, RoutedMessage};
+
+/// 一条路由在两张表中的近似占用：键值对加上哈希表的控制字节
+const ROUTE_ENTRY_BYTES: usize = size_of::<(Uuid, Uuid)>() + size_of::<(Uuid, u32)>() + 2;
+/// 去重缓存一个条目的近似占用
+const CACHE_ENTRY_BYTES: usize = size_of::<(Uuid, std::time::Instant)>() + 1;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoutingTable {
@@ -32,20 +49,27 @@ impl RoutingTable { // 路由表
     /// 添加路由条目
     pub fn add_route(&mut self, destination: Uuid, next_hop: Uuid, distance: u32) {
         // 只有当新路由距离更短时才更新
-        if let Some(&existing_distance) = self.distances.get(&destination) {
-            if distance >= existing_distance {
-                debug!(
+        if let Some(&existing_distance) = self.distances.get(&destination)
+            && distance >= existing_distance
+        {
+            debug!(
+                "{}",
+                tr!(
                     "忽略更长或相同距离的路由更新: {} -> {} (新距离: {}, 现有: {})",
-                    destination, next_hop, distance, existing_distance
-                );
-                return;
-            }
+                    "Ignoring route update with longer or equal distance: {} -> {} (new distance: {}, current: {})",
+                    destination,
+                    next_hop,
+                    distance,
+                    existing_distance,
+                )
+            );
+            return;
         }
         
         self.routes.insert(destination, next_hop);
         self.distances.insert(destination, distance);
         
-        debug!("添加路由: {} -> {} (距离: {})", destination, next_hop, distance);
+        debug!("{}", tr!("添加路由: {} -> {} (距离: {})", "Added route: {} -> {} (distance: {})", destination, next_hop, distance));
     }
     
     /// 获取到目标节点的下一跳
@@ -53,6 +77,24 @@ impl RoutingTable { // 路由表
         self.routes.get(destination).copied()
     }
     
+    /// 按下一跳把多播目标分组：每组只需沿该下一跳发出一份副本
+    pub fn distribution_tree(&self, members: &[Uuid]) -> DistributionTree {
+        let mut tree = DistributionTree::default();
+        for member in members {
+            match self.get_next_hop(member) {
+                Some(next_hop) => {
+                    let branch = tree.branches.entry(next_hop).or_default();
+                    if !branch.contains(member) {
+                        branch.push(*member);
+                    }
+                }
+                None if !tree.unreachable.contains(member) => tree.unreachable.push(*member),
+                None => {}
+            }
+        }
+        tree
+    }
+
     /// 获取到目标节点的距离
     #[allow(dead_code)]
     pub fn get_distance(&self, destination: &Uuid) -> Option<u32> {
@@ -63,7 +105,7 @@ impl RoutingTable { // 路由表
     pub fn remove_route(&mut self, destination: &Uuid) {
         self.routes.remove(destination);
         self.distances.remove(destination);
-        debug!("移除路由: {}", destination);
+        debug!("{}", tr!("移除路由: {}", "Removed route: {}", destination));
     }
     
     /// 移除通过特定下一跳的所有路由
@@ -74,9 +116,13 @@ impl RoutingTable { // 路由表
             .map(|(&dest, _)| dest)
             .collect();
         debug!(
-            "移除经由下一跳 {} 的路由条目数量: {}",
-            next_hop,
-            to_remove.len()
+            "{}",
+            tr!(
+                "移除经由下一跳 {} 的路由条目数量: {}",
+                "Removed {1} route entries via next hop {0}",
+                next_hop,
+                to_remove.len(),
+            )
         );
         
         for dest in to_remove {
@@ -84,6 +130,49 @@ impl RoutingTable { // 路由表
         }
     }
     
+    /// 用新计算的路由整体替换路由表（链路状态模式使用）
+    pub fn replace_routes(&mut self, entries: Vec<(Uuid, Uuid, u32)>) {
+        self.routes.clear();
+        self.distances.clear();
+        for (dest, next_hop, distance) in entries {
+            self.routes.insert(dest, next_hop);
+            self.distances.insert(dest, distance);
+        }
+        debug!("{}", tr!("路由表已整体替换，条目数: {}", "Routing table replaced, entries: {}", self.routes.len()));
+    }
+    
+    /// 路由条目数
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// 近似内存占用（字节）
+    pub fn approx_bytes(&self) -> usize {
+        self.routes.len() * ROUTE_ENTRY_BYTES
+    }
+
+    /// 按距离从远到近淘汰间接路由（直连路由保留），直到占用不超过 `limit` 字节，返回淘汰数
+    pub fn evict_farthest(&mut self, limit: usize) -> usize {
+        let excess = self.approx_bytes().saturating_sub(limit).div_ceil(ROUTE_ENTRY_BYTES);
+        if excess == 0 {
+            return 0;
+        }
+        let mut indirect: Vec<(Uuid, u32)> = self.distances.iter().filter(|(_, d)| **d > 1).map(|(&dest, &d)| (dest, d)).collect();
+        indirect.sort_by_key(|(_, d)| std::cmp::Reverse(*d));
+        let evicted = indirect.len().min(excess);
+        for (dest, _) in indirect.into_iter().take(evicted) {
+            self.routes.remove(&dest);
+            self.distances.remove(&dest);
+        }
+        self.routes.shrink_to_fit();
+        self.distances.shrink_to_fit();
+        evicted
+    }
+
     /// 获取所有路由条目
     pub fn get_all_routes(&self) -> Vec<(Uuid, Uuid, u32)> {
         self.routes
@@ -96,42 +185,39 @@ impl RoutingTable { // 路由表
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RoutedMessage {
-    pub original_message: Message,
-    pub source_node: Uuid,
-    pub destination_node: Uuid,
-    pub hop_count: u32,
-    pub max_hops: u32,
-    pub route_id: Uuid,
+/// 多播分发树在本节点的一层
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DistributionTree {
+    /// 下一跳 → 经它到达的目标节点
+    pub branches: BTreeMap<Uuid, Vec<Uuid>>,
+    /// 没有路由的目标节点
+    pub unreachable: Vec<Uuid>,
 }
 
 impl RoutedMessage {
-    #[allow(dead_code)]
-    pub fn new(
-        message: Message,
-        source: Uuid,
-        destination: Uuid,
-        max_hops: u32,
-    ) -> Self {
-        Self {
-            original_message: message,
-            source_node: source,
-            destination_node: destination,
-            hop_count: 0,
-            max_hops,
-            route_id: Uuid::new_v4(),
+    /// 分发树的一个分支：只剩一个目标时退化为发给它的单播路由消息
+    fn branch(&self, members: Vec<Uuid>) -> Self {
+        let mut branch = self.clone();
+        match members[..] {
+            [member] => {
+                branch.destination_node = member;
+                branch.multicast = Vec::new();
+            }
+            _ => branch.multicast = members,
         }
+        branch
     }
-    
-    pub fn increment_hop(&mut self) -> bool {
-        self.hop_count += 1;
-        self.hop_count <= self.max_hops
+
+    /// 目标节点发回源节点的送达回执，沿反向路由转发
+    pub fn receipt(&self) -> Result<RoutedMessage, ProtocolError> {
+        let receipt = DeliveryReceipt { route_id: self.route_id, destination: self.destination_node };
+        let message = self.original_message.respond_as(MessageType::Receipt, serde_json::to_value(receipt)?);
+        Ok(RoutedMessage::new(message, self.destination_node, self.source_node, self.max_hops))
     }
     
-    pub fn to_message(&self) -> Message {
-        let payload = serde_json::to_value(self).unwrap();
-        Message::new(MessageType::Data, payload)
+    pub fn to_message(&self) -> Result<Message, ProtocolError> {
+        let payload = serde_json::to_value(self)?;
+        Ok(Message::new(MessageType::Data, payload))
     }
     
     pub fn from_message(message: &Message) -> Result<Self> {
@@ -152,12 +238,35 @@ pub struct MessageRouter {
     message_cache: Arc<RwLock<HashMap<Uuid, std::time::Instant>>>,
     /// 缓存清理间隔
     cache_cleanup_interval: std::time::Duration,
+    /// 路由模式
+    routing_mode: RoutingMode,
+    /// 链路状态数据库（仅链路状态模式使用）
+    link_state: Arc<RwLock<LinkStateDatabase>>,
+    /// 跨重启的去重日志（按目标节点记录 route_id）
+    route_log: Option<Arc<RouteIdLog>>,
+    /// 等待送达回执的本地发出的消息
+    receipts: PendingReplies,
+    /// 从网络收到的路由消息的签名校验策略
+    signature_policy: RouteSignaturePolicy,
+    /// 重复广播的识别（未启用时为 `None`）
+    broadcast_guard: Option<BroadcastGuard>,
+    /// 服务器指标（被抑制的广播计数）
+    metrics: Arc<ServerMetrics>,
 }
 
 impl MessageRouter {
     pub fn new(
         local_node_id: Uuid,
         peer_manager: Arc<PeerManager>,
+    ) -> Self {
+        Self::new_with_mode(local_node_id, peer_manager, RoutingMode::DistanceVector)
+    }
+
+    /// 创建指定路由模式的路由器
+    pub fn new_with_mode(
+        local_node_id: Uuid,
+        peer_manager: Arc<PeerManager>,
+        routing_mode: RoutingMode,
     ) -> Self {
         Self {
             routing_table: Arc::new(RwLock::new(RoutingTable::new())),
@@ -165,8 +274,57 @@ impl MessageRouter {
             peer_manager,
             message_cache: Arc::new(RwLock::new(HashMap::new())),
             cache_cleanup_interval: std::time::Duration::from_secs(300), // 5分钟
+            routing_mode,
+            link_state: Arc::new(RwLock::new(LinkStateDatabase::new(local_node_id))),
+            route_log: None,
+            receipts: PendingReplies::new(),
+            signature_policy: RouteSignaturePolicy::Off,
+            broadcast_guard: None,
+            metrics: Arc::new(ServerMetrics::new()),
         }
     }
+
+    /// 使用去重日志：内存缓存过期或服务器重启后，窗口内重发的消息仍不会被再次转发
+    pub fn with_route_log(mut self, route_log: Arc<RouteIdLog>) -> Self {
+        self.route_log = Some(route_log);
+        self
+    }
+
+    /// 窗口内内容相同的广播只发出第一份：客户端重试生成的新 `route_id` 不会让广播成倍增加
+    pub fn with_broadcast_dedup(mut self, window: std::time::Duration) -> Self {
+        self.broadcast_guard = Some(BroadcastGuard::new(window));
+        self
+    }
+
+    /// 使用指定的指标（例如与服务器共享）
+    pub fn with_metrics(mut self, metrics: Arc<ServerMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// 设置路由消息的签名校验策略
+    pub fn with_signature_policy(mut self, policy: RouteSignaturePolicy) -> Self {
+        self.signature_policy = policy;
+        self
+    }
+
+    /// 按签名策略检查从网络收到的路由消息
+    ///
+    /// 源节点在本服务器登记过公钥时，签名公钥必须是它当前的公钥；未登记的源节点只校验签名本身。
+    pub fn check_signature(&self, routed_message: &RoutedMessage) -> Result<(), IdentityError> {
+        let Some(public_key) = identity::check_routed(routed_message, self.signature_policy, self.peer_manager.network_id())? else {
+            return Ok(());
+        };
+        match self.peer_manager.identities().current(&routed_message.source_node) {
+            Some(current) if current != public_key => Err(IdentityError::KeyMismatch(routed_message.source_node)),
+            _ => Ok(()),
+        }
+    }
+
+    /// 当前路由模式
+    pub fn routing_mode(&self) -> RoutingMode {
+        self.routing_mode
+    }
     
     /// 路由消息到目标节点
     #[allow(dead_code)]
@@ -178,12 +336,19 @@ impl MessageRouter {
     ) -> Result<()> {
         let routes_len = { self.routing_table.read().await.get_all_routes().len() };
         debug!(
-            "路由请求: 目标={} hops={} 本地={} 当前路由条目={}",
-            destination, max_hops, self.local_node_id, routes_len
+            "{}",
+            tr!(
+                "路由请求: 目标={} hops={} 本地={} 当前路由条目={}",
+                "Route request: destination={} hops={} local={} current routes={}",
+                destination,
+                max_hops,
+                self.local_node_id,
+                routes_len,
+            )
         );
         // 如果目标是本地节点，直接处理
         if destination == self.local_node_id {
-            debug!("目标是本地节点，直接处理消息");
+            debug!("{}", tr!("目标是本地节点，直接处理消息", "Destination is the local node, handling the message directly"));
             return self.handle_local_message(message).await;
         }
         
@@ -194,46 +359,153 @@ impl MessageRouter {
             max_hops,
         );
         debug!(
-            "构造路由消息: route_id={} src={} dst={} max_hops={}",
-            routed_message.route_id,
-            routed_message.source_node,
-            routed_message.destination_node,
-            routed_message.max_hops
+            "{}",
+            tr!(
+                "构造路由消息: route_id={} src={} dst={} max_hops={}",
+                "Built routed message: route_id={} src={} dst={} max_hops={}",
+                routed_message.route_id,
+                routed_message.source_node,
+                routed_message.destination_node,
+                routed_message.max_hops,
+            )
         );
         
         self.forward_message(routed_message).await
     }
     
+    /// 路由消息到目标节点，并等待目标节点发回的送达回执
+    ///
+    /// 逐跳的 ACK 只说明下一跳收到了消息；回执由目标节点发出，沿反向路由回到本节点。
+    /// `wait` 内未收到回执时返回错误（消息可能仍在途中、已暂存或已丢失）。
+    pub async fn route_with_receipt(
+        &self,
+        message: Message,
+        destination: Uuid,
+        max_hops: u32,
+        wait: std::time::Duration,
+    ) -> Result<DeliveryReceipt> {
+        let routed = RoutedMessage::new(message, self.local_node_id, destination, max_hops).with_receipt();
+        if destination == self.local_node_id {
+            let receipt = DeliveryReceipt { route_id: routed.route_id, destination };
+            self.handle_local_message(routed.original_message).await?;
+            return Ok(receipt);
+        }
+        let request = routed.original_message.clone();
+        let reply = self.receipts.request(&request, wait, self.forward_message(routed)).await?;
+        Ok(serde_json::from_value(reply.payload)?)
+    }
+
+    /// 多播消息到一组节点
+    pub async fn route_multicast(&self, message: Message, members: Vec<Uuid>, max_hops: u32) -> Result<usize> {
+        self.forward_multicast(RoutedMessage::multicast(message, self.local_node_id, members, max_hops)).await
+    }
+
+    /// 转发多播消息：按路由表把目标节点按下一跳分组，每个下一跳只发一份副本，由它继续分叉
+    ///
+    /// 每条链路上至多经过一份负载；下一跳就是目标节点时副本为普通的单播路由消息。
+    /// 没有路由的目标节点在开启离线暂存时暂存，否则丢弃。返回发出的副本数。
+    pub async fn forward_multicast(&self, mut routed_message: RoutedMessage) -> Result<usize> {
+        if self.is_message_cached(&routed_message.route_id).await {
+            debug!("{}", tr!("多播消息 {} 已经处理过，跳过", "Multicast message {} already processed, skipping", routed_message.route_id));
+            return Ok(0);
+        }
+        self.cache_message_id(routed_message.route_id).await;
+        if !routed_message.increment_hop() {
+            warn!("{}", tr!("消息 {} 达到最大跳数限制", "Message {} reached the maximum hop count", routed_message.route_id));
+            return Err(anyhow::anyhow!("达到最大跳数限制"));
+        }
+
+        let mut members = std::mem::take(&mut routed_message.multicast);
+        if let Some(pos) = members.iter().position(|member| *member == self.local_node_id) {
+            members.remove(pos);
+            self.handle_local_message(routed_message.original_message.clone()).await?;
+        }
+        let mut tree = {
+            let routing_table = self.routing_table.read().await;
+            routing_table.distribution_tree(&members)
+        };
+
+        let mut copies = 0;
+        for (next_hop, branch_members) in std::mem::take(&mut tree.branches) {
+            let Some(peer) = self.peer_manager.get_peer(&next_hop).await else {
+                tree.unreachable.extend(branch_members);
+                continue;
+            };
+            let branch = routed_message.branch(branch_members);
+            match peer.read().await.send_message(&branch.to_message()?).await {
+                Ok(()) => copies += 1,
+                Err(e) => warn!("{}", tr!("向下一跳 {} 转发多播消息 {} 失败: {}", "Failed to forward multicast message {} to next hop {}: {}", next_hop, routed_message.route_id, e)),
+            }
+        }
+        for member in tree.unreachable {
+            if !self.store_if_offline(&routed_message.branch(vec![member])).await {
+                debug!("{}", tr!("多播消息 {} 的目标 {} 没有路由，丢弃", "Multicast message {} has no route to member {}, dropping", routed_message.route_id, member));
+            }
+        }
+        debug!("{}", tr!("多播消息 {} 发出 {} 份副本，覆盖 {} 个目标", "Multicast message {} sent as {} copies covering {} members", routed_message.route_id, copies, members.len()));
+        Ok(copies)
+    }
+
     /// 转发路由消息
-    pub async fn forward_message(&self, mut routed_message: RoutedMessage) -> Result<()> {
+    pub async fn forward_message(&self, routed_message: RoutedMessage) -> Result<()> {
+        self.forward(routed_message, true).await.map(|_| ())
+    }
+
+    /// 转发路由消息但从不广播：没有到目标节点的路由时既不转发也不暂存，返回 `false`
+    pub async fn forward_without_flood(&self, routed_message: RoutedMessage) -> Result<bool> {
+        self.forward(routed_message, false).await
+    }
+
+    /// 转发路由消息；`flood` 为 `false` 时没有路由的消息不广播，返回 `false`
+    async fn forward(&self, mut routed_message: RoutedMessage, flood: bool) -> Result<bool> {
+        if !routed_message.multicast.is_empty() {
+            return self.forward_multicast(routed_message).await.map(|_| true);
+        }
         debug!(
-            "开始转发: route_id={} src={} dst={} hop={}/{}",
-            routed_message.route_id,
-            routed_message.source_node,
-            routed_message.destination_node,
-            routed_message.hop_count,
-            routed_message.max_hops
+            "{}",
+            tr!(
+                "开始转发: route_id={} src={} dst={} hop={}/{}",
+                "Forwarding: route_id={} src={} dst={} hop={}/{}",
+                routed_message.route_id,
+                routed_message.source_node,
+                routed_message.destination_node,
+                routed_message.hop_count,
+                routed_message.max_hops,
+            )
         );
         // 检查是否已经处理过这个消息
         if self.is_message_cached(&routed_message.route_id).await {
-            debug!("消息 {} 已经处理过，跳过", routed_message.route_id);
-            return Ok(());
+            debug!("{}", tr!("消息 {} 已经处理过，跳过", "Message {} already processed, skipping", routed_message.route_id));
+            return Ok(true);
+        }
+        
+        if let Some(route_log) = &self.route_log
+            && !route_log.record(routed_message.destination_node, routed_message.route_id)
+        {
+            debug!("{}", tr!("消息 {} 在去重窗口内已转发过，跳过", "Message {} already forwarded within the dedup window, skipping", routed_message.route_id));
+            return Ok(true);
         }
         
         // 缓存消息ID
         self.cache_message_id(routed_message.route_id).await;
-        debug!("缓存消息ID: {}", routed_message.route_id);
+        debug!("{}", tr!("缓存消息ID: {}", "Cached message ID: {}", routed_message.route_id));
         
         // 检查跳数限制
         if !routed_message.increment_hop() {
-            warn!("消息 {} 达到最大跳数限制", routed_message.route_id);
+            warn!("{}", tr!("消息 {} 达到最大跳数限制", "Message {} reached the maximum hop count", routed_message.route_id));
             return Err(anyhow::anyhow!("达到最大跳数限制"));
         }
         
         // 如果目标是本地节点，处理消息
         if routed_message.destination_node == self.local_node_id {
-            debug!("转发目标解析为本地节点，交由本地处理");
-            return self.handle_local_message(routed_message.original_message).await;
+            debug!("{}", tr!("转发目标解析为本地节点，交由本地处理", "Forward destination resolves to the local node, handling locally"));
+            if routed_message.receipt_requested {
+                debug!("{}", tr!("向源节点 {} 发回消息 {} 的送达回执", "Sending delivery receipt to source node {} for message {}", routed_message.source_node, routed_message.route_id));
+                if let Err(e) = Box::pin(self.forward_message(routed_message.receipt()?)).await {
+                    warn!("{}", tr!("发回送达回执失败: {}", "Failed to send delivery receipt: {}", e));
+                }
+            }
+            return self.handle_local_message(routed_message.original_message).await.map(|()| true);
         }
         
         // 查找下一跳
@@ -242,9 +514,13 @@ impl MessageRouter {
             routing_table.get_next_hop(&routed_message.destination_node)
         };
         debug!(
-            "查找下一跳: dst={} next_hop={:?}",
-            routed_message.destination_node,
-            next_hop
+            "{}",
+            tr!(
+                "查找下一跳: dst={} next_hop={:?}",
+                "Next hop lookup: dst={} next_hop={:?}",
+                routed_message.destination_node,
+                next_hop,
+            )
         );
         
         match next_hop {
@@ -254,60 +530,119 @@ impl MessageRouter {
                     let peer_addr = peer.read().await.addr();
                     let peer_status_dbg = format!("{:?}", peer.read().await.status);
                     debug!(
-                        "下一跳peer可用: id={} addr={} status={}",
-                        next_hop_id,
-                        peer_addr,
-                        peer_status_dbg
+                        "{}",
+                        tr!(
+                            "下一跳peer可用: id={} addr={} status={}",
+                            "Next hop peer available: id={} addr={} status={}",
+                            next_hop_id,
+                            peer_addr,
+                            peer_status_dbg,
+                        )
                     );
-                    let message = routed_message.to_message();
+                    let message = routed_message.to_message()?;
                     peer.read().await.send_message(&message).await?;
                     
                     debug!(
-                        "转发消息 {} 到下一跳 {} (目标: {})",
-                        routed_message.route_id,
-                        next_hop_id,
-                        routed_message.destination_node
+                        "{}",
+                        tr!(
+                            "转发消息 {} 到下一跳 {} (目标: {})",
+                            "Forwarding message {} to next hop {} (destination: {})",
+                            routed_message.route_id,
+                            next_hop_id,
+                            routed_message.destination_node,
+                        )
                     );
                 } else {
                     // 下一跳节点不可达，移除路由并尝试广播
-                    warn!("下一跳节点 {} 不可达，移除相关路由", next_hop_id);
-                    self.routing_table.write().await.remove_routes_via(&next_hop_id);
+                    warn!("{}", tr!("下一跳节点 {} 不可达，移除相关路由", "Next hop {} is unreachable, removing its routes", next_hop_id));
+                    self.modify_routes(|table| table.remove_routes_via(&next_hop_id)).await;
+                    if !flood {
+                        return Ok(false);
+                    }
+                    if self.store_if_offline(&routed_message).await {
+                        return Ok(true);
+                    }
                     
                     // 尝试广播到所有连接的节点
                     self.broadcast_message(routed_message).await?;
                 }
             }
             None => {
+                if !flood {
+                    return Ok(false);
+                }
+                if self.store_if_offline(&routed_message).await {
+                    return Ok(true);
+                }
                 // 没有找到路由，广播到所有连接的节点
-                debug!("没有找到到 {} 的路由，广播消息", routed_message.destination_node);
+                debug!("{}", tr!("没有找到到 {} 的路由，广播消息", "No route to {}, broadcasting the message", routed_message.destination_node));
                 self.broadcast_message(routed_message).await?;
             }
         }
         
-        Ok(())
+        Ok(true)
     }
     
+    /// 启用离线消息暂存且目标节点不在线时暂存消息，返回是否已暂存
+    async fn store_if_offline(&self, routed_message: &RoutedMessage) -> bool {
+        let Some(store) = self.peer_manager.offline_store() else {
+            return false;
+        };
+        if self.peer_manager.get_peer(&routed_message.destination_node).await.is_some() {
+            return false;
+        }
+        store.push(routed_message.clone())
+    }
+
     /// 广播消息到所有连接的节点
     async fn broadcast_message(&self, routed_message: RoutedMessage) -> Result<()> {
+        if let Some(guard) = &self.broadcast_guard
+            && let BroadcastCheck::Duplicate { suppressed } = guard.check(&routed_message, std::time::Instant::now())
+        {
+            ServerMetrics::incr(&self.metrics.broadcasts_suppressed);
+            if suppressed == 1 {
+                warn!(
+                    "{}",
+                    tr!(
+                        "节点 {} 发往 {} 的相同内容被重复广播，窗口内只发出第一份（route_id={}）",
+                        "Node {} keeps broadcasting the same content to {}; only the first copy in the window is sent (route_id={})",
+                        routed_message.source_node,
+                        routed_message.destination_node,
+                        routed_message.route_id,
+                    )
+                );
+            } else {
+                debug!("{}", tr!("丢弃重复广播 {}（窗口内第 {} 份）", "Dropping duplicate broadcast {} (copy {} in window)", routed_message.route_id, suppressed + 1));
+            }
+            return Ok(());
+        }
         let peers = self.peer_manager.get_authenticated_peers().await;
-        let message = routed_message.to_message();
+        let message = routed_message.to_message()?;
         
         let mut success_count = 0;
         let mut error_count = 0;
         
         debug!(
-            "开始广播: route_id={} 源={} 候选节点数={}",
-            routed_message.route_id,
-            routed_message.source_node,
-            peers.len()
+            "{}",
+            tr!(
+                "开始广播: route_id={} 源={} 候选节点数={}",
+                "Broadcasting: route_id={} source={} candidates={}",
+                routed_message.route_id,
+                routed_message.source_node,
+                peers.len(),
+            )
         );
         for p in &peers {
             let g = p.read().await;
             debug!(
-                "广播候选: id={} addr={} status={:?}",
-                g.id,
-                g.addr(),
-                g.status
+                "{}",
+                tr!(
+                    "广播候选: id={} addr={} status={:?}",
+                    "Broadcast candidate: id={} addr={} status={:?}",
+                    g.id,
+                    g.addr(),
+                    g.status,
+                )
             );
         }
         for peer in peers {
@@ -321,20 +656,24 @@ impl MessageRouter {
             match peer.read().await.send_message(&message).await {
                 Ok(_) => {
                     success_count += 1;
-                    debug!("广播消息到节点 {}", peer_id);
+                    debug!("{}", tr!("广播消息到节点 {}", "Broadcasting message to node {}", peer_id));
                 }
                 Err(e) => {
                     error_count += 1;
-                    warn!("广播消息到节点 {} 失败: {}", peer_id, e);
+                    warn!("{}", tr!("广播消息到节点 {} 失败: {}", "Failed to broadcast message to node {}: {}", peer_id, e));
                 }
             }
         }
         
         info!(
-            "广播消息 {} 完成: 成功 {}, 失败 {}",
-            routed_message.route_id,
-            success_count,
-            error_count
+            "{}",
+            tr!(
+                "广播消息 {} 完成: 成功 {}, 失败 {}",
+                "Broadcast of message {} finished: {} succeeded, {} failed",
+                routed_message.route_id,
+                success_count,
+                error_count,
+            )
         );
         
         Ok(())
@@ -342,18 +681,23 @@ impl MessageRouter {
     
     /// 处理本地消息
     async fn handle_local_message(&self, message: Message) -> Result<()> {
-        info!("处理本地消息: {:?}", message.message_type);
+        info!("{}", tr!("处理本地消息: {:?}", "Handling local message: {:?}", message.message_type));
         
         // 这里可以根据消息类型进行不同的处理
         match message.message_type {
+            MessageType::Receipt => {
+                if self.receipts.resolve(message).is_some() {
+                    debug!("{}", tr!("收到无人等待的送达回执（可能已超时）", "Received a delivery receipt nobody is waiting for (may have timed out)"));
+                }
+            }
             MessageType::Data => {
                 // 处理数据消息 - 这是路由到本地节点的消息，应该在服务器层面处理
                 // 由于这是在路由器中，我们只记录日志，实际的客户端消息传递应该在服务器层处理
-                debug!("接收到路由到本地的数据消息: {:?}", message.payload);
-                info!("消息已到达目标节点（服务器本身），但这可能不是预期行为");
+                debug!("{}", tr!("接收到路由到本地的数据消息 {}", "Received data message {} routed to the local node", message.id));
+                info!("{}", tr!("消息已到达目标节点（服务器本身），但这可能不是预期行为", "Message reached the target node (the server itself), which may not be intended"));
             }
             _ => {
-                debug!("接收到其他类型消息: {:?}", message.message_type);
+                debug!("{}", tr!("接收到其他类型消息: {:?}", "Received message of another type: {:?}", message.message_type));
             }
         }
         
@@ -361,24 +705,226 @@ impl MessageRouter {
     }
     
     /// 更新路由表
+    ///
+    /// 链路状态模式下只接受直连路由（`node_id == next_hop`），将其记为本地邻接并重新泛洪；
+    /// 多跳路由由拓扑图计算得出，忽略外部推导的条目。
     pub async fn update_routing_table(&self, node_id: Uuid, next_hop: Uuid, distance: u32) {
-        self.routing_table.write().await.add_route(node_id, next_hop, distance);
+        if self.routing_mode == RoutingMode::LinkState {
+            if node_id != next_hop {
+                debug!("{}", tr!("链路状态模式忽略推导路由: {} via {}", "Link-state mode ignores derived route: {} via {}", node_id, next_hop));
+                return;
+            }
+            let changed = self.link_state.write().await.add_local_link(node_id, distance);
+            if changed {
+                self.originate_link_state().await;
+            }
+            return;
+        }
+        // 距离相同时改走连接更久的下一跳：长期在线的节点更不容易中途掉线
+        let current = {
+            let table = self.routing_table.read().await;
+            table.get_next_hop(&node_id).filter(|_| table.get_distance(&node_id) == Some(distance))
+        };
+        if let Some(current) = current
+            && current != next_hop
+            && self.is_more_stable(next_hop, current).await
+        {
+            debug!("{}", tr!("改用更稳定的下一跳: {} via {} (原: {})", "Switching to a more stable next hop: {} via {} (was: {})", node_id, next_hop, current));
+            self.modify_routes(|table| {
+                table.remove_route(&node_id);
+                table.add_route(node_id, next_hop, distance);
+            })
+            .await;
+            return;
+        }
+        self.modify_routes(|table| table.add_route(node_id, next_hop, distance)).await;
+    }
+
+    /// `candidate` 是否比 `current` 连接得更久；`current` 已不在线时总是成立
+    async fn is_more_stable(&self, candidate: Uuid, current: Uuid) -> bool {
+        match (self.peer_manager.connected_for(&candidate).await, self.peer_manager.connected_for(&current).await) {
+            (Some(candidate), Some(current)) => candidate > current,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
     }
     
     /// 移除节点的路由
     pub async fn remove_node_routes(&self, node_id: &Uuid) {
-        let mut routing_table = self.routing_table.write().await;
-        routing_table.remove_route(node_id);
-        routing_table.remove_routes_via(node_id);
+        if self.routing_mode == RoutingMode::LinkState {
+            let changed = self.link_state.write().await.remove_local_link(node_id);
+            if changed {
+                self.originate_link_state().await;
+            }
+            return;
+        }
+        self.modify_routes(|table| {
+            table.remove_route(node_id);
+            table.remove_routes_via(node_id);
+        })
+        .await;
+    }
+
+    /// 修改路由表，并为变化的条目发布 `route.added` / `route.removed` 事件
+    async fn modify_routes(&self, change: impl FnOnce(&mut RoutingTable)) {
+        Self::modify_table(&self.routing_table, self.peer_manager.events(), change).await;
+    }
+
+    async fn modify_table(routing_table: &RwLock<RoutingTable>, events: &EventBus, change: impl FnOnce(&mut RoutingTable)) {
+        let mut table = routing_table.write().await;
+        if !events.is_active() {
+            change(&mut table);
+            return;
+        }
+        let before: HashMap<Uuid, (Uuid, u32)> = table.get_all_routes().into_iter().map(|(d, h, m)| (d, (h, m))).collect();
+        change(&mut table);
+        let after: HashMap<Uuid, (Uuid, u32)> = table.get_all_routes().into_iter().map(|(d, h, m)| (d, (h, m))).collect();
+        drop(table);
+        for (destination, (next_hop, distance)) in &after {
+            if before.get(destination).map(|(hop, _)| hop) != Some(next_hop) {
+                events.emit(EventKind::RouteAdded, Some(*destination), json!({ "next_hop": next_hop, "distance": distance }));
+            }
+        }
+        for (destination, (next_hop, _)) in &before {
+            if !after.contains_key(destination) {
+                events.emit(EventKind::RouteRemoved, Some(*destination), json!({ "next_hop": next_hop }));
+            }
+        }
+    }
+
+    /// 生成新的本地链路状态通告，重新计算路由并泛洪给所有已认证节点
+    async fn originate_link_state(&self) {
+        let mut lsa = self.link_state.write().await.originate();
+        Self::sign_link_state(&self.peer_manager, &mut lsa);
+        Self::recompute_link_state_routes(&self.link_state, &self.routing_table, self.peer_manager.events()).await;
+        Self::flood_link_state(&self.peer_manager, &lsa, &[]).await;
+    }
+
+    /// 处理收到的链路状态通告：安装更新的通告、重算路由并继续泛洪
+    pub async fn handle_link_state_update(&self, from: Uuid, message: &Message) -> Result<()> {
+        if self.routing_mode != RoutingMode::LinkState {
+            debug!("{}", tr!("未启用链路状态模式，忽略来自 {} 的链路状态通告", "Link-state mode is disabled, ignoring link-state advertisement from {}", from));
+            return Ok(());
+        }
+
+        let lsa: LinkStateAdvertisement = serde_json::from_value(message.payload.clone())?;
+        let (origin, sequence) = (lsa.origin, lsa.sequence);
+        // 来源节点自己发来的通告直接接受，附带公钥时校验签名并登记该公钥；
+        // 其他节点转发的通告只用登记表中来源节点的公钥校验，不信任通告自带的公钥
+        let verified = if origin == from {
+            match &lsa.public_key {
+                Some(public_key) => identity::verify_link_state(&lsa, public_key, self.peer_manager.network_id())
+                    .and_then(|()| self.peer_manager.identities().admit(origin, Some(public_key))),
+                None => Ok(()),
+            }
+        } else {
+            self.peer_manager
+                .identities()
+                .current(&origin)
+                .ok_or(IdentityError::UnknownKey(origin))
+                .and_then(|public_key| identity::verify_link_state(&lsa, &public_key, self.peer_manager.network_id()))
+        };
+        if let Err(e) = verified {
+            warn!("{}", tr!("丢弃节点 {} 发来的来源为 {} 的链路状态通告: {}", "Dropping link-state advertisement for origin {1} sent by node {0}: {2}", from, origin, e));
+            return Ok(());
+        }
+        let installed = self.link_state.write().await.install(lsa.clone());
+        if !installed {
+            return Ok(());
+        }
+
+        debug!("{}", tr!("安装链路状态通告: origin={} seq={} 来自 {}", "Installed link-state advertisement: origin={} seq={} from {}", origin, sequence, from));
+        Self::recompute_link_state_routes(&self.link_state, &self.routing_table, self.peer_manager.events()).await;
+        Self::flood_link_state(&self.peer_manager, &lsa, &[from, origin]).await;
+        Ok(())
+    }
+
+    /// 用服务器身份密钥签名本地通告，经其他节点转发后仍能通过来源校验
+    fn sign_link_state(peer_manager: &PeerManager, lsa: &mut LinkStateAdvertisement) {
+        if let Some(identity) = peer_manager.identity() {
+            identity.sign_link_state(lsa, peer_manager.network_id());
+        }
+    }
+
+    /// 基于链路状态数据库重新计算路由表
+    async fn recompute_link_state_routes(
+        link_state: &Arc<RwLock<LinkStateDatabase>>,
+        routing_table: &Arc<RwLock<RoutingTable>>,
+        events: &EventBus,
+    ) {
+        let routes = link_state.read().await.compute_routes();
+        Self::modify_table(routing_table, events, |table| table.replace_routes(routes)).await;
+    }
+
+    /// 泛洪链路状态通告到已认证节点（跳过排除列表中的节点）
+    async fn flood_link_state(
+        peer_manager: &Arc<PeerManager>,
+        lsa: &LinkStateAdvertisement,
+        exclude: &[Uuid],
+    ) {
+        let message = match Message::link_state_update(lsa) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("{}", tr!("构造链路状态通告失败: {}", "Failed to build link-state advertisement: {}", e));
+                return;
+            }
+        };
+        for peer in peer_manager.get_authenticated_peers().await {
+            let guard = peer.read().await;
+            if exclude.contains(&guard.id) {
+                continue;
+            }
+            if let Err(e) = guard.send_message(&message).await {
+                warn!("{}", tr!("泛洪链路状态通告到 {} 失败: {}", "Failed to flood link-state advertisement to {}: {}", guard.id, e));
+            }
+        }
+    }
+
+    /// 启动链路状态刷新任务：周期性重新通告本地邻接关系，并老化过期通告
+    pub fn start_link_state_refresh_task(
+        &self,
+        refresh_interval: std::time::Duration,
+        max_age: std::time::Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let link_state = self.link_state.clone();
+        let routing_table = self.routing_table.clone();
+        let peer_manager = self.peer_manager.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(refresh_interval);
+
+            loop {
+                interval.tick().await;
+
+                let (mut lsa, expired) = {
+                    let mut db = link_state.write().await;
+                    let expired = db.expire(max_age);
+                    (db.originate(), expired)
+                };
+                Self::sign_link_state(&peer_manager, &mut lsa);
+                Self::recompute_link_state_routes(&link_state, &routing_table, peer_manager.events()).await;
+                Self::flood_link_state(&peer_manager, &lsa, &[]).await;
+
+                debug!("{}", tr!("刷新本地链路状态通告 seq={}，老化通告 {} 条", "Refreshed local link-state advertisement seq={}, expired {} advertisements", lsa.sequence, expired));
+            }
+        })
     }
     
     /// 获取路由表快照
     pub async fn get_routing_table_snapshot(&self) -> Vec<(Uuid, Uuid, u32)> {
         let snapshot = self.routing_table.read().await.get_all_routes();
-        debug!("路由表快照生成，条目数: {}", snapshot.len());
+        debug!("{}", tr!("路由表快照生成，条目数: {}", "Routing table snapshot taken, entries: {}", snapshot.len()));
         snapshot
     }
     
+    /// 获取链路状态数据库中的全部链路（非链路状态模式下为空）
+    pub async fn get_link_state_links(&self) -> Vec<(Uuid, Uuid, u32)> {
+        if self.routing_mode != RoutingMode::LinkState {
+            return Vec::new();
+        }
+        self.link_state.read().await.links()
+    }
+    
     /// 检查消息是否已缓存
     async fn is_message_cached(&self, message_id: &Uuid) -> bool {
         self.message_cache.read().await.contains_key(message_id)
@@ -387,9 +933,40 @@ impl MessageRouter {
     /// 缓存消息ID
     async fn cache_message_id(&self, message_id: Uuid) {
         self.message_cache.write().await.insert(message_id, std::time::Instant::now());
-        debug!("缓存消息ID完成: {}", message_id);
+        debug!("{}", tr!("缓存消息ID完成: {}", "Finished caching message ID: {}", message_id));
     }
     
+    /// 路由表的近似内存占用（字节）
+    pub async fn routing_table_bytes(&self) -> usize {
+        self.routing_table.read().await.approx_bytes()
+    }
+
+    /// 淘汰距离最远的间接路由，直到路由表占用不超过 `limit` 字节，返回淘汰数
+    pub async fn evict_routes(&self, limit: usize) -> usize {
+        self.routing_table.write().await.evict_farthest(limit)
+    }
+
+    /// 去重缓存的近似内存占用（字节）
+    pub async fn message_cache_bytes(&self) -> usize {
+        self.message_cache.read().await.len() * CACHE_ENTRY_BYTES
+    }
+
+    /// 淘汰最早的去重缓存条目，直到占用不超过 `limit` 字节，返回淘汰数
+    pub async fn evict_message_cache(&self, limit: usize) -> usize {
+        let mut cache = self.message_cache.write().await;
+        let excess = (cache.len() * CACHE_ENTRY_BYTES).saturating_sub(limit).div_ceil(CACHE_ENTRY_BYTES);
+        if excess == 0 {
+            return 0;
+        }
+        let mut entries: Vec<(Uuid, std::time::Instant)> = cache.iter().map(|(&id, &at)| (id, at)).collect();
+        entries.sort_by_key(|(_, at)| *at);
+        for (id, _) in entries.into_iter().take(excess) {
+            cache.remove(&id);
+        }
+        cache.shrink_to_fit();
+        excess
+    }
+
     /// 启动缓存清理任务
     pub fn start_cache_cleanup_task(&self) -> tokio::task::JoinHandle<()> {
         let message_cache = self.message_cache.clone();
@@ -409,7 +986,7 @@ impl MessageRouter {
                     now.duration_since(timestamp) < std::time::Duration::from_secs(300)
                 });
                 
-                debug!("清理消息缓存，当前缓存大小: {}", cache.len());
+                debug!("{}", tr!("清理消息缓存，当前缓存大小: {}", "Pruned message cache, current size: {}", cache.len()));
             }
         })
     }
@@ -417,24 +994,24 @@ impl MessageRouter {
     /// 处理路由发现
     #[allow(dead_code)]
     pub async fn handle_route_discovery(&self, source: Uuid, target: Uuid) -> Result<()> {
-        debug!("处理路由发现: source={} target={}", source, target);
+        debug!("{}", tr!("处理路由发现: source={} target={}", "Handling route discovery: source={} target={}", source, target));
         // 简单的路由发现：如果我们知道目标节点，返回路由信息
         let routing_table = self.routing_table.read().await;
         
-        if let Some(next_hop) = routing_table.get_next_hop(&target) {
-            if let Some(distance) = routing_table.get_distance(&target) {
-                // 发送路由响应给源节点
-                let route_info = serde_json::json!({
-                    "target": target,
-                    "next_hop": next_hop,
-                    "distance": distance + 1
-                });
-                
-                let response = Message::new(MessageType::Data, route_info);
-                self.route_message(response, source, 10).await?;
-                
-                debug!("发送路由信息给 {}: {} -> {} (距离: {})", source, target, next_hop, distance + 1);
-            }
+        if let Some(next_hop) = routing_table.get_next_hop(&target)
+            && let Some(distance) = routing_table.get_distance(&target)
+        {
+            // 发送路由响应给源节点
+            let route_info = serde_json::json!({
+                "target": target,
+                "next_hop": next_hop,
+                "distance": distance + 1
+            });
+            
+            let response = Message::new(MessageType::Data, route_info);
+            self.route_message(response, source, 10).await?;
+            
+            debug!("{}", tr!("发送路由信息给 {}: {} -> {} (距离: {})", "Sending route info to {}: {} -> {} (distance: {})", source, target, next_hop, distance + 1));
         }
         
         Ok(())
@@ -520,6 +1097,25 @@ mod tests {
         assert_eq!(routed.source_node, local_info.id);
     }
 
+    #[test]
+    fn test_evict_farthest_keeps_direct_routes() {
+        let mut table = RoutingTable::new();
+        let (direct, near, far, hop) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        table.add_route(direct, direct, 1);
+        table.add_route(near, hop, 2);
+        table.add_route(far, hop, 5);
+
+        // 超出一条，先淘汰最远的路由
+        assert_eq!(table.evict_farthest(2 * ROUTE_ENTRY_BYTES), 1);
+        assert!(table.get_next_hop(&far).is_none());
+        assert!(table.get_next_hop(&near).is_some());
+
+        // 直连路由不淘汰
+        assert_eq!(table.evict_farthest(0), 1);
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get_next_hop(&direct), Some(direct));
+    }
+
     #[tokio::test]
     async fn test_broadcast_when_no_route() {
         // 一个发送socket，两个不同的对端地址
@@ -567,6 +1163,34 @@ mod tests {
         assert_eq!(routed2.destination_node, dest);
     }
 
+    #[tokio::test]
+    async fn test_retried_broadcast_is_suppressed() {
+        let sock_local = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let local_addr = sock_local.local_addr().unwrap();
+        let sock_peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let peer_addr = sock_peer.local_addr().unwrap();
+
+        let local_info = NodeInfo::new("local_test".to_string(), local_addr, "testnet".to_string());
+        let peer_manager = Arc::new(PeerManager::new(local_info.clone(), 10));
+        let peer = peer_manager.add_peer(Arc::new(Connection::new(sock_local.clone(), peer_addr, local_addr))).await.unwrap();
+        peer.write().await.update_status(PeerStatus::Authenticated);
+
+        let metrics = Arc::new(ServerMetrics::new());
+        let router = MessageRouter::new(local_info.id, peer_manager.clone())
+            .with_broadcast_dedup(Duration::from_secs(2))
+            .with_metrics(metrics.clone());
+
+        // 重试生成新的 route_id，内容相同的广播只发出第一份
+        let dest = Uuid::new_v4();
+        for _ in 0..3 {
+            router.route_message(Message::data(serde_json::json!({"retry": true})), dest, 10).await.unwrap();
+        }
+        let mut buf = vec![0u8; 65536];
+        timeout(Duration::from_millis(300), sock_peer.recv_from(&mut buf)).await.unwrap().unwrap();
+        assert!(timeout(Duration::from_millis(200), sock_peer.recv_from(&mut buf)).await.is_err());
+        assert_eq!(metrics.broadcasts_suppressed.load(std::sync::atomic::Ordering::Relaxed), 2);
+    }
+
     #[tokio::test]
     async fn test_unreachable_next_hop_removes_route_and_broadcasts() {
         // 一个发送socket和一个已认证peer，用于接收广播
@@ -608,4 +1232,211 @@ mod tests {
         let still_exists = snapshot.iter().any(|(d, _, _)| *d == dest);
         assert!(!still_exists);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_receipt_returned_along_reverse_route() {
+        let sock_local = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let local_addr = sock_local.local_addr().unwrap();
+        let sock_dest = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let dest_addr = sock_dest.local_addr().unwrap();
+
+        let local_info = NodeInfo::new("local_test".to_string(), local_addr, "testnet".to_string());
+        let peer_manager = Arc::new(PeerManager::new(local_info.clone(), 10));
+        let peer = peer_manager.add_peer(Arc::new(Connection::new(sock_local.clone(), dest_addr, local_addr))).await.unwrap();
+        peer.write().await.update_status(PeerStatus::Authenticated);
+        let dest = peer.read().await.id;
+
+        let router = Arc::new(MessageRouter::new(local_info.id, peer_manager.clone()));
+        router.update_routing_table(dest, dest, 1).await;
+
+        let sender = router.clone();
+        let waiting = tokio::spawn(async move {
+            sender.route_with_receipt(Message::data(serde_json::json!({"k": "v"})), dest, 5, Duration::from_secs(2)).await
+        });
+
+        // 目标节点收到要求回执的消息，回执作为反向路由消息到达本节点
+        let mut buf = vec![0u8; 65536];
+        let (len, _from) = timeout(Duration::from_millis(500), sock_dest.recv_from(&mut buf)).await.unwrap().unwrap();
+        let received: Message = serde_json::from_slice(&buf[..len]).unwrap();
+        let routed = RoutedMessage::from_message(&received).unwrap();
+        assert!(routed.receipt_requested);
+        let receipt = routed.receipt().unwrap();
+        assert_eq!((receipt.source_node, receipt.destination_node), (dest, local_info.id));
+        router.forward_message(receipt).await.unwrap();
+
+        let delivered = waiting.await.unwrap().unwrap();
+        assert_eq!(delivered, DeliveryReceipt { route_id: routed.route_id, destination: dest });
+    }
+
+    #[test]
+    fn test_distribution_tree_groups_members_by_next_hop() {
+        let mut table = RoutingTable::new();
+        let (hop, direct, m1, m2, lost) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        table.add_route(hop, hop, 1);
+        table.add_route(direct, direct, 1);
+        table.add_route(m1, hop, 2);
+        table.add_route(m2, hop, 2);
+
+        let tree = table.distribution_tree(&[m1, direct, m2, m1, lost]);
+        assert_eq!(tree.branches.len(), 2);
+        assert_eq!(tree.branches[&hop], vec![m1, m2]);
+        assert_eq!(tree.branches[&direct], vec![direct]);
+        assert_eq!(tree.unreachable, vec![lost]);
+    }
+
+    #[tokio::test]
+    async fn test_multicast_sends_one_copy_per_next_hop() {
+        let sock_local = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let local_addr = sock_local.local_addr().unwrap();
+        let sock_hop = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sock_direct = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let local_info = NodeInfo::new("local_test".to_string(), local_addr, "testnet".to_string());
+        let peer_manager = Arc::new(PeerManager::new(local_info.clone(), 10));
+        let mut ids = Vec::new();
+        for sock in [&sock_hop, &sock_direct] {
+            let conn = Arc::new(Connection::new(sock_local.clone(), sock.local_addr().unwrap(), local_addr));
+            let peer = peer_manager.add_peer(conn).await.unwrap();
+            peer.write().await.update_status(PeerStatus::Authenticated);
+            ids.push(peer.read().await.id);
+        }
+        let (hop, direct) = (ids[0], ids[1]);
+
+        let router = MessageRouter::new(local_info.id, peer_manager.clone());
+        let (m1, m2) = (Uuid::new_v4(), Uuid::new_v4());
+        router.update_routing_table(direct, direct, 1).await;
+        router.update_routing_table(m1, hop, 2).await;
+        router.update_routing_table(m2, hop, 2).await;
+
+        let copies = router.route_multicast(Message::data(serde_json::json!({"k": "v"})), vec![m1, m2, direct], 5).await.unwrap();
+        assert_eq!(copies, 2);
+
+        // 经同一下一跳的两个目标共用一份副本，由下一跳继续分叉
+        let mut buf = vec![0u8; 65536];
+        let (len, _from) = timeout(Duration::from_millis(300), sock_hop.recv_from(&mut buf)).await.unwrap().unwrap();
+        let routed = RoutedMessage::from_message(&serde_json::from_slice(&buf[..len]).unwrap()).unwrap();
+        assert_eq!(routed.multicast, vec![m1, m2]);
+        assert_eq!(routed.destination_node, Uuid::nil());
+
+        // 直连的目标收到普通的单播路由消息
+        let (len, _from) = timeout(Duration::from_millis(300), sock_direct.recv_from(&mut buf)).await.unwrap().unwrap();
+        let routed = RoutedMessage::from_message(&serde_json::from_slice(&buf[..len]).unwrap()).unwrap();
+        assert!(routed.multicast.is_empty());
+        assert_eq!(routed.destination_node, direct);
+        assert_eq!(routed.original_message.payload, serde_json::json!({"k": "v"}));
+        assert!(timeout(Duration::from_millis(100), sock_hop.recv_from(&mut buf)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_link_state_mode_computes_multi_hop_route() {
+        let sock_local = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let local_addr = sock_local.local_addr().unwrap();
+        let sock_neighbor = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let neighbor_addr = sock_neighbor.local_addr().unwrap();
+
+        let conn = Arc::new(Connection::new(sock_local.clone(), neighbor_addr, local_addr));
+        let local_info = NodeInfo::new("local_test".to_string(), local_addr, "testnet".to_string());
+        let peer_manager = Arc::new(PeerManager::new(local_info.clone(), 10));
+        let peer = peer_manager.add_peer(conn).await.unwrap();
+        peer.write().await.update_status(PeerStatus::Authenticated);
+        let neighbor_id = peer.read().await.id;
+
+        let router = MessageRouter::new_with_mode(local_info.id, peer_manager.clone(), RoutingMode::LinkState);
+
+        // 建立直连链路后应向邻居泛洪本地通告
+        router.update_routing_table(neighbor_id, neighbor_id, 1).await;
+        let mut buf = vec![0u8; 65536];
+        let (len, _) = timeout(Duration::from_millis(300), sock_neighbor.recv_from(&mut buf)).await.unwrap().unwrap();
+        let flooded: Message = serde_json::from_slice(&buf[..len]).unwrap();
+        assert_eq!(flooded.message_type, MessageType::LinkStateUpdate);
+
+        // 邻居通告其后方还有一个节点
+        let remote = Uuid::new_v4();
+        let lsa = LinkStateAdvertisement {
+            origin: neighbor_id,
+            sequence: 1,
+            neighbors: vec![crate::link_state::LinkEntry { node_id: remote, cost: 1 }],
+            signature: None,
+            public_key: None,
+        };
+        router
+            .handle_link_state_update(neighbor_id, &Message::link_state_update(&lsa).unwrap())
+            .await
+            .unwrap();
+
+        let snapshot = router.get_routing_table_snapshot().await;
+        assert!(snapshot.contains(&(remote, neighbor_id, 2)));
+
+        // 邻居冒充 remote 通告的链路被丢弃；带有 remote 登记公钥签名的转发通告被接受
+        let behind = Uuid::new_v4();
+        let mut forwarded = LinkStateAdvertisement {
+            origin: remote,
+            sequence: 1,
+            neighbors: vec![
+                crate::link_state::LinkEntry { node_id: neighbor_id, cost: 1 },
+                crate::link_state::LinkEntry { node_id: behind, cost: 1 },
+            ],
+            signature: None,
+            public_key: None,
+        };
+        router
+            .handle_link_state_update(neighbor_id, &Message::link_state_update(&forwarded).unwrap())
+            .await
+            .unwrap();
+        assert!(!router.get_routing_table_snapshot().await.iter().any(|(dest, _, _)| *dest == behind));
+        let remote_identity = crate::identity::NodeIdentity::generate(remote);
+        peer_manager.identities().admit(remote, Some(&remote_identity.public_key_hex())).unwrap();
+        remote_identity.sign_link_state(&mut forwarded, "testnet");
+        router
+            .handle_link_state_update(neighbor_id, &Message::link_state_update(&forwarded).unwrap())
+            .await
+            .unwrap();
+        assert!(router.get_routing_table_snapshot().await.contains(&(behind, neighbor_id, 3)));
+
+        // 邻居用自己的密钥签名并附上公钥冒充 remote，与登记的公钥不符被丢弃
+        let forger = crate::identity::NodeIdentity::generate(remote);
+        let mut forged = LinkStateAdvertisement { sequence: 2, neighbors: Vec::new(), ..forwarded.clone() };
+        forger.sign_link_state(&mut forged, "testnet");
+        router
+            .handle_link_state_update(neighbor_id, &Message::link_state_update(&forged).unwrap())
+            .await
+            .unwrap();
+        assert!(router.get_routing_table_snapshot().await.contains(&(behind, neighbor_id, 3)));
+
+        // 链路断开后经该邻居的路由应消失
+        router.remove_node_routes(&neighbor_id).await;
+        assert!(router.get_routing_table_snapshot().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_equal_distance_prefers_longer_connected_next_hop() {
+        let sock_local = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let local_addr = sock_local.local_addr().unwrap();
+        let local_info = NodeInfo::new("local_test".to_string(), local_addr, "testnet".to_string());
+        let peer_manager = Arc::new(PeerManager::new(local_info.clone(), 10));
+
+        // 两个下一跳，其中一个已连接十分钟
+        let mut hops = Vec::new();
+        for connected in [Duration::ZERO, Duration::from_secs(600)] {
+            let addr = UdpSocket::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+            let peer = peer_manager.add_peer(Arc::new(Connection::new(sock_local.clone(), addr, local_addr))).await.unwrap();
+            let mut guard = peer.write().await;
+            guard.update_status(PeerStatus::Authenticated);
+            guard.created_at = std::time::Instant::now() - connected;
+            hops.push(guard.id);
+        }
+        let (fresh, stable) = (hops[0], hops[1]);
+
+        let router = MessageRouter::new(local_info.id, peer_manager.clone());
+        let dest = Uuid::new_v4();
+        router.update_routing_table(dest, fresh, 2).await;
+        router.update_routing_table(dest, stable, 2).await;
+        assert_eq!(router.routing_table.read().await.get_next_hop(&dest), Some(stable));
+
+        // 连接较新的节点不会抢走距离相同的路由，更短的路由仍然优先
+        router.update_routing_table(dest, fresh, 2).await;
+        assert_eq!(router.routing_table.read().await.get_next_hop(&dest), Some(stable));
+        router.update_routing_table(dest, fresh, 1).await;
+        assert_eq!(router.routing_table.read().await.get_next_hop(&dest), Some(fresh));
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// 服务名最大长度
+pub const MAX_SERVICE_NAME_LEN: usize = 128;
+/// 调用未指定超时时服务器等待结果的时长
+pub const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(10);
+/// 服务器等待调用结果的最长时长
+pub const MAX_CALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 已转发、等待提供者返回结果的调用
+#[derive(Debug)]
+struct PendingCall {
+    caller: Uuid,
+    provider: Uuid,
+    deadline: Instant,
+}
+
+/// 服务目录
+///
+/// 节点通过 `RegisterService` / `UnregisterService` 声明自己提供的服务（如 `storage.get`），
+/// `RpcCall` 由服务器转发给提供者（未指定提供者时轮询），提供者的 `RpcResult` 再按调用ID
+/// 转回调用方。结果只接受被转发调用的提供者发来的、且未超时的那一份。
+#[derive(Debug, Default)]
+pub struct ServiceDirectory {
+    providers: RwLock<HashMap<String, Vec<Uuid>>>,
+    next: AtomicUsize,
+    calls: std::sync::Mutex<HashMap<Uuid, PendingCall>>,
+}
+
+impl ServiceDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 校验服务名：字母、数字与 `.`、`_`、`-`
+    pub fn validate_name(service: &str) -> Result<(), &'static str> {
+        if service.is_empty() {
+            Err("服务名不能为空")
+        } else if service.len() > MAX_SERVICE_NAME_LEN {
+            Err("服务名过长")
+        } else if !service.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')) {
+            Err("服务名只能包含字母、数字与 . _ -")
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 注册服务，返回是否为新注册
+    pub async fn register(&self, peer_id: Uuid, service: &str) -> bool {
+        let mut providers = self.providers.write().await;
+        let list = providers.entry(service.to_string()).or_default();
+        if list.contains(&peer_id) {
+            return false;
+        }
+        list.push(peer_id);
+        true
+    }
+
+    /// 注销服务，返回是否存在该注册
+    pub async fn unregister(&self, peer_id: &Uuid, service: &str) -> bool {
+        let mut providers = self.providers.write().await;
+        let Some(list) = providers.get_mut(service) else { return false };
+        let before = list.len();
+        list.retain(|id| id != peer_id);
+        let removed = list.len() != before;
+        if list.is_empty() {
+            providers.remove(service);
+        }
+        removed
+    }
+
+    /// 移除节点注册的全部服务
+    pub async fn remove_peer(&self, peer_id: &Uuid) {
+        self.providers.write().await.retain(|_, list| {
+            list.retain(|id| id != peer_id);
+            !list.is_empty()
+        });
+    }
+
+    /// 服务的提供者（按注册顺序）
+    pub async fn providers(&self, service: &str) -> Vec<Uuid> {
+        self.providers.read().await.get(service).cloned().unwrap_or_default()
+    }
+
+    /// 已注册的服务及提供者数量
+    pub async fn services(&self) -> Vec<(String, usize)> {
+        let mut services: Vec<(String, usize)> = self.providers
+            .read()
+            .await
+            .iter()
+            .map(|(service, list)| (service.clone(), list.len()))
+            .collect();
+        services.sort();
+        services
+    }
+
+    /// 选择提供者的顺序：指定了 `target` 时只有它（须已注册该服务），否则从下一个轮询位置开始的全部提供者
+    pub async fn candidates(&self, service: &str, target: Option<Uuid>) -> Vec<Uuid> {
+        let mut providers = self.providers(service).await;
+        if let Some(target) = target {
+            providers.retain(|id| *id == target);
+        } else if !providers.is_empty() {
+            let start = self.next.fetch_add(1, Ordering::Relaxed) % providers.len();
+            providers.rotate_left(start);
+        }
+        providers
+    }
+
+    /// 记录已转发的调用，同时清理超时的调用
+    pub fn start_call(&self, call_id: Uuid, caller: Uuid, provider: Uuid, timeout: Duration) {
+        let now = Instant::now();
+        let mut calls = self.calls.lock().unwrap();
+        calls.retain(|_, call| call.deadline > now);
+        calls.insert(call_id, PendingCall { caller, provider, deadline: now + timeout });
+    }
+
+    /// 提供者返回调用结果，返回应转交的调用方；调用不存在、已超时或结果并非来自该提供者时返回 `None`
+    pub fn finish_call(&self, call_id: &Uuid, provider: &Uuid) -> Option<Uuid> {
+        let mut calls = self.calls.lock().unwrap();
+        let call = calls.get(call_id)?;
+        if call.provider != *provider {
+            return None;
+        }
+        let call = calls.remove(call_id)?;
+        (call.deadline > Instant::now()).then_some(call.caller)
+    }
+
+    /// 放弃调用（如转发失败）
+    pub fn cancel_call(&self, call_id: &Uuid) {
+        self.calls.lock().unwrap().remove(call_id);
+    }
+
+    /// 等待结果的调用数
+    pub fn pending_calls(&self) -> usize {
+        self.calls.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_directory_round_robin_and_call_matching() {
+        let directory = ServiceDirectory::new();
+        let (a, b, caller) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        assert!(ServiceDirectory::validate_name("storage.get").is_ok());
+        assert!(ServiceDirectory::validate_name("storage get").is_err());
+
+        assert!(directory.register(a, "storage.get").await);
+        assert!(directory.register(b, "storage.get").await);
+        assert!(!directory.register(a, "storage.get").await);
+
+        // 未指定提供者时轮询
+        let first = directory.candidates("storage.get", None).await[0];
+        let second = directory.candidates("storage.get", None).await[0];
+        assert_ne!(first, second);
+        // 指定的节点须已注册该服务
+        assert_eq!(directory.candidates("storage.get", Some(b)).await, vec![b]);
+        assert!(directory.candidates("storage.get", Some(caller)).await.is_empty());
+
+        // 只接受被转发的提供者返回的结果，且只转交一次
+        let call = Uuid::new_v4();
+        directory.start_call(call, caller, a, Duration::from_secs(5));
+        assert_eq!(directory.finish_call(&call, &b), None);
+        assert_eq!(directory.finish_call(&call, &a), Some(caller));
+        assert_eq!(directory.finish_call(&call, &a), None);
+
+        directory.remove_peer(&a).await;
+        assert_eq!(directory.services().await, vec![("storage.get".to_string(), 1)]);
+    }
+}
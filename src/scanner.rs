@@ -0,0 +1,212 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::ScannerDetectionConfig;
+
+/// 非协议数据包的特征，用于粗略判断扫描器探测的是哪种服务
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScannerKind {
+    /// HTTP 请求行
+    Http,
+    /// TLS/DTLS 握手记录
+    Tls,
+    /// SIP 请求
+    Sip,
+    /// SSH 版本标识
+    Ssh,
+    /// 空数据包
+    Empty,
+    /// 其他可打印文本
+    Text,
+    /// 其他二进制数据
+    Binary,
+}
+
+/// 按前几个字节判断非协议数据包的特征
+pub fn classify(data: &[u8]) -> ScannerKind {
+    const HTTP_METHODS: &[&[u8]] = &[b"GET ", b"POST ", b"HEAD ", b"PUT ", b"DELETE ", b"CONNECT ", b"PRI * HTTP"];
+    const SIP_METHODS: &[&[u8]] = &[b"OPTIONS sip:", b"INVITE sip:", b"REGISTER sip:", b"SIP/2.0"];
+    if data.is_empty() {
+        ScannerKind::Empty
+    } else if HTTP_METHODS.iter().any(|m| data.starts_with(m)) {
+        ScannerKind::Http
+    } else if SIP_METHODS.iter().any(|m| data.starts_with(m)) {
+        ScannerKind::Sip
+    } else if data.starts_with(b"SSH-") {
+        ScannerKind::Ssh
+    } else if data[0] == 0x16 && matches!(data.get(1), Some(0x03) | Some(0xfe)) {
+        // TLS 记录版本 3.x，DTLS 记录版本 0xfeXX
+        ScannerKind::Tls
+    } else if data.iter().take(64).all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace()) {
+        ScannerKind::Text
+    } else {
+        ScannerKind::Binary
+    }
+}
+
+#[derive(Debug)]
+struct SourceRecord {
+    garbage: VecDeque<Instant>,
+    kind: ScannerKind,
+    /// 已被判定为扫描器的次数，决定下一次屏蔽的时长
+    strikes: u32,
+    blocked_until: Option<Instant>,
+    last_seen: Instant,
+}
+
+/// 被屏蔽的扫描来源（供管理接口展示）
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct BlockedScanner {
+    pub ip: IpAddr,
+    pub kind: ScannerKind,
+    pub strikes: u32,
+    pub remaining_secs: u64,
+}
+
+/// 扫描器统计
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct ScannerCounts {
+    /// 累计判定为扫描器的次数，按数据包特征分类
+    pub detected: BTreeMap<ScannerKind, u64>,
+    /// 当前被屏蔽的来源数
+    pub blocked: usize,
+}
+
+/// 识别反复发送非协议数据的来源（端口扫描器、其他协议的探测），判定后静默丢弃其数据包
+///
+/// 屏蔽时长随判定次数翻倍，直到 `max_block_secs`；与 panic 封禁一样按 IP 统计。
+#[derive(Debug)]
+pub struct ScannerDetector {
+    config: ScannerDetectionConfig,
+    sources: Mutex<HashMap<IpAddr, SourceRecord>>,
+    detected: Mutex<BTreeMap<ScannerKind, u64>>,
+}
+
+impl ScannerDetector {
+    pub fn new(config: ScannerDetectionConfig) -> Self {
+        Self { config, sources: Mutex::new(HashMap::new()), detected: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// 记录来源发来的一个无法解析的数据包；来源因此被屏蔽时返回其特征与屏蔽时长
+    pub fn record_garbage(&self, ip: IpAddr, data: &[u8], now: Instant) -> Option<(ScannerKind, Duration)> {
+        let window = Duration::from_secs(self.config.window_secs);
+        let forget = Duration::from_secs(self.config.max_block_secs).max(window);
+        let kind = classify(data);
+        let mut sources = self.sources.lock().unwrap();
+        // 顺带清理已解封且长时间没有动静的来源，在此之前保留判定次数以便加长下一次屏蔽
+        sources.retain(|_, r| r.blocked_until.is_some_and(|t| t > now) || now.duration_since(r.last_seen) <= forget);
+        let record = sources.entry(ip).or_insert_with(|| SourceRecord {
+            garbage: VecDeque::new(),
+            kind,
+            strikes: 0,
+            blocked_until: None,
+            last_seen: now,
+        });
+        record.garbage.push_back(now);
+        record.kind = kind;
+        record.last_seen = now;
+        while record.garbage.front().is_some_and(|t| now.duration_since(*t) > window) {
+            record.garbage.pop_front();
+        }
+        if self.config.block_after == 0 || record.garbage.len() < self.config.block_after {
+            return None;
+        }
+        record.garbage.clear();
+        record.strikes += 1;
+        let block = self
+            .config
+            .block_secs
+            .saturating_mul(1u64 << (record.strikes - 1).min(32))
+            .min(self.config.max_block_secs.max(self.config.block_secs));
+        let block = Duration::from_secs(block);
+        record.blocked_until = Some(now + block);
+        drop(sources);
+        *self.detected.lock().unwrap().entry(kind).or_default() += 1;
+        Some((kind, block))
+    }
+
+    /// 来源当前是否被屏蔽
+    pub fn is_blocked(&self, ip: &IpAddr, now: Instant) -> bool {
+        self.sources.lock().unwrap().get(ip).and_then(|r| r.blocked_until).is_some_and(|t| t > now)
+    }
+
+    /// 当前被屏蔽的来源
+    pub fn blocked(&self) -> Vec<BlockedScanner> {
+        let now = Instant::now();
+        let mut blocked: Vec<BlockedScanner> = self
+            .sources
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(ip, r)| {
+                let until = r.blocked_until.filter(|t| *t > now)?;
+                Some(BlockedScanner {
+                    ip: *ip,
+                    kind: r.kind,
+                    strikes: r.strikes,
+                    remaining_secs: until.duration_since(now).as_secs(),
+                })
+            })
+            .collect();
+        blocked.sort_by_key(|b| b.ip);
+        blocked
+    }
+
+    pub fn counts(&self) -> ScannerCounts {
+        ScannerCounts { detected: self.detected.lock().unwrap().clone(), blocked: self.blocked().len() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_common_probes() {
+        assert_eq!(classify(b"GET / HTTP/1.1\r\n"), ScannerKind::Http);
+        assert_eq!(classify(b"OPTIONS sip:nm SIP/2.0\r\n"), ScannerKind::Sip);
+        assert_eq!(classify(b"SSH-2.0-OpenSSH_9.0"), ScannerKind::Ssh);
+        assert_eq!(classify(&[0x16, 0xfe, 0xfd, 0x00]), ScannerKind::Tls);
+        assert_eq!(classify(b""), ScannerKind::Empty);
+        assert_eq!(classify(b"hello"), ScannerKind::Text);
+        assert_eq!(classify(&[0x00, 0x01, 0xff]), ScannerKind::Binary);
+    }
+
+    #[test]
+    fn test_block_escalates_on_repeat_offences() {
+        let config = ScannerDetectionConfig { block_after: 3, window_secs: 10, block_secs: 60, max_block_secs: 200 };
+        let detector = ScannerDetector::new(config);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let start = Instant::now();
+        let probe = b"GET / HTTP/1.0\r\n\r\n";
+
+        // 窗口外的数据包不累计
+        assert_eq!(detector.record_garbage(ip, probe, start), None);
+        assert_eq!(detector.record_garbage(ip, probe, start + Duration::from_secs(11)), None);
+        assert_eq!(detector.record_garbage(ip, probe, start + Duration::from_secs(12)), None);
+        let first = start + Duration::from_secs(13);
+        assert_eq!(detector.record_garbage(ip, probe, first), Some((ScannerKind::Http, Duration::from_secs(60))));
+        assert!(detector.is_blocked(&ip, first + Duration::from_secs(59)));
+        assert!(!detector.is_blocked(&ip, first + Duration::from_secs(61)));
+
+        // 再犯时屏蔽时长翻倍，并受上限约束
+        let again = first + Duration::from_secs(100);
+        for offset in 0..2 {
+            assert_eq!(detector.record_garbage(ip, probe, again + Duration::from_secs(offset)), None);
+        }
+        assert_eq!(detector.record_garbage(ip, probe, again + Duration::from_secs(2)).map(|v| v.1), Some(Duration::from_secs(120)));
+        let third = again + Duration::from_secs(150);
+        for offset in 0..2 {
+            detector.record_garbage(ip, b"\x00", third + Duration::from_secs(offset));
+        }
+        assert_eq!(
+            detector.record_garbage(ip, b"\x00", third + Duration::from_secs(2)),
+            Some((ScannerKind::Binary, Duration::from_secs(200)))
+        );
+        assert_eq!(detector.counts().detected.values().sum::<u64>(), 3);
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::config::SchedulerConfig;
+
+/// 数据包入队结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enqueue {
+    Queued,
+    /// 该来源的队列已满
+    PeerQueueFull,
+    /// 全局排队数已达上限
+    Overloaded,
+}
+
+struct Pending {
+    data: Vec<u8>,
+    received_at: Instant,
+}
+
+/// 按来源地址公平调度的数据包队列
+///
+/// 每个来源地址一个先进先出队列，同一来源同一时刻最多只有一个数据包在处理，
+/// 保证单个来源的处理顺序；来源之间轮询出队，发包频繁的节点不会饿死其他节点。
+/// 排队超过 `packet_ttl_ms` 的数据包在出队时丢弃。
+pub struct FairScheduler {
+    config: SchedulerConfig,
+    queues: HashMap<SocketAddr, VecDeque<Pending>>,
+    /// 有待处理数据包且当前空闲的来源，按轮询顺序排列
+    ready: VecDeque<SocketAddr>,
+    /// 正在处理的来源
+    busy: HashSet<SocketAddr>,
+    queued: usize,
+    expired: u64,
+}
+
+impl FairScheduler {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            config,
+            queues: HashMap::new(),
+            ready: VecDeque::new(),
+            busy: HashSet::new(),
+            queued: 0,
+            expired: 0,
+        }
+    }
+
+    /// 排队中（未开始处理）的数据包数量
+    pub fn queued(&self) -> usize {
+        self.queued
+    }
+
+    /// 正在处理的数据包数量
+    pub fn in_flight(&self) -> usize {
+        self.busy.len()
+    }
+
+    /// 数据包入队
+    pub fn push(&mut self, source: SocketAddr, data: Vec<u8>, now: Instant) -> Enqueue {
+        if self.queued >= self.config.max_queued {
+            return Enqueue::Overloaded;
+        }
+        let queue = self.queues.entry(source).or_default();
+        if queue.len() >= self.config.max_queue_per_peer {
+            return Enqueue::PeerQueueFull;
+        }
+        if queue.is_empty() && !self.busy.contains(&source) {
+            self.ready.push_back(source);
+        }
+        queue.push_back(Pending { data, received_at: now });
+        self.queued += 1;
+        Enqueue::Queued
+    }
+
+    /// 取出下一个待处理的数据包及其接收时刻，并把其来源标记为处理中，直到调用 [`FairScheduler::complete`]
+    pub fn next(&mut self, now: Instant) -> Option<(SocketAddr, Vec<u8>, Instant)> {
+        let ttl = Duration::from_millis(self.config.packet_ttl_ms);
+        while let Some(source) = self.ready.pop_front() {
+            let Some(queue) = self.queues.get_mut(&source) else { continue };
+            while let Some(pending) = queue.pop_front() {
+                self.queued -= 1;
+                if !ttl.is_zero() && now.saturating_duration_since(pending.received_at) > ttl {
+                    self.expired += 1;
+                    continue;
+                }
+                self.busy.insert(source);
+                return Some((source, pending.data, pending.received_at));
+            }
+            self.queues.remove(&source);
+        }
+        None
+    }
+
+    /// 来源的当前数据包处理完毕，有剩余数据包时排到轮询队尾
+    pub fn complete(&mut self, source: SocketAddr) {
+        self.busy.remove(&source);
+        match self.queues.get(&source) {
+            Some(queue) if !queue.is_empty() => self.ready.push_back(source),
+            _ => {
+                self.queues.remove(&source);
+            }
+        }
+    }
+
+    /// 取出并清零自上次调用以来因排队超时丢弃的数据包数
+    pub fn take_expired(&mut self) -> u64 {
+        std::mem::take(&mut self.expired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_robin_with_per_source_order() {
+        let mut scheduler = FairScheduler::new(SchedulerConfig {
+            max_queue_per_peer: 3,
+            max_queued: 5,
+            packet_ttl_ms: 100,
+            ..SchedulerConfig::default()
+        });
+        let chatty: SocketAddr = "10.0.0.1:1000".parse().unwrap();
+        let quiet: SocketAddr = "10.0.0.2:1000".parse().unwrap();
+        let now = Instant::now();
+        for i in 0..3 {
+            assert_eq!(scheduler.push(chatty, vec![i], now), Enqueue::Queued);
+        }
+        assert_eq!(scheduler.push(chatty, vec![3], now), Enqueue::PeerQueueFull);
+        assert_eq!(scheduler.push(quiet, vec![10], now), Enqueue::Queued);
+
+        // 同一来源处理完之前不会再出队，其他来源得以插入
+        assert_eq!(scheduler.next(now), Some((chatty, vec![0], now)));
+        assert_eq!(scheduler.next(now), Some((quiet, vec![10], now)));
+        assert_eq!(scheduler.next(now), None);
+        scheduler.complete(quiet);
+        scheduler.complete(chatty);
+        assert_eq!(scheduler.next(now), Some((chatty, vec![1], now)));
+        scheduler.complete(chatty);
+
+        // 排队超时的数据包被丢弃
+        let later = now + Duration::from_millis(200);
+        assert_eq!(scheduler.next(later), None);
+        assert_eq!(scheduler.take_expired(), 1);
+        assert_eq!((scheduler.queued(), scheduler.in_flight()), (0, 0));
+
+        for i in 0..5 {
+            scheduler.push(SocketAddr::new(quiet.ip(), 2000 + i), vec![], later);
+        }
+        assert_eq!(scheduler.push(chatty, vec![], later), Enqueue::Overloaded);
+    }
+}
@@ -0,0 +1,429 @@
+//! 消息策略脚本：在握手、路由转发、中继前调用运维人员提供的 Lua 脚本，
+//! 由脚本放行、拒绝或改写消息（需启用 `scripting` 特性）
+//!
+//! 脚本在加载时执行一次，按需定义以下全局函数，未定义的钩子直接放行：
+//!
+//! - `pre_handshake(ctx)`：`ctx` 含 `addr`、`ip`、`node_id`、`name`、`network_id`、`payload`
+//! - `pre_route(ctx)`：`ctx` 含 `addr`、`ip`、`source`、`destination`、`hop_count`、`message_type`、`payload`
+//! - `pre_relay(ctx)`：`ctx` 含 `addr`、`ip`、`from`、`to`、`data`（字符串形式的原始字节）
+//!
+//! 返回 `nil` 或 `true` 放行；返回 `false, "原因"` 拒绝；`pre_route` 返回表时替换被转发消息的
+//! `payload`，`pre_relay` 返回字符串时替换中继的数据。
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::config::ScriptingConfig;
+use crate::metrics::ServerMetrics;
+
+/// 脚本钩子点
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// 处理握手请求前
+    PreHandshake,
+    /// 转发路由消息前
+    PreRoute,
+    /// 中继数据前
+    PreRelay,
+}
+
+impl Hook {
+    /// 脚本中对应的全局函数名
+    pub fn function_name(&self) -> &'static str {
+        match self {
+            Hook::PreHandshake => "pre_handshake",
+            Hook::PreRoute => "pre_route",
+            Hook::PreRelay => "pre_relay",
+        }
+    }
+}
+
+/// 钩子的判定结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// 放行
+    Allow,
+    /// 拒绝，附带原因
+    Deny(String),
+    /// 放行并替换路由消息的 payload（仅 `pre_route`）
+    ReplacePayload(Value),
+    /// 放行并替换中继数据（仅 `pre_relay`）
+    ReplaceData(Vec<u8>),
+}
+
+/// 传给钩子的上下文
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    /// 消息来源地址
+    pub addr: SocketAddr,
+    /// 钩子相关字段，须为 JSON 对象
+    pub fields: Value,
+    /// 原始字节（`pre_relay` 的 `data`），以 Lua 字符串传入
+    pub data: Option<Vec<u8>>,
+}
+
+impl HookContext {
+    pub fn new(addr: SocketAddr, fields: Value) -> Self {
+        Self { addr, fields, data: None }
+    }
+
+    pub fn with_data(mut self, data: Vec<u8>) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
+/// 已加载的策略脚本
+pub struct PolicyScripts {
+    #[cfg(feature = "scripting")]
+    engine: engine::LuaEngine,
+    #[cfg(feature = "scripting")]
+    deny_on_error: bool,
+    #[cfg(feature = "scripting")]
+    metrics: Arc<ServerMetrics>,
+}
+
+impl PolicyScripts {
+    /// 按配置加载脚本；未启用时返回 `None`，脚本无法读取或执行出错时返回错误
+    #[cfg(feature = "scripting")]
+    pub fn load(config: &ScriptingConfig, metrics: Arc<ServerMetrics>) -> Result<Option<Self>> {
+        use anyhow::Context;
+        use log::info;
+
+        use crate::tr;
+
+        if !config.enable {
+            return Ok(None);
+        }
+        let path = config.script_file.as_ref().context("scripting.enable 需要配置 scripting.script_file")?;
+        let source = std::fs::read_to_string(path).with_context(|| format!("读取策略脚本 {} 失败", path.display()))?;
+        let engine = engine::LuaEngine::new(&source, &path.display().to_string(), config)?;
+        info!("{}", tr!("已加载策略脚本 {}", "Loaded policy script {}", path.display()));
+        Ok(Some(Self { engine, deny_on_error: config.deny_on_error, metrics }))
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    pub fn load(config: &ScriptingConfig, _metrics: Arc<ServerMetrics>) -> Result<Option<Self>> {
+        if config.enable {
+            log::warn!(
+                "{}",
+                crate::tr!(
+                    "策略脚本需要启用 scripting 特性，已忽略 scripting.enable",
+                    "Policy scripts require the scripting feature, ignoring scripting.enable"
+                )
+            );
+        }
+        Ok(None)
+    }
+
+    /// 调用钩子；脚本出错时按 `deny_on_error` 放行或拒绝
+    #[cfg(feature = "scripting")]
+    pub fn run(&self, hook: Hook, context: HookContext) -> Verdict {
+        use log::warn;
+
+        use crate::tr;
+
+        let verdict = match self.engine.call(hook, &context) {
+            Ok(verdict) => verdict,
+            Err(e) => {
+                ServerMetrics::incr(&self.metrics.script_errors);
+                warn!("{}", tr!("策略脚本 {} 执行失败: {}", "Policy script {} failed: {}", hook.function_name(), e));
+                if self.deny_on_error {
+                    Verdict::Deny(tr!("策略脚本出错", "policy script error").to_string())
+                } else {
+                    Verdict::Allow
+                }
+            }
+        };
+        if matches!(verdict, Verdict::Deny(_)) {
+            ServerMetrics::incr(&self.metrics.script_vetoes);
+        }
+        verdict
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    pub fn run(&self, _hook: Hook, _context: HookContext) -> Verdict {
+        Verdict::Allow
+    }
+}
+
+/// `ip` 是否属于 `cidr`（如 `10.0.0.0/8`、`2001:db8::/32`；不带前缀长度时按单个地址比较）
+pub fn ip_in_cidr(ip: std::net::IpAddr, cidr: &str) -> bool {
+    use std::net::IpAddr;
+
+    let (network, prefix) = match cidr.split_once('/') {
+        Some((network, prefix)) => (network, prefix.parse::<u32>().ok()),
+        None => (cidr, None),
+    };
+    let Ok(network) = network.trim().parse::<IpAddr>() else { return false };
+    match (ip.to_canonical(), network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let prefix = prefix.unwrap_or(32);
+            prefix <= 32 && prefix_matches(u32::from(ip) as u128, u32::from(network) as u128, 32, prefix)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let prefix = prefix.unwrap_or(128);
+            prefix <= 128 && prefix_matches(u128::from(ip), u128::from(network), 128, prefix)
+        }
+        _ => false,
+    }
+}
+
+fn prefix_matches(ip: u128, network: u128, bits: u32, prefix: u32) -> bool {
+    if prefix == 0 {
+        return true;
+    }
+    let shift = bits - prefix;
+    ip >> shift == network >> shift
+}
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use anyhow::{Result, anyhow};
+    use mlua::{HookTriggers, Lua, LuaOptions, MultiValue, StdLib, Table, Value as LuaValue};
+    use serde_json::{Map, Number, Value};
+
+    use super::{Hook, HookContext, Verdict};
+    use crate::config::ScriptingConfig;
+
+    /// 指令计数钩子的触发间隔
+    const INSTRUCTION_STEP: u32 = 1000;
+
+    /// 沙箱化的 Lua 虚拟机：只加载 table/string/math/utf8 标准库，去掉文件与代码加载函数，
+    /// 限制内存与每次调用执行的指令数
+    pub(super) struct LuaEngine {
+        lua: Mutex<Lua>,
+        budget: Arc<AtomicU64>,
+        instruction_limit: u64,
+    }
+
+    impl LuaEngine {
+        pub(super) fn new(source: &str, name: &str, config: &ScriptingConfig) -> Result<Self> {
+            let lua = Lua::new_with(StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8, LuaOptions::new())?;
+            let globals = lua.globals();
+            for name in ["dofile", "loadfile", "load", "require", "collectgarbage"] {
+                globals.set(name, LuaValue::Nil)?;
+            }
+            let p2p = lua.create_table()?;
+            p2p.set(
+                "log",
+                lua.create_function(|_, message: String| {
+                    log::info!("[script] {}", message);
+                    Ok(())
+                })?,
+            )?;
+            p2p.set(
+                "ip_in",
+                lua.create_function(|_, (ip, cidr): (String, String)| {
+                    Ok(ip.parse().map(|ip| super::ip_in_cidr(ip, &cidr)).unwrap_or(false))
+                })?,
+            )?;
+            globals.set("p2p", p2p)?;
+            drop(globals);
+
+            let budget = Arc::new(AtomicU64::new(u64::MAX));
+            if config.instruction_limit > 0 {
+                let remaining = budget.clone();
+                lua.set_hook(HookTriggers::new().every_nth_instruction(INSTRUCTION_STEP), move |_, _| {
+                    let left = remaining.load(Ordering::Relaxed);
+                    if left < INSTRUCTION_STEP as u64 {
+                        return Err(mlua::Error::RuntimeError("超过指令上限".to_string()));
+                    }
+                    remaining.store(left - INSTRUCTION_STEP as u64, Ordering::Relaxed);
+                    Ok(())
+                });
+            }
+            if config.memory_limit_bytes > 0 {
+                lua.set_memory_limit(config.memory_limit_bytes)?;
+            }
+            let engine = Self { lua: Mutex::new(lua), budget, instruction_limit: config.instruction_limit };
+            {
+                let lua = engine.lua.lock().unwrap();
+                engine.reset_budget();
+                lua.load(source).set_name(name).exec().map_err(|e| anyhow!("执行策略脚本失败: {}", e))?;
+            }
+            Ok(engine)
+        }
+
+        fn reset_budget(&self) {
+            let budget = if self.instruction_limit > 0 { self.instruction_limit } else { u64::MAX };
+            self.budget.store(budget, Ordering::Relaxed);
+        }
+
+        pub(super) fn call(&self, hook: Hook, context: &HookContext) -> Result<Verdict> {
+            let lua = self.lua.lock().unwrap_or_else(|e| e.into_inner());
+            let function: Option<mlua::Function> = lua.globals().get(hook.function_name())?;
+            let Some(function) = function else { return Ok(Verdict::Allow) };
+
+            let ctx = match json_to_lua(&lua, &context.fields)? {
+                LuaValue::Table(table) => table,
+                _ => lua.create_table()?,
+            };
+            ctx.set("hook", hook.function_name())?;
+            ctx.set("addr", context.addr.to_string())?;
+            ctx.set("ip", context.addr.ip().to_canonical().to_string())?;
+            if let Some(data) = &context.data {
+                ctx.set("data", lua.create_string(data)?)?;
+            }
+
+            self.reset_budget();
+            let results: MultiValue = function.call(ctx)?;
+            let mut results = results.into_iter();
+            let verdict = match results.next().unwrap_or(LuaValue::Nil) {
+                LuaValue::Nil | LuaValue::Boolean(true) => Verdict::Allow,
+                LuaValue::Boolean(false) => {
+                    let reason = match results.next() {
+                        Some(LuaValue::String(reason)) => reason.to_string_lossy().into_owned(),
+                        _ => "策略拒绝".to_string(),
+                    };
+                    Verdict::Deny(reason)
+                }
+                LuaValue::Table(table) if hook == Hook::PreRoute => Verdict::ReplacePayload(table_to_json(table, 0)?),
+                LuaValue::String(data) if hook == Hook::PreRelay => Verdict::ReplaceData(data.as_bytes().to_vec()),
+                other => return Err(anyhow!("{} 返回了不支持的值: {}", hook.function_name(), other.type_name())),
+            };
+            Ok(verdict)
+        }
+    }
+
+    fn json_to_lua<'lua>(lua: &'lua Lua, value: &Value) -> mlua::Result<LuaValue<'lua>> {
+        Ok(match value {
+            Value::Null => LuaValue::Nil,
+            Value::Bool(b) => LuaValue::Boolean(*b),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => LuaValue::Integer(i),
+                None => LuaValue::Number(n.as_f64().unwrap_or_default()),
+            },
+            Value::String(s) => LuaValue::String(lua.create_string(s)?),
+            Value::Array(items) => {
+                let table = lua.create_table_with_capacity(items.len(), 0)?;
+                for item in items {
+                    table.raw_push(json_to_lua(lua, item)?)?;
+                }
+                LuaValue::Table(table)
+            }
+            Value::Object(fields) => {
+                let table = lua.create_table_with_capacity(0, fields.len())?;
+                for (key, item) in fields {
+                    table.raw_set(key.as_str(), json_to_lua(lua, item)?)?;
+                }
+                LuaValue::Table(table)
+            }
+        })
+    }
+
+    /// 表转 JSON：键为 1..n 的连续整数时视为数组（空表视为对象）
+    fn table_to_json(table: Table, depth: usize) -> Result<Value> {
+        if depth > 32 {
+            return Err(anyhow!("返回的表嵌套过深"));
+        }
+        let len = table.raw_len();
+        let mut entries = Vec::new();
+        for pair in table.pairs::<LuaValue, LuaValue>() {
+            entries.push(pair?);
+        }
+        if len > 0 && entries.len() == len {
+            let mut items = vec![Value::Null; len];
+            for (key, value) in entries {
+                let LuaValue::Integer(index) = key else { return Err(anyhow!("数组表含非整数键")) };
+                items[(index - 1) as usize] = lua_to_json(value, depth)?;
+            }
+            return Ok(Value::Array(items));
+        }
+        let mut fields = Map::new();
+        for (key, value) in entries {
+            let key = match key {
+                LuaValue::String(s) => s.to_string_lossy().into_owned(),
+                LuaValue::Integer(i) => i.to_string(),
+                other => return Err(anyhow!("不支持的表键类型: {}", other.type_name())),
+            };
+            fields.insert(key, lua_to_json(value, depth)?);
+        }
+        Ok(Value::Object(fields))
+    }
+
+    fn lua_to_json(value: LuaValue, depth: usize) -> Result<Value> {
+        Ok(match value {
+            LuaValue::Nil => Value::Null,
+            LuaValue::Boolean(b) => Value::Bool(b),
+            LuaValue::Integer(i) => Value::from(i),
+            LuaValue::Number(n) => Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null),
+            LuaValue::String(s) => Value::String(s.to_string_lossy().into_owned()),
+            LuaValue::Table(table) => table_to_json(table, depth + 1)?,
+            other => return Err(anyhow!("不支持的返回值类型: {}", other.type_name())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_in_cidr() {
+        assert!(ip_in_cidr("10.1.2.3".parse().unwrap(), "10.0.0.0/8"));
+        assert!(!ip_in_cidr("11.1.2.3".parse().unwrap(), "10.0.0.0/8"));
+        assert!(ip_in_cidr("::ffff:192.168.1.9".parse().unwrap(), "192.168.1.0/24"));
+        assert!(ip_in_cidr("2001:db8::1".parse().unwrap(), "2001:db8::/32"));
+        assert!(ip_in_cidr("1.2.3.4".parse().unwrap(), "1.2.3.4"));
+        assert!(ip_in_cidr("1.2.3.4".parse().unwrap(), "0.0.0.0/0"));
+        assert!(!ip_in_cidr("1.2.3.4".parse().unwrap(), "1.2.3.0/33"));
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn test_script_verdicts() {
+        use serde_json::json;
+        use std::io::Write;
+
+        let mut file = tempfile_path("verdicts");
+        writeln!(
+            file.1,
+            r#"
+            function pre_route(ctx)
+              if ctx.payload.secret then return false, "no secrets" end
+              ctx.payload.tagged = true
+              return ctx.payload
+            end
+            function pre_relay(ctx) return string.upper(ctx.data) end
+            function pre_handshake(ctx) while true do end end
+            "#
+        )
+        .unwrap();
+        let config = ScriptingConfig {
+            enable: true,
+            script_file: Some(file.0.clone()),
+            instruction_limit: 100_000,
+            ..ScriptingConfig::default()
+        };
+        let metrics = Arc::new(ServerMetrics::new());
+        let scripts = PolicyScripts::load(&config, metrics.clone()).unwrap().unwrap();
+        let addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+
+        let denied = scripts.run(Hook::PreRoute, HookContext::new(addr, json!({ "payload": { "secret": 1 } })));
+        assert_eq!(denied, Verdict::Deny("no secrets".to_string()));
+        let replaced = scripts.run(Hook::PreRoute, HookContext::new(addr, json!({ "payload": { "n": [1, 2] } })));
+        assert_eq!(replaced, Verdict::ReplacePayload(json!({ "n": [1, 2], "tagged": true })));
+        let data = scripts.run(Hook::PreRelay, HookContext::new(addr, json!({})).with_data(b"abc".to_vec()));
+        assert_eq!(data, Verdict::ReplaceData(b"ABC".to_vec()));
+        // 死循环被指令上限打断，默认放行并计为错误
+        assert_eq!(scripts.run(Hook::PreHandshake, HookContext::new(addr, json!({}))), Verdict::Allow);
+        assert_eq!(metrics.script_errors.load(std::sync::atomic::Ordering::Relaxed), 1);
+        assert_eq!(metrics.script_vetoes.load(std::sync::atomic::Ordering::Relaxed), 1);
+        let _ = std::fs::remove_file(&file.0);
+    }
+
+    #[cfg(feature = "scripting")]
+    fn tempfile_path(name: &str) -> (std::path::PathBuf, std::fs::File) {
+        let path = std::env::temp_dir().join(format!("p2p-script-{}-{}.lua", name, std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        (path, file)
+    }
+}
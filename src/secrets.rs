@@ -0,0 +1,270 @@
+use std::fs;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result, bail};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::Sha256;
+
+use crate::identity::{decode_hex, encode_hex};
+
+/// 解锁加密段的口令所在的环境变量
+pub const PASSPHRASE_ENV: &str = "P2P_CONFIG_PASSPHRASE";
+
+/// 可以改为引用文件（`<字段>_file`）或环境变量（`<字段>_env`）的敏感字段
+const SECRET_FIELDS: &[&str] = &["token", "password", "cluster_key", "secret"];
+
+/// 新加密段使用的 PBKDF2 迭代次数
+const PBKDF2_ROUNDS: u32 = 200_000;
+
+/// 配置文件中的加密段：解密后是一个 JSON 对象，按字段合并进配置的其余部分
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedSection {
+    /// PBKDF2-HMAC-SHA256 的盐（十六进制）
+    pub salt: String,
+    /// PBKDF2 迭代次数
+    pub rounds: u32,
+    /// AES-256-GCM 的 nonce（十六进制）
+    pub nonce: String,
+    /// 密文与认证标签（十六进制）
+    pub ciphertext: String,
+}
+
+impl EncryptedSection {
+    /// 用口令加密一段配置（须为 JSON 对象）
+    pub fn seal(section: &Value, passphrase: &str) -> Result<Self> {
+        if !section.is_object() {
+            bail!("加密段的内容必须是 JSON 对象");
+        }
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = cipher(passphrase, &salt, PBKDF2_ROUNDS);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, serde_json::to_vec(section)?.as_slice())
+            .map_err(|_| anyhow::anyhow!("加密配置段失败"))?;
+        Ok(Self {
+            salt: encode_hex(&salt),
+            rounds: PBKDF2_ROUNDS,
+            nonce: encode_hex(&nonce),
+            ciphertext: encode_hex(&ciphertext),
+        })
+    }
+
+    /// 用口令解密，口令错误或密文被篡改时返回错误
+    pub fn open(&self, passphrase: &str) -> Result<Value> {
+        let salt = decode_hex(&self.salt).context("加密段的 salt 不是十六进制")?;
+        let nonce = decode_hex(&self.nonce)
+            .filter(|nonce| nonce.len() == 12)
+            .context("加密段的 nonce 不是 12 字节的十六进制")?;
+        let ciphertext = decode_hex(&self.ciphertext).context("加密段的 ciphertext 不是十六进制")?;
+        let plaintext = cipher(passphrase, &salt, self.rounds)
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow::anyhow!("解密配置段失败：口令错误或内容被篡改"))?;
+        let section: Value = serde_json::from_slice(&plaintext).context("加密段解密后不是合法的 JSON")?;
+        if !section.is_object() {
+            bail!("加密段解密后不是 JSON 对象");
+        }
+        Ok(section)
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8], rounds: u32) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, rounds, &mut key);
+    Aes256Gcm::new(&key.into())
+}
+
+/// 在反序列化前处理配置中的敏感信息：
+///
+/// 1. 顶层的 `encrypted` 段用环境变量 [`PASSPHRASE_ENV`] 中的口令解密，合并进配置；
+/// 2. 各层对象中的 `<字段>_file` / `<字段>_env` 替换为文件内容或环境变量的值。
+pub fn resolve(config: &mut Value) -> Result<()> {
+    resolve_with(config, |name| std::env::var(name).ok())
+}
+
+fn resolve_with(config: &mut Value, env: impl Fn(&str) -> Option<String>) -> Result<()> {
+    if let Some(section) = config.as_object_mut().and_then(|root| root.remove("encrypted")) {
+        let section: EncryptedSection = serde_json::from_value(section).context("encrypted 段格式错误")?;
+        let passphrase = env(PASSPHRASE_ENV)
+            .with_context(|| format!("配置含加密段，但未设置环境变量 {}", PASSPHRASE_ENV))?;
+        merge(config, section.open(&passphrase)?);
+    }
+    resolve_references(config, "", &env)
+}
+
+/// 把 `overlay` 的字段合并进 `base`：两边都是对象时逐字段递归，否则覆盖
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// 已设置的敏感字段输出时的替代值
+pub const REDACTED: &str = "<redacted>";
+
+/// 以令牌本身为键的映射（如 `control.tokens`），输出时键名也要隐藏
+const SECRET_KEYED_FIELDS: &[&str] = &["tokens"];
+
+/// 以用户名为键、密码为值的映射（如 `stun_server.turn.users`），输出时保留键名、隐藏值
+const SECRET_VALUED_FIELDS: &[&str] = &["users"];
+
+/// 把各层对象中已设置的敏感字段替换为 [`REDACTED`]，用于输出生效配置
+///
+/// 以令牌为键的映射保留值，键名依次替换为 `<redacted-1>`、`<redacted-2>`……；
+/// 以用户名为键的口令映射保留键名，值替换为 [`REDACTED`]
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, child) in object.iter_mut() {
+                if SECRET_KEYED_FIELDS.contains(&key.as_str())
+                    && let Value::Object(map) = child
+                {
+                    let entries = std::mem::take(map);
+                    for (i, (_, entry)) in entries.into_iter().enumerate() {
+                        map.insert(format!("<redacted-{}>", i + 1), entry);
+                    }
+                } else if SECRET_VALUED_FIELDS.contains(&key.as_str())
+                    && let Value::Object(map) = child
+                {
+                    map.values_mut().for_each(|entry| *entry = Value::String(REDACTED.to_string()));
+                } else if SECRET_FIELDS.contains(&key.as_str()) && !child.is_null() {
+                    *child = Value::String(REDACTED.to_string());
+                } else {
+                    redact(child);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn resolve_references(value: &mut Value, path: &str, env: &impl Fn(&str) -> Option<String>) -> Result<()> {
+    match value {
+        Value::Object(object) => {
+            for field in SECRET_FIELDS {
+                resolve_field(object, field, path, env)?;
+            }
+            for (key, child) in object.iter_mut() {
+                resolve_references(child, &format!("{}{}.", path, key), env)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                resolve_references(item, path, env)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn resolve_field(
+    object: &mut Map<String, Value>,
+    field: &str,
+    path: &str,
+    env: &impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    let file = object.remove(&format!("{}_file", field));
+    let var = object.remove(&format!("{}_env", field));
+    let name = format!("{}{}", path, field);
+    let secret = match (file, var) {
+        (None, None) => return Ok(()),
+        (Some(_), Some(_)) => bail!("{} 不能同时引用文件和环境变量", name),
+        (Some(file), None) => {
+            let file = file.as_str().with_context(|| format!("{}_file 必须是路径字符串", name))?.to_string();
+            let content = fs::read_to_string(&file).with_context(|| format!("读取 {} 的密钥文件 {} 失败", name, file))?;
+            content.trim_end_matches(['\r', '\n']).to_string()
+        }
+        (None, Some(var)) => {
+            let var = var.as_str().with_context(|| format!("{}_env 必须是环境变量名", name))?;
+            env(var).with_context(|| format!("{} 引用的环境变量 {} 未设置", name, var))?
+        }
+    };
+    if object.get(field).is_some_and(|value| !value.is_null()) {
+        bail!("{} 已直接设置，不能再引用文件或环境变量", name);
+    }
+    object.insert(field.to_string(), Value::String(secret));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use serde_json::json;
+
+    #[test]
+    fn test_secret_references_resolve_from_file_and_env() {
+        let path = std::env::temp_dir().join(format!("p2p-secret-{}", uuid::Uuid::new_v4()));
+        fs::write(&path, "admin-secret\n").unwrap();
+        let mut value = json!({
+            "admin": { "token_file": path.to_str().unwrap() },
+            "mqtt": { "password_env": "MQTT_PASSWORD" },
+        });
+        let env = |name: &str| (name == "MQTT_PASSWORD").then(|| "mqtt-secret".to_string());
+        resolve_with(&mut value, env).unwrap();
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.admin.token.as_deref(), Some("admin-secret"));
+        assert_eq!(config.mqtt.password.as_deref(), Some("mqtt-secret"));
+        let _ = fs::remove_file(&path);
+
+        let mut missing = json!({ "grpc": { "token_env": "UNSET_VARIABLE" } });
+        let error = resolve_with(&mut missing, |_| None).unwrap_err();
+        assert!(error.to_string().contains("grpc.token"), "{}", error);
+        let mut conflicting = json!({ "grpc": { "token": "inline", "token_env": "GRPC_TOKEN" } });
+        assert!(resolve_with(&mut conflicting, |_| Some("x".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_redact_hides_set_secrets_only() {
+        let mut config = Config::default();
+        config.admin.token = Some("admin-secret".to_string());
+        config.webhooks.endpoints.push(crate::config::WebhookEndpoint { secret: Some("hook-secret".to_string()), ..Default::default() });
+        config.control.tokens.insert("ops-secret".to_string(), crate::config::PeerRole::Admin);
+        config.stun_server.turn.users.insert("alice".to_string(), "turn-secret".to_string());
+        let mut value = serde_json::to_value(&config).unwrap();
+        redact(&mut value);
+        assert_eq!(value["admin"]["token"], REDACTED);
+        assert_eq!(value["control"]["tokens"]["<redacted-1>"], "admin");
+        assert_eq!(value["webhooks"]["endpoints"][0]["secret"], REDACTED);
+        assert_eq!(value["stun_server"]["turn"]["users"]["alice"], REDACTED);
+        assert!(value["grpc"]["token"].is_null());
+        assert!(!value.to_string().contains("admin-secret"));
+        assert!(!value.to_string().contains("hook-secret"));
+        assert!(!value.to_string().contains("ops-secret"));
+        assert!(!value.to_string().contains("turn-secret"));
+    }
+
+    #[test]
+    fn test_encrypted_section_merges_with_passphrase() {
+        let section = EncryptedSection::seal(&json!({ "cluster": { "cluster_key": "shared" } }), "hunter2").unwrap();
+        let mut value = json!({
+            "cluster": { "enable": true },
+            "encrypted": serde_json::to_value(&section).unwrap(),
+        });
+        let env = |name: &str| (name == PASSPHRASE_ENV).then(|| "hunter2".to_string());
+        resolve_with(&mut value, env).unwrap();
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert!(config.cluster.enable);
+        assert_eq!(config.cluster.cluster_key.as_deref(), Some("shared"));
+
+        assert!(section.open("wrong").is_err());
+        let mut locked = json!({ "encrypted": serde_json::to_value(&section).unwrap() });
+        let error = resolve_with(&mut locked, |_| None).unwrap_err();
+        assert!(error.to_string().contains(PASSPHRASE_ENV), "{}", error);
+    }
+}
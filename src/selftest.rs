@@ -0,0 +1,273 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+use anyhow::{Context, Result, anyhow, bail};
+use futures::future::join_all;
+use log::{error, info, warn};
+use serde::Serialize;
+use tokio::net::{lookup_host, TcpListener, UdpSocket};
+use tokio::time::{timeout, Instant};
+
+use crate::config::Config;
+use crate::http_client::HttpUrl;
+use crate::stun_protocol::StunMessage;
+use crate::tr;
+
+/// 单项检查的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// 启动自检报告
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelfTestReport {
+    pub checks: Vec<Check>,
+}
+
+impl SelfTestReport {
+    fn push(&mut self, name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(Check { name: name.into(), status, detail: detail.into() });
+    }
+
+    /// 没有失败项
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.status != CheckStatus::Failed)
+    }
+
+    /// 按结果级别逐项记录日志
+    pub fn log(&self) {
+        for check in &self.checks {
+            match check.status {
+                CheckStatus::Ok => info!("{}", tr!("自检通过 [{}]: {}", "Self-test passed [{}]: {}", check.name, check.detail)),
+                CheckStatus::Warning => warn!("{}", tr!("自检警告 [{}]: {}", "Self-test warning [{}]: {}", check.name, check.detail)),
+                CheckStatus::Failed => error!("{}", tr!("自检失败 [{}]: {}", "Self-test failed [{}]: {}", check.name, check.detail)),
+            }
+        }
+    }
+
+    /// 有失败项时返回汇总全部失败项的错误
+    pub fn into_result(self) -> Result<Self> {
+        if self.passed() {
+            return Ok(self);
+        }
+        let failures: Vec<String> = self
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Failed)
+            .map(|check| format!("  - [{}] {}", check.name, check.detail.replace('\n', "\n    ")))
+            .collect();
+        bail!("启动自检失败（{} 项）:\n{}", failures.len(), failures.join("\n"))
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                CheckStatus::Ok => "ok",
+                CheckStatus::Warning => "warn",
+                CheckStatus::Failed => "FAIL",
+            };
+            writeln!(f, "[{:>4}] {}: {}", status, check.name, check.detail.replace('\n', "\n       "))?;
+        }
+        Ok(())
+    }
+}
+
+/// 运行启动自检：校验配置、试绑定各监听端口、解析外部服务的域名、向配置的 STUN 服务器发送绑定请求
+///
+/// 试绑定的套接字随即释放，服务器启动时再正式绑定。
+pub async fn run(config: &Config) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+
+    match config.validate() {
+        Ok(()) => report.push("config", CheckStatus::Ok, "配置有效"),
+        Err(e) => report.push("config", CheckStatus::Failed, e.to_string()),
+    }
+
+    check_udp(&mut report, "listen_address", config.listen_address).await;
+    if config.stun_server.enable {
+        let stun_addr = SocketAddr::new(config.listen_address.ip(), config.stun_server.port);
+        check_udp(&mut report, "stun_server.port", stun_addr).await;
+        if config.stun_server.tcp {
+            check_tcp(&mut report, "stun_server.tcp", stun_addr).await;
+        }
+    }
+    if config.admin.enable {
+        check_tcp(&mut report, "admin.listen_address", config.admin.listen_address).await;
+    }
+    if config.grpc.enable {
+        check_tcp(&mut report, "grpc.listen_address", config.grpc.listen_address).await;
+    }
+
+    check_dns(&mut report, config).await;
+
+    let mut servers: Vec<&String> = Vec::new();
+    if config.ice.enable {
+        servers.extend(&config.ice.stun_servers);
+    }
+    if config.nat_detection.enable {
+        servers.extend(&config.nat_detection.stun_servers);
+    }
+    servers.sort();
+    servers.dedup();
+    let wait = Duration::from_millis(config.startup.stun_timeout_ms);
+    let results = join_all(servers.iter().map(|server| stun_binding(server, wait))).await;
+    let unreachable = if config.startup.require_stun { CheckStatus::Failed } else { CheckStatus::Warning };
+    for (server, result) in servers.into_iter().zip(results) {
+        let name = format!("stun {}", server);
+        match result {
+            Ok((mapped, rtt)) => report.push(name, CheckStatus::Ok, format!("映射地址 {}，往返 {} 毫秒", mapped, rtt.as_millis())),
+            Err(e) => report.push(
+                name,
+                unreachable,
+                format!("不可达: {:#}。检查 DNS 与出站 UDP 是否被防火墙拦截，或从 ice.stun_servers / nat_detection.stun_servers 中移除", e),
+            ),
+        }
+    }
+    report
+}
+
+async fn check_udp(report: &mut SelfTestReport, name: &str, addr: SocketAddr) {
+    match UdpSocket::bind(addr).await {
+        Ok(_) => report.push(name, CheckStatus::Ok, format!("可绑定 UDP {}", addr)),
+        Err(e) => report.push(name, CheckStatus::Failed, bind_failure("UDP", addr, &e)),
+    }
+}
+
+async fn check_tcp(report: &mut SelfTestReport, name: &str, addr: SocketAddr) {
+    match TcpListener::bind(addr).await {
+        Ok(_) => report.push(name, CheckStatus::Ok, format!("可绑定 TCP {}", addr)),
+        Err(e) => report.push(name, CheckStatus::Failed, bind_failure("TCP", addr, &e)),
+    }
+}
+
+/// 解析已启用的外部服务（MQTT broker、OTLP 收集器、webhook）的域名；解析失败记为警告，
+/// 这些服务在运行中会重试
+async fn check_dns(report: &mut SelfTestReport, config: &Config) {
+    let mut targets: Vec<(String, Result<String>)> = Vec::new();
+    if config.mqtt.enable {
+        targets.push(("dns mqtt.broker_host".to_string(), Ok(format!("{}:{}", config.mqtt.broker_host, config.mqtt.broker_port))));
+    }
+    if config.telemetry.enable {
+        let url = HttpUrl::parse(&config.telemetry.endpoint).map(|url| format!("{}:{}", url.host, url.port));
+        targets.push(("dns telemetry.endpoint".to_string(), url));
+    }
+    if config.webhooks.enable {
+        for (i, endpoint) in config.webhooks.endpoints.iter().enumerate() {
+            let url = HttpUrl::parse(&endpoint.url).map(|url| format!("{}:{}", url.host, url.port));
+            targets.push((format!("dns webhooks.endpoints[{}]", i), url));
+        }
+    }
+
+    let wait = Duration::from_millis(config.startup.dns_timeout_ms);
+    for (name, target) in targets {
+        let target = match target {
+            Ok(target) => target,
+            Err(e) => {
+                report.push(name, CheckStatus::Failed, format!("地址无效: {:#}", e));
+                continue;
+            }
+        };
+        match timeout(wait, lookup_host(target.as_str())).await {
+            Ok(Ok(addrs)) => {
+                let addrs: Vec<String> = addrs.map(|addr| addr.ip().to_string()).collect();
+                report.push(name, CheckStatus::Ok, format!("{} 解析为 {}", target, addrs.join(", ")));
+            }
+            Ok(Err(e)) => report.push(name, CheckStatus::Warning, format!("无法解析 {}: {}。检查主机名拼写与 DNS 配置", target, e)),
+            Err(_) => report.push(name, CheckStatus::Warning, format!("{} 毫秒内未能解析 {}，检查 DNS 服务器是否可达", wait.as_millis(), target)),
+        }
+    }
+}
+
+/// 绑定失败的说明，附上常见原因
+fn bind_failure(protocol: &str, addr: SocketAddr, e: &std::io::Error) -> String {
+    let hint = match e.kind() {
+        std::io::ErrorKind::AddrInUse => "端口已被其他进程占用，换一个端口或停止占用它的进程",
+        std::io::ErrorKind::PermissionDenied => "权限不足，1024 以下的端口需要 root 或 CAP_NET_BIND_SERVICE",
+        std::io::ErrorKind::AddrNotAvailable => "本机没有这个IP地址，改用 0.0.0.0 或本机网卡的地址",
+        _ => "检查地址与端口是否正确",
+    };
+    format!("无法绑定 {} {}: {}。{}", protocol, addr, e, hint)
+}
+
+/// 向 STUN 服务器发送绑定请求，返回映射地址与往返时间
+async fn stun_binding(server: &str, wait: Duration) -> Result<(SocketAddr, Duration)> {
+    timeout(wait, async {
+        let addr = lookup_host(server).await.context("DNS 解析失败")?.next().ok_or_else(|| anyhow!("DNS 没有返回地址"))?;
+        let bind: SocketAddr = if addr.is_ipv4() { "0.0.0.0:0".parse()? } else { "[::]:0".parse()? };
+        let socket = UdpSocket::bind(bind).await?;
+        let request = StunMessage::new_binding_request();
+        let started = Instant::now();
+        socket.send_to(&request.to_bytes(), addr).await?;
+        let mut buffer = [0u8; 1500];
+        loop {
+            let (len, from) = socket.recv_from(&mut buffer).await?;
+            if from != addr {
+                continue;
+            }
+            let Ok(response) = StunMessage::from_bytes(&buffer[..len]) else {
+                continue;
+            };
+            if response.transaction_id != request.transaction_id {
+                continue;
+            }
+            let mapped = response.extract_mapped_address().ok_or_else(|| anyhow!("响应中没有映射地址"))?;
+            return Ok((mapped, started.elapsed()));
+        }
+    })
+    .await
+    .map_err(|_| anyhow!("{} 毫秒内没有应答", wait.as_millis()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stun_server::StunServer;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_reports_invalid_config_busy_port_and_stun() {
+        // 本地 STUN 服务器可达
+        let stun_config = crate::stun_server::StunServerConfig { enable: true, ..Default::default() };
+        let stun = Arc::new(StunServer::new(stun_config, "127.0.0.1:0".parse().unwrap()).await.unwrap());
+        let stun_addr = stun.local_addr();
+        let runner = stun.clone();
+        tokio::spawn(async move { runner.run().await });
+
+        let busy = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut config = Config { listen_address: busy.local_addr().unwrap(), heartbeat_interval: 0, ..Config::default() };
+        config.nat_detection.enable = false;
+        config.ice.stun_servers = vec![stun_addr.to_string(), "127.0.0.1:9".to_string()];
+        config.startup.stun_timeout_ms = 300;
+        config.webhooks.enable = true;
+        config.webhooks.endpoints = vec![
+            crate::config::WebhookEndpoint { url: "http://localhost:9/hook".to_string(), ..Default::default() },
+            crate::config::WebhookEndpoint { url: "https://localhost/hook".to_string(), ..Default::default() },
+        ];
+
+        let report = run(&config).await;
+        let status = |name: &str| report.checks.iter().find(|c| c.name == name).map(|c| c.status);
+        assert_eq!(status("config"), Some(CheckStatus::Failed));
+        assert_eq!(status("listen_address"), Some(CheckStatus::Failed));
+        assert_eq!(status(&format!("stun {}", stun_addr)), Some(CheckStatus::Ok));
+        assert_eq!(status("stun 127.0.0.1:9"), Some(CheckStatus::Warning));
+        assert_eq!(status("dns webhooks.endpoints[0]"), Some(CheckStatus::Ok));
+        assert_eq!(status("dns webhooks.endpoints[1]"), Some(CheckStatus::Failed));
+
+        let err = report.into_result().unwrap_err().to_string();
+        assert!(err.contains("heartbeat_interval"), "{}", err);
+        assert!(err.contains("端口已被其他进程占用"), "{}", err);
+    }
+}
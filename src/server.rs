@@ -1,23 +1,82 @@
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::Mutex;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use futures::stream::{FuturesUnordered, StreamExt};
 use tokio::time::interval;
 use tokio::select;
 use anyhow::{Result, Context};
 use log::{info, warn, error, debug};
 use uuid::Uuid;
 
-use crate::config::Config;
+use crate::admin::{self, AdminServer, AdminState};
+#[cfg(feature = "grpc")]
+use crate::grpc::GrpcServer;
+#[cfg(feature = "mqtt")]
+use crate::mqtt_bridge::MqttBridge;
+use crate::bandwidth::BandwidthMap;
+use crate::carrier_nat::{self, CarrierNatStats};
+use crate::cluster::{ClusterDelivery, GossipRegistry, HandoffRoute, HandoffSession, PeerRegistry, RegisteredPeer};
+use crate::codec::CodecSet;
+use crate::config::{ClusterBackend, Config, ControlCommand, OversizedRoutePolicy, PacketLogMode, PeerRole, RoutingMode};
+use crate::dns_bootstrap::DnsBootstrap;
+use crate::events::{EventBus, EventKind};
+use crate::correlation::PendingReplies;
+use crate::handler::{MessageHandler, Requester};
+use crate::handshake_cookie::HandshakeCookies;
+use crate::heartbeat::HeartbeatPlan;
+use crate::i18n;
+use crate::keepalive::{KeepaliveProber, ProbeAction};
+use crate::latency::LatencyMatrix;
+use crate::log_capture::RecentLogs;
+use crate::maintenance::Maintenance;
+use crate::memory::{MemoryAccounting, MemoryUsage};
+use crate::metrics::ServerMetrics;
 use crate::network::NetworkManager;
-use crate::peer::{PeerManager, Peer, PeerStatus};
-use crate::protocol::{NodeInfo, Message, MessageType, PeerInfo, HandshakeProtocol};
+use crate::peer::{ConnectionLimits, PeerManager, Peer, PeerRelease, PeerSnapshot, PeerStatus};
+use crate::peer_list::PeerListSnapshot;
+use crate::plugin::{Plugin, PluginContext, PluginRegistry};
+use crate::protocol::{
+    BandwidthProbe, BandwidthReport, CarrierNat, LinkQuality, ProbeRole, GetConfigResponse, GetPeersResponse, GetRoutesResponse, GetStatsResponse, HandshakeProtocol, Message, MessageType, NodeInfo, P2PPath,
+    AddressUpdate, DeliveryReceipt, DiagnoseReport, MigrateAddressRequest, PeerCounts, PeerInfo, RouteEntry, RpcCall, ServiceLookupResponse,
+    DisconnectReason, KeyRotation, MtuProbe, PathMtu, PunchBeacon, ServiceRegistration, TcpPunch, WatchRequest, WatchResponse, RelayFrame, RELAY_FRAME_CAPABILITY,
+    RELAY_FRAME_HEADER_LEN, RateLimitStanding, RelayClose, RelayCloseReason, RouteRejectReason, RouteRejection, parse_relay_data,
+};
+use crate::pubsub::{Published, TopicBus};
+use crate::quarantine::PanicQuarantine;
+use crate::scanner::ScannerDetector;
+use crate::spoof_guard::SpoofGuard;
+use crate::scripting::{Hook, HookContext, PolicyScripts, Verdict};
+use crate::identity::{ServerIdentity, ServerIdentityFile};
+use crate::reachability::PeerTraits;
+use crate::rpc::{ServiceDirectory, DEFAULT_CALL_TIMEOUT, MAX_CALL_TIMEOUT};
+use crate::relay::{self, ClosedRelay, RelaySessions};
+use crate::offline::OfflineStore;
+use crate::route_log::RouteIdLog;
 use crate::router::{MessageRouter, RoutedMessage};
+use crate::scheduler::{Enqueue, FairScheduler};
+use crate::log_capture::packet_preview;
+use crate::supervisor::{panic_message, Supervisor};
 use crate::stun_server::StunServer;
 use crate::stun_protocol::is_stun_packet;
+use crate::telemetry::Telemetry;
+use crate::tcp_punch::TcpPunchOffers;
+use crate::ack_batch::{AckBatcher, AckQueued};
+use crate::pmtu::MtuSearch;
+use crate::dormant::{self, Dormancy};
+use crate::timesync::{check_freshness, estimate_offset, instant_to_unix_millis, unix_millis, Freshness};
+use crate::topology::{self, TopologyFormat, TopologySnapshot};
+use crate::webhooks::Webhooks;
+use crate::tr;
+
+/// 弃用的字符串命令只在首次使用时警告一次
+static LEGACY_COMMAND_WARNED: AtomicBool = AtomicBool::new(false);
 
 pub struct P2PServer {
     config: Config,
-    network_manager: NetworkManager,
+    network_manager: Arc<NetworkManager>,
     peer_manager: Arc<PeerManager>,
     local_node_info: NodeInfo,
     message_router: Arc<MessageRouter>,
@@ -28,12 +87,70 @@ pub struct P2PServer {
     broadcast_exclude_id: Arc<Mutex<Option<Uuid>>>,
     /// STUN服务器实例
     stun_server: Option<Arc<StunServer>>,
+    /// 运行指标
+    metrics: Arc<ServerMetrics>,
+    /// 中继会话统计
+    relay_sessions: Arc<RelaySessions>,
+    /// 内存统计与软限制
+    memory: Arc<MemoryAccounting>,
+    /// 主题发布/订阅
+    topic_bus: Arc<TopicBus>,
+    /// 节点注册的 RPC 服务
+    services: Arc<ServiceDirectory>,
+    /// 计划维护公告与排空状态
+    maintenance: Arc<Maintenance>,
+    /// 后台任务监督
+    supervisor: Arc<Supervisor>,
+    quarantine: Arc<PanicQuarantine>,
+    /// 协议扫描器识别与屏蔽
+    scanners: Arc<ScannerDetector>,
+    /// 对握手响应签名的服务器身份密钥
+    identity: Arc<ServerIdentity>,
+    /// 无状态握手 cookie（启用时）
+    handshake_cookies: Arc<HandshakeCookies>,
+    /// 最近日志缓冲区（由启动程序注入，供管理接口展示）
+    recent_logs: Option<Arc<RecentLogs>>,
+    /// OTLP 遥测
+    telemetry: Arc<Telemetry>,
+    /// 集群共享节点注册表（集群模式）
+    peer_registry: Option<Arc<dyn PeerRegistry>>,
+    /// 应用层数据消息处理器
+    message_handler: Option<Arc<dyn MessageHandler>>,
+    /// 服务器发起、等待节点应答的请求
+    pending_replies: Arc<PendingReplies>,
+    /// 消息策略脚本（scripting 特性）
+    scripts: Option<Arc<PolicyScripts>>,
+    /// 已注册的插件
+    plugins: PluginRegistry,
+    /// 强制断开节点后释放其状态的钩子，运行期间由服务器持有
+    release_hook: Option<Arc<PeerResources>>,
+    /// 等待对方应答的 TCP 打洞提议
+    tcp_punch_offers: Arc<TcpPunchOffers>,
+    /// 积攒中的批量确认，未开启 `ack_batch` 时为 `None`
+    ack_batcher: Option<Arc<AckBatcher>>,
 }
 
 impl P2PServer {
     pub async fn new(config: Config) -> Result<Self> {
-        let network_manager = NetworkManager::new(config.listen_address).await
+        let codecs = CodecSet::new(config.codec.format.build()?, config.codec.auto_detect);
+        Self::with_codecs(config, codecs).await
+    }
+
+    /// 使用自定义的编解码器集合创建服务器（例如注册了自定义编码）
+    pub async fn with_codecs(config: Config, codecs: CodecSet) -> Result<Self> {
+        i18n::set_language(config.logging.language);
+        config.validate()?;
+        let network_manager = NetworkManager::with_options(config.listen_address, codecs, &config.network).await
             .context("创建网络管理器失败")?;
+        #[cfg(feature = "chaos")]
+        if config.chaos.enable {
+            network_manager.faults().set_config(config.chaos.clone())?;
+            warn!("{}", tr!("网络故障注入已启用，仅应在测试环境使用", "Network fault injection enabled; use only in test environments"));
+        }
+        #[cfg(not(feature = "chaos"))]
+        if config.chaos.enable {
+            warn!("{}", tr!("故障注入需要启用 chaos 特性，已忽略 chaos.enable", "Fault injection requires the chaos feature, ignoring chaos.enable"));
+        }
         
         let local_addr = network_manager.local_addr();
         let mut local_node_info = NodeInfo::new(
@@ -41,19 +158,80 @@ impl P2PServer {
             local_addr,
             config.network_id.clone(), // 传递 network_id
         );
-        local_node_info.network_id = config.network_id.clone();
+        local_node_info.network_id = crate::intern::intern(&config.network_id);
+        local_node_info.addresses = config.network.advertise_addresses.clone();
+        if !local_node_info.addresses.is_empty() {
+            info!("{}", tr!("通告本地地址: {:?}", "Advertised local addresses: {:?}", local_node_info.advertised_addrs()));
+        }
         
-        let peer_manager = Arc::new(PeerManager::new(
-            local_node_info.clone(),
-            config.max_connections,
-        ));
-        let message_router = Arc::new(MessageRouter::new(
-            local_node_info.id,
-            peer_manager.clone(),
-        ));
+        if let Some(path) = &config.identity_path {
+            local_node_info.id = ServerIdentityFile::load_or_create(path)?.node_id;
+        }
+        let identity = Arc::new(ServerIdentity::from_config(&config.identity)?);
+        let metrics = Arc::new(ServerMetrics::new());
+        let mut peer_manager = PeerManager::with_limits(local_node_info.clone(), ConnectionLimits::from_config(&config))
+            .with_identity(identity.clone())
+            .with_keepalive(Arc::new(KeepaliveProber::new(config.keepalive.clone())))
+            .with_pinned(config.pinning.peers.clone())
+            .with_discovery_max_bytes(config.discovery.max_payload_bytes)
+            .with_node_info_limits(config.node_info_limits.clone())
+            .with_carrier_nat(config.carrier_nat.clone())
+            .with_spoof_guard(config.spoof_guard.clone())
+            .with_metrics(metrics.clone())
+            .with_events(Arc::new(EventBus::new(config.events.buffer)))
+            .with_bandwidth(Arc::new(BandwidthMap::new(
+                config.bandwidth.reference_bps,
+                Duration::from_secs(config.bandwidth.report_timeout_secs),
+            )));
+        if config.offline_store.enable {
+            info!(
+                "{}",
+                tr!(
+                    "离线消息暂存已启用: 每个节点最多 {} 条，保留 {} 秒",
+                    "Offline message store enabled: up to {} messages per node, kept for {} seconds",
+                    config.offline_store.max_per_peer,
+                    config.offline_store.ttl_secs,
+                )
+            );
+            peer_manager = peer_manager.with_offline_store(Arc::new(OfflineStore::new(&config.offline_store)));
+        }
+        let peer_manager = Arc::new(peer_manager);
+        for (origin, public_key) in &config.routing.origin_keys {
+            peer_manager.identities().admit(*origin, Some(public_key))?;
+        }
+        let dedup_window = Duration::from_secs(config.routing.dedup_window_secs);
+        let route_log = RouteIdLog::open(config.routing.dedup_log_path.as_deref(), config.routing.dedup_log_capacity, dedup_window)
+            .unwrap_or_else(|e| {
+                warn!("{}", tr!("{}，路由去重日志将只保存在内存中", "{}, route dedup log will be kept in memory only", e));
+                RouteIdLog::in_memory(config.routing.dedup_log_capacity, dedup_window)
+            });
+        let mut message_router = MessageRouter::new_with_mode(local_node_info.id, peer_manager.clone(), config.routing.mode)
+            .with_route_log(Arc::new(route_log))
+            .with_signature_policy(config.routing.signatures)
+            .with_metrics(metrics.clone());
+        if config.routing.broadcast_dedup_window_ms > 0 {
+            message_router = message_router.with_broadcast_dedup(Duration::from_millis(config.routing.broadcast_dedup_window_ms));
+        }
+        let message_router = Arc::new(message_router);
         // 启动路由器的消息缓存清理任务
         let _cache_task = message_router.start_cache_cleanup_task();
+        // 链路状态模式下启动通告刷新与老化任务
+        if config.routing.mode == RoutingMode::LinkState {
+            let _lsa_task = message_router.start_link_state_refresh_task(
+                Duration::from_secs(config.routing.lsa_refresh_interval),
+                Duration::from_secs(config.routing.lsa_max_age),
+            );
+        }
         
+        let relay_sessions = Arc::new(RelaySessions::new());
+        let memory = Arc::new(MemoryAccounting::new(
+            config.memory.clone(),
+            peer_manager.clone(),
+            message_router.clone(),
+            relay_sessions.clone(),
+            metrics.clone(),
+        ));
+
         // 初始化STUN服务器（如果启用）
         let stun_server = if config.stun_server.enable {
             let stun_bind_addr = std::net::SocketAddr::new(
@@ -61,29 +239,56 @@ impl P2PServer {
                 config.stun_server.port
             );
             
-            match StunServer::new(config.stun_server.clone(), stun_bind_addr).await {
+            match StunServer::with_network(config.stun_server.clone(), stun_bind_addr, &config.network).await {
                 Ok(server) => {
-                    info!("STUN服务器初始化成功，监听端口: {}", config.stun_server.port);
-                    Some(Arc::new(server))
+                    info!("{}", tr!("STUN服务器初始化成功，监听端口: {}", "STUN server initialized, listening on port: {}", config.stun_server.port));
+                    Some(Arc::new(server.with_metrics(metrics.clone())))
                 }
                 Err(e) => {
-                    warn!("STUN服务器初始化失败: {}，将禁用STUN功能", e);
+                    warn!("{}", tr!("STUN服务器初始化失败: {}，将禁用STUN功能", "STUN server initialization failed: {}, STUN disabled", e));
                     None
                 }
             }
         } else {
-            info!("STUN服务器已禁用");
+            info!("{}", tr!("STUN服务器已禁用", "STUN server disabled"));
             None
         };
         
-        info!("P2P服务器初始化完成");
-        info!("节点ID: {}", local_node_info.id);
-        info!("监听地址: {}", local_addr);
-        info!("最大连接数: {}", config.max_connections);
+        let telemetry = Arc::new(Telemetry::new(config.telemetry.clone()));
+        let supervisor = Arc::new(Supervisor::new(config.supervisor.clone()));
+        let quarantine = Arc::new(PanicQuarantine::new(config.panic_isolation.clone()));
+        let scanners = Arc::new(ScannerDetector::new(config.scanner_detection.clone()));
+        let scripts = PolicyScripts::load(&config.scripting, metrics.clone())?.map(Arc::new);
+
+        // 初始化集群注册表（如果启用）
+        let peer_registry: Option<Arc<dyn PeerRegistry>> = if config.cluster.enable {
+            match config.cluster.backend {
+                ClusterBackend::Gossip => {
+                    let client_addr = config.cluster.advertise_address.unwrap_or(local_addr);
+                    let registry = GossipRegistry::bind(local_node_info.id, client_addr, config.cluster.clone()).await?;
+                    info!("{}", tr!("集群模式已启用（gossip），监听 {}", "Cluster mode enabled (gossip), listening on {}", registry.local_addr()?));
+                    Some(Arc::new(registry))
+                }
+            }
+        } else {
+            None
+        };
+
+        info!("{}", tr!("P2P服务器初始化完成", "P2P server initialized"));
+        info!("{}", tr!("节点ID: {}", "Node ID: {}", local_node_info.id));
+        info!("{}", tr!("监听地址: {}", "Listen address: {}", local_addr));
+        info!("{}", tr!("最大连接数: {}", "Max connections: {}", config.max_connections));
+        if let Some(soft_limit) = peer_manager.limits().soft() {
+            info!("{}", tr!("连接数软限制: {}", "Soft connection limit: {}", soft_limit));
+        }
         
+        let tcp_punch_offers = Arc::new(TcpPunchOffers::new(Duration::from_secs(config.tcp_punch.offer_timeout_secs)));
+        let ack_batcher = config.ack_batch.enable.then(|| Arc::new(AckBatcher::new(config.ack_batch.max_ids)));
+        let handshake_cookies = Arc::new(HandshakeCookies::new(&config.handshake_cookies));
+
         Ok(Self {
             config,
-            network_manager,
+            network_manager: Arc::new(network_manager),
             peer_manager,
             local_node_info,
             message_router,
@@ -91,9 +296,520 @@ impl P2PServer {
             broadcast_task: Arc::new(Mutex::new(None)),
             broadcast_exclude_id: Arc::new(Mutex::new(None)),
             stun_server,
+            metrics,
+            relay_sessions,
+            memory,
+            topic_bus: Arc::new(TopicBus::new()),
+            services: Arc::new(ServiceDirectory::new()),
+            maintenance: Arc::new(Maintenance::new()),
+            supervisor,
+            quarantine,
+            scanners,
+            identity,
+            handshake_cookies,
+            recent_logs: None,
+            telemetry,
+            peer_registry,
+            message_handler: None,
+            pending_replies: Arc::new(PendingReplies::new()),
+            scripts,
+            plugins: PluginRegistry::new(),
+            release_hook: None,
+            tcp_punch_offers,
+            ack_batcher,
+        })
+    }
+
+    /// 注入最近日志缓冲区，管理接口将通过它展示日志
+    pub fn set_recent_logs(&mut self, logs: Arc<RecentLogs>) {
+        self.recent_logs = Some(logs);
+    }
+
+    /// 获取运行指标
+    pub fn metrics(&self) -> Arc<ServerMetrics> {
+        self.metrics.clone()
+    }
+
+    /// 获取节点间 P2P 路径的带宽估计
+    pub fn bandwidth(&self) -> Arc<BandwidthMap> {
+        self.peer_manager.bandwidth().clone()
+    }
+
+    /// 获取节点上报的往返时延矩阵
+    pub fn latency(&self) -> Arc<LatencyMatrix> {
+        self.peer_manager.latency().clone()
+    }
+
+    /// 内存统计与软限制
+    pub fn memory(&self) -> Arc<MemoryAccounting> {
+        self.memory.clone()
+    }
+
+    /// 后台任务监督器（健康检查）
+    pub fn supervisor(&self) -> Arc<Supervisor> {
+        self.supervisor.clone()
+    }
+
+    /// 数据包处理 panic 统计与来源封禁
+    pub fn quarantine(&self) -> Arc<PanicQuarantine> {
+        self.quarantine.clone()
+    }
+
+    /// 协议扫描器识别与屏蔽
+    pub fn scanners(&self) -> Arc<ScannerDetector> {
+        self.scanners.clone()
+    }
+
+    /// 冒用节点身份的握手识别与审计记录
+    pub fn spoof_guard(&self) -> Arc<SpoofGuard> {
+        self.peer_manager.spoof_guard().clone()
+    }
+
+    /// 结构化事件总线，可直接订阅而不经过事件流输出
+    pub fn events(&self) -> Arc<EventBus> {
+        self.peer_manager.events().clone()
+    }
+
+    /// 本服务器的节点ID，配置了 `identity_path` 时重启后保持不变
+    pub fn node_id(&self) -> Uuid {
+        self.local_node_info.id
+    }
+
+    /// 服务器身份密钥，其指纹供客户端固定
+    pub fn identity(&self) -> Arc<ServerIdentity> {
+        self.identity.clone()
+    }
+
+    /// 获取关闭信号发送端，可在 `run` 运行期间从其他任务触发关闭
+    pub fn shutdown_sender(&mut self) -> tokio::sync::broadcast::Sender<()> {
+        self.shutdown_tx
+            .get_or_insert_with(|| tokio::sync::broadcast::channel(1).0)
+            .clone()
+    }
+
+    /// 注册应用层数据消息处理器，服务器无法识别的 `Data` 消息将交给它处理
+    pub fn set_message_handler(&mut self, handler: Arc<dyn MessageHandler>) {
+        self.message_handler = Some(handler);
+    }
+
+    /// 注册插件，认领的扩展标签的消息将交给它处理；须在 `run` 之前调用
+    pub fn register_plugin(&mut self, plugin: Arc<dyn Plugin>) -> Result<()> {
+        self.plugins.register(plugin.clone())?;
+        info!("{}", tr!("已注册插件 {}，扩展标签: {:?}", "Registered plugin {}, extension tags: {:?}", plugin.name(), plugin.tags()));
+        Ok(())
+    }
+
+    /// 获取向节点发起请求并等待应答的句柄，可交给消息处理器持有
+    pub fn requester(&self) -> Requester {
+        Requester::new(self.peer_manager.clone(), self.pending_replies.clone())
+    }
+
+    /// 使用自定义的集群注册表后端（替换配置中的后端）
+    pub fn set_peer_registry(&mut self, registry: Arc<dyn PeerRegistry>) {
+        self.peer_registry = Some(registry);
+    }
+
+    /// 启动集群任务：注册表后台任务和本地节点同步任务；其他实例转来的事件由主循环处理
+    fn start_cluster_tasks(
+        &self,
+    ) -> (Vec<tokio::task::JoinHandle<()>>, Option<tokio::sync::mpsc::UnboundedReceiver<ClusterDelivery>>) {
+        let Some(registry) = self.peer_registry.clone() else {
+            return (Vec::new(), None);
+        };
+        let (delivery_tx, delivery_rx) = tokio::sync::mpsc::unbounded_channel::<ClusterDelivery>();
+        let registry_task = registry.clone().start(delivery_tx);
+
+        // 周期性地将本实例的已认证节点发布到注册表
+        let peer_manager = self.peer_manager.clone();
+        let instance_id = self.local_node_info.id;
+        let sync_interval = Duration::from_millis(self.config.cluster.gossip_interval_ms.max(50));
+        let sync_task = tokio::spawn(async move {
+            let mut interval = interval(sync_interval);
+            loop {
+                interval.tick().await;
+                let mut local = Vec::new();
+                for p in peer_manager.get_authenticated_peers().await {
+                    let p = p.read().await;
+                    if let Some(mut node_info) = p.node_info.clone() {
+                        // 观察到的地址替换监听地址，节点通告的本地地址保留在 `addresses` 中
+                        node_info.addresses = node_info.advertised_addrs();
+                        node_info.listen_addr = p.addr();
+                        local.push(RegisteredPeer { node_info, nat_type: p.nat_type.clone(), instance_id });
+                    }
+                }
+                registry.publish_local(local);
+            }
+        });
+
+        (vec![registry_task, sync_task], Some(delivery_rx))
+    }
+
+    /// 处理其他实例转来的事件
+    async fn handle_cluster_delivery(&self, delivery: ClusterDelivery) {
+        match delivery {
+            ClusterDelivery::Message { target, message } => match self.peer_manager.get_peer(&target).await {
+                Some(peer) => {
+                    if let Err(e) = peer.read().await.send_message(&message).await {
+                        warn!("{}", tr!("投递集群转发消息到 {} 失败: {}", "Failed to deliver cluster-forwarded message to {}: {}", target, e));
+                    }
+                }
+                None => debug!("{}", tr!("集群转发的目标节点不在本实例: {}", "Cluster-forwarded target node is not on this instance: {}", target)),
+            },
+            ClusterDelivery::Handoff { from, sessions } => {
+                let mut adopted = 0;
+                for session in sessions {
+                    let node_id = session.node_info.id;
+                    let connection = self.network_manager.get_or_create_connection(session.node_info.listen_addr).await;
+                    match self.peer_manager.adopt_peer(connection, &session).await {
+                        Ok(_) => {
+                            adopted += 1;
+                            self.message_router.update_routing_table(node_id, node_id, 1).await;
+                            for route in session.routes {
+                                if route.destination != self.local_node_info.id {
+                                    self.message_router
+                                        .update_routing_table(route.destination, route.next_hop, route.distance)
+                                        .await;
+                                }
+                            }
+                        }
+                        Err(e) => warn!("{}", tr!("接管节点 {} 失败: {}", "Failed to take over node {}: {}", node_id, e)),
+                    }
+                }
+                info!("{}", tr!("已接管实例 {} 移交的 {} 个节点", "Took over {1} nodes handed over by instance {0}", from, adopted));
+                if adopted > 0 {
+                    self.schedule_peerlist_broadcast(None).await;
+                }
+            }
+            ClusterDelivery::Relay { from, session_id, from_peer_id, target, data } => {
+                self.handle_tunneled_relay(from, session_id, from_peer_id, target, data).await;
+            }
+            ClusterDelivery::RelayClose { session_id, reason } => {
+                if let Some(closed) = self.relay_sessions.close(&session_id) {
+                    relay::notify_closed(&self.peer_manager, &[closed], reason).await;
+                }
+            }
+        }
+    }
+
+    /// 处理其他实例经隧道转来的中继数据：以对方的会话ID登记镜像会话，计入本实例的统计后转发给目标
+    async fn handle_tunneled_relay(&self, instance_id: Uuid, session_id: Uuid, from_peer_id: Uuid, target: Uuid, data: Vec<u8>) {
+        ServerMetrics::add(&self.metrics.relay_tunnel_bytes, data.len() as u64);
+        let refused = if !self.config.allow_symmetric_nat_relay {
+            Some(RelayCloseReason::Closed)
+        } else if self.relay_target(&target).await.is_err() {
+            Some(RelayCloseReason::PeerDisconnected)
+        } else {
+            None
+        };
+        if let Some(reason) = refused {
+            debug!("{}", tr!("拒绝集群实例 {} 经隧道转来的中继数据: {} -> {}", "Rejecting relay data tunnelled from cluster instance {}: {} -> {}", instance_id, from_peer_id, target));
+            if let Some(registry) = &self.peer_registry {
+                let _ = registry.close_relay(instance_id, session_id, reason).await;
+            }
+            return;
+        }
+        if self.relay_sessions.mirror(session_id, from_peer_id, target, instance_id) {
+            self.peer_manager.events().emit(
+                EventKind::RelayStarted,
+                Some(from_peer_id),
+                serde_json::json!({ "to_node_id": target, "session_id": session_id, "instance_id": instance_id }),
+            );
+            info!("{}", tr!("建立跨实例中继会话 {}: {} -> {}（经实例 {}）", "Cross-instance relay session {} opened: {} -> {} (via instance {})", session_id, from_peer_id, target, instance_id));
+        }
+        if let Err(reason) = self.deliver_relay(session_id, from_peer_id, target, RelayPayload::Bytes(data)).await {
+            debug!("{}", tr!("转发集群隧道中继数据失败: {}", "Failed to forward cluster tunnel relay data: {}", reason));
+        }
+    }
+
+    /// 将已认证节点的会话移交给其他实例，并通知客户端重连到接收方
+    async fn handoff_sessions(&self) -> Result<usize> {
+        let Some(registry) = &self.peer_registry else {
+            return Ok(0);
+        };
+        let routes = self.message_router.get_routing_table_snapshot().await;
+        let peers = self.peer_manager.get_authenticated_peers().await;
+        let mut sessions = Vec::new();
+        for p in &peers {
+            let p = p.read().await;
+            if let Some(mut node_info) = p.node_info.clone() {
+                // 观察到的地址替换监听地址，节点通告的本地地址保留在 `addresses` 中
+                node_info.addresses = node_info.advertised_addrs();
+                node_info.listen_addr = p.addr();
+                let routes = routes
+                    .iter()
+                    .filter(|(destination, next_hop, _)| *next_hop == node_info.id && *destination != node_info.id)
+                    .map(|&(destination, next_hop, distance)| HandoffRoute { destination, next_hop, distance })
+                    .collect();
+                sessions.push(HandoffSession {
+                    public_key: self.peer_manager.identities().current(&node_info.id),
+                    node_info,
+                    nat_type: p.nat_type.clone(),
+                    routes,
+                    session_ticket: p.session_ticket.clone(),
+                });
+            }
+        }
+        if sessions.is_empty() {
+            return Ok(0);
+        }
+
+        let count = sessions.len();
+        let target = registry.handoff(sessions).await?;
+        let hint = Message::reconnect(target.client_addr, true, "服务器正在下线".to_string());
+        for p in peers {
+            if let Err(e) = p.read().await.send_message(&hint).await {
+                warn!("{}", tr!("发送重连提示到 {} 失败: {}", "Failed to send reconnect hint to {}: {}", p.read().await.addr(), e));
+            }
+        }
+        Ok(count)
+    }
+
+    /// 向尚未连接的固定节点发送握手请求，节点应答后按普通节点管理
+    async fn connect_pinned_peers(&self) {
+        let request = match Message::handshake_request(self.local_node_info.clone()) {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("{}", tr!("构造固定节点握手请求失败: {}", "Failed to build handshake request for static peer: {}", e));
+                return;
+            }
+        };
+        for pinned in self.peer_manager.pinned() {
+            if let Some(peer) = self.peer_manager.get_peer(&pinned.node_id).await
+                && peer.read().await.is_authenticated()
+            {
+                continue;
+            }
+            let connection = self.network_manager.get_or_create_connection(pinned.addr).await;
+            if self.peer_manager.get_peer_by_addr(&pinned.addr).await.is_none()
+                && let Err(e) = self.peer_manager.add_peer(connection.clone()).await
+            {
+                warn!("{}", tr!("为固定节点 {} 创建连接失败: {}", "Failed to create connection for static peer {}: {}", pinned.node_id, e));
+                continue;
+            }
+            match connection.send_message(&request).await {
+                Ok(()) => debug!("{}", tr!("向固定节点 {} ({}) 发起握手", "Initiating handshake with pinned node {} ({})", pinned.node_id, pinned.addr)),
+                Err(e) => warn!("{}", tr!("向固定节点 {} ({}) 发起握手失败: {}", "Failed to start handshake with static peer {} ({}): {}", pinned.node_id, pinned.addr, e)),
+            }
+        }
+    }
+
+    /// 启动保活探测任务：按节点空闲时长发送探测包，回显超时的探测给出结果
+    fn start_keepalive_task(&self) -> tokio::task::JoinHandle<()> {
+        let peer_manager = self.peer_manager.clone();
+
+        self.supervisor.spawn("keepalive", move || {
+            let peer_manager = peer_manager.clone();
+            async move {
+                let mut interval = interval(Duration::from_millis(500));
+                loop {
+                    interval.tick().await;
+                    for action in peer_manager.keepalive().poll(Instant::now()) {
+                        let (peer_id, message) = match action {
+                            ProbeAction::Send { peer_id, delay_secs } => (peer_id, Message::keepalive_probe(delay_secs)),
+                            ProbeAction::Finished { peer_id, recommended_secs } => {
+                                info!("{}", tr!("节点 {} 的保活探测结束，建议保活间隔 {} 秒", "Keepalive probing of node {} finished, suggested keepalive interval {} seconds", peer_id, recommended_secs));
+                                (peer_id, Message::keepalive_result(recommended_secs))
+                            }
+                        };
+                        let Some(peer) = peer_manager.get_peer(&peer_id).await else {
+                            peer_manager.keepalive().cancel(&peer_id);
+                            continue;
+                        };
+                        if let Err(e) = peer.read().await.send_message(&message).await {
+                            warn!("{}", tr!("向节点 {} 发送保活探测失败: {}", "Failed to send keepalive probe to node {}: {}", peer_id, e));
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// 启动DNS引导任务（如果配置了域名）：定期解析引导记录，把 gossip 种子加入集群成员
+    fn start_dns_bootstrap_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        self.config.dns_bootstrap.domain.as_ref()?;
+        let resolver = match DnsBootstrap::from_config(&self.config.dns_bootstrap) {
+            Ok(resolver) => resolver,
+            Err(e) => {
+                warn!("{}", tr!("DNS引导启动失败: {}，将禁用DNS引导", "DNS bootstrap failed to start: {}, DNS bootstrap disabled", e));
+                return None;
+            }
+        };
+        let registry = self.peer_registry.clone();
+        let network_id = self.config.network_id.clone();
+        let refresh = Duration::from_secs(self.config.dns_bootstrap.refresh_interval_secs.max(1));
+
+        Some(tokio::spawn(async move {
+            let mut interval = interval(refresh);
+            loop {
+                interval.tick().await;
+                let info = match resolver.resolve().await {
+                    Ok(info) => info,
+                    Err(e) => {
+                        warn!("{}", tr!("解析DNS引导记录 {} 失败: {}", "Failed to parse DNS bootstrap record {}: {}", resolver.domain(), e));
+                        continue;
+                    }
+                };
+                debug!("{}", tr!("DNS引导记录: {:?}", "DNS bootstrap record: {:?}", info));
+                if let Some(id) = &info.network_id
+                    && *id != network_id
+                {
+                    warn!("{}", tr!("DNS引导记录中的网络ID {} 与本地配置 {} 不一致", "Network ID {} in DNS bootstrap record does not match local configuration {}", id, network_id));
+                }
+                if let Some(registry) = &registry
+                    && !info.seeds.is_empty()
+                {
+                    registry.add_members(&info.seeds);
+                    info!("{}", tr!("从DNS引导记录加入 {} 个集群种子", "Adding {} cluster seeds from DNS bootstrap records", info.seeds.len()));
+                }
+            }
+        }))
+    }
+
+    /// 启动事件流输出（如果启用）
+    async fn start_event_tasks(&self) -> Vec<tokio::task::JoinHandle<()>> {
+        if !self.config.events.enable {
+            return Vec::new();
+        }
+        match self.peer_manager.events().start_outputs(&self.config.events).await {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                warn!("{}", tr!("事件流启动失败: {}，将禁用事件流", "Event stream failed to start: {}, event stream disabled", e));
+                Vec::new()
+            }
+        }
+    }
+
+    /// 通知插件服务器开始运行
+    async fn start_plugins(&self) {
+        let context = PluginContext::new(self.network_manager.clone(), self.peer_manager.clone());
+        for plugin in self.plugins.plugins() {
+            if let Err(e) = plugin.start(context.clone()).await {
+                warn!("{}", tr!("插件 {} 启动失败: {}", "Plugin {} failed to start: {}", plugin.name(), e));
+            }
+        }
+    }
+
+    /// 启动 webhook 投递任务（如果启用）
+    fn start_webhook_tasks(&self) -> Vec<tokio::task::JoinHandle<()>> {
+        if !self.config.webhooks.enable {
+            return Vec::new();
+        }
+        let webhooks = Webhooks::new(self.config.webhooks.clone(), self.metrics.clone());
+        match webhooks.start(self.peer_manager.events()) {
+            Ok(tasks) => {
+                info!("{}", tr!("webhook 已启用: {} 个地址", "Webhooks enabled: {} endpoints", tasks.len()));
+                tasks
+            }
+            Err(e) => {
+                warn!("{}", tr!("webhook 启动失败: {}，将禁用 webhook", "Webhooks failed to start: {}, webhooks disabled", e));
+                Vec::new()
+            }
+        }
+    }
+
+    /// 启动OTLP导出任务（如果启用）
+    fn start_telemetry_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.telemetry.is_enabled() {
+            return None;
+        }
+        match self.telemetry.start_export_task(self.metrics.clone(), self.peer_manager.clone()) {
+            Ok(handle) => {
+                info!("{}", tr!("OTLP遥测导出已启用: {}", "OTLP telemetry export enabled: {}", self.config.telemetry.endpoint));
+                Some(handle)
+            }
+            Err(e) => {
+                warn!("{}", tr!("OTLP遥测导出启动失败: {}，将禁用遥测", "OTLP telemetry export failed to start: {}, telemetry disabled", e));
+                None
+            }
+        }
+    }
+
+    /// 管理接口与gRPC控制面共享的服务器状态
+    fn admin_state(&self) -> Arc<AdminState> {
+        Arc::new(AdminState {
+            local_node_id: self.local_node_info.id,
+            peer_manager: self.peer_manager.clone(),
+            message_router: self.message_router.clone(),
+            metrics: self.metrics.clone(),
+            relay_sessions: self.relay_sessions.clone(),
+            memory: self.memory.clone(),
+            topic_bus: self.topic_bus.clone(),
+            services: self.services.clone(),
+            recent_logs: self.recent_logs.clone(),
+            maintenance: self.maintenance.clone(),
+            supervisor: self.supervisor.clone(),
+            quarantine: self.quarantine.clone(),
+            scanners: self.scanners.clone(),
+            config: self.config.admin.clone(),
+            server_config: Arc::new(self.config.clone()),
+            #[cfg(feature = "chaos")]
+            faults: self.network_manager.faults().clone(),
         })
     }
 
+    /// 启动管理HTTP接口（如果启用）
+    async fn start_admin_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.config.admin.enable {
+            return None;
+        }
+        match AdminServer::bind(self.admin_state()).await {
+            Ok(admin) => Some(tokio::spawn(async move {
+                if let Err(e) = admin.run().await {
+                    error!("{}", tr!("管理接口运行失败: {}", "Admin API failed: {}", e));
+                }
+            })),
+            Err(e) => {
+                warn!("{}", tr!("管理接口启动失败: {}，将禁用管理接口", "Admin API failed to start: {}, admin API disabled", e));
+                None
+            }
+        }
+    }
+
+    /// 启动gRPC控制面（如果启用）
+    #[cfg(feature = "grpc")]
+    async fn start_grpc_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.config.grpc.enable {
+            return None;
+        }
+        match GrpcServer::bind(self.admin_state(), self.config.grpc.clone()).await {
+            Ok(grpc) => Some(tokio::spawn(async move {
+                if let Err(e) = grpc.run().await {
+                    error!("{}", tr!("gRPC控制面运行失败: {}", "gRPC control plane failed: {}", e));
+                }
+            })),
+            Err(e) => {
+                warn!("{}", tr!("gRPC控制面启动失败: {}，将禁用gRPC控制面", "gRPC control plane failed to start: {}, gRPC control plane disabled", e));
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "grpc"))]
+    async fn start_grpc_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if self.config.grpc.enable {
+            warn!("{}", tr!("gRPC控制面需要启用 grpc 特性，已忽略 grpc.enable", "gRPC control plane requires the grpc feature, ignoring grpc.enable"));
+        }
+        None
+    }
+
+    /// 启动MQTT桥接（如果启用）
+    #[cfg(feature = "mqtt")]
+    fn start_mqtt_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.config.mqtt.enable {
+            return None;
+        }
+        let bridge = MqttBridge::new(self.config.mqtt.clone(), self.topic_bus.clone(), self.peer_manager.clone());
+        Some(bridge.start())
+    }
+
+    #[cfg(not(feature = "mqtt"))]
+    fn start_mqtt_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if self.config.mqtt.enable {
+            warn!("{}", tr!("MQTT桥接需要启用 mqtt 特性，已忽略 mqtt.enable", "MQTT bridge requires the mqtt feature, ignoring mqtt.enable"));
+        }
+        None
+    }
+
     /// 调度一次去抖的节点列表广播，将在窗口结束后向所有节点推送当前列表
     async fn schedule_peerlist_broadcast(&self, exclude_id: Option<Uuid>) {
         // 记录最后一次加入的节点ID，用于在广播时排除该节点
@@ -117,15 +833,8 @@ impl P2PServer {
             };
 
             // 广播（按接收者定制，不发送给处于排除列表的节点）
-            let peers = peer_manager.get_authenticated_peers().await;
-            for p in peers {
-                let pid = p.read().await.id;
-                if exclude_id == Some(pid) { continue; }
-                let infos = peer_manager.get_peer_info_list_excluding(Some(pid)).await;
-                let msg = Message::discovery_response(infos);
-                if let Err(e) = p.read().await.send_message(&msg).await {
-                    warn!("去抖广播节点列表到 {} 失败: {}", p.read().await.addr(), e);
-                }
+            if let Err(e) = peer_manager.broadcast_peer_list(exclude_id).await {
+                warn!("{}", tr!("去抖广播节点列表失败: {}", "Debounced node list broadcast failed: {}", e));
             }
         });
 
@@ -133,10 +842,14 @@ impl P2PServer {
     }
     
     pub async fn run(&mut self) -> Result<()> {
-        let (shutdown_tx, mut shutdown_rx) = tokio::sync::broadcast::channel(1);
-        self.shutdown_tx = Some(shutdown_tx);
+        let mut shutdown_rx = self.shutdown_sender().subscribe();
         
-        info!("P2P服务器开始运行...");
+        info!("{}", tr!("P2P服务器开始运行...", "P2P server running..."));
+
+        // 强制断开节点（管理接口、封禁、配额）时释放其路由、中继会话等状态
+        let release_hook = Arc::new(self.peer_resources());
+        self.peer_manager.set_release_hook(Arc::downgrade(&release_hook) as Weak<dyn PeerRelease>);
+        self.release_hook = Some(release_hook);
         
         // 启动心跳任务
         let heartbeat_task = self.start_heartbeat_task();
@@ -146,87 +859,211 @@ impl P2PServer {
         
         // 启动统计任务
         let stats_task = self.start_stats_task();
+
+        // 启动内存软限制检查任务（如果配置了软限制）
+        let memory_task = self.start_memory_task();
         
         // 启动STUN服务器任务（如果启用）
         let stun_task = if let Some(ref stun_server) = self.stun_server {
             let stun_server_clone = stun_server.clone();
             Some(tokio::spawn(async move {
                 if let Err(e) = stun_server_clone.run().await {
-                    error!("STUN服务器运行失败: {}", e);
+                    error!("{}", tr!("STUN服务器运行失败: {}", "STUN server failed: {}", e));
                 }
             }))
         } else {
             None
         };
+
+        // 启动管理接口任务（如果启用）
+        let admin_task = self.start_admin_task().await;
+
+        // 启动gRPC控制面任务（如果启用）
+        let grpc_task = self.start_grpc_task().await;
+
+        // 启动MQTT桥接任务（如果启用）
+        let mqtt_task = self.start_mqtt_task();
+
+        // 启动遥测导出任务（如果启用）
+        let telemetry_task = self.start_telemetry_task();
+
+        // 启动事件流输出（如果启用）
+        let mut event_tasks = self.start_event_tasks().await;
+
+        // 启动 webhook 投递任务（如果启用）
+        event_tasks.extend(self.start_webhook_tasks());
+
+        // 通知已注册的插件
+        self.start_plugins().await;
+
+        // 启动保活探测任务
+        let keepalive_task = self.start_keepalive_task();
+
+        // 启动DNS引导任务（如果配置了域名）
+        let dns_bootstrap_task = self.start_dns_bootstrap_task();
+
+        // 启动集群任务（如果启用）
+        let (cluster_tasks, mut cluster_rx) = self.start_cluster_tasks();
         
-        // 主循环：接收UDP数据包
+        // 主循环：接收UDP数据包，按来源地址公平调度后并发处理
+        let server = &*self;
+        let workers = server.config.scheduler.workers.max(1);
+        let mut scheduler = FairScheduler::new(server.config.scheduler.clone());
+        let mut in_flight = FuturesUnordered::new();
+        let mut pinning_interval = interval(Duration::from_secs(server.config.pinning.reconnect_interval_secs.max(1)));
+        let mut bandwidth_interval = interval(Duration::from_secs(server.config.bandwidth.probe_interval_secs.max(1)));
         loop {
+            while in_flight.len() < workers
+                && let Some((source, data, received_at)) = scheduler.next(Instant::now())
+            {
+                in_flight.push(server.process_packet(source, data, received_at));
+            }
+            ServerMetrics::add(&server.metrics.packets_expired, scheduler.take_expired());
+
             select! {
                 // 接收UDP数据包
-                packet_result = self.network_manager.receive_from() => {
+                packet_result = server.network_manager.receive_from() => {
                     match packet_result {
+                        // 被封禁来源的数据包不进入调度队列
+                        Ok((_, sender_addr)) if server.quarantine.is_banned(&sender_addr.ip(), Instant::now()) => {
+                            ServerMetrics::incr(&server.metrics.packets_banned);
+                            debug!("{}", tr!("来源 {} 已被封禁，丢弃数据包", "Source {} is banned, dropping packet", sender_addr.ip()));
+                        }
+                        // 判定为扫描器的来源静默丢弃，不记日志
+                        Ok((_, sender_addr)) if server.scanners.is_blocked(&sender_addr.ip(), Instant::now()) => {
+                            ServerMetrics::incr(&server.metrics.packets_from_scanners);
+                        }
+                        // 超长数据包不进入调度队列，也不解析
+                        Ok((data, sender_addr)) if data.len() > server.config.max_datagram_size => {
+                            ServerMetrics::incr(&server.metrics.packets_oversized);
+                            server.reject_oversized(sender_addr, data.len()).await;
+                        }
                         Ok((data, sender_addr)) => {
-                            if let Err(e) = self.handle_udp_packet(data, sender_addr).await {
-                                error!("处理UDP数据包失败: {}", e);
+                            ServerMetrics::incr(&server.metrics.packets_received);
+                            ServerMetrics::add(&server.metrics.bytes_received, data.len() as u64);
+                            match scheduler.push(sender_addr, data, Instant::now()) {
+                                Enqueue::Queued => {}
+                                Enqueue::PeerQueueFull => {
+                                    ServerMetrics::incr(&server.metrics.packets_dropped);
+                                    debug!("{}", tr!("来源 {} 的待处理队列已满，丢弃数据包", "Pending queue for source {} is full, dropping packet", sender_addr));
+                                }
+                                Enqueue::Overloaded => {
+                                    ServerMetrics::incr(&server.metrics.packets_dropped);
+                                    warn!("{}", tr!("待处理数据包过多（{}），丢弃来自 {} 的数据包", "Too many pending packets ({}), dropping packet from {}", scheduler.queued(), sender_addr));
+                                }
                             }
                         }
                         Err(e) => {
-                            error!("接收UDP数据包失败: {}", e);
+                            error!("{}", tr!("接收UDP数据包失败: {}", "Failed to receive UDP packet: {}", e));
                         }
                     }
                 }
+
+                // 数据包处理完成，释放该来源
+                Some(source) = in_flight.next(), if !in_flight.is_empty() => {
+                    scheduler.complete(source);
+                }
+                
+                // 其他集群实例转来的事件
+                Some(delivery) = async { cluster_rx.as_mut()?.recv().await } => {
+                    server.handle_cluster_delivery(delivery).await;
+                }
+                
+                // 向不在线的固定节点重新发起握手
+                _ = pinning_interval.tick(), if !server.peer_manager.pinned().is_empty() => {
+                    server.connect_pinned_peers().await;
+                }
+                
+                // 定期探测直连会话的带宽
+                _ = bandwidth_interval.tick(), if server.config.bandwidth.probe_interval_secs > 0 => {
+                    server.probe_direct_sessions().await;
+                }
                 
                 // 监听关闭信号
                 _ = shutdown_rx.recv() => {
-                    info!("收到关闭信号，正在停止服务器...");
+                    info!("{}", tr!("收到关闭信号，正在停止服务器...", "Shutdown signal received, stopping server..."));
                     break;
                 }
             }
         }
+        // 等待已开始处理的数据包完成，排队中的数据包直接丢弃
+        while in_flight.next().await.is_some() {}
         
-        // 等待所有任务完成
-        if let Some(stun_task) = stun_task {
-            let (hb_res, cl_res, st_res, stun_res) = tokio::join!(heartbeat_task, cleanup_task, stats_task, stun_task);
-            if let Err(e) = hb_res {
-                warn!("心跳任务结束时发生错误: {}", e);
-            }
-            if let Err(e) = cl_res {
-                warn!("清理任务结束时发生错误: {}", e);
-            }
-            if let Err(e) = st_res {
-                warn!("统计任务结束时发生错误: {}", e);
-            }
-            if let Err(e) = stun_res {
-                warn!("STUN服务器任务结束时发生错误: {}", e);
-            }
-        } else {
-            let (hb_res, cl_res, st_res) = tokio::join!(heartbeat_task, cleanup_task, stats_task);
-            if let Err(e) = hb_res {
-                warn!("心跳任务结束时发生错误: {}", e);
-            }
-            if let Err(e) = cl_res {
-                warn!("清理任务结束时发生错误: {}", e);
+        if let Some(admin_task) = admin_task {
+            admin_task.abort();
+        }
+        if let Some(grpc_task) = grpc_task {
+            grpc_task.abort();
+        }
+        if let Some(mqtt_task) = mqtt_task {
+            mqtt_task.abort();
+        }
+        if let Some(telemetry_task) = telemetry_task {
+            telemetry_task.abort();
+        }
+        for task in event_tasks {
+            task.abort();
+        }
+        // 滚动升级：下线前把会话移交给其他实例
+        if self.config.cluster.handoff_on_shutdown {
+            match self.handoff_sessions().await {
+                Ok(0) => {}
+                Ok(count) => info!("{}", tr!("已移交 {} 个会话", "Handed over {} sessions", count)),
+                Err(e) => warn!("{}", tr!("会话移交失败，客户端需要重新握手: {}", "Session handover failed, clients will need to handshake again: {}", e)),
             }
-            if let Err(e) = st_res {
-                warn!("统计任务结束时发生错误: {}", e);
+        }
+        for task in cluster_tasks {
+            task.abort();
+        }
+        
+        // 后台任务均为无限循环，关闭时主动取消并等待其退出
+        let mut tasks = vec![
+            (tr!("心跳", "heartbeat"), heartbeat_task),
+            (tr!("清理", "cleanup"), cleanup_task),
+            (tr!("统计", "stats"), stats_task),
+            (tr!("保活探测", "keepalive probe"), keepalive_task),
+        ];
+        if let Some(stun_task) = stun_task {
+            tasks.push((tr!("STUN服务器", "STUN server"), stun_task));
+        }
+        if let Some(dns_bootstrap_task) = dns_bootstrap_task {
+            tasks.push((tr!("DNS引导", "DNS bootstrap"), dns_bootstrap_task));
+        }
+        if let Some(memory_task) = memory_task {
+            tasks.push((tr!("内存检查", "memory check"), memory_task));
+        }
+        for (name, task) in tasks {
+            task.abort();
+            if let Err(e) = task.await
+                && !e.is_cancelled()
+            {
+                warn!("{}", tr!("{}任务结束时发生错误: {}", "{} task ended with an error: {}", name, e));
             }
         }
         
-        info!("P2P服务器已停止");
+        info!("{}", tr!("P2P服务器已停止", "P2P server stopped"));
         Ok(())
     }
 
+    /// 处理 `RelayRequest`：校验权限与目标节点，建立（或沿用）中继会话并以 `RelayResponse` 返回会话ID；
+    /// 请求携带 `data` 时同时转发这份数据
     async fn handle_relay_request(
         &self,
         peer: Arc<tokio::sync::RwLock<Peer>>,
         message: &Message,
     ) -> Result<()> {
+        let (from_peer_id, authenticated) = {
+            let peer = peer.read().await;
+            (peer.id, peer.is_authenticated())
+        };
         // 检查是否允许为全对称NAT客户端转发流量
         if !self.config.allow_symmetric_nat_relay {
-            let error_response = Message::relay_response(
-                false,
-                Some("服务器不允许流量转发".to_string()),
-            );
+            let error_response = Message::relay_response(false, None, Some("服务器不允许流量转发".to_string()));
+            peer.read().await.send_message(&error_response).await?;
+            return Ok(());
+        }
+        if !authenticated {
+            let error_response = Message::relay_response(false, None, Some("节点未完成握手".to_string()));
             peer.read().await.send_message(&error_response).await?;
             return Ok(());
         }
@@ -237,131 +1074,524 @@ impl P2PServer {
             .get("target_peer_id")
             .and_then(|v| v.as_str())
             .and_then(|s| uuid::Uuid::parse_str(s).ok());
-
-        let data_array = message
-            .payload
-            .get("data")
-            .and_then(|v| v.as_array());
-
-        if let (Some(target_peer_id), Some(data_array)) = (target_peer_id, data_array) {
-            // 将JSON数组转换为字节数组
-            let mut data = Vec::new();
-            for value in data_array {
-                if let Some(num) = value.as_u64() {
-                    if num <= 255 {
-                        data.push(num as u8);
-                    } else {
-                        let error_response = Message::relay_response(
-                            false,
-                            Some("数据格式错误：字节值超出范围".to_string()),
-                        );
-                        peer.read().await.send_message(&error_response).await?;
-                        return Ok(());
-                    }
-                } else {
-                    let error_response = Message::relay_response(
-                        false,
-                        Some("数据格式错误：非数字值".to_string()),
-                    );
+        let Some(target_peer_id) = target_peer_id else {
+            let error_response = Message::relay_response(false, None, Some("缺少必要参数".to_string()));
+            peer.read().await.send_message(&error_response).await?;
+            return Ok(());
+        };
+        let data = match parse_relay_data(&message.payload) {
+            Ok(data) => data,
+            Err(reason) => {
+                let error_response = Message::relay_response(false, None, Some(reason));
+                peer.read().await.send_message(&error_response).await?;
+                return Ok(());
+            }
+        };
+        if target_peer_id == from_peer_id {
+            let error_response = Message::relay_response(false, None, Some("不能向自身转发".to_string()));
+            peer.read().await.send_message(&error_response).await?;
+            return Ok(());
+        }
+        let (session_id, started) = match self.relay_target(&target_peer_id).await {
+            Ok(_) => self.relay_sessions.bind(from_peer_id, target_peer_id),
+            Err(reason) => match self.remote_relay_target(&target_peer_id).await {
+                // 目标连接在集群中的其他实例上，经实例之间的隧道中继
+                Some(entry) => self.relay_sessions.bind_remote(from_peer_id, target_peer_id, entry.instance_id),
+                None => {
+                    let error_response = Message::relay_response(false, None, Some(reason));
                     peer.read().await.send_message(&error_response).await?;
                     return Ok(());
                 }
-            }
-
-            // 查找目标peer
-            if let Some(target_peer) = self.peer_manager.get_peer(&target_peer_id).await {
-                if target_peer.read().await.is_authenticated() {
-                    // 创建转发的数据包
-                    let from_peer_id = peer.read().await.id;
-                    let relay_data_message = Message::relay_data(from_peer_id, data.clone());
-                    
-                    // 转发数据到目标peer
-                    match target_peer.read().await.send_message(&relay_data_message).await {
-                        Ok(_) => {
-                            // 发送成功响应
-                            let success_response = Message::relay_response(true, None);
-                            peer.read().await.send_message(&success_response).await?;
-                            info!(
-                                "成功转发数据: {} -> {} ({} bytes)",
-                                from_peer_id,
-                                target_peer_id,
-                                data.len()
-                            );
-                        }
-                        Err(e) => {
-                            // 发送失败响应
-                            let error_response = Message::relay_response(
-                                false,
-                                Some(format!("转发失败: {}", e)),
-                            );
-                            peer.read().await.send_message(&error_response).await?;
-                            warn!("转发数据失败: {}", e);
-                        }
-                    }
-                } else {
-                    let error_response = Message::relay_response(
-                        false,
-                        Some("目标节点未认证".to_string()),
-                    );
-                    peer.read().await.send_message(&error_response).await?;
-                }
-            } else {
-                let error_response = Message::relay_response(
-                    false,
-                    Some("目标节点未找到".to_string()),
-                );
-                peer.read().await.send_message(&error_response).await?;
-            }
-        } else {
-            let error_response = Message::relay_response(
-                false,
-                Some("缺少必要参数".to_string()),
+            },
+        };
+        if started {
+            self.peer_manager.events().emit(
+                EventKind::RelayStarted,
+                Some(from_peer_id),
+                serde_json::json!({ "to_node_id": target_peer_id, "session_id": session_id }),
             );
+            info!("{}", tr!("建立中继会话 {}: {} -> {}", "Relay session {} opened: {} -> {}", session_id, from_peer_id, target_peer_id));
+        }
+        if let Some(data) = data
+            && let Err(reason) = self.forward_relay(&peer, session_id, target_peer_id, RelayPayload::Bytes(data)).await
+        {
+            let error_response = Message::relay_response(false, Some(session_id), Some(reason));
+            peer.read().await.send_message(&error_response).await?;
+            return Ok(());
+        }
+        peer.read().await.send_message(&Message::relay_session_opened(session_id, target_peer_id)).await?;
+        Ok(())
+    }
+
+    /// 处理节点经已建立的中继会话发送的 `RelayData`：按会话ID找到接收方并转发；
+    /// 成功时不回复，失败时以 `RelayResponse` 告知
+    async fn handle_relay_data(&self, peer: Arc<tokio::sync::RwLock<Peer>>, message: &Message) -> Result<()> {
+        let session_id = message
+            .payload
+            .get("session_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| uuid::Uuid::parse_str(s).ok());
+        let Some(session_id) = session_id else {
+            let error_response = Message::relay_response(false, None, Some("缺少会话ID".to_string()));
             peer.read().await.send_message(&error_response).await?;
+            return Ok(());
+        };
+        let result = match parse_relay_data(&message.payload) {
+            Ok(Some(data)) => self.relay_by_session(&peer, session_id, RelayPayload::Bytes(data)).await,
+            Ok(None) => Err("缺少必要参数".to_string()),
+            Err(reason) => Err(reason),
+        };
+        if let Err(reason) = result {
+            let error_response = Message::relay_response(false, Some(session_id), Some(reason));
+            peer.read().await.send_message(&error_response).await?;
+        }
+        Ok(())
+    }
+
+    /// 处理中继会话关闭请求：会话任一方都可关闭，关闭后通知双方
+    async fn handle_relay_close(&self, peer: Arc<tokio::sync::RwLock<Peer>>, message: &Message) -> Result<()> {
+        let Ok(request) = serde_json::from_value::<RelayClose>(message.payload.clone()) else {
+            let error_response = Message::relay_response(false, None, Some("缺少会话ID".to_string()));
+            peer.read().await.send_message(&error_response).await?;
+            return Ok(());
+        };
+        let peer_id = peer.read().await.id;
+        let owned = self
+            .relay_sessions
+            .resolve(&request.session_id)
+            .is_some_and(|(from, to)| from == peer_id || to == peer_id);
+        let closed = if owned { self.relay_sessions.close(&request.session_id) } else { None };
+        let Some(closed) = closed else {
+            let error_response = Message::relay_response(false, Some(request.session_id), Some("中继会话不存在或已失效".to_string()));
+            peer.read().await.send_message(&error_response).await?;
+            return Ok(());
+        };
+        self.peer_resources().close_relays(&[closed], RelayCloseReason::Closed).await;
+        Ok(())
+    }
+
+    /// 处理节点发来的二进制中继帧：绕过消息解码，按帧头中的会话ID转发
+    async fn handle_relay_frame(&self, packet: Vec<u8>, sender_addr: std::net::SocketAddr) -> Result<()> {
+        let Some(session_id) = RelayFrame::parse(&packet).map(|frame| frame.session_id) else {
+            return Ok(());
+        };
+        let Some(peer) = self.peer_manager.get_peer_by_addr(&sender_addr).await else {
+            debug!("{}", tr!("忽略来自未知地址 {} 的中继帧", "Ignoring relay frame from unknown address {}", sender_addr));
+            return Ok(());
+        };
+        if let Err(reason) = self.relay_by_session(&peer, session_id, RelayPayload::Frame(packet)).await {
+            let error_response = Message::relay_response(false, Some(session_id), Some(reason));
+            peer.read().await.send_message(&error_response).await?;
+        }
+        Ok(())
+    }
+
+    /// 校验会话属于发送方后转发
+    async fn relay_by_session(&self, peer: &Arc<tokio::sync::RwLock<Peer>>, session_id: Uuid, payload: RelayPayload) -> std::result::Result<(), String> {
+        let (from_peer_id, authenticated) = {
+            let peer = peer.read().await;
+            (peer.id, peer.is_authenticated())
+        };
+        match self.relay_sessions.resolve(&session_id) {
+            Some((from, to)) if from == from_peer_id && authenticated && self.config.allow_symmetric_nat_relay => {
+                self.forward_relay(peer, session_id, to, payload).await
+            }
+            _ => Err("中继会话不存在或已失效".to_string()),
+        }
+    }
+
+    /// 中继目标须在线且已认证
+    async fn relay_target(&self, target_peer_id: &Uuid) -> std::result::Result<Arc<tokio::sync::RwLock<Peer>>, String> {
+        match self.peer_manager.get_peer(target_peer_id).await {
+            Some(target_peer) if target_peer.read().await.is_authenticated() => {
+                self.wake_if_dormant(target_peer_id).await;
+                Ok(target_peer)
+            }
+            Some(_) => Err("目标节点未认证".to_string()),
+            None => Err("目标节点未找到".to_string()),
+        }
+    }
+
+    /// 在集群其他实例上查找中继目标
+    async fn remote_relay_target(&self, target_peer_id: &Uuid) -> Option<RegisteredPeer> {
+        self.peer_registry.as_ref()?.locate(target_peer_id).await
+    }
+
+    /// 经中继会话把数据转发给目标节点，失败时返回原因
+    ///
+    /// 目标通告了 [`RELAY_FRAME_CAPABILITY`] 时以二进制帧转发：节点发来的帧只写入发送方ID，不重新编码；
+    /// 否则以 JSON `RelayData` 转发。
+    async fn forward_relay(
+        &self,
+        peer: &Arc<tokio::sync::RwLock<Peer>>,
+        session_id: Uuid,
+        target_peer_id: Uuid,
+        mut payload: RelayPayload,
+    ) -> std::result::Result<(), String> {
+        let (from_peer_id, addr) = {
+            let peer = peer.read().await;
+            (peer.id, peer.addr())
+        };
+        if self.scripts.is_some() {
+            let fields = serde_json::json!({ "from": from_peer_id, "to": target_peer_id });
+            let context = HookContext::new(addr, fields).with_data(payload.data().to_vec());
+            match self.run_script(Hook::PreRelay, context) {
+                Verdict::Deny(reason) => return Err(format!("转发被策略拒绝: {}", reason)),
+                Verdict::ReplaceData(replacement) => payload = RelayPayload::Bytes(replacement),
+                _ => {}
+            }
         }
 
+        if self.relay_sessions.remote_instance(&session_id).is_some() {
+            return self.tunnel_relay(session_id, from_peer_id, target_peer_id, payload.data()).await;
+        }
+        self.deliver_relay(session_id, from_peer_id, target_peer_id, payload).await
+    }
+
+    /// 把中继数据发给本实例上的目标节点并计入统计
+    async fn deliver_relay(&self, session_id: Uuid, from_peer_id: Uuid, target_peer_id: Uuid, payload: RelayPayload) -> std::result::Result<(), String> {
+        let target_peer = self.relay_target(&target_peer_id).await?;
+        let target_peer = target_peer.read().await;
+        let frames = target_peer.node_info.as_ref().is_some_and(|info| info.has_capability(RELAY_FRAME_CAPABILITY));
+        let len = payload.data().len();
+        // 探测出的路径 MTU 装不下的数据包在路上会被丢弃，直接拒绝并告知发送方
+        if let Some(PathMtu { mtu, limited: true }) = target_peer.path_mtu {
+            let size = if frames {
+                RELAY_FRAME_HEADER_LEN + len
+            } else {
+                let message = Message::relay_data(from_peer_id, Some(session_id), payload.data().to_vec());
+                serde_json::to_vec(&message).map_or(0, |bytes| bytes.len())
+            };
+            if size > mtu {
+                ServerMetrics::incr(&self.metrics.relay_mtu_drops);
+                return Err(format!("数据包（{} 字节）超过目标节点的路径MTU（{} 字节）", size, mtu));
+            }
+        }
+        let sent = match payload {
+            RelayPayload::Frame(mut packet) if frames => {
+                RelayFrame::set_sender(&mut packet, from_peer_id);
+                target_peer.send_raw(&packet).await
+            }
+            payload if frames => {
+                let frame = RelayFrame { session_id, from_peer_id, data: payload.data() };
+                target_peer.send_raw(&frame.encode()).await
+            }
+            payload => {
+                let message = Message::relay_data(from_peer_id, Some(session_id), payload.data().to_vec());
+                target_peer.send_message(&message).await
+            }
+        };
+        drop(target_peer);
+        if let Err(e) = sent {
+            warn!("{}", tr!("转发数据失败: {}", "Failed to forward data: {}", e));
+            return Err(format!("转发失败: {}", e));
+        }
+        self.account_relay(from_peer_id, target_peer_id, len);
+        debug!("{}", tr!("成功转发数据: {} -> {} ({} bytes)", "Forwarded data: {} -> {} ({} bytes)", from_peer_id, target_peer_id, len));
         Ok(())
     }
+
+    /// 经集群隧道把中继数据交给目标所在的实例，由其转发给目标节点
+    async fn tunnel_relay(&self, session_id: Uuid, from_peer_id: Uuid, target_peer_id: Uuid, data: &[u8]) -> std::result::Result<(), String> {
+        let Some(registry) = &self.peer_registry else {
+            return Err("中继会话不存在或已失效".to_string());
+        };
+        let Some(entry) = registry.locate(&target_peer_id).await else {
+            return Err("目标节点未找到".to_string());
+        };
+        if let Err(e) = registry.relay(&entry, session_id, from_peer_id, data).await {
+            warn!("{}", tr!("经集群实例 {} 转发中继数据失败: {}", "Failed to tunnel relay data through cluster instance {}: {}", entry.instance_id, e));
+            return Err(format!("转发失败: {}", e));
+        }
+        ServerMetrics::add(&self.metrics.relay_tunnel_bytes, data.len() as u64);
+        self.account_relay(from_peer_id, target_peer_id, data.len());
+        debug!("{}", tr!("经集群实例 {} 转发中继数据: {} -> {} ({} bytes)", "Forwarding relay data via cluster instance {}: {} -> {} ({} bytes)", entry.instance_id, from_peer_id, target_peer_id, data.len()));
+        Ok(())
+    }
+
+    /// 计入一次成功的中继转发；跨实例会话的两个实例各自计入
+    fn account_relay(&self, from_peer_id: Uuid, target_peer_id: Uuid, len: usize) {
+        ServerMetrics::incr(&self.metrics.relay_packets);
+        ServerMetrics::add(&self.metrics.relay_bytes, len as u64);
+        self.relay_sessions.record(from_peer_id, target_peer_id, len);
+        self.peer_manager.record_contact(from_peer_id, target_peer_id);
+    }
     
-    async fn handle_udp_packet(&self, data: Vec<u8>, sender_addr: std::net::SocketAddr) -> Result<()> {
-        debug!("处理来自 {} 的UDP数据包: {} bytes", sender_addr, data.len());
+    /// 处理调度器分派的数据包，完成后返回其来源地址
+    ///
+    /// 处理过程中的 panic 被捕获，不会中断主循环；反复触发 panic 的来源IP按 `panic_isolation` 配置封禁。
+    async fn process_packet(&self, source: std::net::SocketAddr, data: Vec<u8>, received_at: Instant) -> std::net::SocketAddr {
+        match std::panic::AssertUnwindSafe(self.handle_udp_packet(data, source, received_at)).catch_unwind().await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                ServerMetrics::incr(&self.metrics.handle_errors);
+                error!("{}", tr!("处理UDP数据包失败: {}", "Failed to handle UDP packet: {}", e));
+            }
+            Err(panic) => {
+                ServerMetrics::incr(&self.metrics.packet_panics);
+                error!("{}", tr!("处理来自 {} 的数据包时发生 panic: {}", "Panic while handling packet from {}: {}", source, panic_message(&*panic)));
+                if self.quarantine.record_panic(source.ip(), Instant::now()) {
+                    warn!("{}", tr!("来源 {} 反复触发 panic，封禁 {} 秒", "Source {} panicked repeatedly, banned for {} seconds", source.ip(), self.config.panic_isolation.ban_secs));
+                    let peer = self.peer_manager.get_peer_by_addr(&source).await;
+                    let node_id = match &peer {
+                        Some(peer) => peer.read().await.node_info.as_ref().map(|info| info.id),
+                        None => None,
+                    };
+                    self.peer_manager.events().emit(
+                        EventKind::PeerBanned,
+                        node_id,
+                        serde_json::json!({ "ip": source.ip(), "reason": "panic", "block_secs": self.config.panic_isolation.ban_secs }),
+                    );
+                    if let Some(peer) = peer {
+                        let peer_id = peer.read().await.id;
+                        self.peer_manager.kick(&peer_id, DisconnectReason::ProtocolViolation, "数据包反复导致处理失败，已被封禁").await;
+                    }
+                }
+            }
+        }
+        source
+    }
+
+    /// 调用策略脚本钩子；未加载脚本时放行
+    fn run_script(&self, hook: Hook, context: HookContext) -> Verdict {
+        match &self.scripts {
+            Some(scripts) => scripts.run(hook, context),
+            None => Verdict::Allow,
+        }
+    }
+
+    /// 丢弃超长数据包；发送方是已知节点时告知其上限，未知来源不应答
+    async fn reject_oversized(&self, sender_addr: std::net::SocketAddr, size: usize) {
+        let max = self.config.max_datagram_size;
+        debug!("{}", tr!("来自 {} 的数据包 {} 字节超过上限 {} 字节，已丢弃", "Packet from {} is {} bytes, over the {} byte limit, dropped", sender_addr, size, max));
+        let Some(peer) = self.peer_manager.get_peer_by_addr(&sender_addr).await else { return };
+        let notice = Message::new(
+            MessageType::Error,
+            serde_json::json!({ "error": "数据包过大", "size": size, "max_datagram_size": max }),
+        );
+        if let Err(e) = peer.read().await.send_message(&notice).await {
+            debug!("{}", tr!("向 {} 发送超长数据包通知失败: {}", "Failed to send oversized packet notice to {}: {}", sender_addr, e));
+        }
+    }
+
+    /// 新来源的握手请求：带回有效 cookie 或未达触发速率时放行，否则只回复绑定其地址的新 cookie
+    async fn admit_handshake(&self, sender_addr: std::net::SocketAddr, message: &Message, request_len: usize) -> Result<bool> {
+        let cookies = &self.handshake_cookies;
+        let flooded = cookies.note_request();
+        let cookie = message.payload.get("cookie").and_then(|v| v.as_str());
+        if cookie.is_some_and(|cookie| cookies.verify(sender_addr, cookie)) {
+            ServerMetrics::incr(&self.metrics.handshake_cookie_passes);
+            return Ok(true);
+        }
+        if !flooded {
+            return Ok(true);
+        }
+        self.send_cookie_challenge(sender_addr, message, request_len).await?;
+        debug!("{}", tr!("新来源握手过多，向 {} 发放 cookie", "Too many handshakes from new sources, issuing a cookie to {}", sender_addr));
+        Ok(false)
+    }
+
+    /// 声明了公钥的握手：密钥证明须覆盖本服务器为该来源签发的 cookie，且请求ID未被其他来源用过
+    ///
+    /// 截获的握手请求因此无法从攻击者自己的地址重放，也就无法借重连接管已认证的节点ID。
+    async fn admit_keyed_handshake(&self, sender_addr: std::net::SocketAddr, message: &Message, request_len: usize) -> Result<bool> {
+        let cookie = message.payload.get("cookie").and_then(|v| v.as_str());
+        if !cookie.is_some_and(|cookie| self.handshake_cookies.verify(sender_addr, cookie)) {
+            self.send_cookie_challenge(sender_addr, message, request_len).await?;
+            debug!("{}", tr!("带公钥的握手未带回有效 cookie，向 {} 发放 cookie", "Handshake with a public key did not return a valid cookie, issuing a cookie to {}", sender_addr));
+            return Ok(false);
+        }
+        if !self.handshake_cookies.claim(message.id, sender_addr) {
+            ServerMetrics::incr(&self.metrics.handshake_replays_rejected);
+            warn!("{}", tr!("丢弃来自 {} 的重放握手请求 {}", "Dropping replayed handshake request {1} from {0}", sender_addr, message.id));
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// 无状态地回复 cookie 质询；质询不大于请求，伪造来源的握手无法借服务器放大流量
+    async fn send_cookie_challenge(&self, sender_addr: std::net::SocketAddr, message: &Message, request_len: usize) -> Result<()> {
+        let challenge = Message::handshake_cookie(message.id, self.handshake_cookies.issue(sender_addr));
+        if serde_json::to_vec(&challenge)?.len() > request_len {
+            ServerMetrics::incr(&self.metrics.handshake_cookie_drops);
+            debug!("{}", tr!("来自 {} 的握手请求小于 cookie 质询，直接丢弃", "Handshake request from {} is smaller than the cookie challenge, dropping", sender_addr));
+            return Ok(());
+        }
+        self.network_manager.send_to(&challenge, sender_addr).await?;
+        ServerMetrics::incr(&self.metrics.handshake_cookie_challenges);
+        Ok(())
+    }
+
+    async fn handle_udp_packet(&self, data: Vec<u8>, sender_addr: std::net::SocketAddr, received_at: Instant) -> Result<()> {
+        debug!("{}", tr!("处理来自 {} 的UDP数据包: {} bytes", "Handling UDP packet from {}: {} bytes", sender_addr, data.len()));
         
         // 检查是否为STUN消息
         if is_stun_packet(&data) {
-            debug!("检测到STUN消息，来自: {}", sender_addr);
+            debug!("{}", tr!("检测到STUN消息，来自: {}", "Detected STUN message from: {}", sender_addr));
             
             // 如果STUN服务器启用，则由STUN服务器处理
             if let Some(ref _stun_server) = self.stun_server {
                 // STUN消息由独立的STUN服务器处理，这里不需要额外处理
                 // 因为STUN服务器有自己的UDP套接字
-                debug!("STUN消息将由STUN服务器处理");
+                debug!("{}", tr!("STUN消息将由STUN服务器处理", "STUN message will be handled by the STUN server"));
                 return Ok(());
             } else {
-                warn!("收到STUN消息但STUN服务器未启用，来自: {}", sender_addr);
+                warn!("{}", tr!("收到STUN消息但STUN服务器未启用，来自: {}", "Received STUN message but STUN server is disabled, from: {}", sender_addr));
                 return Ok(());
             }
         }
         
-        // 处理P2P消息
-        // 打印最原始的UDP数据包内容
-        if let Ok(text) = std::str::from_utf8(&data) {
-            info!("收到来自 {} 的原始UDP数据包: {}", sender_addr, text);
-        } else {
-            info!("收到来自 {} 的原始UDP数据包 (非UTF-8): {:?}", sender_addr, data);
+        // 二进制中继帧不经过消息解码
+        if RelayFrame::parse(&data).is_some() {
+            return self.handle_relay_frame(data, sender_addr).await;
+        }
+
+        // 处理P2P消息；数据包内容可能包含用户数据，只在 `raw` 模式下记录
+        let packet_log = self.config.logging.packets;
+        if packet_log == PacketLogMode::Raw {
+            debug!("{}", tr!("收到来自 {} 的原始UDP数据包: {}", "Received raw UDP packet from {}: {}", sender_addr, packet_preview(&data, self.config.logging.raw_preview_bytes)));
         }
         
-        // 解析消息
-        let mut message = self.network_manager.parse_message(&data)?;
+        // 解析消息；无法解析的数据包计入扫描器识别，不作为处理错误记录
+        let (mut message, codec) = match self.network_manager.decode_message(&data) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                ServerMetrics::incr(&self.metrics.packets_malformed);
+                debug!("{}", tr!("来自 {} 的数据包无法解析: {}", "Packet from {} could not be parsed: {}", sender_addr, e));
+                if let Some((kind, block)) = self.scanners.record_garbage(sender_addr.ip(), &data, Instant::now()) {
+                    ServerMetrics::incr(&self.metrics.scanners_detected);
+                    warn!("{}", tr!("来源 {} 反复发送非协议数据（{:?}），静默丢弃其数据包 {} 秒", "Source {} keeps sending non-protocol data ({:?}), silently dropping its packets for {} seconds", sender_addr.ip(), kind, block.as_secs()));
+                    self.peer_manager.events().emit(
+                        EventKind::PeerBanned,
+                        None,
+                        serde_json::json!({ "ip": sender_addr.ip(), "reason": "scanner", "kind": kind, "block_secs": block.as_secs() }),
+                    );
+                }
+                return Ok(());
+            }
+        };
         message.sender_addr = Some(sender_addr);
+        if packet_log == PacketLogMode::Redacted {
+            debug!("{}", tr!("收到来自 {} 的 {:?} 消息，{} 字节", "Received {1:?} message from {0}, {2} bytes", sender_addr, message.message_type, data.len()));
+        }
         
-        // 获取或创建连接
+        // 启用无状态握手时，只有握手请求与凭会话票据的地址迁移能让未知来源获得连接与节点：
+        // 握手洪泛时先无状态地应答，新来源带回有效 cookie 之前不为其分配任何状态；其余消息在握手前一律丢弃
+        if self.config.handshake_cookies.enable
+            && self.peer_manager.get_peer_by_addr(&sender_addr).await.is_none()
+        {
+            match message.message_type {
+                MessageType::HandshakeRequest => {
+                    if !self.admit_handshake(sender_addr, &message, data.len()).await? {
+                        return Ok(());
+                    }
+                }
+                MessageType::MigrateAddress => {}
+                _ => {
+                    ServerMetrics::incr(&self.metrics.handshake_cookie_drops);
+                    debug!("{}", tr!("丢弃未握手来源 {} 的 {:?} 消息", "Dropping {1:?} message from unauthenticated source {0}", sender_addr, message.message_type));
+                    return Ok(());
+                }
+            }
+        }
+
+        if message.message_type == MessageType::HandshakeRequest
+            && message.payload.get("public_key").is_some_and(|key| !key.is_null())
+            && !self.admit_keyed_handshake(sender_addr, &message, data.len()).await?
+        {
+            return Ok(());
+        }
+
+        // 获取或创建连接，回复沿用对端的编码
         let connection = self.network_manager.get_or_create_connection(sender_addr).await;
+        connection.set_codec(codec);
+
+        // 地址迁移在创建临时节点之前处理：发送方是换了地址的已认证节点
+        if message.message_type == MessageType::MigrateAddress {
+            self.handle_migrate_address(connection, &message).await?;
+            ServerMetrics::incr(&self.metrics.messages_handled);
+            return Ok(());
+        }
+
+        // 达到硬限制时拒绝新节点的握手，并提示重试时间
+        if message.message_type == MessageType::HandshakeRequest
+            && self.peer_manager.get_peer_by_addr(&sender_addr).await.is_none()
+            && self.peer_manager.pinned_by_addr(&sender_addr).is_none()
+            && self.peer_manager.is_full().await
+        {
+            let limits = self.peer_manager.limits();
+            let mut reject = Message::handshake_rejected(
+                self.local_node_info.clone(),
+                format!("服务器连接数已满（{}），请稍后重试", limits.hard()),
+                limits.retry_after_secs(),
+            )?;
+            self.identity.sign_handshake_response(&mut reject, message.id, &self.config.network_id);
+            connection.send_message(&reject).await?;
+            self.network_manager.remove_connection(&sender_addr).await;
+            warn!("{}", tr!("连接数已达硬限制 {}，拒绝来自 {} 的握手", "Hard connection limit {} reached, rejecting handshake from {}", limits.hard(), sender_addr));
+            return Ok(());
+        }
+        // 维护排空期间拒绝新节点的握手，提示在维护结束后重试
+        if message.message_type == MessageType::HandshakeRequest
+            && self.maintenance.is_draining()
+            && self.peer_manager.get_peer_by_addr(&sender_addr).await.is_none()
+        {
+            let mut reject = Message::handshake_rejected(
+                self.local_node_info.clone(),
+                "服务器正在维护，请稍后重试".to_string(),
+                self.maintenance.retry_after_secs(),
+            )?;
+            self.identity.sign_handshake_response(&mut reject, message.id, &self.config.network_id);
+            connection.send_message(&reject).await?;
+            self.network_manager.remove_connection(&sender_addr).await;
+            info!("{}", tr!("维护排空中，拒绝来自 {} 的握手", "Draining for maintenance, rejecting handshake from {}", sender_addr));
+            return Ok(());
+        }
         
-        // 获取或创建peer
-        let peer = self.peer_manager.get_or_create_peer_by_addr(connection).await?;
+        // 获取peer；新来源在这里才分配节点
+        let peer = match self.peer_manager.get_peer_by_addr(&sender_addr).await {
+            Some(peer) => peer,
+            None => self.peer_manager.add_peer(connection).await?,
+        };
+        let (snapshot, offset_ms) = {
+            let guard = peer.read().await;
+            (guard.snapshot(), guard.clock_offset_ms)
+        };
+        if let Some(recommended_secs) = self.peer_manager.keepalive().on_inbound(&snapshot.id, Instant::now()) {
+            peer.read().await.send_message(&Message::keepalive_result(recommended_secs)).await?;
+        }
+        // 休眠节点发来任何消息即已醒来
+        self.end_dormancy(&peer).await?;
+
+        // 时间同步本身不检查时间戳，时钟偏差大的节点需要先靠它完成同步
+        if message.message_type == MessageType::TimeSync {
+            self.handle_time_sync(peer, &message, received_at).await?;
+            ServerMetrics::incr(&self.metrics.messages_handled);
+            return Ok(());
+        }
+        let freshness = check_freshness(&self.config.time_sync, message.timestamp, offset_ms, unix_millis(std::time::SystemTime::now()));
+        if freshness != Freshness::Fresh {
+            ServerMetrics::incr(&self.metrics.messages_stale);
+            warn!(
+                "{}",
+                tr!(
+                    "丢弃来自 {} 的消息 {:?}：时间戳 {} 校正后{}（时钟偏差 {:?} ms）",
+                    "Dropping message {1:?} from {0}: timestamp {2} is {3} after correction (clock offset {4:?} ms)",
+                    sender_addr,
+                    message.message_type,
+                    message.timestamp,
+                    if freshness == Freshness::Stale { tr!("已过期", "stale") } else { tr!("超前", "ahead") },
+                    offset_ms,
+                )
+            );
+            let err = Message::error("消息时间戳超出允许范围，请先发送 TimeSync 同步时钟".to_string());
+            peer.read().await.send_message(&err).await?;
+            return Ok(());
+        }
         
         // 处理消息
-        self.handle_message(peer, &message).await?;
+        self.handle_message(peer, &snapshot, &message).await?;
+        ServerMetrics::incr(&self.metrics.messages_handled);
         
         Ok(())
     }
@@ -369,68 +1599,123 @@ impl P2PServer {
     async fn handle_message(
         &self,
         peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
         message: &Message,
     ) -> Result<()> {
-        debug!("处理消息类型: {:?} 来自 {}", message.message_type, message.sender_addr.unwrap_or_else(|| "0.0.0.0:0".parse().unwrap()));
+        debug!("{}", tr!("处理消息类型: {:?} 来自 {}", "Handling message type: {:?} from {}", message.message_type, message.sender_addr.unwrap_or_else(|| "0.0.0.0:0".parse().unwrap())));
         
-        // 如果需要确认，发送ACK
-        if message.requires_ack {
+        // 如果需要确认，发送ACK；开启批量确认时先积攒，稍后合并发出
+        if message.requires_ack
+            && let (Some(batcher), Some(sender_addr)) = (&self.ack_batcher, message.sender_addr)
+        {
+            self.queue_ack(batcher, message.id, sender_addr).await;
+        } else if message.requires_ack {
             let ack_message = Message::ack(message.id, self.local_node_info.listen_addr);
             if let Some(sender_addr) = message.sender_addr {
                 if let Err(e) = self.network_manager.send_to(&ack_message, sender_addr).await {
-                    warn!("发送ACK失败: {}", e);
+                    warn!("{}", tr!("发送ACK失败: {}", "Failed to send ACK: {}", e));
                 }
                 info!(
-                    "已发送ACK: ack_for={} 给 {} (seq={:?})",
-                    message.id,
-                    sender_addr,
-                    message.sequence_number
+                    "{}",
+                    tr!(
+                        "已发送ACK: ack_for={} 给 {} (seq={:?})",
+                        "ACK sent: ack_for={} to {} (seq={:?})",
+                        message.id,
+                        sender_addr,
+                        message.sequence_number,
+                    )
                 );
             }
         }
         
         match message.message_type {
             MessageType::HandshakeRequest => {
-                info!("处理握手请求消息，来自 {}", peer.read().await.addr());
+                // 重传或重复投递的握手请求只重发首次的应答
+                if self.peer_manager.answer_duplicate_handshake(&peer, message).await? {
+                    return Ok(());
+                }
+                info!("{}", tr!("处理握手请求消息，来自 {}", "Handling handshake request from {}", snapshot.addr));
+                if self.scripts.is_some() {
+                    let node_info = HandshakeProtocol::validate_handshake_request(message).ok();
+                    let fields = serde_json::json!({
+                        "node_id": node_info.as_ref().map(|info| info.id),
+                        "name": node_info.as_ref().map(|info| info.name.clone()),
+                        "network_id": node_info.as_ref().map(|info| info.network_id.clone()),
+                        "payload": message.payload,
+                    });
+                    if let Verdict::Deny(reason) = self.run_script(Hook::PreHandshake, HookContext::new(snapshot.addr, fields)) {
+                        info!("{}", tr!("策略脚本拒绝了来自 {} 的握手: {}", "Policy script rejected handshake from {}: {}", snapshot.addr, reason));
+                        peer.read().await.send_message(&Message::error(format!("握手被策略拒绝: {}", reason))).await?;
+                        return Ok(());
+                    }
+                }
+                let mut span = self.telemetry.start_span("p2p.handshake");
+                span.set_attribute("peer.addr", snapshot.addr.to_string());
                 // 先解析以便在路由表中添加直连路由
                 if let Ok(node_info) = HandshakeProtocol::validate_handshake_request(message) {
+                    span.set_attribute("peer.id", node_info.id.to_string());
                     self.message_router
                         .update_routing_table(node_info.id, node_info.id, 1)
                         .await;
                     // 处理握手
-                    self.peer_manager.handle_handshake_request(peer, message).await?;
+                    let result = self.peer_manager.handle_handshake_request(peer.clone(), message).await;
+                    self.telemetry.end_span(span, &result);
+                    result?;
+                    self.assign_role(&peer).await;
+                    // 告知新节点尚未开始的维护计划
+                    if peer.read().await.is_authenticated()
+                        && let Some(notice) = self.maintenance.upcoming()
+                    {
+                        peer.read().await.send_message(&Message::maintenance_notice(&notice)?).await?;
+                    }
+                    if self.config.path_mtu.enable && peer.read().await.is_authenticated() {
+                        self.start_path_mtu_probe(node_info.id);
+                    }
                     // 去抖调度一次广播，排除该新加入节点，避免重复推送
                     self.schedule_peerlist_broadcast(Some(node_info.id)).await;
                     return Ok(());
                 }
                 // 验证失败仍尝试交由处理函数返回错误
-                self.peer_manager.handle_handshake_request(peer, message).await?;
+                let result = self.peer_manager.handle_handshake_request(peer, message).await;
+                self.telemetry.end_span(span, &result);
+                result?;
             }
             MessageType::HandshakeResponse => {
-                info!("处理握手响应消息，来自 {}", peer.read().await.addr());
+                info!("{}", tr!("处理握手响应消息，来自 {}", "Handling handshake response from {}", snapshot.addr));
                 self.peer_manager.handle_handshake_response(peer.clone(), message).await?;
+                let (remote_id, remote_addr) = {
+                    let guard = peer.read().await;
+                    (guard.id, guard.addr())
+                };
+                // 固定节点必须以配置的ID应答，防止地址被其他节点占用
+                if let Some(pinned) = self.peer_manager.pinned_by_addr(&remote_addr)
+                    && pinned.node_id != remote_id
+                {
+                    warn!("{}", tr!("固定节点地址 {} 应答的节点ID {} 与配置的 {} 不符，断开", "Static peer address {} answered with node ID {}, expected {}, disconnecting", remote_addr, remote_id, pinned.node_id));
+                    self.peer_manager.remove_peer(&remote_id).await;
+                    return Ok(());
+                }
                 // 握手成功后，添加直连路由（距离为1）
-                let remote_id = peer.read().await.id;
                 self.message_router
                     .update_routing_table(remote_id, remote_id, 1)
                     .await;
             }
             MessageType::Ping => {
-                info!("收到Ping，来自 {}", peer.read().await.addr());
+                info!("{}", tr!("收到Ping，来自 {}", "Ping received from {}", snapshot.addr));
                 self.peer_manager.handle_ping(peer, message).await?;
             }
             MessageType::Pong => {
-                info!("收到Pong，来自 {}", peer.read().await.addr());
+                info!("{}", tr!("收到Pong，来自 {}", "Pong received from {}", snapshot.addr));
                 self.peer_manager.handle_pong(peer, message).await?;
             }
             MessageType::DiscoveryRequest => {
-                Self::handle_discovery_request(&self.peer_manager, peer, message).await?;
+                Self::handle_discovery_request(&self.peer_manager, self.peer_registry.as_ref(), peer, snapshot, message).await?;
             }
             MessageType::DiscoveryResponse => {
-                info!("收到节点发现响应，来自 {}", peer.read().await.addr());
+                info!("{}", tr!("收到节点发现响应，来自 {}", "Discovery response received from {}", snapshot.addr));
                 // 解析对端提供的节点信息列表，并更新路由表（经该对端的下一跳，距离为2）
                 if let Ok(peer_list) = serde_json::from_value::<Vec<PeerInfo>>(message.payload.clone()) {
-                    let next_hop = peer.read().await.id;
+                    let next_hop = snapshot.id;
                     for p in &peer_list {
                         // 跳过本地节点和对端自身
                         if p.id == self.local_node_info.id || p.id == next_hop {
@@ -440,13 +1725,13 @@ impl P2PServer {
                             .update_routing_table(p.id, next_hop, 2)
                             .await;
                     }
-                    debug!("从 {} 更新路由项 {} 条", peer.read().await.addr(), peer_list.len());
+                    debug!("{}", tr!("从 {} 更新路由项 {} 条", "Updated {1} route entries from {0}", snapshot.addr, peer_list.len()));
                 } else {
-                    warn!("解析节点发现响应失败");
+                    warn!("{}", tr!("解析节点发现响应失败", "Failed to parse discovery response"));
                 }
             }
             MessageType::P2PConnect => {
-                info!("处理 P2P 直连协调请求，来自 {}", peer.read().await.addr());
+                info!("{}", tr!("处理 P2P 直连协调请求，来自 {}", "Handling P2P connect request from {}", snapshot.addr));
                 let target_id = message
                     .payload
                     .get("peer_id")
@@ -454,7 +1739,7 @@ impl P2PServer {
                     .and_then(|s| uuid::Uuid::parse_str(s).ok());
 
                 if let Some(target_id) = target_id {
-                    let requester_id = peer.read().await.id;
+                    let requester_id = snapshot.id;
                     if requester_id == target_id {
                         let err = Message::error("不能与自身建立直连".to_string());
                         peer.read().await.send_message(&err).await?;
@@ -463,19 +1748,55 @@ impl P2PServer {
                             let err = Message::error(format!("目标节点未认证: {}", target_id));
                             peer.read().await.send_message(&err).await?;
                         } else {
-                            let requester_addr = peer.read().await.addr();
+                            self.wake_if_dormant(&target_id).await;
+                            let requester_addr = snapshot.addr;
                             let target_addr = target_peer.read().await.addr();
 
-                            // 提取请求方的NAT穿透信息
-                            let requester_nat_type = message.payload.get("nat_type");
-                            let requester_predicted_ports = message.payload.get("predicted_ports");
-                            let requester_public_addr = message.payload.get("public_addr");
+                            // 记录请求方上报的NAT类型
+                            if let Some(nat_type) = message.payload.get("nat_type").and_then(|v| v.as_str()) {
+                                peer.write().await.nat_type = Some(nat_type.to_string());
+                            }
+
+                            let requester_addrs = peer.read().await.advertised_addrs();
+                            let target_addrs = target_peer.read().await.advertised_addrs();
+
+                            // 双方公网IP相同说明位于同一NAT之后，多数NAT不支持回环（hairpin），
+                            // 此时让双方优先尝试对方通告的内网地址
+                            let lan_shortcut = requester_addr.ip() == target_addr.ip()
+                                && !requester_addrs.is_empty()
+                                && !target_addrs.is_empty();
 
                             // 通知请求方目标的直连信息
-                            let msg_to_requester_payload = serde_json::json!({
+                            let mut msg_to_requester_payload = serde_json::json!({
                                 "peer_id": target_id.to_string(),
                                 "peer_addr": target_addr.to_string()
                             });
+                            Self::add_peer_addresses(&mut msg_to_requester_payload, &target_addrs);
+                            // 目标上报过NAT类型时一并告知，与发给目标方的 `peer_nat_type` 对称
+                            if let Some(nat_type) = target_peer.read().await.nat_type.clone() {
+                                msg_to_requester_payload["peer_nat_type"] = serde_json::json!(nat_type);
+                            }
+                            if lan_shortcut {
+                                Self::add_lan_shortcut(&mut msg_to_requester_payload, &target_addrs, target_addr);
+                            }
+                            let prefer_relay = self.prefers_relay(&requester_id, &target_id);
+                            if prefer_relay {
+                                msg_to_requester_payload["prefer_relay"] = serde_json::json!(true);
+                            }
+                            // 双方位于运营商级地址转换之后时打洞注定失败，直接改用中继
+                            let requester_class = self.peer_manager.tag_carrier_nat(&peer).await;
+                            let target_class = self.peer_manager.tag_carrier_nat(&target_peer).await;
+                            let skip_punch = self.config.carrier_nat.enable
+                                && self.config.allow_symmetric_nat_relay
+                                && carrier_nat::punch_doomed(requester_class, target_class);
+                            if skip_punch {
+                                Self::add_skip_punch(&mut msg_to_requester_payload, target_class);
+                            }
+                            // 倒计时信标：双方等到 GO 再开始探测
+                            let punch_in_ms = (self.config.punch_timing.enable && !skip_punch).then(|| self.config.punch_timing.schedule()[0]);
+                            if let Some(punch_in_ms) = punch_in_ms {
+                                msg_to_requester_payload["punch_in_ms"] = serde_json::json!(punch_in_ms);
+                            }
                             
                             let msg_to_requester = Message::new(
                                 MessageType::P2PConnect,
@@ -484,39 +1805,91 @@ impl P2PServer {
                             peer.read().await.send_message(&msg_to_requester).await?;
 
                             // 通知目标方请求方的直连信息，包含NAT穿透信息
-                            let mut msg_to_target_payload = serde_json::json!({
-                                "peer_id": requester_id.to_string(),
-                                "peer_addr": requester_addr.to_string()
-                            });
-
-                            // 转发请求方的NAT穿透信息给目标方
-                            if let Some(nat_type) = requester_nat_type {
-                                msg_to_target_payload["peer_nat_type"] = nat_type.clone();
-                                debug!("转发NAT类型信息: {:?}", nat_type);
+                            let mut msg_to_target = Self::p2p_connect_to_target(requester_id, requester_addr, &requester_addrs, message);
+                            if lan_shortcut {
+                                Self::add_lan_shortcut(&mut msg_to_target.payload, &requester_addrs, requester_addr);
+                                ServerMetrics::incr(&self.metrics.p2p_same_ip_coordinations);
+                                info!(
+                                    "{}",
+                                    tr!(
+                                        "节点 {} 与 {} 公网IP相同（{}），下发内网直连地址",
+                                        "Nodes {} and {} share the public IP {}, sending LAN addresses",
+                                        requester_id,
+                                        target_id,
+                                        requester_addr.ip(),
+                                    )
+                                );
                             }
-                            
-                            if let Some(predicted_ports) = requester_predicted_ports {
-                                msg_to_target_payload["peer_predicted_ports"] = predicted_ports.clone();
-                                debug!("转发预测端口信息: {:?}", predicted_ports);
+                            if prefer_relay {
+                                msg_to_target.payload["prefer_relay"] = serde_json::json!(true);
+                                info!("{}", tr!("节点 {} 与 {} 的直连带宽低于 {} bit/s，建议优先中继", "Direct bandwidth between nodes {} and {} is below {} bit/s, suggesting relay", requester_id, target_id, self.config.bandwidth.min_direct_bps));
                             }
-                            
-                            if let Some(public_addr) = requester_public_addr {
-                                msg_to_target_payload["peer_public_addr"] = public_addr.clone();
-                                debug!("转发公网地址信息: {:?}", public_addr);
+                            if skip_punch {
+                                Self::add_skip_punch(&mut msg_to_target.payload, requester_class);
+                                ServerMetrics::incr(&self.metrics.carrier_nat_relay_fallbacks);
+                                info!(
+                                    "{}",
+                                    tr!(
+                                        "节点 {} ({:?}) 与 {} ({:?}) 位于运营商级地址转换之后，跳过打洞改用中继",
+                                        "Nodes {} ({:?}) and {} ({:?}) are behind carrier-grade translation, skipping punching in favor of relay",
+                                        requester_id,
+                                        requester_class,
+                                        target_id,
+                                        target_class,
+                                    )
+                                );
+                            }
+                            if let Some(punch_in_ms) = punch_in_ms {
+                                msg_to_target.payload["punch_in_ms"] = serde_json::json!(punch_in_ms);
                             }
-
-                            let msg_to_target = Message::new(
-                                MessageType::P2PConnect,
-                                msg_to_target_payload,
-                            );
                             target_peer.read().await.send_message(&msg_to_target).await?;
+                            self.peer_manager.record_contact(requester_id, target_id);
+                            if punch_in_ms.is_some() {
+                                self.start_punch_beacons(requester_id, target_id);
+                            }
 
                             debug!(
-                                "P2P 直连协调成功: requester={}({}), target={}({}), 已转发NAT穿透信息",
-                                requester_id,
-                                requester_addr,
-                                target_id,
-                                target_addr
+                                "{}",
+                                tr!(
+                                    "P2P 直连协调成功: requester={}({}), target={}({}), 已转发NAT穿透信息",
+                                    "P2P direct connection coordinated: requester={}({}), target={}({}), NAT traversal info forwarded",
+                                    requester_id,
+                                    requester_addr,
+                                    target_id,
+                                    target_addr,
+                                )
+                            );
+                        }
+                    } else if let Some(registry) = &self.peer_registry
+                        && let Some(entry) = registry.locate(&target_id).await
+                    {
+                        // 目标节点连接在集群中的其他实例上，由该实例转交协调消息
+                        let requester_addr = snapshot.addr;
+                        let requester_addrs = peer.read().await.advertised_addrs();
+                        if let Some(nat_type) = message.payload.get("nat_type").and_then(|v| v.as_str()) {
+                            peer.write().await.nat_type = Some(nat_type.to_string());
+                        }
+                        let msg_to_target = Self::p2p_connect_to_target(requester_id, requester_addr, &requester_addrs, message);
+                        if let Err(e) = registry.forward(&entry, &msg_to_target).await {
+                            let err = Message::error(format!("目标节点未找到或不可达: {} ({})", target_id, e));
+                            peer.read().await.send_message(&err).await?;
+                        } else {
+                            let mut payload = serde_json::json!({
+                                "peer_id": target_id.to_string(),
+                                "peer_addr": entry.node_info.listen_addr.to_string()
+                            });
+                            Self::add_peer_addresses(&mut payload, &entry.node_info.advertised_addrs());
+                            let msg_to_requester = Message::new(MessageType::P2PConnect, payload);
+                            peer.read().await.send_message(&msg_to_requester).await?;
+                            debug!(
+                                "{}",
+                                tr!(
+                                    "P2P 直连协调已转交集群实例 {}: requester={}, target={}",
+                                    "P2P direct connection handed to cluster instance {}: requester={}, target={}",
+                                    entry.instance_id,
+                                    requester_id,
+                                    target_id,
+                                )
                             );
                         }
                     } else {
@@ -529,35 +1902,90 @@ impl P2PServer {
                 }
             }
             MessageType::Data => {
-                info!("收到数据消息，来自 {}", peer.read().await.addr());
+                info!("{}", tr!("收到数据消息，来自 {}", "Data message received from {}", snapshot.addr));
                 // 尝试作为路由消息处理
                 match RoutedMessage::from_message(message) {
-                    Ok(routed) => {
-                        self.message_router.forward_message(routed).await?;
+                    Ok(mut routed) => {
+                        if let Err(e) = self.message_router.check_signature(&routed) {
+                            ServerMetrics::incr(&self.metrics.route_signature_drops);
+                            warn!("{}", tr!("丢弃路由消息 {}（来自 {}）: {}", "Dropping routed message {} from {}: {}", routed.route_id, routed.source_node, e));
+                            peer.read().await.send_message(&Message::error(format!("路由消息签名校验失败: {}", e))).await?;
+                            return Ok(());
+                        }
+                        let oversized = self.oversized_routed_payload(&routed);
+                        if let Some(size) = oversized
+                            && self.config.routing.oversized_payloads == OversizedRoutePolicy::Reject
+                        {
+                            return self.reject_routed_payload(&peer, &routed, size).await;
+                        }
+                        if self.scripts.is_some() {
+                            let fields = serde_json::json!({
+                                "source": routed.source_node,
+                                "destination": routed.destination_node,
+                                "hop_count": routed.hop_count,
+                                "message_type": routed.original_message.message_type,
+                                "payload": routed.original_message.payload,
+                            });
+                            match self.run_script(Hook::PreRoute, HookContext::new(snapshot.addr, fields)) {
+                                Verdict::Deny(reason) => {
+                                    debug!("{}", tr!("策略脚本拒绝转发 {} -> {}: {}", "Policy script rejected forwarding {} -> {}: {}", routed.source_node, routed.destination_node, reason));
+                                    peer.read().await.send_message(&Message::error(format!("路由消息被策略拒绝: {}", reason))).await?;
+                                    return Ok(());
+                                }
+                                Verdict::ReplacePayload(payload) => routed.original_message.payload = payload,
+                                _ => {}
+                            }
+                        }
+                        ServerMetrics::incr(&self.metrics.routed_messages);
+                        if !routed.multicast.is_empty() {
+                            return self.handle_multicast(&peer, routed).await;
+                        }
+                        self.peer_manager.record_contact(routed.source_node, routed.destination_node);
+                        let mut span = self.telemetry.start_span("p2p.route.forward");
+                        span.set_attribute("route.source", routed.source_node.to_string());
+                        span.set_attribute("route.destination", routed.destination_node.to_string());
+                        span.set_attribute("route.hop_count", routed.hop_count as u64);
+                        self.wake_if_dormant(&routed.destination_node).await;
+                        let result = match oversized {
+                            // 超长的消息只沿已知路由转发，没有路由时拒绝而不广播
+                            Some(size) => {
+                                let rejected = routed.clone();
+                                match self.message_router.forward_without_flood(routed).await {
+                                    Ok(false) => self.reject_routed_payload(&peer, &rejected, size).await,
+                                    forwarded => forwarded.map(|_| ()),
+                                }
+                            }
+                            None => self.message_router.forward_message(routed).await,
+                        };
+                        self.telemetry.end_span(span, &result);
+                        result?;
                     }
                     Err(_) => {
                         // 非路由包，按原有逻辑处理
-                        self.handle_data_message(peer, message).await?;
+                        self.handle_data_message(peer, snapshot, message).await?;
                     }
                 }
             }
             MessageType::Disconnect => {
-                info!("节点 {} 请求断开连接", peer.read().await.id);
+                info!("{}", tr!("节点 {} 请求断开连接", "Node {} requested disconnect", snapshot.id));
                 peer.write().await.update_status(PeerStatus::Disconnected);
-                // 移除相关路由
-                let pid = peer.read().await.id;
-                self.message_router.remove_node_routes(&pid).await;
-                // 立即从PeerManager移除，并调度一次去抖广播以通知其他节点
+                // 立即从PeerManager移除，再释放路由与中继会话等状态（只通知仍在线的一方）
+                let pid = snapshot.id;
                 self.peer_manager.remove_peer(&pid).await;
+                self.peer_resources().release(&pid).await;
+                // 调度一次去抖广播以通知其他节点
                 // 断开不需要排除某个接收者
                 self.schedule_peerlist_broadcast(None).await;
             }
             MessageType::Ack => {
-                info!("收到ACK消息: ack_for={:?} 来自 {}", message.ack_for, peer.read().await.addr());
+                info!("{}", tr!("收到ACK消息: ack_for={:?} 来自 {}", "ACK received: ack_for={:?} from {}", message.acked_ids(), snapshot.addr));
                 // 处理ACK逻辑（如果需要）
             }
             MessageType::ListNodesRequest => {
-                info!("处理列出节点请求消息，来自 {}", peer.read().await.addr());
+                info!("{}", tr!("处理列出节点请求消息，来自 {}", "Handling list-peers request from {}", snapshot.addr));
+                if !self.check_control(&peer, ControlCommand::ListNodes).await? {
+                    return Ok(());
+                }
                 let peers = self.peer_manager.get_authenticated_peers().await;
                 let mut peers_info = Vec::new();
                 let timeout = self.config.connection_timeout;
@@ -570,34 +1998,905 @@ impl P2PServer {
                     };
                     if stale { continue; }
                     if let Some(mut node_info) = p_read.node_info.clone() {
+                        // 观察到的地址替换监听地址，节点通告的本地地址保留在 `addresses` 中
+                        node_info.addresses = node_info.advertised_addrs();
                         node_info.listen_addr = p_read.addr();
                         peers_info.push(node_info);
                     }
                 }
-                let response = Message::list_nodes_response(peers_info);
-                peer.read().await.send_message(&response).await?;
+                if let Some(registry) = &self.peer_registry {
+                    peers_info.extend(registry.remote_peers().into_iter().map(|entry| entry.node_info));
+                }
+                let response = Message::list_nodes_response(peers_info)?;
+                peer.read().await.send_message(&response).await?;
+            }
+            MessageType::Error => {
+                warn!("{}", tr!("收到错误消息: {:?} 来自 {}", "Error message received: {:?} from {}", message.payload, snapshot.addr));
+            }
+            MessageType::RelayRequest => {
+                info!("{}", tr!("处理流量转发请求，来自 {}", "Handling relay request from {}", snapshot.addr));
+                let mut span = self.telemetry.start_span("p2p.relay");
+                span.set_attribute("peer.id", snapshot.id.to_string());
+                if let Some(target) = message.payload.get("target_peer_id").and_then(|v| v.as_str()) {
+                    span.set_attribute("relay.target", target);
+                }
+                let result = self.handle_relay_request(peer, message).await;
+                self.telemetry.end_span(span, &result);
+                result?;
+            }
+            MessageType::RelayResponse => {
+                info!("{}", tr!("收到流量转发响应，来自 {}", "Relay response received from {}", snapshot.addr));
+                // 转发响应通常不需要特殊处理，客户端会直接处理
+            }
+            MessageType::RelayData => {
+                debug!("{}", tr!("收到经中继会话发送的数据包，来自 {}", "Received packet sent through a relay session from {}", snapshot.addr));
+                self.handle_relay_data(peer, message).await?;
+            }
+            MessageType::RelayClose => {
+                debug!("{}", tr!("收到中继会话关闭请求，来自 {}", "Received relay session close request from {}", snapshot.addr));
+                self.handle_relay_close(peer, message).await?;
+            }
+            MessageType::TopologyRequest => {
+                info!("{}", tr!("处理拓扑导出请求，来自 {}", "Handling topology export request from {}", snapshot.addr));
+                if !self.check_control(&peer, ControlCommand::Topology).await? {
+                    return Ok(());
+                }
+                let format = message
+                    .payload
+                    .get("format")
+                    .and_then(|v| serde_json::from_value::<TopologyFormat>(v.clone()).ok())
+                    .unwrap_or_default();
+                let snapshot = self.topology_snapshot().await;
+                let response = Message::topology_response(format, &snapshot);
+                peer.read().await.send_message(&response).await?;
+            }
+            MessageType::GetRoutesRequest => {
+                self.handle_control_request(peer, ControlCommand::GetRoutes).await?;
+            }
+            MessageType::GetStatsRequest => {
+                self.handle_control_request(peer, ControlCommand::GetStats).await?;
+            }
+            MessageType::GetPeersRequest => {
+                self.handle_control_request(peer, ControlCommand::GetPeers).await?;
+            }
+            MessageType::GetConfigRequest => {
+                self.handle_control_request(peer, ControlCommand::GetConfig).await?;
+            }
+            MessageType::Subscribe | MessageType::Unsubscribe | MessageType::Publish => {
+                self.handle_pubsub_message(peer, snapshot, message).await?;
+            }
+            MessageType::RegisterService
+            | MessageType::UnregisterService
+            | MessageType::ServiceLookup
+            | MessageType::RpcCall
+            | MessageType::RpcResult => {
+                self.handle_rpc_message(peer, snapshot, message).await?;
+            }
+            MessageType::Watch | MessageType::Unwatch => {
+                self.handle_watch(peer, snapshot, message).await?;
+            }
+            MessageType::P2PConnectResult => {
+                self.handle_p2p_connect_result(peer, snapshot, message).await?;
+            }
+            MessageType::KeepaliveProbe => {
+                self.handle_keepalive_probe(peer, snapshot, message).await?;
+            }
+            MessageType::BandwidthProbe | MessageType::BandwidthReport => {
+                self.handle_bandwidth_message(peer, snapshot, message).await?;
+            }
+            MessageType::KeyRotation => {
+                self.handle_key_rotation(peer, snapshot, message).await?;
+            }
+            MessageType::MtuProbe => {
+                self.handle_mtu_probe(peer, message).await?;
+            }
+            MessageType::Dormant => {
+                self.handle_dormant(peer, snapshot, message).await?;
+            }
+            MessageType::Diagnose => {
+                self.handle_diagnose(peer, snapshot, message).await?;
+            }
+            MessageType::TcpPunch => {
+                self.handle_tcp_punch(peer, snapshot, message).await?;
+            }
+            MessageType::LinkStateUpdate => {
+                if snapshot.is_authenticated() {
+                    self.message_router.handle_link_state_update(snapshot.id, message).await?;
+                } else {
+                    warn!("{}", tr!("忽略未认证节点 {} 的链路状态通告", "Ignoring link-state advertisement from unauthenticated node {}", snapshot.id));
+                }
+            }
+            MessageType::Extension(ref tag) => {
+                self.handle_extension_message(peer, snapshot, tag, message).await?;
+            }
+            _ => {
+                warn!("{}", tr!("未知消息类型: {:?}", "Unknown message type: {:?}", message.message_type));
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// 把扩展消息交给认领该标签的插件，插件的回复发回发送方
+    async fn handle_extension_message(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        tag: &str,
+        message: &Message,
+    ) -> Result<()> {
+        if !snapshot.is_authenticated() {
+            let err = Message::error("未认证节点不能发送扩展消息".to_string());
+            return peer.read().await.send_message(&err).await;
+        }
+        let Some(plugin) = self.plugins.get(tag) else {
+            ServerMetrics::incr(&self.metrics.data_unhandled);
+            debug!("{}", tr!("没有插件认领扩展标签 {}，来自 {}", "No plugin claimed extension tag {} from {}", tag, snapshot.id));
+            let err = message.respond_as(MessageType::Error, serde_json::json!({ "error": format!("未知的扩展消息类型: {}", tag) }));
+            return peer.read().await.send_message(&err).await;
+        };
+        if let Some(mut reply) = plugin.handle(snapshot.id, message).await? {
+            reply.reply_to.get_or_insert(message.id);
+            peer.read().await.send_message(&reply).await?;
+        }
+        Ok(())
+    }
+
+    /// 处理主题订阅、取消订阅与发布
+    async fn handle_pubsub_message(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        message: &Message,
+    ) -> Result<()> {
+        let (peer_id, authenticated) = (snapshot.id, snapshot.is_authenticated());
+        if !authenticated {
+            let err = Message::error("未认证节点不能使用发布/订阅".to_string());
+            return peer.read().await.send_message(&err).await;
+        }
+        let topic = message.payload.get("topic").and_then(|v| v.as_str()).unwrap_or_default();
+        if let Err(reason) = TopicBus::validate_topic(topic) {
+            return peer.read().await.send_message(&Message::error(reason.to_string())).await;
+        }
+
+        match message.message_type {
+            MessageType::Subscribe => {
+                if self.topic_bus.subscribe(peer_id, topic).await {
+                    info!("{}", tr!("节点 {} 订阅主题 {}", "Node {} subscribed to topic {}", peer_id, topic));
+                }
+            }
+            MessageType::Unsubscribe => {
+                if self.topic_bus.unsubscribe(&peer_id, topic).await {
+                    info!("{}", tr!("节点 {} 取消订阅主题 {}", "Node {} unsubscribed from topic {}", peer_id, topic));
+                }
+            }
+            _ => {
+                let published = Published {
+                    topic: topic.to_string(),
+                    from: Some(peer_id),
+                    data: message.payload.get("data").cloned().unwrap_or(serde_json::Value::Null),
+                };
+                let delivered = self.topic_bus.publish(&self.peer_manager, published).await;
+                debug!("{}", tr!("节点 {} 发布主题 {}，投递给 {} 个订阅者", "Node {} published topic {}, delivered to {} subscribers", peer_id, topic, delivered));
+            }
+        }
+        Ok(())
+    }
+
+    /// 处理在线状态关注与取消关注
+    async fn handle_watch(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        message: &Message,
+    ) -> Result<()> {
+        let (peer_id, authenticated) = (snapshot.id, snapshot.is_authenticated());
+        let reject = |reason: String| message.respond_as(MessageType::Error, serde_json::json!({ "error": reason }));
+        if !authenticated {
+            return peer.read().await.send_message(&reject("未认证节点不能关注在线状态".to_string())).await;
+        }
+        let mut request: WatchRequest = match serde_json::from_value(message.payload.clone()) {
+            Ok(request) => request,
+            Err(e) => return peer.read().await.send_message(&reject(format!("关注请求格式错误: {}", e))).await,
+        };
+        request.peer_ids.retain(|id| *id != peer_id);
+
+        let presence = self.peer_manager.presence();
+        let online = if message.message_type == MessageType::Watch {
+            if let Err(reason) = presence.watch(peer_id, &request.peer_ids) {
+                return peer.read().await.send_message(&reject(reason.to_string())).await;
+            }
+            debug!("{}", tr!("节点 {} 关注 {} 个节点的在线状态", "Node {} watches the presence of {} nodes", peer_id, request.peer_ids.len()));
+            // 应答当前状态，之后只推送变化
+            self.peer_manager.online_among(&request.peer_ids).await
+        } else {
+            presence.unwatch(&peer_id, &request.peer_ids);
+            Vec::new()
+        };
+        let reply = message.respond_as(message.message_type.clone(), serde_json::to_value(WatchResponse { online })?);
+        peer.read().await.send_message(&reply).await
+    }
+
+    /// 处理服务注册、查询与远程调用
+    async fn handle_rpc_message(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        message: &Message,
+    ) -> Result<()> {
+        let (peer_id, authenticated) = (snapshot.id, snapshot.is_authenticated());
+        // 错误应答也带 reply_to，调用方无需等到超时
+        let reject = |reason: String| message.respond_as(MessageType::Error, serde_json::json!({ "error": reason }));
+        if !authenticated {
+            return peer.read().await.send_message(&reject("未认证节点不能使用远程调用".to_string())).await;
+        }
+
+        match message.message_type {
+            MessageType::RegisterService | MessageType::UnregisterService => {
+                let registration: ServiceRegistration = match serde_json::from_value(message.payload.clone()) {
+                    Ok(registration) => registration,
+                    Err(e) => return peer.read().await.send_message(&reject(format!("服务注册格式错误: {}", e))).await,
+                };
+                if let Some(reason) = registration.services.iter().find_map(|s| ServiceDirectory::validate_name(s).err()) {
+                    return peer.read().await.send_message(&reject(reason.to_string())).await;
+                }
+                let register = message.message_type == MessageType::RegisterService;
+                for service in &registration.services {
+                    if register && self.services.register(peer_id, service).await {
+                        info!("{}", tr!("节点 {} 注册服务 {}", "Node {} registered service {}", peer_id, service));
+                    } else if !register && self.services.unregister(&peer_id, service).await {
+                        info!("{}", tr!("节点 {} 注销服务 {}", "Node {} unregistered service {}", peer_id, service));
+                    }
+                }
+                let ack = message.respond_as(message.message_type.clone(), serde_json::to_value(registration)?);
+                peer.read().await.send_message(&ack).await?;
+            }
+            MessageType::ServiceLookup => {
+                let service = message.payload.get("service").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                let providers = self.services.providers(&service).await;
+                let response = ServiceLookupResponse { service, providers };
+                let reply = message.respond_as(MessageType::ServiceLookup, serde_json::to_value(response)?);
+                peer.read().await.send_message(&reply).await?;
+            }
+            MessageType::RpcCall => self.forward_rpc_call(peer, peer_id, message).await?,
+            _ => {
+                // 提供者返回的结果按调用ID转回调用方
+                let Some(call_id) = message.reply_to else {
+                    debug!("{}", tr!("忽略节点 {} 发来的缺少 reply_to 的调用结果", "Ignoring call result without reply_to from node {}", peer_id));
+                    return Ok(());
+                };
+                let Some(caller) = self.services.finish_call(&call_id, &peer_id) else {
+                    debug!("{}", tr!("调用 {} 已超时或不存在，丢弃节点 {} 返回的结果", "Call {} timed out or does not exist, dropping result from node {}", call_id, peer_id));
+                    return Ok(());
+                };
+                match self.peer_manager.get_peer(&caller).await {
+                    Some(caller) => caller.read().await.send_message(message).await?,
+                    None => debug!("{}", tr!("调用方 {} 已离线，丢弃调用 {} 的结果", "Caller {} is offline, dropping result of call {}", caller, call_id)),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 把远程调用转发给服务提供者（依次尝试在线的提供者），无可用提供者时直接回复错误结果
+    async fn forward_rpc_call(&self, peer: Arc<tokio::sync::RwLock<Peer>>, caller: Uuid, message: &Message) -> Result<()> {
+        let mut call: RpcCall = match serde_json::from_value(message.payload.clone()) {
+            Ok(call) => call,
+            Err(e) => {
+                let reply = Message::rpc_result(message.id, Err(format!("远程调用格式错误: {}", e)))?;
+                return peer.read().await.send_message(&reply).await;
+            }
+        };
+        let timeout = call.timeout_ms.map(Duration::from_millis).unwrap_or(DEFAULT_CALL_TIMEOUT).min(MAX_CALL_TIMEOUT);
+        call.from = Some(caller);
+
+        for provider_id in self.services.candidates(&call.service, call.target).await {
+            let Some(provider) = self.peer_manager.get_peer(&provider_id).await else {
+                // 清理已离线节点遗留的注册
+                self.services.remove_peer(&provider_id).await;
+                continue;
+            };
+            // 保留调用ID，提供者据此以 reply_to 返回结果
+            let mut forwarded = message.clone();
+            forwarded.payload = serde_json::to_value(&call)?;
+            self.wake_if_dormant(&provider_id).await;
+            self.services.start_call(message.id, caller, provider_id, timeout);
+            match provider.read().await.send_message(&forwarded).await {
+                Ok(()) => {
+                    ServerMetrics::incr(&self.metrics.rpc_calls);
+                    debug!("{}", tr!("转发远程调用 {} ({}): {} -> {}", "Forwarding remote call {} ({}): {} -> {}", message.id, call.service, caller, provider_id));
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.services.cancel_call(&message.id);
+                    warn!("{}", tr!("向提供者 {} 转发远程调用失败: {}", "Failed to forward remote call to provider {}: {}", provider_id, e));
+                }
+            }
+        }
+
+        ServerMetrics::incr(&self.metrics.rpc_calls_failed);
+        let reason = match call.target {
+            Some(target) => format!("节点 {} 未提供服务 {}", target, call.service),
+            None => format!("服务 {} 没有可用的提供者", call.service),
+        };
+        peer.read().await.send_message(&Message::rpc_result(message.id, Err(reason))?).await
+    }
+
+    /// 处理地址迁移：凭会话票据把节点会话转移到新地址，恢复直连路由，并通知其 P2P 会话对象
+    async fn handle_migrate_address(&self, connection: Arc<crate::network::Connection>, message: &Message) -> Result<()> {
+        let new_addr = connection.peer_addr();
+        let request: MigrateAddressRequest = match serde_json::from_value(message.payload.clone()) {
+            Ok(request) => request,
+            Err(e) => return self.reject_migration(&connection, message, format!("地址迁移请求格式错误: {}", e)).await,
+        };
+
+        // 票据可能被截获，过期的迁移请求不予受理
+        let offset_ms = match self.peer_manager.get_peer(&request.node_id).await {
+            Some(peer) => peer.read().await.clock_offset_ms,
+            None => None,
+        };
+        if check_freshness(&self.config.time_sync, message.timestamp, offset_ms, unix_millis(std::time::SystemTime::now())) != Freshness::Fresh {
+            return self.reject_migration(&connection, message, "地址迁移请求的时间戳超出允许范围".to_string()).await;
+        }
+
+        let (peer, old_addr) = match self.peer_manager.migrate_peer(&request.node_id, &request.session_ticket, connection.clone()).await {
+            Ok(migrated) => migrated,
+            Err(e) => return self.reject_migration(&connection, message, e.to_string()).await,
+        };
+        if old_addr != new_addr {
+            self.network_manager.remove_connection(&old_addr).await;
+        }
+        self.message_router.update_routing_table(request.node_id, request.node_id, 1).await;
+        ServerMetrics::incr(&self.metrics.address_migrations);
+
+        let mut response = Message::migrate_address_response(new_addr)?;
+        response.reply_to = Some(message.id);
+        peer.read().await.send_message(&response).await?;
+        let update = AddressUpdate {
+            peer_id: request.node_id,
+            peer_addr: new_addr,
+            peer_addresses: peer.read().await.advertised_addrs(),
+        };
+        self.peer_manager.notify_address_update(&request.node_id, &Message::address_update(update)?).await;
+        Ok(())
+    }
+
+    async fn reject_migration(&self, connection: &crate::network::Connection, message: &Message, reason: String) -> Result<()> {
+        let addr = connection.peer_addr();
+        warn!("{}", tr!("拒绝来自 {} 的地址迁移: {}", "Rejecting address migration from {}: {}", addr, reason));
+        ServerMetrics::incr(&self.metrics.address_migrations_rejected);
+        connection.send_message(&message.respond_as(MessageType::Error, serde_json::json!({ "error": reason }))).await?;
+        // 新地址若没有对应的节点，无需保留连接
+        if self.peer_manager.get_peer_by_addr(&addr).await.is_none() {
+            self.network_manager.remove_connection(&addr).await;
+        }
+        Ok(())
+    }
+
+    /// 处理时间同步：记录节点的时钟偏差，并回传服务器的收发时间戳供客户端计算偏差与往返时间
+    async fn handle_time_sync(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        message: &Message,
+        received_at: Instant,
+    ) -> Result<()> {
+        let server_receive_ms = instant_to_unix_millis(received_at);
+        let Some(client_send_ms) = message.payload.get("client_send_ms").and_then(|v| v.as_u64()) else {
+            let err = Message::error("时间同步请求缺少 client_send_ms".to_string());
+            peer.read().await.send_message(&err).await?;
+            return Ok(());
+        };
+        let offset_ms = message
+            .payload
+            .get("offset_ms")
+            .and_then(|v| v.as_i64())
+            .unwrap_or_else(|| estimate_offset(client_send_ms, server_receive_ms));
+        peer.write().await.clock_offset_ms = Some(offset_ms);
+        debug!("{}", tr!("节点 {} 的时钟偏差约为 {} ms", "Clock offset of node {} is about {} ms", peer.read().await.addr(), offset_ms));
+
+        let response = Message::time_sync_response(client_send_ms, server_receive_ms, unix_millis(std::time::SystemTime::now()));
+        peer.read().await.send_message(&response).await
+    }
+
+    /// 处理保活探测：不带 `delay_secs` 为发起探测，带 `delay_secs` 为对探测包的回显
+    async fn handle_keepalive_probe(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        message: &Message,
+    ) -> Result<()> {
+        let (peer_id, addr, authenticated) = (snapshot.id, snapshot.addr, snapshot.is_authenticated());
+        if !authenticated {
+            warn!("{}", tr!("忽略未认证节点 {} 的保活探测", "Ignoring keepalive probe from unauthenticated node {}", peer_id));
+            return Ok(());
+        }
+        let keepalive = self.peer_manager.keepalive();
+        match message.payload.get("delay_secs").and_then(|v| v.as_u64()) {
+            Some(delay_secs) => {
+                // 回显说明节点仍然在线，避免探测结束后被当作心跳超时
+                peer.write().await.update_ping();
+                if let Some(ProbeAction::Finished { recommended_secs, .. }) = keepalive.on_echo(&peer_id, delay_secs, Instant::now()) {
+                    info!("{}", tr!("节点 {} 的NAT映射至少保持 {} 秒，建议保活间隔 {} 秒", "NAT binding of node {} lasts at least {} seconds, suggested keepalive interval {} seconds", peer_id, delay_secs, recommended_secs));
+                    peer.read().await.send_message(&Message::keepalive_result(recommended_secs)).await?;
+                }
+            }
+            None => {
+                if keepalive.start(peer_id, addr.ip(), Instant::now()) {
+                    info!("{}", tr!("开始探测节点 {} ({}) 的NAT映射寿命", "Probing NAT binding lifetime of node {} ({})", peer_id, addr));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 连通性自检：汇总服务器视角下该节点的地址、NAT 分类、心跳时延与丢失率、中继与频率限制状况
+    async fn handle_diagnose(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        message: &Message,
+    ) -> Result<()> {
+        if !snapshot.is_authenticated() {
+            let reply = message.respond_as(MessageType::Error, serde_json::json!({ "error": "连通性自检需要先完成握手" }));
+            return peer.read().await.send_message(&reply).await;
+        }
+        let carrier_nat = self.peer_manager.tag_carrier_nat(&peer).await;
+        let report = {
+            let guard = peer.read().await;
+            let control = &self.config.control;
+            // 管理员角色不受控制查询频率限制
+            let per_minute = if guard.role.unwrap_or(control.default_role) >= PeerRole::Admin { 0 } else { control.rate_limit_per_minute };
+            let used = guard.control_quota_used();
+            DiagnoseReport {
+                observed_addr: snapshot.addr,
+                nat_type: guard.nat_type.clone(),
+                carrier_nat,
+                connected_secs: guard.connected_for().as_secs(),
+                pings: guard.pings.summary(Instant::now()),
+                link: guard.link_quality(),
+                path_mtu: guard.path_mtu,
+                relay_allowed: self.config.allow_symmetric_nat_relay,
+                rate_limit: RateLimitStanding {
+                    control_per_minute: per_minute,
+                    control_used: used,
+                    control_remaining: (per_minute > 0).then(|| per_minute.saturating_sub(used)),
+                },
+            }
+        };
+        debug!("{}", tr!("节点 {} 的连通性自检: {:?}", "Connectivity self-check of node {}: {:?}", snapshot.id, report));
+        peer.read().await.send_message(&message.respond_as(MessageType::Diagnose, serde_json::to_value(&report)?)).await
+    }
+
+    /// 处理 `MtuProbe`：服务器探测包的应答交给等待中的探测，节点发来的探测包以不带填充的小包应答
+    async fn handle_mtu_probe(&self, peer: Arc<tokio::sync::RwLock<Peer>>, message: &Message) -> Result<()> {
+        if message.reply_to.is_some() {
+            if self.pending_replies.resolve(message.clone()).is_some() {
+                debug!("{}", tr!("收到无人等待的 MTU 探测应答（可能已超时）", "Received an MTU probe reply nobody is waiting for (may have timed out)"));
+            }
+            return Ok(());
+        }
+        let size = message.payload.get("size").and_then(|v| v.as_u64()).unwrap_or(0);
+        let reply = message.respond_as(MessageType::MtuProbe, serde_json::json!({ "size": size }));
+        peer.read().await.send_message(&reply).await
+    }
+
+    /// 登记节点休眠：先应答，之后发给它的消息暂存到醒来
+    async fn handle_dormant(&self, peer: Arc<tokio::sync::RwLock<Peer>>, snapshot: &PeerSnapshot, message: &Message) -> Result<()> {
+        let config = &self.config.dormant;
+        let token = message.payload.get("token").and_then(|v| v.as_u64());
+        let error = if !config.enable {
+            Some("服务器未开启节点休眠")
+        } else if !snapshot.is_authenticated() {
+            Some("休眠需要先完成握手")
+        } else if token.is_none() {
+            Some("休眠请求缺少 token")
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            let reply = message.respond_as(MessageType::Error, serde_json::json!({ "error": error }));
+            return peer.read().await.send_message(&reply).await;
+        }
+        let reply = message.respond_as(MessageType::Dormant, serde_json::json!({ "max_dormant_secs": config.max_dormant_secs }));
+        let mut guard = peer.write().await;
+        guard.send_message(&reply).await?;
+        guard.dormant = Some(Arc::new(Dormancy::new(token.unwrap_or_default(), Duration::from_secs(config.max_dormant_secs), config.max_queued)));
+        info!("{}", tr!("节点 {} 进入休眠，最长 {} 秒", "Node {} is now dormant for up to {} seconds", snapshot.id, config.max_dormant_secs));
+        Ok(())
+    }
+
+    /// 结束节点的休眠并补发暂存的消息
+    async fn end_dormancy(&self, peer: &Arc<tokio::sync::RwLock<Peer>>) -> Result<()> {
+        if peer.read().await.dormant.is_none() {
+            return Ok(());
+        }
+        let (peer_id, dormancy) = {
+            let mut guard = peer.write().await;
+            // 醒来前的心跳时间早已过期，重新计时以免被当作超时节点移除
+            guard.update_ping();
+            (guard.id, guard.dormant.take())
+        };
+        let Some(dormancy) = dormancy else { return Ok(()) };
+        let queued = dormancy.take_queued();
+        info!("{}", tr!("节点 {} 已醒来，补发休眠期间暂存的 {} 条消息", "Node {} woke up, delivering {} messages queued while dormant", peer_id, queued.len()));
+        let guard = peer.read().await;
+        for message in &queued {
+            guard.send_message(message).await?;
+        }
+        Ok(())
+    }
+
+    /// 有节点要联系休眠节点时向其地址发送唤醒数据包；距上次唤醒不足 `knock_gap_ms` 时不重复发送
+    async fn wake_if_dormant(&self, peer_id: &Uuid) {
+        let Some(peer) = self.peer_manager.get_peer(peer_id).await else { return };
+        let (dormancy, connection) = {
+            let guard = peer.read().await;
+            (guard.dormant.clone(), guard.connection.clone())
+        };
+        let config = &self.config.dormant;
+        let Some(dormancy) = dormancy.filter(|dormancy| dormancy.is_active()) else { return };
+        if !dormancy.begin_knock(Duration::from_millis(config.knock_gap_ms)) {
+            return;
+        }
+        ServerMetrics::incr(&self.metrics.dormant_wakes);
+        info!("{}", tr!("唤醒休眠节点 {}（已暂存 {} 条消息）", "Waking dormant node {} ({} messages queued)", peer_id, dormancy.queued()));
+        let packet = dormant::knock(dormancy.token());
+        let (count, interval) = (config.knock_count, Duration::from_millis(config.knock_interval_ms));
+        let peer_id = *peer_id;
+        tokio::spawn(async move {
+            for i in 0..count {
+                if i > 0 {
+                    tokio::time::sleep(interval).await;
+                }
+                if let Err(e) = connection.send_raw(&packet).await {
+                    debug!("{}", tr!("向休眠节点 {} 发送唤醒数据包失败: {}", "Failed to send wake-up packet to dormant node {}: {}", peer_id, e));
+                    return;
+                }
+            }
+        });
+    }
+
+    /// 路由消息的负载超过 `routing.max_routed_payload_bytes` 时返回其字节数
+    fn oversized_routed_payload(&self, routed: &RoutedMessage) -> Option<usize> {
+        let max = self.config.routing.max_routed_payload_bytes;
+        if max == 0 {
+            return None;
+        }
+        let size = serde_json::to_vec(&routed.original_message.payload).map_or(0, |bytes| bytes.len());
+        (size > max).then_some(size)
+    }
+
+    /// 拒绝负载超过上限的路由消息，向发送方回复 `payload_too_large`
+    async fn reject_routed_payload(&self, peer: &Arc<tokio::sync::RwLock<Peer>>, routed: &RoutedMessage, size: usize) -> Result<()> {
+        let max_bytes = self.config.routing.max_routed_payload_bytes;
+        ServerMetrics::incr(&self.metrics.routed_payload_rejections);
+        debug!("{}", tr!("路由消息 {} 的负载 {} 字节超过上限 {} 字节，拒绝转发", "Routed message {} payload of {} bytes exceeds the {} byte limit, refusing to forward", routed.route_id, size, max_bytes));
+        let rejection = RouteRejection {
+            error: format!("路由消息负载过大: {} 字节，上限 {} 字节", size, max_bytes),
+            code: RouteRejectReason::PayloadTooLarge,
+            route_id: routed.route_id,
+            size,
+            max_bytes,
+        };
+        let notice = routed.original_message.respond_as(MessageType::Error, serde_json::to_value(&rejection)?);
+        peer.read().await.send_message(&notice).await
+    }
+
+    /// 转发多播路由消息：目标节点数受 `routing.max_multicast_members` 限制，按分发树每个下一跳只发一份
+    async fn handle_multicast(&self, peer: &Arc<tokio::sync::RwLock<Peer>>, routed: RoutedMessage) -> Result<()> {
+        let members = routed.multicast.len();
+        let limit = self.config.routing.max_multicast_members;
+        if members > limit {
+            let err = Message::error(format!("多播目标节点过多: {} > {}", members, limit));
+            peer.read().await.send_message(&err).await?;
+            return Ok(());
+        }
+        for member in &routed.multicast {
+            self.peer_manager.record_contact(routed.source_node, *member);
+            self.wake_if_dormant(member).await;
+        }
+        let mut span = self.telemetry.start_span("p2p.route.multicast");
+        span.set_attribute("route.source", routed.source_node.to_string());
+        span.set_attribute("route.members", members as u64);
+        span.set_attribute("route.hop_count", routed.hop_count as u64);
+        let result = self.message_router.forward_multicast(routed).await;
+        self.telemetry.end_span(span, &result);
+        let copies = result?;
+        ServerMetrics::add(&self.metrics.multicast_copies_saved, members.saturating_sub(copies) as u64);
+        Ok(())
+    }
+
+    /// 在后台探测到节点的路径 MTU，结束后记录结果并告知节点
+    fn start_path_mtu_probe(&self, peer_id: Uuid) {
+        let config = self.config.path_mtu.clone();
+        let peer_manager = self.peer_manager.clone();
+        let pending_replies = self.pending_replies.clone();
+        tokio::spawn(async move {
+            let wait = Duration::from_millis(config.probe_timeout_ms);
+            let mut search = MtuSearch::new(config.base_mtu, config.max_mtu, config.max_probes);
+            while let Some(size) = search.next_probe() {
+                let Some(peer) = peer_manager.get_peer(&peer_id).await else { return };
+                let Ok(probe) = Message::mtu_probe(&MtuProbe { size, path_mtu: Some(search.confirmed()), done: false, limited: false }) else { return };
+                let send = async { peer.read().await.send_message(&probe).await };
+                match pending_replies.request(&probe, wait, send).await {
+                    Ok(_) => search.acked(size),
+                    Err(_) => search.timed_out(size),
+                }
             }
-            MessageType::Error => {
-                warn!("收到错误消息: {:?} 来自 {}", message.payload, peer.read().await.addr());
+            let Some(peer) = peer_manager.get_peer(&peer_id).await else { return };
+            let path_mtu = PathMtu { mtu: search.confirmed(), limited: search.limit().is_some() };
+            peer.write().await.path_mtu = Some(path_mtu);
+            info!("{}", tr!("到节点 {} 的路径 MTU: {:?}", "Path MTU to node {}: {:?}", peer_id, path_mtu));
+            let result = Message::mtu_probe(&MtuProbe { size: 0, path_mtu: Some(path_mtu.mtu), done: true, limited: path_mtu.limited });
+            if let Ok(result) = result
+                && let Err(e) = peer.read().await.send_message(&result).await
+            {
+                debug!("{}", tr!("向节点 {} 发送路径 MTU 结果失败: {}", "Failed to send path MTU result to node {}: {}", peer_id, e));
             }
-            MessageType::RelayRequest => {
-                info!("处理流量转发请求，来自 {}", peer.read().await.addr());
-                self.handle_relay_request(peer, message).await?;
+        });
+    }
+
+    /// 协调 TCP 同时打开：转交一方的端点，另一方应答后向双方下发对方端点与统一的连接时间
+    async fn handle_tcp_punch(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        message: &Message,
+    ) -> Result<()> {
+        let reject = |reason: String| message.respond_as(MessageType::Error, serde_json::json!({ "error": reason }));
+        if !self.config.tcp_punch.enable {
+            return peer.read().await.send_message(&reject("服务器未启用 TCP 打洞协调".to_string())).await;
+        }
+        if !snapshot.is_authenticated() {
+            return peer.read().await.send_message(&reject("未认证节点不能请求 TCP 打洞".to_string())).await;
+        }
+        let request: TcpPunch = match serde_json::from_value(message.payload.clone()) {
+            Ok(request) => request,
+            Err(e) => return peer.read().await.send_message(&reject(format!("TCP 打洞请求格式错误: {}", e))).await,
+        };
+        let Some(tcp_addr) = request.tcp_addr else {
+            return peer.read().await.send_message(&reject("TCP 打洞请求缺少 tcp_addr".to_string())).await;
+        };
+        // 端点只转交给对方节点，限定为请求方自己的公网IP，避免诱使对方连接第三方
+        if tcp_addr.ip() != snapshot.addr.ip() {
+            return peer.read().await.send_message(&reject(format!("tcp_addr 的IP须与观测到的地址 {} 相同", snapshot.addr.ip()))).await;
+        }
+        let (requester_id, target_id) = (snapshot.id, request.peer_id);
+        let target = match self.peer_manager.get_peer(&target_id).await {
+            Some(target) if target_id != requester_id && target.read().await.is_authenticated() => target,
+            _ => return peer.read().await.send_message(&reject(format!("目标节点未找到或不可达: {}", target_id))).await,
+        };
+
+        let Some(peer_tcp_addr) = self.tcp_punch_offers.submit(requester_id, target_id, tcp_addr) else {
+            // 提议：把端点转交给对方，等待其提交自己的端点
+            let offer = TcpPunch { peer_id: requester_id, tcp_addr: None, peer_tcp_addr: Some(tcp_addr), connect_at_ms: None, connect_in_ms: None };
+            target.read().await.send_message(&Message::new(MessageType::TcpPunch, serde_json::to_value(&offer)?)).await?;
+            debug!("{}", tr!("已转交节点 {} 给 {} 的 TCP 打洞提议: {}", "Handed TCP punch offer from node {} to {}: {}", requester_id, target_id, tcp_addr));
+            return Ok(());
+        };
+
+        // 应答：双方端点齐全，按各自的时钟偏差给出同一时刻
+        let lead_ms = self.config.tcp_punch.lead_ms;
+        let connect_at = unix_millis(std::time::SystemTime::now()) + lead_ms;
+        for (endpoint, other, other_addr) in [(&target, requester_id, tcp_addr), (&peer, target_id, peer_tcp_addr)] {
+            let offset_ms = endpoint.read().await.clock_offset_ms;
+            let plan = TcpPunch {
+                peer_id: other,
+                tcp_addr: None,
+                peer_tcp_addr: Some(other_addr),
+                connect_at_ms: offset_ms.map(|offset| connect_at.saturating_add_signed(offset)),
+                connect_in_ms: Some(lead_ms),
+            };
+            endpoint.read().await.send_message(&Message::new(MessageType::TcpPunch, serde_json::to_value(&plan)?)).await?;
+        }
+        ServerMetrics::incr(&self.metrics.tcp_punch_coordinations);
+        self.peer_manager.record_contact(requester_id, target_id);
+        info!(
+            "{}",
+            tr!(
+                "节点 {} 与 {} 的 TCP 打洞已协调: {} <-> {}，{} 毫秒后同时连接",
+                "TCP punch coordinated between nodes {} and {}: {} <-> {}, connecting in {} ms",
+                target_id,
+                requester_id,
+                peer_tcp_addr,
+                tcp_addr,
+                lead_ms,
+            )
+        );
+        Ok(())
+    }
+
+    /// 处理节点密钥轮换：校验旧密钥的签名与登记的密钥链，更新登记表后应答并转发给其他已认证节点
+    async fn handle_key_rotation(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        message: &Message,
+    ) -> Result<()> {
+        let (peer_id, authenticated) = (snapshot.id, snapshot.is_authenticated());
+        let reject = |reason: String| message.respond_as(MessageType::Error, serde_json::json!({ "error": reason }));
+        if !authenticated {
+            return peer.read().await.send_message(&reject("未认证节点不能轮换密钥".to_string())).await;
+        }
+        let rotation: KeyRotation = match serde_json::from_value(message.payload.clone()) {
+            Ok(rotation) => rotation,
+            Err(e) => return peer.read().await.send_message(&reject(format!("密钥轮换格式错误: {}", e))).await,
+        };
+        if rotation.node_id != peer_id {
+            return peer.read().await.send_message(&reject("只能轮换本节点的密钥".to_string())).await;
+        }
+        if let Err(e) = self.peer_manager.identities().rotate(&rotation, &self.config.network_id) {
+            warn!("{}", tr!("拒绝节点 {} 的密钥轮换: {}", "Rejecting key rotation from node {}: {}", peer_id, e));
+            return peer.read().await.send_message(&reject(e.to_string())).await;
+        }
+        if let Some(node_info) = peer.write().await.node_info.as_mut() {
+            node_info.public_key = Some(rotation.new_public_key.clone());
+        }
+        let reply = message.respond_as(MessageType::KeyRotation, serde_json::to_value(&rotation)?);
+        peer.read().await.send_message(&reply).await?;
+
+        let notice = Message::key_rotation(&rotation)?;
+        for other in self.peer_manager.get_authenticated_peers().await {
+            let other = other.read().await;
+            if other.id == peer_id {
+                continue;
             }
-            MessageType::RelayResponse => {
-                info!("收到流量转发响应，来自 {}", peer.read().await.addr());
-                // 转发响应通常不需要特殊处理，客户端会直接处理
+            if let Err(e) = other.send_message(&notice).await {
+                warn!("{}", tr!("向节点 {} 转发密钥轮换失败: {}", "Failed to forward key rotation to node {}: {}", other.id, e));
             }
-            MessageType::RelayData => {
-                info!("收到转发的数据包，来自 {}", peer.read().await.addr());
-                // 这种消息类型通常由客户端处理，服务器不应该收到
-                warn!("服务器收到了RelayData消息，这可能是配置错误");
+        }
+        Ok(())
+    }
+
+    /// 处理带宽探测：节点请求探测本节点到对方的直连路径，或接收方上报探测结果
+    async fn handle_bandwidth_message(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        message: &Message,
+    ) -> Result<()> {
+        let (peer_id, authenticated) = (snapshot.id, snapshot.is_authenticated());
+        let reject = |reason: String| message.respond_as(MessageType::Error, serde_json::json!({ "error": reason }));
+        if !authenticated {
+            return peer.read().await.send_message(&reject("未认证节点不能参与带宽探测".to_string())).await;
+        }
+        if message.message_type == MessageType::BandwidthReport {
+            let report: BandwidthReport = serde_json::from_value(message.payload.clone())?;
+            return self.complete_bandwidth_probe(peer_id, report).await;
+        }
+        let target = message.payload.get("peer_id").and_then(|v| v.as_str()).and_then(|v| Uuid::parse_str(v).ok());
+        let Some(target) = target else {
+            return peer.read().await.send_message(&reject("缺少或无效的 peer_id".to_string())).await;
+        };
+        if let Err(e) = self.start_bandwidth_probe(peer_id, target, Some((peer_id, message.clone()))).await {
+            return peer.read().await.send_message(&reject(e.to_string())).await;
+        }
+        Ok(())
+    }
+
+    /// 由服务器发起一次从 `sender` 到 `receiver` 的带宽探测（双方须已直连），
+    /// 接收方上报后结果记入 [`bandwidth`](Self::bandwidth)
+    pub async fn probe_bandwidth(&self, sender: Uuid, receiver: Uuid) -> Result<Uuid> {
+        self.start_bandwidth_probe(sender, receiver, None).await
+    }
+
+    /// 协调带宽探测：先让接收方准备计时，再让发送方沿直连路径发出探测包
+    async fn start_bandwidth_probe(&self, sender: Uuid, receiver: Uuid, request: Option<(Uuid, Message)>) -> Result<Uuid> {
+        if !matches!(self.peer_manager.sessions().path(&sender, &receiver), Some(P2PPath::Public | P2PPath::Private)) {
+            anyhow::bail!("节点 {} 与 {} 之间没有直连会话", sender, receiver);
+        }
+        let sender_peer = self.peer_manager.get_peer(&sender).await.context(format!("节点 {} 不在线", sender))?;
+        let receiver_peer = self.peer_manager.get_peer(&receiver).await.context(format!("节点 {} 不在线", receiver))?;
+        let probe_id = self
+            .peer_manager
+            .bandwidth()
+            .begin(sender, receiver, request)
+            .context(format!("节点 {} 到 {} 的带宽探测正在进行", sender, receiver))?;
+        let config = &self.config.bandwidth;
+        let instruction = |peer_id, role| {
+            Message::bandwidth_probe(&BandwidthProbe {
+                probe_id,
+                peer_id,
+                role,
+                packets: config.packets.max(2),
+                packet_size: config.packet_size,
+            })
+        };
+        receiver_peer.read().await.send_message(&instruction(sender, ProbeRole::Receive)?).await?;
+        sender_peer.read().await.send_message(&instruction(receiver, ProbeRole::Send)?).await?;
+        debug!("{}", tr!("开始带宽探测 {}: {} -> {}", "Starting bandwidth probe {}: {} -> {}", probe_id, sender, receiver));
+        Ok(probe_id)
+    }
+
+    /// 记录接收方上报的探测结果，节点主动请求的探测把结果应答给请求方
+    async fn complete_bandwidth_probe(&self, reporter: Uuid, report: BandwidthReport) -> Result<()> {
+        let Some(done) = self.peer_manager.bandwidth().complete(reporter, &report) else {
+            debug!("{}", tr!("忽略节点 {} 上报的未知或已过期的带宽探测 {}", "Ignoring unknown or expired bandwidth probe reported by node {}: {}", reporter, report.probe_id));
+            return Ok(());
+        };
+        match done.bandwidth_bps {
+            Some(bps) => info!("{}", tr!("节点 {} 到 {} 的带宽约 {} bit/s（收到 {} 个探测包）", "Bandwidth from node {} to {} is about {} bit/s ({} probes received)", done.sender, done.receiver, bps, report.received)),
+            None => warn!("{}", tr!("节点 {} 到 {} 的带宽探测只收到 {} 个探测包，无法估算", "Bandwidth probe from node {} to {} received only {} probes, cannot estimate", done.sender, done.receiver, report.received)),
+        }
+        let Some((requester, request)) = done.request else { return Ok(()) };
+        let reply = if done.bandwidth_bps.is_some() {
+            let report = BandwidthReport { sender: done.sender, receiver: done.receiver, ..report };
+            request.respond_as(MessageType::BandwidthReport, serde_json::to_value(report)?)
+        } else {
+            let error = format!("只收到 {} 个探测包，无法估算带宽", report.received);
+            request.respond_as(MessageType::Error, serde_json::json!({ "error": error }))
+        };
+        if let Some(peer) = self.peer_manager.get_peer(&requester).await {
+            peer.read().await.send_message(&reply).await?;
+        }
+        Ok(())
+    }
+
+    /// 探测全部直连会话两个方向的带宽
+    async fn probe_direct_sessions(&self) {
+        for (a, b, path) in self.peer_manager.sessions().pairs() {
+            if !matches!(path, P2PPath::Public | P2PPath::Private) {
+                continue;
             }
-            _ => {
-                warn!("未知消息类型: {:?}", message.message_type);
+            for (sender, receiver) in [(a, b), (b, a)] {
+                if let Err(e) = self.start_bandwidth_probe(sender, receiver, None).await {
+                    debug!("{}", tr!("跳过带宽探测: {}", "Skipping bandwidth probe: {}", e));
+                }
             }
         }
-        
+    }
+
+    /// 标记直连协调消息：跳过打洞、直接使用中继，并附上对方的分类
+    fn add_skip_punch(payload: &mut serde_json::Value, peer_class: Option<CarrierNat>) {
+        payload["prefer_relay"] = serde_json::json!(true);
+        payload["skip_punch"] = serde_json::json!(true);
+        if let Some(class) = peer_class {
+            payload["peer_carrier_nat"] = serde_json::json!(class);
+        }
+    }
+
+    /// 测得的直连带宽低于 `bandwidth.min_direct_bps` 且服务器允许中继时，建议双方优先经中继通信
+    fn prefers_relay(&self, a: &Uuid, b: &Uuid) -> bool {
+        let min = self.config.bandwidth.min_direct_bps;
+        min > 0
+            && self.config.allow_symmetric_nat_relay
+            && self.peer_manager.bandwidth().path_bandwidth(a, b).is_some_and(|bps| bps < min)
+    }
+
+    /// 记录节点上报的直连结果，用于统计各路径的成功率
+    async fn handle_p2p_connect_result(
+        &self,
+        peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
+        message: &Message,
+    ) -> Result<()> {
+        let (peer_id, authenticated) = (snapshot.id, snapshot.is_authenticated());
+        if !authenticated {
+            warn!("{}", tr!("忽略未认证节点 {} 的直连结果", "Ignoring direct connection result from unauthenticated node {}", peer_id));
+            return Ok(());
+        }
+        let path = message
+            .payload
+            .get("path")
+            .and_then(|v| serde_json::from_value::<P2PPath>(v.clone()).ok());
+        let Some(path) = path else {
+            let err = Message::error("缺少或无效的 path".to_string());
+            return peer.read().await.send_message(&err).await;
+        };
+        let counter = match path {
+            P2PPath::Private => &self.metrics.p2p_private_path,
+            P2PPath::Public => &self.metrics.p2p_public_path,
+            P2PPath::Relay => &self.metrics.p2p_relay_path,
+            P2PPath::Failed => &self.metrics.p2p_failed,
+        };
+        ServerMetrics::incr(counter);
+        let target = message.payload.get("peer_id").and_then(|v| v.as_str()).unwrap_or("未知");
+        info!("{}", tr!("节点 {} 与 {} 的直连结果: {:?}", "Direct connection result between nodes {} and {}: {:?}", peer_id, target, path));
+        if let Ok(target_id) = Uuid::parse_str(target)
+            && self.peer_manager.get_peer(&target_id).await.is_some()
+        {
+            self.peer_manager.record_session(peer_id, target_id, path);
+        }
         Ok(())
     }
 
@@ -610,26 +2909,31 @@ impl P2PServer {
         let peer_id = peer.read().await.id;
         let peer_addr = peer.read().await.addr();
         
-        debug!("开始处理来自 {} 的消息", peer_addr);
+        debug!("{}", tr!("开始处理来自 {} 的消息", "Handling messages from {}", peer_addr));
         
         loop {
             let message = match peer.read().await.receive_message().await {
                 Ok(Some(msg)) => msg,
                 Ok(None) => {
-                    info!("对等节点 {} 断开连接", peer_addr);
+                    info!("{}", tr!("对等节点 {} 断开连接", "Peer {} disconnected", peer_addr));
                     break;
                 }
                 Err(e) => {
-                    warn!("从对等节点 {} 接收消息失败: {}", peer_addr, e);
+                    warn!("{}", tr!("从对等节点 {} 接收消息失败: {}", "Failed to receive message from peer {}: {}", peer_addr, e));
                     break;
                 }
             };
             
-            debug!("从 {} 接收到消息: {:?}", peer_addr, message.message_type);
+            debug!("{}", tr!("从 {} 接收到消息: {:?}", "Received message from {}: {:?}", peer_addr, message.message_type));
+            let snapshot = peer.read().await.snapshot();
             
             let result = match message.message_type {
                 MessageType::HandshakeRequest => {
-                    peer_manager.handle_handshake_request(peer.clone(), &message).await
+                    match peer_manager.answer_duplicate_handshake(&peer, &message).await {
+                        Ok(true) => Ok(()),
+                        Ok(false) => peer_manager.handle_handshake_request(peer.clone(), &message).await,
+                        Err(e) => Err(e),
+                    }
                 }
                 MessageType::HandshakeResponse => {
                     peer_manager.handle_handshake_response(peer.clone(), &message).await
@@ -641,12 +2945,12 @@ impl P2PServer {
                     peer_manager.handle_pong(peer.clone(), &message).await
                 }
                 MessageType::DiscoveryRequest => {
-                    Self::handle_discovery_request(&peer_manager, peer.clone(), &message).await
+                    Self::handle_discovery_request(&peer_manager, self.peer_registry.as_ref(), peer.clone(), &snapshot, &message).await
                 }
                 MessageType::DiscoveryResponse => {
                     // 更新路由表（经该对端的下一跳，距离为2）
                     if let Ok(peer_list) = serde_json::from_value::<Vec<PeerInfo>>(message.payload.clone()) {
-                        let next_hop = peer.read().await.id;
+                        let next_hop = snapshot.id;
                         for p in peer_list {
                             if p.id == next_hop { continue; }
                         }
@@ -658,35 +2962,35 @@ impl P2PServer {
                     match RoutedMessage::from_message(&message) {
                         Ok(routed) => {
                             // 这里无法访问 server 的 router；该函数目前未在运行循环中使用
-                            debug!("收到路由数据消息，route_id={:?}", routed.route_id);
+                            debug!("{}", tr!("收到路由数据消息，route_id={:?}", "Received routed data message, route_id={:?}", routed.route_id));
                             Ok(())
                         }
                         Err(_) => {
-                            self.handle_data_message(peer.clone(), &message).await
+                            self.handle_data_message(peer.clone(), &snapshot, &message).await
                         }
                     }
                 }
                 MessageType::Disconnect => {
-                    info!("对等节点 {} 请求断开连接", peer_addr);
+                    info!("{}", tr!("对等节点 {} 请求断开连接", "Peer {} requested disconnect", peer_addr));
                     break;
                 }
                 MessageType::Error => {
-                    warn!("从对等节点 {} 接收到错误消息: {:?}", peer_addr, message.payload);
+                    warn!("{}", tr!("从对等节点 {} 接收到错误消息: {:?}", "Error message received from peer {}: {:?}", peer_addr, message.payload));
                     Ok(())
                 }
                 _ => {
-                    warn!("未知消息类型: {:?}", message.message_type);
+                    warn!("{}", tr!("未知消息类型: {:?}", "Unknown message type: {:?}", message.message_type));
                     Ok(())
                 }
             };
             
             if let Err(e) = result {
-                error!("处理消息失败: {}", e);
+                error!("{}", tr!("处理消息失败: {}", "Failed to handle message: {}", e));
                 
                 // 发送错误响应
                 let error_msg = Message::error(format!("处理消息失败: {}", e));
                 if let Err(send_err) = peer.read().await.send_message(&error_msg).await {
-                    error!("发送错误消息失败: {}", send_err);
+                    error!("{}", tr!("发送错误消息失败: {}", "Failed to send error message: {}", send_err));
                 }
                 
                 // 对于严重错误，断开连接
@@ -701,144 +3005,452 @@ impl P2PServer {
         Ok(())
     }
     
+    /// 附加内网直连信息：`candidates` 按尝试顺序列出通告的内网地址、公网地址
+    fn add_lan_shortcut(payload: &mut serde_json::Value, private_addrs: &[std::net::SocketAddr], public_addr: std::net::SocketAddr) {
+        let mut candidates: Vec<String> = private_addrs.iter().map(|addr| addr.to_string()).collect();
+        if !private_addrs.contains(&public_addr) {
+            candidates.push(public_addr.to_string());
+        }
+        payload["same_public_ip"] = serde_json::Value::Bool(true);
+        payload["peer_private_addr"] = serde_json::Value::String(private_addrs[0].to_string());
+        payload["candidates"] = serde_json::json!(candidates);
+    }
+
+    /// 附加对方通告的全部本地地址（多宿主主机），由客户端挑选可达的地址
+    fn add_peer_addresses(payload: &mut serde_json::Value, addrs: &[std::net::SocketAddr]) {
+        if !addrs.is_empty() {
+            payload["peer_addresses"] = serde_json::json!(addrs.iter().map(|addr| addr.to_string()).collect::<Vec<_>>());
+        }
+    }
+
+    /// 按 `punch_timing` 的时间表向直连双方发送倒计时信标（T-2s、T-1s、GO），
+    /// 使双方的探测几乎同时到达各自的 NAT，落在端口受限与对称 NAT 的映射窗口内
+    fn start_punch_beacons(&self, a: Uuid, b: Uuid) {
+        let schedule = self.config.punch_timing.schedule();
+        let lead = schedule[0];
+        let peer_manager = self.peer_manager.clone();
+        tokio::spawn(async move {
+            let start = tokio::time::Instant::now();
+            for go_in_ms in schedule {
+                tokio::time::sleep_until(start + Duration::from_millis(lead - go_in_ms)).await;
+                for (to, other) in [(a, b), (b, a)] {
+                    let Some(peer) = peer_manager.get_peer(&to).await else { continue };
+                    let Ok(beacon) = Message::punch_beacon(&PunchBeacon { peer_id: other, go_in_ms }) else { continue };
+                    if let Err(e) = peer.read().await.send_message(&beacon).await {
+                        debug!("{}", tr!("向节点 {} 发送打洞信标失败: {}", "Failed to send punch beacon to node {}: {}", to, e));
+                    }
+                }
+            }
+            debug!("{}", tr!("节点 {} 与 {} 的打洞信标已发送完毕", "Punch beacons for nodes {} and {} all sent", a, b));
+        });
+    }
+
+    /// 构建发给目标方的P2P直连协调消息，附带请求方通告的地址与上报的NAT穿透信息
+    fn p2p_connect_to_target(
+        requester_id: Uuid,
+        requester_addr: std::net::SocketAddr,
+        requester_addrs: &[std::net::SocketAddr],
+        message: &Message,
+    ) -> Message {
+        let mut payload = serde_json::json!({
+            "peer_id": requester_id.to_string(),
+            "peer_addr": requester_addr.to_string()
+        });
+        Self::add_peer_addresses(&mut payload, requester_addrs);
+        for (from, to) in [
+            ("nat_type", "peer_nat_type"),
+            ("predicted_ports", "peer_predicted_ports"),
+            ("public_addr", "peer_public_addr"),
+        ] {
+            if let Some(value) = message.payload.get(from) {
+                debug!("{}", tr!("转发NAT穿透信息 {}: {:?}", "Forwarding NAT traversal info {}: {:?}", from, value));
+                payload[to] = value.clone();
+            }
+        }
+        Message::new(MessageType::P2PConnect, payload)
+    }
+
     async fn handle_discovery_request(
         peer_manager: &Arc<PeerManager>,
+        peer_registry: Option<&Arc<dyn PeerRegistry>>,
         peer: Arc<tokio::sync::RwLock<Peer>>,
-        _message: &Message,
+        snapshot: &PeerSnapshot,
+        message: &Message,
     ) -> Result<()> {
-        let requester_id = peer.read().await.id;
-        let peer_infos = peer_manager.get_peer_info_list_excluding(Some(requester_id)).await;
-        let response = Message::discovery_response(peer_infos);
+        let requester_id = snapshot.id;
+        let mut entries = peer_manager.peer_list_entries(Some(requester_id)).await;
+        // 集群模式下附带注册在其他实例上的节点
+        if let Some(registry) = peer_registry {
+            entries.extend(registry.remote_peers().into_iter().map(|entry| {
+                let traits = PeerTraits::new(Some(&entry.node_info), entry.nat_type, Duration::ZERO);
+                let addresses = entry.node_info.advertised_addrs();
+                let mut info = PeerInfo::new(entry.node_info.id, entry.node_info.listen_addr, entry.node_info.capabilities);
+                info.addresses = addresses;
+                (info, traits)
+            }));
+        }
+        // 按延迟排序：测过往返时延的节点在前，可只取最近的若干个；列表超出大小上限时保留排在前面的节点
+        let mut response = if message.payload.get("order").and_then(|v| v.as_str()) == Some("latency") {
+            let mut peer_infos: Vec<PeerInfo> = entries.into_iter().map(|(info, _)| info).collect();
+            peer_manager.latency().sort_by_latency(&requester_id, &mut peer_infos);
+            if let Some(limit) = message.payload.get("limit").and_then(|v| v.as_u64()) {
+                peer_infos.truncate(limit as usize);
+            }
+            peer_manager.discovery_message(&PeerListSnapshot::new(&peer_infos), None, None).await
+        } else {
+            let traits = peer.read().await.traits();
+            peer_manager.discovery_message(&PeerListSnapshot::with_traits(entries), None, Some(&traits)).await
+        };
+        response.reply_to = Some(message.id);
         
         peer.read().await.send_message(&response).await?;
         
-        debug!("发送节点发现响应给 {}", peer.read().await.addr());
+        debug!("{}", tr!("发送节点发现响应给 {}", "Sending discovery response to {}", peer.read().await.addr()));
         
         Ok(())
     }
     
+    /// 根据握手时出示的访问令牌确定节点角色；令牌在握手时已从节点信息中取出，用后即清除
+    async fn assign_role(&self, peer: &Arc<tokio::sync::RwLock<Peer>>) {
+        let mut guard = peer.write().await;
+        let token = guard.access_token.take();
+        let role = self.config.control.role_for(token.as_deref());
+        if let Some(token) = &token
+            && !self.config.control.tokens.contains_key(token)
+        {
+            warn!("{}", tr!("节点 {} 出示的访问令牌无效，按默认角色 {:?} 处理", "Node {} presented an invalid access token, treating it as default role {:?}", guard.id, role));
+        }
+        guard.role = Some(role);
+    }
+
+    /// 检查节点能否发起控制查询：必须已认证、查询在 `control.allowed` 中、角色满足要求且未超出频率限制
+    async fn authorize_control(&self, peer: &Arc<tokio::sync::RwLock<Peer>>, command: ControlCommand) -> Result<(), String> {
+        let control = &self.config.control;
+        let mut guard = peer.write().await;
+        if !guard.is_authenticated() {
+            return Err("控制查询需要先完成握手".to_string());
+        }
+        if !control.allowed.contains(&command) {
+            return Err(format!("服务器未开放控制查询 {:?}", command));
+        }
+        let role = guard.role.unwrap_or(control.default_role);
+        let required = control.required_role(command);
+        if role < required {
+            return Err(format!("控制查询 {:?} 需要 {:?} 角色", command, required));
+        }
+        if role < PeerRole::Admin && !guard.take_control_quota(control.rate_limit_per_minute) {
+            return Err("控制查询过于频繁，请稍后再试".to_string());
+        }
+        Ok(())
+    }
+
+    /// 授权控制查询，未授权时回复 `Error` 并返回 `false`
+    async fn check_control(&self, peer: &Arc<tokio::sync::RwLock<Peer>>, command: ControlCommand) -> Result<bool> {
+        if let Err(reason) = self.authorize_control(peer, command).await {
+            debug!("{}", tr!("拒绝节点 {} 的控制查询 {:?}: {}", "Rejecting control query {1:?} from node {0}: {2}", peer.read().await.addr(), command, reason));
+            ServerMetrics::incr(&self.metrics.control_denied);
+            peer.read().await.send_message(&Message::error(reason)).await?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// 处理控制查询，返回对应类型的响应；未授权时回复 `Error`
+    async fn handle_control_request(&self, peer: Arc<tokio::sync::RwLock<Peer>>, command: ControlCommand) -> Result<()> {
+        if !self.check_control(&peer, command).await? {
+            return Ok(());
+        }
+        let response = match command {
+            ControlCommand::GetRoutes => {
+                let routes = self.message_router.get_routing_table_snapshot().await
+                    .into_iter()
+                    .map(|(destination, next_hop, distance)| RouteEntry { destination, next_hop, distance })
+                    .collect();
+                Message::get_routes_response(&GetRoutesResponse { routes })?
+            }
+            ControlCommand::GetStats => {
+                let stats = self.peer_manager.get_stats().await;
+                Message::get_stats_response(&GetStatsResponse {
+                    node_id: self.local_node_info.id,
+                    peers: PeerCounts {
+                        total: stats.total_peers,
+                        authenticated: stats.authenticated_peers,
+                        connecting: stats.connecting_peers,
+                    },
+                    metrics: self.metrics.snapshot(),
+                    memory: self.memory.usage().await,
+                })?
+            }
+            ControlCommand::GetPeers => {
+                let peers = admin::peer_summaries(&self.peer_manager).await;
+                Message::get_peers_response(&GetPeersResponse { peers })?
+            }
+            ControlCommand::GetConfig => {
+                let config = admin::config_json(&self.config, &self.peer_manager)?;
+                Message::get_config_response(&GetConfigResponse { node_id: self.local_node_info.id, config })?
+            }
+            ControlCommand::ListNodes | ControlCommand::Topology => {
+                unreachable!("{:?} 有专门的请求处理", command)
+            }
+        };
+        peer.read().await.send_message(&response).await
+    }
+
     async fn handle_data_message(
         &self,
         peer: Arc<tokio::sync::RwLock<Peer>>,
+        snapshot: &PeerSnapshot,
         message: &Message,
     ) -> Result<()> {
         // 这里可以实现数据消息的处理逻辑
         // 例如：转发给其他节点、存储数据等
         
-        debug!("从 {} 接收到数据消息: {:?}", peer.read().await.addr(), message.payload);
-        
-        // 命令：获取路由快照
-        if let Some(obj) = message.payload.as_object() {
-            if let Some(cmd) = obj.get("cmd").and_then(|v| v.as_str()) {
-                if cmd == "get_routes" {
-                    let snapshot = self.message_router.get_routing_table_snapshot().await;
-                    let routes: Vec<serde_json::Value> = snapshot
-                        .into_iter()
-                        .map(|(dest, next_hop, distance)| serde_json::json!({
-                            "destination": dest,
-                            "next_hop": next_hop,
-                            "distance": distance
-                        }))
-                        .collect();
-                    let resp = Message::data(serde_json::json!({ "routes": routes }));
-                    peer.read().await.send_message(&resp).await?;
-                    return Ok(());
-                }
+        // 消息内容是用户数据，与数据包一样只在 `raw` 模式下记录
+        if self.config.logging.packets == PacketLogMode::Raw {
+            debug!("{}", tr!("从 {} 接收到数据消息: {:?}", "Received data message from {}: {:?}", snapshot.addr, message.payload));
+        }
+        
+        // 服务器经 Requester 发出的请求的应答
+        if message.reply_to.is_some() && self.pending_replies.resolve(message.clone()).is_none() {
+            return Ok(());
+        }
+
+        // 已弃用的字符串命令：获取路由快照（请改用 GetRoutesRequest）
+        if self.config.control.legacy_commands
+            && let Some(obj) = message.payload.as_object()
+            && let Some(cmd) = obj.get("cmd").and_then(|v| v.as_str())
+            && cmd == "get_routes"
+        {
+            if !LEGACY_COMMAND_WARNED.swap(true, Ordering::Relaxed) {
+                warn!("{}", tr!("Data 中的 {{\"cmd\": \"get_routes\"}} 命令已弃用，请改用 GetRoutesRequest 消息", "The {{\"cmd\": \"get_routes\"}} command in Data messages is deprecated, use GetRoutesRequest instead"));
             }
+            if !self.check_control(&peer, ControlCommand::GetRoutes).await? {
+                return Ok(());
+            }
+            let snapshot = self.message_router.get_routing_table_snapshot().await;
+            let routes: Vec<serde_json::Value> = snapshot
+                .into_iter()
+                .map(|(dest, next_hop, distance)| serde_json::json!({
+                    "destination": dest,
+                    "next_hop": next_hop,
+                    "distance": distance
+                }))
+                .collect();
+            let resp = Message::data(serde_json::json!({ "routes": routes }));
+            peer.read().await.send_message(&resp).await?;
+            return Ok(());
+        }
+
+        if let Some(handler) = &self.message_handler {
+            let from = peer.read().await.id;
+            if let Some(mut reply) = handler.handle_data(from, message).await? {
+                reply.reply_to.get_or_insert(message.id);
+                peer.read().await.send_message(&reply).await?;
+            }
+            return Ok(());
         }
 
-        // 简单的回显响应（默认行为）
-        let echo_response = Message::data(serde_json::json!({
-            "echo": message.payload,
-            "timestamp": std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-        }));
-        peer.read().await.send_message(&echo_response).await?;
+        // 回显仅用于调试，需在配置中显式开启
+        if self.config.echo_unhandled_data {
+            let echo_response = message.respond(serde_json::json!({
+                "echo": message.payload,
+                "timestamp": std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+            }));
+            peer.read().await.send_message(&echo_response).await?;
+        } else {
+            ServerMetrics::incr(&self.metrics.data_unhandled);
+        }
         
         Ok(())
     }
     
+    /// 登记待确认的消息ID：批次首个ID启动合并计时，攒满时立即发出
+    async fn queue_ack(&self, batcher: &Arc<AckBatcher>, id: Uuid, addr: std::net::SocketAddr) {
+        match batcher.push(addr, id) {
+            AckQueued::Queued => {}
+            AckQueued::Full(ids) => {
+                Self::send_ack_batch(&self.network_manager, &self.metrics, self.local_node_info.listen_addr, addr, &ids).await;
+            }
+            AckQueued::First => {
+                let batcher = batcher.clone();
+                let network_manager = self.network_manager.clone();
+                let metrics = self.metrics.clone();
+                let local_addr = self.local_node_info.listen_addr;
+                let delay = Duration::from_millis(self.config.ack_batch.delay_ms);
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let ids = batcher.take(addr);
+                    Self::send_ack_batch(&network_manager, &metrics, local_addr, addr, &ids).await;
+                });
+            }
+        }
+    }
+
+    async fn send_ack_batch(
+        network_manager: &NetworkManager,
+        metrics: &ServerMetrics,
+        local_addr: std::net::SocketAddr,
+        addr: std::net::SocketAddr,
+        ids: &[Uuid],
+    ) {
+        if ids.is_empty() {
+            return;
+        }
+        if let Err(e) = network_manager.send_to(&Message::ack_batch(ids, local_addr), addr).await {
+            warn!("{}", tr!("发送ACK失败: {}", "Failed to send ACK: {}", e));
+            return;
+        }
+        ServerMetrics::add(&metrics.acks_coalesced, ids.len() as u64 - 1);
+        debug!("{}", tr!("已向 {} 发送批量ACK，确认 {} 条消息", "Sent batched ACK to {} acknowledging {} messages", addr, ids.len()));
+    }
+
+    fn peer_resources(&self) -> PeerResources {
+        PeerResources {
+            peer_manager: self.peer_manager.clone(),
+            peer_registry: self.peer_registry.clone(),
+            message_router: self.message_router.clone(),
+            relay_sessions: self.relay_sessions.clone(),
+            topic_bus: self.topic_bus.clone(),
+            services: self.services.clone(),
+            tcp_punch_offers: self.tcp_punch_offers.clone(),
+        }
+    }
+
     fn start_heartbeat_task(&self) -> tokio::task::JoinHandle<()> {
         let peer_manager = self.peer_manager.clone();
-        let heartbeat_interval = self.config.heartbeat_interval;
-        let timeout = self.config.connection_timeout;
+        let resources = self.peer_resources();
+        let base_timeout = self.config.connection_timeout;
+        let heartbeat = self.config.heartbeat.clone();
         
-        tokio::spawn(async move {
-            let mut interval = interval(Duration::from_secs(heartbeat_interval));
-            
-            loop {
-                interval.tick().await;
-                
-                // 1) 首先清理长期未响应的节点（在发送新的ping之前）
-                let peers = peer_manager.get_authenticated_peers().await;
-                let mut to_remove = Vec::new();
-                let mut active_peers = Vec::new();
+        self.supervisor.spawn("heartbeat", move || {
+            let peer_manager = peer_manager.clone();
+            let resources = resources.clone();
+            let heartbeat = heartbeat.clone();
+            async move {
+                loop {
+                    let round_started = Instant::now();
+                    // 超过软限制时放慢心跳，节点较多时按节点数放大间隔并分散发送；超时判定按同样倍数放宽
+                    let busy = peer_manager.is_busy().await;
+                    let peers = peer_manager.get_authenticated_peers().await;
+                    let plan = HeartbeatPlan::new(&heartbeat, peer_manager.limits().heartbeat_interval_secs(busy), peers.len());
+                    let timeout = base_timeout * peer_manager.limits().heartbeat_multiplier(busy) as u64 * plan.timeout_scale;
+
+                    // 1) 首先清理长期未响应的节点（在发送新的ping之前）
+                    let mut to_remove = Vec::new();
+                    let mut active_peers = Vec::new();
                 
-                for peer in peers {
-                    let pg = peer.read().await;
-                    let stale = match pg.last_ping {
-                        Some(ts) => ts.elapsed().as_secs() > timeout,
-                        None => pg.created_at.elapsed().as_secs() > timeout,
-                    };
+                    for peer in peers {
+                        let pg = peer.read().await;
+                        // 保活探测期间不发心跳（回复的Pong会刷新NAT映射），也不做超时判定；休眠节点同样不打扰
+                        if peer_manager.keepalive().is_probing(&pg.id) || pg.is_dormant() {
+                            continue;
+                        }
+                        let stale = match pg.last_ping {
+                            Some(ts) => ts.elapsed().as_secs() > timeout,
+                            None => pg.created_at.elapsed().as_secs() > timeout,
+                        };
                     
-                    if stale {
-                        to_remove.push(pg.id);
-                        info!("节点 {} ({}) 超时未响应，将被移除", pg.id, pg.addr());
-                    } else {
-                        active_peers.push(peer.clone());
+                        if stale {
+                            to_remove.push(pg.id);
+                            info!("{}", tr!("节点 {} ({}) 超时未响应，将被移除", "Node {} ({}) timed out and will be removed", pg.id, pg.addr()));
+                            let notice = Message::disconnect_with_code(DisconnectReason::IdleTimeout, "心跳超时".to_string());
+                            if let Err(e) = pg.send_message(&notice).await {
+                                debug!("{}", tr!("发送超时断开通知到 {} 失败: {}", "Failed to send timeout disconnect notice to {}: {}", pg.addr(), e));
+                            }
+                        } else {
+                            active_peers.push(peer.clone());
+                        }
                     }
-                }
                 
-                // 移除超时节点
-                let removed_count = to_remove.len();
-                for id in to_remove {
-                    peer_manager.remove_peer(&id).await;
-                }
+                    // 移除超时节点
+                    let removed_count = to_remove.len();
+                    for id in to_remove {
+                        peer_manager.remove_peer(&id).await;
+                        resources.release(&id).await;
+                    }
+                
+                    // 2) 向活跃节点发送心跳，按计划的间隔逐个发出
+                    let peer_count = active_peers.len();
+                    tokio::time::sleep(plan.jitter()).await;
+                    for (i, peer) in active_peers.iter().enumerate() {
+                        if i > 0 && !plan.gap.is_zero() {
+                            tokio::time::sleep(plan.gap).await;
+                        }
+                        let ping_message = Message::ping();
+                        if let Err(e) = peer.read().await.send_message(&ping_message).await {
+                            warn!("{}", tr!("发送心跳失败: {}", "Failed to send heartbeat: {}", e));
+                            peer.write().await.update_status(PeerStatus::Error(e.to_string()));
+                        } else {
+                            peer.write().await.pings.sent(Instant::now());
+                        }
+                    }
                 
-                // 2) 向活跃节点发送心跳
-                let peer_count = active_peers.len();
-                for peer in &active_peers {
-                    let ping_message = Message::ping();
-                    if let Err(e) = peer.read().await.send_message(&ping_message).await {
-                        warn!("发送心跳失败: {}", e);
-                        peer.write().await.update_status(PeerStatus::Error(e.to_string()));
+                    // 3) 如果有节点被移除，广播最新节点列表
+                    if removed_count > 0 {
+                        let _ = peer_manager.broadcast_peer_list(None).await;
                     }
-                }
                 
-                // 3) 如果有节点被移除，广播最新节点列表
-                if removed_count > 0 {
-                    let _ = peer_manager.broadcast_peer_list(None).await;
+                    debug!(
+                        "{}",
+                        tr!(
+                            "发送心跳给 {} 个节点，移除 {} 个超时节点，本轮 {:?}",
+                            "Sent heartbeats to {} nodes, removed {} timed-out nodes, round {:?}",
+                            peer_count,
+                            removed_count,
+                            plan.round,
+                        )
+                    );
+
+                    tokio::time::sleep(plan.round.saturating_sub(round_started.elapsed())).await;
                 }
-                
-                debug!("发送心跳给 {} 个节点，移除 {} 个超时节点", peer_count, removed_count);
             }
         })
     }
     
     fn start_cleanup_task(&self) -> tokio::task::JoinHandle<()> {
         let peer_manager = self.peer_manager.clone();
-        let timeout = self.config.connection_timeout;
+        let base_timeout = self.config.connection_timeout;
+        let heartbeat = self.config.heartbeat.clone();
+        let cleanup_interval = Duration::from_secs(self.config.cleanup_interval.max(1));
+        let relay_idle = Duration::from_secs(self.config.relay.idle_timeout_secs);
+        let resources = self.peer_resources();
         
-        tokio::spawn(async move {
-            let mut interval = interval(Duration::from_secs(30)); // 每30秒清理一次，更频繁
+        self.supervisor.spawn("cleanup", move || {
+            let peer_manager = peer_manager.clone();
+            let resources = resources.clone();
+            let heartbeat = heartbeat.clone();
+            async move {
+                let mut interval = interval(cleanup_interval);
             
-            loop {
-                interval.tick().await;
+                loop {
+                    interval.tick().await;
                 
-                let before_count = peer_manager.get_authenticated_peers().await.len();
-                peer_manager.cleanup_disconnected_peers(timeout).await;
-                let after_count = peer_manager.get_authenticated_peers().await.len();
+                    // 与心跳任务使用相同的超时放大倍数，避免心跳间隔放大后误清理节点
+                    let busy = peer_manager.is_busy().await;
+                    let before_count = peer_manager.get_authenticated_peers().await.len();
+                    let plan = HeartbeatPlan::new(&heartbeat, peer_manager.limits().heartbeat_interval_secs(busy), before_count);
+                    let timeout = base_timeout * peer_manager.limits().heartbeat_multiplier(busy) as u64 * plan.timeout_scale;
+                    for id in peer_manager.cleanup_disconnected_peers(timeout).await {
+                        resources.release(&id).await;
+                    }
+                    if !relay_idle.is_zero() {
+                        let expired = resources.relay_sessions.expire_idle(relay_idle);
+                        resources.close_relays(&expired, RelayCloseReason::IdleTimeout).await;
+                    }
+                    let after_count = peer_manager.get_authenticated_peers().await.len();
                 
-                let cleaned_count = before_count.saturating_sub(after_count);
+                    let cleaned_count = before_count.saturating_sub(after_count);
                 
-                // 只有在清理了节点时才广播和记录日志
-                if cleaned_count > 0 {
-                    let _ = peer_manager.broadcast_peer_list(None).await;
-                    info!("清理任务完成：移除了 {} 个断开的节点，当前活跃节点数: {}", cleaned_count, after_count);
-                } else {
-                    debug!("清理任务完成：无需清理节点，当前活跃节点数: {}", after_count);
+                    // 只有在清理了节点时才广播和记录日志
+                    if cleaned_count > 0 {
+                        let _ = peer_manager.broadcast_peer_list(None).await;
+                        info!("{}", tr!("清理任务完成：移除了 {} 个断开的节点，当前活跃节点数: {}", "Cleanup finished: removed {} disconnected nodes, active nodes: {}", cleaned_count, after_count));
+                    } else {
+                        debug!("{}", tr!("清理任务完成：无需清理节点，当前活跃节点数: {}", "Cleanup finished: no nodes to remove, active nodes: {}", after_count));
+                    }
                 }
             }
         })
@@ -847,27 +3459,53 @@ impl P2PServer {
     fn start_stats_task(&self) -> tokio::task::JoinHandle<()> {
         let peer_manager = self.peer_manager.clone();
         
-        tokio::spawn(async move {
-            let mut interval = interval(Duration::from_secs(300)); // 每5分钟输出一次统计
+        self.supervisor.spawn("stats", move || {
+            let peer_manager = peer_manager.clone();
+            async move {
+                let mut interval = interval(Duration::from_secs(300)); // 每5分钟输出一次统计
             
-            loop {
-                interval.tick().await;
+                loop {
+                    interval.tick().await;
                 
-                let stats = peer_manager.get_stats().await;
-                info!(
-                    "节点统计 - 总数: {}, 已认证: {}, 连接中: {}",
-                    stats.total_peers,
-                    stats.authenticated_peers,
-                    stats.connecting_peers
-                );
+                    let stats = peer_manager.get_stats().await;
+                    info!(
+                        "{}",
+                        tr!(
+                            "节点统计 - 总数: {}, 已认证: {}, 连接中: {}",
+                            "Node stats - total: {}, authenticated: {}, connecting: {}",
+                            stats.total_peers,
+                            stats.authenticated_peers,
+                            stats.connecting_peers,
+                        )
+                    );
+                }
             }
         })
     }
     
+    fn start_memory_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.memory.has_limits() {
+            return None;
+        }
+        let memory = self.memory.clone();
+        let check_interval = Duration::from_secs(self.config.memory.check_interval_secs.max(1));
+
+        Some(self.supervisor.spawn("memory", move || {
+            let memory = memory.clone();
+            async move {
+                let mut interval = interval(check_interval);
+                loop {
+                    interval.tick().await;
+                    memory.enforce().await;
+                }
+            }
+        }))
+    }
+    
     /// 主动连接到其他节点
     #[allow(dead_code)]
     pub async fn connect_to_peer(&self, addr: std::net::SocketAddr) -> Result<()> {
-        info!("尝试连接到UDP对等节点: {}", addr);
+        info!("{}", tr!("尝试连接到UDP对等节点: {}", "Trying to connect to UDP peer: {}", addr));
         
         // 发送握手请求
         let handshake_request = Message::new_with_ack(
@@ -879,23 +3517,35 @@ impl P2PServer {
         
         self.network_manager.send_to(&handshake_request, addr).await?;
         
-        info!("已向 {} 发送握手请求", addr);
+        info!("{}", tr!("已向 {} 发送握手请求", "Handshake request sent to {}", addr));
         Ok(())
     }
     
+    /// 导出服务器已知的网络拓扑：已认证节点、直连链路、链路状态链路与路由表
+    pub async fn topology_snapshot(&self) -> TopologySnapshot {
+        topology::collect(self.local_node_info.id, &self.peer_manager, &self.message_router).await
+    }
+
     /// 获取服务器统计信息
     #[allow(dead_code)]
     pub async fn get_stats(&self) -> ServerStats {
         let peer_stats = self.peer_manager.get_stats().await;
         
+        let peer_connected_secs = self.peer_manager.connection_durations().await
+            .into_iter()
+            .map(|(id, connected)| (id, connected.as_secs()))
+            .collect();
+
         ServerStats {
             node_id: self.local_node_info.id,
             listen_address: self.config.listen_address,
             peer_stats,
-            uptime: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            uptime: self.metrics.uptime().as_secs(),
+            started_at: self.metrics.started_at_unix(),
+            peer_connected_secs,
+            memory: self.memory.usage().await,
+            carrier_nat: self.peer_manager.carrier_nat_stats().await,
+            peer_link_quality: self.peer_manager.link_qualities().await.into_iter().collect(),
         }
     }
     
@@ -909,13 +3559,13 @@ impl P2PServer {
         // 向所有连接的节点发送断开消息
         let peers = self.peer_manager.get_all_peers().await;
         for peer in peers {
-            let disconnect_msg = Message::disconnect("服务器关闭".to_string());
+            let disconnect_msg = Message::disconnect_with_code(DisconnectReason::ServerShutdown, "服务器关闭".to_string());
             if let Err(e) = peer.read().await.send_message(&disconnect_msg).await {
-                warn!("发送断开消息失败: {}", e);
+                warn!("{}", tr!("发送断开消息失败: {}", "Failed to send disconnect message: {}", e));
             }
         }
         
-        info!("服务器关闭完成");
+        info!("{}", tr!("服务器关闭完成", "Server shutdown complete"));
         Ok(())
     }
 
@@ -930,6 +3580,29 @@ impl P2PServer {
         let message = Message::data(data);
         self.message_router.route_message(message, destination, max_hops).await
     }
+
+    /// 通过路由多播数据到一组节点，返回实际发出的副本数
+    pub async fn send_multicast_data(
+        &self,
+        members: Vec<Uuid>,
+        data: serde_json::Value,
+        max_hops: u32,
+    ) -> Result<usize> {
+        let message = Message::data(data);
+        self.message_router.route_multicast(message, members, max_hops).await
+    }
+
+    /// 通过路由向指定节点发送数据，并等待目标节点发回的送达回执
+    pub async fn send_routed_data_with_receipt(
+        &self,
+        destination: Uuid,
+        data: serde_json::Value,
+        max_hops: u32,
+        wait: Duration,
+    ) -> Result<DeliveryReceipt> {
+        let message = Message::data(data);
+        self.message_router.route_with_receipt(message, destination, max_hops, wait).await
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -938,5 +3611,73 @@ pub struct ServerStats {
     pub node_id: Uuid,
     pub listen_address: std::net::SocketAddr,
     pub peer_stats: crate::peer::PeerStats,
+    /// 服务器已运行的秒数
     pub uptime: u64,
-}
\ No newline at end of file
+    /// 服务器启动时刻的UNIX时间戳（秒）
+    pub started_at: u64,
+    /// 各已认证节点已连接的秒数
+    pub peer_connected_secs: std::collections::HashMap<Uuid, u64>,
+    /// 各部分的近似内存占用
+    pub memory: MemoryUsage,
+    /// 已认证节点按运营商级地址转换的分类
+    pub carrier_nat: CarrierNatStats,
+    /// 各已认证节点的链路质量估计（平滑往返时延、抖动与丢失率）
+    pub peer_link_quality: std::collections::HashMap<Uuid, LinkQuality>,
+}
+/// 服务器按节点维护的状态（路由、中继会话、主题订阅与服务注册），节点记录被移除后需一并清理
+#[derive(Clone)]
+struct PeerResources {
+    peer_manager: Arc<PeerManager>,
+    peer_registry: Option<Arc<dyn PeerRegistry>>,
+    message_router: Arc<MessageRouter>,
+    relay_sessions: Arc<RelaySessions>,
+    topic_bus: Arc<TopicBus>,
+    services: Arc<ServiceDirectory>,
+    tcp_punch_offers: Arc<TcpPunchOffers>,
+}
+
+impl PeerRelease for PeerResources {
+    fn release<'a>(&'a self, peer_id: &'a Uuid) -> BoxFuture<'a, ()> {
+        Box::pin(PeerResources::release(self, peer_id))
+    }
+}
+
+impl PeerResources {
+    /// 释放已移除节点的全部状态
+    async fn release(&self, peer_id: &Uuid) {
+        self.message_router.remove_node_routes(peer_id).await;
+        let closed = self.relay_sessions.remove_peer(peer_id);
+        self.close_relays(&closed, RelayCloseReason::PeerDisconnected).await;
+        self.topic_bus.remove_peer(peer_id).await;
+        self.services.remove_peer(peer_id).await;
+        self.tcp_punch_offers.remove_peer(peer_id);
+    }
+
+    /// 通知已关闭中继会话的本地一方；跨实例会话同时通知另一段所在的实例
+    async fn close_relays(&self, closed: &[ClosedRelay], reason: RelayCloseReason) {
+        relay::notify_closed(&self.peer_manager, closed, reason).await;
+        let Some(registry) = &self.peer_registry else { return };
+        for relay in closed {
+            if let Some(instance_id) = relay.remote_instance
+                && let Err(e) = registry.close_relay(instance_id, relay.session_id, reason).await
+            {
+                debug!("{}", tr!("通知集群实例 {} 中继会话 {} 已关闭失败: {}", "Failed to notify cluster instance {} that relay session {} closed: {}", instance_id, relay.session_id, e));
+            }
+        }
+    }
+}
+
+/// 待转发的中继数据：从 JSON 负载解析出的字节，或节点发来的完整二进制帧
+enum RelayPayload {
+    Bytes(Vec<u8>),
+    Frame(Vec<u8>),
+}
+
+impl RelayPayload {
+    fn data(&self) -> &[u8] {
+        match self {
+            RelayPayload::Bytes(data) => data,
+            RelayPayload::Frame(packet) => &packet[RELAY_FRAME_HEADER_LEN..],
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+/// 健康分的平滑系数：每次心跳或握手的结果在新分数中所占的比重
+const SCORE_WEIGHT: f64 = 0.3;
+
+/// 一个握手服务器的健康状况
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ServerHealth {
+    pub addr: SocketAddr,
+    /// 健康分（0–1）：心跳得到应答、握手成功时上升，心跳未应答、握手失败时下降
+    pub score: f64,
+    /// 是否为当前使用的服务器
+    pub active: bool,
+}
+
+/// 客户端可用的握手服务器：主服务器与按配置顺序排列的备用服务器
+///
+/// 记录每个服务器的健康分与活动服务器连续未应答的心跳数，客户端据此决定何时切换、切换到哪一个。
+#[derive(Debug)]
+pub(crate) struct ServerPool {
+    state: Mutex<PoolState>,
+}
+
+#[derive(Debug)]
+struct PoolState {
+    servers: Vec<ServerHealth>,
+    /// 活动服务器连续未应答的心跳数
+    unacked: u32,
+}
+
+impl PoolState {
+    fn adjust(&mut self, addr: SocketAddr, success: bool) {
+        if let Some(server) = self.servers.iter_mut().find(|server| server.addr == addr) {
+            let outcome = if success { 1.0 } else { 0.0 };
+            server.score = server.score * (1.0 - SCORE_WEIGHT) + outcome * SCORE_WEIGHT;
+        }
+    }
+
+    fn active(&self) -> SocketAddr {
+        self.servers.iter().find(|server| server.active).unwrap_or(&self.servers[0]).addr
+    }
+}
+
+impl ServerPool {
+    /// 重复的备用地址只保留一个；主服务器起初为活动服务器
+    pub(crate) fn new(primary: SocketAddr, backups: &[SocketAddr]) -> Self {
+        let mut servers = vec![ServerHealth { addr: primary, score: 1.0, active: true }];
+        for addr in backups {
+            if !servers.iter().any(|server| server.addr == *addr) {
+                servers.push(ServerHealth { addr: *addr, score: 1.0, active: false });
+            }
+        }
+        Self { state: Mutex::new(PoolState { servers, unacked: 0 }) }
+    }
+
+    /// 当前活动服务器
+    pub(crate) fn active(&self) -> SocketAddr {
+        self.state.lock().unwrap().active()
+    }
+
+    /// 全部服务器，按配置顺序（主服务器在前）
+    pub(crate) fn addrs(&self) -> Vec<SocketAddr> {
+        self.state.lock().unwrap().servers.iter().map(|server| server.addr).collect()
+    }
+
+    /// 除活动服务器外的服务器，健康分高的在前，同分时按配置顺序
+    pub(crate) fn standby(&self) -> Vec<SocketAddr> {
+        let state = self.state.lock().unwrap();
+        let mut standby: Vec<&ServerHealth> = state.servers.iter().filter(|server| !server.active).collect();
+        standby.sort_by(|a, b| b.score.total_cmp(&a.score));
+        standby.into_iter().map(|server| server.addr).collect()
+    }
+
+    /// 切换活动服务器，重新开始计算未应答的心跳
+    pub(crate) fn activate(&self, addr: SocketAddr) {
+        let mut state = self.state.lock().unwrap();
+        for server in &mut state.servers {
+            server.active = server.addr == addr;
+        }
+        state.unacked = 0;
+    }
+
+    /// 改用服务器提示的地址：不在列表中时追加，再把它设为活动服务器
+    pub(crate) fn redirect(&self, addr: SocketAddr) {
+        let mut state = self.state.lock().unwrap();
+        if !state.servers.iter().any(|server| server.addr == addr) {
+            state.servers.push(ServerHealth { addr, score: 1.0, active: false });
+        }
+        for server in &mut state.servers {
+            server.active = server.addr == addr;
+        }
+        state.unacked = 0;
+    }
+
+    /// 即将向活动服务器发送心跳：上一次心跳仍未应答时扣分，返回此前连续未应答的心跳数
+    pub(crate) fn heartbeat(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        let missed = state.unacked;
+        if missed > 0 {
+            let active = state.active();
+            state.adjust(active, false);
+        }
+        state.unacked += 1;
+        missed
+    }
+
+    /// 活动服务器应答了心跳
+    pub(crate) fn acked(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.unacked = 0;
+        let active = state.active();
+        state.adjust(active, true);
+    }
+
+    /// 记录一次握手的结果
+    pub(crate) fn handshake_result(&self, addr: SocketAddr, success: bool) {
+        self.state.lock().unwrap().adjust(addr, success);
+    }
+
+    pub(crate) fn health(&self) -> Vec<ServerHealth> {
+        self.state.lock().unwrap().servers.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_missed_heartbeats_lower_score_and_order_standby() {
+        let pool = ServerPool::new(addr(1), &[addr(2), addr(3), addr(1)]);
+        assert_eq!(pool.addrs(), vec![addr(1), addr(2), addr(3)]);
+        assert_eq!(pool.active(), addr(1));
+        assert_eq!(pool.standby(), vec![addr(2), addr(3)]);
+
+        assert_eq!(pool.heartbeat(), 0);
+        assert_eq!(pool.heartbeat(), 1);
+        assert_eq!(pool.heartbeat(), 2);
+        assert!(pool.health()[0].score < 1.0);
+        pool.acked();
+        assert_eq!(pool.heartbeat(), 0);
+
+        // 握手失败的备用服务器排到后面
+        pool.handshake_result(addr(2), false);
+        assert_eq!(pool.standby(), vec![addr(3), addr(2)]);
+        pool.activate(addr(3));
+        assert_eq!(pool.active(), addr(3));
+        // 原主服务器漏过两次心跳，分数低于只握手失败过一次的服务器
+        assert_eq!(pool.standby(), vec![addr(2), addr(1)]);
+        assert_eq!(pool.heartbeat(), 0);
+        assert!(pool.health().iter().filter(|server| server.active).map(|server| server.addr).eq([addr(3)]));
+    }
+
+    #[test]
+    fn test_redirect_adds_unknown_server() {
+        let pool = ServerPool::new(addr(1), &[addr(2)]);
+        pool.redirect(addr(4));
+        assert_eq!(pool.active(), addr(4));
+        assert_eq!(pool.addrs(), vec![addr(1), addr(2), addr(4)]);
+        assert_eq!(pool.standby(), vec![addr(1), addr(2)]);
+        pool.redirect(addr(2));
+        assert_eq!(pool.addrs().len(), 3);
+        assert_eq!(pool.active(), addr(2));
+    }
+}
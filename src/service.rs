@@ -0,0 +1,264 @@
+//! 进程管理器集成：systemd `sd_notify` 通知、操作系统关闭信号以及 Windows 服务控制
+
+use std::time::Duration;
+use log::{debug, info, warn};
+use crate::tr;
+
+/// systemd 通知器（`Type=notify` 服务）
+///
+/// 从 `NOTIFY_SOCKET` 环境变量读取通知套接字，未设置时 [`SystemdNotifier::from_env`] 返回 `None`，
+/// 因此在非 systemd 环境下所有通知都是空操作。
+#[derive(Debug, Clone)]
+pub struct SystemdNotifier {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    socket_path: String,
+    watchdog_interval: Option<Duration>,
+}
+
+impl SystemdNotifier {
+    /// 根据环境变量创建通知器
+    pub fn from_env() -> Option<Self> {
+        let socket_path = std::env::var("NOTIFY_SOCKET").ok().filter(|p| !p.is_empty())?;
+        Some(Self {
+            socket_path,
+            watchdog_interval: Self::watchdog_from_env(),
+        })
+    }
+
+    /// 解析 `WATCHDOG_USEC`；若设置了 `WATCHDOG_PID` 且不是本进程则忽略
+    fn watchdog_from_env() -> Option<Duration> {
+        if let Ok(pid) = std::env::var("WATCHDOG_PID")
+            && pid.trim().parse::<u32>().ok() != Some(std::process::id())
+        {
+            return None;
+        }
+        let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.trim().parse().ok()?;
+        (usec > 0).then(|| Duration::from_micros(usec))
+    }
+
+    /// systemd 要求的看门狗超时；建议以其一半的间隔发送心跳
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog_interval
+    }
+
+    /// 发送原始通知（多个字段以换行分隔）
+    #[cfg(unix)]
+    pub fn notify(&self, state: &str) -> std::io::Result<()> {
+        use std::os::unix::net::UnixDatagram;
+
+        let socket = UnixDatagram::unbound()?;
+        // 以 '@' 开头表示 Linux 抽象命名空间套接字
+        if let Some(name) = self.socket_path.strip_prefix('@') {
+            #[cfg(target_os = "linux")]
+            {
+                use std::os::linux::net::SocketAddrExt;
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+                socket.send_to_addr(state.as_bytes(), &addr)?;
+                return Ok(());
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = name;
+                return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "抽象套接字仅支持Linux"));
+            }
+        }
+        socket.send_to(state.as_bytes(), &self.socket_path)?;
+        Ok(())
+    }
+
+    /// 非 Unix 平台没有 `NOTIFY_SOCKET`，通知为空操作
+    #[cfg(not(unix))]
+    pub fn notify(&self, _state: &str) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn notify_logged(&self, state: &str) {
+        match self.notify(state) {
+            Ok(()) => debug!("sd_notify: {}", state.replace('\n', " ")),
+            Err(e) => warn!("{}", tr!("发送systemd通知失败: {}", "Failed to send systemd notification: {}", e)),
+        }
+    }
+
+    /// 服务已就绪
+    pub fn ready(&self, status: &str) {
+        self.notify_logged(&format!("READY=1\nMAINPID={}\nSTATUS={}", std::process::id(), status));
+    }
+
+    /// 服务正在停止
+    pub fn stopping(&self) {
+        self.notify_logged("STOPPING=1\nSTATUS=正在停止");
+    }
+
+    /// 更新状态描述（`systemctl status` 中显示）
+    pub fn status(&self, status: &str) {
+        self.notify_logged(&format!("STATUS={}", status));
+    }
+
+    /// 看门狗心跳
+    pub fn watchdog(&self) {
+        if let Err(e) = self.notify("WATCHDOG=1") {
+            warn!("{}", tr!("发送systemd看门狗心跳失败: {}", "Failed to send systemd watchdog ping: {}", e));
+        }
+    }
+
+    /// 启动看门狗心跳任务（未配置看门狗时返回 None）
+    pub fn start_watchdog_task(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let timeout = self.watchdog_interval?;
+        let notifier = self.clone();
+        info!("{}", tr!("systemd看门狗已启用，超时 {:?}", "systemd watchdog enabled, timeout {:?}", timeout));
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(timeout / 2);
+            loop {
+                interval.tick().await;
+                notifier.watchdog();
+            }
+        }))
+    }
+}
+
+/// 等待操作系统的关闭请求：Unix 上为 SIGINT/SIGTERM，Windows 控制台上为 Ctrl+C/Ctrl+Break/关闭/注销/关机事件
+pub async fn shutdown_signal() -> std::io::Result<&'static str> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        tokio::select! {
+            _ = terminate.recv() => Ok("SIGTERM"),
+            _ = interrupt.recv() => Ok("SIGINT"),
+        }
+    }
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows;
+        let mut ctrl_c = windows::ctrl_c()?;
+        let mut ctrl_break = windows::ctrl_break()?;
+        let mut ctrl_close = windows::ctrl_close()?;
+        let mut ctrl_logoff = windows::ctrl_logoff()?;
+        let mut ctrl_shutdown = windows::ctrl_shutdown()?;
+        tokio::select! {
+            _ = ctrl_c.recv() => Ok("CTRL_C"),
+            _ = ctrl_break.recv() => Ok("CTRL_BREAK"),
+            _ = ctrl_close.recv() => Ok("CTRL_CLOSE"),
+            _ = ctrl_logoff.recv() => Ok("CTRL_LOGOFF"),
+            _ = ctrl_shutdown.recv() => Ok("CTRL_SHUTDOWN"),
+        }
+    }
+}
+
+/// 在 PID 文件中写入当前进程号，供传统的 init 脚本/进程管理器使用
+pub fn write_pid_file(path: &str) -> std::io::Result<()> {
+    std::fs::write(path, format!("{}\n", std::process::id()))
+}
+
+/// Windows 服务控制管理器（SCM）集成
+#[cfg(windows)]
+pub mod windows {
+    use std::ffi::OsString;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::Duration;
+    use anyhow::Result;
+    use log::error;
+    use tokio::sync::oneshot;
+    use windows_service::service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+    use windows_service::{define_windows_service, service_dispatcher};
+    use crate::tr;
+
+    /// 注册服务时使用的服务名
+    pub const SERVICE_NAME: &str = "p2p_handshake_server";
+
+    /// 服务主体：收到停止请求时 `oneshot::Receiver` 完成
+    type ServiceBody = Box<dyn Fn(oneshot::Receiver<()>) -> Result<()> + Send + Sync>;
+
+    static SERVICE_BODY: OnceLock<ServiceBody> = OnceLock::new();
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    /// 作为 Windows 服务运行（阻塞直到服务停止）。必须由 SCM 启动。
+    pub fn run<F>(body: F) -> Result<()>
+    where
+        F: Fn(oneshot::Receiver<()>) -> Result<()> + Send + Sync + 'static,
+    {
+        SERVICE_BODY
+            .set(Box::new(body))
+            .map_err(|_| anyhow::anyhow!("Windows服务已在运行"))?;
+        service_dispatcher::start(SERVICE_NAME, ffi_service_main)?;
+        Ok(())
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        if let Err(e) = run_service() {
+            error!("{}", tr!("Windows服务运行失败: {}", "Windows service failed: {}", e));
+        }
+    }
+
+    fn status(state: ServiceState, controls_accepted: ServiceControlAccept) -> ServiceStatus {
+        ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted,
+            exit_code: ServiceExitCode::Win32(0),
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        }
+    }
+
+    fn run_service() -> Result<()> {
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let stop_tx = Mutex::new(Some(stop_tx));
+
+        let status_handle = service_control_handler::register(SERVICE_NAME, move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown | ServiceControl::Preshutdown => {
+                if let Some(tx) = stop_tx.lock().ok().and_then(|mut tx| tx.take()) {
+                    let _ = tx.send(());
+                }
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        })?;
+
+        status_handle.set_service_status(status(
+            ServiceState::Running,
+            ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        ))?;
+
+        let body = SERVICE_BODY.get().ok_or_else(|| anyhow::anyhow!("未设置服务主体"))?;
+        let result = body(stop_rx);
+
+        status_handle.set_service_status(status(ServiceState::Stopped, ServiceControlAccept::empty()))?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_notify_writes_datagram() {
+        use std::os::unix::net::UnixDatagram;
+
+        let path = std::env::temp_dir().join(format!("p2p_notify_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = UnixDatagram::bind(&path).unwrap();
+
+        let notifier = SystemdNotifier {
+            socket_path: path.to_string_lossy().into_owned(),
+            watchdog_interval: None,
+        };
+        notifier.ready("监听 127.0.0.1:8080");
+
+        let mut buf = [0u8; 256];
+        let n = receiver.recv(&mut buf).unwrap();
+        let text = String::from_utf8_lossy(&buf[..n]);
+        assert!(text.starts_with("READY=1\n"));
+        assert!(text.contains("STATUS=监听 127.0.0.1:8080"));
+        let _ = std::fs::remove_file(&path);
+    }
+}
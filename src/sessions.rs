@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::protocol::P2PPath;
+
+/// 节点之间经服务器协调建立的 P2P 会话
+///
+/// 客户端以 `P2PConnectResult` 上报直连成功后记录，上报失败或任一方下线时移除。节点地址变化时
+/// 据此向会话另一方推送 `AddressUpdate`，双方无需重新发现即可恢复直连。
+#[derive(Debug, Default)]
+pub struct P2PSessions {
+    sessions: Mutex<HashMap<Uuid, HashMap<Uuid, P2PPath>>>,
+}
+
+impl P2PSessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一次直连结果：成功时建立（或更新）会话，失败时移除
+    pub fn record(&self, a: Uuid, b: Uuid, path: P2PPath) {
+        if a == b {
+            return;
+        }
+        if path == P2PPath::Failed {
+            self.close(a, b);
+            return;
+        }
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.entry(a).or_default().insert(b, path);
+        sessions.entry(b).or_default().insert(a, path);
+    }
+
+    /// 移除一对节点之间的会话
+    pub fn close(&self, a: Uuid, b: Uuid) {
+        let mut sessions = self.sessions.lock().unwrap();
+        for (from, to) in [(a, b), (b, a)] {
+            if let Some(partners) = sessions.get_mut(&from) {
+                partners.remove(&to);
+                if partners.is_empty() {
+                    sessions.remove(&from);
+                }
+            }
+        }
+    }
+
+    /// 与节点保持会话的节点
+    pub fn partners(&self, peer_id: &Uuid) -> Vec<Uuid> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(peer_id)
+            .map(|partners| partners.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// 两个节点之间会话当前使用的路径
+    pub fn path(&self, a: &Uuid, b: &Uuid) -> Option<P2PPath> {
+        self.sessions.lock().unwrap().get(a)?.get(b).copied()
+    }
+
+    /// 全部会话（每对节点只列出一次）
+    pub fn pairs(&self) -> Vec<(Uuid, Uuid, P2PPath)> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .iter()
+            .flat_map(|(a, partners)| partners.iter().filter(move |(b, _)| a < *b).map(move |(b, path)| (*a, *b, *path)))
+            .collect()
+    }
+
+    /// 移除节点的全部会话，返回其会话对象
+    pub fn remove(&self, peer_id: &Uuid) -> Vec<Uuid> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(partners) = sessions.remove(peer_id) else { return Vec::new() };
+        for other in partners.keys() {
+            if let Some(other_partners) = sessions.get_mut(other) {
+                other_partners.remove(peer_id);
+                if other_partners.is_empty() {
+                    sessions.remove(other);
+                }
+            }
+        }
+        partners.into_keys().collect()
+    }
+
+    /// 当前会话数（每对节点计一次）
+    pub fn count(&self) -> usize {
+        self.sessions.lock().unwrap().values().map(HashMap::len).sum::<usize>() / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_open_close_and_remove() {
+        let sessions = P2PSessions::new();
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        sessions.record(a, b, P2PPath::Public);
+        sessions.record(c, a, P2PPath::Private);
+        sessions.record(b, b, P2PPath::Public);
+        assert_eq!(sessions.count(), 2);
+        assert_eq!(sessions.path(&b, &a), Some(P2PPath::Public));
+        assert_eq!(sessions.pairs().len(), 2);
+
+        // 失败的结果结束会话
+        sessions.record(b, a, P2PPath::Failed);
+        assert_eq!(sessions.partners(&a), vec![c]);
+        assert!(sessions.partners(&b).is_empty());
+
+        assert_eq!(sessions.remove(&c), vec![a]);
+        assert_eq!(sessions.count(), 0);
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use log::info;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, UdpSocket};
+
+use crate::config::NetworkConfig;
+use crate::tr;
+
+/// 按 `options` 创建并绑定UDP套接字
+///
+/// `dscp` 为该套接字使用的DSCP标记（控制流量或中继流量）；指定 `label` 时记录实际生效的参数。
+pub fn bind_udp(addr: SocketAddr, options: &NetworkConfig, dscp: Option<u8>, label: Option<&str>) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))
+        .context("创建UDP套接字失败")?;
+    apply(&socket, addr, options, dscp)?;
+    socket.bind(&addr.into()).context(format!("绑定UDP地址 {} 失败", addr))?;
+    socket.set_nonblocking(true)?;
+    if let Some(label) = label {
+        log_applied(&socket, options, label);
+    }
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+/// 按 `options` 创建并监听TCP端口
+pub fn bind_tcp(addr: SocketAddr, options: &NetworkConfig, dscp: Option<u8>, label: Option<&str>) -> Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))
+        .context("创建TCP套接字失败")?;
+    // 与 TcpListener::bind 一致，便于重启后立即复用端口
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    apply(&socket, addr, options, dscp)?;
+    socket.bind(&addr.into()).context(format!("绑定TCP地址 {} 失败", addr))?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    if let Some(label) = label {
+        log_applied(&socket, options, label);
+    }
+    Ok(TcpListener::from_std(socket.into())?)
+}
+
+fn apply(socket: &Socket, addr: SocketAddr, options: &NetworkConfig, dscp: Option<u8>) -> Result<()> {
+    if let Some(interface) = &options.interface {
+        bind_device(socket, interface)?;
+    }
+    if let Some(only_v6) = options.ipv6_only
+        && addr.is_ipv6()
+    {
+        socket.set_only_v6(only_v6).context("设置IPV6_V6ONLY失败")?;
+    }
+    if let Some(size) = options.recv_buffer_size {
+        socket.set_recv_buffer_size(size).context("设置SO_RCVBUF失败")?;
+    }
+    if let Some(size) = options.send_buffer_size {
+        socket.set_send_buffer_size(size).context("设置SO_SNDBUF失败")?;
+    }
+    if let Some(dscp) = dscp {
+        if dscp > 63 {
+            anyhow::bail!("DSCP取值范围为0-63，当前为 {}", dscp);
+        }
+        // DSCP占TOS/Traffic Class字节的高6位
+        let tos = u32::from(dscp) << 2;
+        if addr.is_ipv4() {
+            socket.set_tos_v4(tos).context("设置IP_TOS失败")?;
+        } else {
+            #[cfg(unix)]
+            socket.set_tclass_v6(tos).context("设置IPV6_TCLASS失败")?;
+        }
+    }
+    if let Some(ttl) = options.ttl {
+        if addr.is_ipv4() {
+            socket.set_ttl_v4(ttl).context("设置IP_TTL失败")?;
+        } else {
+            socket.set_unicast_hops_v6(ttl).context("设置IPV6_UNICAST_HOPS失败")?;
+        }
+    }
+    Ok(())
+}
+
+/// 记录实际生效的取值（内核可能调整缓冲区大小）
+fn log_applied(socket: &Socket, options: &NetworkConfig, label: &str) {
+    if *options == NetworkConfig::default() {
+        return;
+    }
+    let ipv4 = socket.local_addr().ok().and_then(|a| a.as_socket()).is_some_and(|a| a.is_ipv4());
+    let tos = if ipv4 { socket.tos_v4().ok() } else { tclass_v6(socket) };
+    let ttl = if ipv4 { socket.ttl_v4().ok() } else { socket.unicast_hops_v6().ok() };
+    info!(
+        "{}",
+        tr!(
+            "{}套接字参数: SO_RCVBUF={:?} SO_SNDBUF={:?} DSCP={:?} TTL={:?} IPV6_V6ONLY={:?}",
+            "{} socket options: SO_RCVBUF={:?} SO_SNDBUF={:?} DSCP={:?} TTL={:?} IPV6_V6ONLY={:?}",
+            label,
+            socket.recv_buffer_size().ok(),
+            socket.send_buffer_size().ok(),
+            tos.map(|t| t >> 2),
+            ttl,
+            if ipv4 { None } else { socket.only_v6().ok() },
+        )
+    );
+    if let Some(interface) = &options.interface {
+        info!("{}", tr!("{}套接字已绑定到接口 {}", "{} socket bound to interface {}", label, interface));
+    }
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_device(socket: &Socket, interface: &str) -> Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+        .context(format!("绑定网络接口 {} 失败", interface))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_device(_socket: &Socket, interface: &str) -> Result<()> {
+    anyhow::bail!("当前平台不支持绑定网络接口 {}，请改用接口的IP地址作为监听地址", interface)
+}
+
+#[cfg(unix)]
+fn tclass_v6(socket: &Socket) -> Option<u32> {
+    socket.tclass_v6().ok()
+}
+
+#[cfg(not(unix))]
+fn tclass_v6(_socket: &Socket) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_udp_applies_options() {
+        let options = NetworkConfig {
+            recv_buffer_size: Some(64 * 1024),
+            ttl: Some(17),
+            control_dscp: Some(46),
+            ..NetworkConfig::default()
+        };
+        let socket = bind_udp("127.0.0.1:0".parse().unwrap(), &options, options.control_dscp, None).unwrap();
+        let socket = socket2::SockRef::from(&socket);
+        assert_eq!(socket.ttl_v4().unwrap(), 17);
+        assert_eq!(socket.tos_v4().unwrap() >> 2, 46);
+        // Linux会把设置值翻倍，只检查不小于请求值
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+    }
+}
@@ -0,0 +1,238 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use uuid::Uuid;
+
+use crate::config::SpoofGuardConfig;
+use crate::timesync::unix_millis;
+
+/// 对一次握手的处理结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpoofVerdict {
+    /// 来源与该节点ID近期的握手来源一致，照常处理
+    Allow,
+    /// 本次握手使该节点ID的来源分布超出阈值，从现在起限制 `block` 时长
+    Flagged { distinct_ips: usize, port_entropy_bits: f64, block: Duration },
+    /// 该节点ID仍处于限制期，还剩 `remaining`
+    Limited { remaining: Duration },
+}
+
+/// 审计记录中的处理动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpoofAction {
+    /// 节点ID被判定为遭冒用，开始限制
+    Flagged,
+    /// 限制期内的握手被拒绝
+    Rejected,
+}
+
+/// 一条冒用审计记录
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpoofAuditEntry {
+    /// UNIX时间戳（毫秒）
+    pub timestamp_ms: u64,
+    pub node_id: Uuid,
+    /// 触发记录的握手来源
+    pub addr: SocketAddr,
+    pub action: SpoofAction,
+    /// 统计窗口内声称该节点ID的不同来源IP数
+    pub distinct_ips: usize,
+    /// 统计窗口内来源端口分布的香农熵（比特）
+    pub port_entropy_bits: f64,
+}
+
+/// 正受限制的节点ID（供管理接口展示）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlaggedIdentity {
+    pub node_id: Uuid,
+    pub distinct_ips: usize,
+    pub port_entropy_bits: f64,
+    pub remaining_secs: u64,
+}
+
+#[derive(Debug)]
+struct ClaimRecord {
+    /// 统计窗口内的握手来源
+    sources: VecDeque<(Instant, SocketAddr)>,
+    /// 判定时的来源IP数与端口熵
+    flagged: Option<(usize, f64)>,
+    blocked_until: Option<Instant>,
+    last_seen: Instant,
+}
+
+impl ClaimRecord {
+    fn distinct_ips(&self) -> usize {
+        self.sources.iter().map(|(_, addr)| addr.ip()).collect::<HashSet<IpAddr>>().len()
+    }
+
+    /// 来源端口分布的香农熵：同一 NAT 映射后的节点端口稳定，伪造来源的握手端口近乎随机
+    fn port_entropy_bits(&self) -> f64 {
+        let mut counts: HashMap<u16, usize> = HashMap::new();
+        for (_, addr) in &self.sources {
+            *counts.entry(addr.port()).or_default() += 1;
+        }
+        let total = self.sources.len() as f64;
+        counts.values().map(|&n| n as f64 / total).map(|p| -p * p.log2()).sum()
+    }
+}
+
+/// 按声称的节点ID统计握手来源，识别冒用身份的握手
+///
+/// 正常节点的握手来自少数几个地址，端口也基本不变；同一节点ID在短时间内从大量不同的IP、
+/// 或端口杂乱无章地握手，多半是有人伪造来源冒用该身份。没有有效会话票据的这类握手会被判定、
+/// 限制一段时间并记入审计记录；持有该节点当前会话票据的握手不受影响，也不计入统计。
+#[derive(Debug)]
+pub struct SpoofGuard {
+    config: SpoofGuardConfig,
+    claims: Mutex<HashMap<Uuid, ClaimRecord>>,
+    audit: Mutex<VecDeque<SpoofAuditEntry>>,
+}
+
+impl SpoofGuard {
+    pub fn new(config: SpoofGuardConfig) -> Self {
+        Self { config, claims: Mutex::new(HashMap::new()), audit: Mutex::new(VecDeque::new()) }
+    }
+
+    /// 记录一次声称 `node_id`、没有有效会话票据的握手，返回对它的处理结果
+    pub fn check(&self, node_id: Uuid, addr: SocketAddr, now: Instant) -> SpoofVerdict {
+        let window = Duration::from_secs(self.config.window_secs);
+        let forget = Duration::from_secs(self.config.block_secs).max(window);
+        let mut claims = self.claims.lock().unwrap();
+        claims.retain(|_, r| r.blocked_until.is_some_and(|t| t > now) || now.duration_since(r.last_seen) <= forget);
+        let record = claims.entry(node_id).or_insert_with(|| ClaimRecord {
+            sources: VecDeque::new(),
+            flagged: None,
+            blocked_until: None,
+            last_seen: now,
+        });
+        record.last_seen = now;
+        if let Some(until) = record.blocked_until.filter(|t| *t > now) {
+            let (distinct_ips, port_entropy_bits) = record.flagged.unwrap_or_default();
+            drop(claims);
+            self.audit(node_id, addr, SpoofAction::Rejected, distinct_ips, port_entropy_bits);
+            return SpoofVerdict::Limited { remaining: until.duration_since(now) };
+        }
+
+        record.sources.push_back((now, addr));
+        while record.sources.front().is_some_and(|(t, _)| now.duration_since(*t) > window) {
+            record.sources.pop_front();
+        }
+        let distinct_ips = record.distinct_ips();
+        let port_entropy_bits = record.port_entropy_bits();
+        let too_many_ips = self.config.max_source_ips > 0 && distinct_ips > self.config.max_source_ips;
+        let too_random = self.config.max_port_entropy_bits > 0.0 && port_entropy_bits > self.config.max_port_entropy_bits;
+        if !too_many_ips && !too_random {
+            return SpoofVerdict::Allow;
+        }
+        let block = Duration::from_secs(self.config.block_secs);
+        record.sources.clear();
+        record.flagged = Some((distinct_ips, port_entropy_bits));
+        record.blocked_until = Some(now + block);
+        drop(claims);
+        self.audit(node_id, addr, SpoofAction::Flagged, distinct_ips, port_entropy_bits);
+        SpoofVerdict::Flagged { distinct_ips, port_entropy_bits, block }
+    }
+
+    fn audit(&self, node_id: Uuid, addr: SocketAddr, action: SpoofAction, distinct_ips: usize, port_entropy_bits: f64) {
+        if self.config.audit_entries == 0 {
+            return;
+        }
+        let mut audit = self.audit.lock().unwrap();
+        if audit.len() >= self.config.audit_entries {
+            audit.pop_front();
+        }
+        audit.push_back(SpoofAuditEntry {
+            timestamp_ms: unix_millis(SystemTime::now()),
+            node_id,
+            addr,
+            action,
+            distinct_ips,
+            port_entropy_bits,
+        });
+    }
+
+    /// 当前受限制的节点ID
+    pub fn flagged(&self) -> Vec<FlaggedIdentity> {
+        let now = Instant::now();
+        let mut flagged: Vec<FlaggedIdentity> = self
+            .claims
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(node_id, r)| {
+                let until = r.blocked_until.filter(|t| *t > now)?;
+                let (distinct_ips, port_entropy_bits) = r.flagged.unwrap_or_default();
+                Some(FlaggedIdentity {
+                    node_id: *node_id,
+                    distinct_ips,
+                    port_entropy_bits,
+                    remaining_secs: until.duration_since(now).as_secs(),
+                })
+            })
+            .collect();
+        flagged.sort_by_key(|f| f.node_id);
+        flagged
+    }
+
+    /// 审计记录，按时间先后
+    pub fn audit_log(&self) -> Vec<SpoofAuditEntry> {
+        self.audit.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> SpoofGuard {
+        SpoofGuard::new(SpoofGuardConfig { window_secs: 60, max_source_ips: 2, max_port_entropy_bits: 2.0, block_secs: 30, audit_entries: 4 })
+    }
+
+    fn addr(ip: u8, port: u16) -> SocketAddr {
+        SocketAddr::from(([192, 0, 2, ip], port))
+    }
+
+    #[test]
+    fn test_many_source_ips_flag_identity() {
+        let guard = guard();
+        let id = Uuid::new_v4();
+        let start = Instant::now();
+        // 同一地址反复握手不受影响
+        for _ in 0..10 {
+            assert_eq!(guard.check(id, addr(1, 4000), start), SpoofVerdict::Allow);
+        }
+        assert_eq!(guard.check(id, addr(2, 4000), start), SpoofVerdict::Allow);
+        let SpoofVerdict::Flagged { distinct_ips, block, .. } = guard.check(id, addr(3, 4000), start) else {
+            panic!("第三个来源IP应被判定");
+        };
+        assert_eq!((distinct_ips, block), (3, Duration::from_secs(30)));
+        assert_eq!(guard.check(id, addr(1, 4000), start + Duration::from_secs(10)), SpoofVerdict::Limited { remaining: Duration::from_secs(20) });
+        assert_eq!(guard.check(id, addr(1, 4000), start + Duration::from_secs(31)), SpoofVerdict::Allow);
+        // 其他节点ID不受牵连
+        assert_eq!(guard.check(Uuid::new_v4(), addr(3, 4000), start), SpoofVerdict::Allow);
+
+        let actions: Vec<SpoofAction> = guard.audit_log().iter().map(|entry| entry.action).collect();
+        assert_eq!(actions, vec![SpoofAction::Flagged, SpoofAction::Rejected]);
+    }
+
+    #[test]
+    fn test_random_ports_flag_identity_and_audit_is_bounded() {
+        let guard = guard();
+        let id = Uuid::new_v4();
+        let start = Instant::now();
+        // 四个均匀分布的端口熵为 2 比特，第五个超出阈值
+        for port in 0..4 {
+            assert_eq!(guard.check(id, addr(1, 5000 + port), start), SpoofVerdict::Allow);
+        }
+        assert!(matches!(guard.check(id, addr(1, 6000), start), SpoofVerdict::Flagged { distinct_ips: 1, .. }));
+        assert_eq!(guard.flagged().iter().map(|f| f.node_id).collect::<Vec<_>>(), vec![id]);
+        for _ in 0..5 {
+            guard.check(id, addr(1, 7000), start);
+        }
+        assert_eq!(guard.audit_log().len(), 4);
+        assert!(guard.audit_log().iter().all(|entry| entry.action == SpoofAction::Rejected));
+    }
+}
@@ -0,0 +1,579 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use anyhow::Result;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf, ReadHalf, WriteHalf};
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep_until, Instant};
+use uuid::Uuid;
+
+use crate::link_quality::{RttEstimator, MAX_RTO, MIN_RTO};
+use crate::protocol::{Message, StreamFrame, StreamFrameKind};
+use crate::identity::RouteSigner;
+use crate::router::{RouteSignature, RoutedMessage};
+use crate::server_pool::ServerPool;
+use crate::tr;
+
+/// 可靠字节流的参数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamConfig {
+    /// 每个数据帧携带的最大字节数
+    pub chunk_size: usize,
+    /// 接收窗口（帧数）：尚未交给应用的帧最多缓存这么多，发送方未确认的帧也不超过对方通告的窗口
+    pub window: u32,
+    /// 尚未测得往返时延时未确认帧的重传间隔（毫秒）；测得后按平滑往返时延自适应
+    pub retransmit_ms: u64,
+    /// 自适应重传间隔的下限（毫秒）
+    pub min_retransmit_ms: u64,
+    /// 自适应重传间隔的上限（毫秒）
+    pub max_retransmit_ms: u64,
+    /// 同一帧重传超过该次数即中止流
+    pub max_retransmits: u32,
+    /// 应用读写缓冲区大小（字节）
+    pub buffer_size: usize,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 1024,
+            window: 32,
+            retransmit_ms: 300,
+            min_retransmit_ms: MIN_RTO.as_millis() as u64,
+            max_retransmit_ms: MAX_RTO.as_millis() as u64,
+            max_retransmits: 10,
+            buffer_size: 64 * 1024,
+        }
+    }
+}
+
+/// 路由转发时服务器可能改写的字段（跳数等）预留的余量
+const ROUTE_SLACK: usize = 32;
+
+/// 按路径 MTU 收紧每帧的数据量，使编码后的路由消息不超过 `mtu`
+///
+/// 帧数据以 JSON 数字数组编码，每字节最多占 4 个字符（`255,`）；帧头部开销按各字段取最大值的空帧测得，`signed` 时计入签名。
+pub(crate) fn fit_to_mtu(config: &StreamConfig, mtu: usize, signed: bool) -> StreamConfig {
+    let frame = StreamFrame { stream_id: Uuid::nil(), kind: StreamFrameKind::Data, seq: u64::MAX, data: Vec::new(), window: u32::MAX, sack: u64::MAX };
+    let signature = signed.then(|| RouteSignature { public_key: "0".repeat(64), signature: "0".repeat(128), multicast: Vec::new() });
+    let overhead = Message::stream_frame(&frame)
+        .and_then(|message| RoutedMessage { signature, ..RoutedMessage::new(message, Uuid::nil(), Uuid::nil(), u32::MAX) }.to_message())
+        .ok()
+        .and_then(|message| serde_json::to_vec(&message).ok())
+        .map_or(mtu, |bytes| bytes.len() + ROUTE_SLACK);
+    let chunk_size = config.chunk_size.min(mtu.saturating_sub(overhead) / 4).max(1);
+    StreamConfig { chunk_size, ..config.clone() }
+}
+
+/// 经服务器把流的帧作为路由消息发给对方
+#[derive(Clone)]
+pub(crate) struct FrameSender {
+    pub(crate) socket: Arc<UdpSocket>,
+    pub(crate) servers: Arc<ServerPool>,
+    pub(crate) node_id: Uuid,
+    pub(crate) max_hops: u32,
+    pub(crate) signer: RouteSigner,
+}
+
+impl FrameSender {
+    async fn send(&self, peer_id: Uuid, frame: &StreamFrame) {
+        let result = async {
+            let mut routed = RoutedMessage::new(Message::stream_frame(frame)?, self.node_id, peer_id, self.max_hops);
+            self.signer.sign(&mut routed);
+            self.socket.send_to(&serde_json::to_vec(&routed.to_message()?)?, self.servers.active()).await?;
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("{}", tr!("发送流 {} 的帧失败: {}", "Failed to send frame of stream {}: {}", frame.stream_id, e));
+        }
+    }
+}
+
+/// 按（对方节点ID，流ID）索引的流驱动任务
+pub(crate) type StreamRegistry = Arc<Mutex<HashMap<(Uuid, Uuid), mpsc::UnboundedSender<StreamFrame>>>>;
+
+/// 把收到的帧交给对应的流；对方新打开的流经 `incoming` 交给应用
+pub(crate) async fn dispatch(
+    sender: &FrameSender,
+    registry: &StreamRegistry,
+    config: &StreamConfig,
+    incoming: &mpsc::UnboundedSender<P2PStream>,
+    peer_id: Uuid,
+    frame: StreamFrame,
+) {
+    let key = (peer_id, frame.stream_id);
+    let existing = registry.lock().unwrap().get(&key).cloned();
+    if let Some(driver) = existing {
+        let _ = driver.send(frame);
+        return;
+    }
+    match frame.kind {
+        StreamFrameKind::Open => {
+            info!("{}", tr!("节点 {} 打开流 {}", "Node {} opened stream {}", peer_id, frame.stream_id));
+            let stream = P2PStream::start(sender.clone(), registry.clone(), config.clone(), peer_id, frame.stream_id, None);
+            if let Some(driver) = registry.lock().unwrap().get(&key) {
+                let _ = driver.send(frame);
+            }
+            let _ = incoming.send(stream);
+        }
+        StreamFrameKind::Data | StreamFrameKind::Fin => {
+            debug!("{}", tr!("收到未知流 {} 的帧，要求对方中止", "Received frame for unknown stream {}, asking the peer to abort", frame.stream_id));
+            sender.send(peer_id, &StreamFrame { kind: StreamFrameKind::Reset, ..frame }).await;
+        }
+        StreamFrameKind::Ack | StreamFrameKind::Reset => {}
+    }
+}
+
+/// 与一个节点之间的可靠字节流
+///
+/// 数据被切分为带序号的帧，作为路由 `Data` 消息经服务器转发；接收方按序重组并累计确认，
+/// 发送方超时重传未确认的帧，未确认的帧数不超过接收方通告的窗口。流实现
+/// [`AsyncRead`] / [`AsyncWrite`]，`shutdown` 后对方读到 EOF；对方中止或重传耗尽时读到 EOF、写入失败。
+pub struct P2PStream {
+    peer_id: Uuid,
+    stream_id: Uuid,
+    io: DuplexStream,
+}
+
+impl P2PStream {
+    /// 打开到 `peer_id` 的流，对方确认后返回
+    pub(crate) async fn open(sender: FrameSender, registry: StreamRegistry, config: StreamConfig, peer_id: Uuid) -> Result<Self> {
+        let (opened_tx, opened) = oneshot::channel();
+        let stream = Self::start(sender, registry, config, peer_id, Uuid::new_v4(), Some(opened_tx));
+        if !opened.await.unwrap_or(false) {
+            anyhow::bail!("节点 {} 未确认打开流", peer_id);
+        }
+        Ok(stream)
+    }
+
+    fn start(
+        sender: FrameSender,
+        registry: StreamRegistry,
+        config: StreamConfig,
+        peer_id: Uuid,
+        stream_id: Uuid,
+        opened: Option<oneshot::Sender<bool>>,
+    ) -> Self {
+        let (io, driver_io) = tokio::io::duplex(config.buffer_size.max(1));
+        let (frames_tx, frames) = mpsc::unbounded_channel();
+        registry.lock().unwrap().insert((peer_id, stream_id), frames_tx);
+        let driver = Driver {
+            sender,
+            registry,
+            peer_id,
+            stream_id,
+            reassembly: Reassembly::new(config.window),
+            config,
+            next_seq: 0,
+            unacked: BTreeMap::new(),
+            send_limit: 0,
+            fin_queued: false,
+            opening: None,
+            opened,
+            last_probe: Instant::now(),
+            ready: Vec::new(),
+            remote_fin: false,
+            app_closed: false,
+            advertised: 0,
+            rtt: RttEstimator::default(),
+        };
+        let (app_read, app_write) = tokio::io::split(driver_io);
+        tokio::spawn(driver.run(app_read, app_write, frames));
+        Self { peer_id, stream_id, io }
+    }
+
+    /// 对方节点ID
+    pub fn peer_id(&self) -> Uuid {
+        self.peer_id
+    }
+
+    /// 流ID
+    pub fn stream_id(&self) -> Uuid {
+        self.stream_id
+    }
+}
+
+impl AsyncRead for P2PStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for P2PStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+/// 接收方的按序重组
+#[derive(Debug)]
+struct Reassembly {
+    /// 下一个应交付的序号
+    expected: u64,
+    /// 提前到达的帧
+    pending: BTreeMap<u64, StreamFrame>,
+    window: u32,
+}
+
+impl Reassembly {
+    fn new(window: u32) -> Self {
+        Self { expected: 0, pending: BTreeMap::new(), window: window.max(1) }
+    }
+
+    /// 接收数据或结束帧，返回可按序交付的帧；重复或超出窗口的帧被忽略
+    fn accept(&mut self, frame: StreamFrame) -> Vec<StreamFrame> {
+        if frame.seq < self.expected || frame.seq >= self.expected + self.window as u64 {
+            return Vec::new();
+        }
+        self.pending.insert(frame.seq, frame);
+        let mut ready = Vec::new();
+        while let Some(frame) = self.pending.remove(&self.expected) {
+            self.expected += 1;
+            let fin = frame.kind == StreamFrameKind::Fin;
+            ready.push(frame);
+            if fin {
+                self.pending.clear();
+                break;
+            }
+        }
+        ready
+    }
+
+    /// 已先行收到的帧的选择性确认位图：第 i 位对应序号 `expected + 1 + i`
+    fn sack(&self) -> u64 {
+        self.pending
+            .range(self.expected + 1..self.expected + 65)
+            .fold(0, |bits, (seq, _)| bits | 1 << (seq - self.expected - 1))
+    }
+
+    /// 还能缓存的帧数（扣除已重组、尚未交给应用的 `buffered` 帧）
+    fn free(&self, buffered: usize) -> u32 {
+        (self.window as usize).saturating_sub(self.pending.len() + buffered) as u32
+    }
+}
+
+/// `seq` 是否被确认帧的选择性确认位图标为已收到
+fn sacked(ack: &StreamFrame, seq: u64) -> bool {
+    seq > ack.seq && seq - ack.seq <= 64 && ack.sack & (1 << (seq - ack.seq - 1)) != 0
+}
+
+/// 已发出、尚未确认的帧
+struct Outgoing {
+    frame: StreamFrame,
+    sent_at: Instant,
+    retries: u32,
+}
+
+enum Event {
+    Read(usize),
+    Frame(Option<StreamFrame>),
+    Written(Option<usize>),
+    Timer,
+}
+
+/// 流的驱动任务：切分应用写入的数据、重组对方的帧、确认与重传
+struct Driver {
+    sender: FrameSender,
+    registry: StreamRegistry,
+    peer_id: Uuid,
+    stream_id: Uuid,
+    config: StreamConfig,
+    next_seq: u64,
+    unacked: BTreeMap<u64, Outgoing>,
+    /// 对方允许发送的序号上限（不含）
+    send_limit: u64,
+    fin_queued: bool,
+    /// 尚未确认的 `Open`
+    opening: Option<Outgoing>,
+    opened: Option<oneshot::Sender<bool>>,
+    /// 窗口耗尽时上次探测的时间
+    last_probe: Instant,
+    reassembly: Reassembly,
+    /// 已按序重组、尚未写给应用的数据
+    ready: Vec<u8>,
+    remote_fin: bool,
+    /// 应用已不再读取
+    app_closed: bool,
+    /// 最近一次确认中通告的窗口
+    advertised: u32,
+    /// 按首次发送即被确认的帧估计的往返时延，决定重传间隔
+    rtt: RttEstimator,
+}
+
+impl Driver {
+    async fn run(
+        mut self,
+        mut app_read: ReadHalf<DuplexStream>,
+        mut app_write: WriteHalf<DuplexStream>,
+        mut frames: mpsc::UnboundedReceiver<StreamFrame>,
+    ) {
+        if self.opened.is_some() {
+            let frame = self.frame(StreamFrameKind::Open, 0, Vec::new());
+            self.sender.send(self.peer_id, &frame).await;
+            self.opening = Some(Outgoing { frame, sent_at: Instant::now(), retries: 0 });
+        }
+        let mut buf = vec![0u8; self.config.chunk_size.max(1)];
+        let mut write_closed = false;
+        while !self.finished() {
+            let can_read = self.opening.is_none() && !self.fin_queued && self.next_seq < self.send_limit;
+            let deadline = self.next_deadline();
+            let event = tokio::select! {
+                read = app_read.read(&mut buf), if can_read => Event::Read(read.unwrap_or(0)),
+                frame = frames.recv() => Event::Frame(frame),
+                written = app_write.write(&self.ready), if !self.ready.is_empty() => Event::Written(written.ok()),
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => Event::Timer,
+            };
+            match event {
+                Event::Read(0) => self.queue(StreamFrameKind::Fin, Vec::new()).await,
+                Event::Read(n) => self.queue(StreamFrameKind::Data, buf[..n].to_vec()).await,
+                Event::Frame(Some(frame)) => {
+                    if !self.on_frame(frame).await {
+                        break;
+                    }
+                }
+                Event::Frame(None) => break,
+                Event::Written(Some(n)) => {
+                    self.ready.drain(..n);
+                    // 窗口曾耗尽，腾出空间后通知对方继续发送
+                    if self.advertised == 0 {
+                        self.send_ack().await;
+                    }
+                }
+                Event::Written(None) => {
+                    self.app_closed = true;
+                    self.ready.clear();
+                }
+                Event::Timer => {
+                    if !self.retransmit().await {
+                        warn!("{}", tr!("流 {} 重传次数耗尽，中止", "Stream {} exhausted its retransmissions, aborting", self.stream_id));
+                        let reset = self.frame(StreamFrameKind::Reset, 0, Vec::new());
+                        self.sender.send(self.peer_id, &reset).await;
+                        break;
+                    }
+                }
+            }
+            if self.remote_fin && self.ready.is_empty() && !write_closed {
+                let _ = app_write.shutdown().await;
+                write_closed = true;
+            }
+        }
+        if let Some(opened) = self.opened.take() {
+            let _ = opened.send(false);
+        }
+        if self.finished() {
+            self.linger(&mut frames).await;
+        }
+        let _ = app_write.shutdown().await;
+        self.registry.lock().unwrap().remove(&(self.peer_id, self.stream_id));
+        debug!("{}", tr!("流 {} 已结束", "Stream {} finished", self.stream_id));
+    }
+
+    /// 双方的数据都已送达
+    fn finished(&self) -> bool {
+        self.fin_queued && self.unacked.is_empty() && self.remote_fin && self.ready.is_empty()
+    }
+
+    /// 结束后短暂保留：最后的确认丢失时对方会重传，仍需应答
+    async fn linger(&mut self, frames: &mut mpsc::UnboundedReceiver<StreamFrame>) {
+        let until = Instant::now() + self.rto() * 2;
+        while let Ok(Some(frame)) = tokio::time::timeout_at(until, frames.recv()).await {
+            if matches!(frame.kind, StreamFrameKind::Data | StreamFrameKind::Fin | StreamFrameKind::Open) {
+                self.send_ack().await;
+            }
+        }
+    }
+
+    fn rto(&self) -> Duration {
+        self.rtt.rto(
+            Duration::from_millis(self.config.retransmit_ms.max(1)),
+            Duration::from_millis(self.config.min_retransmit_ms.max(1)),
+            Duration::from_millis(self.config.max_retransmit_ms),
+        )
+    }
+
+    /// 帧的重传超时：每重传一次翻倍（RFC 6298 第 5.5 节），不超过上限
+    fn backoff(config: &StreamConfig, rto: Duration, retries: u32) -> Duration {
+        let max = Duration::from_millis(config.max_retransmit_ms).max(rto);
+        rto.saturating_mul(1 << retries.min(16)).min(max)
+    }
+
+    fn frame(&self, kind: StreamFrameKind, seq: u64, data: Vec<u8>) -> StreamFrame {
+        let buffered = self.ready.len().div_ceil(self.config.chunk_size.max(1));
+        StreamFrame { stream_id: self.stream_id, kind, seq, data, window: self.reassembly.free(buffered), sack: 0 }
+    }
+
+    async fn queue(&mut self, kind: StreamFrameKind, data: Vec<u8>) {
+        let frame = self.frame(kind, self.next_seq, data);
+        self.next_seq += 1;
+        self.fin_queued |= kind == StreamFrameKind::Fin;
+        self.sender.send(self.peer_id, &frame).await;
+        self.unacked.insert(frame.seq, Outgoing { frame, sent_at: Instant::now(), retries: 0 });
+    }
+
+    async fn send_ack(&mut self) {
+        let ack = StreamFrame { sack: self.reassembly.sack(), ..self.frame(StreamFrameKind::Ack, self.reassembly.expected, Vec::new()) };
+        self.advertised = ack.window;
+        self.sender.send(self.peer_id, &ack).await;
+    }
+
+    /// 处理对方的帧，对方中止时返回 `false`
+    async fn on_frame(&mut self, frame: StreamFrame) -> bool {
+        match frame.kind {
+            StreamFrameKind::Open => {
+                // 首个 Open 携带对方的初始窗口；之后的 Open 是窗口耗尽时的探测，只需应答
+                if self.send_limit == 0 && self.next_seq == 0 {
+                    self.send_limit = frame.window as u64;
+                }
+                self.send_ack().await;
+            }
+            StreamFrameKind::Ack => {
+                // 只用未重传过的帧测量往返时延（Karn 算法），重传帧无法确定确认对应哪一次发送
+                let now = Instant::now();
+                let newest = self.unacked.range(..frame.seq).next_back().map(|(_, outgoing)| outgoing);
+                if let Some(outgoing) = self.opening.iter().chain(newest).find(|outgoing| outgoing.retries == 0) {
+                    self.rtt.sample(now - outgoing.sent_at);
+                }
+                if self.opening.take().is_some()
+                    && let Some(opened) = self.opened.take()
+                {
+                    let _ = opened.send(true);
+                }
+                // 累计确认之后，位图标出的帧也已送达，超时只重传仍缺失的帧
+                self.unacked.retain(|seq, _| *seq >= frame.seq && !sacked(&frame, *seq));
+                self.send_limit = self.send_limit.max(frame.seq + frame.window as u64);
+            }
+            StreamFrameKind::Data | StreamFrameKind::Fin => {
+                for frame in self.reassembly.accept(frame) {
+                    if frame.kind == StreamFrameKind::Fin {
+                        self.remote_fin = true;
+                    } else if !self.app_closed {
+                        self.ready.extend_from_slice(&frame.data);
+                    }
+                }
+                self.send_ack().await;
+            }
+            StreamFrameKind::Reset => {
+                info!("{}", tr!("节点 {} 中止了流 {}", "Node {} aborted stream {}", self.peer_id, self.stream_id));
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 下一次重传或窗口探测的时间
+    fn next_deadline(&self) -> Option<Instant> {
+        let rto = self.rto();
+        let oldest = self.opening.iter().chain(self.unacked.values()).map(|o| o.sent_at + Self::backoff(&self.config, rto, o.retries)).min();
+        let blocked = self.opening.is_none() && !self.fin_queued && self.unacked.is_empty() && self.next_seq >= self.send_limit;
+        match (oldest, blocked) {
+            (Some(at), _) => Some(at),
+            (None, true) => Some(self.last_probe + rto),
+            (None, false) => None,
+        }
+    }
+
+    /// 重传超时未确认的帧；窗口耗尽且无待确认帧时发送探测。某帧重传次数耗尽时返回 `false`
+    async fn retransmit(&mut self) -> bool {
+        let now = Instant::now();
+        let rto = self.rto();
+        let mut due = Vec::new();
+        for outgoing in self.opening.iter_mut().chain(self.unacked.values_mut()) {
+            if outgoing.sent_at + Self::backoff(&self.config, rto, outgoing.retries) > now {
+                continue;
+            }
+            outgoing.retries += 1;
+            if outgoing.retries > self.config.max_retransmits {
+                return false;
+            }
+            outgoing.sent_at = now;
+            due.push(outgoing.frame.clone());
+        }
+        if due.is_empty() && self.opening.is_none() && self.unacked.is_empty() && self.last_probe + rto <= now {
+            self.last_probe = now;
+            due.push(self.frame(StreamFrameKind::Open, 0, Vec::new()));
+        }
+        for frame in due {
+            self.sender.send(self.peer_id, &frame).await;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(kind: StreamFrameKind, seq: u64) -> StreamFrame {
+        StreamFrame { stream_id: Uuid::nil(), kind, seq, data: vec![seq as u8], window: 0, sack: 0 }
+    }
+
+    #[test]
+    fn test_fit_to_mtu_bounds_encoded_frame() {
+        let config = StreamConfig::default();
+        let fitted = fit_to_mtu(&config, 1200, false);
+        assert!(fitted.chunk_size < config.chunk_size);
+        let frame = StreamFrame { data: vec![255; fitted.chunk_size], window: u32::MAX, ..frame(StreamFrameKind::Data, u64::MAX) };
+        let routed = RoutedMessage::new(Message::stream_frame(&frame).unwrap(), Uuid::new_v4(), Uuid::new_v4(), 8);
+        assert!(serde_json::to_vec(&routed.to_message().unwrap()).unwrap().len() <= 1200);
+        // 签名的帧同样不超过路径 MTU
+        let signed = fit_to_mtu(&config, 1200, true);
+        let frame = StreamFrame { data: vec![255; signed.chunk_size], ..frame };
+        let mut routed = RoutedMessage::new(Message::stream_frame(&frame).unwrap(), Uuid::new_v4(), Uuid::new_v4(), 8);
+        crate::identity::NodeIdentity::generate(routed.source_node).sign_routed(&mut routed, "test");
+        assert!(serde_json::to_vec(&routed.to_message().unwrap()).unwrap().len() <= 1200);
+        // 路径足够大时不改变配置
+        assert_eq!(fit_to_mtu(&config, 65_000, false), config);
+    }
+
+    #[test]
+    fn test_reassembly_orders_and_drops_duplicates() {
+        let mut reassembly = Reassembly::new(4);
+        // 提前到达的帧先缓存
+        assert!(reassembly.accept(frame(StreamFrameKind::Data, 1)).is_empty());
+        assert!(reassembly.accept(frame(StreamFrameKind::Data, 2)).is_empty());
+        assert_eq!(reassembly.free(0), 2);
+        assert_eq!(reassembly.sack(), 0b11);
+        // 超出窗口的帧被丢弃
+        assert!(reassembly.accept(frame(StreamFrameKind::Data, 4)).is_empty());
+
+        let seqs: Vec<u64> = reassembly.accept(frame(StreamFrameKind::Data, 0)).iter().map(|f| f.seq).collect();
+        assert_eq!(seqs, vec![0, 1, 2]);
+        assert_eq!(reassembly.expected, 3);
+        // 重复帧不再交付
+        assert!(reassembly.accept(frame(StreamFrameKind::Data, 1)).is_empty());
+
+        let ready = reassembly.accept(frame(StreamFrameKind::Fin, 3));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].kind, StreamFrameKind::Fin);
+        assert_eq!(reassembly.free(1), 3);
+    }
+
+    #[test]
+    fn test_sack_bitmap_marks_frames_after_the_gap() {
+        let mut reassembly = Reassembly::new(80);
+        for seq in [2, 4, 70] {
+            reassembly.accept(frame(StreamFrameKind::Data, seq));
+        }
+        // 序号 1 与 3 缺失；超出 64 位的帧不在位图内
+        let ack = StreamFrame { sack: reassembly.sack(), ..frame(StreamFrameKind::Ack, reassembly.expected) };
+        assert_eq!(ack.sack, 0b1010);
+        let sacked: Vec<u64> = (0..80).filter(|seq| sacked(&ack, *seq)).collect();
+        assert_eq!(sacked, vec![2, 4]);
+    }
+}
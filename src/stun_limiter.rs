@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::stun_server::StunRateLimitConfig;
+
+/// 限流判定结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// 允许处理
+    Allow,
+    /// 本次请求超出配额，来源IP开始被封禁
+    Exceeded,
+    /// 来源IP处于封禁期内
+    Blocked,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    blocked_until: Option<Instant>,
+}
+
+/// 按来源IP的令牌桶限流
+///
+/// 每个IP以 `requests_per_sec` 的速率补充令牌，最多累积 `burst` 个。令牌耗尽的IP在
+/// `block_secs` 内的请求全部被静默丢弃，避免STUN端口被用于反射放大攻击。
+#[derive(Debug)]
+pub struct SourceRateLimiter {
+    config: StunRateLimitConfig,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl SourceRateLimiter {
+    pub fn new(config: StunRateLimitConfig) -> Self {
+        Self { config, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// 判定来自 `ip` 的一次请求
+    pub fn check(&self, ip: IpAddr) -> Verdict {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Verdict {
+        if self.config.requests_per_sec == 0 {
+            return Verdict::Allow;
+        }
+        let burst = self.config.burst.max(1) as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(ip).or_insert(Bucket { tokens: burst, updated: now, blocked_until: None });
+        if let Some(until) = bucket.blocked_until {
+            if now < until {
+                return Verdict::Blocked;
+            }
+            bucket.blocked_until = None;
+        }
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.config.requests_per_sec as f64).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Verdict::Allow;
+        }
+        bucket.blocked_until = Some(now + Duration::from_secs(self.config.block_secs));
+        Verdict::Exceeded
+    }
+
+    /// 移除已回满且未被封禁的记录
+    pub fn cleanup(&self) {
+        let now = Instant::now();
+        let refill = Duration::from_secs_f64(
+            self.config.burst.max(1) as f64 / self.config.requests_per_sec.max(1) as f64,
+        );
+        self.buckets.lock().unwrap().retain(|_, bucket| {
+            bucket.blocked_until.is_some_and(|until| now < until)
+                || now.saturating_duration_since(bucket.updated) < refill
+        });
+    }
+
+    /// 当前跟踪的来源IP数
+    pub fn tracked_sources(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_block_then_recover() {
+        let limiter = SourceRateLimiter::new(StunRateLimitConfig { requests_per_sec: 2, burst: 3, block_secs: 5 });
+        let (ip, other): (IpAddr, IpAddr) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(ip, start), Verdict::Allow);
+        }
+        assert_eq!(limiter.check_at(ip, start), Verdict::Exceeded);
+        // 封禁期内即使令牌已补充也被丢弃，其他IP不受影响
+        assert_eq!(limiter.check_at(ip, start + Duration::from_secs(2)), Verdict::Blocked);
+        assert_eq!(limiter.check_at(other, start), Verdict::Allow);
+
+        assert_eq!(limiter.check_at(ip, start + Duration::from_secs(6)), Verdict::Allow);
+    }
+}
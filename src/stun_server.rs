@@ -1,6 +1,18 @@
-use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::net::UdpSocket;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+#[cfg(feature = "stuns")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+#[cfg(feature = "stuns")]
+use tokio_rustls::TlsAcceptor;
 use anyhow::{Result, Context};
 use log::{info, debug, warn, error};
 use serde::{Serialize, Deserialize};
@@ -10,8 +22,21 @@ use crate::stun_protocol::{
     StunMessage, 
     STUN_BINDING_REQUEST, 
     create_mapped_address_attribute,
+    create_lifetime_probe_attribute,
     create_software_attribute,
+    extract_transaction_id,
+    lifetime_probe,
+    LifetimeProbe,
+    STUN_BINDING_INDICATION,
 };
+use crate::binding_lifetime::BindingRegistry;
+use crate::config::NetworkConfig;
+use crate::metrics::ServerMetrics;
+use crate::sockopt;
+use crate::stun_limiter::{SourceRateLimiter, Verdict};
+#[cfg(feature = "turn")]
+use crate::turn::TurnServer;
+use crate::tr;
 
 /// STUN错误码常量
 const STUN_ERROR_BAD_REQUEST: u16 = 400;
@@ -29,8 +54,30 @@ pub struct StunServerConfig {
     pub software: String,
     /// 是否启用详细日志
     pub verbose_logging: bool,
-    /// 最大并发连接数
+    /// 同时处理的请求与TCP/TLS连接数上限，超出时静默丢弃
     pub max_concurrent_requests: usize,
+    /// TURN 中继配置（需启用 `turn` 特性）
+    #[serde(default)]
+    pub turn: TurnConfig,
+    /// 同时在同一端口监听TCP（RFC 5389 第7.2.2节），供屏蔽UDP的网络使用
+    #[serde(default)]
+    pub tcp: bool,
+    /// TCP/TLS 连接的空闲超时（秒）
+    #[serde(default = "default_stream_idle_timeout_secs")]
+    pub stream_idle_timeout_secs: u64,
+    /// STUN over TLS 配置（需启用 `stuns` 特性）
+    #[serde(default)]
+    pub tls: StunTlsConfig,
+    /// 按来源IP的请求频率限制
+    #[serde(default)]
+    pub rate_limit: StunRateLimitConfig,
+    /// NAT 绑定有效期探测扩展
+    #[serde(default)]
+    pub lifetime_probe: LifetimeProbeConfig,
+}
+
+fn default_stream_idle_timeout_secs() -> u64 {
+    30
 }
 
 impl Default for StunServerConfig {
@@ -41,132 +88,711 @@ impl Default for StunServerConfig {
             software: "P2P-Handshake-Server/1.0".to_string(),
             verbose_logging: false,
             max_concurrent_requests: 1000,
+            turn: TurnConfig::default(),
+            tcp: false,
+            stream_idle_timeout_secs: default_stream_idle_timeout_secs(),
+            tls: StunTlsConfig::default(),
+            rate_limit: StunRateLimitConfig::default(),
+            lifetime_probe: LifetimeProbeConfig::default(),
+        }
+    }
+}
+
+/// NAT 绑定有效期探测扩展（自定义属性 `LIFETIME-PROBE`）配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeProbeConfig {
+    /// 是否启用
+    pub enable: bool,
+    /// 同时保留的登记数上限
+    pub max_registrations: usize,
+    /// 登记的有效期（秒），即可测量的最长绑定有效期
+    pub max_age_secs: u64,
+}
+
+impl Default for LifetimeProbeConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            max_registrations: 10_000,
+            max_age_secs: 3600,
         }
     }
 }
 
+/// STUN请求的来源IP频率限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StunRateLimitConfig {
+    /// 每个来源IP每秒允许的请求数，0 表示不限制
+    pub requests_per_sec: u32,
+    /// 允许的突发请求数
+    pub burst: u32,
+    /// 超出限制的来源IP被静默丢弃的时长（秒）
+    pub block_secs: u64,
+}
+
+impl Default for StunRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_sec: 20,
+            burst: 50,
+            block_secs: 60,
+        }
+    }
+}
+
+/// STUN over TLS（stuns，RFC 5389 第7.2.2节）配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StunTlsConfig {
+    /// 是否启用
+    pub enable: bool,
+    /// 监听端口（标准端口为5349）
+    pub port: u16,
+    /// PEM 格式的证书链
+    pub cert_path: Option<PathBuf>,
+    /// PEM 格式的私钥
+    pub key_path: Option<PathBuf>,
+}
+
+impl Default for StunTlsConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            port: 5349,
+            cert_path: None,
+            key_path: None,
+        }
+    }
+}
+
+/// TURN（RFC 5766）中继配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TurnConfig {
+    /// 是否启用 TURN
+    pub enable: bool,
+    /// 长期凭证的 realm
+    pub realm: String,
+    /// 用户名 -> 密码
+    pub users: HashMap<String, String>,
+    /// 中继套接字绑定的IP，未设置时使用STUN服务器的监听IP
+    pub relay_ip: Option<IpAddr>,
+    /// 对外通告的中继IP（服务器位于NAT之后时设置为公网IP）
+    pub external_ip: Option<IpAddr>,
+    /// 默认分配有效期（秒）
+    pub default_lifetime_secs: u64,
+    /// 客户端可请求的最长有效期（秒）
+    pub max_lifetime_secs: u64,
+    /// 服务器分配总数上限
+    pub max_allocations: usize,
+    /// 每个用户的分配数上限
+    pub max_allocations_per_user: usize,
+    /// 每个分配可中继的总字节数（双向合计），0 表示不限制
+    pub allocation_quota_bytes: u64,
+}
+
+impl Default for TurnConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            realm: "p2p".to_string(),
+            users: HashMap::new(),
+            relay_ip: None,
+            external_ip: None,
+            default_lifetime_secs: 600,
+            max_lifetime_secs: 3600,
+            max_allocations: 100,
+            max_allocations_per_user: 10,
+            allocation_quota_bytes: 0,
+        }
+    }
+}
+
+/// 统计不同客户端时最多记录的来源IP数，超过后计数不再增长
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+/// 单个请求的处理结果，用于请求日志与指标
+struct Outcome {
+    transaction_id: [u8; 12],
+    message_type: Option<u16>,
+    result: &'static str,
+    error: bool,
+}
+
+impl Outcome {
+    fn ok(transaction_id: [u8; 12], message_type: Option<u16>, result: &'static str) -> Self {
+        Self { transaction_id, message_type, result, error: false }
+    }
+
+    fn error(transaction_id: [u8; 12], message_type: Option<u16>, result: &'static str) -> Self {
+        Self { transaction_id, message_type, result, error: true }
+    }
+}
+
+/// STUN请求到达的传输方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    Udp,
+    Tcp,
+    #[cfg(feature = "stuns")]
+    Tls,
+}
+
+/// STUN over TLS 监听器
+#[cfg(feature = "stuns")]
+struct TlsListener {
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+}
+
+/// 从PEM文件加载证书链和私钥，构造TLS接受器
+#[cfg(feature = "stuns")]
+fn load_tls_acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor> {
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .context(format!("读取证书 {} 失败", cert_path.display()))?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .context(format!("读取私钥 {} 失败", key_path.display()))?;
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("证书与私钥不匹配")?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// 从流中读取一条STUN消息（RFC 5389 第7.2.2节：消息直接首尾相接，长度取自消息头），连接关闭时返回 `None`
+async fn read_stream_message<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Option<Vec<u8>>> {
+    let mut header = [0u8; 20];
+    match stream.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    if header[0] & 0xC0 != 0 {
+        anyhow::bail!("不是STUN消息");
+    }
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+    let mut message = vec![0u8; 20 + length];
+    message[..20].copy_from_slice(&header);
+    stream.read_exact(&mut message[20..]).await?;
+    Ok(Some(message))
+}
+
 /// STUN服务器实现
 pub struct StunServer {
     config: StunServerConfig,
     socket: Arc<UdpSocket>,
     local_addr: SocketAddr,
+    /// TCP监听器（`tcp` 开启时）
+    tcp_listener: Option<TcpListener>,
+    /// TLS监听器（启用 `stuns` 特性且配置开启时）
+    #[cfg(feature = "stuns")]
+    tls_listener: Option<TlsListener>,
+    /// 并发处理的请求与连接许可
+    permits: Arc<Semaphore>,
+    /// 来源IP频率限制
+    limiter: SourceRateLimiter,
+    /// 绑定有效期探测登记表（扩展启用时）
+    bindings: Option<BindingRegistry>,
+    /// 共享的运行指标
+    metrics: Arc<ServerMetrics>,
+    /// 发起过请求的来源IP
+    clients: Mutex<HashSet<IpAddr>>,
+    /// TURN中继（启用 `turn` 特性且配置开启时）
+    #[cfg(feature = "turn")]
+    turn: Option<Arc<TurnServer>>,
 }
 
 impl StunServer {
     /// 创建新的STUN服务器实例
     pub async fn new(config: StunServerConfig, bind_addr: SocketAddr) -> Result<Self> {
-        let socket = UdpSocket::bind(bind_addr).await
+        Self::with_network(config, bind_addr, &NetworkConfig::default()).await
+    }
+
+    /// 使用指定的套接字参数创建STUN服务器，STUN端口使用控制流量的DSCP标记，TURN中继端口使用中继流量的标记
+    pub async fn with_network(config: StunServerConfig, bind_addr: SocketAddr, network: &NetworkConfig) -> Result<Self> {
+        let socket = sockopt::bind_udp(bind_addr, network, network.control_dscp, Some("STUN"))
             .context("绑定STUN服务器套接字失败")?;
         
         let local_addr = socket.local_addr()
             .context("获取STUN服务器本地地址失败")?;
         
-        info!("STUN服务器启动成功，监听地址: {}", local_addr);
+        info!("{}", tr!("STUN服务器启动成功，监听地址: {}", "STUN server started, listening on: {}", local_addr));
+        let socket = Arc::new(socket);
+
+        // TCP与UDP使用相同端口
+        let tcp_listener = if config.tcp {
+            let listener = sockopt::bind_tcp(local_addr, network, network.control_dscp, Some("STUN TCP"))
+                .context("绑定STUN服务器TCP端口失败")?;
+            info!("{}", tr!("STUN服务器TCP监听地址: {}", "STUN server TCP listen address: {}", local_addr));
+            Some(listener)
+        } else {
+            None
+        };
+
+        #[cfg(feature = "stuns")]
+        let tls_listener = if config.tls.enable {
+            let (Some(cert_path), Some(key_path)) = (&config.tls.cert_path, &config.tls.key_path) else {
+                anyhow::bail!("启用STUN over TLS需要同时配置 cert_path 与 key_path");
+            };
+            let acceptor = load_tls_acceptor(cert_path, key_path)?;
+            let listener = sockopt::bind_tcp(SocketAddr::new(local_addr.ip(), config.tls.port), network, network.control_dscp, Some("STUN TLS"))
+                .context("绑定STUN over TLS端口失败")?;
+            info!("{}", tr!("STUN over TLS 监听地址: {}", "STUN over TLS listen address: {}", listener.local_addr()?));
+            Some(TlsListener { listener, acceptor })
+        } else {
+            None
+        };
+        #[cfg(not(feature = "stuns"))]
+        if config.tls.enable {
+            warn!("{}", tr!("STUN over TLS需要启用 stuns 特性，已忽略 stun_server.tls.enable", "STUN over TLS requires the stuns feature, ignoring stun_server.tls.enable"));
+        }
+
+        #[cfg(feature = "turn")]
+        let turn = config.turn.enable.then(|| {
+            TurnServer::new(config.turn.clone(), config.software.clone(), socket.clone(), local_addr.ip(), network.clone())
+        });
+        #[cfg(not(feature = "turn"))]
+        if config.turn.enable {
+            warn!("{}", tr!("TURN需要启用 turn 特性，已忽略 stun_server.turn.enable", "TURN requires the turn feature, ignoring stun_server.turn.enable"));
+        }
         
+        let permits = Arc::new(Semaphore::new(config.max_concurrent_requests.max(1)));
+        let limiter = SourceRateLimiter::new(config.rate_limit.clone());
+        let bindings = config.lifetime_probe.enable.then(|| {
+            BindingRegistry::new(
+                config.lifetime_probe.max_registrations,
+                Duration::from_secs(config.lifetime_probe.max_age_secs),
+            )
+        });
         Ok(Self {
             config,
-            socket: Arc::new(socket),
+            socket,
             local_addr,
+            tcp_listener,
+            #[cfg(feature = "stuns")]
+            tls_listener,
+            permits,
+            limiter,
+            bindings,
+            metrics: Arc::new(ServerMetrics::new()),
+            clients: Mutex::new(HashSet::new()),
+            #[cfg(feature = "turn")]
+            turn,
         })
     }
 
+    /// 使用共享的运行指标（默认使用独立的指标）
+    pub fn with_metrics(mut self, metrics: Arc<ServerMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     /// 获取本地监听地址
     #[allow(dead_code)]
     pub fn local_addr(&self) -> SocketAddr {
         self.local_addr
     }
 
+    /// TCP监听地址（未开启TCP时为 `None`）
+    pub fn tcp_local_addr(&self) -> Option<SocketAddr> {
+        self.tcp_listener.as_ref().and_then(|l| l.local_addr().ok())
+    }
+
+    /// STUN over TLS 监听地址（未开启时为 `None`）
+    pub fn tls_local_addr(&self) -> Option<SocketAddr> {
+        #[cfg(feature = "stuns")]
+        if let Some(tls) = &self.tls_listener {
+            return tls.listener.local_addr().ok();
+        }
+        None
+    }
+
     /// 启动STUN服务器
     pub async fn run(&self) -> Result<()> {
-        info!("STUN服务器开始运行，监听端口: {}", self.local_addr.port());
+        info!("{}", tr!("STUN服务器开始运行，监听端口: {}", "STUN server running, listening on port: {}", self.local_addr.port()));
         
-        let mut buffer = vec![0u8; 1500]; // MTU大小的缓冲区
+        let mut buffer = vec![0u8; 65535]; // TURN中继的数据可能超过MTU
+        let mut cleanup = tokio::time::interval(std::time::Duration::from_secs(30));
+        // 请求与TCP/TLS连接在本任务内并发处理，每个占用一个许可
+        let mut tasks: FuturesUnordered<BoxFuture<'_, ()>> = FuturesUnordered::new();
         
         loop {
-            match self.socket.recv_from(&mut buffer).await {
+            let received = tokio::select! {
+                received = self.socket.recv_from(&mut buffer) => received,
+                _ = cleanup.tick() => {
+                    self.cleanup_turn_allocations();
+                    self.limiter.cleanup();
+                    if let Some(bindings) = &self.bindings {
+                        bindings.cleanup();
+                    }
+                    continue;
+                }
+                Some((stream, client_addr)) = Self::accept(self.tcp_listener.as_ref()) => {
+                    if let Some(permit) = self.try_permit(client_addr) {
+                        tasks.push(async move {
+                            let _permit = permit;
+                            self.serve_stream(stream, client_addr, Transport::Tcp).await;
+                        }.boxed());
+                    }
+                    continue;
+                }
+                Some((accepted, client_addr)) = self.accept_tls() => {
+                    if let Some(permit) = self.try_permit(client_addr) {
+                        tasks.push(async move {
+                            let _permit = permit;
+                            accepted.await;
+                        }.boxed());
+                    }
+                    continue;
+                }
+                Some(()) = tasks.next(), if !tasks.is_empty() => continue,
+            };
+            match received {
                 Ok((len, client_addr)) => {
                     if self.config.verbose_logging {
-                        debug!("收到来自 {} 的STUN请求，长度: {} 字节", client_addr, len);
+                        debug!("{}", tr!("收到来自 {} 的STUN请求，长度: {} 字节", "Received STUN request from {}, length: {} bytes", client_addr, len));
+                    }
+
+                    // TURN通道数据不是STUN消息
+                    #[cfg(feature = "turn")]
+                    if let Some(turn) = &self.turn
+                        && crate::turn::is_channel_data(&buffer[..len])
+                    {
+                        turn.handle_channel_data(&buffer[..len], client_addr).await;
+                        continue;
                     }
                     
-                    // 处理STUN请求
-                    if let Err(e) = self.handle_stun_request(&buffer[..len], client_addr).await {
-                        warn!("处理来自 {} 的STUN请求失败: {}", client_addr, e);
+                    // 超出频率限制或并发上限的请求静默丢弃，不产生任何响应
+                    if !self.admit(client_addr) {
+                        continue;
                     }
+                    let Some(permit) = self.try_permit(client_addr) else {
+                        continue;
+                    };
+                    let data = buffer[..len].to_vec();
+                    tasks.push(async move {
+                        let _permit = permit;
+                        if let Err(e) = self.handle_stun_request(&data, client_addr).await {
+                            warn!("{}", tr!("处理来自 {} 的STUN请求失败: {}", "Failed to handle STUN request from {}: {}", client_addr, e));
+                        }
+                    }.boxed());
                 }
                 Err(e) => {
-                    error!("接收STUN数据包失败: {}", e);
+                    error!("{}", tr!("接收STUN数据包失败: {}", "Failed to receive STUN packet: {}", e));
                     // 继续运行，不因单个错误而停止服务
                 }
             }
         }
     }
 
-    /// 处理STUN请求
-    async fn handle_stun_request(&self, data: &[u8], client_addr: SocketAddr) -> Result<()> {
-        // 解析STUN消息
-        let request = match StunMessage::from_bytes(data) {
-            Ok(msg) => msg,
+    /// 接受一个TCP连接；未开启监听时永远挂起
+    async fn accept(listener: Option<&TcpListener>) -> Option<(tokio::net::TcpStream, SocketAddr)> {
+        let Some(listener) = listener else {
+            return std::future::pending().await;
+        };
+        match listener.accept().await {
+            Ok(accepted) => Some(accepted),
             Err(e) => {
-                debug!("解析STUN消息失败: {}", e);
-                // 发送错误响应
-                self.send_error_response(client_addr, [0; 12], STUN_ERROR_BAD_REQUEST, "Bad Request").await?;
-                return Ok(());
+                warn!("{}", tr!("接受STUN TCP连接失败: {}", "Failed to accept STUN TCP connection: {}", e));
+                None
+            }
+        }
+    }
+
+    /// 接受一个TLS连接，返回完成握手并处理该连接的任务
+    #[cfg(feature = "stuns")]
+    async fn accept_tls(&self) -> Option<(BoxFuture<'_, ()>, SocketAddr)> {
+        let tls = self.tls_listener.as_ref();
+        let (stream, client_addr) = Self::accept(tls.map(|t| &t.listener)).await?;
+        let acceptor = tls?.acceptor.clone();
+        let idle = Duration::from_secs(self.config.stream_idle_timeout_secs);
+        let serve = async move {
+            match tokio::time::timeout(idle, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => self.serve_stream(stream, client_addr, Transport::Tls).await,
+                Ok(Err(e)) => debug!("{}", tr!("与 {} 的TLS握手失败: {}", "TLS handshake with {} failed: {}", client_addr, e)),
+                Err(_) => debug!("{}", tr!("与 {} 的TLS握手超时", "TLS handshake with {} timed out", client_addr)),
             }
         };
+        Some((serve.boxed(), client_addr))
+    }
 
-        if self.config.verbose_logging {
-            debug!("解析STUN消息成功: 类型={:04x}, 事务ID={:?}", 
-                   request.message_type, request.transaction_id);
+    #[cfg(not(feature = "stuns"))]
+    async fn accept_tls(&self) -> Option<(BoxFuture<'_, ()>, SocketAddr)> {
+        std::future::pending().await
+    }
+
+    /// 处理一条TCP/TLS连接上的STUN请求，直到连接关闭或空闲超时
+    async fn serve_stream<S: AsyncRead + AsyncWrite + Unpin>(&self, mut stream: S, client_addr: SocketAddr, transport: Transport) {
+        let idle = Duration::from_secs(self.config.stream_idle_timeout_secs);
+        loop {
+            let data = match tokio::time::timeout(idle, read_stream_message(&mut stream)).await {
+                Ok(Ok(Some(data))) => data,
+                Ok(Ok(None)) => break,
+                Ok(Err(e)) => {
+                    debug!("{}", tr!("读取来自 {} 的STUN {:?} 消息失败: {}", "Failed to read STUN {1:?} message from {0}: {2}", client_addr, transport, e));
+                    break;
+                }
+                Err(_) => {
+                    debug!("{}", tr!("STUN {:?} 连接 {} 空闲超时", "STUN {:?} connection {} idle timeout", transport, client_addr));
+                    break;
+                }
+            };
+            if self.config.verbose_logging {
+                debug!("{}", tr!("收到来自 {} 的STUN {:?} 请求，长度: {} 字节", "Received STUN {1:?} request from {0}, length: {2} bytes", client_addr, transport, data.len()));
+            }
+            if !self.admit(client_addr) {
+                break;
+            }
+            let Some(response) = self.process_request(&data, client_addr, transport).await else {
+                continue;
+            };
+            if let Err(e) = stream.write_all(&response).await {
+                warn!("{}", tr!("向 {} 发送STUN {:?} 响应失败: {}", "Failed to send STUN {1:?} response to {0}: {2}", client_addr, transport, e));
+                break;
+            }
         }
+    }
 
-        // 处理不同类型的STUN请求
-        match request.message_type {
-            STUN_BINDING_REQUEST => {
-                self.handle_binding_request(&request, client_addr).await?;
+    /// 检查来源IP的请求频率，超出限制时返回 `false`
+    fn admit(&self, client_addr: SocketAddr) -> bool {
+        match self.limiter.check(client_addr.ip()) {
+            Verdict::Allow => true,
+            Verdict::Exceeded => {
+                warn!(
+                    "{}",
+                    tr!(
+                        "来源 {} 的STUN请求超出频率限制，{} 秒内静默丢弃",
+                        "STUN requests from {} exceeded the rate limit, silently dropping for {} seconds",
+                        client_addr.ip(),
+                        self.config.rate_limit.block_secs,
+                    )
+                );
+                ServerMetrics::incr(&self.metrics.stun_dropped);
+                false
             }
-            _ => {
-                debug!("不支持的STUN消息类型: {:04x}", request.message_type);
-                self.send_error_response(
-                    client_addr, 
-                    request.transaction_id, 
-                    STUN_ERROR_BAD_REQUEST, 
-                    "Unsupported Message Type"
-                ).await?;
+            Verdict::Blocked => {
+                ServerMetrics::incr(&self.metrics.stun_dropped);
+                false
             }
         }
+    }
 
-        Ok(())
+    /// 获取一个并发许可，已达上限时返回 `None`
+    fn try_permit(&self, client_addr: SocketAddr) -> Option<OwnedSemaphorePermit> {
+        match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                debug!("{}", tr!("STUN并发请求已达上限 {}，丢弃来自 {} 的请求", "STUN concurrent request limit {} reached, dropping request from {}", self.config.max_concurrent_requests, client_addr));
+                ServerMetrics::incr(&self.metrics.stun_dropped);
+                None
+            }
+        }
     }
 
-    /// 处理STUN绑定请求
-    async fn handle_binding_request(&self, request: &StunMessage, client_addr: SocketAddr) -> Result<()> {
-        if self.config.verbose_logging {
-            debug!("处理来自 {} 的STUN绑定请求", client_addr);
+    /// 因频率限制或并发上限被丢弃的请求数
+    pub fn dropped_requests(&self) -> u64 {
+        self.metrics.stun_dropped.load(Ordering::Relaxed)
+    }
+
+    /// 清理过期的TURN分配
+    fn cleanup_turn_allocations(&self) {
+        #[cfg(feature = "turn")]
+        if let Some(turn) = &self.turn {
+            let expired = turn.cleanup_expired();
+            if expired > 0 {
+                debug!("{}", tr!("清理了 {} 个过期的TURN分配", "Cleaned up {} expired TURN allocations", expired));
+            }
         }
+    }
 
-        // 创建绑定响应
-        let response = self.create_binding_response(request, client_addr)?;
-        let response_bytes = response.to_bytes();
+    /// 当前TURN分配数（未启用TURN时为0）
+    pub fn turn_allocation_count(&self) -> usize {
+        #[cfg(feature = "turn")]
+        if let Some(turn) = &self.turn {
+            return turn.allocation_count();
+        }
+        0
+    }
 
-        // 发送响应
-        match self.socket.send_to(&response_bytes, client_addr).await {
+    /// 处理UDP上的STUN请求
+    async fn handle_stun_request(&self, data: &[u8], client_addr: SocketAddr) -> Result<()> {
+        let Some(response) = self.process_request(data, client_addr, Transport::Udp).await else {
+            return Ok(());
+        };
+        match self.socket.send_to(&response, client_addr).await {
             Ok(sent) => {
                 if self.config.verbose_logging {
-                    debug!("向 {} 发送STUN绑定响应成功，发送 {} 字节", client_addr, sent);
+                    debug!("{}", tr!("向 {} 发送STUN响应成功，发送 {} 字节", "Sent STUN response to {}: {} bytes", client_addr, sent));
                 }
+                Ok(())
             }
             Err(e) => {
-                warn!("向 {} 发送STUN绑定响应失败: {}", client_addr, e);
-                return Err(e.into());
+                warn!("{}", tr!("向 {} 发送STUN响应失败: {}", "Failed to send STUN response to {}: {}", client_addr, e));
+                Err(e.into())
+            }
+        }
+    }
+
+    /// 解析STUN请求并生成响应（各传输方式共用），记录指标与请求日志；无需回复（如已由TURN处理）时返回 `None`
+    async fn process_request(&self, data: &[u8], client_addr: SocketAddr, transport: Transport) -> Option<Vec<u8>> {
+        let started = Instant::now();
+        self.record_client(client_addr.ip());
+        let (response, outcome) = self.respond(data, client_addr, transport).await;
+        let elapsed_us = started.elapsed().as_micros() as u64;
+
+        ServerMetrics::incr(&self.metrics.stun_requests);
+        if outcome.error {
+            ServerMetrics::incr(&self.metrics.stun_errors);
+        }
+        ServerMetrics::add(&self.metrics.stun_latency_us_total, elapsed_us);
+        ServerMetrics::max(&self.metrics.stun_latency_us_max, elapsed_us);
+
+        if log::log_enabled!(log::Level::Debug) {
+            let transaction_id: String = outcome.transaction_id.iter().map(|b| format!("{:02x}", b)).collect();
+            let message_type = outcome.message_type.map(|t| format!("{:04x}", t)).unwrap_or_else(|| "-".to_string());
+            debug!(
+                "{}",
+                tr!(
+                    "STUN请求 txid={} 来源={} 传输={:?} 类型={} 结果={} 耗时={}us",
+                    "STUN request txid={} source={} transport={:?} type={} result={} elapsed={}us",
+                    transaction_id,
+                    client_addr,
+                    transport,
+                    message_type,
+                    outcome.result,
+                    elapsed_us,
+                )
+            );
+        }
+        response
+    }
+
+    /// 生成请求的响应
+    async fn respond(&self, data: &[u8], client_addr: SocketAddr, transport: Transport) -> (Option<Vec<u8>>, Outcome) {
+        // 解析STUN消息
+        let request = match StunMessage::from_bytes(data) {
+            Ok(msg) => msg,
+            Err(e) => {
+                debug!("{}", tr!("解析来自 {} 的STUN消息失败: {}", "Failed to parse STUN message from {}: {}", client_addr, e));
+                let transaction_id = extract_transaction_id(data).unwrap_or([0; 12]);
+                let response = self.error_response(transaction_id, STUN_ERROR_BAD_REQUEST, "Bad Request");
+                return (Some(response), Outcome::error(transaction_id, None, "bad_request"));
+            }
+        };
+        let (transaction_id, message_type) = (request.transaction_id, Some(request.message_type));
+
+        // TURN分配绑定在UDP五元组上，只处理UDP请求
+        #[cfg(feature = "turn")]
+        if transport == Transport::Udp
+            && let Some(turn) = &self.turn
+        {
+            match turn.handle_message(&request, data, client_addr).await {
+                Ok(true) => return (None, Outcome::ok(transaction_id, message_type, "turn")),
+                Ok(false) => {}
+                Err(e) => {
+                    warn!("{}", tr!("处理来自 {} 的TURN请求失败: {}", "Failed to handle TURN request from {}: {}", client_addr, e));
+                    return (None, Outcome::error(transaction_id, message_type, "turn_failed"));
+                }
+            }
+        }
+
+        // 处理不同类型的STUN请求
+        match request.message_type {
+            STUN_BINDING_REQUEST => {
+                if self.config.verbose_logging {
+                    debug!("{}", tr!("处理来自 {} 的STUN绑定请求（{:?}）", "Handling STUN binding request from {} ({:?})", client_addr, transport));
+                }
+                let mut response = self.create_binding_response(&request, client_addr);
+                let probe = match lifetime_probe(&request) {
+                    Ok(probe) => probe,
+                    Err(e) => {
+                        debug!("{}", tr!("来自 {} 的绑定有效期探测无效: {}", "Invalid binding lifetime probe from {}: {}", client_addr, e));
+                        let response = self.error_response(transaction_id, STUN_ERROR_BAD_REQUEST, "Invalid Lifetime Probe");
+                        return (Some(response), Outcome::error(transaction_id, message_type, "invalid_lifetime_probe"));
+                    }
+                };
+                let result = match (probe, &self.bindings) {
+                    (Some(probe), Some(bindings)) => {
+                        match self.handle_lifetime_probe(bindings, probe, &request, client_addr, transport, &mut response).await {
+                            Ok(result) => result,
+                            Err(reason) => {
+                                let response = self.error_response(transaction_id, STUN_ERROR_BAD_REQUEST, reason);
+                                return (Some(response), Outcome::error(transaction_id, message_type, "unknown_lifetime_token"));
+                            }
+                        }
+                    }
+                    // 属性属于可选理解范围，未启用扩展时按普通绑定请求处理
+                    _ => "binding",
+                };
+                (Some(response.to_bytes()), Outcome::ok(transaction_id, message_type, result))
+            }
+            _ => {
+                let response = self.error_response(transaction_id, STUN_ERROR_BAD_REQUEST, "Unsupported Message Type");
+                (Some(response), Outcome::error(transaction_id, message_type, "unsupported"))
             }
         }
+    }
 
-        Ok(())
+    /// 处理绑定有效期探测：登记时在响应中附带令牌，检查时向登记的映射地址回送绑定指示
+    async fn handle_lifetime_probe(
+        &self,
+        bindings: &BindingRegistry,
+        probe: LifetimeProbe,
+        request: &StunMessage,
+        client_addr: SocketAddr,
+        transport: Transport,
+        response: &mut StunMessage,
+    ) -> Result<&'static str, &'static str> {
+        match probe {
+            // 只有UDP映射需要测量
+            LifetimeProbe::Register if transport != Transport::Udp => Ok("binding"),
+            LifetimeProbe::Register => match bindings.register(client_addr) {
+                Some(token) => {
+                    response.add_attribute(create_lifetime_probe_attribute(Some(token)));
+                    Ok("lifetime_register")
+                }
+                None => {
+                    debug!("{}", tr!("绑定有效期探测登记已满，忽略来自 {} 的登记", "Binding lifetime probe registry is full, ignoring registration from {}", client_addr));
+                    Ok("lifetime_full")
+                }
+            },
+            LifetimeProbe::Check(token) => {
+                let Some((mapped, age)) = bindings.lookup(token) else {
+                    return Err("Unknown Lifetime Probe Token");
+                };
+                let mut indication = StunMessage::new(STUN_BINDING_INDICATION, request.transaction_id);
+                indication.add_attribute(create_lifetime_probe_attribute(Some(token)));
+                indication.add_attribute(create_mapped_address_attribute(mapped, true));
+                indication.add_attribute(create_software_attribute(&self.config.software));
+                if let Err(e) = self.socket.send_to(&indication.to_bytes(), mapped).await {
+                    debug!("{}", tr!("向 {} 回送绑定有效期探测失败: {}", "Failed to echo binding lifetime probe to {}: {}", mapped, e));
+                }
+                debug!("{}", tr!("绑定有效期探测：已向 {} 回送（登记于 {} 秒前）", "Binding lifetime probe: echoed to {} (registered {} seconds ago)", mapped, age.as_secs()));
+                response.add_attribute(create_lifetime_probe_attribute(Some(token)));
+                Ok("lifetime_check")
+            }
+        }
+    }
+
+    /// 记录来源IP，更新不同客户端数量
+    fn record_client(&self, ip: IpAddr) {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() < MAX_TRACKED_CLIENTS && clients.insert(ip) {
+            self.metrics.stun_unique_clients.store(clients.len() as u64, Ordering::Relaxed);
+        }
     }
 
     /// 创建STUN绑定响应
-    fn create_binding_response(&self, request: &StunMessage, client_addr: SocketAddr) -> Result<StunMessage> {
+    fn create_binding_response(&self, request: &StunMessage, client_addr: SocketAddr) -> StunMessage {
         let mut response = StunMessage::new_binding_response(request.transaction_id);
 
         // 添加XOR映射地址属性（RFC 5389推荐）
@@ -181,48 +807,28 @@ impl StunServer {
         let software_attr = create_software_attribute(&self.config.software);
         response.add_attribute(software_attr);
 
-        Ok(response)
+        response
     }
 
-
-
-    /// 发送错误响应
-    async fn send_error_response(
-        &self, 
-        client_addr: SocketAddr, 
-        transaction_id: [u8; 12], 
-        error_code: u16, 
-        reason_phrase: &str
-    ) -> Result<()> {
+    /// 创建错误响应
+    fn error_response(&self, transaction_id: [u8; 12], error_code: u16, reason_phrase: &str) -> Vec<u8> {
         let mut response = StunMessage::new_error_response(transaction_id, error_code, reason_phrase);
 
         // 添加软件属性
         let software_attr = create_software_attribute(&self.config.software);
         response.add_attribute(software_attr);
 
-        let response_bytes = response.to_bytes();
-        
-        match self.socket.send_to(&response_bytes, client_addr).await {
-            Ok(_) => {
-                debug!("向 {} 发送STUN错误响应: {} {}", client_addr, error_code, reason_phrase);
-            }
-            Err(e) => {
-                warn!("向 {} 发送STUN错误响应失败: {}", client_addr, e);
-                return Err(e.into());
-            }
-        }
-
-        Ok(())
+        debug!("{}", tr!("生成STUN错误响应: {} {}", "Built STUN error response: {} {}", error_code, reason_phrase));
+        response.to_bytes()
     }
 
-
-
     /// 获取服务器统计信息
     #[allow(dead_code)]
     pub async fn get_stats(&self) -> StunServerStats {
         StunServerStats {
             local_addr: self.local_addr,
             is_running: true,
+            dropped_requests: self.dropped_requests(),
             config: self.config.clone(),
         }
     }
@@ -235,6 +841,8 @@ pub struct StunServerStats {
     pub local_addr: SocketAddr,
     #[allow(dead_code)]
     pub is_running: bool,
+    /// 因频率限制或并发上限被丢弃的请求数
+    pub dropped_requests: u64,
     #[allow(dead_code)]
     pub config: StunServerConfig,
 }
\ No newline at end of file
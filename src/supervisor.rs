@@ -0,0 +1,236 @@
+use log::{error, info};
+use serde::Serialize;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex, Once};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+use crate::config::SupervisorConfig;
+use crate::tr;
+
+static PANIC_HOOK: Once = Once::new();
+
+/// 安装记录 panic 位置与调用栈的钩子（只安装一次，保留原有钩子的输出）
+pub fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let backtrace = std::backtrace::Backtrace::force_capture();
+            error!("{}", tr!("线程 {} panic: {}\n{}", "Thread {} panicked: {}\n{}", std::thread::current().name().unwrap_or("<unnamed>"), info, backtrace));
+            previous(info);
+        }));
+    });
+}
+
+/// 从 panic 负载中取出消息
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "未知的 panic".to_string()
+    }
+}
+
+/// 单个受监督任务的运行状况
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskHealth {
+    pub name: &'static str,
+    /// 累计重启次数
+    pub restarts: u64,
+    /// 统计窗口内的崩溃次数
+    pub recent_crashes: usize,
+    pub last_panic: Option<String>,
+    pub healthy: bool,
+}
+
+#[derive(Debug, Default)]
+struct TaskRecord {
+    restarts: u64,
+    crashes: VecDeque<Instant>,
+    last_panic: Option<String>,
+}
+
+/// 后台任务监督器
+///
+/// 受监督的任务 panic 后记录原因，并按指数退避重新创建；统计窗口内崩溃达到
+/// `unhealthy_after` 次的任务视为不健康，由健康检查报告。任务正常结束或被取消时不再重启。
+#[derive(Debug)]
+pub struct Supervisor {
+    config: SupervisorConfig,
+    tasks: Mutex<HashMap<&'static str, TaskRecord>>,
+}
+
+impl Supervisor {
+    pub fn new(config: SupervisorConfig) -> Self {
+        install_panic_hook();
+        Self { config, tasks: Mutex::new(HashMap::new()) }
+    }
+
+    /// 启动受监督的任务：`make` 在每次（重新）启动时创建任务本体
+    ///
+    /// 取消返回的句柄会一并取消正在运行的任务本体。
+    pub fn spawn<F, Fut>(self: &Arc<Self>, name: &'static str, make: F) -> JoinHandle<()>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.lock().unwrap().entry(name).or_default();
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            let mut consecutive = 0u32;
+            loop {
+                let started = Instant::now();
+                let task = AbortOnDrop(tokio::spawn(make()));
+                let panic = match task.join().await {
+                    Ok(()) => {
+                        info!("{}", tr!("后台任务 {} 已结束", "Background task {} finished", name));
+                        return;
+                    }
+                    Err(e) if e.is_cancelled() => return,
+                    Err(e) => panic_message(&*e.into_panic()),
+                };
+                // 运行超过最大退避时长后再崩溃，重新从初始退避开始
+                if started.elapsed() >= supervisor.max_backoff() {
+                    consecutive = 0;
+                }
+                let backoff = supervisor.backoff(consecutive);
+                consecutive = consecutive.saturating_add(1);
+                let healthy = supervisor.record_crash(name, panic.clone());
+                error!("{}", tr!("后台任务 {} panic: {}，{:?} 后重启{}", "Background task {} panicked: {}, restarting in {:?}{}", name, panic, backoff, if healthy { "" } else { tr!("（已标记为不健康）", " (marked unhealthy)") }));
+                tokio::time::sleep(backoff).await;
+            }
+        })
+    }
+
+    fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.config.max_backoff_ms.max(self.config.initial_backoff_ms))
+    }
+
+    /// 第 `attempt` 次连续重启前的等待时间
+    fn backoff(&self, attempt: u32) -> Duration {
+        let initial = Duration::from_millis(self.config.initial_backoff_ms);
+        initial.saturating_mul(2u32.saturating_pow(attempt.min(16))).min(self.max_backoff())
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.config.failure_window_secs)
+    }
+
+    /// 记录一次崩溃，返回该任务是否仍然健康
+    fn record_crash(&self, name: &'static str, panic: String) -> bool {
+        let now = Instant::now();
+        let mut tasks = self.tasks.lock().unwrap();
+        let record = tasks.entry(name).or_default();
+        record.restarts += 1;
+        record.last_panic = Some(panic);
+        record.crashes.push_back(now);
+        while record.crashes.front().is_some_and(|t| now.duration_since(*t) > self.window()) {
+            record.crashes.pop_front();
+        }
+        self.is_healthy(record, now)
+    }
+
+    fn is_healthy(&self, record: &TaskRecord, now: Instant) -> bool {
+        let recent = record.crashes.iter().filter(|t| now.duration_since(**t) <= self.window()).count();
+        self.config.unhealthy_after == 0 || recent < self.config.unhealthy_after
+    }
+
+    /// 各受监督任务的运行状况
+    pub fn tasks(&self) -> Vec<TaskHealth> {
+        let now = Instant::now();
+        let tasks = self.tasks.lock().unwrap();
+        let mut health: Vec<TaskHealth> = tasks
+            .iter()
+            .map(|(name, record)| TaskHealth {
+                name,
+                restarts: record.restarts,
+                recent_crashes: record.crashes.iter().filter(|t| now.duration_since(**t) <= self.window()).count(),
+                last_panic: record.last_panic.clone(),
+                healthy: self.is_healthy(record, now),
+            })
+            .collect();
+        health.sort_by_key(|t| t.name);
+        health
+    }
+
+    /// 所有受监督任务是否健康
+    pub fn healthy(&self) -> bool {
+        self.tasks().iter().all(|t| t.healthy)
+    }
+}
+
+/// 监督循环被取消时一并取消任务本体
+struct AbortOnDrop(JoinHandle<()>);
+
+impl AbortOnDrop {
+    async fn join(mut self) -> Result<(), tokio::task::JoinError> {
+        (&mut self.0).await
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_restarts_with_backoff_and_reports_health() {
+        let config = SupervisorConfig { initial_backoff_ms: 10, max_backoff_ms: 40, unhealthy_after: 2, failure_window_secs: 60 };
+        let supervisor = Arc::new(Supervisor::new(config));
+        assert_eq!(supervisor.backoff(0), Duration::from_millis(10));
+        assert_eq!(supervisor.backoff(1), Duration::from_millis(20));
+        assert_eq!(supervisor.backoff(5), Duration::from_millis(40));
+
+        // 前三次运行 panic，第四次正常结束
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let handle = supervisor.spawn("flaky", move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < 3 {
+                    panic!("测试 panic");
+                }
+            }
+        });
+        tokio::time::timeout(Duration::from_secs(2), handle).await.unwrap().unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+
+        let tasks = supervisor.tasks();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].restarts, 3);
+        assert_eq!(tasks[0].last_panic.as_deref(), Some("测试 panic"));
+        assert!(!tasks[0].healthy);
+        assert!(!supervisor.healthy());
+
+        // 取消监督句柄时任务本体随之取消
+        let stopped = Arc::new(AtomicUsize::new(0));
+        let flag = stopped.clone();
+        let handle = supervisor.spawn("forever", move || {
+            let flag = flag.clone();
+            async move {
+                struct Mark(Arc<AtomicUsize>);
+                impl Drop for Mark {
+                    fn drop(&mut self) {
+                        self.0.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+                let _mark = Mark(flag);
+                std::future::pending::<()>().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        handle.abort();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(stopped.load(Ordering::SeqCst), 1);
+    }
+}
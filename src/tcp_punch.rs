@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// 等待对方应答的 TCP 打洞提议
+#[derive(Debug)]
+struct Offer {
+    tcp_addr: SocketAddr,
+    created: Instant,
+}
+
+/// TCP 同时打开协调中尚未得到应答的提议
+///
+/// 一方提交自己的公网 TCP 端点后，提议按 (发起方, 目标) 登记并转交给目标；目标提交自己的端点时
+/// 取出反方向的提议，服务器随即向双方下发对方端点与统一的连接时间。双方同时发起时，后到的一方
+/// 即视为应答。提议在 `ttl` 后过期。
+#[derive(Debug)]
+pub struct TcpPunchOffers {
+    ttl: Duration,
+    offers: Mutex<HashMap<(Uuid, Uuid), Offer>>,
+}
+
+impl TcpPunchOffers {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, offers: Mutex::new(HashMap::new()) }
+    }
+
+    /// 登记 `from` 发给 `to` 的端点；`to` 已有发给 `from` 的未过期提议时取出并返回其端点
+    pub fn submit(&self, from: Uuid, to: Uuid, tcp_addr: SocketAddr) -> Option<SocketAddr> {
+        let now = Instant::now();
+        let mut offers = self.offers.lock().unwrap();
+        offers.retain(|_, offer| now.duration_since(offer.created) <= self.ttl);
+        if let Some(offer) = offers.remove(&(to, from)) {
+            return Some(offer.tcp_addr);
+        }
+        offers.insert((from, to), Offer { tcp_addr, created: now });
+        None
+    }
+
+    /// 移除节点发起或收到的全部提议
+    pub fn remove_peer(&self, peer_id: &Uuid) {
+        self.offers.lock().unwrap().retain(|(from, to), _| from != peer_id && to != peer_id);
+    }
+
+    /// 未得到应答的提议数
+    pub fn len(&self) -> usize {
+        self.offers.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answer_takes_reverse_offer() {
+        let offers = TcpPunchOffers::new(Duration::from_secs(10));
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let (a_addr, b_addr): (SocketAddr, SocketAddr) = ("1.1.1.1:4000".parse().unwrap(), "2.2.2.2:5000".parse().unwrap());
+
+        assert_eq!(offers.submit(a, b, a_addr), None);
+        // 同方向重复提交只更新提议，其他节点的提交互不影响
+        assert_eq!(offers.submit(a, b, a_addr), None);
+        assert_eq!(offers.submit(c, b, a_addr), None);
+        assert_eq!(offers.submit(b, a, b_addr), Some(a_addr));
+        assert_eq!(offers.len(), 1);
+
+        offers.remove_peer(&b);
+        assert!(offers.is_empty());
+    }
+
+    #[test]
+    fn test_offers_expire() {
+        let offers = TcpPunchOffers::new(Duration::from_millis(10));
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let addr: SocketAddr = "1.1.1.1:4000".parse().unwrap();
+        assert_eq!(offers.submit(a, b, addr), None);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(offers.submit(b, a, addr), None);
+    }
+}
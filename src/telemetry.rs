@@ -0,0 +1,384 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::Result;
+use log::{debug, warn};
+use rand::Rng;
+use serde_json::{json, Value};
+
+use crate::config::TelemetryConfig;
+use crate::http_client::{self, HttpUrl};
+use crate::metrics::ServerMetrics;
+use crate::peer::PeerManager;
+use crate::tr;
+
+/// span属性值
+#[derive(Debug, Clone)]
+pub enum AttributeValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl From<String> for AttributeValue {
+    fn from(v: String) -> Self { Self::Str(v) }
+}
+
+impl From<&str> for AttributeValue {
+    fn from(v: &str) -> Self { Self::Str(v.to_string()) }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(v: i64) -> Self { Self::Int(v) }
+}
+
+impl From<u64> for AttributeValue {
+    fn from(v: u64) -> Self { Self::Int(v as i64) }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(v: bool) -> Self { Self::Bool(v) }
+}
+
+/// 进行中的span
+#[derive(Debug)]
+pub struct ActiveSpan {
+    name: &'static str,
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    start_unix_nanos: u64,
+    attributes: Vec<(&'static str, AttributeValue)>,
+    enabled: bool,
+}
+
+impl ActiveSpan {
+    /// 设置属性（未启用遥测时为空操作）
+    pub fn set_attribute(&mut self, key: &'static str, value: impl Into<AttributeValue>) {
+        if self.enabled {
+            self.attributes.push((key, value.into()));
+        }
+    }
+}
+
+/// 已结束的span
+#[derive(Debug, Clone)]
+struct SpanRecord {
+    name: &'static str,
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    start_unix_nanos: u64,
+    end_unix_nanos: u64,
+    attributes: Vec<(&'static str, AttributeValue)>,
+    error: Option<String>,
+}
+
+/// OTLP 遥测：记录span并周期性导出span与指标
+pub struct Telemetry {
+    config: TelemetryConfig,
+    spans: Mutex<VecDeque<SpanRecord>>,
+    dropped_spans: AtomicU64,
+    start_unix_nanos: u64,
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn attribute_json(key: &str, value: &AttributeValue) -> Value {
+    let value = match value {
+        AttributeValue::Str(s) => json!({ "stringValue": s }),
+        // OTLP JSON 中 int64 以字符串表示
+        AttributeValue::Int(i) => json!({ "intValue": i.to_string() }),
+        AttributeValue::Bool(b) => json!({ "boolValue": b }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+impl Telemetry {
+    pub fn new(config: TelemetryConfig) -> Self {
+        Self {
+            config,
+            spans: Mutex::new(VecDeque::new()),
+            dropped_spans: AtomicU64::new(0),
+            start_unix_nanos: unix_nanos(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    /// 开始一个span
+    pub fn start_span(&self, name: &'static str) -> ActiveSpan {
+        let enabled = self.config.enable && self.config.export_traces;
+        let mut rng = rand::thread_rng();
+        let mut trace_id = [0u8; 16];
+        let mut span_id = [0u8; 8];
+        if enabled {
+            rng.fill(&mut trace_id);
+            rng.fill(&mut span_id);
+        }
+        ActiveSpan {
+            name,
+            trace_id,
+            span_id,
+            start_unix_nanos: if enabled { unix_nanos() } else { 0 },
+            attributes: Vec::new(),
+            enabled,
+        }
+    }
+
+    /// 结束span并加入导出队列，错误结果记录为span错误状态
+    pub fn end_span<T>(&self, span: ActiveSpan, result: &Result<T>) {
+        if !span.enabled {
+            return;
+        }
+        let record = SpanRecord {
+            name: span.name,
+            trace_id: span.trace_id,
+            span_id: span.span_id,
+            start_unix_nanos: span.start_unix_nanos,
+            end_unix_nanos: unix_nanos(),
+            attributes: span.attributes,
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+        let Ok(mut spans) = self.spans.lock() else { return };
+        if spans.len() >= self.config.max_queued_spans {
+            spans.pop_front();
+            self.dropped_spans.fetch_add(1, Ordering::Relaxed);
+        }
+        spans.push_back(record);
+    }
+
+    fn resource_json(&self) -> Value {
+        json!({
+            "attributes": [
+                attribute_json("service.name", &AttributeValue::Str(self.config.service_name.clone())),
+                attribute_json("service.version", &AttributeValue::Str(env!("CARGO_PKG_VERSION").to_string())),
+            ]
+        })
+    }
+
+    fn scope_json() -> Value {
+        json!({ "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") })
+    }
+
+    /// 取出队列中的span并编码为 OTLP JSON 请求体，队列为空时返回 None
+    fn drain_traces_payload(&self) -> Option<Value> {
+        let drained: Vec<SpanRecord> = {
+            let Ok(mut spans) = self.spans.lock() else { return None };
+            spans.drain(..).collect()
+        };
+        if drained.is_empty() {
+            return None;
+        }
+
+        let spans: Vec<Value> = drained
+            .iter()
+            .map(|span| {
+                let status = match &span.error {
+                    Some(message) => json!({ "code": 2, "message": message }),
+                    None => json!({ "code": 1 }),
+                };
+                json!({
+                    "traceId": hex(&span.trace_id),
+                    "spanId": hex(&span.span_id),
+                    "name": span.name,
+                    "kind": 2,
+                    "startTimeUnixNano": span.start_unix_nanos.to_string(),
+                    "endTimeUnixNano": span.end_unix_nanos.to_string(),
+                    "attributes": span.attributes.iter().map(|(k, v)| attribute_json(k, v)).collect::<Vec<_>>(),
+                    "status": status,
+                })
+            })
+            .collect();
+
+        Some(json!({
+            "resourceSpans": [{
+                "resource": self.resource_json(),
+                "scopeSpans": [{ "scope": Self::scope_json(), "spans": spans }],
+            }]
+        }))
+    }
+
+    /// 将指标编码为 OTLP JSON 请求体（计数器为累计Sum，节点数为Gauge）
+    fn metrics_payload(&self, metrics: &ServerMetrics, peers: (usize, usize, usize)) -> Value {
+        let now = unix_nanos().to_string();
+        let start = self.start_unix_nanos.to_string();
+        let snapshot = metrics.snapshot();
+
+        let counter = |name: &str, unit: &str, value: u64| {
+            json!({
+                "name": name,
+                "unit": unit,
+                "sum": {
+                    "dataPoints": [{ "asInt": value.to_string(), "startTimeUnixNano": start, "timeUnixNano": now }],
+                    "aggregationTemporality": 2,
+                    "isMonotonic": true,
+                }
+            })
+        };
+        let gauge = |name: &str, value: u64| {
+            json!({
+                "name": name,
+                "unit": "1",
+                "gauge": { "dataPoints": [{ "asInt": value.to_string(), "timeUnixNano": now }] }
+            })
+        };
+
+        let metrics = vec![
+            counter("p2p.packets.received", "1", snapshot.packets_received),
+            counter("p2p.bytes.received", "By", snapshot.bytes_received),
+            counter("p2p.messages.handled", "1", snapshot.messages_handled),
+            counter("p2p.handle.errors", "1", snapshot.handle_errors),
+            counter("p2p.packets.dropped", "1", snapshot.packets_dropped),
+            counter("p2p.packets.expired", "1", snapshot.packets_expired),
+            counter("p2p.packets.panics", "1", snapshot.packet_panics),
+            counter("p2p.packets.banned", "1", snapshot.packets_banned),
+            counter("p2p.packets.oversized", "1", snapshot.packets_oversized),
+            counter("p2p.packets.malformed", "1", snapshot.packets_malformed),
+            counter("p2p.scanners.detected", "1", snapshot.scanners_detected),
+            counter("p2p.packets.scanner_dropped", "1", snapshot.packets_from_scanners),
+            counter("p2p.webhooks.delivered", "1", snapshot.webhooks_delivered),
+            counter("p2p.webhooks.failed", "1", snapshot.webhooks_failed),
+            counter("p2p.script.vetoes", "1", snapshot.script_vetoes),
+            counter("p2p.script.errors", "1", snapshot.script_errors),
+            counter("p2p.memory.evictions", "1", snapshot.memory_evictions),
+            counter("p2p.messages.stale", "1", snapshot.messages_stale),
+            counter("p2p.data.unhandled", "1", snapshot.data_unhandled),
+            counter("p2p.control.denied", "1", snapshot.control_denied),
+            counter("p2p.migrations", "1", snapshot.address_migrations),
+            counter("p2p.migrations.rejected", "1", snapshot.address_migrations_rejected),
+            counter("p2p.rpc.calls", "1", snapshot.rpc_calls),
+            counter("p2p.rpc.calls.failed", "1", snapshot.rpc_calls_failed),
+            counter("p2p.stun.requests", "1", snapshot.stun_requests),
+            counter("p2p.stun.errors", "1", snapshot.stun_errors),
+            counter("p2p.stun.dropped", "1", snapshot.stun_dropped),
+            counter("p2p.stun.latency_total", "us", snapshot.stun_latency_us_total),
+            counter("p2p.routed.messages", "1", snapshot.routed_messages),
+            counter("p2p.relay.packets", "1", snapshot.relay_packets),
+            counter("p2p.relay.bytes", "By", snapshot.relay_bytes),
+            counter("p2p.relay.tunnel_bytes", "By", snapshot.relay_tunnel_bytes),
+            counter("p2p.relay.mtu_drops", "1", snapshot.relay_mtu_drops),
+            counter("p2p.dormant.wakes", "1", snapshot.dormant_wakes),
+            counter("p2p.multicast.copies_saved", "1", snapshot.multicast_copies_saved),
+            counter("p2p.route.signature_drops", "1", snapshot.route_signature_drops),
+            counter("p2p.route.payload_rejections", "1", snapshot.routed_payload_rejections),
+            counter("p2p.route.broadcasts_suppressed", "1", snapshot.broadcasts_suppressed),
+            counter("p2p.handshake.cookie_challenges", "1", snapshot.handshake_cookie_challenges),
+            counter("p2p.handshake.cookie_passes", "1", snapshot.handshake_cookie_passes),
+            counter("p2p.handshake.cookie_drops", "1", snapshot.handshake_cookie_drops),
+            counter("p2p.handshake.replays_rejected", "1", snapshot.handshake_replays_rejected),
+            counter("p2p.handshake.spoof_flagged", "1", snapshot.spoof_identities_flagged),
+            counter("p2p.handshake.spoof_rejected", "1", snapshot.spoof_handshakes_rejected),
+            counter("p2p.handshake.node_info_truncated", "1", snapshot.node_info_truncated),
+            counter("p2p.handshake.node_info_rejected", "1", snapshot.node_info_rejected),
+            counter("p2p.handshake.duplicates", "1", snapshot.handshake_duplicates),
+            counter("p2p.connect.same_ip", "1", snapshot.p2p_same_ip_coordinations),
+            counter("p2p.connect.tcp_punch", "1", snapshot.tcp_punch_coordinations),
+            counter("p2p.connect.carrier_nat_relay", "1", snapshot.carrier_nat_relay_fallbacks),
+            counter("p2p.ack.coalesced", "1", snapshot.acks_coalesced),
+            counter("p2p.connect.private_path", "1", snapshot.p2p_private_path),
+            counter("p2p.connect.public_path", "1", snapshot.p2p_public_path),
+            counter("p2p.connect.relay_path", "1", snapshot.p2p_relay_path),
+            counter("p2p.connect.failed", "1", snapshot.p2p_failed),
+            counter("p2p.telemetry.dropped_spans", "1", self.dropped_spans.load(Ordering::Relaxed)),
+            gauge("p2p.peers.total", peers.0 as u64),
+            gauge("p2p.peers.authenticated", peers.1 as u64),
+            gauge("p2p.peers.connecting", peers.2 as u64),
+            gauge("p2p.stun.unique_clients", snapshot.stun_unique_clients),
+            gauge("p2p.stun.latency_max_us", snapshot.stun_latency_us_max),
+        ];
+
+        json!({
+            "resourceMetrics": [{
+                "resource": self.resource_json(),
+                "scopeMetrics": [{ "scope": Self::scope_json(), "metrics": metrics }],
+            }]
+        })
+    }
+
+    /// 启动周期性导出任务
+    pub fn start_export_task(
+        self: &Arc<Self>,
+        metrics: Arc<ServerMetrics>,
+        peer_manager: Arc<PeerManager>,
+    ) -> Result<tokio::task::JoinHandle<()>> {
+        let endpoint = HttpUrl::parse(&self.config.endpoint)?;
+        let telemetry = self.clone();
+        let interval_ms = self.config.export_interval_ms.max(100);
+
+        Ok(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(interval_ms));
+            let timeout = Duration::from_secs(5);
+            let traces_url = endpoint.join("/v1/traces");
+            let metrics_url = endpoint.join("/v1/metrics");
+
+            loop {
+                interval.tick().await;
+
+                if telemetry.config.export_traces
+                    && let Some(payload) = telemetry.drain_traces_payload()
+                {
+                    match http_client::post_json(&traces_url, payload.to_string().as_bytes(), &[], timeout).await {
+                        Ok(status) if (200..300).contains(&status) => debug!("{}", tr!("OTLP span导出完成", "OTLP span export finished")),
+                        Ok(status) => warn!("{}", tr!("OTLP span导出被拒绝: HTTP {}", "OTLP span export rejected: HTTP {}", status)),
+                        Err(e) => warn!("{}", tr!("OTLP span导出失败: {}", "OTLP span export failed: {}", e)),
+                    }
+                }
+
+                if telemetry.config.export_metrics {
+                    let stats = peer_manager.get_stats().await;
+                    let payload = telemetry.metrics_payload(
+                        &metrics,
+                        (stats.total_peers, stats.authenticated_peers, stats.connecting_peers),
+                    );
+                    match http_client::post_json(&metrics_url, payload.to_string().as_bytes(), &[], timeout).await {
+                        Ok(status) if (200..300).contains(&status) => debug!("{}", tr!("OTLP指标导出完成", "OTLP metrics export finished")),
+                        Ok(status) => warn!("{}", tr!("OTLP指标导出被拒绝: HTTP {}", "OTLP metrics export rejected: HTTP {}", status)),
+                        Err(e) => warn!("{}", tr!("OTLP指标导出失败: {}", "OTLP metrics export failed: {}", e)),
+                    }
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_export_payload() {
+        let telemetry = Telemetry::new(TelemetryConfig { enable: true, ..TelemetryConfig::default() });
+        let mut span = telemetry.start_span("p2p.handshake");
+        span.set_attribute("peer.addr", "127.0.0.1:9000");
+        span.set_attribute("payload.bytes", 42u64);
+        telemetry.end_span(span, &Err::<(), _>(anyhow::anyhow!("network_id mismatch")));
+
+        let payload = telemetry.drain_traces_payload().unwrap();
+        let span = &payload["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["name"], "p2p.handshake");
+        assert_eq!(span["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(span["status"]["code"], 2);
+        assert_eq!(span["attributes"][1]["value"]["intValue"], "42");
+        assert!(telemetry.drain_traces_payload().is_none());
+    }
+
+    #[test]
+    fn test_disabled_telemetry_records_nothing() {
+        let telemetry = Telemetry::new(TelemetryConfig::default());
+        let span = telemetry.start_span("p2p.relay");
+        telemetry.end_span(span, &Ok::<(), anyhow::Error>(()));
+        assert!(telemetry.drain_traces_payload().is_none());
+    }
+}
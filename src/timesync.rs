@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::TimeSyncConfig;
+
+/// 消息时间戳的时效判定结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    Fresh,
+    /// 校正后早于服务器时间超过允许的消息时效
+    Stale,
+    /// 校正后晚于服务器时间超过允许的误差
+    Future,
+}
+
+/// 指定时刻的 Unix 毫秒时间戳
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// 单调时钟时刻对应的 Unix 毫秒时间戳（用于还原数据包的接收时间）
+pub fn instant_to_unix_millis(instant: Instant) -> u64 {
+    let now = SystemTime::now();
+    let elapsed = Instant::now().saturating_duration_since(instant);
+    unix_millis(now.checked_sub(elapsed).unwrap_or(now))
+}
+
+/// 由单向时间戳粗略估计客户端时钟偏差（客户端时间 − 服务器时间，毫秒）
+///
+/// 结果包含一个单程网络延迟的误差；客户端上报按往返时间算得的偏差时优先使用上报值。
+pub fn estimate_offset(client_send_ms: u64, server_receive_ms: u64) -> i64 {
+    client_send_ms as i64 - server_receive_ms as i64
+}
+
+/// 检查消息时间戳是否在允许范围内
+///
+/// `offset_ms` 为该节点已知的时钟偏差。已同步的节点先把时间戳换算到服务器时钟，
+/// 只容忍 `sync_tolerance_ms` 的误差；未同步的节点按 `max_clock_skew_secs` 放宽。
+pub fn check_freshness(config: &TimeSyncConfig, timestamp_secs: u64, offset_ms: Option<i64>, now_ms: u64) -> Freshness {
+    if config.max_message_age_secs == 0 {
+        return Freshness::Fresh;
+    }
+    let tolerance = match offset_ms {
+        Some(_) => Duration::from_millis(config.sync_tolerance_ms),
+        None => Duration::from_secs(config.max_clock_skew_secs),
+    }
+    .as_millis() as i64;
+    let sent_ms = (timestamp_secs as i64).saturating_mul(1000) - offset_ms.unwrap_or(0);
+    let age_ms = now_ms as i64 - sent_ms;
+    if age_ms > (config.max_message_age_secs as i64) * 1000 + tolerance {
+        Freshness::Stale
+    } else if -age_ms > tolerance {
+        Freshness::Future
+    } else {
+        Freshness::Fresh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_corrects_drifting_clock() {
+        let config = TimeSyncConfig { max_message_age_secs: 60, max_clock_skew_secs: 300, sync_tolerance_ms: 2000 };
+        let now_ms = 1_700_000_000_000;
+        // 客户端时钟快 10 分钟：未同步时超出允许偏差，同步后校正为新消息
+        let ahead = (now_ms + 600_000) / 1000;
+        assert_eq!(check_freshness(&config, ahead, None, now_ms), Freshness::Future);
+        let offset = estimate_offset(now_ms + 600_000, now_ms);
+        assert_eq!(check_freshness(&config, ahead, Some(offset), now_ms), Freshness::Fresh);
+
+        // 校正后超过消息时效的视为过期（重放）
+        let replayed = (now_ms + 600_000 - 90_000) / 1000;
+        assert_eq!(check_freshness(&config, replayed, Some(offset), now_ms), Freshness::Stale);
+        // 未同步的节点放宽到 max_clock_skew_secs
+        assert_eq!(check_freshness(&config, (now_ms - 200_000) / 1000, None, now_ms), Freshness::Fresh);
+        assert_eq!(check_freshness(&config, (now_ms - 400_000) / 1000, None, now_ms), Freshness::Stale);
+
+        let disabled = TimeSyncConfig { max_message_age_secs: 0, ..config };
+        assert_eq!(check_freshness(&disabled, 0, None, now_ms), Freshness::Fresh);
+    }
+}
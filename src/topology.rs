@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::peer::PeerManager;
+use crate::protocol::P2PPath;
+use crate::router::MessageRouter;
+
+/// 拓扑中的节点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyNode {
+    pub id: Uuid,
+    pub name: Option<String>,
+    /// 服务器观察到的地址（仅直连节点可知）
+    pub addr: Option<SocketAddr>,
+    /// 节点上报的NAT类型
+    pub nat_type: Option<String>,
+    /// 是否与本服务器直连
+    pub direct: bool,
+}
+
+/// 拓扑中的一条链路
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct TopologyLink {
+    pub from: Uuid,
+    pub to: Uuid,
+    pub cost: u32,
+}
+
+/// 路由表条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyRoute {
+    pub destination: Uuid,
+    pub next_hop: Uuid,
+    pub distance: u32,
+}
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TopologyFormat {
+    #[default]
+    Json,
+    Dot,
+}
+
+/// 服务器已知的网络拓扑快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologySnapshot {
+    pub local_node: Uuid,
+    pub nodes: Vec<TopologyNode>,
+    pub links: Vec<TopologyLink>,
+    pub routes: Vec<TopologyRoute>,
+}
+
+impl TopologySnapshot {
+    pub fn new(local_node: Uuid) -> Self {
+        Self {
+            local_node,
+            nodes: Vec::new(),
+            links: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+
+    /// 补全只出现在链路或路由中的节点，并对链路去重
+    pub fn normalize(&mut self) {
+        let mut seen: HashSet<Uuid> = self.nodes.iter().map(|n| n.id).collect();
+        seen.insert(self.local_node);
+
+        let mut referenced = Vec::new();
+        for link in &self.links {
+            referenced.push(link.from);
+            referenced.push(link.to);
+        }
+        for route in &self.routes {
+            referenced.push(route.destination);
+            referenced.push(route.next_hop);
+        }
+        for id in referenced {
+            if seen.insert(id) {
+                self.nodes.push(TopologyNode {
+                    id,
+                    name: None,
+                    addr: None,
+                    nat_type: None,
+                    direct: false,
+                });
+            }
+        }
+
+        let mut unique = HashSet::new();
+        self.links.retain(|l| unique.insert(l.clone()));
+    }
+
+    /// 渲染为 GraphViz DOT 格式
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph p2p_overlay {{");
+        let _ = writeln!(out, "    \"{}\" [label=\"server\\n{}\", shape=doublecircle];", self.local_node, short_id(&self.local_node));
+
+        for node in &self.nodes {
+            // 各字段分别转义后再用 `\n` 连接，节点自报的名称无法跳出标签
+            let mut label = escape_dot(&node.name.clone().unwrap_or_else(|| short_id(&node.id)));
+            if let Some(addr) = node.addr {
+                let _ = write!(label, "\\n{}", escape_dot(&addr.to_string()));
+            }
+            if let Some(nat) = &node.nat_type {
+                let _ = write!(label, "\\nNAT: {}", escape_dot(nat));
+            }
+            let style = if node.direct { "solid" } else { "dashed" };
+            let _ = writeln!(out, "    \"{}\" [label=\"{}\", style={}];", node.id, label, style);
+        }
+
+        for link in &self.links {
+            let _ = writeln!(out, "    \"{}\" -> \"{}\" [label=\"{}\"];", link.from, link.to, link.cost);
+        }
+
+        for route in &self.routes {
+            if route.destination == route.next_hop {
+                continue;
+            }
+            let _ = writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [style=dotted, color=gray, label=\"via {} ({})\"];",
+                self.local_node,
+                route.destination,
+                short_id(&route.next_hop),
+                route.distance
+            );
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// 收集服务器已知的网络拓扑：已认证节点、直连链路、节点间的 P2P 会话、链路状态链路与路由表
+pub async fn collect(
+    local_id: Uuid,
+    peer_manager: &Arc<PeerManager>,
+    router: &Arc<MessageRouter>,
+) -> TopologySnapshot {
+    let mut snapshot = TopologySnapshot::new(local_id);
+
+    for peer in peer_manager.get_authenticated_peers().await {
+        let guard = peer.read().await;
+        snapshot.nodes.push(TopologyNode {
+            id: guard.id,
+            name: guard.node_info.as_ref().map(|n| n.name.to_string()),
+            addr: Some(guard.addr()),
+            nat_type: guard.nat_type.clone(),
+            direct: true,
+        });
+        snapshot.links.push(TopologyLink { from: local_id, to: guard.id, cost: 1 });
+    }
+
+    // 节点之间的直连会话，测过带宽的按带宽折算开销
+    let bandwidth = peer_manager.bandwidth();
+    for (a, b, path) in peer_manager.sessions().pairs() {
+        if matches!(path, P2PPath::Public | P2PPath::Private) {
+            let cost = bandwidth.link_cost(&a, &b).unwrap_or(1);
+            snapshot.links.push(TopologyLink { from: a, to: b, cost });
+        }
+    }
+
+    for (from, to, cost) in router.get_link_state_links().await {
+        snapshot.links.push(TopologyLink { from, to, cost });
+    }
+
+    snapshot.routes = router
+        .get_routing_table_snapshot()
+        .await
+        .into_iter()
+        .map(|(destination, next_hop, distance)| TopologyRoute { destination, next_hop, distance })
+        .collect();
+
+    snapshot.normalize();
+    snapshot
+}
+
+fn short_id(id: &Uuid) -> String {
+    id.to_string()[..8].to_string()
+}
+
+/// 转义 DOT 引号字符串中的反斜杠与双引号
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_and_dot_export() {
+        let local = Uuid::new_v4();
+        let peer = Uuid::new_v4();
+        let remote = Uuid::new_v4();
+
+        let mut snapshot = TopologySnapshot::new(local);
+        snapshot.nodes.push(TopologyNode {
+            id: peer,
+            name: Some("peer".to_string()),
+            addr: Some("127.0.0.1:9000".parse().unwrap()),
+            nat_type: Some("FullCone".to_string()),
+            direct: true,
+        });
+        let link = TopologyLink { from: local, to: peer, cost: 1 };
+        snapshot.links.push(link.clone());
+        snapshot.links.push(link);
+        snapshot.routes.push(TopologyRoute { destination: remote, next_hop: peer, distance: 2 });
+        snapshot.normalize();
+
+        assert_eq!(snapshot.links.len(), 1);
+        assert!(snapshot.nodes.iter().any(|n| n.id == remote && !n.direct));
+
+        let dot = snapshot.to_dot();
+        assert!(dot.starts_with("digraph"));
+        assert!(dot.contains("NAT: FullCone"));
+        assert!(dot.contains(&format!("\"{}\" -> \"{}\"", local, peer)));
+    }
+
+    #[test]
+    fn test_dot_label_escapes_backslash_and_quote() {
+        let mut snapshot = TopologySnapshot::new(Uuid::new_v4());
+        let peer = Uuid::new_v4();
+        snapshot.nodes.push(TopologyNode {
+            id: peer,
+            name: Some("evil\\\"];\"x\" [shape=box\\".to_string()),
+            addr: Some("127.0.0.1:9000".parse().unwrap()),
+            nat_type: Some("Full\"Cone".to_string()),
+            direct: true,
+        });
+
+        let dot = snapshot.to_dot();
+        let expected = format!(
+            "    \"{}\" [label=\"evil\\\\\\\"];\\\"x\\\" [shape=box\\\\\\n127.0.0.1:9000\\nNAT: Full\\\"Cone\", style=solid];",
+            peer
+        );
+        assert!(dot.contains(&expected), "{}", dot);
+    }
+}
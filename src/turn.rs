@@ -0,0 +1,664 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use log::{debug, info, warn};
+use md5::{Digest, Md5};
+use rand::Rng;
+use sha1::Sha1;
+use tokio::net::UdpSocket;
+
+use crate::stun_protocol::{
+    StunAttribute, StunMessage,
+    STUN_ATTR_MESSAGE_INTEGRITY, STUN_ATTR_NONCE, STUN_ATTR_REALM, STUN_ATTR_USERNAME, STUN_ATTR_XOR_MAPPED_ADDRESS,
+    TURN_ALLOCATE_REQUEST, TURN_ATTR_CHANNEL_NUMBER, TURN_ATTR_DATA, TURN_ATTR_LIFETIME, TURN_ATTR_REQUESTED_TRANSPORT,
+    TURN_ATTR_XOR_PEER_ADDRESS, TURN_ATTR_XOR_RELAYED_ADDRESS, TURN_CHANNEL_BIND_REQUEST,
+    TURN_CREATE_PERMISSION_REQUEST, TURN_DATA_INDICATION, TURN_REFRESH_REQUEST, TURN_SEND_INDICATION,
+    create_software_attribute, create_xor_address_attribute, decode_address_attribute, success_response_type,
+};
+use crate::stun_server::TurnConfig;
+use crate::config::NetworkConfig;
+use crate::sockopt;
+use crate::tr;
+
+/// 权限有效期（RFC 5766 固定为5分钟）
+const PERMISSION_LIFETIME: Duration = Duration::from_secs(300);
+/// 通道号范围
+const CHANNEL_MIN: u16 = 0x4000;
+const CHANNEL_MAX: u16 = 0x7FFE;
+/// REQUESTED-TRANSPORT 中的 UDP 协议号
+const TRANSPORT_UDP: u8 = 17;
+
+type HmacSha1 = Hmac<Sha1>;
+
+/// 长期凭证密钥：MD5(username:realm:password)
+pub fn long_term_key(username: &str, realm: &str, password: &str) -> [u8; 16] {
+    Md5::digest(format!("{}:{}:{}", username, realm, password).as_bytes()).into()
+}
+
+/// 序列化消息并在末尾追加 MESSAGE-INTEGRITY
+pub fn encode_with_integrity(message: &StunMessage, key: &[u8]) -> Vec<u8> {
+    let mut bytes = message.to_bytes();
+    // 长度字段需包含 MESSAGE-INTEGRITY 属性本身
+    let length = (bytes.len() - 20 + 24) as u16;
+    bytes[2..4].copy_from_slice(&length.to_be_bytes());
+    let mut mac = HmacSha1::new_from_slice(key).expect("HMAC接受任意长度的密钥");
+    mac.update(&bytes);
+    bytes.extend_from_slice(&STUN_ATTR_MESSAGE_INTEGRITY.to_be_bytes());
+    bytes.extend_from_slice(&20u16.to_be_bytes());
+    bytes.extend_from_slice(&mac.finalize().into_bytes());
+    bytes
+}
+
+/// 校验原始数据包中的 MESSAGE-INTEGRITY
+pub fn verify_integrity(raw: &[u8], key: &[u8]) -> bool {
+    let Some(offset) = integrity_offset(raw) else { return false };
+    if offset + 24 > raw.len() {
+        return false;
+    }
+    let mut prefix = raw[..offset].to_vec();
+    let length = (offset - 20 + 24) as u16;
+    prefix[2..4].copy_from_slice(&length.to_be_bytes());
+    let mut mac = HmacSha1::new_from_slice(key).expect("HMAC接受任意长度的密钥");
+    mac.update(&prefix);
+    mac.verify_slice(&raw[offset + 4..offset + 24]).is_ok()
+}
+
+/// MESSAGE-INTEGRITY 属性在原始数据包中的偏移
+fn integrity_offset(raw: &[u8]) -> Option<usize> {
+    let mut offset = 20;
+    while offset + 4 <= raw.len() {
+        let attr_type = u16::from_be_bytes([raw[offset], raw[offset + 1]]);
+        if attr_type == STUN_ATTR_MESSAGE_INTEGRITY {
+            return Some(offset);
+        }
+        let length = u16::from_be_bytes([raw[offset + 2], raw[offset + 3]]) as usize;
+        offset += 4 + length + (4 - length % 4) % 4;
+    }
+    None
+}
+
+/// 是否为 ChannelData 消息（首两位为 01）
+pub fn is_channel_data(data: &[u8]) -> bool {
+    data.len() >= 4 && (0x40..=0x7f).contains(&data[0])
+}
+
+/// 构造 ChannelData 消息（UDP上同样按4字节对齐）
+pub fn channel_data(channel: u16, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(4 + payload.len() + 3);
+    bytes.extend_from_slice(&channel.to_be_bytes());
+    bytes.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(payload);
+    bytes.resize(bytes.len() + (4 - payload.len() % 4) % 4, 0);
+    bytes
+}
+
+fn lifetime_attribute(lifetime: Duration) -> StunAttribute {
+    StunAttribute {
+        attr_type: TURN_ATTR_LIFETIME,
+        length: 4,
+        value: (lifetime.as_secs() as u32).to_be_bytes().to_vec(),
+    }
+}
+
+fn text_attribute(attr_type: u16, text: &str) -> StunAttribute {
+    StunAttribute { attr_type, length: text.len() as u16, value: text.as_bytes().to_vec() }
+}
+
+/// 请求失败时返回给客户端的错误
+#[derive(Debug, Clone, Copy)]
+struct TurnError {
+    code: u16,
+    reason: &'static str,
+}
+
+impl TurnError {
+    const BAD_REQUEST: Self = Self { code: 400, reason: "Bad Request" };
+    const UNAUTHORIZED: Self = Self { code: 401, reason: "Unauthorized" };
+    const ALLOCATION_MISMATCH: Self = Self { code: 437, reason: "Allocation Mismatch" };
+    const STALE_NONCE: Self = Self { code: 438, reason: "Stale Nonce" };
+    const WRONG_CREDENTIALS: Self = Self { code: 441, reason: "Wrong Credentials" };
+    const UNSUPPORTED_TRANSPORT: Self = Self { code: 442, reason: "Unsupported Transport Protocol" };
+    const QUOTA_REACHED: Self = Self { code: 486, reason: "Allocation Quota Reached" };
+    const INSUFFICIENT_CAPACITY: Self = Self { code: 508, reason: "Insufficient Capacity" };
+}
+
+/// 客户端的一个中继分配
+struct Allocation {
+    username: String,
+    relay_socket: Arc<UdpSocket>,
+    expires_at: Instant,
+    permissions: HashMap<IpAddr, Instant>,
+    channels: HashMap<u16, SocketAddr>,
+    relayed_bytes: u64,
+    relay_task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.relay_task.abort();
+    }
+}
+
+impl Allocation {
+    fn has_permission(&self, ip: &IpAddr) -> bool {
+        self.permissions.get(ip).is_some_and(|expires| *expires > Instant::now())
+    }
+
+    fn channel_for(&self, peer: SocketAddr) -> Option<u16> {
+        self.channels.iter().find(|(_, p)| **p == peer).map(|(c, _)| *c)
+    }
+
+    /// 计入中继字节数，超出配额时返回 `false`
+    fn charge(&mut self, bytes: usize, quota: u64) -> bool {
+        if quota > 0 && self.relayed_bytes + bytes as u64 > quota {
+            return false;
+        }
+        self.relayed_bytes += bytes as u64;
+        true
+    }
+}
+
+/// TURN 中继服务（与STUN服务器共用监听套接字）
+///
+/// 只支持 UDP 中继与 IPv4，认证使用长期凭证机制。
+pub struct TurnServer {
+    config: TurnConfig,
+    software: String,
+    socket: Arc<UdpSocket>,
+    relay_ip: IpAddr,
+    advertised_ip: IpAddr,
+    /// 中继套接字参数（使用中继流量的DSCP标记）
+    network: NetworkConfig,
+    nonce: String,
+    /// 按客户端地址（五元组）索引的分配
+    allocations: Mutex<HashMap<SocketAddr, Allocation>>,
+}
+
+impl TurnServer {
+    pub fn new(config: TurnConfig, software: String, socket: Arc<UdpSocket>, listen_ip: IpAddr, network: NetworkConfig) -> Arc<Self> {
+        let relay_ip = config.relay_ip.unwrap_or(listen_ip);
+        let advertised_ip = config.external_ip.unwrap_or(relay_ip);
+        if advertised_ip.is_unspecified() {
+            warn!("{}", tr!("TURN中继地址为 {}，客户端将无法使用，请设置 turn.external_ip", "TURN relay address is {}, clients will not be able to use it; set turn.external_ip", advertised_ip));
+        }
+        let nonce = format!("{:016x}", rand::thread_rng().r#gen::<u64>());
+        info!("{}", tr!("TURN已启用，realm: {}，中继IP: {}", "TURN enabled, realm: {}, relay IP: {}", config.realm, advertised_ip));
+        Arc::new(Self {
+            config,
+            software,
+            socket,
+            relay_ip,
+            advertised_ip,
+            network,
+            nonce,
+            allocations: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 当前分配数
+    pub fn allocation_count(&self) -> usize {
+        self.allocations.lock().unwrap().len()
+    }
+
+    /// 清理过期的分配，返回清理数量
+    pub fn cleanup_expired(&self) -> usize {
+        let now = Instant::now();
+        let mut allocations = self.allocations.lock().unwrap();
+        let before = allocations.len();
+        allocations.retain(|client, allocation| {
+            let alive = allocation.expires_at > now;
+            if !alive {
+                info!("{}", tr!("TURN分配已过期: {}", "TURN allocation expired: {}", client));
+            }
+            alive
+        });
+        before - allocations.len()
+    }
+
+    /// 处理TURN消息，非TURN方法返回 `false` 交由STUN服务器处理
+    pub async fn handle_message(self: &Arc<Self>, request: &StunMessage, raw: &[u8], client: SocketAddr) -> Result<bool> {
+        match request.message_type {
+            TURN_SEND_INDICATION => {
+                self.handle_send_indication(request, client).await;
+                Ok(true)
+            }
+            TURN_ALLOCATE_REQUEST | TURN_REFRESH_REQUEST | TURN_CREATE_PERMISSION_REQUEST | TURN_CHANNEL_BIND_REQUEST => {
+                let (username, key) = match self.authenticate(request, raw) {
+                    Ok(credentials) => credentials,
+                    Err(error) => {
+                        self.send_error(request, client, error).await?;
+                        return Ok(true);
+                    }
+                };
+                let result = match request.message_type {
+                    TURN_ALLOCATE_REQUEST => self.allocate(request, client, &username).await,
+                    TURN_REFRESH_REQUEST => self.refresh(request, client, &username),
+                    TURN_CREATE_PERMISSION_REQUEST => self.create_permission(request, client, &username),
+                    _ => self.channel_bind(request, client, &username),
+                };
+                match result {
+                    Ok(attributes) => {
+                        let mut response = StunMessage::new(success_response_type(request.message_type), request.transaction_id);
+                        for attribute in attributes {
+                            response.add_attribute(attribute);
+                        }
+                        response.add_attribute(create_software_attribute(&self.software));
+                        self.socket.send_to(&encode_with_integrity(&response, &key), client).await?;
+                    }
+                    Err(error) => self.send_error(request, client, error).await?,
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// 长期凭证认证，成功返回用户名与密钥
+    fn authenticate(&self, request: &StunMessage, raw: &[u8]) -> std::result::Result<(String, [u8; 16]), TurnError> {
+        if request.attribute(STUN_ATTR_MESSAGE_INTEGRITY).is_none() {
+            return Err(TurnError::UNAUTHORIZED);
+        }
+        let text = |attr_type| {
+            request
+                .attribute(attr_type)
+                .and_then(|a| String::from_utf8(a.value.clone()).ok())
+        };
+        let (Some(username), Some(realm), Some(nonce)) =
+            (text(STUN_ATTR_USERNAME), text(STUN_ATTR_REALM), text(STUN_ATTR_NONCE))
+        else {
+            return Err(TurnError::BAD_REQUEST);
+        };
+        if nonce != self.nonce {
+            return Err(TurnError::STALE_NONCE);
+        }
+        let Some(password) = self.config.users.get(&username).filter(|_| realm == self.config.realm) else {
+            return Err(TurnError::UNAUTHORIZED);
+        };
+        let key = long_term_key(&username, &self.config.realm, password);
+        if !verify_integrity(raw, &key) {
+            return Err(TurnError::UNAUTHORIZED);
+        }
+        Ok((username, key))
+    }
+
+    async fn allocate(
+        self: &Arc<Self>,
+        request: &StunMessage,
+        client: SocketAddr,
+        username: &str,
+    ) -> std::result::Result<Vec<StunAttribute>, TurnError> {
+        match request.attribute(TURN_ATTR_REQUESTED_TRANSPORT) {
+            Some(attr) if attr.value.first() == Some(&TRANSPORT_UDP) => {}
+            Some(_) => return Err(TurnError::UNSUPPORTED_TRANSPORT),
+            None => return Err(TurnError::BAD_REQUEST),
+        }
+        self.check_allocation_quota(client, username)?;
+
+        let relay_socket = sockopt::bind_udp(SocketAddr::new(self.relay_ip, 0), &self.network, self.network.relay_dscp, None)
+            .map(Arc::new)
+            .map_err(|e| {
+                warn!("{}", tr!("绑定TURN中继套接字失败: {}", "Failed to bind TURN relay socket: {}", e));
+                TurnError::INSUFFICIENT_CAPACITY
+            })?;
+        let relayed_addr = SocketAddr::new(
+            self.advertised_ip,
+            relay_socket.local_addr().map_err(|_| TurnError::INSUFFICIENT_CAPACITY)?.port(),
+        );
+        let lifetime = self.requested_lifetime(request);
+        let relay_task = self.spawn_relay_task(client, relay_socket.clone());
+
+        let mut allocations = self.allocations.lock().unwrap();
+        // 绑定套接字期间可能已有同一客户端的分配
+        if allocations.contains_key(&client) {
+            relay_task.abort();
+            return Err(TurnError::ALLOCATION_MISMATCH);
+        }
+        allocations.insert(client, Allocation {
+            username: username.to_string(),
+            relay_socket,
+            expires_at: Instant::now() + lifetime,
+            permissions: HashMap::new(),
+            channels: HashMap::new(),
+            relayed_bytes: 0,
+            relay_task,
+        });
+        info!("{}", tr!("TURN分配: {} ({}) -> {}，有效期 {} 秒", "TURN allocation: {} ({}) -> {}, lifetime {} seconds", client, username, relayed_addr, lifetime.as_secs()));
+
+        Ok(vec![
+            create_xor_address_attribute(TURN_ATTR_XOR_RELAYED_ADDRESS, relayed_addr),
+            lifetime_attribute(lifetime),
+            create_xor_address_attribute(STUN_ATTR_XOR_MAPPED_ADDRESS, client),
+        ])
+    }
+
+    fn check_allocation_quota(&self, client: SocketAddr, username: &str) -> std::result::Result<(), TurnError> {
+        let allocations = self.allocations.lock().unwrap();
+        if allocations.contains_key(&client) {
+            return Err(TurnError::ALLOCATION_MISMATCH);
+        }
+        if allocations.len() >= self.config.max_allocations {
+            return Err(TurnError::INSUFFICIENT_CAPACITY);
+        }
+        let per_user = allocations.values().filter(|a| a.username == username).count();
+        if per_user >= self.config.max_allocations_per_user {
+            return Err(TurnError::QUOTA_REACHED);
+        }
+        Ok(())
+    }
+
+    fn refresh(
+        &self,
+        request: &StunMessage,
+        client: SocketAddr,
+        username: &str,
+    ) -> std::result::Result<Vec<StunAttribute>, TurnError> {
+        let lifetime = self.requested_lifetime(request);
+        let mut allocations = self.allocations.lock().unwrap();
+        let allocation = owned_allocation(&mut allocations, client, username)?;
+        if lifetime.is_zero() {
+            allocations.remove(&client);
+            info!("{}", tr!("TURN分配已释放: {}", "TURN allocation released: {}", client));
+        } else {
+            allocation.expires_at = Instant::now() + lifetime;
+        }
+        Ok(vec![lifetime_attribute(lifetime)])
+    }
+
+    fn create_permission(
+        &self,
+        request: &StunMessage,
+        client: SocketAddr,
+        username: &str,
+    ) -> std::result::Result<Vec<StunAttribute>, TurnError> {
+        let peers: Vec<SocketAddr> = request
+            .attributes
+            .iter()
+            .filter(|a| a.attr_type == TURN_ATTR_XOR_PEER_ADDRESS)
+            .map(|a| decode_address_attribute(&a.value, true).ok_or(TurnError::BAD_REQUEST))
+            .collect::<std::result::Result<_, _>>()?;
+        if peers.is_empty() {
+            return Err(TurnError::BAD_REQUEST);
+        }
+        let mut allocations = self.allocations.lock().unwrap();
+        let allocation = owned_allocation(&mut allocations, client, username)?;
+        for peer in peers {
+            allocation.permissions.insert(peer.ip(), Instant::now() + PERMISSION_LIFETIME);
+            debug!("{}", tr!("TURN权限: {} -> {}", "TURN permission: {} -> {}", client, peer.ip()));
+        }
+        Ok(Vec::new())
+    }
+
+    fn channel_bind(
+        &self,
+        request: &StunMessage,
+        client: SocketAddr,
+        username: &str,
+    ) -> std::result::Result<Vec<StunAttribute>, TurnError> {
+        let channel = request
+            .attribute(TURN_ATTR_CHANNEL_NUMBER)
+            .filter(|a| a.value.len() >= 2)
+            .map(|a| u16::from_be_bytes([a.value[0], a.value[1]]))
+            .filter(|c| (CHANNEL_MIN..=CHANNEL_MAX).contains(c))
+            .ok_or(TurnError::BAD_REQUEST)?;
+        let peer = request
+            .attribute(TURN_ATTR_XOR_PEER_ADDRESS)
+            .and_then(|a| decode_address_attribute(&a.value, true))
+            .ok_or(TurnError::BAD_REQUEST)?;
+
+        let mut allocations = self.allocations.lock().unwrap();
+        let allocation = owned_allocation(&mut allocations, client, username)?;
+        // 通道号与对端必须一一对应
+        let channel_conflict = allocation.channels.get(&channel).is_some_and(|p| *p != peer);
+        let peer_conflict = allocation.channel_for(peer).is_some_and(|c| c != channel);
+        if channel_conflict || peer_conflict {
+            return Err(TurnError::BAD_REQUEST);
+        }
+        allocation.channels.insert(channel, peer);
+        allocation.permissions.insert(peer.ip(), Instant::now() + PERMISSION_LIFETIME);
+        debug!("{}", tr!("TURN通道绑定: {} 通道 {:#06x} -> {}", "TURN channel bind: {} channel {:#06x} -> {}", client, channel, peer));
+        Ok(Vec::new())
+    }
+
+    /// Send 指示：客户端 -> 对端（指示没有响应，失败时静默丢弃）
+    async fn handle_send_indication(&self, request: &StunMessage, client: SocketAddr) {
+        let peer = request
+            .attribute(TURN_ATTR_XOR_PEER_ADDRESS)
+            .and_then(|a| decode_address_attribute(&a.value, true));
+        let (Some(peer), Some(data)) = (peer, request.attribute(TURN_ATTR_DATA)) else {
+            debug!("{}", tr!("丢弃来自 {} 的无效Send指示", "Dropping invalid Send indication from {}", client));
+            return;
+        };
+        self.relay_to_peer(client, peer, &data.value).await;
+    }
+
+    /// ChannelData：客户端 -> 对端
+    pub async fn handle_channel_data(&self, data: &[u8], client: SocketAddr) {
+        let channel = u16::from_be_bytes([data[0], data[1]]);
+        let length = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let Some(payload) = data.get(4..4 + length) else {
+            debug!("{}", tr!("丢弃来自 {} 的截断ChannelData", "Dropping truncated ChannelData from {}", client));
+            return;
+        };
+        let peer = self
+            .allocations
+            .lock()
+            .unwrap()
+            .get(&client)
+            .and_then(|a| a.channels.get(&channel).copied());
+        match peer {
+            Some(peer) => self.relay_to_peer(client, peer, payload).await,
+            None => debug!("{}", tr!("丢弃来自 {} 的未绑定通道 {:#06x} 数据", "Dropping data from {} on unbound channel {:#06x}", client, channel)),
+        }
+    }
+
+    async fn relay_to_peer(&self, client: SocketAddr, peer: SocketAddr, payload: &[u8]) {
+        let socket = {
+            let mut allocations = self.allocations.lock().unwrap();
+            let Some(allocation) = allocations.get_mut(&client) else { return };
+            if !allocation.has_permission(&peer.ip()) {
+                debug!("{}", tr!("TURN: {} 没有到 {} 的权限，丢弃数据", "TURN: {} has no permission for {}, dropping data", client, peer));
+                return;
+            }
+            if !allocation.charge(payload.len(), self.config.allocation_quota_bytes) {
+                debug!("{}", tr!("TURN: {} 的中继配额已用尽", "TURN: relay quota of {} exhausted", client));
+                return;
+            }
+            allocation.relay_socket.clone()
+        };
+        if let Err(e) = socket.send_to(payload, peer).await {
+            debug!("{}", tr!("TURN中继发送到 {} 失败: {}", "TURN relay send to {} failed: {}", peer, e));
+        }
+    }
+
+    /// 对端 -> 客户端：有通道时使用 ChannelData，否则使用 Data 指示
+    async fn relay_from_peer(&self, client: SocketAddr, peer: SocketAddr, payload: &[u8]) {
+        let packet = {
+            let mut allocations = self.allocations.lock().unwrap();
+            let Some(allocation) = allocations.get_mut(&client) else { return };
+            if !allocation.has_permission(&peer.ip()) {
+                debug!("{}", tr!("TURN: 丢弃来自未授权对端 {} 的数据", "TURN: dropping data from unauthorized peer {}", peer));
+                return;
+            }
+            if !allocation.charge(payload.len(), self.config.allocation_quota_bytes) {
+                debug!("{}", tr!("TURN: {} 的中继配额已用尽", "TURN: relay quota of {} exhausted", client));
+                return;
+            }
+            match allocation.channel_for(peer) {
+                Some(channel) => channel_data(channel, payload),
+                None => {
+                    let mut indication = StunMessage::new(TURN_DATA_INDICATION, rand::thread_rng().r#gen());
+                    indication.add_attribute(create_xor_address_attribute(TURN_ATTR_XOR_PEER_ADDRESS, peer));
+                    indication.add_attribute(StunAttribute {
+                        attr_type: TURN_ATTR_DATA,
+                        length: payload.len() as u16,
+                        value: payload.to_vec(),
+                    });
+                    indication.to_bytes()
+                }
+            }
+        };
+        if let Err(e) = self.socket.send_to(&packet, client).await {
+            debug!("{}", tr!("TURN中继发送到客户端 {} 失败: {}", "TURN relay send to client {} failed: {}", client, e));
+        }
+    }
+
+    fn spawn_relay_task(self: &Arc<Self>, client: SocketAddr, relay_socket: Arc<UdpSocket>) -> tokio::task::JoinHandle<()> {
+        let turn: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut buffer = vec![0u8; 65535];
+            loop {
+                let (len, peer) = match relay_socket.recv_from(&mut buffer).await {
+                    Ok(received) => received,
+                    Err(e) => {
+                        debug!("{}", tr!("TURN中继套接字接收失败: {}", "TURN relay socket receive failed: {}", e));
+                        continue;
+                    }
+                };
+                let Some(turn) = turn.upgrade() else { break };
+                turn.relay_from_peer(client, peer, &buffer[..len]).await;
+            }
+        })
+    }
+
+    fn requested_lifetime(&self, request: &StunMessage) -> Duration {
+        let requested = request
+            .attribute(TURN_ATTR_LIFETIME)
+            .filter(|a| a.value.len() >= 4)
+            .map(|a| u32::from_be_bytes([a.value[0], a.value[1], a.value[2], a.value[3]]) as u64)
+            .unwrap_or(self.config.default_lifetime_secs);
+        Duration::from_secs(requested.min(self.config.max_lifetime_secs))
+    }
+
+    async fn send_error(&self, request: &StunMessage, client: SocketAddr, error: TurnError) -> Result<()> {
+        let mut response = StunMessage::new_error_response_for(
+            request.message_type,
+            request.transaction_id,
+            error.code,
+            error.reason,
+        );
+        // 认证挑战需携带 realm 与 nonce
+        if error.code == TurnError::UNAUTHORIZED.code || error.code == TurnError::STALE_NONCE.code {
+            response.add_attribute(text_attribute(STUN_ATTR_REALM, &self.config.realm));
+            response.add_attribute(text_attribute(STUN_ATTR_NONCE, &self.nonce));
+        }
+        response.add_attribute(create_software_attribute(&self.software));
+        self.socket
+            .send_to(&response.to_bytes(), client)
+            .await
+            .context("发送TURN错误响应失败")?;
+        debug!("{}", tr!("TURN请求 {:04x} 来自 {} 失败: {} {}", "TURN request {:04x} from {} failed: {} {}", request.message_type, client, error.code, error.reason));
+        Ok(())
+    }
+}
+
+/// 查找客户端的分配并校验属于当前用户
+fn owned_allocation<'a>(
+    allocations: &'a mut HashMap<SocketAddr, Allocation>,
+    client: SocketAddr,
+    username: &str,
+) -> std::result::Result<&'a mut Allocation, TurnError> {
+    match allocations.get_mut(&client) {
+        Some(allocation) if allocation.username == username => Ok(allocation),
+        Some(_) => Err(TurnError::WRONG_CREDENTIALS),
+        None => Err(TurnError::ALLOCATION_MISMATCH),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stun_protocol::STUN_ATTR_ERROR_CODE;
+    use crate::stun_server::{StunServer, StunServerConfig};
+    use tokio::time::timeout;
+
+    async fn request(socket: &UdpSocket, server: SocketAddr, bytes: &[u8]) -> StunMessage {
+        socket.send_to(bytes, server).await.unwrap();
+        let mut buffer = vec![0u8; 2048];
+        let (len, _) = timeout(Duration::from_secs(2), socket.recv_from(&mut buffer)).await.unwrap().unwrap();
+        StunMessage::from_bytes(&buffer[..len]).unwrap()
+    }
+
+    fn authed(message_type: u16, nonce: &str, key: &[u8], attributes: Vec<StunAttribute>) -> Vec<u8> {
+        let mut message = StunMessage::new(message_type, rand::thread_rng().r#gen());
+        for attribute in attributes {
+            message.add_attribute(attribute);
+        }
+        message.add_attribute(text_attribute(STUN_ATTR_USERNAME, "alice"));
+        message.add_attribute(text_attribute(STUN_ATTR_REALM, "p2p"));
+        message.add_attribute(text_attribute(STUN_ATTR_NONCE, nonce));
+        encode_with_integrity(&message, key)
+    }
+
+    #[tokio::test]
+    async fn test_allocate_permission_and_channel_relay() {
+        let mut config = StunServerConfig::default();
+        config.turn.enable = true;
+        config.turn.users.insert("alice".to_string(), "secret".to_string());
+        let stun = Arc::new(StunServer::new(config, "127.0.0.1:0".parse().unwrap()).await.unwrap());
+        let server = stun.local_addr();
+        let runner = stun.clone();
+        tokio::spawn(async move { runner.run().await });
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let transport = StunAttribute { attr_type: TURN_ATTR_REQUESTED_TRANSPORT, length: 4, value: vec![TRANSPORT_UDP, 0, 0, 0] };
+
+        // 未认证的分配请求收到 401 挑战
+        let mut unauthenticated = StunMessage::new(TURN_ALLOCATE_REQUEST, [1; 12]);
+        unauthenticated.add_attribute(transport.clone());
+        let challenge = request(&client, server, &unauthenticated.to_bytes()).await;
+        assert_eq!(challenge.attribute(STUN_ATTR_ERROR_CODE).unwrap().value[2..4], [4, 1]);
+        let nonce = String::from_utf8(challenge.attribute(STUN_ATTR_NONCE).unwrap().value.clone()).unwrap();
+
+        // 错误密码被拒绝
+        let wrong = long_term_key("alice", "p2p", "wrong");
+        let denied = request(&client, server, &authed(TURN_ALLOCATE_REQUEST, &nonce, &wrong, vec![transport.clone()])).await;
+        assert!(denied.attribute(STUN_ATTR_ERROR_CODE).is_some());
+
+        let key = long_term_key("alice", "p2p", "secret");
+        let allocated = request(&client, server, &authed(TURN_ALLOCATE_REQUEST, &nonce, &key, vec![transport])).await;
+        assert_eq!(allocated.message_type, success_response_type(TURN_ALLOCATE_REQUEST));
+        let relayed = decode_address_attribute(&allocated.attribute(TURN_ATTR_XOR_RELAYED_ADDRESS).unwrap().value, true).unwrap();
+
+        // 未授权对端的数据被丢弃；创建权限后经 Data 指示送达
+        let peer_addr = peer.local_addr().unwrap();
+        let peer_attr = create_xor_address_attribute(TURN_ATTR_XOR_PEER_ADDRESS, peer_addr);
+        let permitted = request(&client, server, &authed(TURN_CREATE_PERMISSION_REQUEST, &nonce, &key, vec![peer_attr.clone()])).await;
+        assert_eq!(permitted.message_type, success_response_type(TURN_CREATE_PERMISSION_REQUEST));
+
+        let mut send = StunMessage::new(TURN_SEND_INDICATION, [2; 12]);
+        send.add_attribute(peer_attr.clone());
+        send.add_attribute(StunAttribute { attr_type: TURN_ATTR_DATA, length: 5, value: b"hello".to_vec() });
+        client.send_to(&send.to_bytes(), server).await.unwrap();
+        let mut buffer = vec![0u8; 2048];
+        let (len, from) = timeout(Duration::from_secs(2), peer.recv_from(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!((&buffer[..len], from), (b"hello".as_slice(), relayed));
+
+        peer.send_to(b"world", relayed).await.unwrap();
+        let (len, _) = timeout(Duration::from_secs(2), client.recv_from(&mut buffer)).await.unwrap().unwrap();
+        let data = StunMessage::from_bytes(&buffer[..len]).unwrap();
+        assert_eq!(data.message_type, TURN_DATA_INDICATION);
+        assert_eq!(data.attribute(TURN_ATTR_DATA).unwrap().value, b"world");
+
+        // 绑定通道后使用 ChannelData 双向传输
+        let channel = StunAttribute { attr_type: TURN_ATTR_CHANNEL_NUMBER, length: 4, value: vec![0x40, 0x01, 0, 0] };
+        let bound = request(&client, server, &authed(TURN_CHANNEL_BIND_REQUEST, &nonce, &key, vec![channel, peer_attr])).await;
+        assert_eq!(bound.message_type, success_response_type(TURN_CHANNEL_BIND_REQUEST));
+
+        client.send_to(&channel_data(0x4001, b"abc"), server).await.unwrap();
+        let (len, _) = timeout(Duration::from_secs(2), peer.recv_from(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer[..len], b"abc");
+        peer.send_to(b"xyz", relayed).await.unwrap();
+        let (len, _) = timeout(Duration::from_secs(2), client.recv_from(&mut buffer)).await.unwrap().unwrap();
+        assert_eq!(&buffer[..len], channel_data(0x4001, b"xyz").as_slice());
+
+        // 生命周期为0的刷新释放分配
+        let zero = StunAttribute { attr_type: TURN_ATTR_LIFETIME, length: 4, value: vec![0; 4] };
+        let released = request(&client, server, &authed(TURN_REFRESH_REQUEST, &nonce, &key, vec![zero])).await;
+        assert_eq!(released.message_type, success_response_type(TURN_REFRESH_REQUEST));
+        assert_eq!(stun.turn_allocation_count(), 0);
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use log::{debug, warn};
+use sha2::Sha256;
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::config::{WebhookEndpoint, WebhooksConfig};
+use crate::events::{Event, EventBus};
+use crate::http_client::{self, HttpUrl};
+use crate::identity::encode_hex;
+use crate::metrics::ServerMetrics;
+use crate::tr;
+
+/// 事件类型代码所在的请求头
+pub const EVENT_HEADER: &str = "X-P2P-Event";
+/// 投递ID所在的请求头，同一事件的重试使用相同的ID，接收方可据此去重
+pub const DELIVERY_HEADER: &str = "X-P2P-Delivery";
+/// 签名时间戳（UNIX秒）所在的请求头
+pub const TIMESTAMP_HEADER: &str = "X-P2P-Timestamp";
+/// 签名所在的请求头，值为 `sha256=<十六进制>`
+pub const SIGNATURE_HEADER: &str = "X-P2P-Signature";
+
+/// 计算 webhook 签名：HMAC-SHA256(密钥, `<时间戳>.<请求体>`)，返回 `sha256=<十六进制>`
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC 接受任意长度的密钥");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", encode_hex(&mac.finalize().into_bytes()))
+}
+
+/// 把事件总线上的事件推送给配置的 webhook 地址
+///
+/// 每个地址由独立的任务按事件发生顺序投递：失败（连接错误、超时、5xx、408、429）时按指数退避重试，
+/// 其他 4xx 视为接收方拒绝，不再重试。投递落后超过事件总线缓冲的事件会被跳过并计为失败。
+pub struct Webhooks {
+    config: WebhooksConfig,
+    metrics: Arc<ServerMetrics>,
+}
+
+impl Webhooks {
+    pub fn new(config: WebhooksConfig, metrics: Arc<ServerMetrics>) -> Self {
+        Self { config, metrics }
+    }
+
+    /// 为每个地址订阅事件并启动投递任务；地址无效时返回错误，不启动任何任务
+    pub fn start(&self, events: &EventBus) -> Result<Vec<tokio::task::JoinHandle<()>>> {
+        let endpoints = self
+            .config
+            .endpoints
+            .iter()
+            .map(|endpoint| Ok((HttpUrl::parse(&endpoint.url)?, endpoint.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(endpoints
+            .into_iter()
+            .map(|(url, endpoint)| {
+                let delivery = Delivery { url, endpoint, config: self.config.clone(), metrics: self.metrics.clone() };
+                tokio::spawn(delivery.run(events.subscribe()))
+            })
+            .collect())
+    }
+}
+
+struct Delivery {
+    url: HttpUrl,
+    endpoint: WebhookEndpoint,
+    config: WebhooksConfig,
+    metrics: Arc<ServerMetrics>,
+}
+
+impl Delivery {
+    async fn run(self, mut events: broadcast::Receiver<Arc<Event>>) {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(count)) => {
+                    ServerMetrics::add(&self.metrics.webhooks_failed, count);
+                    warn!("{}", tr!("webhook {} 投递过慢，跳过 {} 个事件", "Webhook {} is falling behind, skipped {} events", self.endpoint.url, count));
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            if !self.endpoint.events.is_empty() && !self.endpoint.events.iter().any(|code| code == event.kind.code()) {
+                continue;
+            }
+            if self.deliver(&event).await {
+                ServerMetrics::incr(&self.metrics.webhooks_delivered);
+            } else {
+                ServerMetrics::incr(&self.metrics.webhooks_failed);
+            }
+        }
+    }
+
+    /// 投递一个事件，返回是否成功
+    async fn deliver(&self, event: &Event) -> bool {
+        let body = match serde_json::to_vec(event) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("{}", tr!("序列化 webhook 事件失败: {}", "Failed to serialize webhook event: {}", e));
+                return false;
+            }
+        };
+        let delivery_id = Uuid::new_v4().to_string();
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        for attempt in 0..=self.config.max_retries {
+            if attempt > 0 {
+                let backoff = self.config.retry_backoff_ms.saturating_mul(1u64 << (attempt - 1).min(16));
+                tokio::time::sleep(Duration::from_millis(backoff)).await;
+            }
+            let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            let mut headers = vec![
+                (EVENT_HEADER, event.kind.code().to_string()),
+                (DELIVERY_HEADER, delivery_id.clone()),
+                (TIMESTAMP_HEADER, timestamp.to_string()),
+            ];
+            if let Some(secret) = &self.endpoint.secret {
+                headers.push((SIGNATURE_HEADER, sign(secret, timestamp, &body)));
+            }
+            match http_client::post_json(&self.url, &body, &headers, timeout).await {
+                Ok(status) if (200..300).contains(&status) => return true,
+                Ok(status) if status != 408 && status != 429 && (400..500).contains(&status) => {
+                    warn!("{}", tr!("webhook {} 拒绝了事件 {}: HTTP {}", "Webhook {} rejected event {}: HTTP {}", self.endpoint.url, event.seq, status));
+                    return false;
+                }
+                Ok(status) => debug!("{}", tr!("webhook {} 第 {} 次投递返回 HTTP {}", "Webhook {} delivery attempt {} returned HTTP {}", self.endpoint.url, attempt + 1, status)),
+                Err(e) => debug!("{}", tr!("webhook {} 第 {} 次投递失败: {}", "Webhook {} delivery attempt {} failed: {}", self.endpoint.url, attempt + 1, e)),
+            }
+        }
+        warn!(
+            "{}",
+            tr!(
+                "webhook {} 投递事件 {} 失败，已重试 {} 次",
+                "Webhook {} failed to receive event {} after {} retries",
+                self.endpoint.url,
+                event.seq,
+                self.config.max_retries,
+            )
+        );
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_covers_timestamp_and_body() {
+        let signature = sign("secret", 1_700_000_000, br#"{"type":"peer.joined"}"#);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_ne!(signature, sign("secret", 1_700_000_001, br#"{"type":"peer.joined"}"#));
+        assert_ne!(signature, sign("other", 1_700_000_000, br#"{"type":"peer.joined"}"#));
+    }
+}
@@ -0,0 +1,392 @@
+//! 与传输无关的协议核心：消息、节点信息、路由消息与 STUN 报文的定义和编解码
+//!
+//! 本模块只依赖 `serde`、`serde_json`、`uuid` 与 `smallvec`，不涉及套接字与异步运行时，
+//! 以 `default-features = false` 构建时可在只有 `alloc` 的环境（如嵌入式客户端）中使用。
+//! 生成随机ID与读取系统时钟的构造函数需要 `std` 特性；服务器与客户端代码在 `server` 特性之后。
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::net::SocketAddr;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use uuid::Uuid;
+
+pub mod stun;
+
+/// 当前 UNIX 时间（秒），系统时钟早于纪元时为 0
+#[cfg(feature = "std")]
+pub(crate) fn unix_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// 网络ID、版本号与能力名：启用 `std` 时驻留共享，否则各自分配
+fn shared(value: &str) -> Arc<str> {
+    #[cfg(feature = "std")]
+    {
+        crate::intern::intern(value)
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        Arc::from(value)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum MessageType {
+    /// 握手请求
+    HandshakeRequest,
+    /// 握手响应
+    HandshakeResponse,
+    /// 心跳包
+    Ping,
+    /// 心跳响应
+    Pong,
+    /// 节点发现请求
+    DiscoveryRequest,
+    /// 节点发现响应
+    DiscoveryResponse,
+    /// 请求节点列表
+    ListNodesRequest,
+    /// 响应节点列表
+    ListNodesResponse,
+    /// 数据传输
+    Data,
+    /// 错误消息
+    Error,
+    /// 断开连接
+    Disconnect,
+    /// 消息确认（UDP可靠性）
+    Ack,
+    /// 重传请求
+    Retransmit,
+    /// P2P 直连指令（NAT 打洞）
+    P2PConnect,
+    /// 流量转发请求（用于全对称NAT）
+    RelayRequest,
+    /// 流量转发响应
+    RelayResponse,
+    /// 转发的数据包
+    RelayData,
+    /// 关闭中继会话（节点发起；服务器也以同类型通知会话双方会话已关闭及原因）
+    RelayClose,
+    /// 链路状态通告（链路状态路由模式）
+    LinkStateUpdate,
+    /// 拓扑导出请求
+    TopologyRequest,
+    /// 拓扑导出响应
+    TopologyResponse,
+    /// 重连提示（服务器下线前通知客户端改连其他实例）
+    Reconnect,
+    /// 订阅主题
+    Subscribe,
+    /// 取消订阅主题
+    Unsubscribe,
+    /// 发布主题消息（服务器投递给订阅者时同样使用该类型）
+    Publish,
+    /// 上报 P2P 直连结果（实际打通的路径）
+    P2PConnectResult,
+    /// 打洞倒计时信标（服务器在协调直连后发给双方，`go_in_ms` 为 0 即 GO）
+    PunchBeacon,
+    /// TCP 同时打开协调（节点提交公网 TCP 端点，服务器转交提议并向双方下发连接时间）
+    TcpPunch,
+    /// 节点下线通知（发给近期与其通信过的节点）
+    PeerDown,
+    /// NAT 映射存活时间探测（请求、探测包、回显与结果共用）
+    KeepaliveProbe,
+    /// 时间同步（请求与响应共用，响应携带服务器收发时间戳）
+    TimeSync,
+    /// 计划维护公告
+    MaintenanceNotice,
+    /// 查询服务器路由表
+    GetRoutesRequest,
+    /// 路由表响应
+    GetRoutesResponse,
+    /// 查询服务器运行统计
+    GetStatsRequest,
+    /// 运行统计响应
+    GetStatsResponse,
+    /// 查询服务器上的节点
+    GetPeersRequest,
+    /// 节点查询响应
+    GetPeersResponse,
+    /// 查询服务器的生效配置（敏感字段已隐藏）
+    GetConfigRequest,
+    /// 生效配置响应
+    GetConfigResponse,
+    /// 地址迁移（节点切换网络后从新地址发起，服务器以同类型应答）
+    MigrateAddress,
+    /// 节点地址变更通知（发给与其保持 P2P 会话或近期通信过的节点）
+    AddressUpdate,
+    /// 注册服务（服务器以同类型应答）
+    RegisterService,
+    /// 注销服务（服务器以同类型应答）
+    UnregisterService,
+    /// 查询服务的提供者（服务器以同类型应答）
+    ServiceLookup,
+    /// 远程调用（经服务器转发给服务提供者）
+    RpcCall,
+    /// 远程调用结果（`reply_to` 指向调用）
+    RpcResult,
+    /// 关注一组节点的在线状态（服务器以同类型应答当前在线的节点）
+    Watch,
+    /// 取消关注
+    Unwatch,
+    /// 被关注节点上线或下线的通知
+    Presence,
+    /// 端到端送达回执（作为路由消息由目标节点发回源节点，`reply_to` 指向原消息）
+    Receipt,
+    /// 可靠字节流的帧（作为路由消息在两个节点之间传递）
+    Stream,
+    /// 带宽探测（节点请求探测、服务器下发探测指令与节点之间的探测包共用）
+    BandwidthProbe,
+    /// 带宽探测结果（接收方上报服务器；服务器以同类型应答请求探测的节点）
+    BandwidthReport,
+    /// 节点密钥轮换（服务器以同类型应答，并转发给其他已认证节点）
+    KeyRotation,
+    /// 连通性自检（节点发起，服务器以同类型应答诊断报告）
+    Diagnose,
+    /// 路径 MTU 探测包（填充到指定大小，收到方以同类型、带 `reply_to` 的小包应答）
+    MtuProbe,
+    /// 节点登记休眠（携带唤醒令牌，服务器以同类型应答）
+    Dormant,
+    /// 插件扩展消息，内含扩展标签（如 `acme.chat`），由认领该标签的插件处理
+    Extension(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub id: Uuid,
+    pub message_type: MessageType,
+    pub timestamp: u64,
+    pub payload: serde_json::Value,
+    /// 发送者地址（UDP需要）
+    pub sender_addr: Option<SocketAddr>,
+    /// 序列号（用于UDP重传和去重）
+    pub sequence_number: Option<u32>,
+    /// 是否需要确认
+    pub requires_ack: bool,
+    /// 确认的消息ID（用于Ack消息）
+    pub ack_for: Option<Uuid>,
+    /// 应答所对应的请求消息ID（请求/应答关联）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<Uuid>,
+    /// 服务器负载提示（配置了软限制时附在节点发现响应上）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<LoadHint>,
+}
+
+/// 服务器负载提示
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadHint {
+    /// 连接数超过软限制，客户端应避免不必要的请求
+    pub busy: bool,
+    /// 服务器当前的心跳间隔（秒），繁忙时会放慢
+    pub heartbeat_interval_secs: u64,
+}
+
+impl Message {
+    /// 以给定的ID与时间戳创建消息，不依赖随机数与系统时钟
+    pub fn with_id(id: Uuid, message_type: MessageType, timestamp: u64, payload: serde_json::Value) -> Self {
+        Self {
+            id,
+            message_type,
+            timestamp,
+            payload,
+            sender_addr: None,
+            sequence_number: None,
+            requires_ack: false,
+            ack_for: None,
+            reply_to: None,
+            load: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn new(message_type: MessageType, payload: serde_json::Value) -> Self {
+        Self::with_id(Uuid::new_v4(), message_type, unix_secs(), payload)
+    }
+}
+
+/// 节点能力列表：通常只有几项，内联存储并共享驻留的能力名
+pub type Capabilities = SmallVec<[Arc<str>; 4]>;
+
+/// 节点元数据：启用 `std` 时为 `HashMap`，只有 `alloc` 时为 `BTreeMap`，两者的线上格式相同
+#[cfg(feature = "std")]
+pub type Metadata = std::collections::HashMap<String, String>;
+#[cfg(not(feature = "std"))]
+pub type Metadata = alloc::collections::BTreeMap<String, String>;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NodeInfo {
+    pub id: Uuid,
+    pub name: Arc<str>,
+    #[cfg_attr(feature = "std", serde(deserialize_with = "crate::intern::deserialize_interned"))]
+    pub version: Arc<str>,
+    pub listen_addr: SocketAddr,
+    #[cfg_attr(feature = "std", serde(deserialize_with = "crate::intern::deserialize_interned_seq"))]
+    pub capabilities: Capabilities,
+    pub metadata: Metadata,
+    #[cfg_attr(feature = "std", serde(deserialize_with = "crate::intern::deserialize_interned"))]
+    pub network_id: Arc<str>, // 新增 network_id 字段
+    /// 多宿主主机在 `listen_addr` 之外的其他本地地址
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<SocketAddr>,
+    /// 节点的 Ed25519 公钥（十六进制），握手请求须附带对应私钥的证明
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+impl NodeInfo {
+    /// 以给定的节点ID创建节点信息，不依赖随机数
+    pub fn with_id(id: Uuid, name: impl Into<Arc<str>>, listen_addr: SocketAddr, network_id: &str) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            version: shared(env!("CARGO_PKG_VERSION")),
+            listen_addr,
+            capabilities: ["handshake", "discovery", "data_transfer"].into_iter().map(shared).collect(),
+            metadata: Metadata::new(),
+            network_id: shared(network_id),
+            addresses: Vec::new(),
+            public_key: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn new(name: impl Into<Arc<str>>, listen_addr: SocketAddr, network_id: impl Into<String>) -> Self {
+        Self::with_id(Uuid::new_v4(), name, listen_addr, &network_id.into())
+    }
+
+    /// 节点通告的全部本地地址：`listen_addr` 在前，去重并忽略未指定地址或端口的项
+    pub fn advertised_addrs(&self) -> Vec<SocketAddr> {
+        let mut addrs = Vec::new();
+        for addr in core::iter::once(self.listen_addr).chain(self.addresses.iter().copied()) {
+            if !addr.ip().is_unspecified() && addr.port() != 0 && !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+        addrs
+    }
+    
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| &**c == capability)
+    }
+
+    #[allow(dead_code)]
+    pub fn add_capability(&mut self, capability: &str) {
+        if !self.capabilities.iter().any(|c| &**c == capability) {
+            self.capabilities.push(shared(capability));
+        }
+    }
+    
+    #[allow(dead_code)]
+    pub fn add_metadata(&mut self, key: String, value: String) {
+        self.metadata.insert(key, value);
+    }
+}
+
+/// 源节点对路由消息的签名，见 `NodeIdentity::sign_routed`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteSignature {
+    /// 源节点的 Ed25519 公钥（十六进制）
+    pub public_key: String,
+    /// 签名（十六进制）
+    pub signature: String,
+    /// 多播签名覆盖的全部目标节点；分叉后的副本只能发往其中的节点
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub multicast: Vec<Uuid>,
+}
+
+/// 经服务器路由的消息，以 `Data` 消息承载
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutedMessage {
+    pub original_message: Message,
+    pub source_node: Uuid,
+    pub destination_node: Uuid,
+    pub hop_count: u32,
+    pub max_hops: u32,
+    pub route_id: Uuid,
+    /// 要求目标节点收到后发回端到端送达回执（`Receipt`）
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub receipt_requested: bool,
+    /// 多播：这份副本负责送达的目标节点，非空时 `destination_node` 为空ID
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub multicast: Vec<Uuid>,
+    /// 源节点的签名，覆盖源节点、目标节点、`route_id` 与内层消息，不覆盖沿途改写的跳数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<RouteSignature>,
+}
+
+impl RoutedMessage {
+    /// 以给定的 `route_id` 创建单播路由消息
+    pub fn with_route_id(message: Message, source: Uuid, destination: Uuid, max_hops: u32, route_id: Uuid) -> Self {
+        Self {
+            original_message: message,
+            source_node: source,
+            destination_node: destination,
+            hop_count: 0,
+            max_hops,
+            route_id,
+            receipt_requested: false,
+            multicast: Vec::new(),
+            signature: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[allow(dead_code)]
+    pub fn new(
+        message: Message,
+        source: Uuid,
+        destination: Uuid,
+        max_hops: u32,
+    ) -> Self {
+        Self::with_route_id(message, source, destination, max_hops, Uuid::new_v4())
+    }
+
+    /// 发给一组节点的多播消息：源只发一份，沿途按路由表分叉
+    #[cfg(feature = "std")]
+    pub fn multicast(message: Message, source: Uuid, members: Vec<Uuid>, max_hops: u32) -> Self {
+        Self { multicast: members, ..Self::new(message, source, Uuid::nil(), max_hops) }
+    }
+
+    /// 要求目标节点发回送达回执
+    pub fn with_receipt(mut self) -> Self {
+        self.receipt_requested = true;
+        self
+    }
+
+    pub fn increment_hop(&mut self) -> bool {
+        self.hop_count += 1;
+        self.hop_count <= self.max_hops
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_constructors_match_wire_format() {
+        let source = Uuid::from_u128(1);
+        let destination = Uuid::from_u128(2);
+        let mut info = NodeInfo::with_id(source, "sensor", "192.0.2.1:4000".parse().unwrap(), "net");
+        info.add_metadata("role".into(), "edge".into());
+        let message = Message::with_id(Uuid::from_u128(3), MessageType::HandshakeRequest, 1_760_000_000, serde_json::to_value(&info).unwrap());
+
+        // 不依赖随机数与时钟构造的消息与服务器解析的格式一致
+        let decoded: Message = serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap();
+        assert_eq!(decoded.id, message.id);
+        assert_eq!(serde_json::from_value::<NodeInfo>(decoded.payload).unwrap(), info);
+
+        let routed = RoutedMessage::with_route_id(message, source, destination, 8, Uuid::from_u128(4)).with_receipt();
+        let value = serde_json::to_value(&routed).unwrap();
+        assert_eq!(value["route_id"], serde_json::json!(Uuid::from_u128(4)));
+        assert_eq!(value["receipt_requested"], serde_json::json!(true));
+        assert!(value.get("multicast").is_none());
+    }
+}
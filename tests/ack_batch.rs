@@ -0,0 +1,49 @@
+use anyhow::Result;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::protocol::{Message, MessageType};
+use p2p_handshake_server::{AckBatchConfig, Config, P2PServer};
+
+#[tokio::test]
+async fn test_acks_are_batched_per_sender() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18694".parse().unwrap(),
+        ack_batch: AckBatchConfig { enable: true, delay_ms: 50, max_ids: 3 },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let local_addr = socket.local_addr()?;
+    let messages: Vec<Message> = (1..=5)
+        .map(|seq| Message::new_with_ack(MessageType::Ping, serde_json::Value::Null, local_addr, seq))
+        .collect();
+    for message in &messages {
+        socket.send_to(&serde_json::to_vec(message)?, server_addr).await?;
+    }
+
+    // 前三条攒满一批立即确认，其余两条在合并窗口结束后一并确认
+    let mut acks = Vec::new();
+    let mut buffer = vec![0u8; 65536];
+    while acks.len() < 2 {
+        let (len, _) = timeout(Duration::from_secs(2), socket.recv_from(&mut buffer)).await??;
+        let message: Message = serde_json::from_slice(&buffer[..len])?;
+        if message.message_type == MessageType::Ack {
+            acks.push(message.acked_ids());
+        }
+    }
+    let ids: Vec<_> = messages.iter().map(|message| message.id).collect();
+    assert_eq!(acks, vec![ids[..3].to_vec(), ids[3..].to_vec()]);
+    assert_eq!(metrics.acks_coalesced.load(Ordering::Relaxed), 3);
+    Ok(())
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, Instant, sleep};
+
+use p2p_handshake_server::protocol::{Message, MessageType};
+use p2p_handshake_server::{Config, HeartbeatConfig, P2PServer};
+use common::handshake;
+
+/// 在 `window` 内回应服务器心跳，返回每次收到 Ping 的时刻
+async fn answer_pings(socket: Arc<UdpSocket>, server: SocketAddr, window: Duration) -> Result<Vec<Instant>> {
+    let deadline = Instant::now() + window;
+    let mut buffer = vec![0u8; 65536];
+    let mut pings = Vec::new();
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await {
+        let (len, _) = received?;
+        let message: Message = serde_json::from_slice(&buffer[..len])?;
+        if message.message_type == MessageType::Ping {
+            pings.push(Instant::now());
+            socket.send_to(&serde_json::to_vec(&Message::pong())?, server).await?;
+        }
+    }
+    Ok(pings)
+}
+
+#[tokio::test]
+async fn test_heartbeat_interval_scales_and_spreads() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    // 4 个节点、每 2 个节点增加一个基础间隔：每轮 3 秒，心跳间隔约 750ms
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18510".parse().unwrap(),
+        heartbeat_interval: 1,
+        connection_timeout: 1,
+        heartbeat: HeartbeatConfig { peers_per_step: 2, max_interval_secs: 10, spread: true, max_pings_per_sec: 0 },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let mut sockets = Vec::new();
+    for i in 0..4 {
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        handshake(&socket, server_addr, &format!("node{}", i)).await?;
+        sockets.push(Arc::new(socket));
+    }
+    let tasks: Vec<_> = sockets
+        .iter()
+        .map(|socket| tokio::spawn(answer_pings(socket.clone(), server_addr, Duration::from_millis(6500))))
+        .collect();
+    let mut first_pings = Vec::new();
+    let mut repeated = 0;
+    for task in tasks {
+        let pings = task.await??;
+        // 固定 1 秒间隔时会收到约 6 次心跳
+        assert!((1..=3).contains(&pings.len()), "收到 {} 次心跳", pings.len());
+        first_pings.push(pings[0]);
+        if pings.len() >= 2 {
+            repeated += 1;
+        }
+    }
+    // 两轮之间超过 1 秒的 connection_timeout，超时判定已随间隔放宽，节点仍在接收心跳
+    assert!(repeated >= 2, "只有 {} 个节点收到第二轮心跳", repeated);
+
+    // 同一轮的心跳分散发出，而不是同时到达
+    let earliest = *first_pings.iter().min().unwrap();
+    let latest = *first_pings.iter().max().unwrap();
+    assert!(latest - earliest >= Duration::from_millis(1000), "心跳未分散: {:?}", latest - earliest);
+
+    Ok(())
+}
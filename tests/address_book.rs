@@ -0,0 +1,77 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::time::{timeout, Duration, sleep};
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer, SessionConfig, SessionState};
+use p2p_handshake_server::protocol::P2PPath;
+
+fn client_config(server_addr: SocketAddr, address_book: Option<PathBuf>) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        address_book,
+        session: SessionConfig {
+            punch_attempts: 5,
+            punch_interval_ms: 50,
+            keepalive_interval_ms: 100,
+            repunch_interval_ms: 60000,
+            ..SessionConfig::default()
+        },
+        ..ClientConfig::default()
+    }
+}
+
+/// 轮询直到会话直连或超时
+async fn wait_direct(state: impl Fn() -> SessionState) -> SessionState {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while !matches!(state(), SessionState::Direct(_)) && tokio::time::Instant::now() < deadline {
+        sleep(Duration::from_millis(20)).await;
+    }
+    state()
+}
+
+#[tokio::test]
+async fn test_reconnecting_client_punches_remembered_address() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18701".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let dir = std::env::temp_dir().join(format!("p2p_address_book_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    let book = dir.join("peers.json");
+    let mut bob = P2PClient::connect(client_config(server_addr, None)).await?;
+    let bob_addr = bob.local_addr()?;
+
+    let alice = P2PClient::connect(client_config(server_addr, Some(book.clone()))).await?;
+    let session = alice.open_session(bob.node_id()).await?;
+    let _accepted = timeout(Duration::from_secs(3), bob.accept()).await?.expect("未收到会话");
+    assert_eq!(wait_direct(|| session.state()).await, SessionState::Direct(bob_addr));
+    sleep(Duration::from_millis(100)).await;
+    let known = alice.known_peers();
+    assert_eq!(known.len(), 1);
+    assert_eq!(known[0].peer_id, bob.node_id());
+    assert_eq!(known[0].last_addr, Some(bob_addr));
+    assert_eq!(known[0].path, Some(P2PPath::Public));
+    drop(session);
+    drop(alice);
+
+    // 重启后的客户端读到地址簿，打开会话时先按记录直连
+    let alice = P2PClient::connect(client_config(server_addr, Some(book.clone()))).await?;
+    assert_eq!(alice.known_peers().len(), 1);
+    let session = alice.open_session(bob.node_id()).await?;
+    assert_eq!(wait_direct(|| session.state()).await, SessionState::Direct(bob_addr));
+    let _accepted = timeout(Duration::from_secs(3), bob.accept()).await?.expect("未收到会话");
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
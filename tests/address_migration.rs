@@ -0,0 +1,69 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+
+use p2p_handshake_server::{Config, P2PServer};
+use p2p_handshake_server::protocol::{
+    AddressUpdate, ListNodesResponse, Message, MessageType, MigrateAddressResponse,
+};
+use common::{handshake, receive_any, send_message};
+
+#[tokio::test]
+async fn test_roaming_client_migrates_session_with_ticket() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18400".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let (alice_info, response) = handshake(&alice, server_addr, "alice").await?;
+    let ticket = response.session_ticket.expect("握手响应缺少会话票据");
+    let (bob_info, _) = handshake(&bob, server_addr, "bob").await?;
+
+    // 经服务器协调直连，bob 成为 alice 的通信对象
+    send_message(&alice, &Message::initiate_p2p(bob_info.id), server_addr).await?;
+    assert!(receive_any(&bob, &[MessageType::P2PConnect]).await?.is_some());
+
+    // alice 切换网络：新地址上的错误票据被拒绝
+    let roamed = UdpSocket::bind("127.0.0.1:0").await?;
+    send_message(&roamed, &Message::migrate_address(alice_info.id, "0".repeat(32))?, server_addr).await?;
+    let denied = receive_any(&roamed, &[MessageType::MigrateAddress, MessageType::Error]).await?.expect("迁移请求未收到应答");
+    assert_eq!(denied.message_type, MessageType::Error);
+
+    send_message(&roamed, &Message::migrate_address(alice_info.id, ticket)?, server_addr).await?;
+    let migrated = receive_any(&roamed, &[MessageType::MigrateAddress, MessageType::Error]).await?.expect("迁移请求未收到应答");
+    assert_eq!(migrated.message_type, MessageType::MigrateAddress, "迁移失败: {:?}", migrated.payload);
+    let migrated: MigrateAddressResponse = serde_json::from_value(migrated.payload)?;
+    assert_eq!(migrated.public_addr, roamed.local_addr()?);
+
+    // bob 收到新地址
+    let update = receive_any(&bob, &[MessageType::AddressUpdate]).await?.expect("通信对象未收到地址变更通知");
+    let update: AddressUpdate = serde_json::from_value(update.payload)?;
+    assert_eq!(update.peer_id, alice_info.id);
+    assert_eq!(update.peer_addr, roamed.local_addr()?);
+
+    // 无需重新握手即可在新地址继续使用会话
+    send_message(&roamed, &Message::list_nodes_request(), server_addr).await?;
+    let list = receive_any(&roamed, &[MessageType::ListNodesResponse, MessageType::Error]).await?.expect("未收到节点列表");
+    let list: ListNodesResponse = serde_json::from_value(list.payload)?;
+    let entry = list.nodes.iter().find(|n| n.id == alice_info.id).expect("节点列表中缺少 alice");
+    assert_eq!(entry.listen_addr, roamed.local_addr()?);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.address_migrations, 1);
+    assert_eq!(snapshot.address_migrations_rejected, 1);
+    Ok(())
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{Config, P2PServer};
+use p2p_handshake_server::protocol::{AddressUpdate, Message, MessageType, P2PPath};
+use common::{handshake, receive_type, receive_type_within, send_message};
+
+/// 从新套接字迁移会话并等待应答
+async fn migrate(socket: &UdpSocket, server: SocketAddr, node_id: uuid::Uuid, ticket: &str) -> Result<()> {
+    send_message(socket, &Message::migrate_address(node_id, ticket.to_string())?, server).await?;
+    assert!(receive_type(socket, MessageType::MigrateAddress).await?.is_some(), "地址迁移未成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_address_update_pushed_to_session_partners() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18410".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let carol = UdpSocket::bind("127.0.0.1:0").await?;
+    let dave = UdpSocket::bind("127.0.0.1:0").await?;
+    let (alice_info, response) = handshake(&alice, server_addr, "alice").await?;
+    let ticket = response.session_ticket.expect("握手响应缺少会话票据");
+    handshake(&carol, server_addr, "carol").await?;
+    handshake(&dave, server_addr, "dave").await?;
+
+    // carol 上报与 alice 直连成功（未经本实例协调，不在近期通信关系中），dave 与 alice 无会话
+    send_message(&carol, &Message::p2p_connect_result(alice_info.id, P2PPath::Public), server_addr).await?;
+    sleep(Duration::from_millis(200)).await;
+
+    let roamed = UdpSocket::bind("127.0.0.1:0").await?;
+    migrate(&roamed, server_addr, alice_info.id, &ticket).await?;
+
+    let update = receive_type(&carol, MessageType::AddressUpdate).await?
+        .expect("会话对象未收到地址变更");
+    let update: AddressUpdate = serde_json::from_value(update.payload)?;
+    assert_eq!(update.peer_id, alice_info.id);
+    assert_eq!(update.peer_addr, roamed.local_addr()?);
+    assert!(receive_type_within(&dave, MessageType::AddressUpdate, Duration::from_millis(300)).await?.is_none());
+
+    // 上报失败后会话结束，再次迁移不再通知 carol
+    send_message(&carol, &Message::p2p_connect_result(alice_info.id, P2PPath::Failed), server_addr).await?;
+    sleep(Duration::from_millis(200)).await;
+    let roamed_again = UdpSocket::bind("127.0.0.1:0").await?;
+    migrate(&roamed_again, server_addr, alice_info.id, &ticket).await?;
+    assert!(receive_type_within(&carol, MessageType::AddressUpdate, Duration::from_millis(300)).await?.is_none());
+
+    Ok(())
+}
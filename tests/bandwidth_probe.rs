@@ -0,0 +1,89 @@
+use anyhow::Result;
+use tokio::time::{timeout, Duration, sleep};
+
+use p2p_handshake_server::{BandwidthConfig, ClientConfig, Config, P2PClient, P2PServer, P2PSession, SessionConfig, SessionState};
+
+fn client_config(server_addr: std::net::SocketAddr, name: &str) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        name: name.to_string(),
+        network_id: "test".to_string(),
+        session: SessionConfig {
+            punch_attempts: 5,
+            punch_interval_ms: 50,
+            keepalive_interval_ms: 100,
+            repunch_interval_ms: 60000,
+            ..SessionConfig::default()
+        },
+        ..ClientConfig::default()
+    }
+}
+
+/// 等待会话进入满足条件的状态
+async fn wait_state(session: &P2PSession, expected: impl Fn(SessionState) -> bool) -> SessionState {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(3);
+    loop {
+        let state = session.state();
+        if expected(state) || tokio::time::Instant::now() >= deadline {
+            return state;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_probe_records_bandwidth_and_steers_relay() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    // 任何测得的带宽都低于门限，测量后再次协调直连应建议走中继
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18490".parse().unwrap(),
+        allow_symmetric_nat_relay: true,
+        bandwidth: BandwidthConfig { min_direct_bps: u64::MAX, ..BandwidthConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let bandwidth = server.bandwidth();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = P2PClient::connect(client_config(server_addr, "alice")).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr, "bob")).await?;
+
+    // 尚未直连时不能探测
+    assert!(alice.probe_bandwidth(bob.node_id(), Duration::from_secs(3)).await.is_err());
+
+    let session = alice.open_session(bob.node_id()).await?;
+    let accepted = timeout(Duration::from_secs(3), bob.accept()).await?.expect("未收到会话");
+    assert!(matches!(wait_state(&session, |s| matches!(s, SessionState::Direct(_))).await, SessionState::Direct(_)));
+    assert!(matches!(wait_state(&accepted, |s| matches!(s, SessionState::Direct(_))).await, SessionState::Direct(_)));
+    // 等待直连结果上报到服务器
+    sleep(Duration::from_millis(200)).await;
+
+    let bps = alice.probe_bandwidth(bob.node_id(), Duration::from_secs(5)).await?;
+    assert!(bps > 0);
+    assert_eq!(bandwidth.get(&alice.node_id(), &bob.node_id()), Some(bps));
+    assert!(bandwidth.link_cost(&bob.node_id(), &alice.node_id()).is_some());
+
+    // 重新建立会话：服务器建议优先中继，双方不再打洞
+    session.close().await?;
+    drop(accepted);
+    sleep(Duration::from_millis(100)).await;
+    let session = alice.open_session(bob.node_id()).await?;
+    let mut accepted = timeout(Duration::from_secs(3), bob.accept()).await?.expect("未收到会话");
+    assert_eq!(wait_state(&session, |s| s == SessionState::Relay).await, SessionState::Relay);
+    assert_eq!(wait_state(&accepted, |s| s == SessionState::Relay).await, SessionState::Relay);
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(session.state(), SessionState::Relay);
+    assert_eq!(accepted.state(), SessionState::Relay);
+
+    session.send(b"via relay").await?;
+    let received = timeout(Duration::from_secs(3), accepted.recv()).await?;
+    assert_eq!(received.as_deref(), Some(&b"via relay"[..]));
+    Ok(())
+}
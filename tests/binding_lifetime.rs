@@ -0,0 +1,60 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration};
+
+use p2p_handshake_server::{StunServer, StunServerConfig};
+use p2p_handshake_server::stun_protocol::{
+    create_lifetime_probe_attribute, lifetime_probe, LifetimeProbe, StunMessage, STUN_BINDING_ERROR_RESPONSE,
+    STUN_BINDING_INDICATION, STUN_BINDING_RESPONSE,
+};
+
+/// 发送带探测属性的绑定请求并返回响应
+async fn probe(socket: &UdpSocket, server: SocketAddr, token: Option<u64>) -> Result<StunMessage> {
+    let mut request = StunMessage::new_binding_request();
+    request.add_attribute(create_lifetime_probe_attribute(token));
+    socket.send_to(&request.to_bytes(), server).await?;
+    receive(socket).await
+}
+
+async fn receive(socket: &UdpSocket) -> Result<StunMessage> {
+    let mut buffer = [0u8; 1024];
+    let (len, _) = timeout(Duration::from_secs(3), socket.recv_from(&mut buffer)).await??;
+    Ok(StunMessage::from_bytes(&buffer[..len])?)
+}
+
+#[tokio::test]
+async fn test_registered_mapping_probed_from_second_socket() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = StunServerConfig { enable: true, ..StunServerConfig::default() };
+    let server = Arc::new(StunServer::new(config, "127.0.0.1:18380".parse().unwrap()).await?);
+    let running = server.clone();
+    tokio::spawn(async move {
+        let _ = running.run().await;
+    });
+
+    // 在被测套接字上登记映射地址
+    let measured = UdpSocket::bind("127.0.0.1:0").await?;
+    let registered = probe(&measured, server.local_addr(), None).await?;
+    assert_eq!(registered.message_type, STUN_BINDING_RESPONSE);
+    let Some(LifetimeProbe::Check(token)) = lifetime_probe(&registered)? else {
+        panic!("登记响应缺少探测令牌");
+    };
+
+    // 从另一个套接字检查：服务器向登记的地址回送指示
+    let checker = UdpSocket::bind("127.0.0.1:0").await?;
+    let checked = probe(&checker, server.local_addr(), Some(token)).await?;
+    assert_eq!(checked.message_type, STUN_BINDING_RESPONSE);
+    let indication = receive(&measured).await?;
+    assert_eq!(indication.message_type, STUN_BINDING_INDICATION);
+    assert_eq!(indication.transaction_id, checked.transaction_id);
+    assert_eq!(lifetime_probe(&indication)?, Some(LifetimeProbe::Check(token)));
+    assert_eq!(indication.extract_mapped_address(), Some(measured.local_addr()?));
+
+    // 未知令牌返回错误
+    let unknown = probe(&checker, server.local_addr(), Some(token.wrapping_add(1))).await?;
+    assert_eq!(unknown.message_type, STUN_BINDING_ERROR_RESPONSE);
+    Ok(())
+}
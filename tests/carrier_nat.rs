@@ -0,0 +1,61 @@
+mod common;
+
+use anyhow::Result;
+use std::sync::atomic::Ordering;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{sleep, Duration};
+
+use p2p_handshake_server::protocol::{Message, MessageType};
+use p2p_handshake_server::{AdminConfig, CarrierNatConfig, Config, P2PServer};
+use common::{handshake, receive_type};
+
+async fn admin_get(admin: &str, path: &str) -> Result<serde_json::Value> {
+    let mut stream = TcpStream::connect(admin).await?;
+    stream.write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (_, body) = response.split_once("\r\n\r\n").unwrap_or_default();
+    Ok(serde_json::from_str(body)?)
+}
+
+#[tokio::test]
+async fn test_shared_public_ip_skips_punching() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18691".parse().unwrap(),
+        allow_symmetric_nat_relay: true,
+        // 两个节点共用 127.0.0.1 即视为 CGNAT
+        carrier_nat: CarrierNatConfig { enable: true, shared_ip_threshold: 2, ..CarrierNatConfig::default() },
+        admin: AdminConfig { enable: true, listen_address: "127.0.0.1:18692".parse().unwrap(), ..AdminConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    handshake(&alice, server_addr, "alice").await?;
+    let bob_id = handshake(&bob, server_addr, "bob").await?.0.id;
+
+    alice.send_to(&serde_json::to_vec(&Message::initiate_p2p(bob_id))?, server_addr).await?;
+    for socket in [&alice, &bob] {
+        let connect = receive_type(socket, MessageType::P2PConnect).await?.expect("未收到 P2PConnect");
+        assert_eq!(connect.payload["skip_punch"], true, "{}", connect.payload);
+        assert_eq!(connect.payload["prefer_relay"], true);
+        assert_eq!(connect.payload["peer_carrier_nat"], "cgnat");
+    }
+    assert_eq!(metrics.carrier_nat_relay_fallbacks.load(Ordering::Relaxed), 1);
+
+    let stats = admin_get("127.0.0.1:18692", "/api/stats").await?;
+    assert_eq!(stats["carrier_nat"], serde_json::json!({ "cgnat_peers": 2, "nat64_peers": 0 }));
+    let peers = admin_get("127.0.0.1:18692", "/api/peers").await?;
+    assert!(peers.as_array().unwrap().iter().all(|peer| peer["carrier_nat"] == "cgnat"), "{}", peers);
+    Ok(())
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "chaos")]
+
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{timeout, Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{AdminConfig, ChaosConfig, Config, FaultProfile, P2PServer};
+use p2p_handshake_server::protocol::{Message, MessageType};
+
+/// 发送 Ping，统计窗口内收到的 Pong 数量
+async fn count_pongs(socket: &UdpSocket, server: SocketAddr) -> Result<usize> {
+    socket.send_to(&serde_json::to_vec(&Message::ping())?, server).await?;
+    let mut buffer = vec![0u8; 65536];
+    let mut pongs = 0;
+    while let Ok(received) = timeout(Duration::from_millis(500), socket.recv_from(&mut buffer)).await {
+        let (len, _addr) = received?;
+        let message: Message = serde_json::from_slice(&buffer[..len])?;
+        if message.message_type == MessageType::Pong {
+            pongs += 1;
+        }
+    }
+    Ok(pongs)
+}
+
+/// 向管理接口发送请求，返回响应正文
+async fn admin_request(admin: SocketAddr, method: &str, path: &str, body: &str) -> Result<serde_json::Value> {
+    let mut stream = TcpStream::connect(admin).await?;
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (_, body) = response.split_once("\r\n\r\n").expect("响应缺少正文");
+    Ok(serde_json::from_str(body)?)
+}
+
+#[tokio::test]
+async fn test_fault_injection_adjustable_at_runtime() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let admin_addr: SocketAddr = "127.0.0.1:18261".parse().unwrap();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18260".parse().unwrap(),
+        admin: AdminConfig { enable: true, listen_address: admin_addr, ..AdminConfig::default() },
+        chaos: ChaosConfig {
+            enable: true,
+            inbound: FaultProfile { drop_rate: 1.0, ..FaultProfile::default() },
+            ..ChaosConfig::default()
+        },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+
+    // 入站全部丢弃：收不到回复
+    assert_eq!(count_pongs(&client, server_addr).await?, 0);
+
+    // 运行时改为出站必定重复：每个 Ping 收到两个 Pong
+    let update = r#"{"enable": true, "outbound": {"duplicate_rate": 1.0, "latency_ms": 20}}"#;
+    let state = admin_request(admin_addr, "PUT", "/api/chaos", update).await?;
+    assert_eq!(state["config"]["inbound"]["drop_rate"], 0.0);
+    assert_eq!(count_pongs(&client, server_addr).await?, 2);
+
+    let state = admin_request(admin_addr, "GET", "/api/chaos", "").await?;
+    assert_eq!(state["stats"]["inbound"]["dropped"], 1);
+    assert_eq!(state["stats"]["outbound"]["duplicated"], 1);
+
+    // 非法概率被拒绝
+    let invalid = r#"{"enable": true, "inbound": {"drop_rate": 2.0}}"#;
+    assert!(admin_request(admin_addr, "PUT", "/api/chaos", invalid).await?.get("error").is_some());
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer, P2PSession, SessionConfig, SessionState};
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo};
+use common::receive_type;
+
+async fn start_server(port: u16) -> Result<SocketAddr> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: format!("127.0.0.1:{}", port).parse().unwrap(),
+        allow_symmetric_nat_relay: true,
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+    Ok(server_addr)
+}
+
+fn client_config(server_addr: SocketAddr, name: &str) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        name: name.to_string(),
+        network_id: "test".to_string(),
+        session: SessionConfig {
+            punch_attempts: 5,
+            punch_interval_ms: 50,
+            keepalive_interval_ms: 100,
+            max_missed_pongs: 3,
+            ..SessionConfig::default()
+        },
+        ..ClientConfig::default()
+    }
+}
+
+/// 等待会话进入满足条件的状态
+async fn wait_state(session: &P2PSession, expected: impl Fn(SessionState) -> bool) -> SessionState {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(3);
+    loop {
+        let state = session.state();
+        if expected(state) || tokio::time::Instant::now() >= deadline {
+            return state;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+async fn recv(session: &mut P2PSession) -> Option<Vec<u8>> {
+    timeout(Duration::from_secs(3), session.recv()).await.ok().flatten()
+}
+
+#[tokio::test]
+async fn test_session_goes_direct_and_falls_back_when_path_dies() -> Result<()> {
+    let _ = env_logger::try_init();
+    let server_addr = start_server(18420).await?;
+
+    let alice = P2PClient::connect(client_config(server_addr, "alice")).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr, "bob")).await?;
+    assert!(alice.session_ticket().is_some());
+
+    let mut to_bob = alice.open_session(bob.node_id()).await?;
+    let mut to_alice = timeout(Duration::from_secs(3), bob.accept()).await?.expect("bob 未收到会话");
+    assert_eq!(to_alice.peer_id(), alice.node_id());
+
+    // 双方打洞成功后直接收发
+    let bob_addr = bob.local_addr()?;
+    assert_eq!(wait_state(&to_bob, |s| matches!(s, SessionState::Direct(_))).await, SessionState::Direct(bob_addr));
+    assert!(matches!(wait_state(&to_alice, |s| matches!(s, SessionState::Direct(_))).await, SessionState::Direct(_)));
+    to_bob.send(b"hello bob").await?;
+    assert_eq!(recv(&mut to_alice).await.as_deref(), Some(&b"hello bob"[..]));
+    to_alice.send(b"hello alice").await?;
+    assert_eq!(recv(&mut to_bob).await.as_deref(), Some(&b"hello alice"[..]));
+
+    // bob 的套接字关闭后连续收不到 Pong，alice 判定直连失效并回退到中继
+    drop(to_alice);
+    drop(bob);
+    assert_eq!(wait_state(&to_bob, |s| s == SessionState::Relay).await, SessionState::Relay);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_relays_when_punching_fails() -> Result<()> {
+    let _ = env_logger::try_init();
+    let server_addr = start_server(18421).await?;
+
+    let alice = P2PClient::connect(client_config(server_addr, "alice")).await?;
+
+    // carol 从不应答打洞探测（如全对称NAT之后）
+    let carol = UdpSocket::bind("127.0.0.1:0").await?;
+    let carol_info = NodeInfo::new("carol".to_string(), carol.local_addr()?, "test".to_string());
+    carol.send_to(&serde_json::to_vec(&Message::handshake_request(carol_info.clone())?)?, server_addr).await?;
+    assert!(receive_type(&carol, MessageType::HandshakeResponse).await?.is_some());
+
+    let mut to_carol = alice.open_session(carol_info.id).await?;
+    assert_eq!(wait_state(&to_carol, |s| s == SessionState::Relay).await, SessionState::Relay);
+
+    // 应用照常收发，数据经服务器中继
+    to_carol.send(b"via relay").await?;
+    let relayed = receive_type(&carol, MessageType::RelayData).await?.expect("carol 未收到中继数据");
+    let data: Vec<u8> = serde_json::from_value(relayed.payload["data"].clone())?;
+    assert_eq!(data, b"via relay");
+
+    // 服务器应答会话ID后改用二进制帧发送，carol 不支持帧，仍收到 JSON
+    sleep(Duration::from_millis(100)).await;
+    to_carol.send(b"via frame").await?;
+    let relayed = receive_type(&carol, MessageType::RelayData).await?.expect("carol 未收到中继数据");
+    let data: Vec<u8> = serde_json::from_value(relayed.payload["data"].clone())?;
+    assert_eq!(data, b"via frame");
+    assert!(relayed.payload["session_id"].is_string());
+
+    let reply = Message::relay_request(alice.node_id(), b"relayed back".to_vec());
+    carol.send_to(&serde_json::to_vec(&reply)?, server_addr).await?;
+    assert_eq!(recv(&mut to_carol).await.as_deref(), Some(&b"relayed back"[..]));
+    Ok(())
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{ClientConfig, ClusterConfig, Config, P2PClient, P2PServer};
+use p2p_handshake_server::protocol::{ListNodesResponse, Message, MessageType};
+use common::{handshake, receive_type, send_message};
+
+async fn start_server(
+    listen: &str,
+    gossip: &str,
+    seeds: Vec<SocketAddr>,
+) -> Result<(SocketAddr, tokio::sync::broadcast::Sender<()>)> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: listen.parse().unwrap(),
+        cluster: ClusterConfig {
+            enable: true,
+            bind_address: gossip.parse().unwrap(),
+            seeds,
+            gossip_interval_ms: 100,
+            cluster_key: Some("cluster-secret".to_string()),
+            ..ClusterConfig::default()
+        },
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config.clone()).await?;
+    let shutdown = server.shutdown_sender();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    Ok((config.listen_address, shutdown))
+}
+
+#[tokio::test]
+async fn test_peers_discover_each_other_across_instances() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let (server_a, _) = start_server("127.0.0.1:18180", "127.0.0.1:17946", Vec::new()).await?;
+    let (server_b, _) = start_server("127.0.0.1:18181", "127.0.0.1:17947", vec!["127.0.0.1:17946".parse().unwrap()]).await?;
+    sleep(Duration::from_millis(200)).await;
+
+    // 客户端1连接实例A，客户端2连接实例B
+    let client1 = UdpSocket::bind("127.0.0.1:0").await?;
+    let client2 = UdpSocket::bind("127.0.0.1:0").await?;
+    let (info1, _) = handshake(&client1, server_a, "client_on_a").await?;
+    let (info2, _) = handshake(&client2, server_b, "client_on_b").await?;
+
+    // 等待注册信息经gossip同步
+    sleep(Duration::from_millis(600)).await;
+
+    // 实例B的节点列表包含连接在实例A上的客户端1
+    send_message(&client2, &Message::new(MessageType::ListNodesRequest, serde_json::json!({})), server_b).await?;
+    let list = receive_type(&client2, MessageType::ListNodesResponse).await?.expect("未收到节点列表");
+    let list: ListNodesResponse = serde_json::from_value(list.payload)?;
+    assert!(list.nodes.iter().any(|n| n.id == info1.id), "实例B的节点列表应包含实例A上的节点");
+
+    // 通过实例B请求与客户端1直连，协调消息经实例A送达客户端1
+    let connect = Message::new(MessageType::P2PConnect, serde_json::json!({ "peer_id": info1.id.to_string() }));
+    send_message(&client2, &connect, server_b).await?;
+    let to_requester = receive_type(&client2, MessageType::P2PConnect).await?.expect("请求方未收到直连信息");
+    assert_eq!(to_requester.payload["peer_addr"], client1.local_addr()?.to_string());
+    let to_target = receive_type(&client1, MessageType::P2PConnect).await?.expect("目标方未收到直连协调");
+    assert_eq!(to_target.payload["peer_id"], info2.id.to_string());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_draining_instance_hands_off_sessions() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let (server_a, _) = start_server("127.0.0.1:18182", "127.0.0.1:17948", Vec::new()).await?;
+    let (server_b, shutdown_b) =
+        start_server("127.0.0.1:18183", "127.0.0.1:17949", vec!["127.0.0.1:17948".parse().unwrap()]).await?;
+    sleep(Duration::from_millis(200)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let (info, response) = handshake(&client, server_b, "client_on_b").await?;
+    let ticket = response.session_ticket.expect("握手响应缺少会话票据");
+    sleep(Duration::from_millis(400)).await;
+
+    // 实例B下线：会话移交给实例A，客户端收到指向A的重连提示
+    shutdown_b.send(())?;
+    let hint = receive_type(&client, MessageType::Reconnect).await?.expect("未收到重连提示");
+    assert_eq!(hint.payload["server_addr"], server_a.to_string());
+    assert_eq!(hint.payload["session_preserved"], true);
+
+    // 无需重新握手，实例A已将客户端视为已认证节点
+    send_message(&client, &Message::new(MessageType::ListNodesRequest, serde_json::json!({})), server_a).await?;
+    let list = receive_type(&client, MessageType::ListNodesResponse).await?.expect("未收到节点列表");
+    let list: ListNodesResponse = serde_json::from_value(list.payload)?;
+    assert!(list.nodes.iter().any(|n| n.id == info.id), "实例A应已接管客户端会话");
+
+    // 客户端经新的 NAT 映射到达实例A：凭移交的会话票据把接管的会话迁移到新地址
+    let roamed = UdpSocket::bind("127.0.0.1:0").await?;
+    send_message(&roamed, &Message::migrate_address(info.id, ticket)?, server_a).await?;
+    let migrated = receive_type(&roamed, MessageType::MigrateAddress).await?.expect("未收到迁移应答");
+    assert_eq!(migrated.payload["public_addr"], roamed.local_addr()?.to_string());
+    send_message(&roamed, &Message::new(MessageType::ListNodesRequest, serde_json::json!({})), server_a).await?;
+    assert!(receive_type(&roamed, MessageType::ListNodesResponse).await?.is_some(), "迁移后的地址应被视为已认证节点");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_client_follows_reconnect_hint_without_handshake() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let (server_a, _) = start_server("127.0.0.1:18184", "127.0.0.1:17950", Vec::new()).await?;
+    let (server_b, shutdown_b) =
+        start_server("127.0.0.1:18185", "127.0.0.1:17951", vec!["127.0.0.1:17950".parse().unwrap()]).await?;
+    sleep(Duration::from_millis(200)).await;
+
+    let key_file = std::env::temp_dir().join(format!("p2p_handoff_{}.key", uuid::Uuid::new_v4()));
+    let mut client = P2PClient::connect(ClientConfig {
+        server_addr: server_b,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        identity_key_file: Some(key_file.clone()),
+        ..ClientConfig::default()
+    })
+    .await?;
+    let ticket = client.session_ticket();
+    sleep(Duration::from_millis(400)).await;
+
+    // 实例B下线：客户端按重连提示改用实例A
+    shutdown_b.send(())?;
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while client.active_server() != server_a && tokio::time::Instant::now() < deadline {
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(client.active_server(), server_a);
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(client.active_server(), server_a, "迁移失败后不应退回已下线的实例");
+
+    // 凭移交的会话票据迁移，没有重新握手领取新票据
+    assert_eq!(client.session_ticket(), ticket);
+    assert_eq!(client.public_addr(), Some(client.local_addr()?));
+    // 实例A按移交的公钥校验密钥轮换
+    client.rotate_key().await?;
+
+    let _ = std::fs::remove_file(&key_file);
+    Ok(())
+}
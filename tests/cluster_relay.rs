@@ -0,0 +1,91 @@
+mod common;
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{sleep, Duration};
+
+use p2p_handshake_server::protocol::{Message, MessageType, RelayClose, RelayCloseReason, RelayData, RelayResponse};
+use p2p_handshake_server::{AdminConfig, ClusterConfig, Config, P2PServer};
+use common::{handshake, receive_type};
+
+async fn start_server(listen: &str, gossip: &str, admin: &str, seeds: Vec<SocketAddr>) -> Result<SocketAddr> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: listen.parse().unwrap(),
+        allow_symmetric_nat_relay: true,
+        cluster: ClusterConfig {
+            enable: true,
+            bind_address: gossip.parse().unwrap(),
+            seeds,
+            gossip_interval_ms: 100,
+            ..ClusterConfig::default()
+        },
+        admin: AdminConfig { enable: true, listen_address: admin.parse().unwrap(), ..AdminConfig::default() },
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config.clone()).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    Ok(config.listen_address)
+}
+
+/// 读取管理接口的中继会话列表
+async fn relays(admin: &str) -> Result<serde_json::Value> {
+    let mut stream = TcpStream::connect(admin).await?;
+    stream.write_all(b"GET /api/relays HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (_, body) = response.split_once("\r\n\r\n").unwrap_or_default();
+    Ok(serde_json::from_str(body)?)
+}
+
+#[tokio::test]
+async fn test_relay_session_spans_two_instances() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let server_a = start_server("127.0.0.1:18682", "127.0.0.1:17950", "127.0.0.1:18684", Vec::new()).await?;
+    let server_b = start_server("127.0.0.1:18683", "127.0.0.1:17951", "127.0.0.1:18685", vec!["127.0.0.1:17950".parse().unwrap()]).await?;
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let alice_id = handshake(&alice, server_a, "alice_on_a").await?.0.id;
+    let bob_id = handshake(&bob, server_b, "bob_on_b").await?.0.id;
+    // 等待两个实例互相同步节点
+    sleep(Duration::from_millis(600)).await;
+
+    // alice 经实例A请求中继到实例B上的 bob
+    alice.send_to(&serde_json::to_vec(&Message::relay_session_request(bob_id))?, server_a).await?;
+    let opened = receive_type(&alice, MessageType::RelayResponse).await?.expect("未收到 RelayResponse");
+    let opened: RelayResponse = serde_json::from_value(opened.payload)?;
+    assert!(opened.success, "{:?}", opened.error_message);
+    let session_id = opened.session_id.unwrap();
+
+    let data = vec![7u8; 600];
+    alice.send_to(&serde_json::to_vec(&Message::relay_session_data(session_id, data.clone()))?, server_a).await?;
+    let relayed = receive_type(&bob, MessageType::RelayData).await?.expect("bob 未收到跨实例中继数据");
+    let relayed: RelayData = serde_json::from_value(relayed.payload)?;
+    assert_eq!((relayed.from_peer_id, relayed.session_id), (alice_id, Some(session_id)));
+    assert_eq!(relayed.data, data);
+
+    // 两个实例都以同一会话ID计入这次转发
+    for admin in ["127.0.0.1:18684", "127.0.0.1:18685"] {
+        let sessions = relays(admin).await?;
+        let session = &sessions[0];
+        assert_eq!(session["session_id"], session_id.to_string(), "{}", sessions);
+        assert_eq!(session["bytes"], 600);
+        assert!(session["remote_instance"].is_string());
+    }
+
+    // bob 从实例B下线，实例A上的会话随之关闭并通知 alice
+    bob.send_to(&serde_json::to_vec(&Message::disconnect("bye".to_string()))?, server_b).await?;
+    let notice = receive_type(&alice, MessageType::RelayClose).await?.expect("alice 未收到关闭通知");
+    let notice: RelayClose = serde_json::from_value(notice.payload)?;
+    assert_eq!(notice, RelayClose { session_id, peer_id: Some(bob_id), reason: Some(RelayCloseReason::PeerDisconnected) });
+    assert_eq!(relays("127.0.0.1:18684").await?, serde_json::json!([]));
+    assert_eq!(relays("127.0.0.1:18685").await?, serde_json::json!([]));
+    Ok(())
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use p2p_handshake_server::{ClusterConfig, Config, P2PServer};
+use p2p_handshake_server::protocol::{ListNodesResponse, Message, MessageType};
+use p2p_handshake_server::rendezvous::home_server;
+use common::{handshake_as, receive_type, send_message};
+
+async fn start_server(listen: &str, gossip: &str, seeds: Vec<SocketAddr>) -> Result<SocketAddr> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: listen.parse().unwrap(),
+        cluster: ClusterConfig {
+            enable: true,
+            bind_address: gossip.parse().unwrap(),
+            seeds,
+            gossip_interval_ms: 100,
+            sharding: true,
+            ..ClusterConfig::default()
+        },
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config.clone()).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    Ok(config.listen_address)
+}
+
+#[tokio::test]
+async fn test_sharded_lookup_via_home_instance() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let seed: SocketAddr = "127.0.0.1:18303".parse().unwrap();
+    let server_a = start_server("127.0.0.1:18300", "127.0.0.1:18303", Vec::new()).await?;
+    let server_b = start_server("127.0.0.1:18301", "127.0.0.1:18304", vec![seed]).await?;
+    let server_c = start_server("127.0.0.1:18302", "127.0.0.1:18305", vec![seed]).await?;
+    sleep(Duration::from_millis(200)).await;
+
+    // 用临时连接取得三个实例的ID
+    let mut instance_ids = Vec::new();
+    for server in [server_a, server_b, server_c] {
+        let probe = UdpSocket::bind("127.0.0.1:0").await?;
+        instance_ids.push(handshake_as(&probe, server, Uuid::new_v4()).await?.node_info.id);
+    }
+    let (id_a, id_b, id_c) = (instance_ids[0], instance_ids[1], instance_ids[2]);
+
+    // 选一个归属于实例C的节点ID：它连接实例A，实例B需要向C询问其位置
+    let target_id = loop {
+        let id = Uuid::new_v4();
+        if home_server(&id, &[id_a, id_b, id_c]) == Some(id_c) {
+            break id;
+        }
+    };
+    let target = UdpSocket::bind("127.0.0.1:0").await?;
+    let requester = UdpSocket::bind("127.0.0.1:0").await?;
+    handshake_as(&target, server_a, target_id).await?;
+    handshake_as(&requester, server_b, Uuid::new_v4()).await?;
+    sleep(Duration::from_millis(600)).await;
+
+    // 注册信息不再全量复制：实例B的节点列表中没有目标节点
+    send_message(&requester, &Message::new(MessageType::ListNodesRequest, serde_json::json!({})), server_b).await?;
+    let list = receive_type(&requester, MessageType::ListNodesResponse).await?.expect("未收到节点列表");
+    let list: ListNodesResponse = serde_json::from_value(list.payload)?;
+    assert!(!list.nodes.iter().any(|n| n.id == target_id), "分片模式下实例B不应持有目标节点的登记");
+
+    // 直连请求仍能经归属实例C定位到实例A并送达
+    let connect = Message::new(MessageType::P2PConnect, serde_json::json!({ "peer_id": target_id.to_string() }));
+    send_message(&requester, &connect, server_b).await?;
+    let to_requester = receive_type(&requester, MessageType::P2PConnect).await?.expect("请求方未收到直连信息");
+    assert_eq!(to_requester.payload["peer_addr"], target.local_addr()?.to_string());
+    assert!(receive_type(&target, MessageType::P2PConnect).await?.is_some(), "目标方未收到直连协调");
+
+    Ok(())
+}
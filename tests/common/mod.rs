@@ -0,0 +1,75 @@
+//! 集成测试共用的原始 UDP 收发与握手辅助函数
+//!
+//! 各测试只用到其中一部分，其余函数在该测试中是死代码。
+#![allow(dead_code)]
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{HandshakeResponse, Message, MessageType, NodeInfo};
+
+/// `receive_type` 与 `receive_any` 的默认等待时间
+pub const RECEIVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub async fn send_message(socket: &UdpSocket, message: &Message, target: SocketAddr) -> Result<()> {
+    socket.send_to(&serde_json::to_vec(message)?, target).await?;
+    Ok(())
+}
+
+/// 接收消息直到出现指定类型，超时返回 `None`
+pub async fn receive_type(socket: &UdpSocket, expected: MessageType) -> Result<Option<Message>> {
+    receive_any_within(socket, &[expected], RECEIVE_TIMEOUT).await
+}
+
+/// 同 [`receive_type`]，等待时间由调用方指定
+pub async fn receive_type_within(socket: &UdpSocket, expected: MessageType, wait: Duration) -> Result<Option<Message>> {
+    receive_any_within(socket, &[expected], wait).await
+}
+
+/// 接收消息直到出现指定类型之一，超时返回 `None`
+pub async fn receive_any(socket: &UdpSocket, expected: &[MessageType]) -> Result<Option<Message>> {
+    receive_any_within(socket, expected, RECEIVE_TIMEOUT).await
+}
+
+/// 同 [`receive_any`]，等待时间由调用方指定
+pub async fn receive_any_within(socket: &UdpSocket, expected: &[MessageType], wait: Duration) -> Result<Option<Message>> {
+    let mut buffer = vec![0u8; 65536];
+    let deadline = tokio::time::Instant::now() + wait;
+    loop {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        match timeout(remaining, socket.recv_from(&mut buffer)).await {
+            Ok(Ok((len, _addr))) => {
+                let message: Message = serde_json::from_slice(&buffer[..len])?;
+                if expected.contains(&message.message_type) {
+                    return Ok(Some(message));
+                }
+            }
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => return Ok(None),
+        }
+    }
+}
+
+/// 以套接字本地地址与网络 `test` 握手，返回节点信息与握手响应
+pub async fn handshake(socket: &UdpSocket, server: SocketAddr, name: &str) -> Result<(NodeInfo, HandshakeResponse)> {
+    let info = NodeInfo::new(name.to_string(), socket.local_addr()?, "test".to_string());
+    let response = handshake_with(socket, server, &info).await?;
+    Ok((info, response))
+}
+
+/// 以指定节点ID握手（用于重新上线或冒用他人ID），返回握手响应
+pub async fn handshake_as(socket: &UdpSocket, server: SocketAddr, id: Uuid) -> Result<HandshakeResponse> {
+    let mut info = NodeInfo::new("client".to_string(), socket.local_addr()?, "test".to_string());
+    info.id = id;
+    handshake_with(socket, server, &info).await
+}
+
+/// 以调用方构造的节点信息（指定节点ID、元数据或地址）握手，返回握手响应
+pub async fn handshake_with(socket: &UdpSocket, server: SocketAddr, info: &NodeInfo) -> Result<HandshakeResponse> {
+    send_message(socket, &Message::handshake_request(info.clone())?, server).await?;
+    let response = receive_type(socket, MessageType::HandshakeResponse).await?.expect("握手未在超时内收到响应");
+    Ok(serde_json::from_value(response.payload)?)
+}
@@ -0,0 +1,65 @@
+use p2p_handshake_server::{Config, P2PServer, WebhookEndpoint};
+
+#[tokio::test]
+async fn test_invalid_config_is_rejected_with_all_problems() {
+    let config = Config {
+        listen_address: "127.0.0.1:18650".parse().unwrap(),
+        heartbeat_interval: 0,
+        discovery_port_range: (9000, 8000),
+        network_id: " ".to_string(),
+        ..Config::default()
+    };
+    let err = P2PServer::new(config).await.err().expect("无效配置不应启动");
+    let message = err.to_string();
+    assert!(message.contains("3 项"), "{}", message);
+    assert!(message.contains("heartbeat_interval"), "{}", message);
+    assert!(message.contains("discovery_port_range"), "{}", message);
+    assert!(message.contains("network_id"), "{}", message);
+
+    assert!(Config::default().validate().is_ok());
+    let config = Config { heartbeat_interval: 30, connection_timeout: 10, ..Config::default() };
+    assert!(config.validate().unwrap_err().to_string().contains("connection_timeout"));
+
+    // 集群 gossip 暴露到回环以外时必须有密钥
+    let mut config = Config::default();
+    config.cluster.enable = true;
+    assert!(config.validate().is_ok());
+    config.cluster.bind_address = "0.0.0.0:7946".parse().unwrap();
+    assert!(config.validate().unwrap_err().to_string().contains("cluster_key"));
+    config.cluster.cluster_key = Some("shared".to_string());
+    assert!(config.validate().is_ok());
+
+    // 管理接口与 gRPC 控制面开放到回环以外时必须有令牌
+    let mut config = Config::default();
+    config.admin.enable = true;
+    config.grpc.enable = true;
+    assert!(config.validate().is_ok());
+    config.admin.listen_address = "0.0.0.0:8088".parse().unwrap();
+    config.grpc.listen_address = "0.0.0.0:50051".parse().unwrap();
+    let message = config.validate().unwrap_err().to_string();
+    assert!(message.contains("2 项"), "{}", message);
+    assert!(message.contains("admin.token") && message.contains("grpc.token"), "{}", message);
+    config.admin.token = Some("admin-token".to_string());
+    config.grpc.token = Some("grpc-token".to_string());
+    assert!(config.validate().is_ok());
+
+    // 出站HTTP没有TLS，只能发往回环地址
+    let mut config = Config::default();
+    config.telemetry.enable = true;
+    config.webhooks.enable = true;
+    config.webhooks.endpoints.push(WebhookEndpoint { url: "http://localhost:9000/hooks".to_string(), ..Default::default() });
+    assert!(config.validate().is_ok());
+    config.telemetry.endpoint = "http://collector.example.com:4318".to_string();
+    config.webhooks.endpoints.push(WebhookEndpoint { url: "https://hooks.example.com/p2p".to_string(), ..Default::default() });
+    let message = config.validate().unwrap_err().to_string();
+    assert!(message.contains("2 项"), "{}", message);
+    assert!(message.contains("telemetry.endpoint (http://collector.example.com:4318) 不是回环地址"), "{}", message);
+    assert!(message.contains("不支持 https://"), "{}", message);
+
+    // 链路状态来源公钥须为 32 字节的十六进制
+    let mut config = Config::default();
+    config.routing.origin_keys.insert(uuid::Uuid::new_v4(), "ab".repeat(32));
+    assert!(config.validate().is_ok());
+    config.routing.origin_keys.insert(uuid::Uuid::new_v4(), "not-a-key".to_string());
+    assert!(config.validate().unwrap_err().to_string().contains("routing.origin_keys"));
+}
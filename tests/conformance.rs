@@ -0,0 +1,262 @@
+//! 协议一致性测试：按 `tests/golden/*.json` 中的脚本向服务器发送消息，逐条比对收到的应答
+//!
+//! 每个脚本声明参与的节点与若干步骤，每步由某个节点发出一条消息，随后收集各节点在静默前收到的全部消息。
+//! 应答中随运行变化的内容（消息ID、时间戳、节点ID、地址、签名等）先替换为占位符再比较，
+//! 协议行为的任何变化都会表现为与黄金文件的差异。协议有意变更时以 `UPDATE_GOLDEN=1 cargo test --test conformance`
+//! 重新生成黄金文件，并在评审中核对差异。
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo};
+use p2p_handshake_server::{Config, P2PServer};
+
+const NETWORK_ID: &str = "conformance";
+/// 各脚本依文件名排序后依次使用 18710 起的端口（预留 18710–18719）
+const FIRST_PORT: u16 = 18710;
+/// 最后一条消息后静默这么久即认为本步的应答已收齐
+const QUIET: Duration = Duration::from_millis(500);
+/// 单步最长等待
+const STEP_LIMIT: Duration = Duration::from_secs(3);
+/// 这些字段的值每次运行都不同，比较前整体替换
+const VOLATILE_KEYS: &[&str] = &["timestamp", "session_ticket", "signature", "cookie", "last_seen", "connected_secs"];
+
+/// 一步中各节点收到的消息，按节点名排列
+type Received = BTreeMap<String, Vec<Value>>;
+
+/// 黄金文件：参与的节点与按顺序执行的步骤
+#[derive(Serialize, Deserialize)]
+struct Script {
+    description: String,
+    nodes: Vec<NodeSpec>,
+    steps: Vec<Step>,
+}
+
+/// 节点写作名称，或带上 `network_id` 以模拟其他网络的节点
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum NodeSpec {
+    Name(String),
+    InNetwork { name: String, network_id: String },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Step {
+    /// 发出消息的节点
+    from: String,
+    /// 要发出的消息，缺省的ID、时间戳等字段由测试补全
+    send: Value,
+    /// 发出后各节点应收到的消息；未收到消息的节点不出现
+    #[serde(default)]
+    expect: Received,
+}
+
+struct Node {
+    socket: UdpSocket,
+    info: NodeInfo,
+}
+
+/// 运行期的值到占位符的映射
+#[derive(Default)]
+struct Placeholders {
+    known: HashMap<String, String>,
+}
+
+impl Placeholders {
+    fn insert(&mut self, value: impl ToString, name: impl Into<String>) {
+        self.known.insert(value.to_string(), name.into());
+    }
+
+    /// 把应答中的运行期值替换为占位符
+    fn normalize(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, field) in map.iter_mut() {
+                    if VOLATILE_KEYS.contains(&key.as_str()) && !field.is_null() {
+                        *field = Value::String(format!("<{key}>"));
+                    } else {
+                        self.normalize(field);
+                    }
+                }
+            }
+            Value::Array(items) => {
+                items.iter_mut().for_each(|item| self.normalize(item));
+                // 节点列表来自散列表，顺序不作保证
+                if items.iter().all(|item| item.get("id").is_some()) {
+                    items.sort_by_key(|item| item.to_string());
+                }
+            }
+            Value::String(s) => {
+                if let Some(name) = self.known.get(s.as_str()) {
+                    *s = name.clone();
+                } else if Uuid::parse_str(s).is_ok() {
+                    *s = "<uuid>".to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 展开脚本中的 `$节点`、`$节点.addr` 与 `$节点.node_info`
+    fn expand(&self, nodes: &BTreeMap<String, Node>, value: &mut Value) -> Result<()> {
+        match value {
+            Value::Object(map) => map.values_mut().try_for_each(|field| self.expand(nodes, field)),
+            Value::Array(items) => items.iter_mut().try_for_each(|item| self.expand(nodes, item)),
+            Value::String(s) if s.starts_with('$') => {
+                let (name, field) = s[1..].split_once('.').unwrap_or((&s[1..], ""));
+                let node = nodes.get(name).with_context(|| format!("脚本引用了未声明的节点: {s}"))?;
+                *value = match field {
+                    "" => Value::String(node.info.id.to_string()),
+                    "addr" => Value::String(node.info.listen_addr.to_string()),
+                    "node_info" => serde_json::to_value(&node.info)?,
+                    _ => anyhow::bail!("未知的占位符: {s}"),
+                };
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 为脚本中的消息（含嵌套的内层消息）补全ID、时间戳等字段，记下生成的ID以便在应答中识别
+fn complete_messages(value: &mut Value, path: &str, placeholders: &mut Placeholders) -> Result<()> {
+    let Value::Object(map) = value else { return Ok(()) };
+    if map.contains_key("message_type") {
+        let Value::Object(template) = serde_json::to_value(Message::new(MessageType::Ping, Value::Null))? else { unreachable!() };
+        placeholders.insert(template["id"].as_str().unwrap_or_default(), format!("$sent{path}"));
+        for (key, default) in template {
+            map.entry(key).or_insert(default);
+        }
+    }
+    for (key, field) in map.iter_mut() {
+        complete_messages(field, &format!("{path}.{key}"), placeholders)?;
+    }
+    Ok(())
+}
+
+async fn start_server(port: u16) -> Result<SocketAddr> {
+    let config = Config {
+        network_id: NETWORK_ID.to_string(),
+        listen_address: SocketAddr::from(([127, 0, 0, 1], port)),
+        // 节点列表立即推送，不随去抖窗口落到下一步
+        peerlist_broadcast_debounce_ms: 0,
+        ..Config::default()
+    };
+    let addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+    Ok(addr)
+}
+
+/// 收集各节点在本步收到的消息，直到静默 `QUIET`
+async fn collect(nodes: &BTreeMap<String, Node>) -> Result<Received> {
+    let mut received = Received::new();
+    let deadline = Instant::now() + STEP_LIMIT;
+    let mut last = Instant::now();
+    let mut buffer = vec![0u8; 65536];
+    while last.elapsed() < QUIET && Instant::now() < deadline {
+        for (name, node) in nodes {
+            while let Ok(Ok((len, _))) = timeout(Duration::from_millis(10), node.socket.recv_from(&mut buffer)).await {
+                let message: Message = serde_json::from_slice(&buffer[..len]).with_context(|| format!("{name} 收到无法解析的消息"))?;
+                received.entry(name.clone()).or_default().push(serde_json::to_value(&message)?);
+                last = Instant::now();
+            }
+        }
+    }
+    Ok(received)
+}
+
+/// 运行一个脚本，返回各步实际收到的（已替换占位符的）应答
+async fn run_script(script: &Script, port: u16) -> Result<Vec<Received>> {
+    let server_addr = start_server(port).await?;
+    let mut placeholders = Placeholders::default();
+    placeholders.insert(server_addr, "$server.addr");
+    placeholders.insert(format!("p2p_node_{port}"), "$server.name");
+    placeholders.insert(env!("CARGO_PKG_VERSION"), "$version");
+
+    let mut nodes = BTreeMap::new();
+    for spec in &script.nodes {
+        let (name, network_id) = match spec {
+            NodeSpec::Name(name) => (name, NETWORK_ID),
+            NodeSpec::InNetwork { name, network_id } => (name, network_id.as_str()),
+        };
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        let info = NodeInfo::new(name.clone(), socket.local_addr()?, network_id.to_string());
+        placeholders.insert(info.id, format!("${name}"));
+        placeholders.insert(info.listen_addr, format!("${name}.addr"));
+        nodes.insert(name.clone(), Node { socket, info });
+    }
+
+    let mut observed = Vec::new();
+    for (index, step) in script.steps.iter().enumerate() {
+        let sender = nodes.get(&step.from).with_context(|| format!("第 {index} 步的发送方未声明: {}", step.from))?;
+        let mut message = step.send.clone();
+        placeholders.expand(&nodes, &mut message)?;
+        // 上一步生成的ID不再指代本步的请求
+        placeholders.known.retain(|_, name| !name.starts_with("$sent"));
+        complete_messages(&mut message, "", &mut placeholders)?;
+        let message: Message = serde_json::from_value(message).with_context(|| format!("第 {index} 步的消息无效"))?;
+        sender.socket.send_to(&serde_json::to_vec(&message)?, server_addr).await?;
+
+        let mut received = collect(&nodes).await?;
+        for messages in received.values_mut() {
+            messages.iter_mut().for_each(|message| placeholders.normalize(message));
+        }
+        observed.push(received);
+    }
+    Ok(observed)
+}
+
+fn golden_files() -> Result<Vec<PathBuf>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+#[tokio::test]
+async fn test_protocol_matches_golden_files() -> Result<()> {
+    let _ = env_logger::try_init();
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let files = golden_files()?;
+    assert!(!files.is_empty() && files.len() <= 10, "黄金文件数应在 1 到 10 之间（每个脚本占用一个端口）");
+
+    let mut failures = Vec::new();
+    for (index, path) in files.iter().enumerate() {
+        let mut script: Script = serde_json::from_str(&std::fs::read_to_string(path)?)
+            .with_context(|| format!("无法解析 {}", path.display()))?;
+        let observed = run_script(&script, FIRST_PORT + index as u16).await.with_context(|| format!("运行 {} 失败", path.display()))?;
+
+        for (step_index, (step, actual)) in script.steps.iter_mut().zip(observed).enumerate() {
+            if update {
+                step.expect = actual;
+            } else if step.expect != actual {
+                failures.push(format!(
+                    "{} 第 {} 步不一致\n期望: {}\n实际: {}",
+                    path.display(),
+                    step_index,
+                    serde_json::to_string_pretty(&step.expect)?,
+                    serde_json::to_string_pretty(&actual)?,
+                ));
+            }
+        }
+        if update {
+            std::fs::write(path, serde_json::to_string_pretty(&script)? + "\n")?;
+        }
+    }
+    assert!(failures.is_empty(), "协议行为与黄金文件不一致（确认是有意的变更后以 UPDATE_GOLDEN=1 重新生成）:\n\n{}", failures.join("\n\n"));
+    Ok(())
+}
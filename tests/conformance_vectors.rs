@@ -0,0 +1,149 @@
+//! 用本实现核对 `tests/fixtures` 中的协议测试向量：每条向量的输入经本实现解析后，结果须与向量记录的期望一致
+
+mod fixtures;
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use fixtures::{Vector, VectorFile};
+use p2p_handshake_server::identity::{verify_handshake_request, verify_handshake_response, verify_routed};
+use p2p_handshake_server::protocol::{HandshakeProtocol, Message};
+use p2p_handshake_server::stun_protocol::StunMessage;
+use p2p_handshake_server::{is_stun_packet, IdentityError, RoutedMessage};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 解析握手请求并校验其中的密钥证明
+fn handshake_request_outcome(message: &Message) -> Value {
+    let Ok(info) = HandshakeProtocol::validate_handshake_request(message) else {
+        return json!({ "valid": false });
+    };
+    let key_proof = match (&info.public_key, verify_handshake_request(message, &info)) {
+        (None, _) => "absent",
+        (Some(_), Ok(())) => "valid",
+        (Some(_), Err(_)) => "invalid",
+    };
+    json!({
+        "valid": true,
+        "node_id": info.id,
+        "name": info.name,
+        "version": info.version,
+        "network_id": info.network_id,
+        "listen_addr": info.listen_addr,
+        "public_key": info.public_key,
+        "key_proof": key_proof,
+    })
+}
+
+/// 解析握手响应并按对应的请求ID校验服务器签名
+fn handshake_response_outcome(message: &Message, context: &Value, network_id: &str) -> Value {
+    let Ok(response) = HandshakeProtocol::validate_handshake_response(message) else {
+        return json!({ "valid": false });
+    };
+    let request_id: Uuid = serde_json::from_value(context["request_id"].clone()).expect("握手响应向量需要 context.request_id");
+    let (signature, fingerprint) = match verify_handshake_response(message, request_id, network_id) {
+        Ok(fingerprint) => ("valid", Some(fingerprint)),
+        Err(IdentityError::Unsigned) => ("absent", None),
+        Err(_) => ("invalid", None),
+    };
+    json!({
+        "valid": true,
+        "success": response.success,
+        "error_message": response.error_message,
+        "public_addr": response.public_addr,
+        "retry_after_secs": response.retry_after_secs,
+        "session_ticket": response.session_ticket,
+        "server_signature": signature,
+        "server_fingerprint": fingerprint,
+    })
+}
+
+/// 解析 cookie 质询
+fn handshake_cookie_outcome(message: &Message) -> Value {
+    match HandshakeProtocol::cookie_challenge(message) {
+        Some(cookie) => json!({ "valid": true, "cookie": cookie }),
+        None => json!({ "valid": false }),
+    }
+}
+
+/// 解析 `Data` 消息中的路由消息并校验源节点签名
+fn routed_outcome(message: &Message, network_id: &str) -> Value {
+    let Ok(routed) = RoutedMessage::from_message(message) else {
+        return json!({ "valid": false });
+    };
+    let signature = match verify_routed(&routed, network_id) {
+        Ok(_) => "valid",
+        Err(IdentityError::UnsignedRoute) => "absent",
+        Err(_) => "invalid",
+    };
+    json!({
+        "valid": true,
+        "source_node": routed.source_node,
+        "destination_node": routed.destination_node,
+        "multicast": routed.multicast,
+        "hop_count": routed.hop_count,
+        "max_hops": routed.max_hops,
+        "route_id": routed.route_id,
+        "receipt_requested": routed.receipt_requested,
+        "inner_message_type": routed.original_message.message_type,
+        "inner_payload": routed.original_message.payload,
+        "signature": signature,
+    })
+}
+
+/// 解析 STUN 数据包
+fn stun_outcome(bytes: &[u8]) -> Value {
+    let is_stun = is_stun_packet(bytes);
+    let Ok(message) = StunMessage::from_bytes(bytes) else {
+        return json!({ "is_stun": is_stun, "valid": false });
+    };
+    let attributes: Vec<Value> = message.attributes.iter().map(|attr| json!({ "type": attr.attr_type, "value": hex(&attr.value) })).collect();
+    json!({
+        "is_stun": is_stun,
+        "valid": true,
+        "message_type": message.message_type,
+        "transaction_id": hex(&message.transaction_id),
+        "attributes": attributes,
+        "mapped_address": message.extract_mapped_address(),
+    })
+}
+
+fn check(file: &VectorFile, outcome: impl Fn(&Vector) -> Value) {
+    assert!(!file.vectors.is_empty(), "{} 中没有向量", file.description);
+    let failures: Vec<String> = file
+        .vectors
+        .iter()
+        .filter_map(|vector| {
+            let actual = outcome(vector);
+            (actual != vector.expect).then(|| format!("{}（{}）\n期望: {}\n实际: {}", vector.name, vector.description, vector.expect, actual))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{} 与本实现不一致:\n\n{}", file.description, failures.join("\n\n"));
+}
+
+#[test]
+fn test_handshake_vectors() {
+    let file = fixtures::load("handshake");
+    check(&file, |vector| {
+        let message = vector.message();
+        match vector.kind.as_str() {
+            "handshake_request" => handshake_request_outcome(&message),
+            "handshake_response" => handshake_response_outcome(&message, &vector.context, &file.network_id),
+            "handshake_cookie" => handshake_cookie_outcome(&message),
+            kind => panic!("向量 {} 的种类 {} 不属于握手", vector.name, kind),
+        }
+    });
+}
+
+#[test]
+fn test_routing_vectors() {
+    let file = fixtures::load("routing");
+    check(&file, |vector| routed_outcome(&vector.message(), &file.network_id));
+}
+
+#[test]
+fn test_stun_vectors() {
+    check(&fixtures::load("stun"), |vector| stun_outcome(&vector.bytes()));
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+
+use p2p_handshake_server::{Config, ConnectionLimitsConfig, P2PServer};
+use p2p_handshake_server::protocol::MessageType;
+use common::{handshake, receive_type};
+
+#[tokio::test]
+async fn test_soft_limit_marks_busy_and_hard_limit_rejects() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18220".parse().unwrap(),
+        max_connections: 2,
+        connection_limits: ConnectionLimitsConfig { soft_limit: Some(1), ..ConnectionLimitsConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 软限制以内：正常接受，负载提示为不繁忙
+    let first = UdpSocket::bind("127.0.0.1:0").await?;
+    assert!(handshake(&first, server_addr, "first").await?.1.success);
+    let discovery = receive_type(&first, MessageType::DiscoveryResponse).await?
+        .expect("未收到节点列表");
+    let load = discovery.load.expect("启用软限制时应附带负载提示");
+    assert!(!load.busy);
+    assert_eq!(load.heartbeat_interval_secs, 30);
+
+    // 超过软限制：仍接受，但标记繁忙并放慢心跳
+    let second = UdpSocket::bind("127.0.0.1:0").await?;
+    assert!(handshake(&second, server_addr, "second").await?.1.success);
+    let discovery = receive_type(&second, MessageType::DiscoveryResponse).await?
+        .expect("未收到节点列表");
+    let load = discovery.load.unwrap();
+    assert!(load.busy);
+    assert_eq!(load.heartbeat_interval_secs, 60);
+
+    // 达到硬限制：拒绝握手并给出重试时间
+    let third = UdpSocket::bind("127.0.0.1:0").await?;
+    let (_, rejected) = handshake(&third, server_addr, "third").await?;
+    assert!(!rejected.success);
+    assert_eq!(rejected.retry_after_secs, Some(30));
+
+    Ok(())
+}
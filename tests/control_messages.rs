@@ -0,0 +1,71 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{Config, ControlCommand, ControlConfig, P2PServer};
+use p2p_handshake_server::protocol::{
+    GetRoutesResponse, GetStatsResponse, HandshakeResponse, Message, MessageType, NodeInfo,
+};
+use common::{receive_any, send_message};
+
+/// 发送控制查询，返回响应或 `Error`
+async fn query(socket: &UdpSocket, server: SocketAddr, request: MessageType, response: MessageType) -> Result<Message> {
+    send_message(socket, &Message::control_request(request), server).await?;
+    Ok(receive_any(socket, &[response, MessageType::Error]).await?.expect("控制查询未收到响应"))
+}
+
+#[tokio::test]
+async fn test_typed_control_queries_and_permissions() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18330".parse().unwrap(),
+        control: ControlConfig {
+            allowed: vec![ControlCommand::GetRoutes, ControlCommand::GetStats],
+            ..ControlConfig::default()
+        },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 未握手的来源无权查询
+    let stranger = UdpSocket::bind("127.0.0.1:0").await?;
+    let denied = query(&stranger, server_addr, MessageType::GetStatsRequest, MessageType::GetStatsResponse).await?;
+    assert_eq!(denied.message_type, MessageType::Error);
+
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("client".to_string(), client.local_addr()?, "test".to_string());
+    send_message(&client, &Message::handshake_request(info.clone())?, server_addr).await?;
+    let handshake = receive_any(&client, &[MessageType::HandshakeResponse]).await?.expect("握手未在超时内收到响应");
+    let handshake: HandshakeResponse = serde_json::from_value(handshake.payload)?;
+
+    // 路由表包含到客户端的直连路由
+    let routes = query(&client, server_addr, MessageType::GetRoutesRequest, MessageType::GetRoutesResponse).await?;
+    let routes: GetRoutesResponse = serde_json::from_value(routes.payload)?;
+    assert!(routes.routes.iter().any(|r| r.destination == info.id && r.distance == 1));
+
+    let stats = query(&client, server_addr, MessageType::GetStatsRequest, MessageType::GetStatsResponse).await?;
+    let stats: GetStatsResponse = serde_json::from_value(stats.payload)?;
+    assert_eq!(stats.node_id, handshake.node_info.id);
+    assert!(stats.peers.authenticated >= 1);
+
+    // 未开放的查询被拒绝
+    let peers = query(&client, server_addr, MessageType::GetPeersRequest, MessageType::GetPeersResponse).await?;
+    assert_eq!(peers.message_type, MessageType::Error);
+
+    // 弃用的字符串命令仍可使用
+    send_message(&client, &Message::data(serde_json::json!({"cmd": "get_routes"})), server_addr).await?;
+    let legacy = receive_any(&client, &[MessageType::Data]).await?.expect("字符串命令未收到响应");
+    assert!(legacy.payload["routes"].is_array());
+
+    Ok(())
+}
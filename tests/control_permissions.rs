@@ -0,0 +1,132 @@
+mod common;
+
+use anyhow::Result;
+use std::collections::HashMap;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration, sleep};
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use p2p_handshake_server::{Config, ControlConfig, P2PServer, PeerRole};
+use p2p_handshake_server::protocol::{ListNodesResponse, Message, MessageType, NodeInfo};
+use common::{handshake_with, receive_any, send_message};
+
+/// 握手，`token` 放入元数据 `access_token`，返回节点ID
+async fn handshake_with_token(socket: &UdpSocket, server: SocketAddr, token: Option<&str>) -> Result<Uuid> {
+    let mut info = NodeInfo::new("client".to_string(), socket.local_addr()?, "test".to_string());
+    if let Some(token) = token {
+        info.metadata.insert("access_token".to_string(), token.to_string());
+    }
+    handshake_with(socket, server, &info).await?;
+    Ok(info.id)
+}
+
+/// 发送查询，返回响应或 `Error`
+async fn query(socket: &UdpSocket, server: SocketAddr, request: MessageType, response: MessageType) -> Result<Message> {
+    send_message(socket, &Message::new(request, serde_json::json!({})), server).await?;
+    Ok(receive_any(socket, &[response, MessageType::Error]).await?.expect("查询未收到响应"))
+}
+
+#[tokio::test]
+async fn test_diagnostics_gated_by_role_and_rate_limited() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18340".parse().unwrap(),
+        control: ControlConfig {
+            default_role: PeerRole::Observer,
+            tokens: HashMap::from([
+                ("member-secret".to_string(), PeerRole::Member),
+                ("admin-secret".to_string(), PeerRole::Admin),
+            ]),
+            rate_limit_per_minute: 3,
+            ..ControlConfig::default()
+        },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 无令牌的观察者只能查看统计信息
+    let observer = UdpSocket::bind("127.0.0.1:0").await?;
+    handshake_with_token(&observer, server_addr, None).await?;
+    let stats = query(&observer, server_addr, MessageType::GetStatsRequest, MessageType::GetStatsResponse).await?;
+    assert_eq!(stats.message_type, MessageType::GetStatsResponse);
+    for (request, response) in [
+        (MessageType::ListNodesRequest, MessageType::ListNodesResponse),
+        (MessageType::GetRoutesRequest, MessageType::GetRoutesResponse),
+        (MessageType::TopologyRequest, MessageType::TopologyResponse),
+    ] {
+        let denied = query(&observer, server_addr, request.clone(), response).await?;
+        assert_eq!(denied.message_type, MessageType::Error, "观察者不应获得 {:?} 的结果", request);
+    }
+
+    // 成员可以列出节点，令牌不会出现在节点信息中；超出每分钟配额后被拒绝
+    let member = UdpSocket::bind("127.0.0.1:0").await?;
+    let member_id = handshake_with_token(&member, server_addr, Some("member-secret")).await?;
+    let list = query(&member, server_addr, MessageType::ListNodesRequest, MessageType::ListNodesResponse).await?;
+    let list: ListNodesResponse = serde_json::from_value(list.payload)?;
+    let listed = list.nodes.iter().find(|n| n.id == member_id).expect("节点列表中缺少成员自身");
+    assert!(!listed.metadata.contains_key("access_token"));
+    for _ in 0..2 {
+        let allowed = query(&member, server_addr, MessageType::GetRoutesRequest, MessageType::GetRoutesResponse).await?;
+        assert_eq!(allowed.message_type, MessageType::GetRoutesResponse);
+    }
+    let limited = query(&member, server_addr, MessageType::GetRoutesRequest, MessageType::GetRoutesResponse).await?;
+    assert_eq!(limited.message_type, MessageType::Error);
+
+    // 管理者不受频率限制
+    let admin = UdpSocket::bind("127.0.0.1:0").await?;
+    handshake_with_token(&admin, server_addr, Some("admin-secret")).await?;
+    for _ in 0..5 {
+        let topology = query(&admin, server_addr, MessageType::TopologyRequest, MessageType::TopologyResponse).await?;
+        assert_eq!(topology.message_type, MessageType::TopologyResponse);
+    }
+
+    assert_eq!(metrics.snapshot().control_denied, 4);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_access_token_never_reaches_other_peers() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18721".parse().unwrap(),
+        control: ControlConfig {
+            tokens: HashMap::from([("member-secret".to_string(), PeerRole::Member)]),
+            ..ControlConfig::default()
+        },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let observer = UdpSocket::bind("127.0.0.1:0").await?;
+    handshake_with_token(&observer, server_addr, None).await?;
+    let member = UdpSocket::bind("127.0.0.1:0").await?;
+    let member_id = handshake_with_token(&member, server_addr, Some("member-secret")).await?;
+
+    // 观察者收到的节点列表广播包含新成员，但任何报文中都没有令牌
+    let mut buffer = vec![0u8; 65536];
+    let mut announced = false;
+    while let Ok(received) = timeout(Duration::from_millis(1500), observer.recv_from(&mut buffer)).await {
+        let (len, _) = received?;
+        let text = String::from_utf8_lossy(&buffer[..len]);
+        assert!(!text.contains("member-secret"), "{}", text);
+        announced |= text.contains(&member_id.to_string());
+    }
+    assert!(announced, "观察者未收到新成员的节点信息");
+    Ok(())
+}
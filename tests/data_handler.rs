@@ -0,0 +1,77 @@
+mod common;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use p2p_handshake_server::{Config, MessageHandler, P2PServer, ServerMetrics};
+use p2p_handshake_server::protocol::{Message, MessageType};
+use common::{handshake, receive_type_within, send_message};
+
+/// 回复发送方ID的处理器
+struct Greeter;
+
+impl MessageHandler for Greeter {
+    fn handle_data<'a>(&'a self, from: Uuid, message: &'a Message) -> BoxFuture<'a, Result<Option<Message>>> {
+        Box::pin(async move {
+            Ok(Some(Message::data(serde_json::json!({ "hello": from, "got": message.payload }))))
+        })
+    }
+}
+
+async fn start_server(listen: &str, echo: bool, handler: Option<Arc<dyn MessageHandler>>) -> Result<(SocketAddr, Arc<ServerMetrics>)> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: listen.parse().unwrap(),
+        echo_unhandled_data: echo,
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config.clone()).await?;
+    if let Some(handler) = handler {
+        server.set_message_handler(handler);
+    }
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+    Ok((config.listen_address, metrics))
+}
+
+#[tokio::test]
+async fn test_unhandled_data_dropped_echoed_or_dispatched() -> Result<()> {
+    let _ = env_logger::try_init();
+    let payload = Message::data(serde_json::json!({"app": "ping"}));
+
+    // 默认：不回显，计入指标
+    let (server, metrics) = start_server("127.0.0.1:18320", false, None).await?;
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    handshake(&client, server, "client").await?;
+    send_message(&client, &payload, server).await?;
+    assert!(receive_type_within(&client, MessageType::Data, Duration::from_millis(500)).await?.is_none());
+    assert_eq!(metrics.snapshot().data_unhandled, 1);
+
+    // 显式开启回显
+    let (server, _) = start_server("127.0.0.1:18321", true, None).await?;
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    handshake(&client, server, "client").await?;
+    send_message(&client, &payload, server).await?;
+    let echo = receive_type_within(&client, MessageType::Data, Duration::from_secs(2)).await?.expect("未收到回显");
+    assert_eq!(echo.payload["echo"]["app"], "ping");
+
+    // 注册处理器后交给处理器，回复发送给客户端
+    let (server, metrics) = start_server("127.0.0.1:18322", true, Some(Arc::new(Greeter))).await?;
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let client_id = handshake(&client, server, "client").await?.0.id;
+    send_message(&client, &payload, server).await?;
+    let reply = receive_type_within(&client, MessageType::Data, Duration::from_secs(2)).await?.expect("未收到处理器回复");
+    assert_eq!(reply.payload["hello"], client_id.to_string());
+    assert_eq!(reply.payload["got"]["app"], "ping");
+    assert_eq!(metrics.snapshot().data_unhandled, 0);
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use tokio::time::{timeout, Duration, sleep};
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer};
+use p2p_handshake_server::router::RoutedMessage;
+
+fn client_config(server_addr: SocketAddr) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        ..ClientConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_destination_receipt_reaches_sender() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18470".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = P2PClient::connect(client_config(server_addr)).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr)).await?;
+
+    // 目标节点收到后自动发回回执
+    let receipt = alice.route_with_receipt(bob.node_id(), serde_json::json!({ "n": 1 }), Duration::from_secs(3)).await?;
+    assert_eq!(receipt.destination, bob.node_id());
+    let delivered = timeout(Duration::from_secs(3), bob.recv_data()).await?.expect("目标节点未收到消息");
+    let routed = RoutedMessage::from_message(&delivered)?;
+    assert_eq!(routed.route_id, receipt.route_id);
+    assert_eq!(routed.original_message.payload["n"], 1);
+    assert!(routed.receipt_requested);
+
+    // 不要求回执的消息照常送达
+    alice.route(bob.node_id(), serde_json::json!({ "n": 2 })).await?;
+    let delivered = timeout(Duration::from_secs(3), bob.recv_data()).await?.expect("目标节点未收到消息");
+    assert!(!RoutedMessage::from_message(&delivered)?.receipt_requested);
+
+    // 目标不存在时收不到回执
+    assert!(alice.route_with_receipt(Uuid::new_v4(), serde_json::json!({ "n": 3 }), Duration::from_millis(300)).await.is_err());
+    Ok(())
+}
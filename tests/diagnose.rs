@@ -0,0 +1,56 @@
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::protocol::{Message, MessageType};
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer};
+
+#[tokio::test]
+async fn test_diagnose_reports_connection_standing() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18693".parse().unwrap(),
+        heartbeat_interval: 1,
+        allow_symmetric_nat_relay: true,
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 未握手的节点不能自检
+    let stranger = UdpSocket::bind("127.0.0.1:0").await?;
+    let request = Message::diagnose();
+    stranger.send_to(&serde_json::to_vec(&request)?, server_addr).await?;
+    let mut buffer = vec![0u8; 65536];
+    let (len, _) = timeout(Duration::from_secs(2), stranger.recv_from(&mut buffer)).await??;
+    let reply: Message = serde_json::from_slice(&buffer[..len])?;
+    assert_eq!((reply.message_type, reply.reply_to), (MessageType::Error, Some(request.id)));
+
+    let client = P2PClient::connect(ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        name: "alice".to_string(),
+        network_id: "test".to_string(),
+        ..ClientConfig::default()
+    })
+    .await?;
+    // 等服务器发出并收到至少一次心跳
+    sleep(Duration::from_millis(2500)).await;
+
+    let report = client.diagnose().await?;
+    assert_eq!(report.observed_addr, client.local_addr()?);
+    assert!(report.pings.answered >= 1, "{:?}", report.pings);
+    assert_eq!(report.pings.lost, 0);
+    assert!(report.pings.rtt_avg_ms.is_some());
+    let link = report.link.expect("缺少链路质量估计");
+    assert!(link.srtt_ms >= 0.0 && link.rto_ms >= 50, "{:?}", link);
+    assert!(report.relay_allowed);
+    assert_eq!(report.carrier_nat, None);
+    assert_eq!((report.rate_limit.control_per_minute, report.rate_limit.control_remaining), (60, Some(60)));
+    Ok(())
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration, sleep};
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use p2p_handshake_server::{Config, DiscoveryConfig, P2PServer};
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo, PeerInfo};
+use common::send_message;
+
+/// 接收消息直到出现满足条件的消息，超时返回 `None`
+async fn receive_matching(socket: &UdpSocket, wait: Duration, matches: impl Fn(&Message) -> bool) -> Result<Option<Message>> {
+    let mut buffer = vec![0u8; 65536];
+    let deadline = tokio::time::Instant::now() + wait;
+    loop {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        match timeout(remaining, socket.recv_from(&mut buffer)).await {
+            Ok(Ok((len, _addr))) => {
+                let message: Message = serde_json::from_slice(&buffer[..len])?;
+                if matches(&message) {
+                    return Ok(Some(message));
+                }
+            }
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => return Ok(None),
+        }
+    }
+}
+
+/// 以指定的NAT类型与区域握手，返回套接字与节点ID
+async fn join(server: SocketAddr, nat_type: &str, region: Option<&str>) -> Result<(UdpSocket, Uuid)> {
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let mut info = NodeInfo::new("client".to_string(), socket.local_addr()?, "test".to_string());
+    info.metadata.insert("nat_type".to_string(), nat_type.to_string());
+    if let Some(region) = region {
+        info.metadata.insert("region".to_string(), region.to_string());
+    }
+    send_message(&socket, &Message::handshake_request(info.clone())?, server).await?;
+    let response = receive_matching(&socket, Duration::from_secs(3), |m| m.message_type == MessageType::HandshakeResponse).await?;
+    assert!(response.is_some(), "握手未在超时内收到响应");
+    Ok((socket, info.id))
+}
+
+#[tokio::test]
+async fn test_truncated_discovery_prefers_reachable_peers() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    // 预算只够放下两个节点
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18540".parse().unwrap(),
+        discovery: DiscoveryConfig { max_payload_bytes: 450 },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let (_a, _) = join(server_addr, "Symmetric", None).await?;
+    let (_b, _) = join(server_addr, "Symmetric", Some("eu")).await?;
+    let (_c, cone) = join(server_addr, "FullCone", None).await?;
+    let (_d, nearby) = join(server_addr, "Restricted", Some("eu")).await?;
+    let (requester, _) = join(server_addr, "Symmetric", Some("eu")).await?;
+
+    // 对称型NAT的请求方：完全锥形的节点最优先，其次是同区域的受限锥形节点，对称型节点被舍弃
+    let request = Message::discovery_request();
+    send_message(&requester, &request, server_addr).await?;
+    let response = receive_matching(&requester, Duration::from_secs(2), |m| m.reply_to == Some(request.id))
+        .await?
+        .expect("未收到节点发现响应");
+    assert!(serde_json::to_vec(&response.payload)?.len() <= 450);
+    let peers: Vec<PeerInfo> = serde_json::from_value(response.payload)?;
+    assert_eq!(peers.iter().map(|p| p.id).collect::<Vec<_>>(), vec![cone, nearby]);
+    Ok(())
+}
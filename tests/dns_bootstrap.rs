@@ -0,0 +1,124 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{ClusterConfig, Config, DnsBootstrap, DnsBootstrapConfig, P2PServer};
+use p2p_handshake_server::dns_bootstrap::{build_query, DNS_TYPE_A, DNS_TYPE_SRV, DNS_TYPE_TXT};
+use p2p_handshake_server::protocol::{ListNodesResponse, Message, MessageType};
+use common::{handshake, receive_type, send_message};
+
+/// 追加一条资源记录，名称使用指向问题区域名的压缩指针
+fn push_record(packet: &mut Vec<u8>, rtype: u16, rdata: &[u8]) {
+    packet.extend_from_slice(&[0xC0, 12]);
+    packet.extend_from_slice(&rtype.to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes());
+    packet.extend_from_slice(&60u32.to_be_bytes());
+    packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    packet.extend_from_slice(rdata);
+}
+
+/// SRV 记录：目标为问题区域名本身，地址由附加区的 A 记录给出
+fn push_srv(packet: &mut Vec<u8>, addr: SocketAddr) {
+    let mut rdata = vec![0, 10, 0, 0];
+    rdata.extend_from_slice(&addr.port().to_be_bytes());
+    rdata.extend_from_slice(&[0xC0, 12]);
+    push_record(packet, DNS_TYPE_SRV, &rdata);
+    let SocketAddr::V4(v4) = addr else { unreachable!() };
+    push_record(packet, DNS_TYPE_A, &v4.ip().octets());
+}
+
+/// 模拟 example.test 域的 DNS 服务器
+async fn serve_dns(socket: UdpSocket, server: SocketAddr, gossip_seed: SocketAddr) -> Result<()> {
+    let mut buffer = vec![0u8; 512];
+    loop {
+        let (len, from) = socket.recv_from(&mut buffer).await?;
+        let query = buffer[..len].to_vec();
+        let mut response = query.clone();
+        response[2] = 0x81;
+        response[3] = 0x80;
+        let (mut answers, mut additional) = (1u16, 0u16);
+        let name_is = |name: &str, qtype: u16| -> bool {
+            let expected = build_query(0, name, qtype).unwrap();
+            query[12..] == expected[12..]
+        };
+        if name_is("_p2p._udp.example.test", DNS_TYPE_SRV) {
+            push_srv(&mut response, server);
+            additional = 1;
+        } else if name_is("_p2p-gossip._udp.example.test", DNS_TYPE_SRV) {
+            push_srv(&mut response, gossip_seed);
+            additional = 1;
+        } else if name_is("_p2p.example.test", DNS_TYPE_TXT) {
+            let mut rdata = vec![15];
+            rdata.extend_from_slice(b"network_id=test");
+            push_record(&mut response, DNS_TYPE_TXT, &rdata);
+        } else {
+            answers = 0;
+            response[3] = 0x83;
+        }
+        response[6..8].copy_from_slice(&answers.to_be_bytes());
+        response[10..12].copy_from_slice(&additional.to_be_bytes());
+        socket.send_to(&response, from).await?;
+    }
+}
+
+async fn start_server(listen: &str, gossip: &str, dns_bootstrap: DnsBootstrapConfig) -> Result<SocketAddr> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: listen.parse().unwrap(),
+        cluster: ClusterConfig {
+            enable: true,
+            bind_address: gossip.parse().unwrap(),
+            gossip_interval_ms: 100,
+            ..ClusterConfig::default()
+        },
+        dns_bootstrap,
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config.clone()).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    Ok(config.listen_address)
+}
+
+#[tokio::test]
+async fn test_bootstrap_from_dns_records() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let dns_addr: SocketAddr = "127.0.0.1:18292".parse().unwrap();
+    let dns = UdpSocket::bind(dns_addr).await?;
+    tokio::spawn(serve_dns(dns, "127.0.0.1:18290".parse().unwrap(), "127.0.0.1:18293".parse().unwrap()));
+
+    // 实例A未配置任何种子；实例B只从DNS得到实例A的gossip地址
+    let server_a = start_server("127.0.0.1:18290", "127.0.0.1:18293", DnsBootstrapConfig::default()).await?;
+    let dns_config = DnsBootstrapConfig {
+        domain: Some("example.test".to_string()),
+        nameserver: Some(dns_addr),
+        refresh_interval_secs: 1,
+        ..DnsBootstrapConfig::default()
+    };
+    let server_b = start_server("127.0.0.1:18291", "127.0.0.1:18294", dns_config.clone()).await?;
+    sleep(Duration::from_millis(200)).await;
+
+    // 客户端同样通过DNS找到服务器，无需写死地址
+    let info = DnsBootstrap::from_config(&dns_config)?.resolve().await?;
+    assert_eq!(info.servers, vec![server_a]);
+    assert_eq!(info.network_id.as_deref(), Some("test"));
+
+    let client_a = UdpSocket::bind("127.0.0.1:0").await?;
+    let client_b = UdpSocket::bind("127.0.0.1:0").await?;
+    let (info_a, _) = handshake(&client_a, info.servers[0], "client_on_a").await?;
+    handshake(&client_b, server_b, "client_on_b").await?;
+    sleep(Duration::from_millis(600)).await;
+
+    // 两个实例经DNS给出的种子组成集群，实例B能看到实例A上的节点
+    send_message(&client_b, &Message::new(MessageType::ListNodesRequest, serde_json::json!({})), server_b).await?;
+    let list = receive_type(&client_b, MessageType::ListNodesResponse).await?.expect("未收到节点列表");
+    let list: ListNodesResponse = serde_json::from_value(list.payload)?;
+    assert!(list.nodes.iter().any(|n| n.id == info_a.id), "实例B应通过DNS种子加入集群");
+
+    Ok(())
+}
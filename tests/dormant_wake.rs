@@ -0,0 +1,70 @@
+use anyhow::Result;
+use std::sync::atomic::Ordering;
+use tokio::time::{timeout, Duration, sleep};
+
+use p2p_handshake_server::{ClientConfig, Config, DormantConfig, P2PClient, P2PServer};
+
+fn client_config(server_addr: std::net::SocketAddr, name: &str) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        name: name.to_string(),
+        network_id: "test".to_string(),
+        ..ClientConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_dormant_peer_is_woken_and_receives_queued_messages() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18696".parse().unwrap(),
+        dormant: DormantConfig { enable: true, max_dormant_secs: 60, ..DormantConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = P2PClient::connect(client_config(server_addr, "alice")).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr, "bob")).await?;
+    assert_eq!(bob.go_dormant().await?, Duration::from_secs(60));
+    assert!(bob.is_dormant());
+
+    // 发给休眠节点的路由消息先暂存，服务器随即发送唤醒数据包
+    alice.route(bob.node_id(), serde_json::json!({ "hello": "bob" })).await?;
+    timeout(Duration::from_secs(3), bob.wait_for_wake()).await?;
+    assert!(!bob.is_dormant());
+
+    // 醒来后暂存的消息照常到达
+    let message = timeout(Duration::from_secs(3), bob.recv_data()).await?.expect("未收到暂存的消息");
+    assert_eq!(message.payload["original_message"]["payload"]["hello"], "bob");
+    assert_eq!(metrics.dormant_wakes.load(Ordering::Relaxed), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_dormancy_requires_server_support() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18697".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client = P2PClient::connect(client_config(server_addr, "client")).await?;
+    assert!(client.go_dormant().await.is_err());
+    assert!(!client.is_dormant());
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::protocol::{GetConfigResponse, Message, MessageType, NodeInfo};
+use p2p_handshake_server::{AdminConfig, Config, ControlConfig, P2PServer, PeerRole};
+
+/// 向管理接口发送请求，返回状态码与正文
+async fn admin_request(admin: SocketAddr, method: &str, path: &str, body: &str) -> Result<(u16, String)> {
+    let mut stream = TcpStream::connect(admin).await?;
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer admin-token\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let status = response.split_whitespace().nth(1).unwrap_or_default().parse()?;
+    let (_, body) = response.split_once("\r\n\r\n").unwrap_or_default();
+    Ok((status, body.to_string()))
+}
+
+/// 握手后发送 `GetConfigRequest`，返回响应或 `Error`
+async fn query_config(server: SocketAddr, token: Option<&str>) -> Result<Message> {
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let mut info = NodeInfo::new("client".to_string(), socket.local_addr()?, "test".to_string());
+    if let Some(token) = token {
+        info.metadata.insert("access_token".to_string(), token.to_string());
+    }
+    socket.send_to(&serde_json::to_vec(&Message::handshake_request(info)?)?, server).await?;
+    socket.send_to(&serde_json::to_vec(&Message::control_request(MessageType::GetConfigRequest))?, server).await?;
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let (len, _) = timeout(Duration::from_secs(2), socket.recv_from(&mut buffer)).await??;
+        let message: Message = serde_json::from_slice(&buffer[..len])?;
+        if matches!(message.message_type, MessageType::GetConfigResponse | MessageType::Error) {
+            return Ok(message);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_effective_config_is_redacted_and_reflects_runtime_limits() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let admin: SocketAddr = "127.0.0.1:18661".parse().unwrap();
+    let mut config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18660".parse().unwrap(),
+        admin: AdminConfig { enable: true, listen_address: admin, token: Some("admin-token".to_string()), ..AdminConfig::default() },
+        control: ControlConfig {
+            tokens: HashMap::from([("ops-token".to_string(), PeerRole::Admin)]),
+            ..ControlConfig::default()
+        },
+        ..Config::default()
+    };
+    config.mqtt.password = Some("mqtt-password".to_string());
+    config.stun_server.turn.users.insert("alice".to_string(), "turn-password".to_string());
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 管理接口：敏感字段隐藏，运行中调整的连接数限制生效
+    let (status, _) = admin_request(admin, "PUT", "/api/limits", r#"{"soft_limit": 40, "hard_limit": 50}"#).await?;
+    assert_eq!(status, 200);
+    let (status, body) = admin_request(admin, "GET", "/api/config", "").await?;
+    assert_eq!(status, 200);
+    assert!(!body.contains("mqtt-password") && !body.contains("admin-token") && !body.contains("ops-token"), "{}", body);
+    assert!(!body.contains("turn-password"), "{}", body);
+    let effective: serde_json::Value = serde_json::from_str(&body)?;
+    assert_eq!(effective["mqtt"]["password"], "<redacted>");
+    assert_eq!(effective["admin"]["token"], "<redacted>");
+    assert_eq!(effective["stun_server"]["turn"]["users"]["alice"], "<redacted>");
+    assert_eq!(effective["max_connections"], 50);
+    assert_eq!(effective["connection_limits"]["soft_limit"], 40);
+    assert_eq!(effective["network_id"], "test");
+
+    // 控制查询：默认要求 admin 角色
+    let denied = query_config(server_addr, None).await?;
+    assert_eq!(denied.message_type, MessageType::Error);
+    let reply = query_config(server_addr, Some("ops-token")).await?;
+    assert_eq!(reply.message_type, MessageType::GetConfigResponse);
+    let response: GetConfigResponse = serde_json::from_value(reply.payload)?;
+    assert_eq!(response.config, effective);
+    Ok(())
+}
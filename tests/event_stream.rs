@@ -0,0 +1,57 @@
+use anyhow::Result;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::protocol::{Message, NodeInfo};
+use p2p_handshake_server::{Config, Event, EventKind, EventStreamConfig, P2PServer};
+
+#[tokio::test]
+async fn test_event_stream_reports_peer_lifecycle() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18590".parse().unwrap(),
+        events: EventStreamConfig {
+            enable: true,
+            listen_address: Some("127.0.0.1:18591".parse().unwrap()),
+            ..EventStreamConfig::default()
+        },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let stream = TcpStream::connect("127.0.0.1:18591").await?;
+    let mut lines = BufReader::new(stream).lines();
+    sleep(Duration::from_millis(100)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("client".to_string(), client.local_addr()?, "test".to_string());
+    let node_id = info.id;
+    client.send_to(&serde_json::to_vec(&Message::handshake_request(info)?)?, server_addr).await?;
+    sleep(Duration::from_millis(200)).await;
+    client.send_to(&serde_json::to_vec(&Message::disconnect("bye".to_string()))?, server_addr).await?;
+
+    // 按发生顺序收到上线、认证、断开事件，序号递增
+    let mut events = Vec::new();
+    while events.len() < 3 {
+        let line = timeout(Duration::from_secs(2), lines.next_line()).await??.expect("事件流不应关闭");
+        let event: Event = serde_json::from_str(&line)?;
+        if event.kind.code().starts_with("peer.") {
+            events.push(event);
+        }
+    }
+    let kinds: Vec<EventKind> = events.iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, vec![EventKind::PeerJoined, EventKind::PeerAuthenticated, EventKind::PeerDisconnected]);
+    assert_eq!(events[1].node_id, Some(node_id));
+    assert_eq!(events[1].data["name"], "client");
+    assert_eq!(events[2].node_id, Some(node_id));
+    assert!(events[0].seq < events[1].seq && events[1].seq < events[2].seq);
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! 协议测试向量的加载器
+//!
+//! 向量文件（`tests/fixtures/*.json`）与语言无关，供其他语言实现的客户端核对兼容性，格式见协议文档的“测试向量”一节。
+//! 本模块把它们读入 Rust，供 `tests/conformance_vectors.rs` 用本实现逐条核对。
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+
+use p2p_handshake_server::protocol::Message;
+
+/// 一个向量文件
+#[derive(Debug, Deserialize)]
+pub struct VectorFile {
+    pub description: String,
+    /// 签名与密钥证明所用的网络ID
+    pub network_id: String,
+    pub vectors: Vec<Vector>,
+}
+
+/// 一条测试向量：输入与解析它应得到的结果
+#[derive(Debug, Deserialize)]
+pub struct Vector {
+    pub name: String,
+    /// 输入的种类：`handshake_request`、`handshake_response`、`handshake_cookie`、`routed_message` 或 `stun`
+    pub kind: String,
+    pub description: String,
+    /// 线上的消息（JSON），STUN 向量为数据包的十六进制
+    pub input: Value,
+    /// 校验所需的上下文，例如握手响应对应的请求ID
+    #[serde(default)]
+    pub context: Value,
+    pub expect: Value,
+}
+
+impl Vector {
+    /// 输入按线上格式解析出的消息
+    pub fn message(&self) -> Message {
+        serde_json::from_value(self.input.clone()).unwrap_or_else(|e| panic!("向量 {} 的输入不是有效消息: {}", self.name, e))
+    }
+
+    /// 十六进制输入对应的字节
+    pub fn bytes(&self) -> Vec<u8> {
+        let hex = self.input.as_str().unwrap_or_else(|| panic!("向量 {} 的输入应为十六进制字符串", self.name));
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or_else(|e| panic!("向量 {} 的输入不是十六进制: {}", self.name, e)))
+            .collect()
+    }
+}
+
+/// 读取 `tests/fixtures/<name>.json`
+pub fn load(name: &str) -> VectorFile {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(format!("{name}.json"));
+    let content = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("读取 {} 失败: {}", path.display(), e));
+    serde_json::from_str(&content).unwrap_or_else(|e| panic!("解析 {} 失败: {}", path.display(), e))
+}
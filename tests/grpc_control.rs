@@ -0,0 +1,79 @@
+#![cfg(feature = "grpc")]
+
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use tonic::Code;
+use tonic::transport::Channel;
+
+use p2p_handshake_server::{Config, GrpcConfig, P2PServer};
+use p2p_handshake_server::grpc::{pb, ControlPlaneClient};
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo};
+use common::receive_type;
+
+fn authorized<T>(message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    request.metadata_mut().insert("authorization", "Bearer secret".parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn test_list_and_kick_peer_over_grpc() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18190".parse().unwrap(),
+        grpc: GrpcConfig {
+            enable: true,
+            listen_address: "127.0.0.1:18191".parse().unwrap(),
+            token: Some("secret".to_string()),
+        },
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config.clone()).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 客户端完成握手
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("grpc_client".to_string(), client.local_addr()?, "test".to_string());
+    let hs = Message::new(MessageType::HandshakeRequest, serde_json::to_value(&info)?);
+    client.send_to(&serde_json::to_vec(&hs)?, config.listen_address).await?;
+    assert!(receive_type(&client, MessageType::HandshakeResponse).await?.is_some(), "握手未在超时内收到响应");
+
+    let channel = Channel::from_shared(format!("http://{}", config.grpc.listen_address))?.connect().await?;
+    let mut control = ControlPlaneClient::new(channel);
+
+    // 未携带令牌的请求被拒绝
+    let denied = control.get_stats(pb::GetStatsRequest {}).await.unwrap_err();
+    assert_eq!(denied.code(), Code::Unauthenticated);
+
+    let stats = control.get_stats(authorized(pb::GetStatsRequest {})).await?.into_inner();
+    assert_eq!(stats.authenticated_peers, 1);
+    assert_eq!(stats.routing_mode, "distance_vector");
+
+    let peers = control.list_peers(authorized(pb::ListPeersRequest {})).await?.into_inner().peers;
+    assert!(peers.iter().any(|p| p.id == info.id.to_string() && p.name.as_deref() == Some("grpc_client")));
+
+    // 踢出节点：客户端收到断开通知，节点从列表中移除
+    let kick = pb::KickPeerRequest { peer_id: info.id.to_string(), reason: "维护".to_string() };
+    assert!(control.kick_peer(authorized(kick.clone())).await?.into_inner().kicked);
+    let disconnect = receive_type(&client, MessageType::Disconnect).await?.expect("未收到断开通知");
+    assert_eq!(disconnect.payload["reason"], "维护");
+    assert_eq!(disconnect.payload["code"], "kicked");
+
+    let peers = control.list_peers(authorized(pb::ListPeersRequest {})).await?.into_inner().peers;
+    assert!(peers.is_empty());
+    let missing = control.kick_peer(authorized(kick)).await.unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+
+    let relays = control.list_relay_sessions(authorized(pb::ListRelaySessionsRequest {})).await?.into_inner();
+    assert!(relays.sessions.is_empty());
+
+    Ok(())
+}
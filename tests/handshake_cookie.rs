@@ -0,0 +1,91 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::protocol::{HandshakeProtocol, Message, MessageType, NodeInfo};
+use p2p_handshake_server::{ClientConfig, Config, HandshakeCookieConfig, P2PClient, P2PServer};
+
+/// 接收下一条握手响应
+async fn receive_response(socket: &UdpSocket) -> Result<Message> {
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let (len, _) = timeout(Duration::from_secs(1), socket.recv_from(&mut buffer)).await??;
+        let message: Message = serde_json::from_slice(&buffer[..len])?;
+        if message.message_type == MessageType::HandshakeResponse {
+            return Ok(message);
+        }
+    }
+}
+
+/// 接收 cookie 质询，核对它不带签名、不大于请求
+async fn receive_challenge(socket: &UdpSocket, request: &Message) -> Result<String> {
+    let challenge = receive_response(socket).await?;
+    assert_eq!(challenge.reply_to, Some(request.id));
+    assert!(challenge.payload.get("signature").is_none() && challenge.payload.get("node_info").is_none());
+    assert!(serde_json::to_vec(&challenge)?.len() <= serde_json::to_vec(request)?.len());
+    Ok(HandshakeProtocol::cookie_challenge(&challenge).expect("应收到 cookie"))
+}
+
+#[tokio::test]
+async fn test_handshake_requires_cookie_under_flood() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18706".parse().unwrap(),
+        // 阈值为 0：始终要求 cookie
+        handshake_cookies: HandshakeCookieConfig { enable: true, trigger_per_sec: 0, ..HandshakeCookieConfig::default() },
+        ..Config::default()
+    };
+    let server_addr: SocketAddr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 不带 cookie 的握手只得到 cookie，服务器不为其创建节点
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("raw".to_string(), socket.local_addr()?, "test".to_string());
+    let mut request = Message::handshake_request(info)?;
+    socket.send_to(&serde_json::to_vec(&request)?, server_addr).await?;
+    let cookie = receive_challenge(&socket, &request).await?;
+    assert_eq!(metrics.handshake_cookie_challenges.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.messages_handled.load(Ordering::Relaxed), 0);
+
+    // 未握手来源的其他消息不分配任何状态，也得不到应答
+    let flooder = UdpSocket::bind("127.0.0.1:0").await?;
+    for message in [Message::ping(), Message::new(MessageType::DiscoveryRequest, serde_json::Value::Null)] {
+        flooder.send_to(&serde_json::to_vec(&message)?, server_addr).await?;
+    }
+    let mut buffer = [0u8; 1024];
+    assert!(timeout(Duration::from_millis(500), flooder.recv_from(&mut buffer)).await.is_err());
+    assert_eq!(metrics.handshake_cookie_drops.load(Ordering::Relaxed), 2);
+    assert_eq!(metrics.messages_handled.load(Ordering::Relaxed), 0);
+
+    // 别的地址带回这个 cookie 无效
+    let other = UdpSocket::bind("127.0.0.1:0").await?;
+    request.payload["cookie"] = cookie.clone().into();
+    other.send_to(&serde_json::to_vec(&request)?, server_addr).await?;
+    receive_challenge(&other, &request).await?;
+
+    // 带回 cookie 后握手成功
+    socket.send_to(&serde_json::to_vec(&request)?, server_addr).await?;
+    let accepted = HandshakeProtocol::validate_handshake_response(&receive_response(&socket).await?).map_err(anyhow::Error::msg)?;
+    assert!(accepted.success);
+    assert_eq!(metrics.handshake_cookie_passes.load(Ordering::Relaxed), 1);
+
+    // 客户端自动带回 cookie 完成握手
+    let client = P2PClient::connect(ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        ..ClientConfig::default()
+    })
+    .await?;
+    assert!(client.session_ticket().is_some());
+    assert_eq!(metrics.handshake_cookie_passes.load(Ordering::Relaxed), 2);
+    Ok(())
+}
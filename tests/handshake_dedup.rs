@@ -0,0 +1,87 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::protocol::{HandshakeResponse, Message, MessageType, NodeInfo};
+use p2p_handshake_server::{Config, P2PServer, ServerMetrics};
+
+/// 收集在 `wait` 内收到的全部消息
+async fn collect(socket: &UdpSocket, wait: Duration) -> Result<Vec<Message>> {
+    let mut buffer = vec![0u8; 65536];
+    let mut messages = Vec::new();
+    let deadline = tokio::time::Instant::now() + wait;
+    loop {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        match timeout(remaining, socket.recv_from(&mut buffer)).await {
+            Ok(Ok((len, _addr))) => messages.push(serde_json::from_slice(&buffer[..len])?),
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => return Ok(messages),
+        }
+    }
+}
+
+fn of_type(messages: &[Message], message_type: MessageType) -> Vec<&Message> {
+    messages.iter().filter(|message| message.message_type == message_type).collect()
+}
+
+fn ticket(message: &Message) -> Result<Option<String>> {
+    Ok(serde_json::from_value::<HandshakeResponse>(message.payload.clone())?.session_ticket)
+}
+
+async fn start_server(port: u16) -> Result<(SocketAddr, Arc<ServerMetrics>)> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: format!("127.0.0.1:{}", port).parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+    Ok((server_addr, metrics))
+}
+
+#[tokio::test]
+async fn test_duplicate_handshake_gets_identical_response() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (server_addr, metrics) = start_server(18688).await?;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("dup".to_string(), socket.local_addr()?, "test".to_string());
+    let request = serde_json::to_vec(&Message::handshake_request(info.clone())?)?;
+
+    // 同一个数据包投递两次，只处理一次
+    socket.send_to(&request, server_addr).await?;
+    sleep(Duration::from_millis(200)).await;
+    socket.send_to(&request, server_addr).await?;
+    let messages = collect(&socket, Duration::from_millis(500)).await?;
+    let responses = of_type(&messages, MessageType::HandshakeResponse);
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0].id, responses[1].id);
+    let first_ticket = ticket(responses[0])?;
+    assert!(first_ticket.is_some());
+    assert_eq!(ticket(responses[1])?, first_ticket);
+    assert_eq!(of_type(&messages, MessageType::DiscoveryResponse).len(), 1);
+    assert_eq!(metrics.handshake_duplicates.load(Ordering::Relaxed), 1);
+
+    // 新的握手请求重新处理并签发新票据
+    let renewed = Message::handshake_request(info)?;
+    socket.send_to(&serde_json::to_vec(&renewed)?, server_addr).await?;
+    let messages = collect(&socket, Duration::from_millis(500)).await?;
+    let responses = of_type(&messages, MessageType::HandshakeResponse);
+    assert_eq!(responses.len(), 1);
+    assert_ne!(ticket(responses[0])?, first_ticket);
+
+    // 被取代的旧请求再到达时不再应答
+    socket.send_to(&request, server_addr).await?;
+    let messages = collect(&socket, Duration::from_millis(500)).await?;
+    assert!(of_type(&messages, MessageType::HandshakeResponse).is_empty());
+    assert_eq!(metrics.handshake_duplicates.load(Ordering::Relaxed), 2);
+    Ok(())
+}
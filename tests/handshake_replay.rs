@@ -0,0 +1,85 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{HandshakeProtocol, Message, MessageType, NodeInfo};
+use p2p_handshake_server::{Config, NodeIdentity, P2PServer};
+
+/// 接收下一条握手响应或错误
+async fn receive_reply(socket: &UdpSocket) -> Result<Message> {
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let (len, _) = timeout(Duration::from_secs(1), socket.recv_from(&mut buffer)).await??;
+        let message: Message = serde_json::from_slice(&buffer[..len])?;
+        if matches!(message.message_type, MessageType::HandshakeResponse | MessageType::Error) {
+            return Ok(message);
+        }
+    }
+}
+
+/// 发送请求并取回服务器发给该地址的 cookie
+async fn fetch_cookie(socket: &UdpSocket, server_addr: SocketAddr, request: &Message) -> Result<String> {
+    socket.send_to(&serde_json::to_vec(request)?, server_addr).await?;
+    Ok(HandshakeProtocol::cookie_challenge(&receive_reply(socket).await?).expect("应收到 cookie"))
+}
+
+/// 确认一段时间内收不到任何应答
+async fn assert_silent(socket: &UdpSocket) {
+    let mut buffer = [0u8; 2048];
+    assert!(timeout(Duration::from_millis(300), socket.recv_from(&mut buffer)).await.is_err());
+}
+
+#[tokio::test]
+async fn test_captured_keyed_handshake_cannot_be_replayed() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18720".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr: SocketAddr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 声明公钥的握手即使不在洪泛中也要先取 cookie，证明覆盖带回的 cookie
+    let identity = NodeIdentity::generate(Uuid::new_v4());
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("alice".to_string(), alice.local_addr()?, "test".to_string());
+    let mut request = identity.handshake_request(info)?;
+    request.payload["cookie"] = fetch_cookie(&alice, server_addr, &request).await?.into();
+    identity.sign_handshake_request(&mut request);
+    alice.send_to(&serde_json::to_vec(&request)?, server_addr).await?;
+    let accepted = HandshakeProtocol::validate_handshake_response(&receive_reply(&alice).await?).map_err(anyhow::Error::msg)?;
+    assert!(accepted.success);
+
+    // 原样从别的地址重放：cookie 绑定了 alice 的地址，只换来一次质询
+    let mallory = UdpSocket::bind("127.0.0.1:0").await?;
+    let cookie = fetch_cookie(&mallory, server_addr, &request).await?;
+
+    // 换上自己的 cookie 而不重签：请求ID已被 alice 占用，直接丢弃
+    let mut replayed = request.clone();
+    replayed.payload["cookie"] = cookie.into();
+    mallory.send_to(&serde_json::to_vec(&replayed)?, server_addr).await?;
+    assert_silent(&mallory).await;
+    assert_eq!(metrics.handshake_replays_rejected.load(Ordering::Relaxed), 1);
+
+    // 换个新的请求ID：证明覆盖请求ID与 cookie，身份校验失败
+    replayed.id = Uuid::new_v4();
+    mallory.send_to(&serde_json::to_vec(&replayed)?, server_addr).await?;
+    let rejected = receive_reply(&mallory).await?;
+    assert_eq!(rejected.message_type, MessageType::Error);
+
+    // alice 的会话未被接管，同一地址的重传仍得到成功应答
+    assert_eq!(metrics.handshake_replays_rejected.load(Ordering::Relaxed), 1);
+    alice.send_to(&serde_json::to_vec(&request)?, server_addr).await?;
+    let resent = HandshakeProtocol::validate_handshake_response(&receive_reply(&alice).await?).map_err(anyhow::Error::msg)?;
+    assert!(resent.success);
+    Ok(())
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+
+use p2p_handshake_server::{Config, KeepaliveConfig, P2PServer};
+use p2p_handshake_server::protocol::{Message, MessageType};
+use common::{handshake, receive_type, receive_type_within, send_message};
+
+#[tokio::test]
+async fn test_keepalive_probe_recommends_interval() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18240".parse().unwrap(),
+        keepalive: KeepaliveConfig {
+            min_interval_secs: 1,
+            initial_probe_secs: 1,
+            max_probe_secs: 2,
+            probe_grace_secs: 1,
+            ..KeepaliveConfig::default()
+        },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let (_, response) = handshake(&client, server_addr, "client").await?;
+    assert_eq!(response.node_info.metadata.get("keepalive_interval_secs").map(|s| s.as_str()), Some("25"));
+
+    // 服务器在客户端空闲 1 秒、2 秒后各发一次探测包，客户端逐一回显
+    send_message(&client, &Message::keepalive_probe_request(), server_addr).await?;
+    for expected in [1, 2] {
+        let probe = receive_type_within(&client, MessageType::KeepaliveProbe, Duration::from_secs(5)).await?
+            .expect("未收到探测包");
+        assert_eq!(probe.payload["delay_secs"], expected);
+        send_message(&client, &Message::keepalive_probe(expected), server_addr).await?;
+    }
+
+    let result = receive_type(&client, MessageType::KeepaliveProbe).await?
+        .expect("未收到探测结果");
+    assert_eq!(result.payload["recommended_keepalive_secs"], 1);
+
+    // 之后同一公网IP的节点握手时直接拿到探测结果
+    let other = UdpSocket::bind("127.0.0.1:0").await?;
+    let (_, response) = handshake(&other, server_addr, "other").await?;
+    assert_eq!(response.node_info.metadata.get("keepalive_interval_secs").map(|s| s.as_str()), Some("1"));
+
+    Ok(())
+}
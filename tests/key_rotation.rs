@@ -0,0 +1,55 @@
+use anyhow::Result;
+use tokio::time::{sleep, timeout, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer};
+
+#[tokio::test]
+async fn test_node_key_rotation_is_validated_and_propagated() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18560".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client_config = |key_file: Option<std::path::PathBuf>| ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        identity_key_file: key_file,
+        ..ClientConfig::default()
+    };
+    let key_file = std::env::temp_dir().join(format!("p2p-node-{}.key", Uuid::new_v4()));
+    let old_key_file = key_file.with_extension("old");
+
+    let mut keyed = P2PClient::connect(client_config(Some(key_file.clone()))).await?;
+    let mut observer = P2PClient::connect(client_config(None)).await?;
+    let node_id = keyed.node_id();
+    let old_fingerprint = keyed.node_fingerprint().expect("配置了密钥文件");
+    std::fs::copy(&key_file, &old_key_file)?;
+
+    // 轮换后服务器把新公钥转发给其他节点
+    let new_fingerprint = keyed.rotate_key().await?;
+    assert_ne!(new_fingerprint, old_fingerprint);
+    let rotation = timeout(Duration::from_secs(2), observer.recv_key_rotation()).await?.expect("应收到轮换通知");
+    assert_eq!(rotation.node_id, node_id);
+
+    // 旧密钥不能再以该节点ID握手，新密钥文件保持节点ID不变
+    let error = P2PClient::connect(client_config(Some(old_key_file.clone()))).await.err().expect("旧密钥不应握手成功");
+    assert!(error.to_string().contains("已被轮换替换"), "{}", error);
+    let reconnected = P2PClient::connect(client_config(Some(key_file.clone()))).await?;
+    assert_eq!(reconnected.node_id(), node_id);
+    assert_eq!(reconnected.node_fingerprint(), Some(new_fingerprint));
+
+    let _ = std::fs::remove_file(&key_file);
+    let _ = std::fs::remove_file(&old_key_file);
+    Ok(())
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use anyhow::Result;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{Config, P2PServer};
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo, P2PPath};
+use common::{handshake_with, receive_type, send_message};
+
+#[tokio::test]
+async fn test_same_public_ip_gets_private_addr_first() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18210".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 两个客户端在服务器看来来自同一IP（127.0.0.1）
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let alice_private: SocketAddr = "192.168.1.10:7000".parse().unwrap();
+    let bob_private: SocketAddr = "192.168.1.11:7000".parse().unwrap();
+    // 上报与观测地址不同的内网监听地址
+    let alice_info = NodeInfo::new("alice".to_string(), alice_private, "test".to_string());
+    let bob_info = NodeInfo::new("bob".to_string(), bob_private, "test".to_string());
+    handshake_with(&alice, server_addr, &alice_info).await?;
+    handshake_with(&bob, server_addr, &bob_info).await?;
+
+    send_message(&alice, &Message::initiate_p2p(bob_info.id), server_addr).await?;
+
+    let to_alice = receive_type(&alice, MessageType::P2PConnect).await?
+        .expect("请求方未收到直连协调");
+    assert_eq!(to_alice.payload["same_public_ip"], true);
+    assert_eq!(to_alice.payload["peer_private_addr"], bob_private.to_string());
+    assert_eq!(to_alice.payload["candidates"][0], bob_private.to_string());
+    assert_eq!(to_alice.payload["candidates"][1], bob.local_addr()?.to_string());
+
+    let to_bob = receive_type(&bob, MessageType::P2PConnect).await?
+        .expect("目标方未收到直连协调");
+    assert_eq!(to_bob.payload["peer_id"], alice_info.id.to_string());
+    assert_eq!(to_bob.payload["peer_private_addr"], alice_private.to_string());
+
+    send_message(&alice, &Message::p2p_connect_result(bob_info.id, P2PPath::Private), server_addr).await?;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(metrics.p2p_same_ip_coordinations.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.p2p_private_path.load(Ordering::Relaxed), 1);
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use tokio::time::{timeout, Duration, sleep};
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer, SessionConfig, SessionState};
+
+#[tokio::test]
+async fn test_pong_rtts_feed_matrix_and_latency_discovery() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18500".parse().unwrap(),
+        heartbeat_interval: 1,
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let latency = server.latency();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client_config = ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        session: SessionConfig { punch_interval_ms: 50, keepalive_interval_ms: 100, ..SessionConfig::default() },
+        ..ClientConfig::default()
+    };
+    let alice = P2PClient::connect(client_config.clone()).await?;
+    let mut bob = P2PClient::connect(client_config.clone()).await?;
+    let carol = P2PClient::connect(client_config).await?;
+
+    let session = alice.open_session(bob.node_id()).await?;
+    let _accepted = timeout(Duration::from_secs(3), bob.accept()).await?.expect("未收到会话");
+
+    // 保活 Ping 测得时延，随下一次心跳的 Pong 上报
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while latency.rtt(&alice.node_id(), &bob.node_id()).is_none() && tokio::time::Instant::now() < deadline {
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert!(matches!(session.state(), SessionState::Direct(_)));
+    assert!(session.rtt().is_some());
+    assert!(latency.rtt(&alice.node_id(), &bob.node_id()).is_some());
+    // 先到达的可能是任一方的上报
+    let pair = [alice.node_id(), bob.node_id()];
+    assert!(latency.entries().iter().any(|e| pair.contains(&e.from) && pair.contains(&e.to)));
+
+    // 测过时延的节点排在前面
+    let nearest = alice.discover_nearest(10).await?;
+    let ids: Vec<_> = nearest.iter().map(|p| p.id).collect();
+    assert_eq!(ids.first(), Some(&bob.node_id()));
+    assert!(ids.contains(&carol.node_id()));
+    assert!(!ids.contains(&alice.node_id()));
+    let nearest = alice.discover_nearest(1).await?;
+    assert_eq!(nearest.iter().map(|p| p.id).collect::<Vec<_>>(), vec![bob.node_id()]);
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{sleep, Duration, Instant};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{Message, NodeInfo};
+use p2p_handshake_server::{AdminConfig, Config, P2PServer, PinnedPeer, PinningConfig, RoutingConfig, RoutingMode};
+
+/// 链路状态模式的服务器配置，`pinned` 为主动握手的固定节点
+fn link_state_config(listen: &str, pinned: Vec<PinnedPeer>) -> Config {
+    Config {
+        network_id: "test".to_string(),
+        listen_address: listen.parse().unwrap(),
+        routing: RoutingConfig { mode: RoutingMode::LinkState, lsa_refresh_interval: 1, ..RoutingConfig::default() },
+        pinning: PinningConfig { peers: pinned, reconnect_interval_secs: 1 },
+        ..Config::default()
+    }
+}
+
+/// 通过管理接口取回路由表，返回（目标，下一跳，距离）
+async fn admin_routes(admin: SocketAddr) -> Result<Vec<(Uuid, Uuid, u32)>> {
+    let mut stream = TcpStream::connect(admin).await?;
+    stream
+        .write_all(b"GET /api/routes HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (_, body) = response.split_once("\r\n\r\n").unwrap_or_default();
+    let body: serde_json::Value = serde_json::from_str(body)?;
+    let routes = body["routes"]
+        .as_array()
+        .map(|routes| {
+            routes
+                .iter()
+                .filter_map(|route| {
+                    Some((
+                        route["destination"].as_str()?.parse().ok()?,
+                        route["next_hop"].as_str()?.parse().ok()?,
+                        route["distance"].as_u64()? as u32,
+                    ))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(routes)
+}
+
+#[tokio::test]
+async fn test_link_state_route_learned_through_middle_server() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    // 拓扑 A - B - C - D：A 与 C 各自固定连接 B，D 是只连接 C 的普通节点
+    let mut b = P2PServer::new(link_state_config("127.0.0.1:18741", Vec::new())).await?;
+    let (b_id, b_addr) = (b.node_id(), "127.0.0.1:18741".parse().unwrap());
+    let pin_b = vec![PinnedPeer { node_id: b_id, addr: b_addr }];
+
+    let mut c = P2PServer::new(link_state_config("127.0.0.1:18742", pin_b.clone())).await?;
+    let (c_id, c_addr): (Uuid, SocketAddr) = (c.node_id(), "127.0.0.1:18742".parse().unwrap());
+
+    // A 与 C 不直接相连，须预先登记 C 的身份公钥才能校验经 B 转发的通告
+    let admin: SocketAddr = "127.0.0.1:18743".parse().unwrap();
+    let mut a_config = link_state_config("127.0.0.1:18740", pin_b);
+    a_config.admin = AdminConfig { enable: true, listen_address: admin, ..AdminConfig::default() };
+    a_config.routing.origin_keys.insert(c_id, c.identity().public_key_hex());
+    let mut a = P2PServer::new(a_config).await?;
+
+    tokio::spawn(async move {
+        let _ = b.run().await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    tokio::spawn(async move {
+        let _ = a.run().await;
+    });
+    tokio::spawn(async move {
+        let _ = c.run().await;
+    });
+    sleep(Duration::from_millis(100)).await;
+    let d = UdpSocket::bind("127.0.0.1:0").await?;
+    let d_info = NodeInfo::new("d".to_string(), d.local_addr()?, "test".to_string());
+    d.send_to(&serde_json::to_vec(&Message::handshake_request(d_info.clone())?)?, c_addr).await?;
+
+    // 到 D 的链路只出现在 C 的通告里：通告经 B 转发到 A，A 用登记的公钥校验 C 的签名后
+    // 算出经 B 到 C 的两跳路由与到 D 的三跳路由
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let routes = admin_routes(admin).await.unwrap_or_default();
+        if routes.contains(&(c_id, b_id, 2)) && routes.contains(&(d_info.id, b_id, 3)) {
+            assert!(routes.contains(&(b_id, b_id, 1)));
+            break;
+        }
+        assert!(Instant::now() < deadline, "A 未学到经 B 到 C、D 的路由: {:?}", routes);
+        sleep(Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
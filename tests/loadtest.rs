@@ -0,0 +1,63 @@
+use anyhow::Result;
+use tokio::time::{sleep, Duration};
+
+use p2p_handshake_server::loadtest::{self, LoadtestConfig};
+use p2p_handshake_server::{Config, P2PServer};
+
+#[tokio::test]
+async fn test_loadtest_reports_handshakes_messages_and_churn() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18630".parse().unwrap(),
+        ..Config::default()
+    };
+    let target = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let report = loadtest::run(LoadtestConfig {
+        target,
+        clients: 5,
+        handshake_rate: 0.0,
+        message_rate: 10.0,
+        payload_size: 64,
+        churn_rate: 5.0,
+        loss_rate: 0.0,
+        duration_secs: 2,
+        timeout_ms: 1000,
+        network_id: "test".to_string(),
+    })
+    .await?;
+
+    assert!(report.handshakes.succeeded >= 5);
+    assert_eq!(report.handshakes.failed, 0);
+    assert!(report.messages.succeeded > 20, "消息确认过少: {:?}", report.messages);
+    assert_eq!(report.messages.failed, 0);
+    assert_eq!(report.messages.attempted, report.messages.succeeded);
+    assert!(report.messages.latency.p50_ms <= report.messages.latency.p99_ms);
+    // 流失的客户端以新身份重新握手
+    assert!(report.disconnects > 0);
+    assert!(report.handshakes.succeeded > report.clients as u64);
+    assert!(report.handshakes.succeeded <= report.clients as u64 + report.disconnects);
+
+    // 网络ID不匹配时握手全部失败
+    let rejected = loadtest::run(LoadtestConfig {
+        target,
+        clients: 2,
+        handshake_rate: 10.0,
+        duration_secs: 1,
+        timeout_ms: 300,
+        network_id: "other".to_string(),
+        ..LoadtestConfig::default()
+    })
+    .await?;
+    assert!(rejected.handshakes.attempted > 0);
+    assert_eq!(rejected.handshakes.error_rate(), 1.0);
+    assert_eq!(rejected.messages.attempted, 0);
+    Ok(())
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{AdminConfig, Config, MaintenanceNotice, P2PServer};
+use p2p_handshake_server::protocol::MessageType;
+use common::{handshake, receive_type};
+
+/// 向管理接口发送请求，返回响应正文
+async fn admin_request(admin: SocketAddr, method: &str, path: &str, body: &str) -> Result<serde_json::Value> {
+    let mut stream = TcpStream::connect(admin).await?;
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (_, body) = response.split_once("\r\n\r\n").expect("响应缺少正文");
+    Ok(serde_json::from_str(body)?)
+}
+
+#[tokio::test]
+async fn test_maintenance_notice_and_scheduled_drain() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let admin_addr: SocketAddr = "127.0.0.1:18271".parse().unwrap();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18270".parse().unwrap(),
+        admin: AdminConfig { enable: true, listen_address: admin_addr, ..AdminConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let online = UdpSocket::bind("127.0.0.1:0").await?;
+    assert!(handshake(&online, server_addr, "online").await?.1.success);
+
+    // 发布公告：在线节点立即收到
+    let request = r#"{"start_in_secs": 1, "duration_secs": 60, "alternative_server": "127.0.0.1:19999", "reason": "升级", "drain": true}"#;
+    let state = admin_request(admin_addr, "POST", "/api/maintenance", request).await?;
+    assert_eq!(state["notified"], 1);
+    assert_eq!(state["draining"], false);
+    let notice = receive_type(&online, MessageType::MaintenanceNotice).await?
+        .expect("在线节点未收到维护公告");
+    let notice: MaintenanceNotice = serde_json::from_value(notice.payload)?;
+    assert_eq!(notice.duration_secs, 60);
+    assert!(notice.drain);
+
+    // 维护开始前加入的节点在握手后收到公告
+    let late = UdpSocket::bind("127.0.0.1:0").await?;
+    assert!(handshake(&late, server_addr, "late").await?.1.success);
+    assert!(receive_type(&late, MessageType::MaintenanceNotice).await?.is_some());
+
+    // 到开始时间后排空：现有节点收到改连提示，新握手被拒绝
+    let reconnect = receive_type(&online, MessageType::Reconnect).await?
+        .expect("排空时未收到重连提示");
+    assert_eq!(reconnect.payload["server_addr"], "127.0.0.1:19999");
+    let rejected_socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let (_, rejected) = handshake(&rejected_socket, server_addr, "rejected").await?;
+    assert!(!rejected.success);
+    assert!(rejected.retry_after_secs.is_some_and(|secs| secs <= 60));
+
+    // 取消维护后恢复接受握手
+    let state = admin_request(admin_addr, "DELETE", "/api/maintenance", "").await?;
+    assert!(state["notice"].is_null());
+    let resumed = UdpSocket::bind("127.0.0.1:0").await?;
+    assert!(handshake(&resumed, server_addr, "resumed").await?.1.success);
+
+    Ok(())
+}
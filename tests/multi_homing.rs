@@ -0,0 +1,88 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{Config, NetworkConfig, P2PServer};
+use p2p_handshake_server::protocol::{ListNodesResponse, Message, MessageType, NodeInfo, PeerInfo};
+use common::{handshake_with, receive_type, send_message};
+
+/// 首个地址作为监听地址，其余作为附加地址通告
+fn multi_homed(name: &str, addrs: &[&str]) -> Result<NodeInfo> {
+    let mut info = NodeInfo::new(name.to_string(), addrs[0].parse()?, "test".to_string());
+    info.addresses = addrs[1..].iter().map(|a| a.parse()).collect::<Result<_, _>>()?;
+    Ok(info)
+}
+
+fn strings(addrs: &[SocketAddr]) -> Vec<String> {
+    addrs.iter().map(|a| a.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_advertised_addresses_carried_through_discovery_and_p2p() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let server_extra: SocketAddr = "10.0.0.1:18390".parse().unwrap();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18390".parse().unwrap(),
+        network: NetworkConfig { advertise_addresses: vec![server_extra], ..NetworkConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 服务器在握手响应中通告自己的其他地址
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let alice_info = multi_homed("alice", &["192.168.1.10:7000", "10.8.0.10:7000"])?;
+    let response = handshake_with(&alice, server_addr, &alice_info).await?;
+    assert_eq!(response.node_info.advertised_addrs(), vec![server_addr, server_extra]);
+
+    // 重复的地址只通告一次
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob_info = multi_homed("bob", &["192.168.1.11:7000", "10.8.0.11:7000", "192.168.1.11:7000"])?;
+    handshake_with(&bob, server_addr, &bob_info).await?;
+    let bob_addrs = bob_info.advertised_addrs();
+    assert_eq!(bob_addrs.len(), 2);
+
+    // 节点发现与节点列表都携带全部通告地址（先前广播的列表可能尚未包含 bob）
+    send_message(&alice, &Message::discovery_request(), server_addr).await?;
+    let bob_entry = loop {
+        let discovery = receive_type(&alice, MessageType::DiscoveryResponse).await?
+            .expect("发现列表中缺少 bob");
+        let peers: Vec<PeerInfo> = serde_json::from_value(discovery.payload)?;
+        if let Some(entry) = peers.into_iter().find(|p| p.id == bob_info.id) {
+            break entry;
+        }
+    };
+    assert_eq!(bob_entry.addresses, bob_addrs);
+
+    send_message(&alice, &Message::list_nodes_request(), server_addr).await?;
+    let list = receive_type(&alice, MessageType::ListNodesResponse).await?
+        .expect("未收到节点列表");
+    let list: ListNodesResponse = serde_json::from_value(list.payload)?;
+    let bob_node = list.nodes.iter().find(|n| n.id == bob_info.id).expect("节点列表中缺少 bob");
+    assert_eq!(bob_node.listen_addr, bob.local_addr()?);
+    assert_eq!(bob_node.addresses, bob_addrs);
+
+    // 直连协调向双方下发对方的全部地址，同一公网IP时候选列表依次为各内网地址与公网地址
+    send_message(&alice, &Message::initiate_p2p(bob_info.id), server_addr).await?;
+    let to_alice = receive_type(&alice, MessageType::P2PConnect).await?
+        .expect("请求方未收到直连协调");
+    assert_eq!(to_alice.payload["peer_addresses"], serde_json::json!(strings(&bob_addrs)));
+    let mut candidates = strings(&bob_addrs);
+    candidates.push(bob.local_addr()?.to_string());
+    assert_eq!(to_alice.payload["candidates"], serde_json::json!(candidates));
+
+    let to_bob = receive_type(&bob, MessageType::P2PConnect).await?
+        .expect("目标方未收到直连协调");
+    assert_eq!(to_bob.payload["peer_addresses"], serde_json::json!(strings(&alice_info.advertised_addrs())));
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use tokio::time::{timeout, Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer};
+use p2p_handshake_server::router::RoutedMessage;
+
+fn client_config(server_addr: SocketAddr) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        ..ClientConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_multicast_reaches_every_member() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18698".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = P2PClient::connect(client_config(server_addr)).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr)).await?;
+    let mut carol = P2PClient::connect(client_config(server_addr)).await?;
+
+    alice.multicast(vec![bob.node_id(), carol.node_id()], serde_json::json!({ "n": 1 })).await?;
+
+    // 每个目标各收到一份发给自己的单播副本，路由 ID 相同
+    let mut route_ids = Vec::new();
+    for member in [&mut bob, &mut carol] {
+        let delivered = timeout(Duration::from_secs(3), member.recv_data()).await?.expect("目标节点未收到多播消息");
+        let routed = RoutedMessage::from_message(&delivered)?;
+        assert_eq!(routed.destination_node, member.node_id());
+        assert_eq!(routed.source_node, alice.node_id());
+        assert_eq!(routed.original_message.payload["n"], 1);
+        route_ids.push(routed.route_id);
+    }
+    assert_eq!(route_ids[0], route_ids[1]);
+    Ok(())
+}
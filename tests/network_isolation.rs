@@ -0,0 +1,113 @@
+mod common;
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::identity::{self, IdentityError};
+use p2p_handshake_server::protocol::{HandshakeProtocol, HandshakeResponse, Message, MessageType, NodeInfo};
+use p2p_handshake_server::router::RoutedMessage;
+use p2p_handshake_server::{Config, NodeIdentity, P2PServer, RouteSignaturePolicy, RoutingConfig, ServerMetrics};
+use common::{receive_any, send_message};
+
+async fn start_server(network_id: &str, listen: &str) -> Result<(SocketAddr, std::sync::Arc<ServerMetrics>)> {
+    let config = Config {
+        network_id: network_id.to_string(),
+        listen_address: listen.parse().unwrap(),
+        routing: RoutingConfig { signatures: RouteSignaturePolicy::Require, ..RoutingConfig::default() },
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config.clone()).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    Ok((config.listen_address, metrics))
+}
+
+/// 先取 cookie，再把带回 cookie 的请求交给 `finish` 处理后发出，返回服务器的最终应答
+async fn keyed_handshake(
+    socket: &UdpSocket,
+    server: SocketAddr,
+    mut request: Message,
+    finish: impl FnOnce(&mut Message),
+) -> Result<Message> {
+    send_message(socket, &request, server).await?;
+    let challenge = receive_any(socket, &[MessageType::HandshakeResponse]).await?.expect("应收到 cookie 质询");
+    request.payload["cookie"] = HandshakeProtocol::cookie_challenge(&challenge).expect("应收到 cookie").into();
+    finish(&mut request);
+    send_message(socket, &request, server).await?;
+    Ok(receive_any(socket, &[MessageType::HandshakeResponse, MessageType::Error]).await?.expect("握手未收到应答"))
+}
+
+#[tokio::test]
+async fn test_credentials_minted_in_one_network_fail_in_another() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (alpha, _) = start_server("alpha", "127.0.0.1:18722").await?;
+    let (beta, beta_metrics) = start_server("beta", "127.0.0.1:18723").await?;
+    sleep(Duration::from_millis(200)).await;
+
+    let node = NodeIdentity::generate(Uuid::new_v4());
+    let info = |socket: &UdpSocket, network_id: &str| -> Result<NodeInfo> {
+        Ok(NodeInfo::new("node".to_string(), socket.local_addr()?, network_id.to_string()))
+    };
+
+    // 在 alpha 握手：服务器签名与会话票据都属于 alpha
+    let on_alpha = UdpSocket::bind("127.0.0.1:0").await?;
+    let request = node.handshake_request(info(&on_alpha, "alpha")?)?;
+    let request_id = request.id;
+    let response = keyed_handshake(&on_alpha, alpha, request, |request| node.sign_handshake_request(request)).await?;
+    assert!(identity::verify_handshake_response(&response, request_id, "alpha").is_ok());
+    assert_eq!(identity::verify_handshake_response(&response, request_id, "beta"), Err(IdentityError::BadSignature));
+    let alpha_ticket = serde_json::from_value::<HandshakeResponse>(response.payload)?.session_ticket.expect("缺少会话票据");
+
+    // 为 alpha 计算的握手证明改写成 beta 后被 beta 拒绝
+    let forged = UdpSocket::bind("127.0.0.1:0").await?;
+    let request = node.handshake_request(info(&forged, "alpha")?)?;
+    let rejected = keyed_handshake(&forged, beta, request, |request| {
+        node.sign_handshake_request(request);
+        request.payload["network_id"] = "beta".into();
+    })
+    .await?;
+    assert_eq!(rejected.message_type, MessageType::Error);
+    assert!(rejected.payload.to_string().contains("节点身份校验失败"), "{}", rejected.payload);
+
+    // 同一节点正常加入 beta，另有一个观察者
+    let on_beta = UdpSocket::bind("127.0.0.1:0").await?;
+    let request = node.handshake_request(info(&on_beta, "beta")?)?;
+    let accepted = keyed_handshake(&on_beta, beta, request, |request| node.sign_handshake_request(request)).await?;
+    assert!(HandshakeProtocol::validate_handshake_response(&accepted).map_err(anyhow::Error::msg)?.success);
+    let observer = UdpSocket::bind("127.0.0.1:0").await?;
+    let observer_info = info(&observer, "beta")?;
+    send_message(&observer, &Message::handshake_request(observer_info.clone())?, beta).await?;
+    assert!(receive_any(&observer, &[MessageType::HandshakeResponse]).await?.is_some());
+
+    // alpha 签发的会话票据不能在 beta 迁移会话
+    let roamed = UdpSocket::bind("127.0.0.1:0").await?;
+    send_message(&roamed, &Message::migrate_address(node.node_id(), alpha_ticket)?, beta).await?;
+    let denied = receive_any(&roamed, &[MessageType::MigrateAddress, MessageType::Error]).await?.expect("迁移请求未收到应答");
+    assert_eq!(denied.message_type, MessageType::Error);
+
+    // 在 alpha 签名的路由消息被 beta 丢弃，为 beta 签名的同一消息照常送达
+    let route = |network_id: &str| -> Result<Message> {
+        let mut routed = RoutedMessage::new(Message::data(serde_json::json!({ "network": network_id })), node.node_id(), observer_info.id, 8);
+        node.sign_routed(&mut routed, network_id);
+        Ok(routed.to_message()?)
+    };
+    send_message(&on_beta, &route("alpha")?, beta).await?;
+    assert!(receive_any(&observer, &[MessageType::Data]).await?.is_none());
+    assert_eq!(beta_metrics.route_signature_drops.load(Ordering::Relaxed), 1);
+    send_message(&on_beta, &route("beta")?, beta).await?;
+    let delivered = receive_any(&observer, &[MessageType::Data]).await?.expect("为 beta 签名的消息应送达");
+    assert_eq!(RoutedMessage::from_message(&delivered)?.original_message.payload["network"], "beta");
+
+    // 在 alpha 签名的轮换声明被 beta 拒绝
+    let (_, rotation) = node.rotate("alpha");
+    send_message(&on_beta, &Message::key_rotation(&rotation)?, beta).await?;
+    let refused = receive_any(&on_beta, &[MessageType::KeyRotation, MessageType::Error]).await?.expect("轮换未收到应答");
+    assert_eq!(refused.message_type, MessageType::Error);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo, PeerInfo};
+use p2p_handshake_server::{Config, LimitAction, NodeInfoLimitsConfig, P2PServer, ServerMetrics};
+use common::receive_any;
+
+async fn start_server(port: u16, action: LimitAction) -> Result<(SocketAddr, Arc<ServerMetrics>)> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: format!("127.0.0.1:{}", port).parse().unwrap(),
+        node_info_limits: NodeInfoLimitsConfig { action, ..NodeInfoLimitsConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+    Ok((server_addr, metrics))
+}
+
+/// 名称、能力与元数据都超出默认限制（仍在单个数据包以内）的节点信息
+fn oversized(socket: &UdpSocket) -> Result<NodeInfo> {
+    let mut info = NodeInfo::new("n".repeat(300), socket.local_addr()?, "test".to_string());
+    for i in 0..60 {
+        info.add_capability(&format!("cap_{}", i));
+        info.metadata.insert(format!("key_{:03}", i), "v".to_string());
+    }
+    info.add_capability(&"c".repeat(500));
+    info.metadata.insert("large".to_string(), "v".repeat(3000));
+    Ok(info)
+}
+
+#[tokio::test]
+async fn test_oversized_node_info_is_truncated() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (server_addr, metrics) = start_server(18686, LimitAction::Truncate).await?;
+
+    let big = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = oversized(&big)?;
+    big.send_to(&serde_json::to_vec(&Message::handshake_request(info.clone())?)?, server_addr).await?;
+    let reply = receive_any(&big, &[MessageType::HandshakeResponse, MessageType::Error]).await?.expect("未收到握手应答");
+    assert_eq!(reply.message_type, MessageType::HandshakeResponse);
+    assert_eq!(metrics.node_info_truncated.load(Ordering::Relaxed), 1);
+
+    // 其他节点看到的能力列表已截断
+    let other = UdpSocket::bind("127.0.0.1:0").await?;
+    let other_info = NodeInfo::new("other".to_string(), other.local_addr()?, "test".to_string());
+    other.send_to(&serde_json::to_vec(&Message::handshake_request(other_info)?)?, server_addr).await?;
+    let discovery = receive_any(&other, &[MessageType::DiscoveryResponse]).await?.expect("未收到节点列表");
+    let peers: Vec<PeerInfo> = serde_json::from_value(discovery.payload)?;
+    let listed = peers.iter().find(|peer| peer.id == info.id).expect("节点列表中没有该节点");
+    assert_eq!(listed.capabilities.len(), NodeInfoLimitsConfig::default().max_capabilities);
+    assert!(listed.capabilities.iter().all(|capability| capability.len() <= 64));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_oversized_node_info_is_rejected() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (server_addr, metrics) = start_server(18687, LimitAction::Reject).await?;
+
+    let big = UdpSocket::bind("127.0.0.1:0").await?;
+    big.send_to(&serde_json::to_vec(&Message::handshake_request(oversized(&big)?)?)?, server_addr).await?;
+    let reply = receive_any(&big, &[MessageType::HandshakeResponse, MessageType::Error]).await?.expect("未收到握手应答");
+    assert_eq!(reply.message_type, MessageType::Error);
+    assert!(reply.payload.to_string().contains("节点信息超出限制"), "{}", reply.payload);
+    assert_eq!(metrics.node_info_rejected.load(Ordering::Relaxed), 1);
+
+    // 未超限的节点照常握手
+    let small = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("small".to_string(), small.local_addr()?, "test".to_string());
+    small.send_to(&serde_json::to_vec(&Message::handshake_request(info)?)?, server_addr).await?;
+    let reply = receive_any(&small, &[MessageType::HandshakeResponse, MessageType::Error]).await?.expect("未收到握手应答");
+    assert_eq!(reply.message_type, MessageType::HandshakeResponse);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use uuid::Uuid;
+
+use p2p_handshake_server::{Config, OfflineStoreConfig, P2PServer};
+use p2p_handshake_server::protocol::{Message, MessageType};
+use p2p_handshake_server::router::RoutedMessage;
+use common::{handshake_as, receive_type_within, send_message};
+
+/// 接收下一条路由数据消息的负载序号
+async fn next_routed(socket: &UdpSocket, wait: Duration) -> Result<Option<i64>> {
+    let Some(message) = receive_type_within(socket, MessageType::Data, wait).await? else { return Ok(None) };
+    Ok(RoutedMessage::from_message(&message)?.original_message.payload["n"].as_i64())
+}
+
+#[tokio::test]
+async fn test_messages_for_offline_peer_delivered_on_handshake() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18460".parse().unwrap(),
+        offline_store: OfflineStoreConfig { enable: true, max_per_peer: 2, ..OfflineStoreConfig::default() },
+        ..Config::default()
+    };
+    let server = config.listen_address;
+    let mut p2p_server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = p2p_server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let (sender_id, receiver_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let sender = UdpSocket::bind("127.0.0.1:0").await?;
+    let receiver = UdpSocket::bind("127.0.0.1:0").await?;
+    assert_eq!(handshake_as(&sender, server, sender_id).await?.stored_messages, Some(0));
+
+    // 目标不在线时暂存，超出上限丢弃最早的一条
+    for n in 1..=3 {
+        let routed = RoutedMessage::new(Message::data(serde_json::json!({ "n": n })), sender_id, receiver_id, 5);
+        send_message(&sender, &routed.to_message()?, server).await?;
+    }
+    sleep(Duration::from_millis(200)).await;
+
+    let response = handshake_as(&receiver, server, receiver_id).await?;
+    assert_eq!(response.stored_messages, Some(2));
+    assert_eq!(next_routed(&receiver, Duration::from_secs(3)).await?, Some(2));
+    assert_eq!(next_routed(&receiver, Duration::from_secs(3)).await?, Some(3));
+
+    // 在线时直接转发，不再暂存
+    let routed = RoutedMessage::new(Message::data(serde_json::json!({ "n": 4 })), sender_id, receiver_id, 5);
+    send_message(&sender, &routed.to_message()?, server).await?;
+    assert_eq!(next_routed(&receiver, Duration::from_secs(3)).await?, Some(4));
+
+    // 断开后再次暂存，重新握手时投递
+    send_message(&receiver, &Message::disconnect("bye".to_string()), server).await?;
+    sleep(Duration::from_millis(200)).await;
+    let routed = RoutedMessage::new(Message::data(serde_json::json!({ "n": 5 })), sender_id, receiver_id, 5);
+    send_message(&sender, &routed.to_message()?, server).await?;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(handshake_as(&receiver, server, receiver_id).await?.stored_messages, Some(1));
+    assert_eq!(next_routed(&receiver, Duration::from_secs(3)).await?, Some(5));
+    assert_eq!(next_routed(&receiver, Duration::from_millis(300)).await?, None);
+    Ok(())
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo};
+use p2p_handshake_server::{Config, P2PServer};
+use common::receive_type_within;
+
+fn oversized_data() -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(&Message::data(serde_json::json!({ "blob": "x".repeat(2000) })))?)
+}
+
+#[tokio::test]
+async fn test_oversized_datagrams_are_dropped_before_parsing() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18580".parse().unwrap(),
+        max_datagram_size: 1024,
+        ..Config::default()
+    };
+    let server_addr: SocketAddr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 未知来源的超长数据包只计数，不应答
+    let stranger = UdpSocket::bind("127.0.0.1:0").await?;
+    stranger.send_to(&oversized_data()?, server_addr).await?;
+    assert!(receive_type_within(&stranger, MessageType::Error, Duration::from_millis(300)).await?.is_none());
+    assert_eq!(metrics.packets_oversized.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.packets_malformed.load(Ordering::Relaxed), 0);
+
+    // 已握手的节点收到带上限的错误应答
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("client".to_string(), client.local_addr()?, "test".to_string());
+    client.send_to(&serde_json::to_vec(&Message::handshake_request(info)?)?, server_addr).await?;
+    assert!(receive_type_within(&client, MessageType::HandshakeResponse, Duration::from_secs(1)).await?.is_some());
+    client.send_to(&oversized_data()?, server_addr).await?;
+    let error = receive_type_within(&client, MessageType::Error, Duration::from_secs(1)).await?.expect("应收到超长通知");
+    assert_eq!(error.payload["max_datagram_size"], 1024);
+    assert_eq!(metrics.packets_oversized.load(Ordering::Relaxed), 2);
+    Ok(())
+}
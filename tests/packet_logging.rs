@@ -0,0 +1,78 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::log_capture::LogEntry;
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo};
+use p2p_handshake_server::{CapturingLogger, Config, LoggingConfig, P2PServer, PacketLogMode, RecentLogs};
+
+/// 数据消息中携带的用户数据，除 `raw` 模式外不得出现在任何日志中
+const SECRET: &str = "user-secret-7f3a";
+
+/// 以指定的数据包日志模式启动服务器，握手后发送一条带 [`SECRET`] 的数据消息，返回客户端地址
+async fn exchange(mode: PacketLogMode, listen: &str) -> Result<SocketAddr> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: listen.parse().unwrap(),
+        logging: LoggingConfig { packets: mode, ..LoggingConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("client".to_string(), client.local_addr()?, "test".to_string());
+    client.send_to(&serde_json::to_vec(&Message::handshake_request(info)?)?, server_addr).await?;
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let (len, _) = timeout(Duration::from_secs(2), client.recv_from(&mut buffer)).await??;
+        if serde_json::from_slice::<Message>(&buffer[..len])?.message_type == MessageType::HandshakeResponse {
+            break;
+        }
+    }
+    let data = Message::data(serde_json::json!({ "note": SECRET }));
+    client.send_to(&serde_json::to_vec(&data)?, server_addr).await?;
+    sleep(Duration::from_millis(100)).await;
+    Ok(client.local_addr()?)
+}
+
+/// 与指定客户端相关的日志
+fn entries_for(logs: &RecentLogs, client: SocketAddr) -> Vec<LogEntry> {
+    let client = client.to_string();
+    logs.recent(usize::MAX).into_iter().filter(|entry| entry.message.contains(&client)).collect()
+}
+
+#[tokio::test]
+async fn test_packet_contents_are_logged_only_in_raw_mode() -> Result<()> {
+    let logs = Arc::new(RecentLogs::new(10_000));
+    let logger = env_logger::Builder::new().filter_level(log::LevelFilter::Debug).is_test(true).build();
+    CapturingLogger::new(logger, logs.clone()).init()?;
+
+    // off：不记录数据包，日志中没有用户数据
+    let client = exchange(PacketLogMode::Off, "127.0.0.1:18729").await?;
+    let entries = entries_for(&logs, client);
+    assert!(!entries.is_empty());
+    assert!(entries.iter().all(|entry| !entry.message.contains("字节")), "{:?}", entries);
+    assert!(logs.recent(usize::MAX).iter().all(|entry| !entry.message.contains(SECRET)));
+
+    // redacted（默认）：只在 debug 级别记录类型、大小与发送方
+    let client = exchange(PacketLogMode::Redacted, "127.0.0.1:18730").await?;
+    let entries = entries_for(&logs, client);
+    let summary = format!("收到来自 {} 的 Data 消息", client);
+    let line = entries.iter().find(|entry| entry.message.starts_with(&summary)).expect("缺少数据包摘要日志");
+    assert_eq!(line.level, "DEBUG");
+    assert!(logs.recent(usize::MAX).iter().all(|entry| !entry.message.contains(SECRET)));
+
+    // raw：数据包内容只出现在 debug 级别
+    let client = exchange(PacketLogMode::Raw, "127.0.0.1:18731").await?;
+    let leaked: Vec<LogEntry> = entries_for(&logs, client).into_iter().filter(|entry| entry.message.contains(SECRET)).collect();
+    assert!(!leaked.is_empty(), "raw 模式应记录数据包内容");
+    assert!(leaked.iter().all(|entry| entry.level == "DEBUG"), "{:?}", leaked);
+    Ok(())
+}
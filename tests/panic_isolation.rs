@@ -0,0 +1,84 @@
+mod common;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use p2p_handshake_server::{Config, MessageHandler, P2PServer, PanicIsolationConfig};
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo};
+use common::{receive_type_within, send_message};
+
+/// 发送握手请求，返回是否在超时内收到响应
+async fn handshake_answered(socket: &UdpSocket, server: SocketAddr) -> Result<bool> {
+    let info = NodeInfo::new("client".to_string(), socket.local_addr()?, "test".to_string());
+    send_message(socket, &Message::handshake_request(info)?, server).await?;
+    Ok(receive_type_within(socket, MessageType::HandshakeResponse, Duration::from_secs(1)).await?.is_some())
+}
+
+/// 负载含 `boom` 时 panic，否则原样回复
+struct Fragile;
+
+impl MessageHandler for Fragile {
+    fn handle_data<'a>(&'a self, _from: Uuid, message: &'a Message) -> BoxFuture<'a, Result<Option<Message>>> {
+        Box::pin(async move {
+            if message.payload.get("boom").is_some() {
+                panic!("处理器无法处理该负载");
+            }
+            Ok(Some(Message::data(message.payload.clone())))
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_panicking_source_is_banned_and_server_survives() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18530".parse().unwrap(),
+        panic_isolation: PanicIsolationConfig { ban_after: 3, window_secs: 60, ban_secs: 600 },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    server.set_message_handler(Arc::new(Fragile));
+    let metrics = server.metrics();
+    let quarantine = server.quarantine();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 前两次 panic 只计数，服务器继续处理该来源的消息
+    let attacker = UdpSocket::bind("127.0.0.1:0").await?;
+    assert!(handshake_answered(&attacker, server_addr).await?);
+    let boom = Message::data(serde_json::json!({"boom": true}));
+    for _ in 0..2 {
+        send_message(&attacker, &boom, server_addr).await?;
+    }
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(metrics.snapshot().packet_panics, 2);
+    send_message(&attacker, &Message::data(serde_json::json!({"ok": 1})), server_addr).await?;
+    assert!(receive_type_within(&attacker, MessageType::Data, Duration::from_secs(2)).await?.is_some());
+
+    // 第三次 panic 后整个IP被封禁，换端口也无法握手
+    send_message(&attacker, &boom, server_addr).await?;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(metrics.snapshot().packet_panics, 3);
+    assert_eq!(quarantine.banned().iter().map(|b| b.ip.to_string()).collect::<Vec<_>>(), vec!["127.0.0.1"]);
+    let other_port = UdpSocket::bind("127.0.0.1:0").await?;
+    assert!(!handshake_answered(&other_port, server_addr).await?);
+    assert!(metrics.snapshot().packets_banned >= 1);
+
+    // 其他来源不受影响
+    let client = UdpSocket::bind("127.0.0.2:0").await?;
+    assert!(handshake_answered(&client, server_addr).await?);
+    send_message(&client, &Message::data(serde_json::json!({"ok": 2})), server_addr).await?;
+    let reply = receive_type_within(&client, MessageType::Data, Duration::from_secs(2)).await?.expect("服务器未继续处理其他来源");
+    assert_eq!(reply.payload["ok"], 2);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use tokio::time::{timeout, Duration, sleep};
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer, PathMtu, PathMtuConfig, SessionConfig, SessionState};
+
+fn client_config(server_addr: std::net::SocketAddr, name: &str) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        name: name.to_string(),
+        network_id: "test".to_string(),
+        session: SessionConfig {
+            punch_attempts: 5,
+            punch_interval_ms: 50,
+            keepalive_interval_ms: 100,
+            repunch_interval_ms: 60000,
+            probe_mtu: true,
+            ..SessionConfig::default()
+        },
+        ..ClientConfig::default()
+    }
+}
+
+/// 轮询直到取到值或超时
+async fn wait_for<T>(probe: impl Fn() -> Option<T>) -> Option<T> {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    loop {
+        if let Some(value) = probe() {
+            return Some(value);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return None;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_server_and_direct_paths_probe_mtu() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18695".parse().unwrap(),
+        path_mtu: PathMtuConfig { enable: true, ..PathMtuConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = P2PClient::connect(client_config(server_addr, "alice")).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr, "bob")).await?;
+
+    // 回环地址上探测上限直接可达：路径 MTU 等于上限且没有限制
+    let expected = PathMtu { mtu: 1472, limited: false };
+    assert_eq!(wait_for(|| alice.path_mtu()).await, Some(expected));
+    assert_eq!(alice.diagnose().await?.path_mtu, Some(expected));
+
+    let session = alice.open_session(bob.node_id()).await?;
+    let accepted = timeout(Duration::from_secs(3), bob.accept()).await?.expect("未收到会话");
+    assert_eq!(wait_for(|| session.path_mtu()).await, Some(expected));
+    assert_eq!(wait_for(|| accepted.path_mtu()).await, Some(expected));
+    assert!(matches!(session.state(), SessionState::Direct(_)));
+    Ok(())
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+
+use p2p_handshake_server::{Config, P2PServer};
+use p2p_handshake_server::protocol::{Message, MessageType};
+use common::{handshake, receive_type, receive_type_within, send_message};
+
+#[tokio::test]
+async fn test_peer_down_reaches_recent_contacts_only() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18230".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let bystander = UdpSocket::bind("127.0.0.1:0").await?;
+    handshake(&alice, server_addr, "alice").await?;
+    let (bob_info, _) = handshake(&bob, server_addr, "bob").await?;
+    handshake(&bystander, server_addr, "bystander").await?;
+
+    // 经服务器协调直连后，双方互为近期通信节点
+    send_message(&alice, &Message::initiate_p2p(bob_info.id), server_addr).await?;
+    receive_type(&alice, MessageType::P2PConnect).await?
+        .expect("请求方未收到直连协调");
+
+    send_message(&bob, &Message::disconnect("bye".to_string()), server_addr).await?;
+
+    let notice = receive_type(&alice, MessageType::PeerDown).await?
+        .expect("近期通信节点未收到下线通知");
+    assert_eq!(notice.payload["peer_id"], bob_info.id.to_string());
+    assert!(receive_type_within(&bystander, MessageType::PeerDown, Duration::from_millis(300)).await?.is_none());
+
+    Ok(())
+}
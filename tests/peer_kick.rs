@@ -0,0 +1,117 @@
+mod common;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::sync::{Arc, Mutex, Weak};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{DisconnectNotice, Message, MessageType, NodeInfo, PeerInfo};
+use p2p_handshake_server::{AdminConfig, Config, Connection, DisconnectReason, P2PServer, PeerManager, PeerRelease};
+use common::{receive_type, receive_type_within};
+
+/// 记录被释放的节点
+#[derive(Default)]
+struct Released(Mutex<Vec<Uuid>>);
+
+impl PeerRelease for Released {
+    fn release<'a>(&'a self, peer_id: &'a Uuid) -> BoxFuture<'a, ()> {
+        Box::pin(async move { self.0.lock().unwrap().push(*peer_id) })
+    }
+}
+
+#[tokio::test]
+async fn test_kick_notifies_releases_and_broadcasts() -> Result<()> {
+    let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await?);
+    let local_addr = socket.local_addr()?;
+    let peer_manager = PeerManager::new(NodeInfo::new("server".to_string(), local_addr, "test".to_string()), 16);
+    let released = Arc::new(Released::default());
+    let hook: Arc<dyn PeerRelease> = released.clone();
+    peer_manager.set_release_hook(Arc::downgrade(&hook) as Weak<dyn PeerRelease>);
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let mut ids = Vec::new();
+    for (remote, name) in [(&alice, "alice"), (&bob, "bob")] {
+        let peer = peer_manager.add_peer(Arc::new(Connection::new(socket.clone(), remote.local_addr()?, local_addr))).await?;
+        let info = NodeInfo::new(name.to_string(), remote.local_addr()?, "test".to_string());
+        peer_manager.handle_handshake_request(peer, &Message::handshake_request(info.clone())?).await?;
+        ids.push(info.id);
+    }
+    while receive_type_within(&alice, MessageType::DiscoveryResponse, Duration::from_secs(1)).await?.is_some() {}
+
+    assert!(peer_manager.kick(&ids[1], DisconnectReason::Kicked, "测试踢出").await);
+    let notice = receive_type(&bob, MessageType::Disconnect).await?.expect("未收到断开通知");
+    let notice: DisconnectNotice = serde_json::from_value(notice.payload)?;
+    assert_eq!(notice, DisconnectNotice { code: Some(DisconnectReason::Kicked), reason: "测试踢出".to_string() });
+    assert_eq!(*released.0.lock().unwrap(), vec![ids[1]]);
+    assert!(peer_manager.get_peer(&ids[1]).await.is_none());
+
+    // 剩下的节点收到不含被踢节点的节点列表
+    let list = receive_type(&alice, MessageType::DiscoveryResponse).await?.expect("未收到节点列表广播");
+    let peers: Vec<PeerInfo> = serde_json::from_value(list.payload)?;
+    assert!(peers.iter().all(|peer| peer.id != ids[1]));
+
+    // 节点不存在时返回 false，不再释放
+    assert!(!peer_manager.kick(&ids[1], DisconnectReason::Kicked, "测试踢出").await);
+    assert_eq!(released.0.lock().unwrap().len(), 1);
+
+    // 钩子被释放后只移除节点记录
+    drop(hook);
+    drop(released);
+    assert!(peer_manager.kick(&ids[0], DisconnectReason::QuotaExceeded, "测试").await);
+    assert!(peer_manager.get_peer(&ids[0]).await.is_none());
+    Ok(())
+}
+
+/// 向管理接口发送请求，返回状态码与正文
+async fn admin_request(admin: std::net::SocketAddr, method: &str, path: &str) -> Result<(u16, String)> {
+    let mut stream = TcpStream::connect(admin).await?;
+    let request = format!("{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", method, path);
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let status = response.split_whitespace().nth(1).unwrap_or_default().parse()?;
+    let (_, body) = response.split_once("\r\n\r\n").unwrap_or_default();
+    Ok((status, body.to_string()))
+}
+
+#[tokio::test]
+async fn test_admin_http_kick_with_encoded_token_and_reason() -> Result<()> {
+    let _ = env_logger::try_init();
+    let admin: std::net::SocketAddr = "127.0.0.1:18725".parse().unwrap();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18724".parse().unwrap(),
+        admin: AdminConfig { enable: true, listen_address: admin, token: Some("a+b&c".to_string()), ..AdminConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("bob".to_string(), bob.local_addr()?, "test".to_string());
+    bob.send_to(&serde_json::to_vec(&Message::handshake_request(info.clone())?)?, server_addr).await?;
+    assert!(receive_type(&bob, MessageType::HandshakeResponse).await?.is_some());
+
+    // 令牌须百分号编码后才能放进查询参数
+    let path = format!("/api/peers/{}?token=a%2Bb%26c&reason=%E7%BB%B4%E6%8A%A4+%E7%AA%97%E5%8F%A3", info.id);
+    assert_eq!(admin_request(admin, "DELETE", &path.replace("a%2Bb%26c", "a+b&c")).await?.0, 401);
+    let (status, body) = admin_request(admin, "DELETE", &path).await?;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&body)?["kicked"], true);
+    let notice = receive_type(&bob, MessageType::Disconnect).await?.expect("未收到断开通知");
+    let notice: DisconnectNotice = serde_json::from_value(notice.payload)?;
+    assert_eq!(notice, DisconnectNotice { code: Some(DisconnectReason::Kicked), reason: "维护 窗口".to_string() });
+
+    // 节点已不在线，非法ID返回 400
+    assert_eq!(admin_request(admin, "DELETE", &path).await?.0, 404);
+    assert_eq!(admin_request(admin, "DELETE", "/api/peers/not-a-uuid?token=a%2Bb%26c").await?.0, 400);
+    Ok(())
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use anyhow::Result;
+use futures::future::try_join_all;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{Message, MessageType, P2PPath, PeerInfo};
+use p2p_handshake_server::{Config, DiscoveryConfig, P2PServer};
+use common::{handshake, receive_any, send_message};
+
+/// 发送节点发现请求，返回对该请求的应答中的节点ID
+async fn discover(socket: &UdpSocket, server: SocketAddr) -> Result<Vec<Uuid>> {
+    let request = Message::discovery_request();
+    send_message(socket, &request, server).await?;
+    loop {
+        let response = receive_any(socket, &[MessageType::DiscoveryResponse]).await?.expect("未在超时内收到应答");
+        if response.reply_to == Some(request.id) {
+            let peers: Vec<PeerInfo> = serde_json::from_value(response.payload)?;
+            return Ok(peers.into_iter().map(|peer| peer.id).collect());
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_handlers_see_the_sender_of_each_message() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18728".parse().unwrap(),
+        // 完整的节点列表要放进一个应答
+        discovery: DiscoveryConfig { max_payload_bytes: 8192 },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let mut clients = Vec::new();
+    for i in 0..8 {
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        let (info, response) = handshake(&socket, server_addr, &format!("node-{}", i)).await?;
+        clients.push((socket, info.id, response.session_ticket.expect("握手响应缺少会话票据")));
+    }
+    let ids: Vec<Uuid> = clients.iter().map(|(_, id, _)| *id).collect();
+
+    // 未握手的来源按未认证处理，直连结果不被采信
+    let stranger = UdpSocket::bind("127.0.0.1:0").await?;
+    send_message(&stranger, &Message::p2p_connect_result(ids[1], P2PPath::Public), server_addr).await?;
+
+    // 所有节点同时反复请求节点列表：每个应答都发回请求者，且只排除请求者自己
+    try_join_all(clients.iter().map(|(socket, id, _)| {
+        let ids = &ids;
+        async move {
+            for _ in 0..5 {
+                let listed = discover(socket, server_addr).await?;
+                assert!(!listed.contains(id), "节点列表不应包含请求者自己");
+                assert_eq!(listed.len(), ids.len() - 1, "节点列表应包含其余全部节点");
+            }
+            anyhow::Ok(())
+        }
+    }))
+    .await?;
+    assert_eq!(metrics.p2p_public_path.load(Ordering::Relaxed), 0);
+
+    // 迁移地址后，新地址发来的消息归属于同一节点
+    let (_, first_id, ticket) = &clients[0];
+    let roamed = UdpSocket::bind("127.0.0.1:0").await?;
+    send_message(&roamed, &Message::migrate_address(*first_id, ticket.clone())?, server_addr).await?;
+    let migrated = receive_any(&roamed, &[MessageType::MigrateAddress, MessageType::Error]).await?.expect("未在超时内收到应答");
+    assert_eq!(migrated.message_type, MessageType::MigrateAddress, "迁移失败: {:?}", migrated.payload);
+    let listed = discover(&roamed, server_addr).await?;
+    assert!(!listed.contains(first_id));
+    assert_eq!(listed.len(), ids.len() - 1);
+
+    send_message(&roamed, &Message::p2p_connect_result(ids[1], P2PPath::Public), server_addr).await?;
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(metrics.p2p_public_path.load(Ordering::Relaxed), 1);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+
+use p2p_handshake_server::protocol::{Message, NodeInfo};
+use p2p_handshake_server::{Connection, PeerManager, PeerStatus};
+
+/// 逐个锁定节点统计，用于核对计数器
+async fn recount(peer_manager: &PeerManager) -> (usize, usize, usize) {
+    let peers = peer_manager.get_all_peers().await;
+    let mut authenticated = 0;
+    let mut connecting = 0;
+    for peer in &peers {
+        match peer.read().await.status {
+            PeerStatus::Authenticated => authenticated += 1,
+            PeerStatus::Connecting | PeerStatus::Handshaking => connecting += 1,
+            _ => {}
+        }
+    }
+    (peers.len(), authenticated, connecting)
+}
+
+async fn assert_stats(peer_manager: &PeerManager, expected: (usize, usize, usize)) {
+    let stats = peer_manager.get_stats().await;
+    assert_eq!((stats.total_peers, stats.authenticated_peers, stats.connecting_peers), expected);
+    assert_eq!(recount(peer_manager).await, expected);
+}
+
+#[tokio::test]
+async fn test_stats_follow_state_transitions() -> Result<()> {
+    let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await?);
+    let local_addr = socket.local_addr()?;
+    let local_info = NodeInfo::new("server".to_string(), local_addr, "test".to_string());
+    let peer_manager = PeerManager::new(local_info, 16);
+    let mut remotes = Vec::new();
+    for _ in 0..3 {
+        remotes.push(UdpSocket::bind("127.0.0.1:0").await?);
+    }
+    let connection = |i: usize| Arc::new(Connection::new(socket.clone(), remotes[i].local_addr().unwrap(), local_addr));
+
+    let a = peer_manager.add_peer(connection(0)).await?;
+    let b = peer_manager.add_peer(connection(1)).await?;
+    assert_stats(&peer_manager, (2, 0, 2)).await;
+
+    // 握手成功后计入已认证
+    let info = NodeInfo::new("a".to_string(), remotes[0].local_addr()?, "test".to_string());
+    peer_manager.handle_handshake_request(a.clone(), &Message::handshake_request(info.clone())?).await?;
+    b.write().await.update_status(PeerStatus::Error("测试".to_string()));
+    assert_stats(&peer_manager, (2, 1, 0)).await;
+
+    // 同ID从新地址重连：旧节点被替换，不再计入
+    let c = peer_manager.add_peer(connection(2)).await?;
+    let mut reconnect = info.clone();
+    reconnect.listen_addr = remotes[2].local_addr()?;
+    peer_manager.handle_handshake_request(c.clone(), &Message::handshake_request(reconnect)?).await?;
+    assert_stats(&peer_manager, (2, 1, 0)).await;
+
+    // 移除后的状态变化不影响统计
+    let b_id = b.read().await.id;
+    peer_manager.remove_peer(&b_id).await;
+    b.write().await.update_status(PeerStatus::Authenticated);
+    a.write().await.update_status(PeerStatus::Authenticated);
+    assert_stats(&peer_manager, (1, 1, 0)).await;
+
+    peer_manager.remove_peer(&info.id).await;
+    assert_stats(&peer_manager, (0, 0, 0)).await;
+    Ok(())
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use uuid::Uuid;
+
+use p2p_handshake_server::{Config, P2PServer, PinnedPeer, PinningConfig};
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo, PeerInfo};
+use common::{handshake, receive_type, receive_type_within, send_message};
+
+#[tokio::test]
+async fn test_pinned_peer_kept_connected_and_listed() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let pinned_socket = UdpSocket::bind("127.0.0.1:18281").await?;
+    let pinned = PinnedPeer { node_id: Uuid::new_v4(), addr: pinned_socket.local_addr()? };
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18280".parse().unwrap(),
+        max_connections: 1,
+        pinning: PinningConfig { peers: vec![pinned.clone()], reconnect_interval_secs: 1 },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 固定节点未在线时同样出现在节点列表中
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    assert!(handshake(&client, server_addr, "client").await?.1.success);
+    let discovery = receive_type(&client, MessageType::DiscoveryResponse).await?
+        .expect("未收到节点列表");
+    let peers: Vec<PeerInfo> = serde_json::from_value(discovery.payload)?;
+    let listed = peers.iter().find(|p| p.id == pinned.node_id).expect("节点列表缺少固定节点");
+    assert!(listed.pinned);
+    assert_eq!(listed.addr, pinned.addr);
+
+    // 服务器持续向未应答的固定节点发起握手；已达连接数上限也不影响
+    let first = receive_type(&pinned_socket, MessageType::HandshakeRequest).await?;
+    assert!(first.is_some());
+    let retry = receive_type(&pinned_socket, MessageType::HandshakeRequest).await?;
+    assert!(retry.is_some(), "未应答时应重试握手");
+
+    let mut info = NodeInfo::new("pinned".to_string(), pinned.addr, "test".to_string());
+    info.id = pinned.node_id;
+    send_message(&pinned_socket, &Message::handshake_response(info, true)?, server_addr).await?;
+
+    // 应答后不再重试
+    let again = receive_type_within(&pinned_socket, MessageType::HandshakeRequest, Duration::from_millis(2500)).await?;
+    assert!(again.is_none(), "固定节点已连接，不应再次发起握手");
+
+    // 普通节点仍受连接数限制
+    let rejected = UdpSocket::bind("127.0.0.1:0").await?;
+    assert!(!handshake(&rejected, server_addr, "rejected").await?.1.success);
+
+    Ok(())
+}
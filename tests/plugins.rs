@@ -0,0 +1,89 @@
+mod common;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo};
+use p2p_handshake_server::{Config, P2PServer, Plugin, PluginContext};
+use common::receive_type_within;
+
+/// 回显 `acme.echo` 消息，并经上下文给发送方额外推送一条 `acme.notice`
+#[derive(Default)]
+struct EchoPlugin {
+    context: Mutex<Option<PluginContext>>,
+}
+
+impl Plugin for EchoPlugin {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn tags(&self) -> Vec<String> {
+        vec!["acme.echo".to_string()]
+    }
+
+    fn start(&self, context: PluginContext) -> BoxFuture<'_, Result<()>> {
+        *self.context.lock().unwrap() = Some(context);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn handle<'a>(&'a self, from: Uuid, message: &'a Message) -> BoxFuture<'a, Result<Option<Message>>> {
+        Box::pin(async move {
+            let context = self.context.lock().unwrap().clone().expect("插件应已启动");
+            assert_eq!(context.authenticated_peers().await, vec![from]);
+            context.send_to_peer(from, &Message::extension("acme.notice", serde_json::json!({ "to": from }))).await?;
+            Ok(Some(Message::extension("acme.echo", message.payload.clone())))
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_plugin_claims_extension_tag() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18620".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr: SocketAddr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    server.register_plugin(Arc::new(EchoPlugin::default()))?;
+    assert!(server.register_plugin(Arc::new(EchoPlugin::default())).is_err(), "同一标签不能被认领两次");
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("client".to_string(), client.local_addr()?, "test".to_string());
+    let node_id = info.id;
+
+    // 握手前发送的扩展消息被拒绝
+    let early = Message::extension("acme.echo", serde_json::json!({ "n": 0 }));
+    client.send_to(&serde_json::to_vec(&early)?, server_addr).await?;
+    assert!(receive_type_within(&client, MessageType::Error, Duration::from_secs(2)).await?.is_some());
+
+    client.send_to(&serde_json::to_vec(&Message::handshake_request(info)?)?, server_addr).await?;
+    assert!(receive_type_within(&client, MessageType::HandshakeResponse, Duration::from_secs(2)).await?.is_some());
+
+    let request = Message::extension("acme.echo", serde_json::json!({ "n": 1 }));
+    client.send_to(&serde_json::to_vec(&request)?, server_addr).await?;
+    let notice = receive_type_within(&client, MessageType::Extension("acme.notice".to_string()), Duration::from_secs(2)).await?.expect("应收到插件主动发送的消息");
+    assert_eq!(notice.payload["to"], node_id.to_string());
+    let reply = receive_type_within(&client, MessageType::Extension("acme.echo".to_string()), Duration::from_secs(2)).await?.expect("应收到插件回复");
+    assert_eq!(reply.reply_to, Some(request.id));
+    assert_eq!(reply.payload["n"], 1);
+
+    // 没有插件认领的标签
+    let unknown = Message::extension("acme.unknown", serde_json::json!({}));
+    client.send_to(&serde_json::to_vec(&unknown)?, server_addr).await?;
+    let error = receive_type_within(&client, MessageType::Error, Duration::from_secs(2)).await?.expect("应收到错误");
+    assert_eq!(error.reply_to, Some(unknown.id));
+    Ok(())
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration, sleep};
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer};
+use p2p_handshake_server::protocol::{Message, NodeInfo, PresenceUpdate};
+use common::handshake_with;
+
+async fn next_presence(client: &mut P2PClient, wait: Duration) -> Option<PresenceUpdate> {
+    timeout(wait, client.recv_presence()).await.ok().flatten()
+}
+
+#[tokio::test]
+async fn test_watchers_notified_on_join_leave_and_timeout() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18450".parse().unwrap(),
+        heartbeat_interval: 1,
+        connection_timeout: 1,
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client_config = ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        server_keepalive_secs: 1,
+        ..ClientConfig::default()
+    };
+    let mut alice = P2PClient::connect(client_config.clone()).await?;
+    let carol = P2PClient::connect(client_config).await?;
+
+    // 关注时应答当前在线的节点
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob_info = NodeInfo::new("bob".to_string(), bob.local_addr()?, "test".to_string());
+    let dave = UdpSocket::bind("127.0.0.1:0").await?;
+    let dave_info = NodeInfo::new("dave".to_string(), dave.local_addr()?, "test".to_string());
+    let online = alice.watch(vec![bob_info.id, carol.node_id(), alice.node_id()]).await?;
+    assert_eq!(online, vec![carol.node_id()]);
+
+    // 上线、主动断开
+    handshake_with(&bob, server_addr, &bob_info).await?;
+    let update = next_presence(&mut alice, Duration::from_secs(3)).await.expect("未收到上线通知");
+    assert_eq!(update, PresenceUpdate { peer_id: bob_info.id, online: true });
+    bob.send_to(&serde_json::to_vec(&Message::disconnect("bye".to_string()))?, server_addr).await?;
+    let update = next_presence(&mut alice, Duration::from_secs(3)).await.expect("未收到下线通知");
+    assert_eq!(update, PresenceUpdate { peer_id: bob_info.id, online: false });
+
+    // 未关注的节点不推送；关注后超时下线也会通知
+    handshake_with(&dave, server_addr, &dave_info).await?;
+    assert!(next_presence(&mut alice, Duration::from_millis(300)).await.is_none());
+    assert_eq!(alice.watch(vec![dave_info.id]).await?, vec![dave_info.id]);
+    let update = next_presence(&mut alice, Duration::from_secs(6)).await.expect("未收到超时下线通知");
+    assert_eq!(update, PresenceUpdate { peer_id: dave_info.id, online: false });
+
+    // 取消关注后不再推送
+    alice.unwatch(vec![carol.node_id()]).await?;
+    drop(carol);
+    assert!(next_presence(&mut alice, Duration::from_secs(4)).await.is_none());
+    Ok(())
+}
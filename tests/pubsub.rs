@@ -0,0 +1,54 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+
+use p2p_handshake_server::{Config, P2PServer};
+use p2p_handshake_server::protocol::{Message, MessageType};
+use common::{handshake, receive_type, receive_type_within, send_message};
+
+#[tokio::test]
+async fn test_publish_reaches_subscribers_only() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18200".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let publisher = UdpSocket::bind("127.0.0.1:0").await?;
+    let subscriber = UdpSocket::bind("127.0.0.1:0").await?;
+    let bystander = UdpSocket::bind("127.0.0.1:0").await?;
+    let (publisher_info, _) = handshake(&publisher, server_addr, "publisher").await?;
+    handshake(&subscriber, server_addr, "subscriber").await?;
+    handshake(&bystander, server_addr, "bystander").await?;
+
+    send_message(&subscriber, &Message::subscribe("sensors/temp"), server_addr).await?;
+    sleep(Duration::from_millis(100)).await;
+
+    let data = serde_json::json!({ "celsius": 21.5 });
+    send_message(&publisher, &Message::publish("sensors/temp", data.clone()), server_addr).await?;
+
+    let delivered = receive_type(&subscriber, MessageType::Publish).await?
+        .expect("订阅者未收到主题消息");
+    assert_eq!(delivered.payload["topic"], "sensors/temp");
+    assert_eq!(delivered.payload["from"], publisher_info.id.to_string());
+    assert_eq!(delivered.payload["data"], data);
+    assert!(receive_type_within(&bystander, MessageType::Publish, Duration::from_millis(300)).await?.is_none());
+
+    // 取消订阅后不再收到
+    send_message(&subscriber, &Message::unsubscribe("sensors/temp"), server_addr).await?;
+    sleep(Duration::from_millis(100)).await;
+    send_message(&publisher, &Message::publish("sensors/temp", data), server_addr).await?;
+    assert!(receive_type_within(&subscriber, MessageType::Publish, Duration::from_millis(300)).await?.is_none());
+
+    Ok(())
+}
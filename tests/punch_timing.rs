@@ -0,0 +1,65 @@
+mod common;
+
+use anyhow::Result;
+use std::time::Instant;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+
+use p2p_handshake_server::protocol::{Message, MessageType};
+use p2p_handshake_server::{Config, P2PServer, PunchBeacon, PunchTimingConfig};
+use common::{handshake, receive_type};
+
+/// 依次收到的信标及收到的时刻
+async fn beacons(socket: &UdpSocket, count: usize) -> Result<Vec<(PunchBeacon, Instant)>> {
+    let mut received = Vec::new();
+    for _ in 0..count {
+        let message = receive_type(socket, MessageType::PunchBeacon).await?.expect("未收到打洞信标");
+        received.push((serde_json::from_value(message.payload)?, Instant::now()));
+    }
+    Ok(received)
+}
+
+#[tokio::test]
+async fn test_both_peers_receive_countdown_to_go() -> Result<()> {
+    let _ = env_logger::try_init();
+    assert_eq!(PunchTimingConfig { enable: true, countdown_ms: vec![1000, 2000, 1000] }.schedule(), vec![2000, 1000, 0]);
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18689".parse().unwrap(),
+        punch_timing: PunchTimingConfig { enable: true, countdown_ms: vec![400, 200] },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let alice_id = handshake(&alice, server_addr, "alice").await?.0.id;
+    let bob_id = handshake(&bob, server_addr, "bob").await?.0.id;
+
+    let requested = Instant::now();
+    alice.send_to(&serde_json::to_vec(&Message::initiate_p2p(bob_id))?, server_addr).await?;
+    for socket in [&alice, &bob] {
+        let connect = receive_type(socket, MessageType::P2PConnect).await?.expect("未收到 P2PConnect");
+        assert_eq!(connect.payload["punch_in_ms"], 400);
+    }
+
+    let (to_alice, to_bob) = tokio::join!(beacons(&alice, 3), beacons(&bob, 3));
+    let (to_alice, to_bob) = (to_alice?, to_bob?);
+    for (received, other) in [(&to_alice, bob_id), (&to_bob, alice_id)] {
+        let countdown: Vec<u64> = received.iter().map(|(beacon, _)| beacon.go_in_ms).collect();
+        assert_eq!(countdown, vec![400, 200, 0]);
+        assert!(received.iter().all(|(beacon, _)| beacon.peer_id == other));
+        // GO 约在协调 400 毫秒后到达
+        let go = received[2].1.duration_since(requested);
+        assert!(go >= Duration::from_millis(350) && go < Duration::from_millis(1500), "{:?}", go);
+    }
+    let skew = to_alice[2].1.max(to_bob[2].1) - to_alice[2].1.min(to_bob[2].1);
+    assert!(skew < Duration::from_millis(100), "{:?}", skew);
+    Ok(())
+}
@@ -1,17 +1,13 @@
+mod common;
+
 use anyhow::Result;
 use tokio::net::UdpSocket;
 use tokio::time::{timeout, Duration, sleep};
-use std::net::SocketAddr;
 
 use p2p_handshake_server::{Config, P2PServer};
 use p2p_handshake_server::protocol::{Message, MessageType, HandshakeResponse, NodeInfo};
 use uuid::Uuid;
-
-async fn send_message(socket: &UdpSocket, message: &Message, target: SocketAddr) -> Result<()> {
-    let data = serde_json::to_vec(message)?;
-    socket.send_to(&data, target).await?;
-    Ok(())
-}
+use common::send_message;
 
 async fn receive_message(socket: &UdpSocket) -> Result<Option<Message>> {
     let mut buffer = vec![0u8; 65536];
@@ -27,6 +23,7 @@ async fn receive_message(socket: &UdpSocket) -> Result<Option<Message>> {
 }
 
 #[tokio::test]
+#[allow(clippy::field_reassign_with_default)]
 async fn test_reconnect_same_node_id() -> Result<()> {
     // 初始化日志（忽略重复初始化错误）
     let _ = env_logger::try_init();
@@ -0,0 +1,148 @@
+mod common;
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo, RelayData, RelayResponse, RELAY_FRAME_CAPABILITY, RELAY_FRAME_HEADER_LEN};
+use p2p_handshake_server::RelayFrame;
+use p2p_handshake_server::{Config, P2PServer};
+use common::{handshake, handshake_with, receive_type};
+
+async fn relay_response(socket: &UdpSocket, server: SocketAddr, message: &Message) -> Result<RelayResponse> {
+    socket.send_to(&serde_json::to_vec(message)?, server).await?;
+    let response = receive_type(socket, MessageType::RelayResponse).await?.expect("未收到 RelayResponse");
+    Ok(serde_json::from_value(response.payload)?)
+}
+
+async fn start_server(port: u16) -> Result<SocketAddr> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: format!("127.0.0.1:{}", port).parse().unwrap(),
+        allow_symmetric_nat_relay: true,
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+    Ok(server_addr)
+}
+
+#[tokio::test]
+async fn test_relay_request_opens_session_and_forwards_data_by_session() -> Result<()> {
+    let _ = env_logger::try_init();
+    let server_addr = start_server(18670).await?;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let alice_id = handshake(&alice, server_addr, "alice").await?.0.id;
+    let bob_id = handshake(&bob, server_addr, "bob").await?.0.id;
+
+    // 未握手的节点不能请求中继
+    let stranger = UdpSocket::bind("127.0.0.1:0").await?;
+    let denied = relay_response(&stranger, server_addr, &Message::relay_session_request(bob_id)).await?;
+    assert!(!denied.success && denied.session_id.is_none());
+
+    // 目标不存在或为自身时拒绝
+    let missing = relay_response(&alice, server_addr, &Message::relay_session_request(Uuid::new_v4())).await?;
+    assert_eq!(missing.error_message.as_deref(), Some("目标节点未找到"));
+    let to_self = relay_response(&alice, server_addr, &Message::relay_session_request(alice_id)).await?;
+    assert!(!to_self.success);
+
+    // 建立会话，重复请求沿用同一会话ID
+    let opened = relay_response(&alice, server_addr, &Message::relay_session_request(bob_id)).await?;
+    assert!(opened.success, "{:?}", opened.error_message);
+    let session_id = opened.session_id.expect("响应中没有会话ID");
+    let again = relay_response(&alice, server_addr, &Message::relay_session_request(bob_id)).await?;
+    assert_eq!(again.session_id, Some(session_id));
+
+    // 按会话转发数据，成功时不回复发送方
+    alice.send_to(&serde_json::to_vec(&Message::relay_session_data(session_id, b"via session".to_vec()))?, server_addr).await?;
+    let relayed = receive_type(&bob, MessageType::RelayData).await?.expect("bob 未收到中继数据");
+    let relayed: RelayData = serde_json::from_value(relayed.payload)?;
+    assert_eq!((relayed.from_peer_id, relayed.session_id), (alice_id, Some(session_id)));
+    assert_eq!(relayed.data, b"via session");
+
+    // 会话只属于发起方；未知会话ID被拒绝
+    let hijack = relay_response(&bob, server_addr, &Message::relay_session_data(session_id, b"x".to_vec())).await?;
+    assert!(!hijack.success);
+    assert_eq!(hijack.session_id, Some(session_id));
+    let unknown = relay_response(&alice, server_addr, &Message::relay_session_data(Uuid::new_v4(), b"x".to_vec())).await?;
+    assert!(!unknown.success);
+
+    // 目标断开后会话随之失效
+    bob.send_to(&serde_json::to_vec(&Message::disconnect("bye".to_string()))?, server_addr).await?;
+    sleep(Duration::from_millis(200)).await;
+    let closed = relay_response(&alice, server_addr, &Message::relay_session_data(session_id, b"x".to_vec())).await?;
+    assert_eq!(closed.error_message.as_deref(), Some("中继会话不存在或已失效"));
+    Ok(())
+}
+
+/// 接收下一个中继帧，超时返回 `None`
+async fn receive_frame(socket: &UdpSocket) -> Result<Option<Vec<u8>>> {
+    let mut buffer = vec![0u8; 65536];
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+    loop {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        match timeout(remaining, socket.recv_from(&mut buffer)).await {
+            Ok(Ok((len, _addr))) if RelayFrame::parse(&buffer[..len]).is_some() => return Ok(Some(buffer[..len].to_vec())),
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => return Ok(None),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_binary_frames_are_forwarded_without_json() -> Result<()> {
+    let _ = env_logger::try_init();
+    let server_addr = start_server(18671).await?;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let carol = UdpSocket::bind("127.0.0.1:0").await?;
+    let alice_id = handshake(&alice, server_addr, "alice").await?.0.id;
+    let mut bob_info = NodeInfo::new("bob".to_string(), bob.local_addr()?, "test".to_string());
+    bob_info.add_capability(RELAY_FRAME_CAPABILITY);
+    handshake_with(&bob, server_addr, &bob_info).await?;
+    let bob_id = bob_info.id;
+    let carol_id = handshake(&carol, server_addr, "carol").await?.0.id;
+
+    let to_bob = relay_response(&alice, server_addr, &Message::relay_session_request(bob_id)).await?;
+    assert_eq!(to_bob.target_peer_id, Some(bob_id));
+    let to_bob = to_bob.session_id.unwrap();
+    let to_carol = relay_response(&alice, server_addr, &Message::relay_session_request(carol_id)).await?.session_id.unwrap();
+
+    // 声明了能力的目标收到的帧只改写了发送方ID，发送方填写的ID被忽略
+    let data = vec![0xAB; 1000];
+    let forged = Uuid::new_v4();
+    let frame = RelayFrame { session_id: to_bob, from_peer_id: forged, data: &data }.encode();
+    alice.send_to(&frame, server_addr).await?;
+    let received = receive_frame(&bob).await?.expect("bob 未收到中继帧");
+    assert_eq!(received.len(), RELAY_FRAME_HEADER_LEN + data.len());
+    assert_eq!(RelayFrame::parse(&received), Some(RelayFrame { session_id: to_bob, from_peer_id: alice_id, data: &data }));
+
+    // JSON 负载转发给支持帧的目标时同样编码为帧
+    alice.send_to(&serde_json::to_vec(&Message::relay_session_data(to_bob, b"json".to_vec()))?, server_addr).await?;
+    let received = receive_frame(&bob).await?.expect("bob 未收到中继帧");
+    assert_eq!(RelayFrame::parse(&received).unwrap().data, b"json");
+
+    // 不支持帧的目标收到 JSON RelayData
+    let frame = RelayFrame { session_id: to_carol, from_peer_id: Uuid::nil(), data: b"legacy" }.encode();
+    alice.send_to(&frame, server_addr).await?;
+    let relayed = receive_type(&carol, MessageType::RelayData).await?.expect("carol 未收到中继数据");
+    let relayed: RelayData = serde_json::from_value(relayed.payload)?;
+    assert_eq!((relayed.from_peer_id, relayed.data.as_slice()), (alice_id, &b"legacy"[..]));
+
+    // 他人的会话帧被拒绝
+    let frame = RelayFrame { session_id: to_bob, from_peer_id: Uuid::nil(), data: b"x" }.encode();
+    carol.send_to(&frame, server_addr).await?;
+    let rejected = receive_type(&carol, MessageType::RelayResponse).await?.expect("未收到 RelayResponse");
+    assert_eq!(rejected.payload["success"], false);
+    Ok(())
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{Message, MessageType, RelayClose, RelayCloseReason, RelayResponse};
+use p2p_handshake_server::{Config, P2PServer, RelayConfig};
+use common::{handshake, receive_type_within};
+
+async fn receive_close(socket: &UdpSocket, wait: Duration) -> Result<RelayClose> {
+    let message = receive_type_within(socket, MessageType::RelayClose, wait).await?.expect("未收到 RelayClose");
+    Ok(serde_json::from_value(message.payload)?)
+}
+
+async fn open_session(socket: &UdpSocket, server: SocketAddr, target: Uuid) -> Result<Uuid> {
+    socket.send_to(&serde_json::to_vec(&Message::relay_session_request(target))?, server).await?;
+    let response = receive_type_within(socket, MessageType::RelayResponse, Duration::from_secs(2)).await?.expect("未收到 RelayResponse");
+    let response: RelayResponse = serde_json::from_value(response.payload)?;
+    Ok(response.session_id.expect("响应中没有会话ID"))
+}
+
+async fn start_server(port: u16, idle_timeout_secs: u64) -> Result<SocketAddr> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: format!("127.0.0.1:{}", port).parse().unwrap(),
+        allow_symmetric_nat_relay: true,
+        cleanup_interval: 1,
+        relay: RelayConfig { idle_timeout_secs },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+    Ok(server_addr)
+}
+
+#[tokio::test]
+async fn test_explicit_close_and_peer_disconnect_notify_endpoints() -> Result<()> {
+    let _ = env_logger::try_init();
+    let server_addr = start_server(18680, 0).await?;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let alice_id = handshake(&alice, server_addr, "alice").await?.0.id;
+    let bob_id = handshake(&bob, server_addr, "bob").await?.0.id;
+
+    // 目标一方也可以关闭会话，双方都收到通知
+    let session_id = open_session(&alice, server_addr, bob_id).await?;
+    bob.send_to(&serde_json::to_vec(&Message::relay_close(session_id))?, server_addr).await?;
+    let to_alice = receive_close(&alice, Duration::from_secs(2)).await?;
+    assert_eq!(to_alice, RelayClose { session_id, peer_id: Some(bob_id), reason: Some(RelayCloseReason::Closed) });
+    let to_bob = receive_close(&bob, Duration::from_secs(2)).await?;
+    assert_eq!(to_bob.peer_id, Some(alice_id));
+
+    // 已关闭的会话不能再关闭或发送数据
+    alice.send_to(&serde_json::to_vec(&Message::relay_close(session_id))?, server_addr).await?;
+    let response = receive_type_within(&alice, MessageType::RelayResponse, Duration::from_secs(2)).await?.expect("未收到 RelayResponse");
+    assert_eq!(response.payload["success"], false);
+
+    // 一方断开时通知仍在线的一方
+    let session_id = open_session(&alice, server_addr, bob_id).await?;
+    bob.send_to(&serde_json::to_vec(&Message::disconnect("bye".to_string()))?, server_addr).await?;
+    let notice = receive_close(&alice, Duration::from_secs(2)).await?;
+    assert_eq!(notice, RelayClose { session_id, peer_id: Some(bob_id), reason: Some(RelayCloseReason::PeerDisconnected) });
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_idle_sessions_expire() -> Result<()> {
+    let _ = env_logger::try_init();
+    let server_addr = start_server(18681, 1).await?;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    handshake(&alice, server_addr, "alice").await?;
+    let bob_id = handshake(&bob, server_addr, "bob").await?.0.id;
+
+    let session_id = open_session(&alice, server_addr, bob_id).await?;
+    let notice = receive_close(&alice, Duration::from_secs(4)).await?;
+    assert_eq!((notice.session_id, notice.reason), (session_id, Some(RelayCloseReason::IdleTimeout)));
+    let notice = receive_close(&bob, Duration::from_secs(2)).await?;
+    assert_eq!(notice.session_id, session_id);
+
+    alice.send_to(&serde_json::to_vec(&Message::relay_session_data(session_id, b"late".to_vec()))?, server_addr).await?;
+    let response = receive_type_within(&alice, MessageType::RelayResponse, Duration::from_secs(2)).await?.expect("未收到 RelayResponse");
+    assert_eq!(response.payload["error_message"], "中继会话不存在或已失效");
+    Ok(())
+}
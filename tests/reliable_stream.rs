@@ -0,0 +1,109 @@
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{timeout, Duration, sleep};
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer, StreamConfig};
+
+fn client_config(server_addr: SocketAddr) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        stream: StreamConfig { retransmit_ms: 200, max_retransmits: 3, ..StreamConfig::default() },
+        ..ClientConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_stream_transfers_bytes_in_order() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18480".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = P2PClient::connect(client_config(server_addr)).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr)).await?;
+    let (alice_id, bob_id) = (alice.node_id(), bob.node_id());
+
+    // bob 读完整个流后回复收到的字节数
+    let receiver = tokio::spawn(async move {
+        let mut stream = bob.accept_stream().await.expect("未收到对方打开的流");
+        assert_eq!(stream.peer_id(), alice_id);
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).await?;
+        stream.write_all(received.len().to_string().as_bytes()).await?;
+        stream.shutdown().await?;
+        anyhow::Ok(received)
+    });
+
+    // 远大于窗口的数据需要多轮确认
+    let payload: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let mut stream = alice.open_stream(bob_id).await?;
+    stream.write_all(&payload).await?;
+    stream.shutdown().await?;
+    let mut reply = String::new();
+    timeout(Duration::from_secs(20), stream.read_to_string(&mut reply)).await??;
+    assert_eq!(reply, payload.len().to_string());
+    assert_eq!(receiver.await??, payload);
+
+    // 对方不存在时打开失败
+    assert!(alice.open_stream(Uuid::new_v4()).await.is_err());
+    Ok(())
+}
+
+#[cfg(feature = "chaos")]
+#[tokio::test]
+async fn test_stream_recovers_from_loss_and_reordering() -> Result<()> {
+    use p2p_handshake_server::{ChaosConfig, FaultProfile};
+    let _ = env_logger::try_init();
+
+    // 服务器发出的数据包丢失、重复与乱序
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18481".parse().unwrap(),
+        chaos: ChaosConfig {
+            enable: true,
+            outbound: FaultProfile { drop_rate: 0.1, duplicate_rate: 0.05, reorder_rate: 0.1, reorder_delay_ms: 30, ..FaultProfile::default() },
+            ..ChaosConfig::default()
+        },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let lossy = |server_addr| ClientConfig {
+        stream: StreamConfig { retransmit_ms: 100, max_retransmits: 30, ..StreamConfig::default() },
+        ..client_config(server_addr)
+    };
+    let alice = P2PClient::connect(lossy(server_addr)).await?;
+    let mut bob = P2PClient::connect(lossy(server_addr)).await?;
+    let bob_id = bob.node_id();
+    let receiver = tokio::spawn(async move {
+        let mut stream = bob.accept_stream().await.expect("未收到对方打开的流");
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).await?;
+        anyhow::Ok(received)
+    });
+
+    let payload: Vec<u8> = (0..50_000u32).map(|i| (i % 253) as u8).collect();
+    let mut stream = alice.open_stream(bob_id).await?;
+    stream.write_all(&payload).await?;
+    stream.shutdown().await?;
+    assert_eq!(timeout(Duration::from_secs(30), receiver).await???, payload);
+    Ok(())
+}
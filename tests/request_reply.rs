@@ -0,0 +1,71 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+use tokio::time::{timeout, Duration, sleep};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use p2p_handshake_server::{ClientConfig, Config, MessageHandler, P2PClient, P2PServer};
+use p2p_handshake_server::protocol::Message;
+
+/// 求和；`"silent"` 请求不应答
+struct Adder;
+
+impl MessageHandler for Adder {
+    fn handle_data<'a>(&'a self, _from: Uuid, message: &'a Message) -> BoxFuture<'a, Result<Option<Message>>> {
+        Box::pin(async move {
+            if message.payload.get("silent").is_some() {
+                return Ok(None);
+            }
+            let sum = message.payload["a"].as_i64().unwrap_or(0) + message.payload["b"].as_i64().unwrap_or(0);
+            // 未设置 reply_to，由服务器补上
+            Ok(Some(Message::data(serde_json::json!({ "sum": sum }))))
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_request_reply_both_directions() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18430".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    server.set_message_handler(Arc::new(Adder));
+    let requester = server.requester();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let mut client = P2PClient::connect(ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        ..ClientConfig::default()
+    }).await?;
+
+    // 客户端 → 服务器
+    let reply = client.request(serde_json::json!({ "a": 2, "b": 3 }), Duration::from_secs(3)).await?;
+    assert_eq!(reply.payload["sum"], 5);
+    assert!(reply.reply_to.is_some());
+
+    // 无应答时按超时返回错误
+    assert!(client.request(serde_json::json!({ "silent": true }), Duration::from_millis(200)).await.is_err());
+
+    // 服务器 → 客户端
+    let node_id = client.node_id();
+    let asking = tokio::spawn(async move {
+        requester.request(node_id, serde_json::json!({ "question": "name" }), Duration::from_secs(3)).await
+    });
+    let request = timeout(Duration::from_secs(3), client.recv_data()).await?.expect("客户端未收到请求");
+    assert_eq!(request.payload["question"], "name");
+    client.respond(&request, serde_json::json!({ "name": "alice" })).await?;
+    let answer = asking.await??;
+    assert_eq!(answer.reply_to, Some(request.id));
+    assert_eq!(answer.payload["name"], "alice");
+    Ok(())
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use p2p_handshake_server::{Config, P2PServer, RoutingConfig};
+use p2p_handshake_server::protocol::{Message, MessageType};
+use p2p_handshake_server::router::RoutedMessage;
+use common::{handshake_as, receive_type_within, send_message};
+
+async fn start_server(listen: &str, routing: RoutingConfig) -> Result<(SocketAddr, tokio::sync::broadcast::Sender<()>)> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: listen.parse().unwrap(),
+        routing,
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config.clone()).await?;
+    let shutdown = server.shutdown_sender();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+    Ok((config.listen_address, shutdown))
+}
+
+#[tokio::test]
+async fn test_resent_route_id_not_delivered_after_restart() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let log_path = std::env::temp_dir().join(format!("route_dedup_{}.bin", Uuid::new_v4()));
+    let routing = RoutingConfig { dedup_log_path: Some(log_path.clone()), ..RoutingConfig::default() };
+    let (sender_id, receiver_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let sender = UdpSocket::bind("127.0.0.1:0").await?;
+    let receiver = UdpSocket::bind("127.0.0.1:0").await?;
+
+    let (server, shutdown) = start_server("127.0.0.1:18310", routing.clone()).await?;
+    handshake_as(&sender, server, sender_id).await?;
+    handshake_as(&receiver, server, receiver_id).await?;
+    let routed = RoutedMessage::new(Message::data(serde_json::json!({"n": 1})), sender_id, receiver_id, 5);
+    send_message(&sender, &routed.to_message()?, server).await?;
+    assert!(receive_type_within(&receiver, MessageType::Data, Duration::from_secs(2)).await?.is_some());
+    shutdown.send(())?;
+    sleep(Duration::from_millis(300)).await;
+
+    // 服务器重启后客户端重连并重发同一条消息：接收方不会收到第二份
+    let (server, _shutdown) = start_server("127.0.0.1:18311", routing).await?;
+    handshake_as(&sender, server, sender_id).await?;
+    handshake_as(&receiver, server, receiver_id).await?;
+    send_message(&sender, &routed.to_message()?, server).await?;
+    assert!(receive_type_within(&receiver, MessageType::Data, Duration::from_secs(1)).await?.is_none(), "重发的消息不应再次投递");
+
+    // 新消息照常投递
+    let fresh = RoutedMessage::new(Message::data(serde_json::json!({"n": 2})), sender_id, receiver_id, 5);
+    send_message(&sender, &fresh.to_message()?, server).await?;
+    let delivered = receive_type_within(&receiver, MessageType::Data, Duration::from_secs(2)).await?.expect("新消息未送达");
+    assert_eq!(RoutedMessage::from_message(&delivered)?.route_id, fresh.route_id);
+
+    let _ = std::fs::remove_file(&log_path);
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use tokio::time::{timeout, Duration, sleep};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer, RouteSignaturePolicy, RoutingConfig};
+use p2p_handshake_server::router::RoutedMessage;
+
+fn client_config(server_addr: SocketAddr, identity_key_file: Option<PathBuf>) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        identity_key_file,
+        route_signatures: RouteSignaturePolicy::Require,
+        ..ClientConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_server_drops_unsigned_routed_messages() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18699".parse().unwrap(),
+        routing: RoutingConfig { signatures: RouteSignaturePolicy::Require, ..RoutingConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let key_file = std::env::temp_dir().join(format!("p2p-node-{}.key", Uuid::new_v4()));
+    let mut alice = P2PClient::connect(client_config(server_addr, Some(key_file.clone()))).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr, None)).await?;
+
+    // 有身份密钥的节点发出的路由消息带签名，服务器与目标节点都校验通过
+    alice.route(bob.node_id(), serde_json::json!({ "n": 1 })).await?;
+    let delivered = timeout(Duration::from_secs(3), bob.recv_data()).await?.expect("目标节点未收到签名的消息");
+    let routed = RoutedMessage::from_message(&delivered)?;
+    assert!(routed.signature.is_some());
+    assert_eq!(routed.original_message.payload["n"], 1);
+
+    // 没有身份密钥的节点发出的消息未签名，被服务器丢弃
+    bob.route(alice.node_id(), serde_json::json!({ "n": 2 })).await?;
+    assert!(timeout(Duration::from_millis(500), alice.recv_data()).await.is_err());
+
+    let _ = std::fs::remove_file(&key_file);
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use tokio::time::{timeout, Duration, Instant, sleep};
+use uuid::Uuid;
+
+use p2p_handshake_server::{ClientConfig, Config, OversizedRoutePolicy, P2PClient, P2PServer, RoutingConfig, ServerMetrics};
+
+async fn start_server(listen_address: &str, policy: OversizedRoutePolicy) -> Result<(SocketAddr, Arc<ServerMetrics>)> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: listen_address.parse().unwrap(),
+        routing: RoutingConfig { max_routed_payload_bytes: 1024, oversized_payloads: policy, ..RoutingConfig::default() },
+        ..Config::default()
+    };
+    let addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+    Ok((addr, metrics))
+}
+
+fn client_config(server_addr: SocketAddr) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        ..ClientConfig::default()
+    }
+}
+
+fn large_payload() -> serde_json::Value {
+    serde_json::json!({ "blob": "x".repeat(4000) })
+}
+
+#[tokio::test]
+async fn test_oversized_routed_payload_is_rejected() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (server_addr, metrics) = start_server("127.0.0.1:18708", OversizedRoutePolicy::Reject).await?;
+    let alice = P2PClient::connect(client_config(server_addr)).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr)).await?;
+
+    // 拒绝通知带有 reply_to，发送方不必等到超时
+    let started = Instant::now();
+    let err = alice.route_with_receipt(bob.node_id(), large_payload(), Duration::from_secs(5)).await.unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(err.to_string().contains("负载过大"));
+    assert!(timeout(Duration::from_millis(300), bob.recv_data()).await.is_err());
+    assert_eq!(metrics.routed_payload_rejections.load(Ordering::Relaxed), 1);
+
+    // 上限以内的消息照常送达
+    alice.route_with_receipt(bob.node_id(), serde_json::json!({ "n": 1 }), Duration::from_secs(3)).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_flood_policy_forwards_only_along_known_routes() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (server_addr, metrics) = start_server("127.0.0.1:18709", OversizedRoutePolicy::NoFlood).await?;
+    let alice = P2PClient::connect(client_config(server_addr)).await?;
+    let mut bob = P2PClient::connect(client_config(server_addr)).await?;
+
+    // 有路由时超长消息照常转发
+    alice.route_with_receipt(bob.node_id(), large_payload(), Duration::from_secs(3)).await?;
+    assert!(timeout(Duration::from_secs(3), bob.recv_data()).await?.is_some());
+
+    // 没有路由时拒绝，而不是广播给所有节点
+    let err = alice.route_with_receipt(Uuid::new_v4(), large_payload(), Duration::from_secs(5)).await.unwrap_err();
+    assert!(err.to_string().contains("负载过大"));
+    assert!(timeout(Duration::from_millis(300), bob.recv_data()).await.is_err());
+    assert_eq!(metrics.routed_payload_rejections.load(Ordering::Relaxed), 1);
+    Ok(())
+}
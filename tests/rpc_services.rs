@@ -0,0 +1,70 @@
+use anyhow::Result;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer};
+
+fn client_config(server_addr: SocketAddr) -> ClientConfig {
+    ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        ..ClientConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_named_services_called_through_directory() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18440".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // alice 提供 storage.get，另有一个注册了服务却从不应答的节点
+    let mut alice = P2PClient::connect(client_config(server_addr)).await?;
+    let alice_id = alice.node_id();
+    alice.register_service("storage.get").await?;
+    assert!(alice.register_service("storage get").await.is_err(), "非法服务名应被拒绝");
+    tokio::spawn(async move {
+        while let Some(call) = alice.recv_call().await {
+            let result = match call.args["key"].as_str() {
+                Some("color") => Ok(serde_json::json!("blue")),
+                _ => Err("no such key".to_string()),
+            };
+            alice.reply_call(&call, result).await.unwrap();
+        }
+    });
+    let mute = P2PClient::connect(client_config(server_addr)).await?;
+    mute.register_service("storage.slow").await?;
+
+    let bob = P2PClient::connect(client_config(server_addr)).await?;
+    assert_eq!(bob.lookup_service("storage.get").await?, vec![alice_id]);
+
+    let value = bob.call("storage.get", serde_json::json!({ "key": "color" }), Duration::from_secs(3)).await?;
+    assert_eq!(value, "blue");
+    let value = bob.call_peer(alice_id, "storage.get", serde_json::json!({ "key": "color" }), Duration::from_secs(3)).await?;
+    assert_eq!(value, "blue");
+
+    // 提供者返回的错误、未提供该服务的节点、不存在的服务与超时都作为错误返回
+    let err = bob.call("storage.get", serde_json::json!({ "key": "size" }), Duration::from_secs(3)).await.unwrap_err();
+    assert!(err.to_string().contains("no such key"), "{}", err);
+    assert!(bob.call_peer(bob.node_id(), "storage.get", serde_json::Value::Null, Duration::from_secs(3)).await.is_err());
+    assert!(bob.call("storage.put", serde_json::Value::Null, Duration::from_secs(3)).await.is_err());
+    assert!(bob.call("storage.slow", serde_json::Value::Null, Duration::from_millis(300)).await.is_err());
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.rpc_calls, 4);
+    assert_eq!(snapshot.rpc_calls_failed, 2);
+    drop(mute);
+    Ok(())
+}
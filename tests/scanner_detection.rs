@@ -0,0 +1,46 @@
+use anyhow::Result;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+
+use p2p_handshake_server::{Config, P2PServer, ScannerDetectionConfig, ScannerKind};
+
+#[tokio::test]
+async fn test_repeated_garbage_source_is_blocked_silently() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18570".parse().unwrap(),
+        scanner_detection: ScannerDetectionConfig { block_after: 3, window_secs: 60, block_secs: 60, max_block_secs: 600 },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    let scanners = server.scanners();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 发送 HTTP 探测，第三个数据包后被判定为扫描器
+    let scanner = UdpSocket::bind("127.0.0.1:0").await?;
+    for _ in 0..3 {
+        scanner.send_to(b"GET / HTTP/1.1\r\nHost: example\r\n\r\n", server_addr).await?;
+        sleep(Duration::from_millis(50)).await;
+    }
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(metrics.packets_malformed.load(Ordering::Relaxed), 3);
+    assert_eq!(metrics.scanners_detected.load(Ordering::Relaxed), 1);
+    let blocked = scanners.blocked();
+    assert_eq!(blocked.len(), 1);
+    assert_eq!(blocked[0].kind, ScannerKind::Http);
+
+    // 之后的数据包在进入调度前即被丢弃
+    scanner.send_to(b"GET /admin HTTP/1.1\r\n\r\n", server_addr).await?;
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(metrics.packets_from_scanners.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.packets_malformed.load(Ordering::Relaxed), 3);
+    Ok(())
+}
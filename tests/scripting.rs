@@ -0,0 +1,80 @@
+#![cfg(feature = "scripting")]
+
+use anyhow::Result;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo};
+use p2p_handshake_server::{Config, P2PServer, ScriptingConfig};
+
+const SCRIPT: &str = r#"
+function pre_handshake(ctx)
+  if ctx.name == "blocked" then
+    return false, "name not allowed"
+  end
+  if not p2p.ip_in(ctx.ip, "127.0.0.0/8") then
+    return false, "outside allowed range"
+  end
+end
+"#;
+
+async fn handshake(server_addr: std::net::SocketAddr, name: &str) -> Result<Message> {
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new(name.to_string(), client.local_addr()?, "test".to_string());
+    client.send_to(&serde_json::to_vec(&Message::handshake_request(info)?)?, server_addr).await?;
+    let mut buf = vec![0u8; 65536];
+    let (len, _) = timeout(Duration::from_secs(2), client.recv_from(&mut buf)).await??;
+    Ok(serde_json::from_slice(&buf[..len])?)
+}
+
+#[tokio::test]
+async fn test_pre_handshake_script_vetoes_by_name() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let script = std::env::temp_dir().join(format!("p2p-policy-{}.lua", std::process::id()));
+    std::fs::write(&script, SCRIPT)?;
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18610".parse().unwrap(),
+        scripting: ScriptingConfig {
+            enable: true,
+            script_file: Some(script.clone()),
+            ..ScriptingConfig::default()
+        },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let rejected = handshake(server_addr, "blocked").await?;
+    assert_eq!(rejected.message_type, MessageType::Error);
+    assert!(rejected.payload["error"].as_str().unwrap().contains("name not allowed"));
+
+    let accepted = handshake(server_addr, "client").await?;
+    assert_eq!(accepted.message_type, MessageType::HandshakeResponse);
+
+    assert_eq!(metrics.script_vetoes.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.script_errors.load(Ordering::Relaxed), 0);
+    let _ = std::fs::remove_file(&script);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_broken_script_fails_startup() -> Result<()> {
+    let script = std::env::temp_dir().join(format!("p2p-policy-broken-{}.lua", std::process::id()));
+    std::fs::write(&script, "function pre_route(ctx")?;
+    let config = Config {
+        listen_address: "127.0.0.1:18611".parse().unwrap(),
+        scripting: ScriptingConfig { enable: true, script_file: Some(script.clone()), ..ScriptingConfig::default() },
+        ..Config::default()
+    };
+    assert!(P2PServer::new(config).await.is_err());
+    let _ = std::fs::remove_file(&script);
+    Ok(())
+}
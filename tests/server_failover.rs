@@ -0,0 +1,86 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::time::{Duration, sleep};
+
+use p2p_handshake_server::{ClientConfig, Config, P2PClient, P2PServer};
+
+async fn start_server(listen_address: &str) -> Result<(SocketAddr, tokio::sync::broadcast::Sender<()>)> {
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: listen_address.parse().unwrap(),
+        ..Config::default()
+    };
+    let addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let shutdown = server.shutdown_sender();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    Ok((addr, shutdown))
+}
+
+#[tokio::test]
+async fn test_client_fails_over_to_backup_with_same_identity() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (primary, shutdown_primary) = start_server("127.0.0.1:18702").await?;
+    let (backup, _shutdown_backup) = start_server("127.0.0.1:18703").await?;
+    sleep(Duration::from_millis(200)).await;
+
+    let key_file = std::env::temp_dir().join(format!("p2p_failover_{}.key", uuid::Uuid::new_v4()));
+    let config = ClientConfig {
+        server_addr: primary,
+        backup_servers: vec![backup],
+        failover_after_missed: 2,
+        server_keepalive_secs: 1,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        identity_key_file: Some(key_file.clone()),
+        ..ClientConfig::default()
+    };
+    let alice = P2PClient::connect(config.clone()).await?;
+    let bob = P2PClient::connect(ClientConfig { identity_key_file: None, ..config }).await?;
+    let alice_id = alice.node_id();
+    assert_eq!(alice.active_server(), primary);
+    let ticket = alice.session_ticket();
+
+    // 主服务器停止后，连续两次心跳未应答即以同一身份在备用服务器上重新握手
+    shutdown_primary.send(())?;
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+    while (alice.active_server() != backup || bob.active_server() != backup) && tokio::time::Instant::now() < deadline {
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(alice.active_server(), backup);
+    assert_eq!(bob.active_server(), backup);
+    assert_eq!(alice.node_id(), alice_id);
+    assert!(alice.session_ticket().is_some());
+    assert_ne!(alice.session_ticket(), ticket);
+    let health = alice.server_health();
+    assert_eq!(health.iter().map(|server| server.addr).collect::<Vec<_>>(), vec![primary, backup]);
+    assert!(health[0].score < health[1].score);
+    assert!(health[1].active);
+
+    // 切换后经备用服务器的请求照常工作
+    let peers = alice.discover_nearest(10).await?;
+    assert!(peers.iter().any(|peer| peer.id == bob.node_id()));
+    let _ = std::fs::remove_file(&key_file);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connect_falls_through_to_backup() -> Result<()> {
+    let _ = env_logger::try_init();
+    let (backup, _shutdown) = start_server("127.0.0.1:18704").await?;
+    sleep(Duration::from_millis(200)).await;
+    // 主服务器地址上没有服务器，握手失败后改用备用服务器
+    let client = P2PClient::connect(ClientConfig {
+        server_addr: "127.0.0.1:18705".parse().unwrap(),
+        backup_servers: vec![backup],
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        ..ClientConfig::default()
+    })
+    .await?;
+    assert_eq!(client.active_server(), backup);
+    assert!(client.server_health()[0].score < 1.0);
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::{ClientConfig, Config, IdentityConfig, P2PClient, P2PServer};
+
+#[tokio::test]
+async fn test_client_pins_server_fingerprint() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let key_file = std::env::temp_dir().join(format!("p2p-server-identity-{}.key", Uuid::new_v4()));
+    let identity_path = std::env::temp_dir().join(format!("p2p-server-identity-{}.json", Uuid::new_v4()));
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18550".parse().unwrap(),
+        identity: IdentityConfig { key_file: Some(key_file.clone()) },
+        identity_path: Some(identity_path.clone()),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config.clone()).await?;
+    let fingerprint = server.identity().fingerprint();
+    let node_id = server.node_id();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client_config = |pins: Vec<String>| ClientConfig {
+        server_addr,
+        bind_addr: "127.0.0.1:0".parse().unwrap(),
+        network_id: "test".to_string(),
+        server_pins: pins,
+        ..ClientConfig::default()
+    };
+
+    // 指纹匹配时正常握手
+    let client = P2PClient::connect(client_config(vec![fingerprint.clone()])).await?;
+    assert_eq!(client.server_fingerprint(), Some(fingerprint.clone()));
+
+    // 固定了其他指纹时拒绝连接
+    let wrong = format!("sha256:{}", "0".repeat(64));
+    let error = P2PClient::connect(client_config(vec![wrong])).await.err().expect("指纹不符时不应连接成功");
+    assert!(error.to_string().contains("身份校验失败"), "{}", error);
+
+    // 重启后从密钥文件加载同一密钥，从身份文件加载同一节点ID
+    let reloaded = p2p_handshake_server::ServerIdentity::load_or_create(&key_file)?;
+    assert_eq!(reloaded.fingerprint(), fingerprint);
+    let restarted = P2PServer::new(Config { listen_address: "127.0.0.1:18700".parse().unwrap(), ..config }).await?;
+    assert_eq!(restarted.node_id(), node_id);
+    assert_eq!(restarted.identity().fingerprint(), fingerprint);
+    let _ = std::fs::remove_file(&key_file);
+    let _ = std::fs::remove_file(&identity_path);
+    Ok(())
+}
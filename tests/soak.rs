@@ -0,0 +1,161 @@
+//! 长时间运行测试：服务器加数十个模拟客户端，带流失与丢包运行一段时间后检查不变量
+//!
+//! 默认只运行几秒；用环境变量调整规模：
+//! `P2P_SOAK_SECS=600 P2P_SOAK_CLIENTS=64 cargo test --release --test soak -- --nocapture`
+
+use anyhow::Result;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{sleep, Duration, Instant};
+
+use p2p_handshake_server::loadtest::{self, LoadtestConfig};
+use p2p_handshake_server::{AdminConfig, Config, P2PServer};
+
+/// 统计当前仍被占用的堆内存字节数
+struct CountingAllocator;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            LIVE_BYTES.fetch_add(new_size, Ordering::Relaxed);
+            LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// 向管理接口发送 GET 请求，返回响应正文
+async fn admin_get(admin: SocketAddr, path: &str) -> Result<serde_json::Value> {
+    let mut stream = TcpStream::connect(admin).await?;
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (_, body) = response.split_once("\r\n\r\n").expect("响应缺少正文");
+    Ok(serde_json::from_str(body)?)
+}
+
+/// 等待全部模拟客户端被移除（主动断开或心跳超时），返回剩余节点数
+async fn wait_for_no_peers(admin: SocketAddr, wait: Duration) -> Result<u64> {
+    let deadline = Instant::now() + wait;
+    loop {
+        let total = admin_get(admin, "/api/stats").await?["peers"]["total"].as_u64().unwrap_or(u64::MAX);
+        if total == 0 || Instant::now() >= deadline {
+            return Ok(total);
+        }
+        sleep(Duration::from_millis(250)).await;
+    }
+}
+
+fn soak_config(target: SocketAddr, clients: usize, duration_secs: u64) -> LoadtestConfig {
+    LoadtestConfig {
+        target,
+        clients,
+        handshake_rate: 0.0,
+        message_rate: 5.0,
+        payload_size: 256,
+        churn_rate: clients as f64 / 4.0,
+        loss_rate: 0.05,
+        duration_secs,
+        timeout_ms: 500,
+        network_id: "soak".to_string(),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_soak_invariants_hold_under_churn_and_loss() -> Result<()> {
+    let _ = env_logger::try_init();
+    let duration_secs: u64 = env_or("P2P_SOAK_SECS", 6);
+    let clients: usize = env_or("P2P_SOAK_CLIENTS", 32);
+
+    let admin: SocketAddr = "127.0.0.1:18641".parse().unwrap();
+    let config = Config {
+        network_id: "soak".to_string(),
+        listen_address: "127.0.0.1:18640".parse().unwrap(),
+        max_connections: clients * 4,
+        heartbeat_interval: 1,
+        connection_timeout: 2,
+        cleanup_interval: 1,
+        admin: AdminConfig { enable: true, listen_address: admin, ..AdminConfig::default() },
+        ..Config::default()
+    };
+    let target = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    // 预热一轮，让各类缓存与连接表达到稳定容量后再记基线
+    loadtest::run(soak_config(target, clients, 1)).await?;
+    assert_eq!(wait_for_no_peers(admin, Duration::from_secs(10)).await?, 0, "预热后节点未清空");
+    let baseline = LIVE_BYTES.load(Ordering::Relaxed);
+
+    // 运行期间采样内存峰值
+    let running = Arc::new(AtomicBool::new(true));
+    let sampler = {
+        let running = running.clone();
+        tokio::spawn(async move {
+            let mut peak = 0;
+            while running.load(Ordering::Relaxed) {
+                peak = peak.max(LIVE_BYTES.load(Ordering::Relaxed));
+                sleep(Duration::from_millis(100)).await;
+            }
+            peak
+        })
+    };
+    let report = loadtest::run(soak_config(target, clients, duration_secs)).await?;
+    running.store(false, Ordering::Relaxed);
+    let peak = sampler.await?;
+    println!("{}", report);
+
+    // 流量确实经过了服务器，丢包造成的失败在合理范围内
+    assert!(report.handshakes.succeeded >= clients as u64);
+    assert!(report.disconnects > 0);
+    assert!(report.messages.succeeded > 0);
+    assert!(report.messages.error_rate() < 0.5, "消息错误率过高: {:?}", report.messages);
+
+    // 不变量 1：没有泄漏的节点（断开消息丢失的客户端由心跳超时移除）
+    assert_eq!(wait_for_no_peers(admin, Duration::from_secs(10)).await?, 0, "客户端全部离开后仍有节点残留");
+
+    // 不变量 2：路由表与中继会话随节点一起清空，残留的路由都指向已不存在的节点
+    let routes = admin_get(admin, "/api/routes").await?;
+    assert_eq!(routes["routes"].as_array().map(Vec::len), Some(0), "残留路由: {}", routes);
+    let relays = admin_get(admin, "/api/relays").await?;
+    assert_eq!(relays.as_array().map(Vec::len), Some(0), "残留中继会话: {}", relays);
+
+    // 不变量 3：内存不随运行时长增长；流失与丢包结束后回落到基线附近
+    sleep(Duration::from_millis(500)).await;
+    let settled = LIVE_BYTES.load(Ordering::Relaxed);
+    println!("堆内存: 基线 {} 字节，峰值 {} 字节，结束后 {} 字节", baseline, peak, settled);
+    let tolerance = 4 * 1024 * 1024;
+    assert!(settled <= baseline + tolerance, "内存增长过多: 基线 {} 字节，结束后 {} 字节", baseline, settled);
+    Ok(())
+}
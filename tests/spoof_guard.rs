@@ -0,0 +1,68 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::protocol::{HandshakeProtocol, HandshakeResponse, Message, MessageType, NodeInfo};
+use p2p_handshake_server::{Config, P2PServer, SpoofAction, SpoofGuardConfig};
+
+/// 从新的本地端口以同一节点ID握手，返回握手响应
+async fn handshake_from_new_port(server_addr: SocketAddr, info: &NodeInfo, session_ticket: Option<&str>) -> Result<HandshakeResponse> {
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let mut request = Message::handshake_request(info.clone())?;
+    if let Some(ticket) = session_ticket {
+        request.payload["session_ticket"] = ticket.into();
+    }
+    socket.send_to(&serde_json::to_vec(&request)?, server_addr).await?;
+    let mut buffer = vec![0u8; 65536];
+    loop {
+        let (len, _) = timeout(Duration::from_secs(1), socket.recv_from(&mut buffer)).await??;
+        let message: Message = serde_json::from_slice(&buffer[..len])?;
+        if message.message_type == MessageType::HandshakeResponse {
+            return HandshakeProtocol::validate_handshake_response(&message).map_err(anyhow::Error::msg);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_identity_claimed_from_scattered_ports_is_limited() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18707".parse().unwrap(),
+        // 本机测试只有一个来源IP，按端口熵判定：三个不同端口即超过 1 比特
+        spoof_guard: SpoofGuardConfig { max_source_ips: 0, max_port_entropy_bits: 1.0, ..SpoofGuardConfig::default() },
+        ..Config::default()
+    };
+    let server_addr: SocketAddr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    let guard = server.spoof_guard();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let info = NodeInfo::new("victim".to_string(), "127.0.0.1:1".parse().unwrap(), "test".to_string());
+    assert!(handshake_from_new_port(server_addr, &info, None).await?.success);
+    let current = handshake_from_new_port(server_addr, &info, None).await?;
+    assert!(current.success);
+
+    // 第三个端口使端口熵超出阈值，此后不带会话票据的握手都被拒绝
+    let flagged = handshake_from_new_port(server_addr, &info, None).await?;
+    assert!(!flagged.success);
+    assert_eq!(flagged.retry_after_secs, Some(60));
+    assert!(!handshake_from_new_port(server_addr, &info, None).await?.success);
+    assert_eq!(metrics.spoof_identities_flagged.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.spoof_handshakes_rejected.load(Ordering::Relaxed), 2);
+
+    // 持有当前会话票据的真实节点仍可从新地址握手
+    let ticket = current.session_ticket.expect("应收到会话票据");
+    assert!(handshake_from_new_port(server_addr, &info, Some(&ticket)).await?.success);
+
+    assert_eq!(guard.flagged().iter().map(|flagged| flagged.node_id).collect::<Vec<_>>(), vec![info.id]);
+    let actions: Vec<SpoofAction> = guard.audit_log().iter().map(|entry| entry.action).collect();
+    assert_eq!(actions, vec![SpoofAction::Flagged, SpoofAction::Rejected]);
+    Ok(())
+}
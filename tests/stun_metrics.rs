@@ -0,0 +1,48 @@
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration, sleep};
+
+use p2p_handshake_server::{Config, P2PServer, StunServerConfig};
+use p2p_handshake_server::stun_protocol::StunMessage;
+
+#[tokio::test]
+async fn test_stun_requests_reported_in_server_metrics() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18370".parse().unwrap(),
+        stun_server: StunServerConfig { enable: true, port: 18371, ..StunServerConfig::default() },
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    let stun_addr = "127.0.0.1:18371";
+    let mut buffer = [0u8; 1024];
+
+    // 一次成功的绑定请求
+    let request = StunMessage::new_binding_request();
+    socket.send_to(&request.to_bytes(), stun_addr).await?;
+    let (len, _) = timeout(Duration::from_secs(3), socket.recv_from(&mut buffer)).await??;
+    assert_eq!(StunMessage::from_bytes(&buffer[..len])?.transaction_id, request.transaction_id);
+
+    // 不支持的消息类型得到错误响应
+    let unsupported = StunMessage::new(0x0009, [7; 12]);
+    socket.send_to(&unsupported.to_bytes(), stun_addr).await?;
+    let (len, _) = timeout(Duration::from_secs(3), socket.recv_from(&mut buffer)).await??;
+    assert_eq!(StunMessage::from_bytes(&buffer[..len])?.transaction_id, [7; 12]);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.stun_requests, 2);
+    assert_eq!(snapshot.stun_errors, 1);
+    assert_eq!(snapshot.stun_dropped, 0);
+    assert_eq!(snapshot.stun_unique_clients, 1);
+    assert!(snapshot.stun_latency_us_max <= snapshot.stun_latency_us_total);
+    Ok(())
+}
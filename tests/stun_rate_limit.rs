@@ -0,0 +1,75 @@
+use anyhow::Result;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{timeout, Duration, sleep};
+
+use p2p_handshake_server::{StunRateLimitConfig, StunServer, StunServerConfig};
+use p2p_handshake_server::stun_protocol::StunMessage;
+
+async fn start_server(config: StunServerConfig, bind: &str) -> Result<Arc<StunServer>> {
+    let server = Arc::new(StunServer::new(config, bind.parse().unwrap()).await?);
+    let running = server.clone();
+    tokio::spawn(async move {
+        let _ = running.run().await;
+    });
+    Ok(server)
+}
+
+/// 统计在等待时间内收到的响应数
+async fn count_responses(socket: &UdpSocket, wait: Duration) -> usize {
+    let mut buffer = [0u8; 1024];
+    let mut count = 0;
+    while timeout(wait, socket.recv_from(&mut buffer)).await.is_ok() {
+        count += 1;
+    }
+    count
+}
+
+#[tokio::test]
+async fn test_offending_source_silently_dropped() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = StunServerConfig {
+        enable: true,
+        rate_limit: StunRateLimitConfig { requests_per_sec: 1, burst: 3, block_secs: 60 },
+        ..StunServerConfig::default()
+    };
+    let server = start_server(config, "127.0.0.1:18360").await?;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    for _ in 0..10 {
+        socket.send_to(&StunMessage::new_binding_request().to_bytes(), server.local_addr()).await?;
+    }
+    assert_eq!(count_responses(&socket, Duration::from_millis(500)).await, 3);
+
+    // 封禁按来源IP生效，换端口也不会得到响应
+    let other = UdpSocket::bind("127.0.0.1:0").await?;
+    other.send_to(&StunMessage::new_binding_request().to_bytes(), server.local_addr()).await?;
+    assert_eq!(count_responses(&other, Duration::from_millis(300)).await, 0);
+    assert_eq!(server.dropped_requests(), 8);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrency_cap_refuses_extra_connections() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = StunServerConfig {
+        enable: true,
+        tcp: true,
+        max_concurrent_requests: 1,
+        ..StunServerConfig::default()
+    };
+    let server = start_server(config, "127.0.0.1:18361").await?;
+
+    // 第一条连接占用唯一的许可，第二条连接被立即关闭
+    let _first = TcpStream::connect(server.local_addr()).await?;
+    sleep(Duration::from_millis(100)).await;
+    let mut second = TcpStream::connect(server.local_addr()).await?;
+    let mut buffer = [0u8; 1];
+    let read = timeout(Duration::from_secs(2), second.read(&mut buffer)).await?;
+    assert!(matches!(read, Ok(0) | Err(_)), "超出并发上限的连接应被关闭");
+    assert_eq!(server.dropped_requests(), 1);
+    Ok(())
+}
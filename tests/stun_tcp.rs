@@ -0,0 +1,105 @@
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+
+use p2p_handshake_server::{StunServer, StunServerConfig};
+use p2p_handshake_server::stun_protocol::StunMessage;
+
+/// 在流上发送绑定请求，按消息头中的长度读回响应并返回映射地址
+async fn binding_over_stream<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S) -> Result<SocketAddr> {
+    let request = StunMessage::new_binding_request();
+    stream.write_all(&request.to_bytes()).await?;
+    let mut header = [0u8; 20];
+    timeout(Duration::from_secs(3), stream.read_exact(&mut header)).await??;
+    let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+    let mut response = header.to_vec();
+    response.resize(20 + length, 0);
+    stream.read_exact(&mut response[20..]).await?;
+    let response = StunMessage::from_bytes(&response)?;
+    assert_eq!(response.transaction_id, request.transaction_id);
+    Ok(response.extract_mapped_address().expect("响应缺少映射地址"))
+}
+
+async fn start_server(config: StunServerConfig, bind: &str) -> Result<Arc<StunServer>> {
+    let server = Arc::new(StunServer::new(config, bind.parse().unwrap()).await?);
+    let running = server.clone();
+    tokio::spawn(async move {
+        let _ = running.run().await;
+    });
+    Ok(server)
+}
+
+#[tokio::test]
+async fn test_binding_over_tcp_same_port() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = StunServerConfig { enable: true, tcp: true, ..StunServerConfig::default() };
+    let server = start_server(config, "127.0.0.1:18350").await?;
+    assert_eq!(server.tcp_local_addr(), Some(server.local_addr()));
+
+    // 同一连接上可连续发送多个请求
+    let mut stream = TcpStream::connect(server.local_addr()).await?;
+    let local = stream.local_addr()?;
+    assert_eq!(binding_over_stream(&mut stream).await?, local);
+    assert_eq!(binding_over_stream(&mut stream).await?, local);
+
+    // UDP照常工作
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+    socket.send_to(&StunMessage::new_binding_request().to_bytes(), server.local_addr()).await?;
+    let mut buffer = [0u8; 1024];
+    let (len, _) = timeout(Duration::from_secs(3), socket.recv_from(&mut buffer)).await??;
+    assert_eq!(StunMessage::from_bytes(&buffer[..len])?.extract_mapped_address(), Some(socket.local_addr()?));
+    Ok(())
+}
+
+#[cfg(feature = "stuns")]
+#[tokio::test]
+async fn test_binding_over_tls() -> Result<()> {
+    use p2p_handshake_server::StunTlsConfig;
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::TlsConnector;
+
+    let _ = env_logger::try_init();
+
+    // 自签名证书写入临时文件
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+    let dir = std::env::temp_dir().join(format!("stuns_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+    std::fs::write(&cert_path, cert.cert.pem())?;
+    std::fs::write(&key_path, cert.key_pair.serialize_pem())?;
+
+    let config = StunServerConfig {
+        enable: true,
+        tls: StunTlsConfig {
+            enable: true,
+            port: 18352,
+            cert_path: Some(cert_path),
+            key_path: Some(key_path),
+        },
+        ..StunServerConfig::default()
+    };
+    let server = start_server(config, "127.0.0.1:18351").await?;
+    let tls_addr = server.tls_local_addr().expect("TLS未监听");
+
+    let mut roots = RootCertStore::empty();
+    roots.add(cert.cert.der().clone())?;
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let client_config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let tcp = TcpStream::connect(tls_addr).await?;
+    let local = tcp.local_addr()?;
+    let mut stream = TlsConnector::from(Arc::new(client_config))
+        .connect(ServerName::try_from("localhost")?, tcp)
+        .await?;
+    assert_eq!(binding_over_stream(&mut stream).await?, local);
+
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{Duration, sleep};
+use std::net::SocketAddr;
+
+use p2p_handshake_server::{AdminConfig, Config, P2PServer};
+
+/// 向管理接口发送 GET 请求，返回状态码与响应正文
+async fn admin_get(admin: SocketAddr, path: &str) -> Result<(u16, serde_json::Value)> {
+    let mut stream = TcpStream::connect(admin).await?;
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (head, body) = response.split_once("\r\n\r\n").expect("响应缺少正文");
+    let status = head.split_whitespace().nth(1).expect("响应缺少状态码").parse()?;
+    Ok((status, serde_json::from_str(body)?))
+}
+
+#[tokio::test]
+async fn test_health_lists_supervised_tasks() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let admin_addr: SocketAddr = "127.0.0.1:18521".parse().unwrap();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18520".parse().unwrap(),
+        admin: AdminConfig { enable: true, listen_address: admin_addr, ..AdminConfig::default() },
+        ..Config::default()
+    };
+    let mut server = P2PServer::new(config).await?;
+    let supervisor = server.supervisor();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let (status, body) = admin_get(admin_addr, "/api/health").await?;
+    assert_eq!(status, 200);
+    assert_eq!(body["healthy"], true);
+    let names: Vec<&str> = body["tasks"].as_array().unwrap().iter().filter_map(|t| t["name"].as_str()).collect();
+    for task in ["cleanup", "heartbeat", "keepalive", "stats"] {
+        assert!(names.contains(&task), "缺少受监督任务 {}", task);
+    }
+    assert!(supervisor.healthy());
+    assert!(supervisor.tasks().iter().all(|t| t.restarts == 0));
+    Ok(())
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep, Duration};
+
+use p2p_handshake_server::protocol::{Message, MessageType};
+use p2p_handshake_server::{Config, P2PServer, TcpPunch, TcpPunchConfig};
+use common::{handshake, receive_any};
+
+async fn receive_punch(socket: &UdpSocket) -> Result<TcpPunch> {
+    let message = receive_any(socket, &[MessageType::TcpPunch]).await?.expect("未收到 TcpPunch");
+    Ok(serde_json::from_value(message.payload)?)
+}
+
+#[tokio::test]
+async fn test_tcp_punch_offer_and_answer() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18690".parse().unwrap(),
+        tcp_punch: TcpPunchConfig { enable: true, lead_ms: 300, ..TcpPunchConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let alice_id = handshake(&alice, server_addr, "alice").await?.0.id;
+    let bob_id = handshake(&bob, server_addr, "bob").await?.0.id;
+
+    // 端点IP与观测地址不符时拒绝
+    let foreign: SocketAddr = "10.1.2.3:4000".parse().unwrap();
+    alice.send_to(&serde_json::to_vec(&Message::tcp_punch(bob_id, foreign)?)?, server_addr).await?;
+    let error = receive_any(&alice, &[MessageType::Error, MessageType::TcpPunch]).await?.expect("未收到应答");
+    assert_eq!(error.message_type, MessageType::Error);
+
+    // alice 提议，bob 收到 alice 的端点
+    let alice_tcp: SocketAddr = "127.0.0.1:40001".parse().unwrap();
+    let bob_tcp: SocketAddr = "127.0.0.1:40002".parse().unwrap();
+    alice.send_to(&serde_json::to_vec(&Message::tcp_punch(bob_id, alice_tcp)?)?, server_addr).await?;
+    let offer = receive_punch(&bob).await?;
+    assert_eq!((offer.peer_id, offer.peer_tcp_addr, offer.connect_in_ms), (alice_id, Some(alice_tcp), None));
+
+    // bob 应答，双方收到对方端点与连接时间
+    bob.send_to(&serde_json::to_vec(&Message::tcp_punch(alice_id, bob_tcp)?)?, server_addr).await?;
+    let (to_alice, to_bob) = (receive_punch(&alice).await?, receive_punch(&bob).await?);
+    assert_eq!((to_alice.peer_id, to_alice.peer_tcp_addr, to_alice.connect_in_ms), (bob_id, Some(bob_tcp), Some(300)));
+    assert_eq!((to_bob.peer_id, to_bob.peer_tcp_addr, to_bob.connect_in_ms), (alice_id, Some(alice_tcp), Some(300)));
+    assert_eq!(metrics.tcp_punch_coordinations.load(Ordering::Relaxed), 1);
+    Ok(())
+}
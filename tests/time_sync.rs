@@ -0,0 +1,74 @@
+mod common;
+
+use anyhow::Result;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration, sleep};
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use p2p_handshake_server::{Config, P2PServer};
+use p2p_handshake_server::protocol::{Message, MessageType};
+use common::send_message;
+
+/// 模拟快 10 分钟的客户端时钟（毫秒）
+const DRIFT_MS: u64 = 600_000;
+
+fn client_now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64 + DRIFT_MS
+}
+
+async fn receive_message(socket: &UdpSocket) -> Result<Message> {
+    let mut buffer = vec![0u8; 65536];
+    let (len, _addr) = timeout(Duration::from_secs(3), socket.recv_from(&mut buffer)).await??;
+    Ok(serde_json::from_slice(&buffer[..len])?)
+}
+
+/// 按漂移后的客户端时钟发送 Ping，返回服务器的回复类型
+async fn ping_at(socket: &UdpSocket, server: SocketAddr, timestamp_secs: u64) -> Result<MessageType> {
+    let mut ping = Message::ping();
+    ping.timestamp = timestamp_secs;
+    send_message(socket, &ping, server).await?;
+    Ok(receive_message(socket).await?.message_type)
+}
+
+#[tokio::test]
+async fn test_time_sync_tolerates_clock_drift() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18250".parse().unwrap(),
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+
+    // 未同步时，超出允许偏差的时间戳被拒绝
+    assert_eq!(ping_at(&client, server_addr, client_now_ms() / 1000).await?, MessageType::Error);
+
+    // 时间同步：响应回传客户端发送时间和服务器收发时间
+    let t0 = client_now_ms();
+    send_message(&client, &Message::time_sync_request(t0, None), server_addr).await?;
+    let response = receive_message(&client).await?;
+    let t3 = client_now_ms();
+    assert_eq!(response.message_type, MessageType::TimeSync);
+    assert_eq!(response.payload["client_send_ms"].as_u64(), Some(t0));
+    let t1 = response.payload["server_receive_ms"].as_u64().unwrap() as i64;
+    let t2 = response.payload["server_send_ms"].as_u64().unwrap() as i64;
+    assert!(t2 >= t1);
+    let offset = ((t0 as i64 - t1) + (t3 as i64 - t2)) / 2;
+    assert!((offset - DRIFT_MS as i64).abs() < 1000, "偏差估计错误: {}", offset);
+
+    // 同步后，同样漂移的时间戳按偏差校正后视为有效
+    assert_eq!(ping_at(&client, server_addr, client_now_ms() / 1000).await?, MessageType::Pong);
+    // 校正后早于消息时效的消息被视为重放
+    assert_eq!(ping_at(&client, server_addr, client_now_ms() / 1000 - 200).await?, MessageType::Error);
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+use p2p_handshake_server::protocol::{Message, MessageType, NodeInfo, P2PPath};
+use p2p_handshake_server::{AdminConfig, Config, P2PServer, TopologySnapshot};
+use common::{handshake_with, receive_any, send_message};
+
+/// 以指定名称与NAT类型握手，返回节点ID与服务器ID
+async fn handshake_with_nat(socket: &UdpSocket, server: SocketAddr, name: &str, nat_type: &str) -> Result<(Uuid, Uuid)> {
+    let mut info = NodeInfo::new(name.to_string(), socket.local_addr()?, "test".to_string());
+    info.metadata.insert("nat_type".to_string(), nat_type.to_string());
+    let response = handshake_with(socket, server, &info).await?;
+    Ok((info.id, response.node_info.id))
+}
+
+/// 通过管理接口取回拓扑，返回 Content-Type 与正文
+async fn admin_topology(admin: SocketAddr, query: &str) -> Result<(String, String)> {
+    let mut stream = TcpStream::connect(admin).await?;
+    let request = format!("GET /api/topology{} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", query);
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or_default();
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    let content_type = head
+        .lines()
+        .find_map(|line| line.strip_prefix("Content-Type: "))
+        .unwrap_or_default()
+        .to_string();
+    Ok((content_type, body.to_string()))
+}
+
+#[tokio::test]
+async fn test_topology_export_over_udp_and_admin_api() -> Result<()> {
+    let _ = env_logger::try_init();
+    let admin: SocketAddr = "127.0.0.1:18727".parse().unwrap();
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18726".parse().unwrap(),
+        admin: AdminConfig { enable: true, listen_address: admin, ..AdminConfig::default() },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let alice = UdpSocket::bind("127.0.0.1:0").await?;
+    let bob = UdpSocket::bind("127.0.0.1:0").await?;
+    let (alice_id, server_id) = handshake_with_nat(&alice, server_addr, "alice", "FullCone").await?;
+    let (bob_id, _) = handshake_with_nat(&bob, server_addr, "bob", "Symmetric").await?;
+
+    // alice 报告与 bob 打通了公网直连
+    send_message(&alice, &Message::p2p_connect_result(bob_id, P2PPath::Public), server_addr).await?;
+    sleep(Duration::from_millis(100)).await;
+
+    // JSON：两个直连节点带名称、地址与NAT类型，链路含服务器到节点与节点之间的会话
+    send_message(&alice, &Message::new(MessageType::TopologyRequest, serde_json::json!({})), server_addr).await?;
+    let response = receive_any(&alice, &[MessageType::TopologyResponse, MessageType::Error]).await?.expect("未在超时内收到应答");
+    assert_eq!(response.message_type, MessageType::TopologyResponse, "{}", response.payload);
+    assert_eq!(response.payload["format"], "json");
+    let topology: TopologySnapshot = serde_json::from_value(response.payload["topology"].clone())?;
+    assert_eq!(topology.local_node, server_id);
+    let bob_node = topology.nodes.iter().find(|node| node.id == bob_id).expect("拓扑中缺少 bob");
+    assert_eq!(bob_node.name.as_deref(), Some("bob"));
+    assert_eq!(bob_node.addr, Some(bob.local_addr()?));
+    assert_eq!(bob_node.nat_type.as_deref(), Some("Symmetric"));
+    assert!(bob_node.direct);
+    for (from, to) in [(server_id, alice_id), (server_id, bob_id)] {
+        assert!(topology.links.iter().any(|link| link.from == from && link.to == to), "缺少链路 {} -> {}", from, to);
+    }
+    assert!(topology.links.iter().any(|link| [link.from, link.to] == [alice_id, bob_id] || [link.from, link.to] == [bob_id, alice_id]));
+
+    // DOT：同一拓扑渲染为 GraphViz
+    send_message(&bob, &Message::new(MessageType::TopologyRequest, serde_json::json!({ "format": "dot" })), server_addr).await?;
+    let response = receive_any(&bob, &[MessageType::TopologyResponse, MessageType::Error]).await?.expect("未在超时内收到应答");
+    assert_eq!(response.payload["format"], "dot");
+    let dot = response.payload["dot"].as_str().expect("DOT 应以字符串携带");
+    assert!(dot.starts_with("digraph p2p_overlay {"), "{}", dot);
+    assert!(dot.contains("NAT: FullCone") && dot.contains("NAT: Symmetric"), "{}", dot);
+    assert!(dot.contains(&format!("\"{}\" -> \"{}\"", server_id, bob_id)), "{}", dot);
+
+    // 管理接口导出同一份拓扑
+    let (content_type, body) = admin_topology(admin, "?format=dot").await?;
+    assert_eq!(content_type, "text/vnd.graphviz");
+    assert!(body.contains(&format!("\"{}\" -> \"{}\"", server_id, alice_id)), "{}", body);
+    let (_, body) = admin_topology(admin, "").await?;
+    let body: serde_json::Value = serde_json::from_str(&body)?;
+    let topology: TopologySnapshot = serde_json::from_value(body["topology"].clone())?;
+    assert_eq!(topology.nodes.iter().filter(|node| node.direct).count(), 2);
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Duration};
+
+use p2p_handshake_server::protocol::{Message, NodeInfo};
+use p2p_handshake_server::webhooks::{self, DELIVERY_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use p2p_handshake_server::{Config, Event, EventKind, P2PServer, WebhookEndpoint, WebhooksConfig};
+
+/// 收到的一次 POST：小写的请求头与请求体
+type Request = (HashMap<String, String>, Vec<u8>);
+
+/// 读取一个 HTTP 请求（依赖 Content-Length）
+async fn read_request(stream: &mut tokio::net::TcpStream) -> Result<Request> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        anyhow::ensure!(n > 0, "连接提前关闭");
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let headers: HashMap<String, String> = head
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(": "))
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.to_string()))
+        .collect();
+    let length: usize = headers["content-length"].parse()?;
+    while buffer.len() < header_end + length {
+        let n = stream.read(&mut chunk).await?;
+        anyhow::ensure!(n > 0, "连接提前关闭");
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+    Ok((headers, buffer[header_end..header_end + length].to_vec()))
+}
+
+#[tokio::test]
+async fn test_webhook_is_signed_and_retried() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    // 接收方：第一次返回 503，之后返回 200
+    let receiver = TcpListener::bind("127.0.0.1:18601").await?;
+    let (requests_tx, mut requests) = mpsc::unbounded_channel::<Request>();
+    tokio::spawn(async move {
+        let mut served = 0;
+        while let Ok((mut stream, _)) = receiver.accept().await {
+            let Ok(request) = read_request(&mut stream).await else { continue };
+            let status = if served == 0 { "503 Service Unavailable" } else { "200 OK" };
+            served += 1;
+            let _ = stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes()).await;
+            let _ = requests_tx.send(request);
+        }
+    });
+
+    let config = Config {
+        network_id: "test".to_string(),
+        listen_address: "127.0.0.1:18600".parse().unwrap(),
+        webhooks: WebhooksConfig {
+            enable: true,
+            endpoints: vec![WebhookEndpoint {
+                url: "http://127.0.0.1:18601/hooks".to_string(),
+                secret: Some("shared-secret".to_string()),
+                events: vec!["peer.authenticated".to_string()],
+            }],
+            retry_backoff_ms: 50,
+            ..WebhooksConfig::default()
+        },
+        ..Config::default()
+    };
+    let server_addr = config.listen_address;
+    let mut server = P2PServer::new(config).await?;
+    let metrics = server.metrics();
+    tokio::spawn(async move {
+        let _ = server.run().await;
+    });
+    sleep(Duration::from_millis(200)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await?;
+    let info = NodeInfo::new("client".to_string(), client.local_addr()?, "test".to_string());
+    let node_id = info.id;
+    client.send_to(&serde_json::to_vec(&Message::handshake_request(info)?)?, server_addr).await?;
+
+    // 失败后以相同的投递ID重试，每次请求都带有效签名
+    let (first_headers, first_body) = timeout(Duration::from_secs(3), requests.recv()).await?.expect("应收到第一次投递");
+    let (headers, body) = timeout(Duration::from_secs(3), requests.recv()).await?.expect("应收到重试");
+    assert_eq!(first_body, body);
+    assert_eq!(first_headers[&DELIVERY_HEADER.to_ascii_lowercase()], headers[&DELIVERY_HEADER.to_ascii_lowercase()]);
+    let timestamp: u64 = headers[&TIMESTAMP_HEADER.to_ascii_lowercase()].parse()?;
+    assert_eq!(headers[&SIGNATURE_HEADER.to_ascii_lowercase()], webhooks::sign("shared-secret", timestamp, &body));
+
+    let event: Event = serde_json::from_slice(&body)?;
+    assert_eq!(event.kind, EventKind::PeerAuthenticated);
+    assert_eq!(event.node_id, Some(node_id));
+
+    // 未订阅的事件类型（peer.joined）不会推送
+    assert!(timeout(Duration::from_millis(300), requests.recv()).await.is_err());
+    assert_eq!(metrics.webhooks_delivered.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.webhooks_failed.load(Ordering::Relaxed), 0);
+    Ok(())
+}